@@ -0,0 +1,119 @@
+//! End-to-end scenarios on a private server. Each scenario seeds the server with a situation, like
+//! a fresh respawn or a siege, lets the bot play it for a number of ticks and checks what it did.
+//! The bot has to be deployed to the server as `--user` beforehand, built with the
+//! `private-server` feature. Scenarios wipe everything the user has on the server.
+//!
+//! ```text
+//! scenarios [--cli http://localhost:21026] [--user linucc] [--room W1N1] [--remote W2N1]
+//!           [--poll-ms 200] [--stall-secs 60] [--list] [SCENARIO...]
+//! ```
+//!
+//! Without names all scenarios run. A scenario is given up once the game time didn't advance for
+//! `--stall-secs`. The CLI of the server has to be reachable over HTTP, the
+//! rooms need sources and a controller each and `--remote` has to be next to `--room`. Exits with
+//! 1 if a scenario failed and with 2 if it couldn't get that far.
+mod scenario;
+mod scenarios;
+mod server;
+mod world;
+
+use std::{env, process, time::Duration};
+
+use anyhow::{anyhow, bail, Result};
+
+use scenario::Rooms;
+use server::Server;
+use world::World;
+
+struct Args {
+    cli_url: String,
+    user: String,
+    rooms: Rooms,
+    poll: Duration,
+    /// Wall clock time without a tick after which a scenario is given up
+    stall: Duration,
+    list: bool,
+    names: Vec<String>,
+}
+
+fn parse_args() -> Result<Args> {
+    let mut args = Args {
+        cli_url: "http://localhost:21026".into(),
+        user: "linucc".into(),
+        rooms: Rooms {
+            base: "W1N1".into(),
+            remote: "W2N1".into(),
+        },
+        poll: Duration::from_millis(200),
+        stall: Duration::from_secs(60),
+        list: false,
+        names: vec![],
+    };
+    let mut given = env::args().skip(1);
+    while let Some(arg) = given.next() {
+        let mut value = || given.next().ok_or_else(|| anyhow!("{} needs a value", arg));
+        match arg.as_str() {
+            "--cli" => args.cli_url = value()?,
+            "--user" => args.user = value()?,
+            "--room" => args.rooms.base = value()?,
+            "--remote" => args.rooms.remote = value()?,
+            "--poll-ms" => args.poll = Duration::from_millis(value()?.parse()?),
+            "--stall-secs" => args.stall = Duration::from_secs(value()?.parse()?),
+            "--list" => args.list = true,
+            flag if flag.starts_with("--") => bail!("unknown option {}", flag),
+            name => args.names.push(name.to_string()),
+        }
+    }
+    Ok(args)
+}
+
+fn run() -> Result<bool> {
+    let args = parse_args()?;
+    let mut scenarios = scenarios::all();
+    if args.list {
+        for scenario in &scenarios {
+            println!("{:<16} {}", scenario.name, scenario.description);
+        }
+        return Ok(true);
+    }
+    if !args.names.is_empty() {
+        if let Some(name) = args
+            .names
+            .iter()
+            .find(|name| !scenarios.iter().any(|s| s.name == name.as_str()))
+        {
+            bail!("no scenario {}, see --list", name);
+        }
+        scenarios.retain(|s| args.names.iter().any(|name| name == s.name));
+    }
+    let world = World::new(Server::new(&args.cli_url), &args.user)?;
+    let mut passed = true;
+    for scenario in &scenarios {
+        println!(
+            "{}: {} for {} ticks",
+            scenario.name, scenario.description, scenario.ticks
+        );
+        let outcome = scenario::run(&world, &args.rooms, scenario, args.poll, args.stall)?;
+        if outcome.passed() {
+            println!("{}: passed", scenario.name);
+        } else {
+            passed = false;
+            for failure in &outcome.failures {
+                println!("{}: {}", scenario.name, failure);
+            }
+            println!("{}: FAILED", scenario.name);
+        }
+    }
+    Ok(passed)
+}
+
+fn main() {
+    match run() {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(err) => {
+            eprintln!("{:#}", err);
+            process::exit(2);
+        }
+    }
+}
@@ -0,0 +1,125 @@
+//! A scenario seeds the world with a situation, lets the bot handle it for a number of ticks and
+//! checks what it did meanwhile and at the end.
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Result};
+
+use crate::world::World;
+
+/// Rooms the scenarios play in. `remote` is next to `base`.
+#[derive(Debug, Clone)]
+pub struct Rooms {
+    pub base: String,
+    pub remote: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum When {
+    /// Holds in every tick
+    Always,
+    /// Holds in one tick at least
+    Eventually,
+    /// Holds after the last tick
+    AtEnd,
+}
+
+pub struct Expectation {
+    pub what: &'static str,
+    pub when: When,
+    pub check: fn(&World, &Rooms) -> Result<bool>,
+}
+
+pub struct Scenario {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Ticks the bot gets
+    pub ticks: u64,
+    pub seed: fn(&World, &Rooms) -> Result<()>,
+    pub expectations: Vec<Expectation>,
+}
+
+/// Expectations that didn't hold, with the tick they broke in where it is known
+#[derive(Debug, Default)]
+pub struct Outcome {
+    pub failures: Vec<String>,
+}
+
+impl Outcome {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// Seeds the scenario and watches the bot, `poll` is how often the game time is checked. Gives up
+/// once the game time stood still for `stall`, a paused or crashed server never gets to the end.
+pub fn run(
+    world: &World,
+    rooms: &Rooms,
+    scenario: &Scenario,
+    poll: Duration,
+    stall: Duration,
+) -> Result<Outcome> {
+    (scenario.seed)(world, rooms)?;
+    let start = world.time()?;
+    let end = start + scenario.ticks;
+    let mut outcome = Outcome::default();
+    let mut seen = vec![false; scenario.expectations.len()];
+    let mut broken = vec![false; scenario.expectations.len()];
+    let mut last_tick = start;
+    let mut ticked_at = Instant::now();
+    loop {
+        let now = world.time()?;
+        if now == last_tick {
+            if ticked_at.elapsed() >= stall {
+                bail!(
+                    "game time stuck at {} for {:?} in tick {} of {}",
+                    now,
+                    stall,
+                    now - start,
+                    scenario.name
+                );
+            }
+            thread::sleep(poll);
+            continue;
+        }
+        last_tick = now;
+        ticked_at = Instant::now();
+        for (i, expectation) in scenario.expectations.iter().enumerate() {
+            let holds = match expectation.when {
+                When::Always if !broken[i] => (expectation.check)(world, rooms)?,
+                When::Eventually if !seen[i] => (expectation.check)(world, rooms)?,
+                _ => continue,
+            };
+            match expectation.when {
+                When::Always if !holds => {
+                    broken[i] = true;
+                    outcome.failures.push(format!(
+                        "{} broke in tick {}",
+                        expectation.what,
+                        now - start
+                    ));
+                }
+                When::Eventually if holds => seen[i] = true,
+                _ => {}
+            }
+        }
+        if now >= end {
+            break;
+        }
+    }
+    for (i, expectation) in scenario.expectations.iter().enumerate() {
+        match expectation.when {
+            When::Eventually if !seen[i] => outcome
+                .failures
+                .push(format!("{} never happened", expectation.what)),
+            When::AtEnd if !(expectation.check)(world, rooms)? => outcome
+                .failures
+                .push(format!("{} didn't hold at the end", expectation.what)),
+            _ => {}
+        }
+    }
+    Ok(outcome)
+}
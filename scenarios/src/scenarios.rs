@@ -0,0 +1,89 @@
+//! The situations the bot gets put in. Each one starts over with a fresh base in `Rooms::base`,
+//! whatever the bot had on the server before is gone.
+pub mod economy_crash;
+pub mod fresh_respawn;
+pub mod remote_invader;
+pub mod siege;
+
+use anyhow::{anyhow, Result};
+
+use crate::{
+    scenario::Scenario,
+    world::{Owner, World},
+};
+
+/// Extensions a controller of the level allows
+const EXTENSIONS: [u32; 9] = [0, 0, 5, 10, 20, 30, 40, 50, 60];
+
+pub fn all() -> Vec<Scenario> {
+    vec![
+        fresh_respawn::scenario(),
+        siege::scenario(),
+        remote_invader::scenario(),
+        economy_crash::scenario(),
+    ]
+}
+
+/// Takes everything from the bot and gives it the room at `level`, with a spawn, the extensions
+/// and from level 3 on a tower and from level 4 on a storage. The spawn, extensions and tower hold
+/// energy if `full`, the storage holds `stored`. Returns the tiles left to place things on.
+fn seed_base(
+    world: &World,
+    room: &str,
+    level: u32,
+    full: bool,
+    stored: u32,
+) -> Result<Vec<(u32, u32)>> {
+    world.reset_user()?;
+    world.clear_room(room)?;
+    world.claim(room, level)?;
+    let mut tiles = world.open_tiles(room)?.into_iter();
+    let mut next_tile = || {
+        tiles
+            .next()
+            .ok_or_else(|| anyhow!("{} is out of space", room))
+    };
+    let fill = |capacity: u32| if full { capacity } else { 0 };
+
+    let (x, y) = next_tile()?;
+    world.structure(room, "spawn", x, y, fill(300), 300)?;
+    let extension_capacity = match level {
+        7 => 100,
+        8 => 200,
+        _ => 50,
+    };
+    for _ in 0..EXTENSIONS[level as usize] {
+        let (x, y) = next_tile()?;
+        world.structure(
+            room,
+            "extension",
+            x,
+            y,
+            fill(extension_capacity),
+            extension_capacity,
+        )?;
+    }
+    if level >= 3 {
+        let (x, y) = next_tile()?;
+        world.structure(room, "tower", x, y, fill(1_000), 1_000)?;
+    }
+    if level >= 4 {
+        let (x, y) = next_tile()?;
+        world.structure(room, "storage", x, y, stored, 1_000_000)?;
+    }
+    Ok(tiles.collect())
+}
+
+/// Creeps of mine in the room, by the prefix of their race, see `creeps::naming` of the bot
+fn count_mine(world: &World, room: &str, prefix: &str) -> Result<usize> {
+    Ok(world
+        .creeps(room, Owner::Me)?
+        .iter()
+        .filter(|creep| {
+            creep["name"]
+                .as_str()
+                .map(|name| name.starts_with(prefix))
+                .unwrap_or(false)
+        })
+        .count())
+}
@@ -0,0 +1,48 @@
+//! An economy crash: a level 5 base lost all its creeps, its spawn, extensions and storage are
+//! empty. The bot has to notice the panic and get the base going again from what the spawn
+//! regenerates.
+use crate::{
+    scenario::{Expectation, Rooms, Scenario, When},
+    world::{Owner, World},
+};
+
+use super::seed_base;
+
+fn seed(world: &World, rooms: &Rooms) -> anyhow::Result<()> {
+    seed_base(world, &rooms.base, 5, false, 0)?;
+    Ok(())
+}
+
+fn extension_energy(world: &World, rooms: &Rooms) -> anyhow::Result<u64> {
+    Ok(world
+        .objects(&rooms.base, "extension")?
+        .iter()
+        .filter_map(|extension| extension["store"]["energy"].as_u64())
+        .sum())
+}
+
+pub fn scenario() -> Scenario {
+    Scenario {
+        name: "economy-crash",
+        description: "A level 5 base without creeps or energy",
+        ticks: 600,
+        seed,
+        expectations: vec![
+            Expectation {
+                what: "a creep is spawned",
+                when: When::Eventually,
+                check: |world, rooms| Ok(!world.creeps(&rooms.base, Owner::Me)?.is_empty()),
+            },
+            Expectation {
+                what: "three creeps or more live in the base",
+                when: When::AtEnd,
+                check: |world, rooms| Ok(world.creeps(&rooms.base, Owner::Me)?.len() >= 3),
+            },
+            Expectation {
+                what: "the extensions hold energy again",
+                when: When::AtEnd,
+                check: |world, rooms| Ok(extension_energy(world, rooms)? > 0),
+            },
+        ],
+    }
+}
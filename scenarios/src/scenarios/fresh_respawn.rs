@@ -0,0 +1,39 @@
+//! A fresh respawn: a spawn with 300 energy in a level 1 room and nothing else. The bot has to
+//! get its economy going on its own and upgrade the controller.
+use crate::{
+    scenario::{Expectation, Rooms, Scenario, When},
+    world::{Owner, World},
+};
+
+use super::seed_base;
+
+fn seed(world: &World, rooms: &Rooms) -> anyhow::Result<()> {
+    seed_base(world, &rooms.base, 1, true, 0)?;
+    Ok(())
+}
+
+pub fn scenario() -> Scenario {
+    Scenario {
+        name: "fresh-respawn",
+        description: "A lone spawn in a level 1 room",
+        ticks: 1_500,
+        seed,
+        expectations: vec![
+            Expectation {
+                what: "a creep is spawned",
+                when: When::Eventually,
+                check: |world, rooms| Ok(!world.creeps(&rooms.base, Owner::Me)?.is_empty()),
+            },
+            Expectation {
+                what: "four creeps or more live in the base",
+                when: When::AtEnd,
+                check: |world, rooms| Ok(world.creeps(&rooms.base, Owner::Me)?.len() >= 4),
+            },
+            Expectation {
+                what: "the controller reached level 2",
+                when: When::AtEnd,
+                check: |world, rooms| Ok(world.controller_level(&rooms.base)? >= 2),
+            },
+        ],
+    }
+}
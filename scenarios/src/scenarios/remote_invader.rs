@@ -0,0 +1,81 @@
+//! An invader in a remote: the room next to a level 4 base is reserved by the bot and has a
+//! farmer of it in there, then an invader shows up. The base has to send a harasser and clear
+//! the remote.
+use crate::{
+    scenario::{Expectation, Rooms, Scenario, When},
+    world::{Owner, World},
+};
+
+use super::{count_mine, seed_base};
+
+fn seed(world: &World, rooms: &Rooms) -> anyhow::Result<()> {
+    let tiles = seed_base(world, &rooms.base, 4, true, 50_000)?;
+    let mut tiles = tiles.into_iter();
+    for name in &["c-seed1", "c-seed2"] {
+        if let Some((x, y)) = tiles.next() {
+            world.creep(
+                &rooms.base,
+                Owner::Me,
+                name,
+                x,
+                y,
+                &["carry", "carry", "move", "move"],
+            )?;
+        }
+    }
+    world.clear_room(&rooms.remote)?;
+    world.reserve(&rooms.remote, 3_000)?;
+    let mut remote_tiles = world.open_tiles(&rooms.remote)?.into_iter();
+    // Keeps the remote in sight
+    if let Some((x, y)) = remote_tiles.next() {
+        world.creep(
+            &rooms.remote,
+            Owner::Me,
+            "w-seed1",
+            x,
+            y,
+            &["work", "work", "move"],
+        )?;
+    }
+    if let Some((x, y)) = remote_tiles.next() {
+        world.creep(
+            &rooms.remote,
+            Owner::Invader,
+            "invader-0",
+            x,
+            y,
+            &["ranged_attack", "move", "move"],
+        )?;
+    }
+    Ok(())
+}
+
+pub fn scenario() -> Scenario {
+    Scenario {
+        name: "remote-invader",
+        description: "An invader in a reserved remote of a level 4 base",
+        ticks: 600,
+        seed,
+        expectations: vec![
+            Expectation {
+                what: "the spawn stands",
+                when: When::Always,
+                check: |world, rooms| Ok(!world.objects(&rooms.base, "spawn")?.is_empty()),
+            },
+            Expectation {
+                what: "a harasser is out",
+                when: When::Eventually,
+                check: |world, rooms| {
+                    Ok(count_mine(world, &rooms.base, "r-")?
+                        + count_mine(world, &rooms.remote, "r-")?
+                        > 0)
+                },
+            },
+            Expectation {
+                what: "the invader in the remote is gone",
+                when: When::Eventually,
+                check: |world, rooms| Ok(world.creeps(&rooms.remote, Owner::Invader)?.is_empty()),
+            },
+        ],
+    }
+}
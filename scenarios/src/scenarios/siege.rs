@@ -0,0 +1,73 @@
+//! A siege: invaders walk into a level 4 base with full extensions, a tower and a storage. The
+//! bot has to fight them off without losing its spawn.
+use crate::{
+    scenario::{Expectation, Rooms, Scenario, When},
+    world::{Owner, World},
+};
+
+use super::seed_base;
+
+const INVADER_BODY: [&str; 8] = [
+    "tough", "tough", "attack", "attack", "move", "move", "move", "move",
+];
+
+fn seed(world: &World, rooms: &Rooms) -> anyhow::Result<()> {
+    let tiles = seed_base(world, &rooms.base, 4, true, 50_000)?;
+    let mut tiles = tiles.into_iter();
+    for name in &["c-seed1", "c-seed2"] {
+        if let Some((x, y)) = tiles.next() {
+            world.creep(
+                &rooms.base,
+                Owner::Me,
+                name,
+                x,
+                y,
+                &["carry", "carry", "move", "move"],
+            )?;
+        }
+    }
+    for name in &["w-seed1", "w-seed2"] {
+        if let Some((x, y)) = tiles.next() {
+            world.creep(
+                &rooms.base,
+                Owner::Me,
+                name,
+                x,
+                y,
+                &["work", "carry", "move"],
+            )?;
+        }
+    }
+    // The tiles furthest from the center, where invaders come in
+    for (i, (x, y)) in tiles.rev().take(3).enumerate() {
+        let name = format!("invader-{}", i);
+        world.creep(&rooms.base, Owner::Invader, &name, x, y, &INVADER_BODY)?;
+    }
+    Ok(())
+}
+
+pub fn scenario() -> Scenario {
+    Scenario {
+        name: "siege",
+        description: "Three melee invaders in a level 4 base",
+        ticks: 300,
+        seed,
+        expectations: vec![
+            Expectation {
+                what: "the spawn stands",
+                when: When::Always,
+                check: |world, rooms| Ok(!world.objects(&rooms.base, "spawn")?.is_empty()),
+            },
+            Expectation {
+                what: "the invaders are gone",
+                when: When::Eventually,
+                check: |world, rooms| Ok(world.creeps(&rooms.base, Owner::Invader)?.is_empty()),
+            },
+            Expectation {
+                what: "creeps of mine live in the base",
+                when: When::AtEnd,
+                check: |world, rooms| Ok(!world.creeps(&rooms.base, Owner::Me)?.is_empty()),
+            },
+        ],
+    }
+}
@@ -0,0 +1,65 @@
+//! The CLI of a private server, the one `screeps cli` talks to. Commands are JavaScript evaluated
+//! in the server, with `storage` and `system` in scope.
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::Value;
+
+pub struct Server {
+    cli_url: String,
+}
+
+impl Server {
+    /// `cli_url` like `http://localhost:21026`
+    pub fn new(cli_url: &str) -> Server {
+        Server {
+            cli_url: format!("{}/cli", cli_url.trim_end_matches('/')),
+        }
+    }
+
+    /// Runs the command and returns what it printed
+    pub fn cli(&self, command: &str) -> Result<String> {
+        let response = ureq::post(&self.cli_url)
+            .timeout_connect(5_000)
+            .timeout_read(30_000)
+            .send_string(command);
+        if let Some(err) = response.synthetic_error() {
+            bail!("CLI at {} unreachable: {}", self.cli_url, err);
+        }
+        if response.error() {
+            bail!("CLI answered {} to {}", response.status(), command);
+        }
+        response.into_string().context("reading CLI output")
+    }
+
+    /// Evaluates the expression, a promise or a plain value, and returns its result
+    pub fn query(&self, expression: &str) -> Result<Value> {
+        let command = format!(
+            "Promise.resolve({}).then(r => print(JSON.stringify(r === undefined ? null : r)))",
+            expression
+        );
+        let output = self.cli(&command)?;
+        output
+            .lines()
+            .find_map(|line| serde_json::from_str(line.trim()).ok())
+            .ok_or_else(|| anyhow!("no result for {}, got {:?}", expression, output))
+    }
+
+    /// Runs the command for its effect, errors thrown in the server come back as `Err`
+    pub fn exec(&self, command: &str) -> Result<()> {
+        let output = self.cli(&format!(
+            "Promise.resolve({}).then(() => print('ok'), err => print('error: ' + err))",
+            command
+        ))?;
+        match output.lines().find(|line| line.starts_with("error: ")) {
+            Some(err) => bail!("{} failed with {}", command, err),
+            None => Ok(()),
+        }
+    }
+
+    pub fn time(&self) -> Result<u64> {
+        let time = self.query("storage.env.get(storage.env.keys.GAMETIME)")?;
+        // Some storages keep it as string
+        time.as_u64()
+            .or_else(|| time.as_str().and_then(|time| time.parse().ok()))
+            .ok_or_else(|| anyhow!("game time unreadable: {}", time))
+    }
+}
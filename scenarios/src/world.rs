@@ -0,0 +1,280 @@
+//! The game world of the server, seen and changed through its database. Objects are written like
+//! the engine writes them, the next tick picks them up.
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+
+use crate::server::Server;
+
+/// User id of the invaders on every server
+const INVADER: &str = "2";
+/// Where the engine keeps the objects of all rooms
+const OBJECTS: &str = "storage.db['rooms.objects']";
+/// Ticks a creep lives
+const CREEP_LIFE_TIME: u64 = 1_500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Owner {
+    Me,
+    Invader,
+}
+
+pub struct World {
+    server: Server,
+    user_id: String,
+}
+
+/// Hits of a fresh structure, `None` for structures the scenarios don't place
+fn structure_hits(structure_type: &str) -> Option<u32> {
+    Some(match structure_type {
+        "spawn" => 5_000,
+        "extension" => 1_000,
+        "storage" => 10_000,
+        "tower" => 3_000,
+        "container" => 250_000,
+        _ => return None,
+    })
+}
+
+impl World {
+    /// The world as the user the bot plays as
+    pub fn new(server: Server, username: &str) -> Result<World> {
+        let user = server.query(&format!(
+            "storage.db.users.findOne({{ username: {} }})",
+            json!(username)
+        ))?;
+        let user_id = user["_id"]
+            .as_str()
+            .ok_or_else(|| anyhow!("no user {} on the server", username))?
+            .to_string();
+        Ok(World { server, user_id })
+    }
+
+    pub fn time(&self) -> Result<u64> {
+        self.server.time()
+    }
+
+    fn owner_id(&self, owner: Owner) -> &str {
+        match owner {
+            Owner::Me => &self.user_id,
+            Owner::Invader => INVADER,
+        }
+    }
+
+    /// Takes everything from the user and forgets its memory, like a respawn
+    pub fn reset_user(&self) -> Result<()> {
+        let user = json!(self.user_id);
+        self.server.exec(&format!(
+            "{db}.update({{ type: 'controller', user: {user} }}, \
+             {{ $set: {{ user: null, level: 0, progress: 0, downgradeTime: null, \
+             safeMode: null }} }})",
+            db = OBJECTS,
+            user = user
+        ))?;
+        self.server.exec(&format!(
+            "{db}.removeWhere({{ user: {user} }})",
+            db = OBJECTS,
+            user = user
+        ))?;
+        self.server.exec(&format!(
+            "storage.env.set(storage.env.keys.MEMORY + {user}, '{{}}')",
+            user = user
+        ))?;
+        self.server.exec(&format!(
+            "storage.db.users.update({{ _id: {user} }}, \
+             {{ $set: {{ active: 10000, cpu: 100, cpuAvailable: 10000 }} }})",
+            user = user
+        ))
+    }
+
+    /// Removes everything from the room but the sources, minerals and the controller, which goes
+    /// back to neutral
+    pub fn clear_room(&self, room: &str) -> Result<()> {
+        let room = json!(room);
+        self.server.exec(&format!(
+            "{db}.removeWhere({{ room: {room}, \
+             type: {{ $nin: ['source', 'mineral', 'controller'] }} }})",
+            db = OBJECTS,
+            room = room
+        ))?;
+        self.server.exec(&format!(
+            "{db}.update({{ room: {room}, type: 'controller' }}, \
+             {{ $set: {{ user: null, level: 0, progress: 0, downgradeTime: null, \
+             safeMode: null, reservation: null }} }})",
+            db = OBJECTS,
+            room = room
+        ))?;
+        self.server.exec(&format!(
+            "storage.db.rooms.update({{ _id: {room} }}, {{ $set: {{ active: true }} }})",
+            room = room
+        ))
+    }
+
+    /// Makes the controller of the room mine at `level`
+    pub fn claim(&self, room: &str, level: u32) -> Result<()> {
+        let time = self.time()?;
+        self.server.exec(&format!(
+            "{db}.update({{ room: {room}, type: 'controller' }}, \
+             {{ $set: {{ user: {user}, level: {level}, progress: 0, \
+             downgradeTime: {downgrade}, reservation: null }} }})",
+            db = OBJECTS,
+            room = json!(room),
+            user = json!(self.user_id),
+            level = level,
+            downgrade = time + 20_000
+        ))
+    }
+
+    /// Reserves the controller of the room for me
+    pub fn reserve(&self, room: &str, ticks: u64) -> Result<()> {
+        let time = self.time()?;
+        self.server.exec(&format!(
+            "{db}.update({{ room: {room}, type: 'controller' }}, \
+             {{ $set: {{ reservation: {{ user: {user}, endTime: {end} }} }} }})",
+            db = OBJECTS,
+            room = json!(room),
+            user = json!(self.user_id),
+            end = time + ticks
+        ))
+    }
+
+    /// Places a structure of mine holding `energy` of `capacity`
+    pub fn structure(
+        &self,
+        room: &str,
+        structure_type: &str,
+        x: u32,
+        y: u32,
+        energy: u32,
+        capacity: u32,
+    ) -> Result<()> {
+        let hits = structure_hits(structure_type)
+            .ok_or_else(|| anyhow!("can't place a {}", structure_type))?;
+        let mut object = json!({
+            "type": structure_type,
+            "room": room,
+            "x": x,
+            "y": y,
+            "user": self.user_id,
+            "hits": hits,
+            "hitsMax": hits,
+            "notifyWhenAttacked": true,
+        });
+        if capacity > 0 {
+            object["store"] = json!({ "energy": energy });
+            object["storeCapacityResource"] = json!({ "energy": capacity });
+        }
+        if structure_type == "spawn" {
+            object["name"] = json!(format!("Spawn{}{}", x, y));
+            object["spawning"] = Value::Null;
+        }
+        self.server.exec(&format!("{}.insert({})", OBJECTS, object))
+    }
+
+    /// Places a fresh creep with the body, parts like `"work"`
+    pub fn creep(
+        &self,
+        room: &str,
+        owner: Owner,
+        name: &str,
+        x: u32,
+        y: u32,
+        body: &[&str],
+    ) -> Result<()> {
+        let time = self.time()?;
+        let carry = body.iter().filter(|part| **part == "carry").count() as u32;
+        let object = json!({
+            "type": "creep",
+            "room": room,
+            "x": x,
+            "y": y,
+            "name": name,
+            "user": self.owner_id(owner),
+            "body": body
+                .iter()
+                .map(|part| json!({ "type": part, "hits": 100 }))
+                .collect::<Vec<Value>>(),
+            "hits": body.len() * 100,
+            "hitsMax": body.len() * 100,
+            "ageTime": time + CREEP_LIFE_TIME,
+            "spawning": false,
+            "fatigue": 0,
+            "store": {},
+            "storeCapacity": carry * 50,
+            "notifyWhenAttacked": true,
+        });
+        self.server.exec(&format!("{}.insert({})", OBJECTS, object))
+    }
+
+    /// Objects of the type in the room, the way the database has them
+    pub fn objects(&self, room: &str, object_type: &str) -> Result<Vec<Value>> {
+        let objects = self.server.query(&format!(
+            "{}.find({{ room: {}, type: {} }})",
+            OBJECTS,
+            json!(room),
+            json!(object_type)
+        ))?;
+        Ok(objects.as_array().cloned().unwrap_or_default())
+    }
+
+    /// Creeps of the owner in the room
+    pub fn creeps(&self, room: &str, owner: Owner) -> Result<Vec<Value>> {
+        let owner_id = self.owner_id(owner);
+        Ok(self
+            .objects(room, "creep")?
+            .into_iter()
+            .filter(|creep| creep["user"].as_str() == Some(owner_id))
+            .collect())
+    }
+
+    /// Tiles of the room to place things on, closest to the center first. Walls, the room edge
+    /// and the tiles around sources, minerals and the controller are left out, and only every
+    /// other tile is taken so there is always a way between the things placed.
+    pub fn open_tiles(&self, room: &str) -> Result<Vec<(u32, u32)>> {
+        let terrain = self.server.query(&format!(
+            "storage.db['rooms.terrain'].findOne({{ room: {} }})",
+            json!(room)
+        ))?;
+        let terrain: Vec<u8> = terrain["terrain"]
+            .as_str()
+            .ok_or_else(|| anyhow!("no terrain for {}", room))?
+            .bytes()
+            .collect();
+        let mut blocked: Vec<(u32, u32)> = vec![];
+        for object_type in &["source", "mineral", "controller"] {
+            for object in self.objects(room, object_type)? {
+                if let (Some(x), Some(y)) = (object["x"].as_u64(), object["y"].as_u64()) {
+                    blocked.push((x as u32, y as u32));
+                }
+            }
+        }
+        let is_wall = |x: u32, y: u32| {
+            terrain
+                .get((y * 50 + x) as usize)
+                .map(|tile| (tile - b'0') & 1 == 1)
+                .unwrap_or(true)
+        };
+        let near_blocked = |x: u32, y: u32| {
+            blocked.iter().any(|(bx, by)| {
+                (*bx as i32 - x as i32).abs() <= 2 && (*by as i32 - y as i32).abs() <= 2
+            })
+        };
+        let mut tiles: Vec<(u32, u32)> = (3..47)
+            .flat_map(|y| (3..47).map(move |x| (x, y)))
+            .filter(|(x, y)| (x + y) % 2 == 0 && !is_wall(*x, *y) && !near_blocked(*x, *y))
+            .collect();
+        tiles.sort_by_key(|(x, y)| {
+            let (dx, dy) = (*x as i32 - 25, *y as i32 - 25);
+            dx * dx + dy * dy
+        });
+        Ok(tiles)
+    }
+
+    /// Level of the controller of the room, 0 if it isn't mine
+    pub fn controller_level(&self, room: &str) -> Result<u64> {
+        let controller = self.objects(room, "controller")?.into_iter().next();
+        Ok(controller
+            .filter(|controller| controller["user"].as_str() == Some(self.user_id.as_str()))
+            .and_then(|controller| controller["level"].as_u64())
+            .unwrap_or(0))
+    }
+}
@@ -0,0 +1,253 @@
+//! Mails for what needs a human: sieges, controllers about to downgrade, lost spawns, empty
+//! storages, stuck rooms, the VM resetting over and over and bugs in the tick. Every alert has a
+//! cool-down per room, so a long siege doesn't flood the inbox. `muteAlerts(ticks)` in the console
+//! silences all of them, `unmuteAlerts()` ends that early.
+//!
+//! Everything lives in `Memory`, a reset loop would forget the cool-downs otherwise.
+use std::{collections::HashMap, fmt};
+
+use log::{info, warn};
+use screeps::{
+    find, game, HasStore, OwnedStructureProperties, Part, ResourceType, Room, RoomName,
+    SharedCreepProperties,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config,
+    constants::MEM_ALERTS,
+    rooms::{defense::bunker, labels, stagger},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Alert {
+    Siege,
+    ControllerDowngrade,
+    SpawnDestroyed,
+    StorageEmpty,
+    ResetLoop,
+    Halt,
+    StageFailed,
+    Deadlock,
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Alert::Siege => f.write_str("siege"),
+            Alert::ControllerDowngrade => f.write_str("controller_downgrade"),
+            Alert::SpawnDestroyed => f.write_str("spawn_destroyed"),
+            Alert::StorageEmpty => f.write_str("storage_empty"),
+            Alert::ResetLoop => f.write_str("reset_loop"),
+            Alert::Halt => f.write_str("halt"),
+            Alert::StageFailed => f.write_str("stage_failed"),
+            Alert::Deadlock => f.write_str("deadlock"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct AlertMemory {
+    /// Tick an alert went out last, by `<alert>.<room>`
+    #[serde(default)]
+    sent: HashMap<String, u32>,
+    /// Set by `muteAlerts` in the console
+    #[serde(default)]
+    muted_until: u32,
+    /// Ticks of the last global resets
+    #[serde(default)]
+    resets: Vec<u32>,
+    /// Spawns seen in the room on the last check
+    #[serde(default)]
+    spawns: HashMap<String, u32>,
+}
+
+js_serializable!(AlertMemory);
+js_deserializable!(AlertMemory);
+
+fn load() -> AlertMemory {
+    match screeps::memory::root().get::<AlertMemory>(MEM_ALERTS) {
+        Ok(Some(memory)) => memory,
+        Ok(None) => Default::default(),
+        Err(err) => {
+            warn!("Could not load alerts: {}", err);
+            Default::default()
+        }
+    }
+}
+
+fn save(memory: &AlertMemory) {
+    screeps::memory::root().set(MEM_ALERTS, memory.clone());
+}
+
+/// Mails `message` unless alerts are muted or the same alert for the room went out recently
+fn send(memory: &mut AlertMemory, alert: Alert, room: Option<RoomName>, message: &str) {
+    let time = game::time();
+    if memory.muted_until > time {
+        return;
+    }
+    let key = match room {
+        Some(room) => format!("{}.{}", alert, room),
+        None => alert.to_string(),
+    };
+    if let Some(sent_at) = memory.sent.get(&key) {
+        if sent_at + config::ALERT_COOLDOWN_TICKS > time {
+            return;
+        }
+    }
+    memory.sent.insert(key, time);
+    let message = format!("[{}] {}", time, labels::label_message(message));
+    info!("Alert: {}", message);
+    js! {
+        Game.notify(@{message});
+    }
+}
+
+fn is_siege(room: &Room) -> bool {
+    room.find(find::HOSTILE_CREEPS).iter().any(|hostile| {
+        hostile.owner_name() != "Invader"
+            && (hostile.get_active_bodyparts(Part::Attack) > 0
+                || hostile.get_active_bodyparts(Part::RangedAttack) > 0
+                || hostile.get_active_bodyparts(Part::Work) > 0)
+    })
+}
+
+fn check_room(memory: &mut AlertMemory, room: &Room) {
+    let room_name = room.name();
+    if is_siege(room) {
+        let walls = match bunker::last_check(room_name) {
+            Some(check) if !check.is_closed() => ", the walls are open",
+            _ => "",
+        };
+        send(
+            memory,
+            Alert::Siege,
+            Some(room_name),
+            &format!("{} is under siege{}", room_name, walls),
+        );
+    }
+    if let Some(controller) = room.controller() {
+        let ticks_to_downgrade = controller.ticks_to_downgrade();
+        if ticks_to_downgrade < config::ALERT_DOWNGRADE_TICKS {
+            send(
+                memory,
+                Alert::ControllerDowngrade,
+                Some(room_name),
+                &format!(
+                    "Controller of {} downgrades in {} ticks",
+                    room_name, ticks_to_downgrade
+                ),
+            );
+        }
+    }
+    let spawns = room.find(find::MY_SPAWNS).len() as u32;
+    let known_spawns = memory
+        .spawns
+        .insert(room_name.to_string(), spawns)
+        .unwrap_or(spawns);
+    if spawns < known_spawns {
+        send(
+            memory,
+            Alert::SpawnDestroyed,
+            Some(room_name),
+            &format!(
+                "{} lost a spawn, {} of {} left",
+                room_name, spawns, known_spawns
+            ),
+        );
+    }
+    if let Some(storage) = room.storage() {
+        let energy = storage.store_used_capacity(Some(ResourceType::Energy));
+        if energy < config::ALERT_STORAGE_EMPTY_ENERGY {
+            send(
+                memory,
+                Alert::StorageEmpty,
+                Some(room_name),
+                &format!("Storage of {} is empty, {} energy left", room_name, energy),
+            );
+        }
+    }
+}
+
+/// Looks for trouble in all my rooms, every `ALERT_CHECK_INTERVAL` ticks, staggered by room
+pub fn check_rooms() {
+    let due: Vec<Room> = game::rooms::values()
+        .into_iter()
+        .filter(|room| {
+            room.controller()
+                .map(|controller| controller.my())
+                .unwrap_or(false)
+        })
+        .filter(|room| stagger::is_due(room.name(), config::ALERT_CHECK_INTERVAL))
+        .collect();
+    if due.is_empty() {
+        return;
+    }
+    let mut memory = load();
+    for room in due {
+        check_room(&mut memory, &room);
+    }
+    save(&memory);
+}
+
+/// Counts a global reset, call it once when the VM starts
+pub fn track_reset() {
+    let time = game::time();
+    let mut memory = load();
+    memory
+        .resets
+        .retain(|reset| reset + config::ALERT_RESET_LOOP_TICKS > time);
+    memory.resets.push(time);
+    if memory.resets.len() >= config::ALERT_RESET_LOOP_RESETS {
+        let message = format!(
+            "{} global resets in the last {} ticks",
+            memory.resets.len(),
+            config::ALERT_RESET_LOOP_TICKS
+        );
+        send(&mut memory, Alert::ResetLoop, None, &message);
+    }
+    save(&memory);
+}
+
+/// Tells the CPU gets halted after `failures` failed initializations in a row
+pub fn halting(failures: i32) {
+    let mut memory = load();
+    let message = format!(
+        "Initialization failed {} times in a row, halting the CPU",
+        failures
+    );
+    send(&mut memory, Alert::Halt, None, &message);
+    save(&memory);
+}
+
+/// Tells a stage of the tick failed on what looks like a bug
+pub fn stage_failed(stage: &str, err: &str) {
+    let mut memory = load();
+    let message = format!("Stage {} failed: {}", stage, err);
+    send(&mut memory, Alert::StageFailed, None, &message);
+    save(&memory);
+}
+
+/// Tells the room got stuck and what the watchdog did about it, see `rooms::watchdog`
+pub fn deadlock(room_name: RoomName, message: &str) {
+    let mut memory = load();
+    send(&mut memory, Alert::Deadlock, Some(room_name), message);
+    save(&memory);
+}
+
+/// Makes `muteAlerts(ticks)` and `unmuteAlerts()` available in the console
+pub fn register_console_mute() {
+    js! {
+        var key = @{MEM_ALERTS};
+        global.muteAlerts = function(ticks) {
+            Memory[key] = Memory[key] || {};
+            Memory[key].muted_until = Game.time + (ticks || 1000);
+            return "Alerts muted until " + Memory[key].muted_until;
+        };
+        global.unmuteAlerts = function() {
+            Memory[key] = Memory[key] || {};
+            Memory[key].muted_until = 0;
+            return "Alerts unmuted";
+        };
+    }
+}
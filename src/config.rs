@@ -0,0 +1,563 @@
+//! Knobs of the bot which are meant to be tweaked, as opposed to the identifiers in `constants`.
+use std::ops::Range;
+
+use screeps::{ResourceType, StructureType};
+
+use crate::{
+    creeps::{races::OokRaceKind, speech::Verbosity},
+    pipeline::Stage,
+    rooms::defense::{threats::RetaliationPolicy, towers::TowerRepairRule},
+    rooms::withdraw_permissions::{Resources, Role, WithdrawRule},
+};
+
+/// How the average CPU usage follows the CPU usage of the last tick, in (0, 1]
+///
+/// Higher values react faster to spikes, lower values smooth them out more.
+pub const CPU_AVG_WEIGHT: f64 = 0.1;
+
+/// Pixels are skipped if the average CPU usage is above this share of the CPU limit, as the
+/// bucket is going to be needed soon
+pub const PIXEL_MAX_CPU_SHARE: f64 = 0.9;
+
+/// Bucket a pixel costs, generating one with less fails anyway
+pub const PIXEL_BUCKET_COST: i32 = 10_000;
+
+pub const PIXEL_POLICY: PixelPolicy = PixelPolicy::BucketThreshold {
+    bucket: PIXEL_BUCKET_COST,
+};
+
+#[derive(Debug, Clone, Copy)]
+pub enum PixelPolicy {
+    /// Never generate pixels, keep the bucket for bad times
+    Off,
+    /// Generate a pixel as soon as the bucket reaches `bucket`
+    BucketThreshold { bucket: i32 },
+    /// Generate a pixel every `every_ticks`, if the bucket allows it
+    Schedule { every_ticks: u32 },
+}
+
+impl PixelPolicy {
+    /// If a pixel should be generated this tick
+    ///
+    /// `avg_cpu_used` is the smoothed CPU usage of the last ticks, see `CPU_AVG_WEIGHT`.
+    pub fn should_generate(
+        &self,
+        time: u32,
+        bucket: i32,
+        avg_cpu_used: f64,
+        cpu_limit: u32,
+    ) -> bool {
+        if bucket < PIXEL_BUCKET_COST {
+            return false;
+        }
+        if avg_cpu_used >= cpu_limit as f64 * PIXEL_MAX_CPU_SHARE {
+            return false;
+        }
+        match self {
+            PixelPolicy::Off => false,
+            PixelPolicy::BucketThreshold { bucket: threshold } => bucket >= *threshold,
+            PixelPolicy::Schedule { every_ticks } => *every_ticks > 0 && time % every_ticks == 0,
+        }
+    }
+}
+
+/// Credits that are never spent, no matter how good a deal looks
+pub const CREDIT_RESERVE_FLOOR: f64 = 50_000.;
+
+/// Spend budgets start over after this many ticks
+pub const CREDIT_BUDGET_PERIOD: u32 = 10_000;
+
+/// Credits each spend category may use per `CREDIT_BUDGET_PERIOD`
+pub const CREDIT_BUDGET_ENERGY_BUYING: f64 = 20_000.;
+pub const CREDIT_BUDGET_MINERAL_BUYING: f64 = 10_000.;
+pub const CREDIT_BUDGET_PIXEL_CPU: f64 = 0.;
+
+/// Sell pixels and buy CPU unlocks on the market, see `trade::intershard`
+pub const INTERSHARD_TRADING_ENABLED: bool = false;
+
+/// Pixels that are never sold
+pub const PIXEL_KEEP: u32 = 0;
+
+/// Pixels are only sold to buy orders paying at least this much
+pub const PIXEL_SELL_MIN_PRICE: f64 = 20_000.;
+
+/// CPU unlocks are only bought from sell orders asking at most this much, the credits come
+/// from `CREDIT_BUDGET_PIXEL_CPU`
+pub const CPU_UNLOCK_BUY_MAX_PRICE: f64 = 1_000_000.;
+
+/// What the terminal of a room keeps in stock for trading and sends, everything above goes to
+/// the storage. Resources not listed are kept in the storage only.
+pub const TERMINAL_STOCK_LEVELS: [(ResourceType, u32); 8] = [
+    (ResourceType::Energy, 20_000),
+    (ResourceType::Hydrogen, 3_000),
+    (ResourceType::Oxygen, 3_000),
+    (ResourceType::Utrium, 3_000),
+    (ResourceType::Lemergium, 3_000),
+    (ResourceType::Keanium, 3_000),
+    (ResourceType::Zynthium, 3_000),
+    (ResourceType::Catalyst, 3_000),
+];
+
+pub fn terminal_stock_level(resource_type: ResourceType) -> u32 {
+    TERMINAL_STOCK_LEVELS
+        .iter()
+        .find(|(ty, _)| *ty == resource_type)
+        .map(|(_, level)| *level)
+        .unwrap_or(0)
+}
+
+/// How far towers repair structures of a type, types without a rule are repaired up to the usual
+/// targets from any range. Everything a tower doesn't repair is left to the builders. See
+/// `rooms::defense::towers`.
+pub const TOWER_REPAIR_RULES: &[TowerRepairRule] = &[
+    // Roads and containers are cheap for creeps passing by, towers only keep them from decaying
+    TowerRepairRule {
+        structure: StructureType::Road,
+        max_hits: Some(1_000),
+        max_range: Some(10),
+    },
+    TowerRepairRule {
+        structure: StructureType::Container,
+        max_hits: Some(50_000),
+        max_range: Some(10),
+    },
+    // Walls are raised by builders, towers only help out close by
+    TowerRepairRule {
+        structure: StructureType::Wall,
+        max_hits: Some(100_000),
+        max_range: Some(10),
+    },
+    // Ramparts at the far end of the bunker get a quarter of the power only
+    TowerRepairRule {
+        structure: StructureType::Rampart,
+        max_hits: None,
+        max_range: Some(15),
+    },
+];
+
+/// Walls and ramparts in reach of campers with less hits are about to break, carriers fill the
+/// towers before anything else then. See `rooms::defense::haul_priority`.
+pub const BREACH_WARNING_HITS: u32 = 50_000;
+
+/// Who may take what from which structure, the first matching rule decides. See
+/// `rooms::withdraw_permissions`.
+pub const WITHDRAW_PERMISSIONS: &[WithdrawRule] = &[
+    // The compounds in the labs are for boosting, only the haulers move them
+    WithdrawRule {
+        room: None,
+        role: Some(Role::Hauler),
+        resources: Resources::NotEnergy,
+        structure: StructureType::Lab,
+        allow: true,
+    },
+    WithdrawRule {
+        room: None,
+        role: None,
+        resources: Resources::NotEnergy,
+        structure: StructureType::Lab,
+        allow: false,
+    },
+    // The terminal keeps its energy for sends and trading
+    WithdrawRule {
+        room: None,
+        role: Some(Role::Upgrader),
+        resources: Resources::Any,
+        structure: StructureType::Terminal,
+        allow: false,
+    },
+];
+
+/// Resources the labs of every base are stocked with, the first lab by position gets the first
+/// one and so on. Labs without one only get energy. See `rooms::fill_orders`. The lab room of the
+/// boosts stocks its labs for the reactions instead.
+pub const LAB_RESOURCES: &[ResourceType] = &[];
+
+/// Compounds to keep for boosting and how much of each, the labs of the base with the most of them
+/// make them. See `economy::boosts`.
+pub const BOOST_TARGETS: &[(ResourceType, u32)] = &[
+    (ResourceType::CatalyzedGhodiumAcid, 3_000),
+    (ResourceType::CatalyzedUtriumAcid, 3_000),
+];
+
+/// Ticks between two boost plans
+pub const BOOST_PLAN_INTERVAL: u32 = 1_000;
+
+/// Smaller sends and buys of base minerals for the boosts aren't worth it
+pub const BOOST_MIN_TRANSFER: u32 = 1_000;
+
+/// The nuker only gets energy while the storage holds at least this much
+pub const NUKER_FILL_MIN_STORED_ENERGY: u32 = 100_000;
+
+/// Share of the storage capacity above which a room is overflowing, see `economy`
+pub const STORAGE_HIGH_WATER_MARK: f64 = 0.8;
+
+/// Workers spawned on top of the target while the storage is overflowing, they upgrade and build
+pub const STORAGE_OVERFLOW_EXTRA_WORKERS: u8 = 2;
+
+/// Hits walls and ramparts are repaired up to
+pub const WALL_TARGET_HITS: f64 = 10_000_000.;
+
+/// Hits walls and ramparts are repaired up to while the storage is overflowing
+pub const WALL_TARGET_HITS_OVERFLOW: f64 = 50_000_000.;
+
+/// Ticks between two plans of the GCL push, see `economy::empire`
+pub const EMPIRE_PLAN_INTERVAL: u32 = 500;
+
+/// Energy an overflowing base sends the push room at once
+pub const EMPIRE_PUSH_SEND_AMOUNT: u32 = 20_000;
+
+/// Workers the push room spawns on top of its target, they upgrade the energy sent to it
+pub const EMPIRE_PUSH_EXTRA_WORKERS: u8 = 3;
+
+/// The push room only spawns its extra workers with this much energy stored
+pub const EMPIRE_PUSH_MIN_STORED_ENERGY: u32 = 50_000;
+
+/// Defaults of a funnel set up in the console, see `economy::funnel`. Donors stop sending with
+/// less energy in their storage than the floor.
+pub const FUNNEL_DONOR_FLOOR: u32 = 100_000;
+pub const FUNNEL_SEND_AMOUNT: u32 = 25_000;
+pub const FUNNEL_SEND_INTERVAL: u32 = 200;
+pub const FUNNEL_EXTRA_WORKERS: u8 = 4;
+
+/// Energy sold at once while the storage is overflowing
+pub const STORAGE_OVERFLOW_SELL_AMOUNT: u32 = 10_000;
+
+/// Surplus energy is sold down to this share of its moving average price
+pub const STORAGE_OVERFLOW_MIN_PRICE_SHARE: f64 = 0.8;
+
+/// Text controllers of owned, reserved and scouted rooms get signed with, `None` leaves them
+/// alone. Changing it makes the creeps re-sign every controller they come by.
+pub const CONTROLLER_SIGN: Option<&str> = Some("Territory of the blob. Ook.");
+
+/// Room states created per tick after a reset, creating them all at once can blow the CPU limit
+pub const ROOM_STATE_INITS_PER_TICK: usize = 2;
+
+/// Extensions a room being set up needs before it becomes a base, all of RCL 2
+pub const SETUP_FINISHED_MIN_EXTENSIONS: usize = 5;
+
+/// Bases lend builders to bases at most this many rooms away, see `rooms::labor`
+pub const LABOR_SHARING_RADIUS: i32 = 2;
+
+/// Builders a lending base keeps at home anyway, they upgrade
+pub const LABOR_SHARING_KEPT_BUILDERS: usize = 1;
+
+/// Builders are lent and brought home every this many ticks
+pub const LABOR_SHARING_INTERVAL: u32 = 100;
+
+/// A base spawns a worker more per this many work-ticks of construction backlog, see
+/// `rooms::labor`
+pub const BACKLOG_WORK_TICKS_PER_WORKER: u32 = 3_000;
+
+/// Most workers a base spawns for its backlog on top of its target
+pub const BACKLOG_MAX_EXTRA_WORKERS: u8 = 4;
+
+/// Energy per tick a backlog worker is expected to use, the energy surplus of the base has to
+/// cover it
+pub const BACKLOG_WORKER_ENERGY_PER_TICK: f64 = 5.;
+
+/// Stages of a tick in the order they run, leave one out to skip it
+pub const TICK_STAGES: &[Stage] = &[
+    Stage::Intel,
+    Stage::Legacy,
+    Stage::RoomStates,
+    Stage::Requests,
+    Stage::Spawning,
+    Stage::Creeps,
+    Stage::Movement,
+    Stage::Persistence,
+    Stage::Trade,
+    // Last but stats, everything before may have submitted intents
+    Stage::Intents,
+    // Gets what CPU the tick has left
+    Stage::Deferred,
+    Stage::Stats,
+];
+
+/// Shards the bot runs on, each one reads what the others publish in `InterShardMemory`
+pub const SHARDS: &[&str] = &["shard2", "shard3"];
+
+/// Rooms colonies want claimed by a claimer from another shard, see `shards`
+pub const INTERSHARD_CLAIMS: &[InterShardClaim] = &[];
+
+#[derive(Debug, Clone, Copy)]
+pub struct InterShardClaim {
+    /// Shard of the colony, the room to claim is on it
+    pub shard: &'static str,
+    pub target_room: &'static str,
+    /// Shard spawning the claimer
+    pub from_shard: &'static str,
+    /// Room on `from_shard` with the portal to `shard`
+    pub portal_room: &'static str,
+}
+
+/// Names rooms go by in logs, overlays and the console, as (room, label). `roomLabel("W12N16")`
+/// and `roomByLabel("Main")` look them up in the console.
+pub const ROOM_LABELS: &[(&str, &str)] = &[("W12N16", "Main")];
+
+/// Construction sites get built in this order of their structure types, types not listed come
+/// last. See `rooms::BuildPolicy`.
+pub const BUILD_PRIORITY: &[StructureType] = &[
+    StructureType::Spawn,
+    StructureType::Tower,
+    StructureType::Extension,
+    StructureType::Container,
+    StructureType::Storage,
+    StructureType::Road,
+    StructureType::Wall,
+];
+
+/// Sites already under construction are finished before new ones get started, whatever they are
+pub const BUILD_FINISH_STARTED: bool = true;
+
+/// Structures of these types get a construction site where they were destroyed in my bases right
+/// away, see `rooms::rebuild`. Ramparts are placed again by the planner.
+pub const REBUILT_STRUCTURES: &[StructureType] = &[
+    StructureType::Spawn,
+    StructureType::Tower,
+    StructureType::Extension,
+    StructureType::Storage,
+    StructureType::Terminal,
+    StructureType::Link,
+    StructureType::Container,
+    StructureType::Lab,
+    StructureType::Road,
+];
+
+/// Rebuild sites skip `BUILD_FINISH_STARTED` for this many ticks after they were placed
+pub const REBUILD_RUSH_TICKS: u32 = 1_500;
+
+/// Workers and carriers with WORK parts repair roads and containers they pass by below this share
+/// of their hits
+pub const REPAIR_IN_PASSING_HITS_SHARE: f64 = 0.8;
+
+/// The same alert for the same room goes out by mail at most once in this many ticks.
+/// `muteAlerts(ticks)` in the console silences all of them for a while.
+pub const ALERT_COOLDOWN_TICKS: u32 = 3_000;
+
+/// Rooms are checked for alerts every this many ticks
+pub const ALERT_CHECK_INTERVAL: u32 = 10;
+
+/// Controllers this close to downgrading raise an alert
+pub const ALERT_DOWNGRADE_TICKS: u32 = 5_000;
+
+/// Controllers this close to downgrading get the energy of every creep around, see
+/// `creeps::emergency`
+pub const EMERGENCY_UPGRADE_TICKS: u32 = 500;
+
+/// Creeps further away from the downgrading controller than this keep doing their task
+pub const EMERGENCY_UPGRADE_DISTANCE: u32 = 10;
+
+/// Creeps whose body costs less than this share of the body their base would spawn for the job
+/// now get replaced early, see `creeps::refresh`
+pub const BODY_REFRESH_MAX_BODY_SHARE: f64 = 0.5;
+
+/// Creeps about to die anyway are left alone
+pub const BODY_REFRESH_MIN_TICKS_TO_LIVE: u32 = 300;
+
+/// Bases with a storage only replace creeps early with at least this much energy stored, bases
+/// without one with their extensions full
+pub const BODY_REFRESH_MIN_STORED_ENERGY: u32 = 20_000;
+
+/// Citizens are checked for outgrown bodies every this many ticks
+pub const BODY_REFRESH_CHECK_INTERVAL: u32 = 50;
+
+/// Creeps next to an idle spawn get renewed below this many ticks to live, see `creeps::renewal`
+pub const RENEW_BELOW_TICKS: u32 = 500;
+
+/// Cheaper creeps are left to die, spawning them again costs about as much
+pub const RENEW_MIN_BODY_ENERGY: u32 = 1_000;
+
+/// A carrier running dry while filling extensions tops up at a storage or a source container this
+/// close instead of giving the run up, see `tasks::spawn_supplies_run`
+pub const SPAWN_SUPPLY_REFILL_RANGE: u32 = 10;
+
+/// Providers with less energy left after the reservations aren't worth the detour for a refill
+pub const SPAWN_SUPPLY_REFILL_MIN_ENERGY: u32 = 100;
+
+/// Silences every creep, see `creeps::speech`
+pub const QUIET_MODE: bool = false;
+
+/// Creeps are quiet while the bucket is below this
+pub const SPEECH_MIN_BUCKET: i32 = 5_000;
+
+/// How much creeps of a race say, like `(OokRaceKind::Carrier, Verbosity::Quiet)`. Races not
+/// listed go with `DEFAULT_SPEECH_VERBOSITY`.
+pub const SPEECH_VERBOSITY: &[(OokRaceKind, Verbosity)] = &[];
+pub const DEFAULT_SPEECH_VERBOSITY: Verbosity = Verbosity::Chatter;
+
+/// Creeps using this many times the CPU of their race and job on average are outliers, see
+/// `creeps::cpu_usage`
+pub const CREEP_CPU_OUTLIER_FACTOR: f64 = 3.;
+
+/// Creeps using less CPU per tick are never outliers, however cheap their kind is
+pub const CREEP_CPU_OUTLIER_MIN: f64 = 0.5;
+
+/// Outliers for this many ticks in a row get reset
+pub const CREEP_CPU_OUTLIER_TICKS: u32 = 20;
+
+/// Storages with less energy count as empty
+pub const ALERT_STORAGE_EMPTY_ENERGY: u32 = 1_000;
+
+/// That many global resets within `ALERT_RESET_LOOP_TICKS` are a reset loop
+pub const ALERT_RESET_LOOP_RESETS: usize = 5;
+pub const ALERT_RESET_LOOP_TICKS: u32 = 100;
+
+/// Initializations failing in a row before the CPU gets halted instead of only resetting the VM
+pub const INIT_FAILURES_BEFORE_HALT: i32 = 10;
+
+/// Cleared rooms with less loot aren't worth a salvager, in the units of
+/// `rooms::salvage::resource_value`
+pub const SALVAGE_MIN_VALUE: f64 = 5_000.;
+
+/// Rooms further away from every base are left to decay
+pub const SALVAGE_MAX_ROOM_DISTANCE: i32 = 3;
+
+/// Rooms in sight are checked for loot every this many ticks
+pub const SALVAGE_CHECK_INTERVAL: u32 = 50;
+
+/// Sight requested of a room is given up after this many ticks without anyone asking again, see
+/// `rooms::visibility`
+pub const VISIBILITY_REQUEST_TICKS: u32 = 200;
+
+/// Rooms further away from every base and out of observer range stay out of sight
+pub const SCOUT_MAX_ROOM_DISTANCE: i32 = 5;
+
+/// Rooms are checked for being stuck every this many ticks, see `rooms::watchdog`
+pub const WATCHDOG_CHECK_INTERVAL: u32 = 100;
+
+/// A room with full extensions that didn't spawn for this many ticks is stuck
+pub const WATCHDOG_SPAWN_STALL_TICKS: u32 = 1_500;
+
+/// A room below RCL 8 whose controller made no progress for this many ticks is stuck
+pub const WATCHDOG_CONTROLLER_STALL_TICKS: u32 = 2_000;
+
+/// Share of the citizens of a room out of tasks that counts as an idle check
+pub const WATCHDOG_IDLE_SHARE: f64 = 0.5;
+
+/// Rooms with fewer citizens are never idle, a lone hauler waits often enough
+pub const WATCHDOG_IDLE_MIN_CITIZENS: usize = 3;
+
+/// Idle checks in a row after which the room is stuck
+pub const WATCHDOG_IDLE_CHECKS: u32 = 5;
+
+/// The structures of a room are scanned again after this many ticks at the latest, see
+/// `rooms::structures`
+pub const STRUCTURE_CACHE_TICKS: u32 = 100;
+
+/// Below this bucket no room visuals are drawn, see `visuals`
+pub const VISUALS_MIN_BUCKET: i32 = 2_000;
+
+/// Estimated bytes of room visuals per room and tick, more get dropped
+pub const VISUALS_ROOM_BYTES: usize = 20_000;
+
+/// CPU drawing the room visuals may take per tick
+pub const VISUALS_CPU_PER_TICK: f64 = 1.;
+
+/// Ticks the `profiler` sums up the CPU of its scopes before reporting them
+pub const PROFILER_REPORT_TICKS: u32 = 100;
+
+/// Segment the `profiler` writes its reports to, as folded stacks, outside of `DATA_SEGMENTS`.
+/// Without one they go to the console.
+pub const PROFILER_SEGMENT: Option<u32> = None;
+
+/// Deferred work like base planning only runs while the bucket holds more than this
+pub const DEFERRED_MIN_BUCKET: i32 = 5_000;
+
+/// CPU deferred work may use in one tick
+pub const DEFERRED_CPU_PER_TICK: f64 = 20.;
+
+/// Rooms kept reserved by the closest base, as remotes
+pub const RESERVED_REMOTES: &[&str] = &[];
+
+/// Reservations are renewed before they drop below this many ticks
+pub const RESERVATION_FLOOR_TICKS: u32 = 1_000;
+
+/// Haul routes of remotes whose sources get harvested at least this share of their capacity are
+/// paved, see `harvest_stats`
+pub const REMOTE_ROADS_MIN_UTILIZATION: f64 = 0.6;
+
+/// Remotes whose sources get harvested at least this share of their capacity get haulers, below
+/// the containers fill too slowly to pay for them, see `remotes::hauling_due`
+pub const REMOTE_HAULING_MIN_UTILIZATION: f64 = 0.2;
+
+/// Road sites placed per remote and deferred step, a whole route at once eats the construction
+/// site limit
+pub const REMOTE_ROAD_SITES_PER_STEP: u32 = 5;
+
+/// Construction sites a player may have at once, see `rooms::sites`
+pub const SITE_LIMIT: u32 = 100;
+
+/// Queued construction sites placed per room and tick at most
+pub const SITES_PER_ROOM_PER_TICK: u32 = 5;
+
+/// A site that couldn't be placed is tried again after this many ticks, doubled with each failure
+pub const SITE_RETRY_TICKS: u32 = 20;
+
+/// Failed placements of a site before it is dropped from the queue
+pub const SITE_MAX_ATTEMPTS: u32 = 5;
+
+/// A request whose spawn failed is tried again after this many ticks, twice as long after each
+/// failure, see `rooms::room_state::spawn_failures`
+pub const SPAWN_RETRY_TICKS: u32 = 10;
+
+/// Failed spawns for a request in one room before another base spawns it
+pub const SPAWN_FAILURES_BEFORE_REROUTE: u32 = 5;
+
+/// Ticks the spawn ledger sums up the energy spent on spawning before starting over, see
+/// `economy::ledger`
+pub const SPAWN_LEDGER_PERIOD: u32 = 1_500;
+
+/// Bodies from this much energy on only spawn if the energy forecast of the room covers them, see
+/// `economy::ledger::forecast`. Defense and rescues spawn regardless.
+pub const SPAWN_FORECAST_MIN_ENERGY: u32 = 1_500;
+
+/// Storage energy below this isn't counted in the forecast
+pub const SPAWN_FORECAST_STORAGE_FLOOR: u32 = 20_000;
+
+/// Share of its energy a body is shrunk to before its spawn is deferred instead
+pub const SPAWN_FORECAST_MAX_SHRINK: f64 = 0.5;
+
+/// Threat score per point of damage a player does to my creeps and structures, see
+/// `rooms::defense::threats`
+pub const THREAT_DAMAGE_SCORE: f64 = 0.01;
+
+/// Threat score per creep of mine a player kills
+pub const THREAT_KILL_SCORE: f64 = 10.;
+
+/// Threat scores halve in this many ticks
+pub const THREAT_HALF_LIFE_TICKS: u32 = 10_000;
+
+/// How players are answered by their threat score. Below `defend_at` their creeps are ignored,
+/// set `harass_at` to `None` to never go after their remotes.
+pub const RETALIATION_POLICY: RetaliationPolicy = RetaliationPolicy {
+    defend_at: 5.,
+    harass_at: Some(100.),
+};
+
+/// Remotes of hostile players are harassed up to this many rooms from a base
+pub const RETALIATION_MAX_ROOM_DISTANCE: i32 = 2;
+
+/// Ticks between two steps of the memory garbage collection, each step collects one kind of
+/// garbage, see `gc`
+pub const GC_STEP_TICKS: u32 = 25;
+
+/// Handled requests are forgotten after this many ticks
+pub const GC_REQUEST_HISTORY_TICKS: u32 = 1_500;
+
+/// Rooms unseen for this many ticks drop out of the intel, even with portals in them
+pub const GC_INTEL_TICKS: u32 = 200_000;
+
+/// How the names of new creeps look, see `creeps::naming`
+pub const CREEP_NAMING: CreepNaming = CreepNaming::Words;
+
+#[derive(Debug, Clone, Copy)]
+pub enum CreepNaming {
+    /// The race prefix and a base36 counter, like `c-1z`
+    Counter,
+    /// The race prefix and a word, with a base36 suffix once all words are used, like `c-owl2`
+    Words,
+}
+
+/// Memory segments bulky data like the terrain analyses is kept in, one key per segment, see
+/// `segments`. The ones outside are left for manual use.
+pub const DATA_SEGMENTS: Range<u32> = 10..90;
@@ -26,13 +26,27 @@ pub static MEM_FLAG_SECONDARY_COLOR: &str = "flag_sec_col";
 pub static MEM_OOK_ROOMS_DATA: &str = "ook_rooms_data";
 pub static MEM_OOK_ROOMS: &str = "ook_rooms";
 pub static MEM_REQUEST_ID: &str = "req_id";
+pub static MEM_TERMINAL_SENDS: &str = "terminal_sends";
+pub static MEM_CREDIT_BUDGET: &str = "credit_budget";
+pub static MEM_ALERTS: &str = "alerts";
+pub static MEM_INIT_FAILURES: &str = "init_failures";
+pub static MEM_SEGMENTS: &str = "segments";
+pub static MEM_CREEP_NAMES: &str = "creep_names";
+pub static MEM_DRILL: &str = "drill";
+pub static MEM_PORTALS: &str = "portals";
+pub static MEM_SPAWN_LEDGER: &str = "spawn_ledger";
+pub static MEM_FUNNEL: &str = "funnel";
+pub static MEM_OOK_FLAGS: &str = "ook_flags";
+pub static MEM_THREATS: &str = "threats";
 
+/// Creeps of the legacy code and the opener, they have no race
+pub static CREEP_ID_PREFIX: &str = "👾";
 pub static CREEP_ID_BITCH: &str = "👾-i";
 pub static CREEP_ID_BUILDER: &str = "👾-b";
 pub static CREEP_ID_FARMER: &str = "👾-f";
 pub static CREEP_ID_RUNNER: &str = "👾-r";
 pub static CREEP_ID_UNKNOWN: &str = "👾-?";
+pub static CREEP_ID_OPENER: &str = "👾-o";
 
 pub const ROOM_ID_MAIN: &str = "W12N16";
 
-pub const TERMINAL_TRADE_BUFFER: u32 = 20_000;
@@ -1,20 +1,32 @@
 use core::fmt;
-use std::{cmp::{self, Reverse}, collections::HashMap, convert::TryFrom, error::Error};
+use std::{cmp::{self, Reverse}, collections::HashMap, convert::TryFrom};
 
 use log::{debug, info, warn};
-use screeps::{Attackable, ConstructionSite, FindOptions, HasId, HasPosition, HasStore, MoveToOptions, ObjectId, Part, Path, Position, RawObjectId, Resource, ResourceType, ReturnCode, Room, RoomName, RoomObjectProperties, Ruin, SharedCreepProperties, Source, Structure, StructureContainer, StructureExtension, StructureSpawn, StructureStorage, StructureTerminal, StructureTower, creep, find, game::get_object_typed, look, memory::MemoryReference};
+use screeps::{Attackable, ConstructionSite, HasId, HasPosition, HasStore, ObjectId, Part, Position, RawObjectId, Resource, ResourceType, ReturnCode, Room, RoomName, RoomObjectProperties, Ruin, SharedCreepProperties, Source, Structure, StructureContainer, StructureExtension, StructureSpawn, StructureStorage, StructureTerminal, StructureTower, StructureType, Tombstone, creep, find, game::get_object_typed, look, memory::MemoryReference};
 
-use crate::{constants::{CREEP_ID_BITCH, CREEP_ID_BUILDER, CREEP_ID_FARMER, CREEP_ID_RUNNER, CREEP_ID_UNKNOWN, MEM_ASSIGNED_SOURCE, MEM_FARM_POSITION_X, MEM_FARM_POSITION_Y, MEM_HARVESTING, MEM_KIND, MEM_POST, MEM_RESOURCE_PROVIDER_ID, TERMINAL_TRADE_BUFFER}, rooms::{FarmPosition, MyRoom, PathOptionUnwrapper, RoomMaintenance, resource_provider::{ResourceData, ResourceProvider, RoomObjectData, TakeResourceResult}, room_ext::RoomExt, room_state::{RoomState, SetupBaseStateVisibility}}, state::{BWContext, UniqId}, utils::HexStr};
+use crate::{constants::{CREEP_ID_BITCH, CREEP_ID_BUILDER, CREEP_ID_FARMER, CREEP_ID_RUNNER, CREEP_ID_UNKNOWN, MEM_ASSIGNED_SOURCE, MEM_FARM_POSITION_X, MEM_FARM_POSITION_Y, MEM_HARVESTING, MEM_KIND, MEM_POST, MEM_RESOURCE_PROVIDER_ID}, rooms::{FarmPosition, MyRoom, distance::{rank_two_stage, Distance, PATHED_CANDIDATES}, PathOptionUnwrapper, RoomMaintenance, repairs, resource_provider::{dropped_amount_after, ProviderReservations, ResourceData, ResourceProvider, RoomObjectData, TakeResourceResult}, room_ext::RoomExt, room_state::{RoomState, SetupBaseStateVisibility}, structures}, economy, state::{get_object_cached, BWContext, BWState, UniqId}, trade, utils::HexStr};
 
-use self::{jobs::OokCreepJob, races::{OokRace, OokRaceKind}};
+use self::{jobs::OokCreepJob, races::{OokRace, OokRaceKind}, speech::Verbosity, utils::SpawnableTimer};
 
 use anyhow::anyhow;
 
+pub mod actions;
+pub mod cpu_usage;
+pub mod deaths;
+pub mod emergency;
+pub mod fitness;
 pub mod harvesting;
 pub mod races;
 pub mod tasks;
 pub mod utils;
 pub mod jobs;
+pub mod movement;
+pub mod naming;
+pub mod recovery;
+pub mod refresh;
+pub mod renewal;
+pub mod retirement;
+pub mod speech;
 
 #[derive(thiserror::Error, Debug)]
 pub enum CreepError {
@@ -50,8 +62,8 @@ pub enum CreepKind {
 trait HandlesResource {
     fn calc_next_fetch<'a>(
         &mut self,
-        rooms_state: &'a HashMap<RoomName, RoomState>,
-    ) -> Result<Option<(&'a ResourceProvider, ResourceType, u32)>, Box<dyn Error>>;
+        state: &'a BWState,
+    ) -> anyhow::Result<Option<(&'a ResourceProvider, ResourceType, u32)>>;
     // fn select_target_provider(states: RoomState) -> Result<(ResourceProvider, ResourceType, u32), Box<dyn Error>>;
 }
 
@@ -91,7 +103,7 @@ impl CreepKind {
 }
 
 impl TryFrom<screeps::objects::Creep> for CreepKind {
-    type Error = Box<dyn std::error::Error>;
+    type Error = anyhow::Error;
 
     fn try_from(creep: screeps::objects::Creep) -> Result<Self, Self::Error> {
         let mem = creep.memory();
@@ -99,17 +111,17 @@ impl TryFrom<screeps::objects::Creep> for CreepKind {
             let my_room = MyRoom::by_room_name(
                 creep
                     .room()
-                    .ok_or(Box::new(CreepError::RoomNotFound()))?
+                    .ok_or(CreepError::RoomNotFound())?
                     .name(),
             )
-            .ok_or(Box::new(CreepError::RoomNotFound()))?;
+            .ok_or(CreepError::RoomNotFound())?;
             Ok(match kind_str.as_str() {
                 k if k == CREEP_ID_BITCH => CreepKind::Bitch(CreepBitch {
                     my_room,
                     id: creep.id(),
                     post: mem
                         .string(MEM_POST)?
-                        .ok_or(Box::new(CreepError::MissingPost(format!("{}", creep.id()))))?,
+                        .ok_or(CreepError::MissingPost(format!("{}", creep.id())))?,
                     creep,
                 }),
                 k if k == CREEP_ID_BUILDER => CreepKind::Builder(CreepBuilder {
@@ -117,33 +129,33 @@ impl TryFrom<screeps::objects::Creep> for CreepKind {
                     id: creep.id(),
                     post: mem
                         .string(MEM_POST)?
-                        .ok_or(Box::new(CreepError::MissingPost(format!("{}", creep.id()))))?,
+                        .ok_or(CreepError::MissingPost(format!("{}", creep.id())))?,
                     creep,
                     harvesting: mem.bool(MEM_HARVESTING),
                     target: None,
                 }),
                 k if k == CREEP_ID_FARMER => {
                     let assigned_source = ObjectId::from(RawObjectId::from_hex_string(
-                        &mem.string(MEM_ASSIGNED_SOURCE)?.ok_or(Box::new(
-                            CreepError::MissingAssignedSource(format!("{}", creep.id())),
-                        ))?,
+                        &mem.string(MEM_ASSIGNED_SOURCE)?.ok_or_else(|| {
+                            CreepError::MissingAssignedSource(format!("{}", creep.id()))
+                        })?,
                     )?);
-                    let room = creep.room().ok_or(Box::new(CreepError::RoomNotFound()))?;
+                    let room = creep.room().ok_or(CreepError::RoomNotFound())?;
                     CreepKind::Farmer(CreepFarmer {
                         my_room,
                         id: creep.id(),
                         post: mem
                             .string(MEM_POST)?
-                            .ok_or(Box::new(CreepError::MissingPost(format!("{}", creep.id()))))?,
+                            .ok_or(CreepError::MissingPost(format!("{}", creep.id())))?,
                         creep: creep.clone(),
                         assigned_source,
                         farm_position: FarmPosition::from_basic(
-                            mem.i32(MEM_FARM_POSITION_X)?.ok_or(Box::new(
-                                CreepError::MissingFarmPosition(format!("{}", creep.id())),
-                            ))? as u32,
-                            mem.i32(MEM_FARM_POSITION_Y)?.ok_or(Box::new(
-                                CreepError::MissingFarmPosition(format!("{}", creep.id())),
-                            ))? as u32,
+                            mem.i32(MEM_FARM_POSITION_X)?.ok_or_else(|| {
+                                CreepError::MissingFarmPosition(format!("{}", creep.id()))
+                            })? as u32,
+                            mem.i32(MEM_FARM_POSITION_Y)?.ok_or_else(|| {
+                                CreepError::MissingFarmPosition(format!("{}", creep.id()))
+                            })? as u32,
                             assigned_source,
                             room,
                         ),
@@ -155,7 +167,7 @@ impl TryFrom<screeps::objects::Creep> for CreepKind {
                         id: creep.id(),
                         post: mem
                             .string(MEM_POST)?
-                            .ok_or(Box::new(CreepError::MissingPost(format!("{}", creep.id()))))?,
+                            .ok_or(CreepError::MissingPost(format!("{}", creep.id())))?,
                         creep: creep.clone(),
                         state: None,
                     })
@@ -168,7 +180,7 @@ impl TryFrom<screeps::objects::Creep> for CreepKind {
                 }),
             })
         } else {
-            Err(Box::new(CreepError::CreepNotConvertible()))
+            Err(CreepError::CreepNotConvertible().into())
         }
     }
 }
@@ -207,22 +219,30 @@ impl CreepBitch {
     //     CREEP_ID_BITCH.into()
     // }
     //
-    pub fn run(&mut self) -> Result<(), Box<dyn Error>> {
+    pub fn run(&mut self) -> anyhow::Result<()> {
         if self.creep.memory().bool(MEM_HARVESTING) {
             if self.creep.store_free_capacity(Some(ResourceType::Energy)) == 0 {
                 self.creep.memory().set(MEM_HARVESTING, false);
                 self.creep.memory().del(MEM_RESOURCE_PROVIDER_ID);
             }
         } else {
-            self.creep.say("ᕕ( ᐛ )ᕗ", true);
+            speech::say(&self.creep, "ᕕ( ᐛ )ᕗ", Verbosity::Chatter);
             if self.creep.store_used_capacity(None) == 0 {
-                let context = BWContext::get();
-                let state = context.state()?;
-                if let Some(fetch_target) = self.calc_next_fetch(&state.room_states)? {
+                let mut context = BWContext::get();
+                let state = context.mut_state()?;
+                let fetch_target = self
+                    .calc_next_fetch(state)?
+                    .map(|(provider, resource_type, amount)| {
+                        (provider.to_owned(), resource_type, amount)
+                    });
+                if let Some((provider, resource_type, amount)) = fetch_target {
                     self.creep.memory().set(MEM_HARVESTING, true);
                     self.creep
                         .memory()
-                        .set(MEM_RESOURCE_PROVIDER_ID, fetch_target.0.ident());
+                        .set(MEM_RESOURCE_PROVIDER_ID, provider.ident());
+                    state
+                        .provider_reservations
+                        .reserve(&provider, resource_type, amount);
                 }
             }
         }
@@ -234,7 +254,7 @@ impl CreepBitch {
                 .creep
                 .memory()
                 .string(MEM_RESOURCE_PROVIDER_ID)?
-                .ok_or(Box::new(CreepError::ResourceProviderIdNotStored))?;
+                .ok_or(CreepError::ResourceProviderIdNotStored)?;
             let resource_provider = state
                 .room_states
                 .get(&self.my_room.room()?.name())
@@ -285,7 +305,7 @@ impl CreepBitch {
                         }
                     };
                 } else {
-                    self.creep.move_to_with_options(&resource_provider.pos()?, MoveToOptions::new().ignore_creeps(true));
+                    movement::move_creep_to(&self.creep, &resource_provider.pos()?, 1);
                 }
             } else {
                 warn!("Room provider missing, resetting Bitch {}", self.creep.id());
@@ -301,7 +321,7 @@ impl CreepBitch {
             {
                 let r = self.creep.upgrade_controller(&c);
                 if r == ReturnCode::NotInRange {
-                    self.creep.move_to(&c);
+                    movement::move_creep_to(&self.creep, &c.pos(), 3);
                 } else if r != ReturnCode::Ok {
                     warn!("couldn't upgrade: {:?}", r);
                 }
@@ -316,24 +336,29 @@ impl CreepBitch {
 impl HandlesResource for CreepBitch {
     fn calc_next_fetch<'a>(
         &mut self,
-        rooms_state: &'a HashMap<RoomName, RoomState>,
-    ) -> Result<Option<(&'a ResourceProvider, ResourceType, u32)>, Box<dyn Error>> {
+        state: &'a BWState,
+    ) -> anyhow::Result<Option<(&'a ResourceProvider, ResourceType, u32)>> {
         let room = self.my_room.room()?;
-        let room_state = rooms_state
+        let room_state = state
+            .room_states
             .get(&room.name())
-            .ok_or_else(|| Box::new(CreepError::RoomNotFound()))?;
+            .ok_or_else(CreepError::RoomNotFound)?;
         let amount = self.creep.store_free_capacity(Some(ResourceType::Energy));
         match room_state {
             RoomState::Base(room_state) => {
                 let working_providers: Vec<&ResourceProvider> = room_state
                     .resource_providers
                     .iter()
-                    .filter_map(|(_id, p)| match p.creep_can_use(&self.creep) {
-                        Ok(true) => Some(p),
-                        Ok(false) => None,
-                        Err(err) => {
-                            warn!("Could not check for `creep_can_use`: {}", err);
-                            None
+                    // Upgrading takes nothing from the spawns, only construction may
+                    .filter(|(_id, p)| !p.is_spawn_supply())
+                    .filter_map(|(_id, p)| {
+                        match p.creep_can_use(&self.creep, ResourceType::Energy) {
+                            Ok(true) => Some(p),
+                            Ok(false) => None,
+                            Err(err) => {
+                                warn!("Could not check for `creep_can_use`: {}", err);
+                                None
+                            }
                         }
                     })
                     .collect();
@@ -341,6 +366,8 @@ impl HandlesResource for CreepBitch {
                     &room,
                     self.creep.pos(),
                     working_providers,
+                    ResourceType::Energy,
+                    &state.provider_reservations,
                 )?;
                 match prioed {
                     Some(prov) => Ok(Some((prov, ResourceType::Energy, amount as u32))),
@@ -351,12 +378,15 @@ impl HandlesResource for CreepBitch {
                 if let SetupBaseStateVisibility::Visible{ref resource_providers, ..} = room_state.state {
                     let working_providers: Vec<&ResourceProvider> = resource_providers
                         .iter()
-                        .filter_map(|(_id, p)| match p.creep_can_use(&self.creep) {
-                            Ok(true) => Some(p),
-                            Ok(false) => None,
-                            Err(err) => {
-                                warn!("Could not check for `creep_can_use`: {}", err);
-                                None
+                        .filter(|(_id, p)| !p.is_spawn_supply())
+                        .filter_map(|(_id, p)| {
+                            match p.creep_can_use(&self.creep, ResourceType::Energy) {
+                                Ok(true) => Some(p),
+                                Ok(false) => None,
+                                Err(err) => {
+                                    warn!("Could not check for `creep_can_use`: {}", err);
+                                    None
+                                }
                             }
                         })
                         .collect();
@@ -364,6 +394,8 @@ impl HandlesResource for CreepBitch {
                         &room,
                         self.creep.pos(),
                         working_providers,
+                        ResourceType::Energy,
+                        &state.provider_reservations,
                     )?;
                     match prioed {
                         Some(prov) => Ok(Some((prov, ResourceType::Energy, amount as u32))),
@@ -377,70 +409,113 @@ impl HandlesResource for CreepBitch {
     }
 }
 
+/// Providers holding some of the resource after the reservations of this tick
+fn with_resource<'a>(
+    providers: Vec<&'a ResourceProvider>,
+    resource_type: ResourceType,
+    reservations: &ProviderReservations,
+) -> Vec<&'a ResourceProvider> {
+    providers
+        .into_iter()
+        .filter(|p| {
+            reservations
+                .available(p, resource_type)
+                .map(|available| available > 0)
+                .unwrap_or(false)
+        })
+        .collect()
+}
+
+/// Harvesting the sources is the fallback for creeps with WORK parts, only while no provider of
+/// stored energy is worth going to. Early rooms without containers or a storage then still get
+/// built. The spawn and extensions don't count as stored energy, they are only taken from when
+/// there are no sources either.
 fn generic_creep_fetch_from_provider_prio<'a>(
     room: &Room,
     creep_pos: Position,
     working_providers: Vec<&'a ResourceProvider>,
+    resource_type: ResourceType,
+    reservations: &ProviderReservations,
 ) -> anyhow::Result<Option<&'a ResourceProvider>> {
-    let mut sorted = working_providers.clone();
-    sorted.sort_by_cached_key(|a| {
-        Reverse(generic_working_providers_points(room, a, &creep_pos)
+    let points = |a: &&ResourceProvider, distance: Distance| {
+        generic_working_providers_points(room, a, &creep_pos, distance, resource_type, reservations)
             .unwrap_or(Some(-10000))
-            .unwrap_or(-10000))
-    });
-    // sorted.sort_by(|a, b| {
-    //     let a_p = generic_working_providers_points(room, a, &creep_pos)
-    //         .unwrap_or(Some(-10000))
-    //         .unwrap_or(-10000);
-    //     let b_p = generic_working_providers_points(room, b, &creep_pos)
-    //         .unwrap_or(Some(-10000))
-    //         .unwrap_or(-10000);
-    //     a_p.cmp(&b_p).reverse()
-    // });
+            .unwrap_or(-10000)
+    };
+    let (farms, others): (Vec<&ResourceProvider>, Vec<&ResourceProvider>) = working_providers
+        .into_iter()
+        .partition(|p| matches!(p, ResourceProvider::EnergyFarm { .. }));
+    let (spawn_supplies, stored): (Vec<&ResourceProvider>, Vec<&ResourceProvider>) =
+        others.into_iter().partition(|p| p.is_spawn_supply());
+    let worth_it: Vec<&ResourceProvider> =
+        with_resource(stored.clone(), resource_type, reservations)
+            .into_iter()
+            .filter(|p| points(p, Distance::Range) > 0)
+            .collect();
+    let candidates = if !worth_it.is_empty() {
+        worth_it
+    } else if resource_type != ResourceType::Energy || farms.is_empty() {
+        stored.into_iter().chain(spawn_supplies).collect()
+    } else {
+        let full_farms = with_resource(farms.clone(), resource_type, reservations);
+        // All sources are empty, wait at the closest one
+        if full_farms.is_empty() {
+            farms
+        } else {
+            full_farms
+        }
+    };
+    let sorted = rank_two_stage(candidates, PATHED_CANDIDATES, false, points);
     Ok(sorted.first().map(|s| *s))
 }
 
-// TODO needs to know the resource type!
 fn generic_working_providers_points(
     room: &Room,
     prov: &ResourceProvider,
     for_pos: &Position,
-) -> Result<Option<i32>, Box<dyn Error>> {
+    distance: Distance,
+    resource_type: ResourceType,
+    reservations: &ProviderReservations,
+) -> anyhow::Result<Option<i32>> {
     let mut points: i32 = 0;
+    let reserved = reservations.reserved(prov, resource_type);
     match prov {
         ResourceProvider::EnergyFarm { resource_farm_data } => {
+            if resource_type != ResourceType::Energy {
+                return Ok(None);
+            }
             points += 100;
             // if let Some(source) = get_object_typed(resource_farm_data.obj_id)? {
             //     points += (source.energy() as f32 / 1000.).ceil() as i32;
             // }
-            let path = resource_farm_data
-                .pos()?
-                .find_path_to(for_pos, FindOptions::default());
-            let vec_path = match path {
-                Path::Serialized(p) => room.deserialize_path(&p),
-                Path::Vectorized(p) => p,
-            };
-            points -= vec_path.len() as i32;
+            let path_len = distance.between(room, &resource_farm_data.pos()?, for_pos);
+            points -= path_len as i32;
         }
         ResourceProvider::SourceDump { room_object_data } => {
             points += 200;
-            // TODO Doesnt check which type of resoure yet
+            let path_len = distance.between(room, &room_object_data.pos()?, for_pos);
             let resource_amount = match room_object_data {
                 RoomObjectData::StorageStructure { obj_id } => {
-                    let obj = get_object_typed(*obj_id)?.ok_or_else(|| {
-                        Box::new(CreepError::ObjectNotFound(format!("{}", *obj_id)))
+                    let obj = get_object_cached(*obj_id)?.ok_or_else(|| {
+                        CreepError::ObjectNotFound(format!("{}", *obj_id))
                     })?;
                     obj.as_has_store()
-                        .map(|s| s.store_used_capacity(Some(ResourceType::Energy)))
+                        .map(|s| s.store_used_capacity(Some(resource_type)))
                         .unwrap_or(0)
                 }
                 RoomObjectData::Litter { obj_id } => {
-                    let obj = get_object_typed(*obj_id)?.ok_or_else(|| {
-                        Box::new(CreepError::ObjectNotFound(format!("{}", *obj_id)))
+                    let obj = get_object_cached(*obj_id)?.ok_or_else(|| {
+                        CreepError::ObjectNotFound(format!("{}", *obj_id))
                     })?;
-                    obj.amount()
+                    if obj.resource_type() == resource_type {
+                        // Whatever is left once we get there
+                        dropped_amount_after(obj.amount(), path_len)
+                    } else {
+                        0
+                    }
                 }
-            };
+            }
+            .saturating_sub(reserved);
             // Poor man's curve
             if resource_amount == 0 {
                 points = 0;
@@ -451,28 +526,18 @@ fn generic_working_providers_points(
             } else {
                 points += (resource_amount as f32 / 100.).round() as i32;
             }
-            let path = room_object_data
-                .pos()?
-                .find_path_to(for_pos, FindOptions::default());
-            let vec_path = match path {
-                Path::Serialized(p) => room.deserialize_path(&p),
-                Path::Vectorized(p) => p,
-            };
-            points -= vec_path.len() as i32 * 3;
+            points -= path_len as i32 * 3;
         }
         ResourceProvider::BufferControllerUpgrade { room_object_data } => {
             points += 200;
-            // TODO Doesnt check which type of resoure yet
-            let obj = get_object_typed(room_object_data.obj_id)?.ok_or_else(|| {
-                Box::new(CreepError::ObjectNotFound(format!(
-                    "{}",
-                    room_object_data.obj_id
-                )))
+            let obj = get_object_cached(room_object_data.obj_id)?.ok_or_else(|| {
+                CreepError::ObjectNotFound(format!("{}", room_object_data.obj_id))
             })?;
             let resource_amount = obj
                 .as_has_store()
-                .map(|s| s.store_used_capacity(Some(ResourceType::Energy)))
-                .unwrap_or(0);
+                .map(|s| s.store_used_capacity(Some(resource_type)))
+                .unwrap_or(0)
+                .saturating_sub(reserved);
             // Poor man's curve
             if resource_amount == 0 {
                 points = 0;
@@ -483,69 +548,94 @@ fn generic_working_providers_points(
             } else {
                 points += (resource_amount as f32 / 100.).round() as i32;
             }
-            let path = room_object_data
-                .pos()?
-                .find_path_to(for_pos, FindOptions::default());
-            let vec_path = match path {
-                Path::Serialized(p) => room.deserialize_path(&p),
-                Path::Vectorized(p) => p,
-            };
-            points -= vec_path.len() as i32 * 3;
+            let path_len = distance.between(room, &room_object_data.pos()?, for_pos);
+            points -= path_len as i32 * 3;
         }
         ResourceProvider::LongTermStorage { room_object_data } => {
             points += 200;
-            // TODO Doesnt check which type of resoure yet
-            let obj = get_object_typed(room_object_data.obj_id)?.ok_or_else(|| {
-                Box::new(CreepError::ObjectNotFound(format!(
-                    "{}",
-                    room_object_data.obj_id
-                )))
+            let obj = get_object_cached(room_object_data.obj_id)?.ok_or_else(|| {
+                CreepError::ObjectNotFound(format!("{}", room_object_data.obj_id))
             })?;
             let resource_amount = obj
                 .as_has_store()
-                .map(|s| s.store_used_capacity(Some(ResourceType::Energy)))
-                .unwrap_or(0);
+                .map(|s| s.store_used_capacity(Some(resource_type)))
+                .unwrap_or(0)
+                .saturating_sub(reserved);
             if resource_amount < 20000 {
                 // Ensure minimum of energy
                 points = 1;
             }
-            let path = room_object_data
-                .pos()?
-                .find_path_to(for_pos, FindOptions::default());
-            let vec_path = match path {
-                Path::Serialized(p) => room.deserialize_path(&p),
-                Path::Vectorized(p) => p,
-            };
-            points -= vec_path.len() as i32 * 3;
+            let path_len = distance.between(room, &room_object_data.pos()?, for_pos);
+            points -= path_len as i32 * 3;
         }
         ResourceProvider::TerminalOverflow { room_object_data } => {
             points += 150;
-            // TODO Doesnt check which type of resoure yet
-            let obj = get_object_typed(room_object_data.obj_id)?.ok_or_else(|| {
-                Box::new(CreepError::ObjectNotFound(format!(
-                    "{}",
-                    room_object_data.obj_id
-                )))
+            let obj = get_object_cached(room_object_data.obj_id)?.ok_or_else(|| {
+                CreepError::ObjectNotFound(format!("{}", room_object_data.obj_id))
             })?;
             let resource_amount = obj
                 .as_has_store()
-                .map(|s| s.store_used_capacity(Some(ResourceType::Energy)))
-                .unwrap_or(0);
-            let overflow_resource_amount = resource_amount as i32 - TERMINAL_TRADE_BUFFER as i32;
+                .map(|s| s.store_used_capacity(Some(resource_type)))
+                .unwrap_or(0)
+                .saturating_sub(reserved);
+            let keep_amount = trade::terminal_keep_amount(room.name(), resource_type);
+            let overflow_resource_amount = resource_amount as i32 - keep_amount as i32;
             if overflow_resource_amount < 0 {
                 // Ensure minimum of energy
                 points = -100;
             } else if overflow_resource_amount > 1000 {
                 points += cmp::max((overflow_resource_amount as f32 / 10000.).round() as i32, 5);
             }
-            let path = room_object_data
-                .pos()?
-                .find_path_to(for_pos, FindOptions::default());
-            let vec_path = match path {
-                Path::Serialized(p) => room.deserialize_path(&p),
-                Path::Vectorized(p) => p,
-            };
-            points -= vec_path.len() as i32 * 3;
+            let path_len = distance.between(room, &room_object_data.pos()?, for_pos);
+            points -= path_len as i32 * 3;
+        }
+        ResourceProvider::Tombstone { tombstone_data } => {
+            let obj = get_object_cached(tombstone_data.obj_id)?.ok_or_else(|| {
+                CreepError::ObjectNotFound(format!("{}", tombstone_data.obj_id))
+            })?;
+            let resource_amount = obj
+                .store_used_capacity(Some(resource_type))
+                .saturating_sub(reserved);
+            if resource_amount == 0 {
+                return Ok(None);
+            }
+            // Decays soon, so it goes before everything else
+            points += 300 + (resource_amount as f32 / 50.).round() as i32;
+            let path_len = distance.between(room, &tombstone_data.pos()?, for_pos);
+            if path_len >= obj.ticks_to_decay() {
+                return Ok(None);
+            }
+            points -= path_len as i32 * 3;
+        }
+        ResourceProvider::SpawnSupply { spawn_supply_data } => {
+            let resource_amount = spawn_supply_data
+                .provides(&resource_type)?
+                .saturating_sub(reserved);
+            if resource_amount == 0 {
+                return Ok(None);
+            }
+            // Only when there's nothing else, the spawn fills up again from it
+            points += 50;
+            let path_len = distance.between(room, &spawn_supply_data.pos()?, for_pos);
+            points -= path_len as i32 * 3;
+        }
+        ResourceProvider::Unknown {
+            room_object_data: RoomObjectData::Litter { obj_id },
+        } => {
+            let obj = get_object_cached(*obj_id)?
+                .ok_or_else(|| CreepError::ObjectNotFound(format!("{}", *obj_id)))?;
+            if obj.resource_type() != resource_type {
+                return Ok(None);
+            }
+            let path_len = distance.between(room, &obj.pos(), for_pos);
+            let resource_amount = dropped_amount_after(obj.amount(), path_len)
+                .saturating_sub(reserved);
+            if resource_amount < 50 {
+                return Ok(None);
+            }
+            // Rather get it before it is gone
+            points += 150 + (resource_amount as f32 / 50.).round() as i32;
+            points -= path_len as i32 * 3;
         }
         _ => return Ok(None),
     };
@@ -579,24 +669,27 @@ impl fmt::Debug for CreepBuilder {
 impl HandlesResource for CreepBuilder {
     fn calc_next_fetch<'a>(
         &mut self,
-        rooms_state: &'a HashMap<RoomName, RoomState>,
-    ) -> Result<Option<(&'a ResourceProvider, ResourceType, u32)>, Box<dyn Error>> {
+        state: &'a BWState,
+    ) -> anyhow::Result<Option<(&'a ResourceProvider, ResourceType, u32)>> {
         let room = self.my_room.room()?;
-        let room_state = rooms_state
+        let room_state = state
+            .room_states
             .get(&room.name())
-            .ok_or_else(|| Box::new(CreepError::RoomNotFound()))?;
+            .ok_or_else(CreepError::RoomNotFound)?;
         let amount = self.creep.store_free_capacity(Some(ResourceType::Energy));
         match room_state {
             RoomState::Base(room_state) => {
                 let working_providers: Vec<&ResourceProvider> = room_state
                     .resource_providers
                     .iter()
-                    .filter_map(|(_id, p)| match p.creep_can_use(&self.creep) {
-                        Ok(true) => Some(p),
-                        Ok(false) => None,
-                        Err(err) => {
-                            warn!("Could not check for `creep_can_use`: {}", err);
-                            None
+                    .filter_map(|(_id, p)| {
+                        match p.creep_can_use(&self.creep, ResourceType::Energy) {
+                            Ok(true) => Some(p),
+                            Ok(false) => None,
+                            Err(err) => {
+                                warn!("Could not check for `creep_can_use`: {}", err);
+                                None
+                            }
                         }
                     })
                     .collect();
@@ -604,6 +697,8 @@ impl HandlesResource for CreepBuilder {
                     &room,
                     self.creep.pos(),
                     working_providers,
+                    ResourceType::Energy,
+                    &state.provider_reservations,
                 )?;
                 match prioed {
                     Some(prov) => Ok(Some((prov, ResourceType::Energy, amount as u32))),
@@ -614,12 +709,14 @@ impl HandlesResource for CreepBuilder {
                 if let SetupBaseStateVisibility::Visible{ref resource_providers, ..} = room_state.state {
                     let working_providers: Vec<&ResourceProvider> = resource_providers
                         .iter()
-                        .filter_map(|(_id, p)| match p.creep_can_use(&self.creep) {
-                            Ok(true) => Some(p),
-                            Ok(false) => None,
-                            Err(err) => {
-                                warn!("Could not check for `creep_can_use`: {}", err);
-                                None
+                        .filter_map(|(_id, p)| {
+                            match p.creep_can_use(&self.creep, ResourceType::Energy) {
+                                Ok(true) => Some(p),
+                                Ok(false) => None,
+                                Err(err) => {
+                                    warn!("Could not check for `creep_can_use`: {}", err);
+                                    None
+                                }
                             }
                         })
                         .collect();
@@ -627,6 +724,8 @@ impl HandlesResource for CreepBuilder {
                         &room,
                         self.creep.pos(),
                         working_providers,
+                        ResourceType::Energy,
+                        &state.provider_reservations,
                     )?;
                     match prioed {
                         Some(prov) => Ok(Some((prov, ResourceType::Energy, amount as u32))),
@@ -679,25 +778,33 @@ impl CreepBuilder {
         self.target = target;
     }
 
-    pub fn harvest_check(&mut self) -> Result<(), Box<dyn Error>> {
+    pub fn harvest_check(&mut self) -> anyhow::Result<()> {
         if self.harvesting {
             if self.creep.store_free_capacity(Some(ResourceType::Energy)) == 0 {
                 self.set_getting_resource(None);
             }
         } else {
-            self.creep.say("ᕕ( ᐛ )ᕗ", true);
+            speech::say(&self.creep, "ᕕ( ᐛ )ᕗ", Verbosity::Chatter);
             if self.creep.store_used_capacity(None) == 0 {
-                let context = BWContext::get();
-                let state = context.state()?;
-                if let Some(fetch_target) = self.calc_next_fetch(&state.room_states)? {
-                    self.set_getting_resource(Some(fetch_target.0));
+                let mut context = BWContext::get();
+                let state = context.mut_state()?;
+                let fetch_target = self
+                    .calc_next_fetch(state)?
+                    .map(|(provider, resource_type, amount)| {
+                        (provider.to_owned(), resource_type, amount)
+                    });
+                if let Some((provider, resource_type, amount)) = fetch_target {
+                    self.set_getting_resource(Some(&provider));
+                    state
+                        .provider_reservations
+                        .reserve(&provider, resource_type, amount);
                 }
             }
         }
         Ok(())
     }
 
-    pub fn harvest(&mut self) -> Result<(), Box<dyn Error>> {
+    pub fn harvest(&mut self) -> anyhow::Result<()> {
         let context = BWContext::get();
         let state = context.state()?;
         let resource_provider_id = self.creep.memory().string(MEM_RESOURCE_PROVIDER_ID)?;
@@ -709,7 +816,7 @@ impl CreepBuilder {
                     self.creep.id()
                 );
                 self.set_getting_resource(None);
-                return Err(Box::new(CreepError::ResourceProviderIdNotStored));
+                return Err(CreepError::ResourceProviderIdNotStored.into());
             }
         };
         let resource_provider = state
@@ -759,7 +866,7 @@ impl CreepBuilder {
                     }
                 };
             } else {
-                self.creep.move_to(&resource_provider.pos()?);
+                movement::move_creep_to(&self.creep, &resource_provider.pos()?, 1);
             }
         } else {
             warn!(
@@ -771,11 +878,11 @@ impl CreepBuilder {
         Ok(())
     }
 
-    pub fn build(&mut self) -> Result<(), Box<dyn Error>> {
+    pub fn build(&mut self) -> anyhow::Result<()> {
         let room = &self
             .creep
             .room()
-            .ok_or(Box::new(CreepError::RoomNotFound()))?;
+            .ok_or(CreepError::RoomNotFound())?;
 
         // Precursory checks
         match &self.target {
@@ -787,12 +894,13 @@ impl CreepBuilder {
                 let room_settings = state
                     .room_settings
                     .get(&self.my_room)
-                    .ok_or(Box::new(CreepError::RoomNotFound()))?;
+                    .ok_or(CreepError::RoomNotFound())?;
 
-                match (
-                    get_prio_repair_target(room)?,
-                    room_settings.maintenance.priority_item()?,
-                ) {
+                // Towers and others may have claimed a repair target for this tick already
+                let repair_target = prio_repair_targets(room)
+                    .into_iter()
+                    .find(|t| !repairs::is_claimed(t.structure().id()));
+                match (repair_target, room_settings.maintenance.priority_item()?) {
                     // TODO Use `RoomMaintenance also for repairs
                     (Some(RepairTarget::Important { target }), _) => {
                         self.set_target(Some(CreepBuilderTarget::Repair(target.id().into())));
@@ -864,7 +972,7 @@ impl CreepBuilder {
                                     self.set_target(None);
                                 }
                             } else {
-                                self.creep.move_to(&target);
+                                movement::move_creep_to(&self.creep, &target.pos(), 1);
                             }
                         }
                         Ok(None) => {
@@ -883,7 +991,12 @@ impl CreepBuilder {
                     match object {
                         Some(target) => {
                             if let Some(attackable_target) = target.as_attackable() {
-                                if self.creep.pos().in_range_to(&target, 3) {
+                                let power = self.creep.get_active_bodyparts(Part::Work)
+                                    * repairs::CREEP_REPAIR_POWER;
+                                if !repairs::claim(target.id(), power) {
+                                    // Someone else repairs it this tick
+                                    self.set_target(None);
+                                } else if self.creep.pos().in_range_to(&target, 3) {
                                     let r = self.creep.repair(&target);
 
                                     if r != ReturnCode::Ok {
@@ -891,7 +1004,7 @@ impl CreepBuilder {
                                         self.set_target(None);
                                     }
                                 } else {
-                                    self.creep.move_to(&target);
+                                    movement::move_creep_to(&self.creep, &target.pos(), 3);
                                 }
                                 if attackable_target.hits() == attackable_target.hits_max() {
                                     self.set_target(None);
@@ -918,10 +1031,20 @@ pub enum RepairTarget {
     Important { target: Structure },
 }
 
+impl RepairTarget {
+    pub fn structure(&self) -> &Structure {
+        match self {
+            RepairTarget::Arbeitsbeschaffung { target } => target,
+            RepairTarget::Important { target } => target,
+        }
+    }
+}
+
 const HIGHER_NUM: f64 = 1_000_000_000_000.;
 // const HIGHER_NUM: f32 = 10.;
 
-pub fn get_prio_repair_target(room: &Room) -> Result<Option<RepairTarget>, Box<dyn Error>> {
+/// Structures in need of repair, the most urgent first
+pub fn prio_repair_targets(room: &Room) -> Vec<RepairTarget> {
     let mut repairable_structures: Vec<Structure> = room
         .find(find::STRUCTURES)
         .into_iter()
@@ -935,21 +1058,23 @@ pub fn get_prio_repair_target(room: &Room) -> Result<Option<RepairTarget>, Box<d
             _ => false,
         })
         .collect();
+    let wall_target = economy::wall_target_hits(room);
     repairable_structures.sort_by_cached_key(|a| {
-        -get_structure_prio_val(a)
+        -get_structure_prio_val(a, wall_target)
     });
-    Ok(repairable_structures.first().map(|s| {
-        if get_structure_prio_val(s) < HIGHER_NUM as i64 + 10 {
-            RepairTarget::Arbeitsbeschaffung { target: s.clone() }
-        } else {
-            RepairTarget::Important { target: s.clone() }
-        }
-    }))
+    repairable_structures
+        .into_iter()
+        .map(|s| {
+            if get_structure_prio_val(&s, wall_target) < HIGHER_NUM as i64 + 10 {
+                RepairTarget::Arbeitsbeschaffung { target: s }
+            } else {
+                RepairTarget::Important { target: s }
+            }
+        })
+        .collect()
 }
 
-const TARGET_WALLING: f64 = 10_000_000.;
-
-fn get_structure_prio_val(structure: &Structure) -> i64 {
+fn get_structure_prio_val(structure: &Structure, wall_target: f64) -> i64 {
     match structure {
         Structure::Road(road) => (HIGHER_NUM
             + ((1. - road.hits() as f64 / road.hits_max() as f64) * 100.))
@@ -958,10 +1083,10 @@ fn get_structure_prio_val(structure: &Structure) -> i64 {
             + ((1. - container.hits() as f64 / container.hits_max() as f64) * 100.))
             .round() as i64,
         Structure::Rampart(rampart) => (HIGHER_NUM
-            + ((1. - rampart.hits() as f64 / TARGET_WALLING as f64) * 1.01))
+            + ((1. - rampart.hits() as f64 / wall_target) * 1.01))
             .round() as i64,
         Structure::Wall(wall) => {
-            (HIGHER_NUM * ((1. - wall.hits() as f64 / TARGET_WALLING) * 1.)).round() as i64
+            (HIGHER_NUM * ((1. - wall.hits() as f64 / wall_target) * 1.)).round() as i64
         }
         _ => -1,
     }
@@ -1014,12 +1139,9 @@ impl CreepFarmer {
     //     self.assigned_source = assigned_source;
     // }
 
-    pub fn harvest(&mut self) -> Result<(), Box<dyn Error>> {
+    pub fn harvest(&mut self) -> anyhow::Result<()> {
         let source = get_object_typed(self.assigned_source)?.ok_or_else(|| {
-            Box::new(CreepError::SourceNotFound(format!(
-                "{}",
-                self.assigned_source
-            )))
+            CreepError::SourceNotFound(format!("{}", self.assigned_source))
         })?;
         let target_pos = self.farm_position.position();
         if self.creep.pos() == target_pos {
@@ -1028,7 +1150,7 @@ impl CreepFarmer {
                 warn!("couldn't harvest: {:?}", r);
             }
         } else {
-            self.creep.move_to(&target_pos);
+            movement::move_creep_to(&self.creep, &target_pos, 0);
         }
         Ok(())
     }
@@ -1079,7 +1201,7 @@ impl CreepRunner {
         CREEP_ID_RUNNER.into()
     }
 
-    pub fn run(&mut self) -> Result<(), Box<dyn Error>> {
+    pub fn run(&mut self) -> anyhow::Result<()> {
         let room = self.my_room.room()?;
         if let Some(state) = &self.state {
             match state {
@@ -1115,9 +1237,8 @@ impl CreepRunner {
                     if self.creep.pos().is_near_to(&from.pos()) {
                         match from {
                             CreepRunnerFetchTarget::PermanentFarmerContainer { id, .. } => {
-                                let obj = get_object_typed(*id)?.ok_or(Box::new(
-                                    CreepError::ObjectNotFound(format!("{}", id)),
-                                ))?;
+                                let obj = get_object_typed(*id)?
+                                    .ok_or(CreepError::ObjectNotFound(format!("{}", id)))?;
                                 let amount = cmp::min(
                                     // to.requested(),
                                     self.creep.store_free_capacity(Some(ResourceType::Energy)) as u32,
@@ -1127,9 +1248,8 @@ impl CreepRunner {
                                     .withdraw_amount(&obj, ResourceType::Energy, amount);
                             }
                             CreepRunnerFetchTarget::Ruin { id, .. } => {
-                                let obj = get_object_typed(*id)?.ok_or(Box::new(
-                                    CreepError::ObjectNotFound(format!("{}", id)),
-                                ))?;
+                                let obj = get_object_typed(*id)?
+                                    .ok_or(CreepError::ObjectNotFound(format!("{}", id)))?;
                                 let amount = cmp::min(
                                     self.creep.store_free_capacity(Some(ResourceType::Energy))
                                         as u32,
@@ -1142,6 +1262,17 @@ impl CreepRunner {
                                 self.creep
                                     .withdraw_amount(&obj, ResourceType::Energy, amount);
                             }
+                            CreepRunnerFetchTarget::Tombstone { id, .. } => {
+                                let obj = get_object_typed(*id)?
+                                    .ok_or(CreepError::ObjectNotFound(format!("{}", id)))?;
+                                let amount = cmp::min(
+                                    self.creep.store_free_capacity(Some(ResourceType::Energy))
+                                        as u32,
+                                    obj.store_used_capacity(Some(ResourceType::Energy)),
+                                );
+                                self.creep
+                                    .withdraw_amount(&obj, ResourceType::Energy, amount);
+                            }
                             CreepRunnerFetchTarget::DroppedSource { id, pos, .. } => {
                                 let obj = get_object_typed(*id)?;
                                 let farmer_container =
@@ -1210,9 +1341,8 @@ impl CreepRunner {
                                 }
                             }
                             CreepRunnerFetchTarget::Terminal { id, .. } => {
-                                let obj = get_object_typed(*id)?.ok_or(Box::new(
-                                    CreepError::ObjectNotFound(format!("{}", id)),
-                                ))?;
+                                let obj = get_object_typed(*id)?
+                                    .ok_or(CreepError::ObjectNotFound(format!("{}", id)))?;
                                 let amount = cmp::min(
                                     // to.requested(),
                                     self.creep.store_free_capacity(Some(ResourceType::Energy)) as u32,
@@ -1225,16 +1355,15 @@ impl CreepRunner {
                         // FIXME Hack
                         self.new_run()?;
                     } else {
-                        self.creep.move_to(&from.pos());
+                        movement::move_creep_to(&self.creep, &from.pos(), 1);
                     }
                 }
                 CreepRunnerState::Delivering { to, provided } => {
                     if self.creep.pos().is_near_to(&to.pos()) {
                         match to {
                             CreepRunnerDeliverTarget::Tower { id, .. } => {
-                                let obj = get_object_typed(*id)?.ok_or(Box::new(
-                                    CreepError::ObjectNotFound(format!("{}", id)),
-                                ))?;
+                                let obj = get_object_typed(*id)?
+                                    .ok_or(CreepError::ObjectNotFound(format!("{}", id)))?;
                                 let amount = cmp::min(
                                     to.requested(),
                                     self.creep.store_used_capacity(Some(ResourceType::Energy)),
@@ -1244,9 +1373,8 @@ impl CreepRunner {
                                 *provided += amount;
                             }
                             CreepRunnerDeliverTarget::Extension { id, .. } => {
-                                let obj = get_object_typed(*id)?.ok_or(Box::new(
-                                    CreepError::ObjectNotFound(format!("{}", id)),
-                                ))?;
+                                let obj = get_object_typed(*id)?
+                                    .ok_or(CreepError::ObjectNotFound(format!("{}", id)))?;
                                 let amount = cmp::min(
                                     to.requested(),
                                     self.creep.store_used_capacity(Some(ResourceType::Energy)),
@@ -1256,9 +1384,8 @@ impl CreepRunner {
                                 *provided += amount;
                             }
                             CreepRunnerDeliverTarget::Spawn { id, .. } => {
-                                let obj = get_object_typed(*id)?.ok_or(Box::new(
-                                    CreepError::ObjectNotFound(format!("{}", id)),
-                                ))?;
+                                let obj = get_object_typed(*id)?
+                                    .ok_or(CreepError::ObjectNotFound(format!("{}", id)))?;
                                 let amount = cmp::min(
                                     to.requested(),
                                     self.creep.store_used_capacity(Some(ResourceType::Energy)),
@@ -1268,9 +1395,8 @@ impl CreepRunner {
                                 *provided += amount;
                             }
                             CreepRunnerDeliverTarget::PermanentUpgraderContainer { id, .. } => {
-                                let obj = get_object_typed(*id)?.ok_or(Box::new(
-                                    CreepError::ObjectNotFound(format!("{}", id)),
-                                ))?;
+                                let obj = get_object_typed(*id)?
+                                    .ok_or(CreepError::ObjectNotFound(format!("{}", id)))?;
                                 let amount = cmp::min(
                                     to.requested(),
                                     self.creep.store_used_capacity(Some(ResourceType::Energy)),
@@ -1280,21 +1406,8 @@ impl CreepRunner {
                                 *provided += amount;
                             }
                             CreepRunnerDeliverTarget::TempStorage { id, .. } => {
-                                let obj = get_object_typed(*id)?.ok_or(Box::new(
-                                    CreepError::ObjectNotFound(format!("{}", id)),
-                                ))?;
-                                let amount = cmp::min(
-                                    to.requested(),
-                                    self.creep.store_used_capacity(Some(ResourceType::Energy)),
-                                );
-                                self.creep
-                                    .transfer_amount(&obj, ResourceType::Energy, amount);
-                                *provided += amount;
-                            }
-                            CreepRunnerDeliverTarget::TradeTransactionFee { id, .. } => {
-                                let obj = get_object_typed(*id)?.ok_or(Box::new(
-                                    CreepError::ObjectNotFound(format!("terminal TradeTransactionFee {}", id)),
-                                ))?;
+                                let obj = get_object_typed(*id)?
+                                    .ok_or(CreepError::ObjectNotFound(format!("{}", id)))?;
                                 let amount = cmp::min(
                                     to.requested(),
                                     self.creep.store_used_capacity(Some(ResourceType::Energy)),
@@ -1305,7 +1418,7 @@ impl CreepRunner {
                             }
                         }
                     } else {
-                        self.creep.move_to(&to.pos());
+                        movement::move_creep_to(&self.creep, &to.pos(), 1);
                     }
                 }
             }
@@ -1313,9 +1426,9 @@ impl CreepRunner {
         Ok(())
     }
 
-    pub fn new_run(&mut self) -> Result<(), Box<dyn Error>> {
+    pub fn new_run(&mut self) -> anyhow::Result<()> {
         let room = self.my_room.room()?;
-        let deliver_target = get_prio_deliver_target(&room, &self.creep)?;
+        let deliver_target = get_prio_deliver_target(&room, &self.creep, false)?;
         info!("del target {:?} in {}", deliver_target, room.name());
         if let Some(deliver_target) = deliver_target {
             if deliver_target.requested()
@@ -1326,7 +1439,27 @@ impl CreepRunner {
                     provided: 0,
                 });
             } else {
-                let fetch_target = get_prio_fetch_target(&room, &deliver_target, &self.creep.pos())?;
+                let fetch_target = {
+                    let mut context = BWContext::get();
+                    let reservations = &mut context.mut_state()?.provider_reservations;
+                    let fetch_target = get_prio_fetch_target(
+                        &room,
+                        &deliver_target,
+                        &self.creep.pos(),
+                        reservations,
+                    )?;
+                    if let Some(fetch_target) = &fetch_target {
+                        reservations.reserve_object(
+                            fetch_target.raw_id(),
+                            ResourceType::Energy,
+                            cmp::min(
+                                self.creep.store_free_capacity(Some(ResourceType::Energy)) as u32,
+                                fetch_target.provides(),
+                            ),
+                        );
+                    }
+                    fetch_target
+                };
                 if let Some(fetch_target) = fetch_target {
                     self.state = Some(CreepRunnerState::Fetching {
                         from: fetch_target,
@@ -1348,25 +1481,21 @@ impl CreepRunner {
 }
 
 /// Searches for something that provides the resources for the delivery_target
+///
+/// Targets other creeps already reserved in this tick are skipped, see `ProviderReservations`.
 fn get_prio_fetch_target(
     room: &Room,
     _delivery_target: &CreepRunnerDeliverTarget,
     creep_pos: &Position,
-) -> Result<Option<CreepRunnerFetchTarget>, Box<dyn Error>> {
+    reservations: &ProviderReservations,
+) -> anyhow::Result<Option<CreepRunnerFetchTarget>> {
     let controller = room.controller().ok_or(anyhow!("Controller not found"))?; 
-    let mut containers: Vec<StructureContainer> = room
-        .find(find::STRUCTURES)
-        .into_iter()
-        .filter_map(|s| match s {
-            Structure::Container(container) => Some(container),
-            _ => None,
-        })
-        .collect();
+    let containers: Vec<StructureContainer> =
+        structures::of_type(room.name(), StructureType::Container);
     // TODO Dummy implementation
-    containers.sort_by_cached_key(|container| {
-        let path_len = container.pos().find_path_to(creep_pos, FindOptions::default()).vectorized().unwrap_or(vec![]).len() as i32;
-        -(container.store_used_capacity(Some(ResourceType::Energy)) as i32
-            - path_len * 100)
+    let containers = rank_two_stage(containers, PATHED_CANDIDATES, false, |container, distance| {
+        let path_len = distance.between(room, &container.pos(), creep_pos) as i32;
+        container.store_used_capacity(Some(ResourceType::Energy)) as i32 - path_len * 100
     });
     let viable_containers: Vec<CreepRunnerFetchTarget> = containers
         .into_iter()
@@ -1383,14 +1512,18 @@ fn get_prio_fetch_target(
         .into_iter()
         .filter(|res| res.resource_type() == ResourceType::Energy)
         .collect();
-    dropped_resources.sort_by(|res_a, res_b| res_a.amount().cmp(&res_b.amount()).reverse());
+    // Piles decay while we walk there, so compare what will be left on arrival
+    let arrival_amount =
+        |res: &screeps::Resource| dropped_amount_after(res.amount(), creep_pos.get_range_to(res));
+    dropped_resources.sort_by_cached_key(|res| Reverse(arrival_amount(res)));
 
     let viable_dropped_sources: Vec<CreepRunnerFetchTarget> = dropped_resources
         .into_iter()
+        .filter(|res| arrival_amount(res) > 0)
         .map(|res| CreepRunnerFetchTarget::DroppedSource {
             id: res.id(),
             pos: res.pos(),
-            provides: res.amount(),
+            provides: arrival_amount(&res),
         })
         .collect();
 
@@ -1409,63 +1542,74 @@ fn get_prio_fetch_target(
             })
         })
         .collect();
-    let terminal: Vec<CreepRunnerFetchTarget> = room
-        .find(find::STRUCTURES)
+    let viable_tombstones: Vec<CreepRunnerFetchTarget> = room
+        .find(find::TOMBSTONES)
         .into_iter()
-        .filter_map(|s| match s {
-            Structure::Terminal(terminal) => {
-                if terminal.store_used_capacity(Some(ResourceType::Energy)) > TERMINAL_TRADE_BUFFER {
+        .filter_map(|t| {
+            let energy = t.store_used_capacity(Some(ResourceType::Energy));
+            if energy == 0 {
+                return None;
+            }
+            Some(CreepRunnerFetchTarget::Tombstone {
+                id: t.id(),
+                pos: t.pos(),
+                provides: energy,
+            })
+        })
+        .collect();
+    let terminal: Vec<CreepRunnerFetchTarget> =
+        structures::of_type::<StructureTerminal>(room.name(), StructureType::Terminal)
+            .into_iter()
+            .filter_map(|terminal| {
+                let keep_amount = trade::terminal_keep_amount(room.name(), ResourceType::Energy);
+                let energy = terminal.store_used_capacity(Some(ResourceType::Energy));
+                if energy > keep_amount {
                     Some(CreepRunnerFetchTarget::Terminal {
                         id: terminal.id(),
                         pos: terminal.pos(),
-                        provides: terminal.store_free_capacity(Some(ResourceType::Energy))
-                            as u32 - TERMINAL_TRADE_BUFFER,
+                        provides: energy - keep_amount,
                     })
                 } else {
                     None
                 }
-            }
-            _ => None,
-        })
-        .collect();
+            })
+            .collect();
 
-    if viable_ruins.len() > 0 {
-        Ok(viable_ruins.first().and_then(|c| Some(c.clone())))
-    } else if viable_dropped_sources.len() > 0 {
-        Ok(viable_dropped_sources.first().and_then(|c| Some(c.clone())))
-    } else if viable_containers.len() > 0 {
-        Ok(viable_containers.first().and_then(|c| Some(c.clone())))
-    } else {
-        Ok(terminal.first().and_then(|c| Some(c.clone())))
-    }
+    let first_unreserved = |targets: &[CreepRunnerFetchTarget]| {
+        targets
+            .iter()
+            .find(|target| {
+                target.provides()
+                    > reservations.reserved_object(target.raw_id(), ResourceType::Energy)
+            })
+            .cloned()
+    };
+    Ok(first_unreserved(&viable_tombstones)
+        .or_else(|| first_unreserved(&viable_ruins))
+        .or_else(|| first_unreserved(&viable_dropped_sources))
+        .or_else(|| first_unreserved(&viable_containers))
+        .or_else(|| first_unreserved(&terminal)))
 }
 
+/// The structure most in need of energy, spawn and extensions first. With `towers_first` the
+/// towers go before them, see `rooms::defense::haul_priority`.
 fn get_prio_deliver_target(
     room: &Room,
     creep: &screeps::Creep,
-) -> Result<Option<CreepRunnerDeliverTarget>, Box<dyn Error>> {
+    towers_first: bool,
+) -> anyhow::Result<Option<CreepRunnerDeliverTarget>> {
     // TODO Dummy implementation
-    let structures = room.find(find::STRUCTURES);
-    let mut extensions: Vec<&StructureExtension> = structures
-        .iter()
-        .filter_map(|s| match s {
-            Structure::Extension(ext) => {
-                if ext.store_free_capacity(Some(ResourceType::Energy)) > 0 {
-                    Some(ext)
-                } else {
-                    None
-                }
-            }
-            _ => None,
-        })
-        .collect();
-    extensions.sort_by_cached_key(|ext| {
+    let extensions: Vec<StructureExtension> =
+        structures::of_type::<StructureExtension>(room.name(), StructureType::Extension)
+            .into_iter()
+            .filter(|ext| ext.store_free_capacity(Some(ResourceType::Energy)) > 0)
+            .collect();
+    let extensions = rank_two_stage(extensions, PATHED_CANDIDATES, false, |ext, distance| {
         // let a_cap = ext
         //     .store_free_capacity(Some(ResourceType::Energy));
         // let b_cap = ext_b
         //     .store_free_capacity(Some(ResourceType::Energy));
-        let range = ext.pos().find_path_to(creep, FindOptions::default());
-        range.vectorized().unwrap().len() as i32
+        -(distance.between(room, &ext.pos(), &creep.pos()) as i32)
     });
     let viable_extensions: Vec<CreepRunnerDeliverTarget> = extensions
         .into_iter()
@@ -1475,19 +1619,11 @@ fn get_prio_deliver_target(
             requested: ext.store_free_capacity(Some(ResourceType::Energy)) as u32,
         })
         .collect();
-    let mut spawns: Vec<&StructureSpawn> = structures
-        .iter()
-        .filter_map(|s| match s {
-            Structure::Spawn(spawn) => {
-                if spawn.store_free_capacity(Some(ResourceType::Energy)) > 0 {
-                    Some(spawn)
-                } else {
-                    None
-                }
-            }
-            _ => None,
-        })
-        .collect();
+    let mut spawns: Vec<StructureSpawn> =
+        structures::of_type::<StructureSpawn>(room.name(), StructureType::Spawn)
+            .into_iter()
+            .filter(|spawn| spawn.store_free_capacity(Some(ResourceType::Energy)) > 0)
+            .collect();
     spawns.sort_by(|spawn_a, spawn_b| {
         spawn_a
             .store_free_capacity(Some(ResourceType::Energy))
@@ -1503,19 +1639,11 @@ fn get_prio_deliver_target(
         })
         .collect();
 
-    let mut towers: Vec<&StructureTower> = structures
-        .iter()
-        .filter_map(|s| match s {
-            Structure::Tower(tower) => {
-                if tower.store_free_capacity(Some(ResourceType::Energy)) > 0 {
-                    Some(tower)
-                } else {
-                    None
-                }
-            }
-            _ => None,
-        })
-        .collect();
+    let mut towers: Vec<StructureTower> =
+        structures::of_type::<StructureTower>(room.name(), StructureType::Tower)
+            .into_iter()
+            .filter(|tower| tower.store_free_capacity(Some(ResourceType::Energy)) > 0)
+            .collect();
     towers.sort_by(|tower_a, tower_b| {
         tower_a
             .store_free_capacity(Some(ResourceType::Energy))
@@ -1553,45 +1681,20 @@ fn get_prio_deliver_target(
     } else {
         vec![]
     };
-    let storage: Vec<CreepRunnerDeliverTarget> = structures
-        .iter()
-        .filter_map(|s| match s {
-            Structure::Storage(storage) => {
-                if storage.store_free_capacity(Some(ResourceType::Energy)) > 0 {
-                    Some(CreepRunnerDeliverTarget::TempStorage {
-                        id: storage.id(),
-                        pos: storage.pos(),
-                        requested: storage.store_free_capacity(Some(ResourceType::Energy))
-                            as u32,
-                    })
-                } else {
-                    None
-                }
-            }
-            _ => None,
-        })
-        .collect();
-
-    let terminal: Vec<CreepRunnerDeliverTarget> = structures
-        .iter()
-        .filter_map(|s| match s {
-            Structure::Terminal(terminal) => {
-                if terminal.store_used_capacity(Some(ResourceType::Energy)) < TERMINAL_TRADE_BUFFER {
-                    Some(CreepRunnerDeliverTarget::TradeTransactionFee {
-                        id: terminal.id(),
-                        pos: terminal.pos(),
-                        requested: terminal.store_free_capacity(Some(ResourceType::Energy))
-                            as u32,
-                    })
-                } else {
-                    None
-                }
-            }
-            _ => None,
-        })
-        .collect();
+    let storage: Vec<CreepRunnerDeliverTarget> =
+        structures::of_type::<StructureStorage>(room.name(), StructureType::Storage)
+            .into_iter()
+            .filter(|storage| storage.store_free_capacity(Some(ResourceType::Energy)) > 0)
+            .map(|storage| CreepRunnerDeliverTarget::TempStorage {
+                id: storage.id(),
+                pos: storage.pos(),
+                requested: storage.store_free_capacity(Some(ResourceType::Energy)) as u32,
+            })
+            .collect();
 
-    if viable_extensions.len() > 0 {
+    if towers_first && viable_towers.len() > 0 {
+        Ok(viable_towers.first().and_then(|c| Some(c.clone())))
+    } else if viable_extensions.len() > 0 {
         Ok(viable_extensions.first().and_then(|c| Some(c.clone())))
     } else if viable_spawns.len() > 0 {
         Ok(viable_spawns.first().and_then(|c| Some(c.clone())))
@@ -1599,9 +1702,7 @@ fn get_prio_deliver_target(
         Ok(viable_towers.first().and_then(|c| Some(c.clone())))
     } else if viable_containers.len() > 0 {
         Ok(viable_containers.first().and_then(|c| Some(c.clone())))
-    } else if terminal.len() > 0 {
-        Ok(terminal.first().and_then(|c| Some(c.clone())))
-    } else{
+    } else {
         Ok(storage.first().and_then(|c| Some(c.clone())))
     }
 }
@@ -1618,6 +1719,12 @@ pub enum CreepRunnerFetchTarget {
         pos: Position,
         provides: u32,
     },
+    /// Expires quickly, so grab it first
+    Tombstone {
+        id: ObjectId<Tombstone>,
+        pos: Position,
+        provides: u32,
+    },
     DroppedSource {
         id: ObjectId<Resource>,
         pos: Position,
@@ -1636,10 +1743,33 @@ impl CreepRunnerFetchTarget {
         match self {
             PermanentFarmerContainer { pos, .. } => *pos,
             Ruin { pos, .. } => *pos,
+            Tombstone { pos, .. } => *pos,
             DroppedSource { pos, .. } => *pos,
             Terminal { pos, .. } => *pos,
         }
     }
+
+    pub fn raw_id(&self) -> RawObjectId {
+        use CreepRunnerFetchTarget::*;
+        match self {
+            PermanentFarmerContainer { id, .. } => (*id).into(),
+            Ruin { id, .. } => (*id).into(),
+            Tombstone { id, .. } => (*id).into(),
+            DroppedSource { id, .. } => (*id).into(),
+            Terminal { id, .. } => (*id).into(),
+        }
+    }
+
+    pub fn provides(&self) -> u32 {
+        use CreepRunnerFetchTarget::*;
+        match self {
+            PermanentFarmerContainer { provides, .. } => *provides,
+            Ruin { provides, .. } => *provides,
+            Tombstone { provides, .. } => *provides,
+            DroppedSource { provides, .. } => *provides,
+            Terminal { provides, .. } => *provides,
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -1669,11 +1799,6 @@ pub enum CreepRunnerDeliverTarget {
         pos: Position,
         requested: u32,
     },
-    TradeTransactionFee {
-        id: ObjectId<StructureTerminal>,
-        pos: Position,
-        requested: u32,
-    },
     // TODO might make sense to differentiate the two, e.g. backup Storage
     //   should always be there in times of needs, TempStorage just for if
     //   nothing else accepts energy.
@@ -1693,7 +1818,6 @@ impl CreepRunnerDeliverTarget {
             Spawn { pos, .. } => *pos,
             PermanentUpgraderContainer { pos, .. } => *pos,
             TempStorage { pos, .. } => *pos,
-            TradeTransactionFee { pos, .. } => *pos,
         }
     }
 
@@ -1705,7 +1829,6 @@ impl CreepRunnerDeliverTarget {
             Spawn { requested, .. } => *requested,
             PermanentUpgraderContainer { requested, .. } => *requested,
             TempStorage { requested, .. } => *requested,
-            TradeTransactionFee { requested, .. } => *requested,
         }
     }
 }
@@ -1732,6 +1855,8 @@ pub struct TrySpawnOptions<'a> {
     pub target_energy_usage: u32,
     pub request_id: Option<UniqId>,
     pub preset_parts: Option<Vec<Part>>,
+    /// From `naming::NameRegistry::next_name`
+    pub creep_name: String,
 }
 
 impl<'a> fmt::Debug for TrySpawnOptions<'a> {
@@ -1769,9 +1894,34 @@ pub struct CalcSpawnBodyResult {
     pub body: Vec<creep::Part>,
 }
 
+/// What a spawn would look like, see `Spawnable::plan_spawn`
+#[derive(Debug, Clone)]
+pub struct SpawnPlan {
+    pub body: Vec<creep::Part>,
+    pub cost: u32,
+    /// Ticks the spawn is busy with it
+    pub spawn_ticks: u32,
+}
+
 pub trait Spawnable<O: fmt::Debug + Clone> {
     fn try_spawn(opts: &TrySpawnOptions, race_opts: &O) -> anyhow::Result<TrySpawnResult>;
     fn calc_spawn_body(opts: &TrySpawnOptions, race_opts: &O) -> anyhow::Result<CalcSpawnBodyResult>;
+
+    /// The body `try_spawn` would spawn with the options, without spawning anything
+    fn plan_spawn(opts: &TrySpawnOptions, race_opts: &O) -> anyhow::Result<SpawnPlan> {
+        let calc_result = match &opts.preset_parts {
+            Some(preset_parts) => CalcSpawnBodyResult {
+                amount: preset_parts.iter().map(|p| p.cost()).sum(),
+                body: preset_parts.to_owned(),
+            },
+            None => Self::calc_spawn_body(opts, race_opts)?,
+        };
+        Ok(SpawnPlan {
+            spawn_ticks: calc_result.body.get_spawn_time() as u32,
+            cost: calc_result.amount,
+            body: calc_result.body,
+        })
+    }
 }
 
 #[derive(Debug, Clone)]
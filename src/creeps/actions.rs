@@ -0,0 +1,76 @@
+//! Things citizens do on the side, without touching their task. Intents that don't get in the way
+//! of what the task did this tick go here.
+use screeps::{
+    find, Attackable, HasId, HasPosition, HasStore, Part, ResourceType, RoomObjectProperties,
+    Structure,
+};
+
+use crate::{
+    config,
+    intents::{self, Action, Actor, Priority},
+    rooms::repairs,
+};
+
+use super::{
+    races::{DynamicTasked, OokRace, RepresentsCreep},
+    tasks::OokCreepTask,
+};
+
+/// If the task may have issued a work intent this tick. Repair wins over build and harvest in the
+/// intent pipeline, so repairing on the side would cancel it.
+fn works_this_tick(citizen: &OokRace) -> bool {
+    match citizen {
+        OokRace::Worker(worker) => matches!(
+            worker.task(),
+            Some(OokCreepTask::Build(_)) | Some(OokCreepTask::FarmSource(_))
+        ),
+        _ => false,
+    }
+}
+
+fn is_decayed(hits: u32, hits_max: u32) -> bool {
+    (hits as f64) < hits_max as f64 * config::REPAIR_IN_PASSING_HITS_SHARE
+}
+
+/// Repairs a decayed road or container the citizen stands on or next to, if it carries energy and
+/// has WORK parts
+pub fn repair_in_passing(citizen: &OokRace) {
+    if !matches!(citizen, OokRace::Worker(_) | OokRace::Carrier(_)) || works_this_tick(citizen) {
+        return;
+    }
+    let creep = match citizen.creep() {
+        Ok(creep) => creep,
+        Err(_) => return,
+    };
+    if creep.spawning()
+        || creep.get_active_bodyparts(Part::Work) == 0
+        || creep.store_used_capacity(Some(ResourceType::Energy)) == 0
+    {
+        return;
+    }
+    let room = match creep.room() {
+        Some(room) => room,
+        None => return,
+    };
+    let target = room
+        .find(find::STRUCTURES)
+        .into_iter()
+        .filter(|s| s.pos().in_range_to(&creep.pos(), 1))
+        .filter(|s| match s {
+            Structure::Road(road) => is_decayed(road.hits(), road.hits_max()),
+            Structure::Container(container) => is_decayed(container.hits(), container.hits_max()),
+            _ => false,
+        })
+        .filter(|s| !repairs::is_claimed(s.id()))
+        .min_by_key(|s| s.as_attackable().map(|a| a.hits()).unwrap_or(0));
+    let power = creep.get_active_bodyparts(Part::Work) * repairs::CREEP_REPAIR_POWER;
+    if let Some(target) = target.filter(|target| repairs::claim(target.id(), power)) {
+        intents::submit(
+            Actor::Creep(creep.id()),
+            Action::Repair {
+                target: target.id(),
+            },
+            Priority::Passing,
+        );
+    }
+}
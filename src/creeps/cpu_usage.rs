@@ -0,0 +1,158 @@
+//! CPU every citizen uses for its job, measured around `do_job`. Each creep and each race and job
+//! kind keeps a rolling average, see `config::CPU_AVG_WEIGHT`. A creep using far more than the
+//! others of its kind for `config::CREEP_CPU_OUTLIER_TICKS` ticks in a row is most likely stuck in
+//! something expensive, like pathing again and again. It is logged and reset: its task and its
+//! paths are dropped, so it starts over with a fresh task.
+//!
+//! `report` logs the most expensive kinds.
+use std::{collections::HashMap, convert::TryFrom, sync::Mutex};
+
+use lazy_static::lazy_static;
+use log::{info, warn};
+use screeps::{game, Creep, SharedCreepProperties};
+
+use crate::config;
+
+use super::{
+    movement,
+    races::{OokRace, RepresentsCreep},
+};
+
+/// Creeps in the cache longer than this without a measurement are gone
+const STATS_MAX_AGE: u32 = 100;
+/// Kinds in a report
+const REPORT_KINDS: usize = 5;
+
+#[derive(Debug)]
+struct CreepCpu {
+    average: f64,
+    /// Ticks in a row over the outlier threshold
+    outlier_ticks: u32,
+    last_tick: u32,
+}
+
+#[derive(Debug, Default)]
+struct KindCpu {
+    average: f64,
+    runs: u32,
+}
+
+#[derive(Debug, Default)]
+struct CpuUsage {
+    creeps: HashMap<String, CreepCpu>,
+    kinds: HashMap<String, KindCpu>,
+}
+
+lazy_static! {
+    static ref USAGE: Mutex<CpuUsage> = Mutex::new(CpuUsage::default());
+}
+
+fn race_name(citizen: &OokRace) -> &'static str {
+    match citizen {
+        OokRace::Worker(_) => "worker",
+        OokRace::Carrier(_) => "carrier",
+        OokRace::Claimer(_) => "claimer",
+        OokRace::Blocker(_) => "blocker",
+        OokRace::Healer(_) => "healer",
+        OokRace::Dismantler(_) => "dismantler",
+        OokRace::Harasser(_) => "harasser",
+    }
+}
+
+/// Race and job of the citizen, creeps of the same kind should use about the same CPU
+fn kind_of(citizen: &OokRace) -> String {
+    format!("{}/{:?}", race_name(citizen), citizen.job().kind())
+}
+
+fn average(average: f64, cpu: f64) -> f64 {
+    average + (cpu - average) * config::CPU_AVG_WEIGHT
+}
+
+/// Records the CPU the citizen used for its job this tick, true if it has been an outlier for too
+/// long
+fn record(creep: &Creep, citizen: &OokRace, cpu: f64) -> bool {
+    let time = game::time();
+    let kind = kind_of(citizen);
+    let mut usage = USAGE.lock().unwrap();
+    let kind_stats = usage.kinds.entry(kind).or_default();
+    kind_stats.average = if kind_stats.runs == 0 {
+        cpu
+    } else {
+        average(kind_stats.average, cpu)
+    };
+    kind_stats.runs += 1;
+    let kind_average = kind_stats.average;
+
+    let creep_stats = usage
+        .creeps
+        .entry(creep.name())
+        .or_insert_with(|| CreepCpu {
+            average: cpu,
+            outlier_ticks: 0,
+            last_tick: time,
+        });
+    creep_stats.average = average(creep_stats.average, cpu);
+    creep_stats.last_tick = time;
+    let is_outlier = creep_stats.average >= config::CREEP_CPU_OUTLIER_MIN
+        && creep_stats.average >= kind_average * config::CREEP_CPU_OUTLIER_FACTOR;
+    creep_stats.outlier_ticks = if is_outlier {
+        creep_stats.outlier_ticks + 1
+    } else {
+        0
+    };
+    if creep_stats.outlier_ticks < config::CREEP_CPU_OUTLIER_TICKS {
+        return false;
+    }
+    warn!(
+        "{} uses {:.2} CPU per tick, its kind {:.2}, resetting it",
+        creep.name(),
+        creep_stats.average,
+        kind_average
+    );
+    creep_stats.outlier_ticks = 0;
+    true
+}
+
+/// Drops the task and the paths of the citizen, it gets a new task next time
+fn reset(creep: &Creep, citizen: &mut OokRace) {
+    movement::forget(creep);
+    creep.memory().del("_move");
+    match OokRace::try_from(creep) {
+        Ok(fresh) => *citizen = fresh,
+        Err(err) => warn!("Couldn't reset {}: {}", creep.name(), err),
+    }
+}
+
+/// Runs the job of the citizen, measuring its CPU and resetting it if it keeps using too much
+pub fn measure<F>(citizen: &mut OokRace, do_job: F)
+where
+    F: FnOnce(&mut OokRace),
+{
+    let started = game::cpu::get_used();
+    do_job(citizen);
+    let used = game::cpu::get_used() - started;
+    let creep = match citizen.creep() {
+        Ok(creep) => creep,
+        Err(_) => return,
+    };
+    if record(&creep, citizen, used) {
+        reset(&creep, citizen);
+    }
+}
+
+/// Logs the kinds using the most CPU per creep and drops creeps that are gone
+pub fn report() {
+    let time = game::time();
+    let mut usage = USAGE.lock().unwrap();
+    usage
+        .creeps
+        .retain(|_, stats| stats.last_tick + STATS_MAX_AGE >= time);
+    let mut kinds: Vec<(&String, &KindCpu)> = usage.kinds.iter().collect();
+    kinds.sort_by(|(_, a), (_, b)| b.average.partial_cmp(&a.average).unwrap());
+    for (kind, stats) in kinds.into_iter().take(REPORT_KINDS) {
+        info!(
+            "{:>6.3} CPU per creep, {} runs: {}",
+            stats.average, stats.runs, kind
+        );
+    }
+}
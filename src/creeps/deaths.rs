@@ -0,0 +1,206 @@
+//! Where my creeps and the energy in their bodies went. Every creep of mine that died last tick
+//! is found by its tombstone and booked with its cause:
+//!
+//! - aged out, it lived its full life
+//! - killed by a player, the one that did the most damage to it in its last tick
+//! - nuked, a nuke landed in its room
+//! - recycled, sent off by `retirement` or the dismantlers, a suicide without a spawn close by
+//!   included
+//! - unknown, it died early with no hostile in sight
+//!
+//! The deaths are added up per room the creep worked for, with the energy of their bodies and
+//! their jobs. Deaths away from that room are counted on their own, creeps dying far out waste
+//! their energy and the walk of their replacement. The numbers end up in `Memory.stats.deaths`.
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Mutex,
+};
+
+use lazy_static::lazy_static;
+use log::{info, warn};
+use screeps::{find, game, memory, Creep, HasId, ObjectId, Room, RoomName, SharedCreepProperties};
+use serde::{Deserialize, Serialize};
+
+use crate::{rooms::defense::threats, state::BWState};
+
+const MEM_STATS_DEATHS: &str = "stats.deaths";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeathCause {
+    AgedOut,
+    Killed(String),
+    Nuked,
+    Recycled,
+    /// Died early with nobody around to blame
+    Unknown,
+}
+
+impl DeathCause {
+    fn key(&self) -> String {
+        match self {
+            DeathCause::AgedOut => "aged_out".to_string(),
+            DeathCause::Killed(player) => format!("killed_by_{}", player),
+            DeathCause::Nuked => "nuked".to_string(),
+            DeathCause::Recycled => "recycled".to_string(),
+            DeathCause::Unknown => "unknown".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct RoomDeaths {
+    /// Deaths by cause
+    #[serde(default)]
+    count: HashMap<String, u32>,
+    /// Energy of the bodies by cause
+    #[serde(default)]
+    energy: HashMap<String, u32>,
+    /// Energy of the bodies by job
+    #[serde(default)]
+    energy_by_job: HashMap<String, u32>,
+    /// Deaths outside of the room
+    #[serde(default)]
+    away: u32,
+}
+
+js_serializable!(RoomDeaths);
+js_deserializable!(RoomDeaths);
+
+lazy_static! {
+    /// Loaded from `Memory.stats.deaths` on first use
+    static ref DEATHS: Mutex<Option<HashMap<String, RoomDeaths>>> = Mutex::new(None);
+    /// Names of the creeps sent to be recycled
+    static ref RECYCLED: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+    /// Tick the last nuke landed or is going to land in the room
+    static ref NUKES: Mutex<HashMap<RoomName, u32>> = Mutex::new(HashMap::new());
+}
+
+/// Books the creep as recycled if it dies within the next ticks, call it with the recycle or
+/// suicide
+pub fn note_recycled(creep: &Creep) {
+    RECYCLED.lock().unwrap().insert(creep.name());
+}
+
+fn track_nukes(room: &Room, time: u32) {
+    for nuke in room.find(find::NUKES) {
+        NUKES
+            .lock()
+            .unwrap()
+            .insert(room.name(), time + nuke.time_to_land());
+    }
+}
+
+fn cause_of(room: &Room, creep: &Creep, death_time: u32) -> DeathCause {
+    if RECYCLED.lock().unwrap().remove(&creep.name()) {
+        return DeathCause::Recycled;
+    }
+    let nuked = NUKES
+        .lock()
+        .unwrap()
+        .get(&room.name())
+        .map(|landed_at| (*landed_at as i64 - death_time as i64).abs() <= 1)
+        .unwrap_or(false);
+    if nuked {
+        return DeathCause::Nuked;
+    }
+    if creep.ticks_to_live().unwrap_or(0) <= 1 {
+        return DeathCause::AgedOut;
+    }
+    let killer = threats::killer_of(room, &creep.id().to_string()).or_else(|| {
+        room.find(find::HOSTILE_CREEPS)
+            .first()
+            .map(|hostile| hostile.owner_name())
+    });
+    match killer {
+        Some(player) => DeathCause::Killed(player),
+        None => DeathCause::Unknown,
+    }
+}
+
+fn load() -> HashMap<String, RoomDeaths> {
+    let deaths = match memory::root()
+        .dict("stats")
+        .ok()
+        .flatten()
+        .and_then(|stats| stats.dict("deaths").ok().flatten())
+    {
+        Some(deaths) => deaths,
+        None => return HashMap::new(),
+    };
+    deaths
+        .keys()
+        .into_iter()
+        .filter_map(|room| match deaths.get::<RoomDeaths>(&room) {
+            Ok(Some(room_deaths)) => Some((room, room_deaths)),
+            Ok(None) => None,
+            Err(err) => {
+                warn!("Could not load the deaths of {}: {}", room, err);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Books the creeps of mine that died last tick, call it each tick before the citizens of the last
+/// tick are replaced
+pub fn track(state: &BWState) {
+    let time = game::time();
+    let mut deaths = vec![];
+    for room in game::rooms::values() {
+        track_nukes(&room, time);
+        for tombstone in room.find(find::TOMBSTONES) {
+            if tombstone.death_time() + 1 != time {
+                continue;
+            }
+            let creep = tombstone.creep();
+            if !creep.my() {
+                continue;
+            }
+            let cause = cause_of(&room, &creep, tombstone.death_time());
+            let energy: u32 = creep.body().iter().map(|b| b.part.cost()).sum();
+            let id: ObjectId<Creep> = creep.id();
+            let job = state.citizens.get(&id).map(|citizen| citizen.job());
+            let home = job
+                .as_ref()
+                .map(|job| job.target_room())
+                .unwrap_or_else(|| room.name());
+            let job_name = job
+                .map(|job| format!("{:?}", job.kind()))
+                .unwrap_or_else(|| "none".to_string());
+            info!(
+                "{} ({}, {} energy) died in {}: {:?}",
+                creep.name(),
+                job_name,
+                energy,
+                room.name(),
+                cause
+            );
+            deaths.push((home, room.name(), cause, energy, job_name));
+        }
+    }
+    NUKES
+        .lock()
+        .unwrap()
+        .retain(|_, landed_at| *landed_at + 1 >= time);
+    if deaths.is_empty() {
+        return;
+    }
+    let mut stats = DEATHS.lock().unwrap();
+    let stats = stats.get_or_insert_with(load);
+    for (home, died_in, cause, energy, job_name) in deaths {
+        let room_deaths = stats.entry(home.to_string()).or_default();
+        *room_deaths.count.entry(cause.key()).or_insert(0) += 1;
+        *room_deaths.energy.entry(cause.key()).or_insert(0) += energy;
+        *room_deaths.energy_by_job.entry(job_name).or_insert(0) += energy;
+        if died_in != home {
+            room_deaths.away += 1;
+        }
+    }
+}
+
+/// Writes the deaths to `Memory.stats.deaths`
+pub fn persist_stats() {
+    if let Some(stats) = DEATHS.lock().unwrap().as_ref() {
+        memory::root().path_set(MEM_STATS_DEATHS, stats.clone());
+    }
+}
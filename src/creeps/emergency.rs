@@ -0,0 +1,56 @@
+//! Controllers about to downgrade. Upgraders can be dead, stuck or just not spawned in time, and a
+//! lost level costs far more than whatever the creeps close by were doing. So every citizen with
+//! energy and WORK parts near such a controller puts its energy in first, before its task runs.
+use log::debug;
+use screeps::{
+    HasPosition, HasStore, OwnedStructureProperties, Part, ResourceType, ReturnCode,
+    RoomObjectProperties, SharedCreepProperties,
+};
+
+use crate::config;
+
+use super::{
+    movement::move_creep_to,
+    races::{OokRace, RepresentsCreep},
+};
+
+/// Creeps upgrade from this far away
+const UPGRADE_RANGE: u32 = 3;
+
+/// Has the citizen upgrade the controller of its room instead of doing its task, if it is about
+/// to downgrade and the citizen can help. True if the citizen got diverted.
+pub fn upgrade_if_downgrading(citizen: &OokRace) -> bool {
+    let creep = match citizen.creep() {
+        Ok(creep) => creep,
+        Err(_) => return false,
+    };
+    if creep.spawning()
+        || creep.get_active_bodyparts(Part::Work) == 0
+        || creep.store_used_capacity(Some(ResourceType::Energy)) == 0
+    {
+        return false;
+    }
+    let controller = match creep.room().and_then(|room| room.controller()) {
+        Some(controller) if controller.my() => controller,
+        _ => return false,
+    };
+    if controller.ticks_to_downgrade() > config::EMERGENCY_UPGRADE_TICKS
+        || !creep
+            .pos()
+            .in_range_to(&controller, config::EMERGENCY_UPGRADE_DISTANCE)
+    {
+        return false;
+    }
+    if creep.pos().in_range_to(&controller, UPGRADE_RANGE) {
+        match creep.upgrade_controller(&controller) {
+            ReturnCode::Ok => debug!("{} upgrades the downgrading controller", creep.name()),
+            code => {
+                debug!("{} couldn't upgrade: {:?}", creep.name(), code);
+                return false;
+            }
+        }
+    } else {
+        move_creep_to(&creep, &controller.pos(), UPGRADE_RANGE);
+    }
+    true
+}
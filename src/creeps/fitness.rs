@@ -0,0 +1,72 @@
+//! How well the body of a creep fits a job. Some jobs can't be done at all without certain parts,
+//! a claimer can't haul and a farmer without CARRY can't fill extensions. Creeps only get jobs
+//! they fit, and among several creeps for a job the best fitting ones go first, see
+//! `rooms::labor`, `retirement` and `recovery`.
+//!
+//! The fitness is 0 without one of the required parts, otherwise the share of the body that is of
+//! use for the job. Broken parts don't count.
+use screeps::{Creep, Part};
+
+use super::jobs::OokCreepJob;
+
+/// Parts the job can't be done without
+fn required_parts(job: &OokCreepJob) -> &'static [Part] {
+    match job {
+        OokCreepJob::UpgradeController { .. }
+        | OokCreepJob::MaintainStructures { .. }
+        | OokCreepJob::BootstrapRoom { .. } => &[Part::Work, Part::Carry],
+        OokCreepJob::RoomLogistics { .. }
+        | OokCreepJob::LogisticsExtensionRoom { .. }
+        | OokCreepJob::Salvage(_) => &[Part::Carry],
+        OokCreepJob::FarmSource(_)
+        | OokCreepJob::FarmExtensionRoom { .. }
+        | OokCreepJob::Dismantle(_) => &[Part::Work],
+        OokCreepJob::ClaimRoom { .. }
+        | OokCreepJob::ReserveRoom { .. }
+        | OokCreepJob::CrossPortal(_) => &[Part::Claim],
+        OokCreepJob::DefendRoom { .. } => &[Part::Attack],
+        OokCreepJob::HealCitizens { .. } => &[Part::Heal],
+        OokCreepJob::PatrolRemotes { .. } => &[Part::RangedAttack],
+        OokCreepJob::ScoutRoom { .. } => &[Part::Move],
+    }
+}
+
+/// Parts of use for the job besides the required ones
+fn helpful_parts(job: &OokCreepJob) -> &'static [Part] {
+    match job {
+        OokCreepJob::DefendRoom { .. } | OokCreepJob::Dismantle(_) => &[Part::Move, Part::Tough],
+        OokCreepJob::PatrolRemotes { .. } => &[Part::Move, Part::Heal],
+        OokCreepJob::ScoutRoom { .. } => &[],
+        _ => &[Part::Move],
+    }
+}
+
+/// The parts of the creep that still work
+pub fn active_parts(creep: &Creep) -> Vec<Part> {
+    creep
+        .body()
+        .into_iter()
+        .filter(|b| b.hits > 0)
+        .map(|b| b.part)
+        .collect()
+}
+
+/// Fitness of the body for the job, from 0 for a body that can't do it to 1 for a body that has
+/// nothing else
+pub fn fitness(parts: &[Part], job: &OokCreepJob) -> f64 {
+    let required = required_parts(job);
+    if parts.is_empty() || required.iter().any(|part| !parts.contains(part)) {
+        return 0.;
+    }
+    let helpful = helpful_parts(job);
+    let useful = parts
+        .iter()
+        .filter(|part| required.contains(part) || helpful.contains(part))
+        .count();
+    useful as f64 / parts.len() as f64
+}
+
+/// If the body can do the job at all
+pub fn fits(parts: &[Part], job: &OokCreepJob) -> bool {
+    fitness(parts, job) > 0.
+}
@@ -1,5 +1,8 @@
 use log::warn;
-use screeps::{Creep, HasPosition, HasStore, ResourceType, ReturnCode, RoomObjectProperties, SharedCreepProperties, find};
+use screeps::{find, Creep, HasPosition, HasStore, ResourceType, ReturnCode, RoomObjectProperties, SharedCreepProperties};
+
+use super::movement::move_creep_to;
+use super::speech::{self, Verbosity};
 
 pub fn run_harvester(creep: Creep) {
     if creep.memory().bool("harvesting") {
@@ -7,34 +10,56 @@ pub fn run_harvester(creep: Creep) {
             creep.memory().set("harvesting", false);
         }
     } else {
-        creep.say("ᕕ( ᐛ )ᕗ", true);
+        speech::say(&creep, "ᕕ( ᐛ )ᕗ", Verbosity::Chatter);
         if creep.store_used_capacity(None) == 0 {
             creep.memory().set("harvesting", true);
         }
     }
 
     if creep.memory().bool("harvesting") {
-        let source = &creep
+        let sources = creep
             .room()
             .expect("room is not visible to you")
-            .find(find::SOURCES)[0];
+            .find(find::SOURCES);
+        if sources.is_empty() {
+            return;
+        }
+        // Spread the creeps over the sources, the name stays the same for its whole life
+        let name_sum: usize = creep.name().bytes().map(|b| b as usize).sum();
+        let source = &sources[name_sum % sources.len()];
         if creep.pos().is_near_to(source) {
             let r = creep.harvest(source);
             if r != ReturnCode::Ok {
                 warn!("couldn't harvest: {:?}", r);
             }
         } else {
-            creep.move_to(source);
+            move_creep_to(&creep, &source.pos(), 1);
         }
     } else {
-        if let Some(c) = creep
+        if let Some(spawn) = creep
+            .room()
+            .expect("room is not visible to you")
+            .find(find::MY_SPAWNS)
+            .into_iter()
+            .find(|s| s.store_free_capacity(Some(ResourceType::Energy)) > 0)
+        {
+            // Without a full spawn there is no next creep, so it goes first
+            if creep.pos().is_near_to(&spawn) {
+                let r = creep.transfer_all(&spawn, ResourceType::Energy);
+                if r != ReturnCode::Ok {
+                    warn!("couldn't transfer: {:?}", r);
+                }
+            } else {
+                move_creep_to(&creep, &spawn.pos(), 1);
+            }
+        } else if let Some(c) = creep
             .room()
             .expect("room is not visible to you")
             .controller()
         {
             let r = creep.upgrade_controller(&c);
             if r == ReturnCode::NotInRange {
-                creep.move_to(&c);
+                move_creep_to(&creep, &c.pos(), 3);
             } else if r != ReturnCode::Ok {
                 warn!("couldn't upgrade: {:?}", r);
             }
@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 
 use anyhow::{anyhow, bail, Context};
-use screeps::{memory::MemoryReference, traits::TryFrom, ObjectId, RoomName, Source};
+use screeps::{memory::MemoryReference, traits::TryFrom, ObjectId, RoomName, Source, Structure};
 use serde::{Deserialize, Serialize};
 use stdweb::JsSerialize;
 
@@ -22,6 +22,14 @@ pub enum OokCreepJobKind {
     MaintainStructures = 6,
     ClaimRoom = 7,
     BootstrapRoom = 8,
+    DefendRoom = 9,
+    HealCitizens = 10,
+    Dismantle = 11,
+    PatrolRemotes = 12,
+    CrossPortal = 13,
+    Salvage = 14,
+    ReserveRoom = 15,
+    ScoutRoom = 16,
 }
 
 impl TryFrom<i32> for OokCreepJobKind {
@@ -38,6 +46,14 @@ impl TryFrom<i32> for OokCreepJobKind {
             6 => MaintainStructures,
             7 => ClaimRoom,
             8 => BootstrapRoom,
+            9 => DefendRoom,
+            10 => HealCitizens,
+            11 => Dismantle,
+            12 => PatrolRemotes,
+            13 => CrossPortal,
+            14 => Salvage,
+            15 => ReserveRoom,
+            16 => ScoutRoom,
             _ => bail!("Unknown creep job kind {}", i),
         })
     }
@@ -52,6 +68,41 @@ pub struct FarmSource {
 js_serializable!(FarmSource);
 js_deserializable!(FarmSource);
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Dismantle {
+    /// Where the recovered energy goes
+    pub home_room: RoomName,
+    pub target_room: RoomName,
+    pub targets: Vec<ObjectId<Structure>>,
+}
+
+js_serializable!(Dismantle);
+js_deserializable!(Dismantle);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrossPortal {
+    pub portal_room: RoomName,
+    pub target_shard: String,
+    /// Room to claim on `target_shard`
+    pub target_room: RoomName,
+}
+
+js_serializable!(CrossPortal);
+js_deserializable!(CrossPortal);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Salvage {
+    /// Spawned the salvager, its storage takes the loot if no terminal is around
+    pub home_room: RoomName,
+    pub target_room: RoomName,
+    /// Nothing was left to loot, the salvager is on its way to be recycled
+    #[serde(default)]
+    pub done: bool,
+}
+
+js_serializable!(Salvage);
+js_deserializable!(Salvage);
+
 /// Identifies which citizen takes which tasks for himself.
 ///
 /// Higher view on the things a creep does.
@@ -85,6 +136,32 @@ pub enum OokCreepJob {
     BootstrapRoom {
         target_room: RoomName,
     },
+    /// Hold the ramparts while the room is raided
+    DefendRoom {
+        target_room: RoomName,
+    },
+    /// Keep the citizens in the room alive, mostly the defenders during raids
+    HealCitizens {
+        target_room: RoomName,
+    },
+    /// Tear down structures and bring their energy home
+    Dismantle(Dismantle),
+    /// Keep invaders out of the remotes of the base `target_room`
+    PatrolRemotes {
+        target_room: RoomName,
+    },
+    /// Walk through a portal to claim a room on another shard
+    CrossPortal(CrossPortal),
+    /// Loot the ruins and tombstones of a cleared room
+    Salvage(Salvage),
+    /// Keep the controller of a remote reserved
+    ReserveRoom {
+        target_room: RoomName,
+    },
+    /// Stand in the room so it is visible, see `rooms::visibility`
+    ScoutRoom {
+        target_room: RoomName,
+    },
 }
 
 impl OokCreepJob {
@@ -98,6 +175,14 @@ impl OokCreepJob {
             OokCreepJob::MaintainStructures { .. } => OokCreepJobKind::MaintainStructures,
             OokCreepJob::ClaimRoom { .. } => OokCreepJobKind::ClaimRoom,
             OokCreepJob::BootstrapRoom { .. } => OokCreepJobKind::BootstrapRoom,
+            OokCreepJob::DefendRoom { .. } => OokCreepJobKind::DefendRoom,
+            OokCreepJob::HealCitizens { .. } => OokCreepJobKind::HealCitizens,
+            OokCreepJob::Dismantle(Dismantle { .. }) => OokCreepJobKind::Dismantle,
+            OokCreepJob::PatrolRemotes { .. } => OokCreepJobKind::PatrolRemotes,
+            OokCreepJob::CrossPortal(CrossPortal { .. }) => OokCreepJobKind::CrossPortal,
+            OokCreepJob::Salvage(Salvage { .. }) => OokCreepJobKind::Salvage,
+            OokCreepJob::ReserveRoom { .. } => OokCreepJobKind::ReserveRoom,
+            OokCreepJob::ScoutRoom { .. } => OokCreepJobKind::ScoutRoom,
         }
     }
 
@@ -111,6 +196,14 @@ impl OokCreepJob {
             OokCreepJob::MaintainStructures { target_room, .. } => target_room,
             OokCreepJob::ClaimRoom { target_room, .. } => target_room,
             OokCreepJob::BootstrapRoom { target_room, .. } => target_room,
+            OokCreepJob::DefendRoom { target_room, .. } => target_room,
+            OokCreepJob::HealCitizens { target_room, .. } => target_room,
+            OokCreepJob::Dismantle(Dismantle { target_room, .. }) => target_room,
+            OokCreepJob::PatrolRemotes { target_room, .. } => target_room,
+            OokCreepJob::CrossPortal(CrossPortal { portal_room, .. }) => portal_room,
+            OokCreepJob::Salvage(Salvage { target_room, .. }) => target_room,
+            OokCreepJob::ReserveRoom { target_room, .. } => target_room,
+            OokCreepJob::ScoutRoom { target_room, .. } => target_room,
         }
         .to_owned()
     }
@@ -154,6 +247,30 @@ impl<'a> StorableJob<'a, Self> for OokCreepJob {
             OokCreepJob::BootstrapRoom { target_room } => {
                 map.insert(MEM_TARGET_ROOM.to_string(), Box::new(target_room));
             }
+            OokCreepJob::DefendRoom { target_room } => {
+                map.insert(MEM_TARGET_ROOM.to_string(), Box::new(target_room));
+            }
+            OokCreepJob::HealCitizens { target_room } => {
+                map.insert(MEM_TARGET_ROOM.to_string(), Box::new(target_room));
+            }
+            OokCreepJob::Dismantle(job_data) => {
+                map.insert(MEM_JOB_DATA.to_string(), Box::new(job_data));
+            }
+            OokCreepJob::PatrolRemotes { target_room } => {
+                map.insert(MEM_TARGET_ROOM.to_string(), Box::new(target_room));
+            }
+            OokCreepJob::CrossPortal(job_data) => {
+                map.insert(MEM_JOB_DATA.to_string(), Box::new(job_data));
+            }
+            OokCreepJob::Salvage(job_data) => {
+                map.insert(MEM_JOB_DATA.to_string(), Box::new(job_data));
+            }
+            OokCreepJob::ReserveRoom { target_room } => {
+                map.insert(MEM_TARGET_ROOM.to_string(), Box::new(target_room));
+            }
+            OokCreepJob::ScoutRoom { target_room } => {
+                map.insert(MEM_TARGET_ROOM.to_string(), Box::new(target_room));
+            }
         }
         map
     }
@@ -254,6 +371,74 @@ impl<'a> StorableJob<'a, Self> for OokCreepJob {
                 .context("loading mem target_room")?;
                 Some(OokCreepJob::BootstrapRoom { target_room })
             }
+            OokCreepJobKind::DefendRoom => {
+                let target_room = RoomName::new(
+                    &memory
+                        .string(MEM_TARGET_ROOM)
+                        .context("loading mem target_room")?
+                        .ok_or(anyhow!("mem target_room missing"))?,
+                )
+                .context("loading mem target_room")?;
+                Some(OokCreepJob::DefendRoom { target_room })
+            }
+            OokCreepJobKind::HealCitizens => {
+                let target_room = RoomName::new(
+                    &memory
+                        .string(MEM_TARGET_ROOM)
+                        .context("loading mem target_room")?
+                        .ok_or(anyhow!("mem target_room missing"))?,
+                )
+                .context("loading mem target_room")?;
+                Some(OokCreepJob::HealCitizens { target_room })
+            }
+            OokCreepJobKind::Dismantle => {
+                let job_data: Dismantle = memory
+                    .get(MEM_JOB_DATA)
+                    .err_or_none("unable to get job data for dismantle")?;
+                Some(OokCreepJob::Dismantle(job_data))
+            }
+            OokCreepJobKind::PatrolRemotes => {
+                let target_room = RoomName::new(
+                    &memory
+                        .string(MEM_TARGET_ROOM)
+                        .context("loading mem target_room")?
+                        .ok_or(anyhow!("mem target_room missing"))?,
+                )
+                .context("loading mem target_room")?;
+                Some(OokCreepJob::PatrolRemotes { target_room })
+            }
+            OokCreepJobKind::CrossPortal => {
+                let job_data: CrossPortal = memory
+                    .get(MEM_JOB_DATA)
+                    .err_or_none("unable to get job data for cross portal")?;
+                Some(OokCreepJob::CrossPortal(job_data))
+            }
+            OokCreepJobKind::Salvage => {
+                let job_data: Salvage = memory
+                    .get(MEM_JOB_DATA)
+                    .err_or_none("unable to get job data for salvage")?;
+                Some(OokCreepJob::Salvage(job_data))
+            }
+            OokCreepJobKind::ReserveRoom => {
+                let target_room = RoomName::new(
+                    &memory
+                        .string(MEM_TARGET_ROOM)
+                        .context("loading mem target_room")?
+                        .ok_or(anyhow!("mem target_room missing"))?,
+                )
+                .context("loading mem target_room")?;
+                Some(OokCreepJob::ReserveRoom { target_room })
+            }
+            OokCreepJobKind::ScoutRoom => {
+                let target_room = RoomName::new(
+                    &memory
+                        .string(MEM_TARGET_ROOM)
+                        .context("loading mem target_room")?
+                        .ok_or(anyhow!("mem target_room missing"))?,
+                )
+                .context("loading mem target_room")?;
+                Some(OokCreepJob::ScoutRoom { target_room })
+            }
         })
     }
 }
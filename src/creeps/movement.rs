@@ -0,0 +1,295 @@
+//! Every creep moves through `move_creep_to`, which notices when a creep didn't get anywhere and
+//! tries to get it unstuck instead of grinding against the same tile forever. Creeps on a highway
+//! to their target follow it, see `rooms::highways`. Paths searched with `cost_matrix::search` are
+//! kept per creep and walked until the creep gets stuck or a cost matrix changes.
+use std::{collections::HashMap, sync::Mutex};
+
+use lazy_static::lazy_static;
+use log::{info, warn};
+use screeps::{
+    find, game, look, memory, Creep, Direction, HasPosition, MoveToOptions,
+    OwnedStructureProperties, Part, Position, ReturnCode, Room, RoomObjectProperties,
+    SharedCreepProperties, Structure, Terrain,
+};
+
+use crate::{
+    rooms::{cost_matrix, highways, spawn_area},
+    utils::DIRECTIONS,
+};
+
+/// From this amount of own creeps in a room on, `move_to` defaults are bumping into each other
+const CONGESTED_ROOM_CREEPS: usize = 12;
+/// Stuck for this many ticks: path again, this time around other creeps
+const STUCK_REPATH_TICKS: u32 = 2;
+/// Stuck for this many ticks: repathing didn't help, step to any free tile
+const STUCK_SIDESTEP_TICKS: u32 = 5;
+/// Tracks of creeps that didn't move for this long are dropped (creep died or arrived)
+const TRACK_MAX_AGE: u32 = 100;
+/// Jams with at least this many stuck creeps are logged
+const JAM_CHRONIC_COUNT: u32 = 20;
+/// Amount of jam locations written to `Memory.stats.jams`
+const JAM_STATS_LIMIT: usize = 10;
+
+const MEM_STATS_JAMS: &str = "stats.jams";
+
+#[derive(Debug, Clone)]
+struct CachedPath {
+    target: Position,
+    range: u32,
+    path: Vec<Position>,
+    /// `cost_matrix::version` the path was searched with
+    matrices: u32,
+}
+
+#[derive(Debug, Clone)]
+struct MoveTrack {
+    last_pos: Position,
+    target: Position,
+    last_tick: u32,
+    stuck_ticks: u32,
+}
+
+lazy_static! {
+    static ref TRACKS: Mutex<HashMap<String, MoveTrack>> = Mutex::new(HashMap::new());
+    static ref JAMS: Mutex<HashMap<Position, u32>> = Mutex::new(HashMap::new());
+    static ref PATHS: Mutex<HashMap<String, CachedPath>> = Mutex::new(HashMap::new());
+}
+
+/// Moves the creep until it is in `range` of `target`
+///
+/// Creeps on a highway to the target follow it. Short trips in quiet rooms use plain `move_to`,
+/// everything else goes through `cost_matrix::search`, around dangerous rooms for creeps that
+/// can't fight. A creep that hasn't moved for `STUCK_REPATH_TICKS` gets a fresh path
+/// avoiding creeps, after `STUCK_SIDESTEP_TICKS` it steps aside.
+pub fn move_creep_to(creep: &Creep, target: &Position, range: u32) -> ReturnCode {
+    let pos = creep.pos();
+    if pos.in_range_to(target, range) {
+        return ReturnCode::Ok;
+    }
+    let stuck_ticks = track(creep, pos, *target);
+    if stuck_ticks == STUCK_REPATH_TICKS {
+        *JAMS.lock().unwrap().entry(pos).or_insert(0) += 1;
+    }
+    if stuck_ticks >= STUCK_SIDESTEP_TICKS {
+        if let Some(code) = side_step(creep, &[]) {
+            return code;
+        }
+    }
+    if stuck_ticks >= STUCK_REPATH_TICKS {
+        PATHS.lock().unwrap().remove(&creep.name());
+        return creep.move_to_with_options(
+            target,
+            MoveToOptions::new()
+                .range(range)
+                .ignore_creeps(false)
+                .reuse_path(0),
+        );
+    }
+    // Highways are only left when blocked, the live path then goes around
+    if stuck_ticks == 0 {
+        if let Some(direction) = highways::next_step(&pos, target) {
+            return creep.move_direction(direction);
+        }
+    }
+
+    let same_room = pos.room_name() == target.room_name();
+    let congested = creep.room().map(|r| is_congested(&r)).unwrap_or(false);
+    if same_room && !congested {
+        return creep.move_to_with_options(target, MoveToOptions::new().range(range));
+    }
+    if let Some(direction) = cached_step(creep, pos, *target, range) {
+        return creep.move_direction(direction);
+    }
+    let results = cost_matrix::search(&pos, target, range, !can_fight(creep));
+    if results.incomplete {
+        warn!(
+            "Incomplete path for {} from {} to {}",
+            creep.name(),
+            pos,
+            target
+        );
+    }
+    let code = creep.move_by_path_search_result(&results);
+    PATHS.lock().unwrap().insert(
+        creep.name(),
+        CachedPath {
+            target: *target,
+            range,
+            path: results.path(),
+            matrices: cost_matrix::version(),
+        },
+    );
+    code
+}
+
+/// Next step on the path kept for the creep, `None` if there is none to the target or it is off it
+fn cached_step(creep: &Creep, pos: Position, target: Position, range: u32) -> Option<Direction> {
+    let paths = PATHS.lock().unwrap();
+    let cached = paths.get(&creep.name())?;
+    if cached.target != target || cached.range != range || cached.matrices != cost_matrix::version()
+    {
+        return None;
+    }
+    // The path doesn't contain the tile it was searched from
+    let next = match cached.path.iter().position(|step| *step == pos) {
+        Some(index) => cached.path.get(index + 1)?,
+        None => cached.path.first().filter(|step| step.is_near_to(&pos))?,
+    };
+    pos.get_direction_to(next)
+}
+
+/// Creeps that can fight path through dangerous rooms, see `rooms::intel`
+fn can_fight(creep: &Creep) -> bool {
+    creep.get_active_bodyparts(Part::Attack) > 0
+        || creep.get_active_bodyparts(Part::RangedAttack) > 0
+}
+
+/// Forgets how long the creep has been stuck, it starts over
+pub fn forget(creep: &Creep) {
+    TRACKS.lock().unwrap().remove(&creep.name());
+    PATHS.lock().unwrap().remove(&creep.name());
+}
+
+fn is_congested(room: &Room) -> bool {
+    room.find(find::MY_CREEPS).len() >= CONGESTED_ROOM_CREEPS
+}
+
+/// Updates the track of the creep and returns for how many ticks it didn't move
+fn track(creep: &Creep, pos: Position, target: Position) -> u32 {
+    let time = game::time();
+    let mut tracks = TRACKS.lock().unwrap();
+    let stuck_ticks = match tracks.get(&creep.name()) {
+        // Fatigued creeps are waiting, not stuck
+        Some(last)
+            if last.last_pos == pos
+                && last.target == target
+                && last.last_tick + 1 == time
+                && creep.fatigue() == 0 =>
+        {
+            last.stuck_ticks + 1
+        }
+        _ => 0,
+    };
+    tracks.insert(
+        creep.name(),
+        MoveTrack {
+            last_pos: pos,
+            target,
+            last_tick: time,
+            stuck_ticks,
+        },
+    );
+    stuck_ticks
+}
+
+/// Moves to the first free neighbouring tile not in `avoid`, starting at a different direction
+/// each tick
+fn side_step(creep: &Creep, avoid: &[Position]) -> Option<ReturnCode> {
+    let pos = creep.pos();
+    let room = creep.room()?;
+    let terrain = game::map::get_room_terrain(pos.room_name());
+    let offset = game::time() as usize;
+    for i in 0..DIRECTIONS.len() {
+        let (direction, dx, dy) = DIRECTIONS[(offset + i) % DIRECTIONS.len()];
+        let x = pos.x() as i32 + dx;
+        let y = pos.y() as i32 + dy;
+        // Don't step out of the room, that's not a side step
+        if x < 1 || x > 48 || y < 1 || y > 48 {
+            continue;
+        }
+        if terrain.get(x as u32, y as u32) == Terrain::Wall {
+            continue;
+        }
+        let step_pos = Position::new(x as u32, y as u32, pos.room_name());
+        if avoid.contains(&step_pos) {
+            continue;
+        }
+        if !room.look_for_at(look::CREEPS, &step_pos).is_empty() {
+            continue;
+        }
+        let blocked = room
+            .look_for_at(look::STRUCTURES, &step_pos)
+            .iter()
+            .any(|s| match s {
+                Structure::Road(_) | Structure::Container(_) => false,
+                Structure::Rampart(rampart) => !(rampart.my() || rampart.is_public()),
+                _ => true,
+            });
+        if blocked {
+            continue;
+        }
+        return Some(creep.move_direction(direction));
+    }
+    None
+}
+
+/// Steps idle creeps off the spawn exits if a spawn is about to finish a creep
+///
+/// Has to run after all creeps moved, a creep that moved this tick is not idle.
+pub fn clear_spawn_exits() {
+    let time = game::time();
+    for spawn in game::spawns::values() {
+        let about_to_finish = spawn
+            .spawning()
+            .map(|spawning| spawning.remaining_time() <= 1)
+            .unwrap_or(false);
+        if !about_to_finish {
+            continue;
+        }
+        let room = match spawn.room() {
+            Some(room) => room,
+            None => continue,
+        };
+        let exits = spawn_area::room_spawn_exits(&room);
+        for exit in &exits {
+            for creep in room.look_for_at(look::CREEPS, exit) {
+                if !creep.my() || creep.spawning() {
+                    continue;
+                }
+                let moved = TRACKS
+                    .lock()
+                    .unwrap()
+                    .get(&creep.name())
+                    .map(|track| track.last_tick == time)
+                    .unwrap_or(false);
+                if !moved {
+                    side_step(&creep, &exits);
+                }
+            }
+        }
+    }
+}
+
+/// Writes the worst jam locations to `Memory.stats.jams` and forgets about old tracks and their
+/// paths
+///
+/// Jam counts are halved each time, so only locations which keep jamming stay on top.
+pub fn persist_jam_stats() {
+    let time = game::time();
+    let mut tracks = TRACKS.lock().unwrap();
+    tracks.retain(|_name, track| track.last_tick + TRACK_MAX_AGE >= time);
+    PATHS
+        .lock()
+        .unwrap()
+        .retain(|name, _path| tracks.contains_key(name));
+    drop(tracks);
+
+    let mut jams = JAMS.lock().unwrap();
+    let mut worst: Vec<(Position, u32)> = jams.iter().map(|(pos, count)| (*pos, *count)).collect();
+    worst.sort_by_key(|(_pos, count)| std::cmp::Reverse(*count));
+    worst.truncate(JAM_STATS_LIMIT);
+
+    let mut stats = HashMap::new();
+    for (pos, count) in worst {
+        if count >= JAM_CHRONIC_COUNT {
+            info!("Chronic jam at {}: {} stuck creeps", pos, count);
+        }
+        stats.insert(format!("{}:{},{}", pos.room_name(), pos.x(), pos.y()), count);
+    }
+    memory::root().path_set(MEM_STATS_JAMS, stats);
+
+    jams.retain(|_pos, count| {
+        *count /= 2;
+        *count > 0
+    });
+}
@@ -0,0 +1,154 @@
+//! Creep names. Every name starts with a short prefix telling the race, followed by a token from a
+//! counter persisted in Memory, so names stay short and never repeat, not even over global resets.
+//! `config::CREEP_NAMING` picks if the token is the counter in base36 or a word.
+//!
+//! The prefix is what's left to go by when a creep lost its memory, see `race_of_name`.
+use log::warn;
+use screeps::{game, ReturnCode};
+
+use crate::{
+    config::{self, CreepNaming},
+    constants::MEM_CREEP_NAMES,
+};
+
+use super::{races::OokRaceKind, TrySpawnResult, TrySpawnResultData};
+
+const WORDS: &[&str] = &[
+    "ant", "bat", "bee", "boar", "carp", "cat", "clam", "cod", "crab", "crow", "deer", "dog",
+    "dove", "duck", "eel", "elk", "emu", "fox", "frog", "gnu", "goat", "gull", "hare", "hawk",
+    "ibis", "jay", "kiwi", "koi", "lark", "lion", "lynx", "mink", "mole", "moth", "mule", "newt",
+    "orca", "owl", "ox", "puma", "ram", "rat", "rook", "seal", "slug", "swan", "toad", "tuna",
+    "wasp", "wolf", "wren", "yak",
+];
+
+/// The prefix of the race in creep names
+pub fn race_prefix(race: OokRaceKind) -> &'static str {
+    match race {
+        OokRaceKind::Worker => "w",
+        OokRaceKind::StaticWorker => "s",
+        OokRaceKind::Carrier => "c",
+        OokRaceKind::Attacker => "a",
+        OokRaceKind::CloseCombatDefender => "d",
+        OokRaceKind::Claimer => "k",
+        OokRaceKind::Blocker => "b",
+        OokRaceKind::Healer => "h",
+        OokRaceKind::Dismantler => "x",
+        OokRaceKind::Harasser => "r",
+    }
+}
+
+const RACES: &[OokRaceKind] = &[
+    OokRaceKind::Worker,
+    OokRaceKind::StaticWorker,
+    OokRaceKind::Carrier,
+    OokRaceKind::Attacker,
+    OokRaceKind::CloseCombatDefender,
+    OokRaceKind::Claimer,
+    OokRaceKind::Blocker,
+    OokRaceKind::Healer,
+    OokRaceKind::Dismantler,
+    OokRaceKind::Harasser,
+];
+
+/// The race a creep was spawned as, going by its name only
+///
+/// Knows the names of before the registry too, those start with the whole race name.
+pub fn race_of_name(name: &str) -> Option<OokRaceKind> {
+    let prefix = name.split('-').next()?;
+    RACES
+        .iter()
+        .find(|race| race_prefix(**race) == prefix)
+        .or_else(|| {
+            RACES
+                .iter()
+                .find(|race| name.starts_with(&format!("{}-", race)))
+        })
+        .copied()
+}
+
+fn to_base36(mut n: u64) -> String {
+    let mut digits = vec![];
+    loop {
+        digits.push(std::char::from_digit((n % 36) as u32, 36).unwrap_or('0'));
+        n /= 36;
+        if n == 0 {
+            break;
+        }
+    }
+    digits.iter().rev().collect()
+}
+
+fn token(n: u64) -> String {
+    match config::CREEP_NAMING {
+        CreepNaming::Counter => to_base36(n),
+        CreepNaming::Words => {
+            let word = WORDS[(n % WORDS.len() as u64) as usize];
+            let round = n / WORDS.len() as u64;
+            if round == 0 {
+                word.to_string()
+            } else {
+                format!("{}{}", word, to_base36(round))
+            }
+        }
+    }
+}
+
+/// Issues the names of new creeps, see the module docs
+#[derive(Debug)]
+pub struct NameRegistry {
+    /// Counter of the next name
+    next: u64,
+}
+
+impl NameRegistry {
+    pub fn load() -> Self {
+        let next = match screeps::memory::root().string(MEM_CREEP_NAMES) {
+            Ok(Some(next)) => u64::from_str_radix(&next, 36).unwrap_or_else(|err| {
+                warn!("Creep name counter {} broken: {}", next, err);
+                0
+            }),
+            Ok(None) => 0,
+            Err(err) => {
+                warn!("Could not load creep name counter: {}", err);
+                0
+            }
+        };
+        NameRegistry { next }
+    }
+
+    /// The name the next creep of `race` gets
+    ///
+    /// The counter only moves on with `issued`, spawns that don't happen don't use up names.
+    /// Names of living creeps are skipped, in case Memory got wiped.
+    pub fn next_name(&mut self, race: OokRaceKind) -> String {
+        loop {
+            let name = format!("{}-{}", race_prefix(race), token(self.next));
+            if game::creeps::get(&name).is_none() {
+                return name;
+            }
+            self.next += 1;
+        }
+    }
+
+    /// The last name from `next_name` went to a creep
+    pub fn issued(&mut self) {
+        self.next += 1;
+        screeps::memory::root().set(MEM_CREEP_NAMES, to_base36(self.next));
+    }
+
+    /// Calls `issued` if the spawn took the name
+    ///
+    /// A name taken by a creep on another shard is used up as well, `next_name` only sees the
+    /// creeps of this one.
+    pub fn note_spawn(&mut self, result: &anyhow::Result<TrySpawnResult>) {
+        match result {
+            Ok(TrySpawnResult::Spawned(TrySpawnResultData { return_code, .. }))
+            | Ok(TrySpawnResult::ForceSpawned(TrySpawnResultData { return_code, .. }))
+                if *return_code == ReturnCode::Ok || *return_code == ReturnCode::NameExists =>
+            {
+                self.issued()
+            }
+            _ => {}
+        }
+    }
+}
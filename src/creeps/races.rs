@@ -4,16 +4,21 @@ use log::{info, warn};
 use std::{cmp, collections::HashMap, convert::TryFrom, iter};
 
 use screeps::{Creep, HasId, HasPosition, MAX_CREEP_SIZE, ObjectId, ResourceType, Room, RoomName, RoomObjectProperties, SharedCreepProperties, creep};
+use stdweb::JsSerialize;
 
-use crate::{constants::MEM_RACE_KIND, rooms::resource_provider::{ResourceData, ResourceProvider}, state::BWState};
+use crate::{constants::{MEM_JOB, MEM_POST, MEM_RACE_KIND, MEM_REQUEST_ID, MEM_ROOM_BASE}, rooms::resource_provider::{ProviderReservations, ResourceData, ResourceProvider}, state::BWState};
 
-use self::{carrier::OokCreepCarrier, claimer::OokCreepClaimer, worker::OokCreepWorker};
+use self::{blocker::OokCreepBlocker, carrier::OokCreepCarrier, claimer::OokCreepClaimer, dismantler::OokCreepDismantler, harasser::OokCreepHarasser, healer::OokCreepHealer, worker::OokCreepWorker};
 
-use super::{generic_creep_fetch_from_provider_prio, jobs::OokCreepJob, tasks::{CalcResourceProviderResult, OokCreepTask}, utils::SpawnableTimer};
+use super::{generic_creep_fetch_from_provider_prio, jobs::{OokCreepJob, StorableJob}, recovery, tasks::{CalcResourceProviderResult, OokCreepTask}, utils::SpawnableTimer};
 
 pub mod claimer;
 pub mod worker;
 pub mod carrier;
+pub mod blocker;
+pub mod healer;
+pub mod dismantler;
+pub mod harasser;
 // pub mod close_combat_defender;
 
 #[derive(thiserror::Error, Debug)]
@@ -143,6 +148,10 @@ pub enum OokRace {
     Claimer(OokCreepClaimer),
     // StaticWorker(OokCreepWorker),
     Carrier(OokCreepCarrier),
+    Blocker(OokCreepBlocker),
+    Healer(OokCreepHealer),
+    Dismantler(OokCreepDismantler),
+    Harasser(OokCreepHarasser),
     // Claimer(OokCreepClaimer),
     // Attacker(OokCreepAttacker),
     // CloseCombatDefender(OokCreepDefender),
@@ -162,6 +171,18 @@ impl TryFrom<&Creep> for OokRace {
             Some(kind) if kind == OokRaceKind::Carrier as i32 => {
                 Ok(OokRace::Carrier(OokCreepCarrier::try_from(creep)?))
             }
+            Some(kind) if kind == OokRaceKind::Blocker as i32 => {
+                Ok(OokRace::Blocker(OokCreepBlocker::try_from(creep)?))
+            }
+            Some(kind) if kind == OokRaceKind::Healer as i32 => {
+                Ok(OokRace::Healer(OokCreepHealer::try_from(creep)?))
+            }
+            Some(kind) if kind == OokRaceKind::Dismantler as i32 => {
+                Ok(OokRace::Dismantler(OokCreepDismantler::try_from(creep)?))
+            }
+            Some(kind) if kind == OokRaceKind::Harasser as i32 => {
+                Ok(OokRace::Harasser(OokCreepHarasser::try_from(creep)?))
+            }
             Some(val) => Err(anyhow!("OokRace: Unknown race mem {}", val)),
             None => Err(RacesError::MemRaceKindMissing.into()),
         }
@@ -174,6 +195,33 @@ impl RepresentsCreep for OokRace {
             OokRace::Worker(worker) => worker.creep(),
             OokRace::Claimer(claimer) => claimer.creep(),
             OokRace::Carrier(carrier) => carrier.creep(),
+            OokRace::Blocker(blocker) => blocker.creep(),
+            OokRace::Healer(healer) => healer.creep(),
+            OokRace::Dismantler(dismantler) => dismantler.creep(),
+            OokRace::Harasser(harasser) => harasser.creep(),
+        }
+    }
+}
+
+impl OokRace {
+    pub fn job(&self) -> OokCreepJob {
+        match self {
+            OokRace::Worker(worker) => worker.job(),
+            OokRace::Claimer(claimer) => claimer.job(),
+            OokRace::Carrier(carrier) => carrier.job(),
+            OokRace::Blocker(blocker) => blocker.job(),
+            OokRace::Healer(healer) => healer.job(),
+            OokRace::Dismantler(dismantler) => dismantler.job(),
+            OokRace::Harasser(harasser) => harasser.job(),
+        }
+    }
+
+    /// If the citizen is out of tasks, only workers and carriers pick tasks at all
+    pub fn is_idle(&self) -> bool {
+        match self {
+            OokRace::Worker(worker) => worker.is_idle(),
+            OokRace::Carrier(carrier) => carrier.is_idle(),
+            _ => false,
         }
     }
 }
@@ -198,6 +246,10 @@ pub enum OokRaceKind {
     Attacker = 3,
     CloseCombatDefender = 4,
     Claimer = 5,
+    Blocker = 6,
+    Healer = 7,
+    Dismantler = 8,
+    Harasser = 9,
 }
 
 impl TryFrom<i32> for OokRaceKind {
@@ -211,6 +263,10 @@ impl TryFrom<i32> for OokRaceKind {
             3 => Self::Attacker,
             4 => Self::CloseCombatDefender,
             5 => Self::Claimer,
+            6 => Self::Blocker,
+            7 => Self::Healer,
+            8 => Self::Dismantler,
+            9 => Self::Harasser,
             _ => Err(anyhow!("Unknown OokRaceKind {}", val))?,
         })
     }
@@ -225,6 +281,10 @@ impl fmt::Display for OokRaceKind {
             OokRaceKind::Attacker => f.write_str("attacker"),
             OokRaceKind::CloseCombatDefender => f.write_str("defender"),
             OokRaceKind::Claimer => f.write_str("claimer"),
+            OokRaceKind::Blocker => f.write_str("blocker"),
+            OokRaceKind::Healer => f.write_str("healer"),
+            OokRaceKind::Dismantler => f.write_str("dismantler"),
+            OokRaceKind::Harasser => f.write_str("harasser"),
         }
     }
 }
@@ -233,6 +293,30 @@ pub enum DoJobResult {
     None,
 }
 
+/// Writes the memory of a `race` citizen with `job` over whatever the creep remembered, for
+/// creeps that lost their memory or change their job. Claimers have no base, see
+/// `OokCreepClaimer::adopt`.
+pub fn adopt(
+    creep: &Creep,
+    race: OokRaceKind,
+    job: OokCreepJob,
+    base_room: RoomName,
+) -> Result<()> {
+    let memory = creep.memory();
+    memory.set(MEM_RACE_KIND, race as i32);
+    memory.set(
+        MEM_JOB,
+        job.to_js_serialize()
+            .iter()
+            .map(|(i, v)| (i.clone(), &**v))
+            .collect::<HashMap<String, &dyn JsSerialize>>(),
+    );
+    memory.set(MEM_POST, "XXX");
+    memory.set(MEM_ROOM_BASE, base_room.to_string());
+    memory.del(MEM_REQUEST_ID);
+    Ok(())
+}
+
 pub fn get_all_citizens_from_creeps(
     creeps: Vec<Creep>,
     cached_citizens: &HashMap<ObjectId<screeps::Creep>, OokRace>,
@@ -247,12 +331,25 @@ pub fn get_all_citizens_from_creeps(
                 Ok(c) => {
                     citizens.insert(creep.id(), c);
                 }
-                Err(err) => match err.downcast_ref::<RacesError>() {
-                    Some(RacesError::MemRaceKindMissing) => {
+                Err(err) => {
+                    // Memory that is there but doesn't parse is a bug, not for the recovery to
+                    // paper over
+                    if !recovery::lost_memory(&creep) {
+                        warn!("Memory of {} broken: {}", creep.name(), err);
                         unknown_creeps += 1;
+                        continue;
                     }
-                    None => warn!("unhandled OokRace try_from error {}", err),
-                },
+                    match recovery::recover_citizen(&creep) {
+                        Ok(Some(c)) => {
+                            citizens.insert(creep.id(), c);
+                        }
+                        Ok(None) => unknown_creeps += 1,
+                        Err(err) => {
+                            warn!("Could not recover {}: {}", creep.name(), err);
+                            unknown_creeps += 1;
+                        }
+                    }
+                }
             }
         } else {
             bail!("creep has no room");
@@ -265,15 +362,21 @@ pub fn get_all_citizens_from_creeps(
     Ok(citizens)
 }
 
-pub fn generic_calc_energy_resource_provider(
+/// `for_construction` lets the creep take from the spawns and extensions, the energy is wasted
+/// on anything else
+pub fn generic_calc_resource_provider(
     resource_providers: &HashMap<String, ResourceProvider>,
+    reservations: &ProviderReservations,
     creep: &Creep,
     room: &Room,
+    resource_type: ResourceType,
     amount: u32,
+    for_construction: bool,
 ) -> Result<Option<CalcResourceProviderResult>> {
     let working_providers: Vec<&ResourceProvider> = resource_providers
         .iter()
-        .filter_map(|(_id, p)| match p.creep_can_use(&creep) {
+        .filter(|(_id, p)| for_construction || !p.is_spawn_supply())
+        .filter_map(|(_id, p)| match p.creep_can_use(&creep, resource_type) {
             Ok(true) => Some(p),
             Ok(false) => None,
             Err(err) => {
@@ -282,11 +385,17 @@ pub fn generic_calc_energy_resource_provider(
             }
         })
         .collect();
-    let prioed = generic_creep_fetch_from_provider_prio(&room, creep.pos(), working_providers)?;
+    let prioed = generic_creep_fetch_from_provider_prio(
+        &room,
+        creep.pos(),
+        working_providers,
+        resource_type,
+        reservations,
+    )?;
     match prioed {
         Some(prov) => Ok(Some(CalcResourceProviderResult {
             resource_provider: prov.to_owned(),
-            resource_type: ResourceType::Energy,
+            resource_type,
             amount: amount as u32,
         })),
         None => Ok(None),
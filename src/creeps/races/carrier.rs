@@ -1,4 +1,5 @@
 use std::{
+    cmp,
     collections::HashMap,
     convert::{TryFrom, TryInto},
 };
@@ -16,17 +17,25 @@ use stdweb::JsSerialize;
 use crate::{
     constants::{MEM_JOB, MEM_POST, MEM_RACE_KIND, MEM_REQUEST_ID, MEM_ROOM_BASE},
     creeps::{
-        get_prio_deliver_target, get_prio_fetch_target,
+        deaths, get_prio_deliver_target, get_prio_fetch_target,
         jobs::{self, OokCreepJob, StorableJob},
+        movement::move_creep_to,
         races::OokRace,
+        speech::{self, Verbosity},
         tasks::{self, OokCreepTask, OokTaskRunnable},
-        utils::create_creep_name,
         CalcSpawnBodyResult, CreepRunnerState, Spawnable, TrySpawnOptions, TrySpawnResult,
         TrySpawnResultData,
     },
-    rooms::room_state::{
-        base::{BaseData, BaseState},
-        RoomState,
+    rooms::{
+        defense::{self, HaulPriority},
+        fill_orders,
+        resource_provider::ProviderReservations,
+        room_state::{
+            base::{BaseData, BaseState},
+            RoomState,
+        },
+        spawn_area::spawn_directions,
+        stock_balance,
     },
     state::{BWState, UniqId},
 };
@@ -37,7 +46,7 @@ use super::{
 };
 use anyhow::{anyhow, bail, Context, Result};
 
-const COMPOSITION: OokRaceBodyComposition = OokRaceBodyComposition {
+pub const COMPOSITION: OokRaceBodyComposition = OokRaceBodyComposition {
     mov: 1,
     carry: 2,
     work: 0,
@@ -105,9 +114,47 @@ pub struct OokCreepCarrier {
 }
 
 impl OokCreepCarrier {
+    /// If the carrier found nothing to do for its job
+    pub fn is_idle(&self) -> bool {
+        self.task.is_none()
+    }
+
+    /// Writes the job back to the memory after it changed
+    fn store_job(&self) -> Result<()> {
+        self.creep()?.memory().set(
+            MEM_JOB,
+            self.job
+                .to_js_serialize()
+                .iter()
+                .map(|(i, v)| (i.clone(), &**v))
+                .collect::<HashMap<String, &dyn JsSerialize>>(),
+        );
+        Ok(())
+    }
+
+    /// The salvage is done, gives back some of the energy it cost
+    fn recycle(&self, home_room: RoomName) -> Result<()> {
+        let creep = self.creep()?;
+        let spawn = rooms::get(home_room)
+            .and_then(|room| room.find(find::MY_SPAWNS).into_iter().next())
+            .ok_or_else(|| anyhow!("No spawn to recycle carrier in {}", home_room))?;
+        if creep.pos().is_near_to(&spawn.pos()) {
+            deaths::note_recycled(&creep);
+            spawn.recycle_creep(&creep);
+        } else {
+            move_creep_to(&creep, &spawn.pos(), 1);
+        }
+        Ok(())
+    }
+
     #[deprecated]
-    fn new_run(&mut self, room: &Room) -> Result<(), Box<dyn std::error::Error>> {
-        let deliver_target = get_prio_deliver_target(&room, &self.creep()?)?;
+    fn new_run(
+        &mut self,
+        room: &Room,
+        towers_first: bool,
+        reservations: &mut ProviderReservations,
+    ) -> anyhow::Result<()> {
+        let deliver_target = get_prio_deliver_target(&room, &self.creep()?, towers_first)?;
         info!("del target {:?} in {}", deliver_target, room.name());
         if let Some(deliver_target) = deliver_target {
             if deliver_target.requested()
@@ -121,18 +168,29 @@ impl OokCreepCarrier {
                             to: deliver_target,
                             provided: 0,
                         },
+                        resource_type: ResourceType::Energy,
                     },
                 ));
             } else {
+                let creep = self.creep()?;
                 let fetch_target =
-                    get_prio_fetch_target(&room, &deliver_target, &self.creep()?.pos())?;
+                    get_prio_fetch_target(&room, &deliver_target, &creep.pos(), reservations)?;
                 if let Some(fetch_target) = fetch_target {
+                    reservations.reserve_object(
+                        fetch_target.raw_id(),
+                        ResourceType::Energy,
+                        cmp::min(
+                            creep.store_free_capacity(Some(ResourceType::Energy)) as u32,
+                            fetch_target.provides(),
+                        ),
+                    );
                     self.task = Some(OokCreepTask::FetchForConsumer(
                         tasks::fetch_for_consumer::Task {
                             state: CreepRunnerState::Fetching {
                                 from: fetch_target,
                                 to: deliver_target,
                             },
+                            resource_type: ResourceType::Energy,
                         },
                     ));
                 } else {
@@ -144,11 +202,39 @@ impl OokCreepCarrier {
             }
             Ok(())
         } else {
-            self.creep()?.say("...", false);
+            speech::say(&self.creep()?, "...", Verbosity::Status);
             Ok(())
         }
     }
 
+    /// Balances storage and terminal if there is nothing to supply
+    fn assign_stock_transfer(&mut self, room: &Room) -> Result<()> {
+        if self.creep()?.store_used_capacity(None) > 0 {
+            return Ok(());
+        }
+        if let Some(transfer) = stock_balance::stock_transfers(room).into_iter().next() {
+            info!("Stock transfer in {}: {:?}", room.name(), transfer);
+            self.task = Some(OokCreepTask::StockTransfer(tasks::stock_transfer::Task::new(
+                transfer,
+            )));
+        }
+        Ok(())
+    }
+
+    /// Fills the labs and the nuker if there is nothing to supply
+    fn assign_fill_order(&mut self, room: &Room) -> Result<()> {
+        if self.creep()?.store_used_capacity(None) > 0 {
+            return Ok(());
+        }
+        if let Some(order) = fill_orders::fill_orders(room).into_iter().next() {
+            info!("Fill order in {}: {:?}", room.name(), order);
+            self.task = Some(OokCreepTask::FillStructure(tasks::fill_structure::Task::new(
+                order,
+            )));
+        }
+        Ok(())
+    }
+
     fn assign_task_for_room_logistics(&mut self, state: &mut BWState) -> Result<()> {
         let room = rooms::get(self.job.target_room())
             .ok_or_else(|| anyhow!("carrier None job RoomLogistics room not found"))?;
@@ -156,7 +242,11 @@ impl OokCreepCarrier {
         match room_state {
             Some(RoomState::Base(base_state)) => {
                 info!("da length {}", base_state.get_open_suppliers_reach_points(state)?.len());
-                if base_state.get_open_suppliers_reach_points(state)?.len() > 0 {
+                let priority = defense::haul_priority(&room, base_state.defender_queued(state));
+                if priority == HaulPriority::Towers {
+                    self.new_run(&room, true, &mut state.provider_reservations)
+                        .context("new_run failed")?;
+                } else if base_state.get_open_suppliers_reach_points(state)?.len() > 0 {
                     self.task =
                         Some(OokCreepTask::SpawnSuppliesRun(tasks::spawn_supplies_run::Task::new(
                             room.name(),
@@ -164,13 +254,19 @@ impl OokCreepCarrier {
                             &OokRace::Carrier(self.clone()),
                         )?));
                 } else {
-                    self.new_run(&room)
-                        .map_err(|err| anyhow!("new_ron fauled: {}", err))?;
+                    self.new_run(&room, false, &mut state.provider_reservations)
+                        .context("new_run failed")?;
+                    if self.task.is_none() {
+                        self.assign_fill_order(&room)?;
+                    }
+                    if self.task.is_none() {
+                        self.assign_stock_transfer(&room)?;
+                    }
                 }
             }
             Some(RoomState::SetupBase(_)) => {
-                self.new_run(&room)
-                    .map_err(|err| anyhow!("new_ron fauled: {}", err))?;
+                self.new_run(&room, false, &mut state.provider_reservations)
+                    .context("new_run failed")?;
             }
             None => {
                 bail!("RoomState not found for {}", room.name());
@@ -312,11 +408,13 @@ impl Spawnable<TrySpawnCarrierOptions> for OokCreepCarrier {
                 opts.spawn_room.name(),
                 opts.request_id.to_owned(),
             );
-            let creep_name = create_creep_name(&opts.race);
+            let creep_name = opts.creep_name.to_owned();
             let return_code = spawn.spawn_creep_with_options(
                 &calc_result.body,
                 &creep_name,
-                &SpawnOptions::default().memory(Some(new_memory.into())),
+                &SpawnOptions::default()
+                    .memory(Some(new_memory.into()))
+                    .directions(&spawn_directions(&spawn)),
             );
             Ok(TrySpawnResult::Spawned(TrySpawnResultData {
                 return_code,
@@ -347,11 +445,13 @@ impl Spawnable<TrySpawnCarrierOptions> for OokCreepCarrier {
                     opts.spawn_room.name(),
                     opts.request_id.to_owned(),
                 );
-                let creep_name = create_creep_name(&opts.race);
+                let creep_name = opts.creep_name.to_owned();
                 let return_code = spawn.spawn_creep_with_options(
                     &calc_result.body,
-                    &create_creep_name(&opts.race),
-                    &SpawnOptions::default().memory(Some(new_memory.into())),
+                    &creep_name,
+                    &SpawnOptions::default()
+                        .memory(Some(new_memory.into()))
+                        .directions(&spawn_directions(&spawn)),
                 );
                 Ok(TrySpawnResult::ForceSpawned(TrySpawnResultData {
                     return_code,
@@ -401,17 +501,42 @@ impl DynamicTasked for OokCreepCarrier {
                     OokCreepTask::UpgradeController(_) => bail!("carrier task not handled"),
                     OokCreepTask::FarmSource(_) => bail!("carrier task not handled"),
                     OokCreepTask::Build(_) => bail!("carrier task not handled"),
+                    OokCreepTask::DefendRoom(_) => bail!("carrier task not handled"),
+                    OokCreepTask::Triage(_) => bail!("carrier task not handled"),
+                    OokCreepTask::Dismantle(_) => bail!("carrier task not handled"),
+                    OokCreepTask::Patrol(_) => bail!("carrier task not handled"),
+                    OokCreepTask::CrossPortal(_) => bail!("carrier task not handled"),
+                    OokCreepTask::Scout(_) => bail!("carrier task not handled"),
                     OokCreepTask::FetchForConsumer(task) => {
                         task.run(state, &OokRace::Carrier(cloned_self))?
                     }
                     OokCreepTask::SpawnSuppliesRun(task) => {
                         task.run(state, &OokRace::Carrier(cloned_self))?
                     }
+                    OokCreepTask::StockTransfer(task) => {
+                        task.run(state, &OokRace::Carrier(cloned_self))?
+                    }
+                    OokCreepTask::FillStructure(task) => {
+                        task.run(state, &OokRace::Carrier(cloned_self))?
+                    }
+                    OokCreepTask::Salvage(task) => {
+                        task.run(state, &OokRace::Carrier(cloned_self))?
+                    }
+                    OokCreepTask::HaulRemote(task) => {
+                        task.run(state, &OokRace::Carrier(cloned_self))?
+                    }
                 };
                 match run_result {
                     tasks::OokTaskRunnableResult::Continue => {}
                     tasks::OokTaskRunnableResult::Finish => {
                         self.task = None;
+                        if let OokCreepJob::Salvage(job_data) = &mut self.job {
+                            // A new salvage task would only walk back to the emptied room
+                            job_data.done = true;
+                            let home_room = job_data.home_room;
+                            self.store_job()?;
+                            self.recycle(home_room)?;
+                        }
                     }
                     tasks::OokTaskRunnableResult::CancelAndDoAnother => {
                         info!("Cancelling and doing another task");
@@ -424,9 +549,28 @@ impl DynamicTasked for OokCreepCarrier {
                 OokCreepJob::RoomLogistics { .. } => {
                     self.assign_task_for_room_logistics(state);
                 }
+                OokCreepJob::LogisticsExtensionRoom {
+                    extension_room,
+                    target_room,
+                } => {
+                    self.task = Some(OokCreepTask::HaulRemote(tasks::haul_remote::Task::new(
+                        *extension_room,
+                        *target_room,
+                    )));
+                    return self.do_job(state);
+                }
+                OokCreepJob::Salvage(job_data) if job_data.done => {
+                    self.recycle(job_data.home_room)?;
+                }
+                OokCreepJob::Salvage(job_data) => {
+                    self.task = Some(OokCreepTask::Salvage(tasks::salvage::Task::new(
+                        job_data.to_owned(),
+                    )));
+                    return self.do_job(state);
+                }
                 job => {
                     if let Ok(creep) = self.creep() {
-                        creep.say("wut job??", false);
+                        speech::say(&creep, "wut job??", Verbosity::Status);
                     }
                     bail!("OokCreepCarrier::do_task unknown job {:?}", job);
                 }
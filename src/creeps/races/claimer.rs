@@ -14,10 +14,11 @@ use crate::{
     creeps::{
         jobs::{OokCreepJob, StorableJob},
         races::OokRace,
+        speech::{self, Verbosity},
         tasks::{self, OokCreepTask, OokTaskRunnable},
-        utils::create_creep_name,
         CalcSpawnBodyResult, Spawnable, TrySpawnOptions, TrySpawnResult, TrySpawnResultData,
     },
+    rooms::spawn_area::spawn_directions,
     state::{BWState, UniqId},
 };
 
@@ -27,7 +28,7 @@ use super::{
 
 use anyhow::{anyhow, bail, Context, Result};
 
-const COMPOSITION: OokRaceBodyComposition = OokRaceBodyComposition {
+pub const COMPOSITION: OokRaceBodyComposition = OokRaceBodyComposition {
     mov: 1,
     carry: 0,
     work: 0,
@@ -94,6 +95,16 @@ impl OokCreepClaimer {
             .string(MEM_POST)?
             .ok_or_else(|| anyhow!("OokRaceKind: Unknown post ident"))
     }
+
+    /// Takes in a claimer that came through a portal, its memory stayed on the shard it left
+    pub fn adopt(creep: &Creep, job: OokCreepJob) -> Result<()> {
+        let claimer = OokCreepClaimer {
+            creep_id: creep.id(),
+            job: job.to_owned(),
+            task: None,
+        };
+        claimer.set_memory(OokCreepClaimerMemory::new(job, "XXX".into(), None))
+    }
 }
 
 impl RepresentsCreep for OokCreepClaimer {
@@ -181,7 +192,14 @@ impl Spawnable<TrySpawnClaimerOptions> for OokCreepClaimer {
         race_opts: &TrySpawnClaimerOptions,
     ) -> Result<TrySpawnResult> {
         let avail_energy = opts.spawn_room.energy_available();
-        let calc_result = Self::calc_spawn_body(opts, race_opts)?;
+        let calc_result = if let Some(preset_parts) = &opts.preset_parts {
+            CalcSpawnBodyResult {
+                amount: preset_parts.iter().fold(0, |acc, &p| acc + p.cost()),
+                body: preset_parts.to_owned(),
+            }
+        } else {
+            Self::calc_spawn_body(opts, race_opts)?
+        };
         if calc_result.amount <= avail_energy {
             let spawn_id = opts
                 .available_spawns
@@ -196,11 +214,13 @@ impl Spawnable<TrySpawnClaimerOptions> for OokCreepClaimer {
                 opts.request_id.to_owned(),
             );
 
-            let creep_name = create_creep_name(&opts.race);
+            let creep_name = opts.creep_name.to_owned();
             let return_code = spawn.spawn_creep_with_options(
                 &calc_result.body,
-                &create_creep_name(&opts.race),
-                &SpawnOptions::default().memory(Some(new_memory.into())),
+                &creep_name,
+                &SpawnOptions::default()
+                    .memory(Some(new_memory.into()))
+                    .directions(&spawn_directions(&spawn)),
             );
             Ok(TrySpawnResult::Spawned(TrySpawnResultData {
                 return_code,
@@ -230,11 +250,13 @@ impl Spawnable<TrySpawnClaimerOptions> for OokCreepClaimer {
                     race_opts.post_ident.to_owned(),
                     opts.request_id.to_owned(),
                 );
-                let creep_name = create_creep_name(&opts.race);
+                let creep_name = opts.creep_name.to_owned();
                 let return_code = spawn.spawn_creep_with_options(
                     &calc_result.body,
                     &creep_name,
-                    &SpawnOptions::default().memory(Some(new_memory.into())),
+                    &SpawnOptions::default()
+                        .memory(Some(new_memory.into()))
+                        .directions(&spawn_directions(&spawn)),
                 );
                 Ok(TrySpawnResult::ForceSpawned(TrySpawnResultData {
                     return_code,
@@ -278,6 +300,18 @@ impl DynamicTasked for OokCreepClaimer {
             Some(OokCreepTask::ClaimController(task)) => {
                 task.run(state, &OokRace::Claimer(cloned_self))?;
             }
+            Some(OokCreepTask::CrossPortal(task)) => {
+                match task.run(state, &OokRace::Claimer(cloned_self))? {
+                    tasks::OokTaskRunnableResult::Continue => {}
+                    tasks::OokTaskRunnableResult::Finish
+                    | tasks::OokTaskRunnableResult::CancelAndDoAnother => {
+                        self.task = None;
+                    }
+                }
+            }
+            Some(OokCreepTask::Scout(task)) => {
+                task.run(state, &OokRace::Claimer(cloned_self))?;
+            }
             Some(_) => bail!("Unhandled task"),
             None => match &self.job {
                 OokCreepJob::ClaimRoom { target_room } => {
@@ -290,9 +324,25 @@ impl DynamicTasked for OokCreepClaimer {
                     )?;
                     self.task = Some(OokCreepTask::ClaimController(task));
                 }
+                OokCreepJob::ReserveRoom { target_room } => {
+                    let task = tasks::claim_controller::Task::reserving(
+                        &state,
+                        &OokRace::Claimer(cloned_self),
+                        *target_room,
+                    )?;
+                    self.task = Some(OokCreepTask::ClaimController(task));
+                }
+                OokCreepJob::CrossPortal(job) => {
+                    self.task = Some(OokCreepTask::CrossPortal(tasks::portal::Task::new(
+                        job.to_owned(),
+                    )));
+                }
+                OokCreepJob::ScoutRoom { target_room } => {
+                    self.task = Some(OokCreepTask::Scout(tasks::scout::Task::new(*target_room)));
+                }
                 job => {
                     if let Ok(creep) = self.creep() {
-                        creep.say("wut job??", false);
+                        speech::say(&creep, "wut job??", Verbosity::Status);
                     }
                     bail!("OokCreepClaimer::do_task unknown job {:?}", job);
                 }
@@ -0,0 +1,334 @@
+use std::{
+    collections::HashMap,
+    convert::{TryFrom, TryInto},
+};
+
+use log::info;
+use screeps::{
+    find,
+    game::{get_object_typed, rooms},
+    memory::MemoryReference,
+    Creep, HasId, HasPosition, ObjectId, RoomName, SharedCreepProperties, SpawnOptions,
+};
+use stdweb::JsSerialize;
+
+use crate::{
+    constants::{MEM_JOB, MEM_POST, MEM_RACE_KIND, MEM_REQUEST_ID, MEM_ROOM_BASE},
+    creeps::{
+        deaths,
+        jobs::{OokCreepJob, StorableJob},
+        movement::move_creep_to,
+        races::OokRace,
+        speech::{self, Verbosity},
+        tasks::{self, OokCreepTask, OokTaskRunnable},
+        CalcSpawnBodyResult, Spawnable, TrySpawnOptions, TrySpawnResult, TrySpawnResultData,
+    },
+    rooms::spawn_area::spawn_directions,
+    state::{BWState, UniqId},
+};
+
+use super::{
+    DoJobResult, DynamicTasked, Memorizing, OokRaceBodyComposition, OokRaceKind, RepresentsCreep,
+    RoomBound,
+};
+use anyhow::{anyhow, bail, Context, Result};
+
+/// TOUGH goes in front, the WORK parts should survive a few hits in hostile rooms. The body gets
+/// reordered by `room_state::dismantler_body`.
+pub const COMPOSITION: OokRaceBodyComposition = OokRaceBodyComposition {
+    mov: 3,
+    carry: 1,
+    work: 3,
+    attack: 0,
+    ranged_attack: 0,
+    heal: 0,
+    tough: 1,
+    claim: 0,
+};
+
+#[derive(Debug, Clone)]
+struct OokCreepDismantlerMemory {
+    race_kind: OokRaceKind,
+    job: OokCreepJob,
+    post_ident: String,
+    base_room: RoomName,
+    request_id: Option<UniqId>,
+}
+
+impl OokCreepDismantlerMemory {
+    fn new(
+        job: OokCreepJob,
+        post_ident: String,
+        base_room: RoomName,
+        request_id: Option<UniqId>,
+    ) -> Self {
+        Self {
+            race_kind: OokRaceKind::Dismantler,
+            job,
+            post_ident,
+            base_room,
+            request_id,
+        }
+    }
+}
+
+impl From<OokCreepDismantlerMemory> for MemoryReference {
+    fn from(mem: OokCreepDismantlerMemory) -> Self {
+        let memory = MemoryReference::new();
+        memory.set(MEM_RACE_KIND, mem.race_kind as i32);
+        memory.set(
+            MEM_JOB,
+            mem.job
+                .to_js_serialize()
+                .iter()
+                .map(|(i, v)| (i.clone(), &**v))
+                .collect::<HashMap<String, &dyn JsSerialize>>(),
+        );
+        memory.set(MEM_POST, mem.post_ident);
+        memory.set(MEM_ROOM_BASE, mem.base_room.to_string());
+        if let Some(id) = mem.request_id {
+            memory.set(MEM_REQUEST_ID, id.to_string());
+        }
+        memory
+    }
+}
+
+/// Tears down structures, hostile ones and misplaced ones of my own
+#[derive(Debug, Clone)]
+pub struct OokCreepDismantler {
+    pub creep_id: ObjectId<Creep>,
+    pub job: OokCreepJob,
+
+    pub task: Option<OokCreepTask>,
+}
+
+impl OokCreepDismantler {
+    /// Nothing left to tear down, gives back some of the energy it cost
+    fn recycle(&self) -> Result<()> {
+        let creep = self.creep()?;
+        let home_room = match &self.job {
+            OokCreepJob::Dismantle(job_data) => job_data.home_room,
+            job => bail!("Dismantler with unknown job {:?}", job),
+        };
+        let spawn = rooms::get(home_room)
+            .and_then(|room| room.find(find::MY_SPAWNS).into_iter().next())
+            .ok_or_else(|| anyhow!("No spawn to recycle dismantler in {}", home_room))?;
+        if creep.pos().is_near_to(&spawn.pos()) {
+            deaths::note_recycled(&creep);
+            spawn.recycle_creep(&creep);
+        } else {
+            move_creep_to(&creep, &spawn.pos(), 1);
+        }
+        Ok(())
+    }
+}
+
+impl RepresentsCreep for OokCreepDismantler {
+    fn creep(&self) -> Result<Creep> {
+        get_object_typed(self.creep_id)
+            .context("Dismantler creep")?
+            .ok_or(anyhow!("Dismantler creep not found {}", self.creep_id))
+    }
+}
+
+impl TryFrom<&screeps::Creep> for OokCreepDismantler {
+    type Error = anyhow::Error;
+
+    fn try_from(creep: &Creep) -> Result<Self, Self::Error> {
+        let memory = creep.memory();
+        let job_dict = memory
+            .dict(MEM_JOB)
+            .context("loading mem job")?
+            .ok_or(anyhow!("mem job missing"))?;
+        let dismantler_memory = OokCreepDismantlerMemory {
+            race_kind: memory
+                .i32(MEM_RACE_KIND)
+                .context("loading mem race_kind")?
+                .ok_or(anyhow!("mem race_kind missing"))?
+                .try_into()?,
+            job: OokCreepJob::from_js_serialize(&job_dict)
+                .context("loading mem job data")?
+                .ok_or(anyhow!("mem job data"))?,
+            post_ident: memory
+                .string(MEM_POST)
+                .context("loading mem post")?
+                .ok_or(anyhow!("mem post missing"))?,
+            base_room: RoomName::new(
+                &memory
+                    .string(MEM_ROOM_BASE)
+                    .context("loading mem room_base")?
+                    .ok_or(anyhow!("mem room_base missing"))?,
+            )
+            .context("loading mem room_base")?,
+            request_id: memory
+                .string(MEM_REQUEST_ID)
+                .context("loading mem request_id")?
+                .map(|s| UniqId::from(s)),
+        };
+
+        Ok(Self {
+            creep_id: creep.id(),
+            job: dismantler_memory.job,
+            task: None,
+        })
+    }
+}
+
+impl Memorizing<OokCreepDismantlerMemory> for OokCreepDismantler {
+    fn creep_mem_race_ident() -> OokRaceKind {
+        OokRaceKind::Dismantler
+    }
+
+    fn set_memory(&self, mem: OokCreepDismantlerMemory) -> Result<()> {
+        let creep = get_object_typed(self.creep_id)?
+            .ok_or(anyhow!("Memo: Creep {} not found", self.creep_id))?;
+        let memory = creep.memory();
+        memory.set(MEM_RACE_KIND, mem.race_kind as i32);
+        memory.set(
+            MEM_JOB,
+            mem.job
+                .to_js_serialize()
+                .iter()
+                .map(|(i, v)| (i.clone(), &**v))
+                .collect::<HashMap<String, &dyn JsSerialize>>(),
+        );
+        memory.set(MEM_POST, mem.post_ident);
+        memory.set(MEM_ROOM_BASE, mem.base_room.to_string());
+        if let Some(id) = mem.request_id {
+            memory.set(MEM_REQUEST_ID, id.to_string());
+        } else {
+            memory.del(MEM_REQUEST_ID);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TrySpawnDismantlerOptions {
+    pub base_room: RoomName,
+    pub post_ident: String,
+}
+
+impl Spawnable<TrySpawnDismantlerOptions> for OokCreepDismantler {
+    fn try_spawn(
+        opts: &TrySpawnOptions,
+        race_opts: &TrySpawnDismantlerOptions,
+    ) -> Result<TrySpawnResult> {
+        let avail_energy = opts.spawn_room.energy_available();
+        let calc_result = if let Some(preset_parts) = &opts.preset_parts {
+            CalcSpawnBodyResult {
+                amount: preset_parts.iter().fold(0, |acc, &p| acc + p.cost()),
+                body: preset_parts.to_owned(),
+            }
+        } else {
+            Self::calc_spawn_body(opts, race_opts)?
+        };
+        if calc_result.amount > avail_energy {
+            return Ok(TrySpawnResult::Skipped);
+        }
+        let spawn_id = opts
+            .available_spawns
+            .first()
+            .ok_or(anyhow!("try_spawn called without available_spawns"))?;
+        let spawn = get_object_typed(*spawn_id)
+            .context("try_spawn")?
+            .ok_or(anyhow!("Could not find spawn {} for try_spawn", spawn_id))?;
+        let new_memory = OokCreepDismantlerMemory::new(
+            opts.assumed_job.to_owned(),
+            race_opts.post_ident.to_owned(),
+            race_opts.base_room,
+            opts.request_id.to_owned(),
+        );
+        let creep_name = opts.creep_name.to_owned();
+        let return_code = spawn.spawn_creep_with_options(
+            &calc_result.body,
+            &creep_name,
+            &SpawnOptions::default()
+                .memory(Some(new_memory.into()))
+                .directions(&spawn_directions(&spawn)),
+        );
+        Ok(TrySpawnResult::Spawned(TrySpawnResultData {
+            return_code,
+            used_energy_amount: calc_result.amount,
+            used_spawn: spawn.id(),
+            creep_name,
+        }))
+    }
+
+    fn calc_spawn_body(
+        opts: &crate::creeps::TrySpawnOptions,
+        race_opts: &TrySpawnDismantlerOptions,
+    ) -> anyhow::Result<CalcSpawnBodyResult> {
+        if let Some((body, amount)) = COMPOSITION.parts_for_x_energy(opts.target_energy_usage) {
+            Ok(CalcSpawnBodyResult { amount, body })
+        } else {
+            bail!(
+                "Could not calc_spawn_body for {:?} // {:?}",
+                opts,
+                race_opts
+            );
+        }
+    }
+}
+
+impl DynamicTasked for OokCreepDismantler {
+    fn task(&self) -> Option<&OokCreepTask> {
+        self.task.as_ref()
+    }
+
+    fn job(&self) -> OokCreepJob {
+        self.job.to_owned()
+    }
+
+    fn do_job(&mut self, state: &mut BWState) -> Result<DoJobResult> {
+        let cloned_self = self.clone();
+        match &mut self.task {
+            Some(OokCreepTask::Dismantle(task)) => {
+                match task.run(state, &OokRace::Dismantler(cloned_self))? {
+                    tasks::OokTaskRunnableResult::Continue => {}
+                    tasks::OokTaskRunnableResult::Finish
+                    | tasks::OokTaskRunnableResult::CancelAndDoAnother => {
+                        info!("Dismantler {} is done", self.creep_id);
+                        self.task = None;
+                        self.recycle()?;
+                    }
+                }
+            }
+            Some(_) => bail!("Unhandled task"),
+            None => match &self.job {
+                OokCreepJob::Dismantle(job_data) => {
+                    self.task = Some(OokCreepTask::Dismantle(tasks::dismantle::Task::new(
+                        job_data.to_owned(),
+                    )));
+                    // Finishing right away sends it to recycling in the same tick
+                    return self.do_job(state);
+                }
+                job => {
+                    if let Ok(creep) = self.creep() {
+                        speech::say(&creep, "wut job??", Verbosity::Status);
+                    }
+                    bail!("OokCreepDismantler::do_task unknown job {:?}", job);
+                }
+            },
+        }
+        Ok(DoJobResult::None)
+    }
+}
+
+impl RoomBound<String> for OokCreepDismantler {
+    fn room_name_of_base(&self) -> Result<RoomName> {
+        Ok(self
+            .creep()?
+            .memory()
+            .string(MEM_ROOM_BASE)?
+            .ok_or_else(|| anyhow!("OokRaceKind: Unknown post ident"))
+            .map(|str| RoomName::new(&str))??)
+    }
+
+    fn post_ident(&self) -> Result<String> {
+        self.creep()?
+            .memory()
+            .string(MEM_POST)?
+            .ok_or_else(|| anyhow!("OokRaceKind: Unknown post ident"))
+    }
+}
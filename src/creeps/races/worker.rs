@@ -17,11 +17,13 @@ use crate::{
     constants::{MEM_JOB, MEM_POST, MEM_RACE_KIND, MEM_REQUEST_ID, MEM_ROOM_BASE},
     creeps::{
         jobs::{self, OokCreepJob, StorableJob},
+        movement::move_creep_to,
         races::OokRace,
+        speech::{self, Verbosity},
         tasks::{self, OokCreepTask, OokTaskRunnable},
-        utils::create_creep_name,
         CalcSpawnBodyResult, Spawnable, TrySpawnOptions, TrySpawnResult, TrySpawnResultData,
     },
+    rooms::{spawn_area::spawn_directions, BuildPolicy},
     state::{BWState, UniqId},
 };
 
@@ -121,6 +123,13 @@ pub struct OokCreepWorker {
     // cached_creep: (u64, Creep),
 }
 
+impl OokCreepWorker {
+    /// If the worker found nothing to do for its job
+    pub fn is_idle(&self) -> bool {
+        self.task.is_none()
+    }
+}
+
 impl RepresentsCreep for OokCreepWorker {
     fn creep(&self) -> Result<Creep> {
         get_object_typed(self.creep_id)
@@ -256,11 +265,13 @@ impl Spawnable<TrySpawnWorkerOptions> for OokCreepWorker {
                 opts.spawn_room.name(),
                 opts.request_id.to_owned(),
             );
-            let creep_name = create_creep_name(&opts.race);
+            let creep_name = opts.creep_name.to_owned();
             let return_code = spawn.spawn_creep_with_options(
                 &calc_result.body,
                 &creep_name,
-                &SpawnOptions::default().memory(Some(new_memory.into())),
+                &SpawnOptions::default()
+                    .memory(Some(new_memory.into()))
+                    .directions(&spawn_directions(&spawn)),
             );
             Ok(TrySpawnResult::Spawned(TrySpawnResultData {
                 return_code,
@@ -291,11 +302,13 @@ impl Spawnable<TrySpawnWorkerOptions> for OokCreepWorker {
                     opts.spawn_room.name(),
                     opts.request_id.to_owned(),
                 );
-                let creep_name = create_creep_name(&opts.race);
+                let creep_name = opts.creep_name.to_owned();
                 let return_code = spawn.spawn_creep_with_options(
                     &calc_result.body,
-                    &create_creep_name(&opts.race),
-                    &SpawnOptions::default().memory(Some(new_memory.into())),
+                    &creep_name,
+                    &SpawnOptions::default()
+                        .memory(Some(new_memory.into()))
+                        .directions(&spawn_directions(&spawn)),
                 );
                 Ok(TrySpawnResult::ForceSpawned(TrySpawnResultData {
                     return_code,
@@ -367,6 +380,16 @@ impl DynamicTasked for OokCreepWorker {
                     OokCreepTask::ClaimController(_) => bail!("worker task not handled"),
                     OokCreepTask::FetchForConsumer(_) => bail!("worker task not handled"),
                     OokCreepTask::SpawnSuppliesRun(_) => bail!("worker task not handled"),
+                    OokCreepTask::StockTransfer(_) => bail!("worker task not handled"),
+                    OokCreepTask::FillStructure(_) => bail!("worker task not handled"),
+                    OokCreepTask::DefendRoom(_) => bail!("worker task not handled"),
+                    OokCreepTask::Triage(_) => bail!("worker task not handled"),
+                    OokCreepTask::Dismantle(_) => bail!("worker task not handled"),
+                    OokCreepTask::Patrol(_) => bail!("worker task not handled"),
+                    OokCreepTask::CrossPortal(_) => bail!("worker task not handled"),
+                    OokCreepTask::Salvage(_) => bail!("worker task not handled"),
+                    OokCreepTask::Scout(_) => bail!("worker task not handled"),
+                    OokCreepTask::HaulRemote(_) => bail!("worker task not handled"),
                 };
                 match run_result {
                     tasks::OokTaskRunnableResult::Continue => {},
@@ -388,7 +411,7 @@ impl DynamicTasked for OokCreepWorker {
                         .pos();
                     let task = tasks::upgrade_controller::Task::new(
                         pos,
-                        &state,
+                        state,
                         &OokRace::Worker(cloned_self),
                     )?;
                     self.task = Some(OokCreepTask::UpgradeController(task));
@@ -406,19 +429,20 @@ impl DynamicTasked for OokCreepWorker {
                         .unwrap_or(Position::new(25, 25, *target_room));
                     // HACK BIG FIN HACK LOL
                     if self.creep()?.room().ok_or(anyhow!("Wer room wut"))?.name() != *target_room {
-                        self.creep()?.move_to(&pos);
+                        move_creep_to(&self.creep()?, &pos, 1);
                     } else {
-                        self.creep()?.move_to(&pos); // HACK
-                        if let Some(construction_site) = self
+                        move_creep_to(&self.creep()?, &pos, 1); // HACK
+                        let construction_sites = self
                             .creep()?
                             .room()
                             .ok_or(anyhow!("Room wut wut"))?
-                            .find(find::CONSTRUCTION_SITES)
-                            .first()
+                            .find(find::CONSTRUCTION_SITES);
+                        if let Some(construction_site) =
+                            BuildPolicy::default().pick(construction_sites)
                         {
                             let task = tasks::build::Task::new(
                                 construction_site.to_owned(),
-                                &state,
+                                state,
                                 &OokRace::Worker(cloned_self),
                             )?;
                             info!("cons site: {:?}", construction_site.id());
@@ -426,7 +450,7 @@ impl DynamicTasked for OokCreepWorker {
                         } else {
                             let task = tasks::upgrade_controller::Task::new(
                                 pos,
-                                &state,
+                                state,
                                 &OokRace::Worker(cloned_self),
                             )?;
                             self.task = Some(OokCreepTask::UpgradeController(task));
@@ -457,12 +481,12 @@ impl DynamicTasked for OokCreepWorker {
                             );
                         }
                         let pos = Position::new(25, 25, *target_room);
-                        creep.move_to(&pos);
+                        move_creep_to(&creep, &pos, 20);
                     }
                 }
                 job => {
                     if let Ok(creep) = self.creep() {
-                        creep.say("wut job??", false);
+                        speech::say(&creep, "wut job??", Verbosity::Status);
                     }
                     bail!("OokCreepWorker::do_task unknown job {:?}", job);
                 }
@@ -0,0 +1,102 @@
+//! Citizens whose memory is missing. Without a race and a job in memory a creep can't be loaded
+//! and would idle until it dies. Memory that is there but doesn't parse is left alone, that is a
+//! bug to fix and not to paper over. The race is told by the name prefix, see `naming`, or by
+//! the body if the name is of no help. The creep then gets the plain job of its race for the
+//! closest base that its body fits, and the memory to go with it.
+use log::info;
+use screeps::{find, Creep, HasId, HasPosition, Part, RoomName, SharedCreepProperties};
+use std::convert::TryFrom;
+
+use crate::{
+    constants::{CREEP_ID_PREFIX, MEM_JOB, MEM_RACE_KIND},
+    game::{owned_rooms, OwnedBy},
+    rooms::remotes::linear_distance,
+};
+
+use super::{
+    fitness,
+    jobs::{self, OokCreepJob},
+    naming,
+    races::{self, OokRace, OokRaceKind},
+};
+
+/// The race going by the body parts, the part only one race has decides
+fn race_of_body(parts: &[Part]) -> Option<OokRaceKind> {
+    let has = |part: Part| parts.contains(&part);
+    if has(Part::Claim) {
+        Some(OokRaceKind::Claimer)
+    } else if has(Part::Heal) {
+        Some(OokRaceKind::Healer)
+    } else if has(Part::RangedAttack) {
+        Some(OokRaceKind::Harasser)
+    } else if has(Part::Attack) {
+        Some(OokRaceKind::Blocker)
+    } else if has(Part::Work) && has(Part::Tough) {
+        Some(OokRaceKind::Dismantler)
+    } else if has(Part::Work) {
+        Some(OokRaceKind::Worker)
+    } else if has(Part::Carry) {
+        Some(OokRaceKind::Carrier)
+    } else {
+        None
+    }
+}
+
+/// The base the creep is in, or the closest one
+fn closest_base(creep: &Creep) -> Option<RoomName> {
+    let room_name = creep.pos().room_name();
+    owned_rooms(OwnedBy::Me)
+        .keys()
+        .min_by_key(|base| linear_distance(**base, room_name))
+        .cloned()
+}
+
+/// If the race or the job is gone from the memory of the creep, not only unreadable
+pub fn lost_memory(creep: &Creep) -> bool {
+    let memory = creep.memory();
+    matches!(memory.i32(MEM_RACE_KIND), Ok(None)) || matches!(memory.dict(MEM_JOB), Ok(None))
+}
+
+/// Gives a creep with missing memory a job again and loads it as citizen
+///
+/// Claimers and dismantlers had a target nobody remembers, they stay unknown. So do the creeps of
+/// the legacy code and the opener, they don't need a race.
+pub fn recover_citizen(creep: &Creep) -> anyhow::Result<Option<OokRace>> {
+    let name = creep.name();
+    if name.starts_with(CREEP_ID_PREFIX) {
+        return Ok(None);
+    }
+    let parts: Vec<Part> = creep.body().into_iter().map(|b| b.part).collect();
+    let race = match naming::race_of_name(&name).or_else(|| race_of_body(&parts)) {
+        Some(race) => race,
+        None => return Ok(None),
+    };
+    let base = match closest_base(creep) {
+        Some(base) => base,
+        None => return Ok(None),
+    };
+    let job = match race {
+        OokRaceKind::Worker
+            if !fitness::fits(&parts, &OokCreepJob::BootstrapRoom { target_room: base }) =>
+        {
+            // Farmers can't carry, they go back to the closest source
+            let source = match creep.pos().find_closest_by_range(find::SOURCES) {
+                Some(source) => source,
+                None => return Ok(None),
+            };
+            OokCreepJob::FarmSource(jobs::FarmSource {
+                target_room: source.pos().room_name(),
+                target_source: source.id(),
+            })
+        }
+        OokRaceKind::Worker => OokCreepJob::BootstrapRoom { target_room: base },
+        OokRaceKind::Carrier => OokCreepJob::RoomLogistics { target_room: base },
+        OokRaceKind::Blocker => OokCreepJob::DefendRoom { target_room: base },
+        OokRaceKind::Healer => OokCreepJob::HealCitizens { target_room: base },
+        OokRaceKind::Harasser => OokCreepJob::PatrolRemotes { target_room: base },
+        _ => return Ok(None),
+    };
+    races::adopt(creep, race, job, base)?;
+    info!("Recovered {} as {} for {}", name, race, base);
+    OokRace::try_from(creep).map(Some)
+}
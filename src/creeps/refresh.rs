@@ -0,0 +1,97 @@
+//! Creeps spawned before the extensions of their base came online. They keep the small body they
+//! were spawned with for their whole life, so once the base could spawn a much bigger one for the
+//! same job and has the energy to spare they get recycled early. The base then spawns the
+//! replacement with the body it can afford now, like for any missing citizen.
+//!
+//! Only one creep per base is replaced at a time, so the base never loses its workforce at once.
+use std::{collections::HashMap, sync::Mutex};
+
+use lazy_static::lazy_static;
+use log::info;
+use screeps::{game, Creep, HasStore, ResourceType, Room, RoomName, SharedCreepProperties};
+
+use crate::{config, rooms::room_state::plan_citizen};
+
+use super::{
+    jobs::OokCreepJob,
+    races::{OokRace, RepresentsCreep},
+    retirement::{base_of_job, retire},
+};
+
+lazy_static! {
+    /// The creep of each base on its way to be recycled
+    static ref REFRESHING: Mutex<HashMap<RoomName, String>> = Mutex::new(HashMap::new());
+}
+
+fn has_energy_to_spare(base: &Room) -> bool {
+    match base.storage() {
+        Some(storage) => {
+            storage.store_used_capacity(Some(ResourceType::Energy))
+                >= config::BODY_REFRESH_MIN_STORED_ENERGY
+        }
+        None => base.energy_available() == base.energy_capacity_available(),
+    }
+}
+
+/// What the body of the creep cost as share of the body the base would spawn for the job now
+fn body_share(creep: &Creep, job: &OokCreepJob, base: &Room) -> Option<f64> {
+    let plan = plan_citizen(base, job, base.name()).ok()??;
+    if plan.cost == 0 {
+        return None;
+    }
+    let cost: u32 = creep.body().iter().map(|b| b.part.cost()).sum();
+    Some(cost as f64 / plan.cost as f64)
+}
+
+fn is_outgrown(creep: &Creep, citizen: &OokRace, base: &Room) -> bool {
+    let ticks_to_live = creep.ticks_to_live().unwrap_or(0);
+    ticks_to_live >= config::BODY_REFRESH_MIN_TICKS_TO_LIVE
+        && has_energy_to_spare(base)
+        && body_share(creep, &citizen.job(), base)
+            .map(|share| share < config::BODY_REFRESH_MAX_BODY_SHARE)
+            .unwrap_or(false)
+}
+
+/// If the creep is on its way to be recycled for a bigger body
+pub fn is_refreshing(creep_name: &str) -> bool {
+    REFRESHING
+        .lock()
+        .unwrap()
+        .values()
+        .any(|name| name == creep_name)
+}
+
+/// Sends the citizen off to be recycled if its base would spawn a much bigger body for its job
+/// now, true while it is on its way. `bases` are the rooms owned by me.
+pub fn replace_if_outgrown(citizen: &OokRace, bases: &HashMap<RoomName, Room>) -> bool {
+    let creep = match citizen.creep() {
+        Ok(creep) => creep,
+        Err(_) => return false,
+    };
+    let base = match base_of_job(&citizen.job()).and_then(|base| bases.get(&base)) {
+        Some(base) => base,
+        None => return false,
+    };
+    let mut refreshing = REFRESHING.lock().unwrap();
+    match refreshing.get(&base.name()) {
+        Some(name) if *name == creep.name() => {
+            retire(&creep, bases);
+            return true;
+        }
+        Some(name) if game::creeps::get(name).is_some() => return false,
+        _ => {}
+    }
+    if game::time() % config::BODY_REFRESH_CHECK_INTERVAL != 0
+        || !is_outgrown(&creep, citizen, base)
+    {
+        return false;
+    }
+    info!(
+        "Replacing {} of {}, the base spawns bigger bodies now",
+        creep.name(),
+        base.name()
+    );
+    refreshing.insert(base.name(), creep.name());
+    retire(&creep, bases);
+    true
+}
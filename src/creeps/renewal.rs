@@ -0,0 +1,95 @@
+//! Renewing creeps at the spawns. Renewing a big creep that is about to die costs less than
+//! spawning it again, but a renewing spawn can't spawn in the same tick. So renewals are items of
+//! the spawn queue like the requests, with the lowest priority: they only get the spawns nothing
+//! else in the queue wants this tick, see `room_state::dummy_handle_requests`.
+//!
+//! Only creeps already standing next to a spawn get renewed, nobody walks over for it. Boosted
+//! creeps would lose their boosts and claimers can't be renewed at all.
+use screeps::{
+    find, game, Attackable, Creep, HasId, HasPosition, ObjectId, Part, ReturnCode, Room,
+    SharedCreepProperties, StructureSpawn, CREEP_LIFE_TIME,
+};
+
+use crate::config;
+
+use super::refresh;
+
+#[derive(Debug, Clone)]
+pub struct Renewal {
+    pub creep_name: String,
+    pub spawn: ObjectId<StructureSpawn>,
+    pub ticks_to_live: u32,
+    /// What one renew takes from the room
+    pub energy: u32,
+}
+
+fn body_energy(creep: &Creep) -> u32 {
+    creep.body().iter().map(|b| b.part.cost()).sum()
+}
+
+/// Ticks of life one renew gives
+fn renew_ticks(creep: &Creep) -> u32 {
+    600 / creep.body().len().max(1) as u32
+}
+
+/// Energy one renew takes
+fn renew_energy(creep: &Creep) -> u32 {
+    (body_energy(creep) as f64 / 2.5 / creep.body().len().max(1) as f64).ceil() as u32
+}
+
+fn is_worth_renewing(creep: &Creep) -> bool {
+    let ticks_to_live = match creep.ticks_to_live() {
+        Some(ticks_to_live) if !creep.spawning() => ticks_to_live,
+        _ => return false,
+    };
+    let body = creep.body();
+    ticks_to_live <= config::RENEW_BELOW_TICKS
+        && ticks_to_live + renew_ticks(creep) <= CREEP_LIFE_TIME
+        && body_energy(creep) >= config::RENEW_MIN_BODY_ENERGY
+        && body.iter().all(|b| b.part != Part::Claim && b.boost.is_none())
+        // Damaged creeps may be on their way to be recycled
+        && creep.hits() == creep.hits_max()
+        && !refresh::is_refreshing(&creep.name())
+}
+
+/// The renewals the spawns of the room could do this tick, the creep closest to death first
+pub fn due(room: &Room) -> Vec<Renewal> {
+    let spawns: Vec<StructureSpawn> = room
+        .find(find::MY_SPAWNS)
+        .into_iter()
+        .filter(|spawn| spawn.spawning().is_none())
+        .collect();
+    if spawns.is_empty() {
+        return vec![];
+    }
+    let mut renewals: Vec<Renewal> = room
+        .find(find::MY_CREEPS)
+        .into_iter()
+        .filter(|creep| is_worth_renewing(creep))
+        .filter_map(|creep| {
+            let spawn = spawns
+                .iter()
+                .find(|spawn| spawn.pos().is_near_to(&creep.pos()))?;
+            Some(Renewal {
+                creep_name: creep.name(),
+                spawn: spawn.id(),
+                ticks_to_live: creep.ticks_to_live().unwrap_or(0),
+                energy: renew_energy(&creep),
+            })
+        })
+        .collect();
+    renewals.sort_by_key(|renewal| renewal.ticks_to_live);
+    renewals
+}
+
+/// Has the spawn renew the creep, the spawn is taken for this tick
+pub fn renew(renewal: &Renewal) -> ReturnCode {
+    let creep = match game::creeps::get(&renewal.creep_name) {
+        Some(creep) => creep,
+        None => return ReturnCode::NotFound,
+    };
+    match game::get_object_typed(renewal.spawn) {
+        Ok(Some(spawn)) => spawn.renew_creep(&creep),
+        _ => ReturnCode::NotFound,
+    }
+}
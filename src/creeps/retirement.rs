@@ -0,0 +1,230 @@
+//! Creeps that can never be useful again. A creep whose parts for its job are all broken keeps
+//! walking around doing nothing, and a creep whose base is lost has nothing to work for. Both cost
+//! CPU and block spawning a replacement, so they get recycled at a spawn close by, or suicide
+//! without one.
+//!
+//! Badly damaged creeps that would only work at a fraction go home first if a tower or a healer
+//! there can heal them. Workers left without CARRY but with WORK can still farm and become farmers.
+use std::{collections::HashMap, convert::TryFrom};
+
+use log::{debug, info};
+use screeps::{
+    find, Attackable, Creep, HasId, HasPosition, HasStore, Part, ResourceType, Room, RoomName,
+    SharedCreepProperties, StructureTower, StructureType,
+};
+
+use crate::rooms::{defense::drill, remotes::linear_distance, structures};
+
+use super::{
+    deaths, fitness,
+    jobs::{self, OokCreepJob},
+    movement::move_creep_to,
+    races::{self, OokRace, OokRaceKind, RepresentsCreep},
+};
+
+/// Creeps further away from every spawn than this, in rooms, suicide instead of walking back
+const MAX_RECYCLE_DISTANCE: i32 = 1;
+/// Creeps with less of their essential parts working than this share go home for healing
+const RETREAT_INTEGRITY: f64 = 0.5;
+
+/// The part the race can't do its job without
+fn essential_part(citizen: &OokRace) -> Part {
+    match citizen {
+        OokRace::Worker(_) => Part::Work,
+        OokRace::Carrier(_) => Part::Carry,
+        OokRace::Claimer(_) => Part::Claim,
+        OokRace::Blocker(_) => Part::Attack,
+        OokRace::Healer(_) => Part::Heal,
+        OokRace::Dismantler(_) => Part::Work,
+        OokRace::Harasser(_) => Part::RangedAttack,
+    }
+}
+
+/// The base the job works for, jobs in foreign rooms have none
+pub(super) fn base_of_job(job: &OokCreepJob) -> Option<RoomName> {
+    match job {
+        OokCreepJob::UpgradeController { target_room }
+        | OokCreepJob::RoomLogistics { target_room }
+        | OokCreepJob::MaintainStructures { target_room }
+        | OokCreepJob::BootstrapRoom { target_room }
+        | OokCreepJob::DefendRoom { target_room }
+        | OokCreepJob::HealCitizens { target_room }
+        | OokCreepJob::PatrolRemotes { target_room } => Some(*target_room),
+        OokCreepJob::FarmSource(job) => Some(job.target_room),
+        _ => None,
+    }
+}
+
+/// Share of the parts of the kind that still work, 1 without any
+fn integrity(creep: &Creep, part: Part) -> f64 {
+    let body = creep.body();
+    let total = body.iter().filter(|b| b.part == part).count();
+    if total == 0 {
+        return 1.;
+    }
+    let working = body.iter().filter(|b| b.part == part && b.hits > 0).count();
+    working as f64 / total as f64
+}
+
+fn has_hostiles(room: &Room) -> bool {
+    !room.find(find::HOSTILE_CREEPS).is_empty()
+}
+
+/// If a tower with energy or a healer is in the room
+///
+/// Towers only heal while neither hostiles are around nor a drill is on, see `defense::towers`.
+fn heals(room: &Room) -> bool {
+    let tower = !has_hostiles(room)
+        && !drill::is_drilling(room.name())
+        && structures::of_type::<StructureTower>(room.name(), StructureType::Tower)
+            .iter()
+            .any(|t| t.store_used_capacity(Some(ResourceType::Energy)) > 0);
+    tower
+        || room
+            .find(find::MY_CREEPS)
+            .iter()
+            .any(|c| c.get_active_bodyparts(Part::Heal) > 0)
+}
+
+/// Why the creep is of no use anymore, if it is
+fn uselessness(
+    creep: &Creep,
+    citizen: &OokRace,
+    bases: &HashMap<RoomName, Room>,
+) -> Option<String> {
+    if let Some(base) = base_of_job(&citizen.job()) {
+        if !bases.contains_key(&base) {
+            return Some(format!("base {} is lost", base));
+        }
+    }
+    // Broken parts only come back by healing
+    if creep.hits() < creep.hits_max() && creep.get_active_bodyparts(essential_part(citizen)) == 0 {
+        let healed = creep
+            .room()
+            .map(|room| {
+                room.find(find::MY_CREEPS)
+                    .iter()
+                    .any(|c| c.get_active_bodyparts(Part::Heal) > 0)
+            })
+            .unwrap_or(false);
+        if !healed {
+            return Some(format!("no {:?} part works", essential_part(citizen)));
+        }
+    }
+    None
+}
+
+pub(super) fn retire(creep: &Creep, bases: &HashMap<RoomName, Room>) {
+    let room_name = creep.pos().room_name();
+    let spawn = bases
+        .iter()
+        .filter(|(base, _)| linear_distance(**base, room_name) <= MAX_RECYCLE_DISTANCE)
+        .flat_map(|(_, room)| room.find(find::MY_SPAWNS))
+        .min_by_key(|spawn| {
+            (
+                linear_distance(spawn.pos().room_name(), room_name),
+                spawn.pos().get_range_to(&creep.pos()),
+            )
+        });
+    match spawn {
+        Some(spawn) if creep.pos().is_near_to(&spawn.pos()) => {
+            deaths::note_recycled(creep);
+            spawn.recycle_creep(creep);
+        }
+        Some(spawn) => move_creep_to(creep, &spawn.pos(), 1),
+        None => {
+            deaths::note_recycled(creep);
+            creep.suicide();
+        }
+    }
+}
+
+/// Gives a worker whose body doesn't fit its job anymore the job of farming the closest source of
+/// its base, true if it did
+fn reassign(creep: &Creep, citizen: &mut OokRace, base: &Room) -> bool {
+    if !matches!(citizen, OokRace::Worker(_)) {
+        return false;
+    }
+    let parts = fitness::active_parts(creep);
+    if fitness::fits(&parts, &citizen.job()) {
+        return false;
+    }
+    let source = match base
+        .find(find::SOURCES)
+        .into_iter()
+        .min_by_key(|s| s.pos().get_range_to(&creep.pos()))
+    {
+        Some(source) => source,
+        None => return false,
+    };
+    let job = OokCreepJob::FarmSource(jobs::FarmSource {
+        target_room: base.name(),
+        target_source: source.id(),
+    });
+    if !fitness::fits(&parts, &job) {
+        return false;
+    }
+    // The citizens are cached, the one of this tick gets loaded from the new memory
+    match races::adopt(creep, OokRaceKind::Worker, job, base.name())
+        .and_then(|_| OokRace::try_from(creep))
+    {
+        Ok(farmer) => {
+            info!("{} doesn't fit its job anymore, farming from now on", creep.name());
+            *citizen = farmer;
+            true
+        }
+        Err(err) => {
+            debug!("Couldn't reassign {}: {}", creep.name(), err);
+            false
+        }
+    }
+}
+
+/// Walks a badly damaged creep home while it can get healed there, true if it does
+///
+/// Fighters hold their ground while hostiles are in their room or the base.
+fn retreat(creep: &Creep, citizen: &OokRace, base: &Room) -> bool {
+    if creep.hits() == creep.hits_max()
+        || integrity(creep, essential_part(citizen)) >= RETREAT_INTEGRITY
+        || !heals(base)
+    {
+        return false;
+    }
+    let fights = matches!(citizen, OokRace::Blocker(_) | OokRace::Harasser(_) | OokRace::Healer(_));
+    if fights && (has_hostiles(base) || creep.room().map(|r| has_hostiles(&r)).unwrap_or(false)) {
+        return false;
+    }
+    let spawn = match base.find(find::MY_SPAWNS).into_iter().next() {
+        Some(spawn) => spawn,
+        None => return false,
+    };
+    debug!("{} retreats to {} for healing", creep.name(), base.name());
+    if creep.pos().get_range_to(&spawn.pos()) > 3 {
+        move_creep_to(creep, &spawn.pos(), 3);
+    }
+    true
+}
+
+/// Sends the citizen off to be recycled if it is of no use anymore, true if it was
+///
+/// Badly damaged citizens go home for healing or get another job first, true while they are busy
+/// with that. `bases` are the rooms owned by me.
+pub fn retire_if_useless(citizen: &mut OokRace, bases: &HashMap<RoomName, Room>) -> bool {
+    let creep = match citizen.creep() {
+        Ok(creep) => creep,
+        Err(_) => return false,
+    };
+    if let Some(base) = base_of_job(&citizen.job()).and_then(|base| bases.get(&base)) {
+        if reassign(&creep, citizen, base) || retreat(&creep, citizen, base) {
+            return true;
+        }
+    }
+    match uselessness(&creep, citizen, bases) {
+        Some(reason) => {
+            debug!("Retiring {}, {}", creep.name(), reason);
+            retire(&creep, bases);
+            true
+        }
+        None => false,
+    }
+}
@@ -0,0 +1,76 @@
+//! Everything creeps say goes through `say`. Every `say` is an intent and costs CPU, so how much
+//! creeps talk is configured: `config::QUIET_MODE` silences all of them, otherwise each race talks
+//! up to its level of `config::SPEECH_VERBOSITY`. While the bucket is below
+//! `config::SPEECH_MIN_BUCKET` everyone is quiet.
+use std::{collections::HashMap, convert::TryFrom, sync::Mutex};
+
+use lazy_static::lazy_static;
+use screeps::{game, Creep, SharedCreepProperties};
+
+use crate::{config, constants::MEM_RACE_KIND};
+
+use super::races::OokRaceKind;
+
+/// How much a creep says, each level includes the ones below
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    Quiet,
+    /// What the creep is doing or why it can't
+    Status,
+    /// Dances and stories, said in public
+    Chatter,
+}
+
+lazy_static! {
+    /// Tick of the last bucket check and if it was too low then
+    static ref LOW_BUCKET: Mutex<(u32, bool)> = Mutex::new((0, false));
+    /// Tick and the verbosity of every creep that talked in it, keyed by creep name
+    static ref VERBOSITIES: Mutex<(u32, HashMap<String, Verbosity>)> =
+        Mutex::new((0, HashMap::new()));
+}
+
+fn is_bucket_low() -> bool {
+    let time = game::time();
+    let mut low_bucket = LOW_BUCKET.lock().unwrap();
+    if low_bucket.0 != time {
+        *low_bucket = (time, game::cpu::bucket() < config::SPEECH_MIN_BUCKET);
+    }
+    low_bucket.1
+}
+
+fn verbosity_of(creep: &Creep) -> Verbosity {
+    let time = game::time();
+    let mut verbosities = VERBOSITIES.lock().unwrap();
+    if verbosities.0 != time {
+        *verbosities = (time, HashMap::new());
+    }
+    *verbosities
+        .1
+        .entry(creep.name())
+        .or_insert_with(|| read_verbosity(creep))
+}
+
+fn read_verbosity(creep: &Creep) -> Verbosity {
+    let race = match creep.memory().i32(MEM_RACE_KIND) {
+        Ok(Some(race)) => OokRaceKind::try_from(race).ok(),
+        _ => None,
+    };
+    race.and_then(|race| {
+        config::SPEECH_VERBOSITY
+            .iter()
+            .find(|(kind, _)| *kind as i32 == race as i32)
+            .map(|(_, verbosity)| *verbosity)
+    })
+    .unwrap_or(config::DEFAULT_SPEECH_VERBOSITY)
+}
+
+/// Has the creep say the message if its race talks that much
+pub fn say(creep: &Creep, message: &str, level: Verbosity) {
+    if config::QUIET_MODE || level == Verbosity::Quiet || is_bucket_low() {
+        return;
+    }
+    if verbosity_of(creep) < level {
+        return;
+    }
+    creep.say(message, level == Verbosity::Chatter);
+}
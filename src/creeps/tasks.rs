@@ -4,6 +4,16 @@ pub mod build;
 pub mod farm;
 pub mod fetch_for_consumer;
 pub mod spawn_supplies_run;
+pub mod stock_transfer;
+pub mod fill_structure;
+pub mod defend_room;
+pub mod triage;
+pub mod dismantle;
+pub mod patrol;
+pub mod portal;
+pub mod salvage;
+pub mod scout;
+pub mod haul_remote;
 
 use std::{collections::HashMap, convert::TryFrom};
 
@@ -59,7 +69,26 @@ pub enum OokCreepTask {
     /// Something requests a resource which needs to be carried over
     FetchForConsumer(fetch_for_consumer::Task),
     SpawnSuppliesRun(spawn_supplies_run::Task),
-    // DefendRoom(defend_room::Task),
+    /// Moves stock between storage and terminal
+    StockTransfer(stock_transfer::Task),
+    /// Fills a lab or the nuker from the storage or the terminal
+    FillStructure(fill_structure::Task),
+    /// Blocks a rampart against wall campers
+    DefendRoom(defend_room::Task),
+    /// Heals the most valuable damaged creeps
+    Triage(triage::Task),
+    /// Tears down structures and brings their energy home
+    Dismantle(dismantle::Task),
+    /// Guards the remotes of a base
+    Patrol(patrol::Task),
+    /// Walks into a portal to another shard
+    CrossPortal(portal::Task),
+    /// Loots ruins and tombstones and brings it all to a terminal
+    Salvage(salvage::Task),
+    /// Keeps a room in sight by standing in it
+    Scout(scout::Task),
+    /// Brings the energy mined in a remote home
+    HaulRemote(haul_remote::Task),
     // BootstrapRoom(bootstrap_room::Task),
 }
 
@@ -90,9 +119,11 @@ pub struct CalcResourceProviderResult {
 }
 
  pub trait FetchesFromResourceProvider<'a> {
+    /// Picks a provider, taking the reservations of other creeps in this tick into account.
+    /// The caller should reserve the result in `BWState::provider_reservations`.
     fn calc_resource_provider(
         &self,
-        rooms_state: &'a HashMap<RoomName, RoomState>,
+        state: &'a BWState,
         race: &'a OokRace,
     ) -> Result<Option<CalcResourceProviderResult>>;
  }
@@ -1,4 +1,4 @@
-use std::{collections::HashMap, fmt, mem};
+use std::{fmt, mem};
 
 use log::warn;
 use screeps::{
@@ -10,7 +10,9 @@ use screeps::{
 use crate::{
     creeps::{
         generic_creep_fetch_from_provider_prio,
-        races::{generic_calc_energy_resource_provider, OokRace, RepresentsCreep},
+        movement::move_creep_to,
+        races::{generic_calc_resource_provider, OokRace, RepresentsCreep},
+        speech::{self, Verbosity},
     },
     rooms::{
         resource_provider::{ResourceData, ResourceProvider, TakeResourceResult},
@@ -80,7 +82,7 @@ pub struct Task {
 }
 
 impl Task {
-    pub fn new(target: ConstructionSite, state: &BWState, race: &OokRace) -> Result<Self> {
+    pub fn new(target: ConstructionSite, state: &mut BWState, race: &OokRace) -> Result<Self> {
         let mut task = Task {
             step: Step::WaitForResource {
                 build_target: target.into(),
@@ -90,12 +92,12 @@ impl Task {
         Ok(task)
     }
 
-    fn precheck(&mut self, state: &BWState, race: &OokRace) -> Result<()> {
+    fn precheck(&mut self, state: &mut BWState, race: &OokRace) -> Result<()> {
         let creep = race.creep()?;
         match &self.step {
             Step::GetEnergy { build_target, .. } => {
                 if creep.store_free_capacity(Some(ResourceType::Energy)) == 0 {
-                    creep.say("🏗", false);
+                    speech::say(&creep, "🏗", Verbosity::Status);
                     self.step = Step::Build {
                         build_target: build_target.to_owned(),
                     };
@@ -104,11 +106,16 @@ impl Task {
             Step::Build { build_target } => {
                 if creep.store_used_capacity(Some(ResourceType::Energy)) == 0 {
                     let calc_result = self
-                        .calc_resource_provider(&state.room_states, race)
+                        .calc_resource_provider(state, race)
                         /*.context("Build precheck calc_resource_provider")*/?;
                     match calc_result {
                         Some(calc_result) => {
-                            creep.say("📦", false);
+                            speech::say(&creep, "📦", Verbosity::Status);
+                            state.provider_reservations.reserve(
+                                &calc_result.resource_provider,
+                                calc_result.resource_type,
+                                calc_result.amount,
+                            );
                             self.step = Step::GetEnergy {
                                 build_target: build_target.to_owned(),
                                 target: calc_result.resource_provider,
@@ -125,11 +132,16 @@ impl Task {
             }
             Step::WaitForResource { build_target } => {
                 let calc_result = self
-                    .calc_resource_provider(&state.room_states, race)
+                    .calc_resource_provider(state, race)
                     /*.context("Build precheck calc_resource_provider")*/?;
                 match calc_result {
                     Some(calc_result) => {
-                        creep.say("📦", false);
+                        speech::say(&creep, "📦", Verbosity::Status);
+                        state.provider_reservations.reserve(
+                            &calc_result.resource_provider,
+                            calc_result.resource_type,
+                            calc_result.amount,
+                        );
                         self.step = Step::GetEnergy {
                             build_target: build_target.to_owned(),
                             target: calc_result.resource_provider,
@@ -163,7 +175,7 @@ impl OokTaskRunnable for Task {
                         creep.store_free_capacity(Some(ResourceType::Energy)) as u32,
                     )? {
                         TakeResourceResult::Withdraw { .. } => {
-                            creep.say("⏫", false);
+                            speech::say(&creep, "⏫", Verbosity::Status);
                             self.step = Step::Build {
                                 build_target: build_target.to_owned(),
                             };
@@ -173,7 +185,7 @@ impl OokTaskRunnable for Task {
                             match return_code {
                                 screeps::ReturnCode::Ok => {}
                                 screeps::ReturnCode::NotEnough => {
-                                    creep.say("⏫", false);
+                                    speech::say(&creep, "⏫", Verbosity::Status);
                                     self.step = Step::Build {
                                         build_target: build_target.to_owned(),
                                     };
@@ -184,14 +196,14 @@ impl OokTaskRunnable for Task {
                             }
                         }
                         TakeResourceResult::Pickup { .. } => {
-                            creep.say("⏫", false);
+                            speech::say(&creep, "⏫", Verbosity::Status);
                             self.step = Step::Build {
                                 build_target: build_target.to_owned(),
                             };
                         }
                     }
                 } else {
-                    creep.move_to(&target_pos);
+                    move_creep_to(&creep, &target_pos, 1);
                 }
                 OokTaskRunnableResult::Continue
             }
@@ -200,7 +212,7 @@ impl OokTaskRunnable for Task {
                     if creep.pos().in_range_to(&build_target.pos, 3) {
                         creep.build(&construction_site);
                     } else {
-                        creep.move_to(&construction_site);
+                        move_creep_to(&creep, &construction_site.pos(), 3);
                     }
                     OokTaskRunnableResult::Continue
                 } else {
@@ -209,7 +221,7 @@ impl OokTaskRunnable for Task {
                         .ok_or_else(|| anyhow!("Room not found for creep {:?}", creep.id()))?;
                     if creep_room.name() != build_target.pos.room_name() {
                         // We just don't see the room
-                        creep.move_to(&build_target.pos);
+                        move_creep_to(&creep, &build_target.pos, 3);
                         OokTaskRunnableResult::Continue
                     } else {
                         // Construction site gone
@@ -218,7 +230,7 @@ impl OokTaskRunnable for Task {
                 }
             }
             Step::WaitForResource { .. } => {
-                creep.say("⏱ ", false);
+                speech::say(&creep, "⏱ ", Verbosity::Status);
                 OokTaskRunnableResult::Continue
             }
         })
@@ -228,7 +240,7 @@ impl OokTaskRunnable for Task {
 impl<'a> FetchesFromResourceProvider<'a> for Task {
     fn calc_resource_provider(
         &self,
-        rooms_state: &'a HashMap<screeps::RoomName, RoomState>,
+        state: &'a BWState,
         race: &'a OokRace,
     ) -> Result<Option<CalcResourceProviderResult>> {
         // Mining in the same room as the build target should make sense most of the time
@@ -236,16 +248,20 @@ impl<'a> FetchesFromResourceProvider<'a> for Task {
         let creep = race.creep()?;
         let room = rooms::get(target_room_name)
             .ok_or_else(|| anyhow!("Room not found {}", target_room_name))?;
-        let room_state = rooms_state
+        let room_state = state
+            .room_states
             .get(&target_room_name)
             .ok_or_else(|| anyhow!("Room state not found"))?;
         let amount = creep.store_free_capacity(Some(ResourceType::Energy));
         match room_state {
-            RoomState::Base(room_state) => generic_calc_energy_resource_provider(
+            RoomState::Base(room_state) => generic_calc_resource_provider(
                 &room_state.resource_providers,
+                &state.provider_reservations,
                 &creep,
                 &room,
+                ResourceType::Energy,
                 amount as u32,
+                true,
             ),
             RoomState::SetupBase(room_state) => {
                 if let SetupBaseStateVisibility::Visible {
@@ -253,11 +269,14 @@ impl<'a> FetchesFromResourceProvider<'a> for Task {
                     ..
                 } = room_state.state
                 {
-                    generic_calc_energy_resource_provider(
+                    generic_calc_resource_provider(
                         resource_providers,
+                        &state.provider_reservations,
                         &creep,
                         &room,
+                        ResourceType::Energy,
                         amount as u32,
+                        true,
                     )
                 } else {
                     Ok(None)
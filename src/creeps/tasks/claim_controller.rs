@@ -1,50 +1,52 @@
 use std::fmt;
 
 use log::warn;
-use screeps::{HasPosition, Position, RoomName, RoomObjectProperties, SharedCreepProperties, StructureController, game::rooms};
+use screeps::{
+    game::rooms, HasPosition, Position, RoomName, RoomObjectProperties, SharedCreepProperties,
+    StructureController,
+};
 
 use crate::{
-    creeps::races::{OokRace, RepresentsCreep},
+    constants::MY_USERNAME,
+    creeps::{
+        movement::move_creep_to,
+        races::{OokRace, RepresentsCreep},
+    },
+    rooms::signs,
     state::BWState,
 };
-use anyhow::{Result, anyhow, bail};
+use anyhow::{anyhow, bail, Result};
 
 use super::{OokTaskRunnable, OokTaskRunnableResult};
 
+/// Ticks left on the reservation of the controller, if someone else than me holds it
+pub fn foreign_reservation_ticks(controller: &StructureController) -> Option<u32> {
+    controller
+        .reservation()
+        .filter(|reservation| reservation.username != MY_USERNAME)
+        .map(|reservation| reservation.ticks_to_end)
+}
 
 #[derive(Clone, Debug)]
 pub enum ControllerPosToClaim {
-    InRoom {
-        room_name: RoomName,
-    },
-    KnowingPos {
-        pos: Position,
-    },
+    InRoom { room_name: RoomName },
+    KnowingPos { pos: Position },
 }
 
 #[derive(Clone)]
 pub enum Step {
-    Move {
-        pos: ControllerPosToClaim,
-    },
-    Claim {
-        controller: StructureController,
-    },
+    Move { pos: ControllerPosToClaim },
+    Claim { controller: StructureController },
 }
 
 impl fmt::Debug for Step {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Step::Move { pos } => {
-                f.debug_struct("Step::Move")
-                    .field("pos", pos)
-                    .finish()
-            },
-            Step::Claim { controller } => {
-                f.debug_struct("Step::Claim")
-                    .field("controller in room", &controller.room().map(|r| r.name()))
-                    .finish()
-            },
+            Step::Move { pos } => f.debug_struct("Step::Move").field("pos", pos).finish(),
+            Step::Claim { controller } => f
+                .debug_struct("Step::Claim")
+                .field("controller in room", &controller.room().map(|r| r.name()))
+                .finish(),
         }
     }
 }
@@ -52,20 +54,45 @@ impl fmt::Debug for Step {
 #[derive(Debug, Clone)]
 pub struct Task {
     step: Step,
+    /// Reserve the controller instead of claiming it
+    reserve: bool,
 }
 
 impl Task {
     pub fn new(state: &BWState, citizen: &OokRace, target_controller_at: RoomName) -> Result<Self> {
+        Self::with_mode(state, citizen, target_controller_at, false)
+    }
+
+    /// Like `new`, but keeps reserving the controller until the creep dies
+    pub fn reserving(
+        state: &BWState,
+        citizen: &OokRace,
+        target_controller_at: RoomName,
+    ) -> Result<Self> {
+        Self::with_mode(state, citizen, target_controller_at, true)
+    }
+
+    fn with_mode(
+        state: &BWState,
+        citizen: &OokRace,
+        target_controller_at: RoomName,
+        reserve: bool,
+    ) -> Result<Self> {
         let room = rooms::get(target_controller_at);
         let controller_pos = if let Some(Some(controller)) = room.map(|r| r.controller()) {
-            ControllerPosToClaim::KnowingPos{ pos: controller.pos() }
+            ControllerPosToClaim::KnowingPos {
+                pos: controller.pos(),
+            }
         } else {
-            ControllerPosToClaim::InRoom{ room_name: target_controller_at }
+            ControllerPosToClaim::InRoom {
+                room_name: target_controller_at,
+            }
         };
         let mut task = Task {
             step: Step::Move {
-                pos: controller_pos
+                pos: controller_pos,
             },
+            reserve,
         };
         task.precheck(state, citizen)?;
         Ok(task)
@@ -74,40 +101,40 @@ impl Task {
     fn precheck(&mut self, _state: &BWState, citizen: &OokRace) -> Result<()> {
         let creep = citizen.creep()?;
         match &mut self.step {
-            Step::Move { pos } => {
-                match pos {
-                    ControllerPosToClaim::InRoom { room_name } => {
-                        let room = creep.room().ok_or(anyhow!("Could not get room from creep"))?;
-                        if room.name() == *room_name {
-                            if let Some(controller) = room.controller() {
-                                *pos = ControllerPosToClaim::KnowingPos {
-                                    pos: controller.pos(),
-                                };
-                            } else {
-                                bail!("Room {} has no controller, cannot claim!", room_name);
-                            }
+            Step::Move { pos } => match pos {
+                ControllerPosToClaim::InRoom { room_name } => {
+                    let room = creep
+                        .room()
+                        .ok_or(anyhow!("Could not get room from creep"))?;
+                    if room.name() == *room_name {
+                        if let Some(controller) = room.controller() {
+                            *pos = ControllerPosToClaim::KnowingPos {
+                                pos: controller.pos(),
+                            };
+                        } else {
+                            bail!("Room {} has no controller, cannot claim!", room_name);
                         }
-                    },
-                    ControllerPosToClaim::KnowingPos { pos } => {
-                        let room = creep.room().ok_or(anyhow!("Could not get room from creep"))?;
-                        if room.name() == pos.room_name() && pos.is_near_to(&creep.pos()) {
-                            if let Some(controller) = room.controller() {
-                                self.step = Step::Claim {
-                                    controller,
-                                };
-                            } else {
-                                bail!("Room {} has no controller, cannot claim!", room.name());
-                            }
+                    }
+                }
+                ControllerPosToClaim::KnowingPos { pos } => {
+                    let room = creep
+                        .room()
+                        .ok_or(anyhow!("Could not get room from creep"))?;
+                    if room.name() == pos.room_name() && pos.is_near_to(&creep.pos()) {
+                        if let Some(controller) = room.controller() {
+                            self.step = Step::Claim { controller };
+                        } else {
+                            bail!("Room {} has no controller, cannot claim!", room.name());
                         }
-                    },
+                    }
                 }
-            }
+            },
             Step::Claim { controller } => {
                 if !controller.pos().in_range_to(&creep.pos(), 3) {
                     self.step = Step::Move {
                         pos: ControllerPosToClaim::KnowingPos {
                             pos: controller.pos(),
-                        }
+                        },
                     };
                 }
             }
@@ -124,22 +151,54 @@ impl OokTaskRunnable for Task {
             Step::Move { pos } => {
                 match pos {
                     ControllerPosToClaim::InRoom { room_name } => {
-                        creep.move_to(&Position::new(25, 25, *room_name));
-                    },
+                        move_creep_to(&creep, &Position::new(25, 25, *room_name), 20);
+                    }
                     ControllerPosToClaim::KnowingPos { pos } => {
-                        creep.move_to(pos);
-                    },
+                        move_creep_to(&creep, pos, 1);
+                    }
                 };
-            },
+                signs::sign_in_passing(&creep);
+            }
             Step::Claim { controller } => {
-                let return_code = creep.claim_controller(&controller);
-                match return_code {
-                    screeps::ReturnCode::Ok => {},
-                    _ => {
-                        warn!("Could not claim controller, return code {:?}", return_code);
-                    },
+                signs::sign(&creep, controller);
+                // The stored controller is from an older tick, its reservation may be outdated
+                let current = creep
+                    .room()
+                    .and_then(|room| room.controller())
+                    .unwrap_or_else(|| controller.clone());
+                if let Some(ticks) = foreign_reservation_ticks(&current) {
+                    // Nobody claims or reserves before the reservation of someone else is gone
+                    let return_code = creep.attack_controller(&current);
+                    match return_code {
+                        screeps::ReturnCode::Ok => {}
+                        _ => {
+                            warn!(
+                                "Could not attack reservation ({} ticks left), return code {:?}",
+                                ticks, return_code
+                            );
+                        }
+                    }
+                } else if self.reserve {
+                    let return_code = creep.reserve_controller(&controller);
+                    match return_code {
+                        screeps::ReturnCode::Ok => {}
+                        _ => {
+                            warn!(
+                                "Could not reserve controller, return code {:?}",
+                                return_code
+                            );
+                        }
+                    }
+                } else {
+                    let return_code = creep.claim_controller(&controller);
+                    match return_code {
+                        screeps::ReturnCode::Ok => {}
+                        _ => {
+                            warn!("Could not claim controller, return code {:?}", return_code);
+                        }
+                    }
                 }
-            },
+            }
         }
         Ok(OokTaskRunnableResult::Continue)
     }
@@ -0,0 +1,77 @@
+use screeps::{find, game::rooms, HasPosition, Position, RoomName, SharedCreepProperties};
+
+use crate::{
+    creeps::{
+        movement::move_creep_to,
+        races::{OokRace, RepresentsCreep},
+    },
+    rooms::defense,
+    state::BWState,
+    utils::AnyhowOptionExt,
+};
+use anyhow::Result;
+
+use super::{OokTaskRunnable, OokTaskRunnableResult};
+
+/// Stands on a choke point rampart and hits whatever comes next to it
+#[derive(Debug, Clone)]
+pub struct Task {
+    room_name: RoomName,
+    rampart: Option<Position>,
+}
+
+impl Task {
+    pub fn new(room_name: RoomName) -> Self {
+        Task {
+            room_name,
+            rampart: None,
+        }
+    }
+}
+
+impl OokTaskRunnable for Task {
+    fn run(&mut self, _state: &mut BWState, race: &OokRace) -> Result<OokTaskRunnableResult> {
+        let creep = race.creep()?;
+        let room = rooms::get(self.room_name).anyhow("defend room not visible")?;
+        let choke_points = defense::choke_points(&room);
+        if choke_points.is_empty() {
+            // Raid is over
+            return Ok(OokTaskRunnableResult::Finish);
+        }
+
+        let keeps_rampart = self
+            .rampart
+            .map(|pos| choke_points.contains(&pos))
+            .unwrap_or(false);
+        if !keeps_rampart {
+            let others: Vec<Position> = room
+                .find(find::MY_CREEPS)
+                .into_iter()
+                .filter(|c| c.name() != creep.name())
+                .map(|c| c.pos())
+                .collect();
+            self.rampart = choke_points
+                .into_iter()
+                .find(|pos| !others.contains(pos))
+                .or(self.rampart);
+        }
+        if let Some(rampart) = self.rampart {
+            if creep.pos() != rampart {
+                move_creep_to(&creep, &rampart, 0);
+            }
+        }
+
+        let focus = defense::focus_target(&room);
+        let target = match focus {
+            Some(focus) if creep.pos().is_near_to(&focus.pos()) => Some(focus),
+            _ => defense::hostiles(&room)
+                .into_iter()
+                .filter(|h| creep.pos().is_near_to(&h.pos()))
+                .min_by_key(|h| h.hits()),
+        };
+        if let Some(target) = target {
+            creep.attack(&target);
+        }
+        Ok(OokTaskRunnableResult::Continue)
+    }
+}
@@ -0,0 +1,123 @@
+use log::warn;
+use screeps::{
+    find, game::rooms, HasPosition, HasStore, Position, ResourceType, ReturnCode,
+    SharedCreepProperties, Structure,
+};
+
+use crate::{
+    creeps::{
+        jobs,
+        movement::move_creep_to,
+        races::{OokRace, RepresentsCreep},
+    },
+    state::{get_object_cached, BWState},
+    utils::AnyhowOptionExt,
+};
+use anyhow::Result;
+
+use super::{OokTaskRunnable, OokTaskRunnableResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    Dismantle,
+    Deliver,
+}
+
+/// Tears down the targets of a `Dismantle` job, full loads of energy go to the home storage
+#[derive(Debug, Clone)]
+pub struct Task {
+    job: jobs::Dismantle,
+    step: Step,
+}
+
+impl Task {
+    pub fn new(job: jobs::Dismantle) -> Self {
+        Task {
+            job,
+            step: Step::Dismantle,
+        }
+    }
+
+    fn dismantle(&mut self, race: &OokRace) -> Result<OokTaskRunnableResult> {
+        let creep = race.creep()?;
+        let carries = creep.store_capacity(None) > 0;
+        if carries && creep.store_free_capacity(Some(ResourceType::Energy)) <= 0 {
+            self.step = Step::Deliver;
+            return self.deliver(race);
+        }
+        if creep.pos().room_name() != self.job.target_room {
+            move_creep_to(&creep, &Position::new(25, 25, self.job.target_room), 20);
+            return Ok(OokTaskRunnableResult::Continue);
+        }
+        let target = self
+            .job
+            .targets
+            .iter()
+            .filter_map(|id| get_object_cached(*id).ok().flatten())
+            .min_by_key(|s| s.pos().get_range_to(&creep.pos()));
+        let target: Structure = match target {
+            Some(target) => target,
+            None => {
+                // Everything is down
+                if creep.store_used_capacity(Some(ResourceType::Energy)) > 0 {
+                    self.step = Step::Deliver;
+                    return Ok(OokTaskRunnableResult::Continue);
+                }
+                return Ok(OokTaskRunnableResult::Finish);
+            }
+        };
+        if !creep.pos().is_near_to(&target.pos()) {
+            move_creep_to(&creep, &target.pos(), 1);
+            return Ok(OokTaskRunnableResult::Continue);
+        }
+        let code = creep.dismantle(&target);
+        if code != ReturnCode::Ok {
+            warn!("Dismantling {} failed: {:?}", target.id(), code);
+        }
+        Ok(OokTaskRunnableResult::Continue)
+    }
+
+    fn deliver(&mut self, race: &OokRace) -> Result<OokTaskRunnableResult> {
+        let creep = race.creep()?;
+        let amount = creep.store_used_capacity(Some(ResourceType::Energy));
+        if amount == 0 {
+            self.step = Step::Dismantle;
+            return Ok(OokTaskRunnableResult::Continue);
+        }
+        let home = rooms::get(self.job.home_room).anyhow("dismantle home room not visible")?;
+        let code = if let Some(storage) = home.storage() {
+            if !creep.pos().is_near_to(&storage.pos()) {
+                move_creep_to(&creep, &storage.pos(), 1);
+                return Ok(OokTaskRunnableResult::Continue);
+            }
+            creep.transfer_all(&storage, ResourceType::Energy)
+        } else {
+            // Too early for a storage, the spawn takes what it can
+            let spawn = home
+                .find(find::MY_SPAWNS)
+                .into_iter()
+                .next()
+                .anyhow("dismantle home room without spawn")?;
+            if !creep.pos().is_near_to(&spawn.pos()) {
+                move_creep_to(&creep, &spawn.pos(), 1);
+                return Ok(OokTaskRunnableResult::Continue);
+            }
+            creep.transfer_all(&spawn, ResourceType::Energy)
+        };
+        if code != ReturnCode::Ok {
+            warn!("Dismantler couldn't deliver energy: {:?}", code);
+            creep.drop(ResourceType::Energy, None);
+        }
+        self.step = Step::Dismantle;
+        Ok(OokTaskRunnableResult::Continue)
+    }
+}
+
+impl OokTaskRunnable for Task {
+    fn run(&mut self, _state: &mut BWState, race: &OokRace) -> Result<OokTaskRunnableResult> {
+        match self.step {
+            Step::Dismantle => self.dismantle(race),
+            Step::Deliver => self.deliver(race),
+        }
+    }
+}
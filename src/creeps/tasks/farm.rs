@@ -8,8 +8,12 @@ use screeps::{
 };
 
 use crate::{
-    creeps::races::{OokRace, RepresentsCreep},
-    rooms::room_ext::RoomExt,
+    creeps::{
+        movement::move_creep_to,
+        races::{DynamicTasked, OokRace, RepresentsCreep},
+        tasks::OokCreepTask,
+    },
+    rooms::{farm_spots, room_ext::RoomExt},
     state::BWState,
     utils::{AnyhowOptionExt, ResultOptionExt},
 };
@@ -48,8 +52,24 @@ impl Task {
             .get(&target.id())
             .anyhow("farm position not found")?;
         let prioed = prioritized_farm_positions(farm_positions);
+        // A source can have more than one farmer, they shouldn't fight for the same tile
+        let creep_id = race.creep()?.id();
+        let taken: Vec<Position> = state
+            .citizens
+            .iter()
+            .filter(|(id, _)| **id != creep_id)
+            .filter_map(|(_, citizen)| match citizen {
+                OokRace::Worker(worker) => match worker.task() {
+                    Some(OokCreepTask::FarmSource(task)) => Some(task.target_position()),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect();
         let farm_position = prioed
-            .first()
+            .iter()
+            .find(|pos| !taken.contains(&pos.position()))
+            .or_else(|| prioed.first())
             .anyhow(&format!("no farm position found for {}", target.id()))?;
         let mut task = Task {
             step: Step::Walk {
@@ -60,6 +80,10 @@ impl Task {
         Ok(task)
     }
 
+    pub fn target_position(&self) -> Position {
+        self.step.target().position()
+    }
+
     fn precheck(&mut self, _state: &BWState, race: &OokRace) -> Result<()> {
         let creep = race.creep()?;
         match &self.step {
@@ -100,7 +124,7 @@ impl OokTaskRunnable for Task {
                 Ok(OokTaskRunnableResult::Continue)
             }
             Step::Walk { target } => {
-                creep.move_to(&target.position());
+                move_creep_to(&creep, &target.position(), 0);
                 Ok(OokTaskRunnableResult::Continue)
             }
         }
@@ -213,17 +237,14 @@ pub enum FarmShiftTarget {
 
 pub fn prioritized_farm_positions(farm_positions: &Vec<FarmPosition>) -> Vec<FarmPosition> {
     let mut sorted_farm_positions = farm_positions.clone();
-    // Prioritize farm positions by dropping first
-    sorted_farm_positions.sort_by(|pos_a, pos_b| {
-        let pos_a_val = match pos_a {
-            FarmPosition::Dropping(_) => 0,
-            _ => 1,
-        };
-        let pos_b_val = match pos_b {
+    // Dropping into a container first, then next to a container / link, then the best tile
+    sorted_farm_positions.sort_by_cached_key(|farm_position| {
+        let kind_val = match farm_position {
             FarmPosition::Dropping(_) => 0,
-            _ => 1,
+            FarmPosition::Shifting(_) => 1,
+            FarmPosition::Running(_) => 2,
         };
-        pos_a_val.cmp(&pos_b_val)
+        (kind_val, -farm_spots::tile_score(&farm_position.position()))
     });
     sorted_farm_positions
 }
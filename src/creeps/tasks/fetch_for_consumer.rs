@@ -1,16 +1,16 @@
-use std::{cmp, collections::HashMap, fmt};
+use std::{cmp, fmt};
 
 use log::{info, warn};
 use screeps::{
-    game::{get_object_typed, rooms},
+    game::rooms,
     look, HasPosition, HasStore, Position, ResourceType, RoomObjectProperties,
     SharedCreepProperties, Structure,
 };
 
-use crate::{creeps::{CreepRunnerDeliverTarget, CreepRunnerFetchTarget, CreepRunnerState, generic_creep_fetch_from_provider_prio, races::{generic_calc_energy_resource_provider, OokRace, RepresentsCreep}}, rooms::{
+use crate::{creeps::{CreepRunnerDeliverTarget, CreepRunnerFetchTarget, CreepRunnerState, generic_creep_fetch_from_provider_prio, movement::move_creep_to, races::{generic_calc_resource_provider, OokRace, RepresentsCreep}}, rooms::{
         resource_provider::{ResourceData, ResourceProvider, TakeResourceResult},
         room_state::{RoomState, SetupBaseStateVisibility},
-    }, state::BWState, utils::AnyhowOptionExt};
+    }, state::{get_object_cached, BWState}, utils::AnyhowOptionExt};
 use anyhow::{anyhow, Context, Result};
 
 use super::{
@@ -55,13 +55,20 @@ pub struct Task {
     // step: Step,
     // requested_amount: u32,
     pub state: CreepRunnerState,
+    pub resource_type: ResourceType,
 }
 
 impl Task {
     /// TODO Rewrite as RunnerState is legacy stuff
-    pub fn new(runner_state: CreepRunnerState, state: &BWState, race: &OokRace) -> Result<Self> {
+    pub fn new(
+        runner_state: CreepRunnerState,
+        resource_type: ResourceType,
+        state: &BWState,
+        race: &OokRace,
+    ) -> Result<Self> {
         let mut task = Task {
             state: runner_state,
+            resource_type,
         };
         task.precheck(state, race)?;
         Ok(task)
@@ -69,11 +76,12 @@ impl Task {
 
     fn precheck(&mut self, state: &BWState, race: &OokRace) -> Result<bool> {
         let creep = race.creep()?;
+        let resource_type = self.resource_type;
         let state = &self.state;
         match state {
             CreepRunnerState::Fetching { to, .. } => {
-                if creep.store_free_capacity(Some(ResourceType::Energy)) == 0
-                    || creep.store_used_capacity(Some(ResourceType::Energy)) >= to.requested()
+                if creep.store_free_capacity(Some(resource_type)) == 0
+                    || creep.store_used_capacity(Some(resource_type)) >= to.requested()
                 {
                     self.state = CreepRunnerState::Delivering {
                         to: to.clone(),
@@ -82,7 +90,7 @@ impl Task {
                 }
             }
             CreepRunnerState::Delivering { to, provided } => {
-                if creep.store_used_capacity(Some(ResourceType::Energy)) == 0
+                if creep.store_used_capacity(Some(resource_type)) == 0
                     || *provided >= to.requested()
                 {
                     return Ok(false);
@@ -102,6 +110,7 @@ impl OokTaskRunnable for Task {
         let room = creep
             .room()
             .ok_or_else(|| anyhow!("task fetchfc Room not found"))?;
+        let resource_type = self.resource_type;
         let state = &mut self.state;
 
         match state {
@@ -109,32 +118,42 @@ impl OokTaskRunnable for Task {
                 if creep.pos().is_near_to(&from.pos()) {
                     match from {
                         CreepRunnerFetchTarget::PermanentFarmerContainer { id, .. } => {
-                            let obj = get_object_typed(*id)?
+                            let obj = get_object_cached(*id)?
                                 .ok_or_else(|| anyhow!("fetchfc farmer container not found"))?;
                             let amount = cmp::min(
                                 // to.requested(),
-                                creep.store_free_capacity(Some(ResourceType::Energy)) as u32,
-                                obj.store_used_capacity(Some(ResourceType::Energy)),
+                                creep.store_free_capacity(Some(resource_type)) as u32,
+                                obj.store_used_capacity(Some(resource_type)),
                             );
-                            creep.withdraw_amount(&obj, ResourceType::Energy, amount);
+                            creep.withdraw_amount(&obj, resource_type, amount);
                             Ok(OokTaskRunnableResult::Continue)
                         }
                         CreepRunnerFetchTarget::Ruin { id, .. } => {
-                            let obj = get_object_typed(*id)?
+                            let obj = get_object_cached(*id)?
                                 .ok_or_else(|| anyhow!("fetchfc ruin not found"))?;
                             let amount = cmp::min(
-                                creep.store_free_capacity(Some(ResourceType::Energy)) as u32,
+                                creep.store_free_capacity(Some(resource_type)) as u32,
                                 // HACK stupid if I fill one extension requesting 50 energy
                                 // cmp::min(
                                 //     to.requested(),
-                                obj.store_used_capacity(Some(ResourceType::Energy)),
+                                obj.store_used_capacity(Some(resource_type)),
                                 // ),
                             );
-                            creep.withdraw_amount(&obj, ResourceType::Energy, amount);
+                            creep.withdraw_amount(&obj, resource_type, amount);
+                            Ok(OokTaskRunnableResult::Continue)
+                        }
+                        CreepRunnerFetchTarget::Tombstone { id, .. } => {
+                            let obj = get_object_cached(*id)?
+                                .ok_or_else(|| anyhow!("fetchfc tombstone not found"))?;
+                            let amount = cmp::min(
+                                creep.store_free_capacity(Some(resource_type)) as u32,
+                                obj.store_used_capacity(Some(resource_type)),
+                            );
+                            creep.withdraw_amount(&obj, resource_type, amount);
                             Ok(OokTaskRunnableResult::Continue)
                         }
                         CreepRunnerFetchTarget::DroppedSource { id, pos, .. } => {
-                            let obj = get_object_typed(*id)?;
+                            let obj = get_object_cached(*id)?;
                             let farmer_container = room.look_for_at(look::STRUCTURES, pos);
 
                             if let Some(obj) = obj {
@@ -150,9 +169,9 @@ impl OokTaskRunnable for Task {
                                             // to.requested(),
                                             // HACK Based on the run, it should take all or ony
                                             // some energy
-                                            creep.store_free_capacity(Some(ResourceType::Energy)),
+                                            creep.store_free_capacity(Some(resource_type)),
                                             container
-                                                .store_used_capacity(Some(ResourceType::Energy))
+                                                .store_used_capacity(Some(resource_type))
                                                 as i32,
                                         ) - obj.amount() as i32;
                                         info!(
@@ -163,7 +182,7 @@ impl OokTaskRunnable for Task {
                                         if container_amount > 0 {
                                             creep.withdraw_amount(
                                                 container,
-                                                ResourceType::Energy,
+                                                resource_type,
                                                 container_amount as u32,
                                             );
                                         }
@@ -186,14 +205,14 @@ impl OokTaskRunnable for Task {
                                             // to.requested(),
                                             // HACK Based on the run, it should take all or ony
                                             // some energy
-                                            creep.store_free_capacity(Some(ResourceType::Energy))
+                                            creep.store_free_capacity(Some(resource_type))
                                                 as u32,
                                             container
-                                                .store_used_capacity(Some(ResourceType::Energy)),
+                                                .store_used_capacity(Some(resource_type)),
                                         );
                                         match creep.withdraw_amount(
                                             container,
-                                            ResourceType::Energy,
+                                            resource_type,
                                             amount,
                                         ) {
                                             screeps::ReturnCode::Ok => {
@@ -211,19 +230,19 @@ impl OokTaskRunnable for Task {
                             }
                         }
                         CreepRunnerFetchTarget::Terminal { id, .. } => {
-                            let obj = get_object_typed(*id)?.anyhow("Terminal not found")?;
+                            let obj = get_object_cached(*id)?.anyhow("Terminal not found")?;
                             let amount = cmp::min(
                                 // to.requested(),
-                                creep.store_free_capacity(Some(ResourceType::Energy)) as u32,
-                                obj.store_used_capacity(Some(ResourceType::Energy)),
+                                creep.store_free_capacity(Some(resource_type)) as u32,
+                                obj.store_used_capacity(Some(resource_type)),
                             );
                             creep
-                                .withdraw_amount(&obj, ResourceType::Energy, amount);
+                                .withdraw_amount(&obj, resource_type, amount);
                             Ok(OokTaskRunnableResult::Continue)
                         }
                     }
                 } else {
-                    creep.move_to(&from.pos());
+                    move_creep_to(&creep, &from.pos(), 1);
                     Ok(OokTaskRunnableResult::Continue)
                 }
             }
@@ -231,79 +250,68 @@ impl OokTaskRunnable for Task {
                 if creep.pos().is_near_to(&to.pos()) {
                     match to {
                         CreepRunnerDeliverTarget::Tower { id, .. } => {
-                            let obj = get_object_typed(*id)?
+                            let obj = get_object_cached(*id)?
                                 .ok_or_else(|| anyhow!("failed getting deliver target"))?;
                             let amount = cmp::min(
                                 to.requested(),
-                                creep.store_used_capacity(Some(ResourceType::Energy)),
+                                creep.store_used_capacity(Some(resource_type)),
                             );
                             creep
-                                .transfer_amount(&obj, ResourceType::Energy, amount);
+                                .transfer_amount(&obj, resource_type, amount);
                             *provided += amount;
                             Ok(OokTaskRunnableResult::Finish)
                         }
                         CreepRunnerDeliverTarget::Extension { id, .. } => {
-                            let obj = get_object_typed(*id)?
+                            let obj = get_object_cached(*id)?
                                 .ok_or_else(|| anyhow!("failed getting deliver target"))?;
                             let amount = cmp::min(
                                 to.requested(),
-                                creep.store_used_capacity(Some(ResourceType::Energy)),
+                                creep.store_used_capacity(Some(resource_type)),
                             );
                             creep
-                                .transfer_amount(&obj, ResourceType::Energy, amount);
+                                .transfer_amount(&obj, resource_type, amount);
                             *provided += amount;
                             Ok(OokTaskRunnableResult::Finish)
                         }
                         CreepRunnerDeliverTarget::Spawn { id, .. } => {
-                            let obj = get_object_typed(*id)?
+                            let obj = get_object_cached(*id)?
                                 .ok_or_else(|| anyhow!("failed getting deliver target"))?;
                             let amount = cmp::min(
                                 to.requested(),
-                                creep.store_used_capacity(Some(ResourceType::Energy)),
+                                creep.store_used_capacity(Some(resource_type)),
                             );
                             creep
-                                .transfer_amount(&obj, ResourceType::Energy, amount);
+                                .transfer_amount(&obj, resource_type, amount);
                             *provided += amount;
                             Ok(OokTaskRunnableResult::Finish)
                         }
                         CreepRunnerDeliverTarget::PermanentUpgraderContainer { id, .. } => {
-                            let obj = get_object_typed(*id)?
+                            let obj = get_object_cached(*id)?
                                 .ok_or_else(|| anyhow!("failed getting deliver target"))?;
                             let amount = cmp::min(
                                 to.requested(),
-                                creep.store_used_capacity(Some(ResourceType::Energy)),
+                                creep.store_used_capacity(Some(resource_type)),
                             );
                             creep
-                                .transfer_amount(&obj, ResourceType::Energy, amount);
+                                .transfer_amount(&obj, resource_type, amount);
                             *provided += amount;
                             Ok(OokTaskRunnableResult::Finish)
                         }
                         CreepRunnerDeliverTarget::TempStorage { id, .. } => {
-                            let obj = get_object_typed(*id)?
+                            let obj = get_object_cached(*id)?
                                 .ok_or_else(|| anyhow!("failed getting deliver target"))?;
                             let amount = cmp::min(
                                 to.requested(),
-                                creep.store_used_capacity(Some(ResourceType::Energy)),
-                            );
-                            creep
-                                .transfer_amount(&obj, ResourceType::Energy, amount);
-                            *provided += amount;
-                            Ok(OokTaskRunnableResult::Finish)
-                        }
-                        CreepRunnerDeliverTarget::TradeTransactionFee { id, .. } => {
-                            let obj = get_object_typed(*id)?.ok_or_else(|| anyhow!("failed getting trade transaciton fee"))?;
-                            let amount = cmp::min(
-                                to.requested(),
-                                creep.store_used_capacity(Some(ResourceType::Energy)),
+                                creep.store_used_capacity(Some(resource_type)),
                             );
                             creep
-                                .transfer_amount(&obj, ResourceType::Energy, amount);
+                                .transfer_amount(&obj, resource_type, amount);
                             *provided += amount;
                             Ok(OokTaskRunnableResult::Finish)
                         }
                     }
                 } else {
-                    creep.move_to(&to.pos());
+                    move_creep_to(&creep, &to.pos(), 1);
                     Ok(OokTaskRunnableResult::Continue)
                 }
             }
@@ -314,21 +322,25 @@ impl OokTaskRunnable for Task {
 impl<'a> FetchesFromResourceProvider<'a> for Task {
     fn calc_resource_provider(
         &self,
-        rooms_state: &'a HashMap<screeps::RoomName, RoomState>,
+        state: &'a BWState,
         race: &'a OokRace,
     ) -> Result<Option<CalcResourceProviderResult>> {
         let creep = race.creep()?;
         let room = creep.room().ok_or(anyhow!("room of creep not found"))?;
-        let room_state = rooms_state
+        let room_state = state
+            .room_states
             .get(&room.name())
             .ok_or_else(|| anyhow!("Room not found"))?;
-        let amount = creep.store_free_capacity(Some(ResourceType::Energy));
+        let amount = creep.store_free_capacity(Some(self.resource_type));
         match room_state {
-            RoomState::Base(room_state) => generic_calc_energy_resource_provider(
+            RoomState::Base(room_state) => generic_calc_resource_provider(
                 &room_state.resource_providers,
+                &state.provider_reservations,
                 &creep,
                 &room,
+                self.resource_type,
                 amount as u32,
+                false,
             ),
             RoomState::SetupBase(room_state) => {
                 if let SetupBaseStateVisibility::Visible {
@@ -336,11 +348,14 @@ impl<'a> FetchesFromResourceProvider<'a> for Task {
                     ..
                 } = room_state.state
                 {
-                    generic_calc_energy_resource_provider(
+                    generic_calc_resource_provider(
                         &resource_providers,
+                        &state.provider_reservations,
                         &creep,
                         &room,
+                        self.resource_type,
                         amount as u32,
+                        false,
                     )
                 } else {
                     Ok(None)
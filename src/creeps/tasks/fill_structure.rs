@@ -0,0 +1,147 @@
+use screeps::{HasId, HasPosition, HasStore, SharedCreepProperties};
+
+use crate::{
+    creeps::{
+        movement::move_creep_to,
+        races::{OokRace, RepresentsCreep},
+    },
+    intents::{self, Action, Actor, Priority},
+    rooms::fill_orders::FillOrder,
+    state::{get_object_cached, BWState},
+    utils::AnyhowOptionExt,
+};
+use anyhow::Result;
+
+use super::{OokTaskRunnable, OokTaskRunnableResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    Withdraw,
+    Deliver,
+    /// Brings back what didn't fit
+    Return,
+}
+
+/// Carries one `FillOrder` from the storage or the terminal to a lab or the nuker, any resource
+#[derive(Debug, Clone)]
+pub struct Task {
+    order: FillOrder,
+    step: Step,
+}
+
+impl Task {
+    pub fn new(order: FillOrder) -> Self {
+        Task {
+            order,
+            step: Step::Withdraw,
+        }
+    }
+
+    fn withdraw(&mut self, race: &OokRace) -> Result<OokTaskRunnableResult> {
+        let creep = race.creep()?;
+        // Something else in the cargo would end up in the lab
+        if creep.store_used_capacity(None) > 0 {
+            return Ok(OokTaskRunnableResult::CancelAndDoAnother);
+        }
+        let resource_type = self.order.resource_type;
+        let source = get_object_cached(self.order.source)?.anyhow("Fill source not found")?;
+        if !creep.pos().is_near_to(&source.pos()) {
+            move_creep_to(&creep, &source.pos(), 1);
+            return Ok(OokTaskRunnableResult::Continue);
+        }
+        let available = source
+            .as_has_store()
+            .map(|store| store.store_used_capacity(Some(resource_type)))
+            .unwrap_or(0);
+        let amount = self
+            .order
+            .amount
+            .min(available)
+            .min(creep.store_free_capacity(Some(resource_type)).max(0) as u32);
+        if amount == 0 {
+            return Ok(OokTaskRunnableResult::CancelAndDoAnother);
+        }
+        intents::submit(
+            Actor::Creep(creep.id()),
+            Action::Withdraw {
+                target: self.order.source,
+                resource_type,
+                amount,
+            },
+            Priority::Task,
+        );
+        // An empty cargo on the next tick means the withdraw lost or failed, delivering finishes
+        self.step = Step::Deliver;
+        Ok(OokTaskRunnableResult::Continue)
+    }
+
+    fn deliver(&mut self, race: &OokRace) -> Result<OokTaskRunnableResult> {
+        let creep = race.creep()?;
+        let resource_type = self.order.resource_type;
+        let amount = creep.store_used_capacity(Some(resource_type));
+        if amount == 0 {
+            return Ok(OokTaskRunnableResult::Finish);
+        }
+        let target = get_object_cached(self.order.target)?.anyhow("Fill target not found")?;
+        if !creep.pos().is_near_to(&target.pos()) {
+            move_creep_to(&creep, &target.pos(), 1);
+            return Ok(OokTaskRunnableResult::Continue);
+        }
+        let free = target
+            .as_has_store()
+            .map(|store| store.store_free_capacity(Some(resource_type)).max(0) as u32)
+            .unwrap_or(0);
+        if free == 0 {
+            self.step = Step::Return;
+            return self.bring_back(race);
+        }
+        intents::submit(
+            Actor::Creep(creep.id()),
+            Action::Transfer {
+                target: self.order.target,
+                resource_type,
+                amount: amount.min(free),
+            },
+            Priority::Task,
+        );
+        if amount > free {
+            self.step = Step::Return;
+            return Ok(OokTaskRunnableResult::Continue);
+        }
+        Ok(OokTaskRunnableResult::Finish)
+    }
+
+    fn bring_back(&mut self, race: &OokRace) -> Result<OokTaskRunnableResult> {
+        let creep = race.creep()?;
+        let resource_type = self.order.resource_type;
+        let amount = creep.store_used_capacity(Some(resource_type));
+        if amount == 0 {
+            return Ok(OokTaskRunnableResult::Finish);
+        }
+        let source = get_object_cached(self.order.source)?.anyhow("Fill source not found")?;
+        if !creep.pos().is_near_to(&source.pos()) {
+            move_creep_to(&creep, &source.pos(), 1);
+            return Ok(OokTaskRunnableResult::Continue);
+        }
+        intents::submit(
+            Actor::Creep(creep.id()),
+            Action::Transfer {
+                target: self.order.source,
+                resource_type,
+                amount,
+            },
+            Priority::Task,
+        );
+        Ok(OokTaskRunnableResult::Finish)
+    }
+}
+
+impl OokTaskRunnable for Task {
+    fn run(&mut self, _state: &mut BWState, race: &OokRace) -> Result<OokTaskRunnableResult> {
+        match self.step {
+            Step::Withdraw => self.withdraw(race),
+            Step::Deliver => self.deliver(race),
+            Step::Return => self.bring_back(race),
+        }
+    }
+}
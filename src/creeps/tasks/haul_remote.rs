@@ -0,0 +1,108 @@
+use log::warn;
+use screeps::{
+    game::rooms, HasPosition, HasStore, Position, ResourceType, ReturnCode, RoomName,
+    RoomObjectProperties, SharedCreepProperties,
+};
+
+use crate::{
+    creeps::{
+        movement::move_creep_to,
+        races::{OokRace, RepresentsCreep},
+    },
+    rooms::remotes,
+    state::BWState,
+    utils::AnyhowOptionExt,
+};
+use anyhow::Result;
+
+use super::{OokTaskRunnable, OokTaskRunnableResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    Fetch,
+    Deliver,
+}
+
+/// Empties the source containers of a remote into the storage of its base, see
+/// `remotes::hauler_carry_parts` for how much CARRY it takes
+#[derive(Debug, Clone)]
+pub struct Task {
+    remote: RoomName,
+    base: RoomName,
+    step: Step,
+}
+
+impl Task {
+    pub fn new(remote: RoomName, base: RoomName) -> Self {
+        Task {
+            remote,
+            base,
+            step: Step::Fetch,
+        }
+    }
+
+    fn fetch(&mut self, race: &OokRace) -> Result<OokTaskRunnableResult> {
+        let creep = race.creep()?;
+        if creep.store_free_capacity(Some(ResourceType::Energy)) <= 0 {
+            self.step = Step::Deliver;
+            return self.deliver(race);
+        }
+        if creep.pos().room_name() != self.remote {
+            move_creep_to(&creep, &Position::new(25, 25, self.remote), 20);
+            return Ok(OokTaskRunnableResult::Continue);
+        }
+        let room = creep.room().anyhow("hauler without room")?;
+        let container = match remotes::fullest_source_container(&room) {
+            Some(container) => container,
+            None => {
+                // The containers are gone, what it has still goes home
+                if creep.store_used_capacity(Some(ResourceType::Energy)) > 0 {
+                    self.step = Step::Deliver;
+                }
+                return Ok(OokTaskRunnableResult::Continue);
+            }
+        };
+        if !creep.pos().is_near_to(&container.pos()) {
+            move_creep_to(&creep, &container.pos(), 1);
+            return Ok(OokTaskRunnableResult::Continue);
+        }
+        // Waits next to it for the miners to fill it up
+        if container.store_used_capacity(Some(ResourceType::Energy)) == 0 {
+            return Ok(OokTaskRunnableResult::Continue);
+        }
+        let code = creep.withdraw_all(&container, ResourceType::Energy);
+        if code != ReturnCode::Ok {
+            warn!("Hauling from {} failed: {:?}", self.remote, code);
+        }
+        Ok(OokTaskRunnableResult::Continue)
+    }
+
+    fn deliver(&mut self, race: &OokRace) -> Result<OokTaskRunnableResult> {
+        let creep = race.creep()?;
+        if creep.store_used_capacity(Some(ResourceType::Energy)) == 0 {
+            self.step = Step::Fetch;
+            return Ok(OokTaskRunnableResult::Continue);
+        }
+        let storage = rooms::get(self.base)
+            .and_then(|room| room.storage())
+            .anyhow("hauler has no storage to deliver to")?;
+        if !creep.pos().is_near_to(&storage.pos()) {
+            move_creep_to(&creep, &storage.pos(), 1);
+            return Ok(OokTaskRunnableResult::Continue);
+        }
+        let code = creep.transfer_all(&storage, ResourceType::Energy);
+        if code != ReturnCode::Ok {
+            warn!("Hauler couldn't deliver to {}: {:?}", self.base, code);
+        }
+        Ok(OokTaskRunnableResult::Continue)
+    }
+}
+
+impl OokTaskRunnable for Task {
+    fn run(&mut self, _state: &mut BWState, race: &OokRace) -> Result<OokTaskRunnableResult> {
+        match self.step {
+            Step::Fetch => self.fetch(race),
+            Step::Deliver => self.deliver(race),
+        }
+    }
+}
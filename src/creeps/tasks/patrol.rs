@@ -0,0 +1,107 @@
+use screeps::{find, Creep, HasPosition, Part, Position, RoomName, SharedCreepProperties};
+
+use crate::{
+    creeps::{
+        movement::move_creep_to,
+        races::{OokRace, RepresentsCreep},
+    },
+    rooms::{defense::threats, remotes, signs},
+    state::BWState,
+};
+use anyhow::Result;
+
+use super::{OokTaskRunnable, OokTaskRunnableResult};
+
+const RANGED_ATTACK_RANGE: u32 = 3;
+/// Melee hostiles closer than this get kited
+const KITE_RANGE: u32 = 2;
+
+/// Walks the remotes of a base and shoots the hostiles in them, staying out of melee reach
+#[derive(Debug, Clone)]
+pub struct Task {
+    base_room: RoomName,
+    /// Remote walked to while none is threatened
+    patrol_index: usize,
+}
+
+impl Task {
+    pub fn new(base_room: RoomName) -> Self {
+        Task {
+            base_room,
+            patrol_index: 0,
+        }
+    }
+
+    /// Threatened remotes first, then the remotes of players retaliated on, otherwise the next one
+    /// of the round
+    fn target_room(&mut self, creep: &Creep) -> Option<RoomName> {
+        if let Some(room) = remotes::threatened_remotes_of(self.base_room).first() {
+            return Some(*room);
+        }
+        if let Some(room) = threats::harass_targets(self.base_room).first() {
+            return Some(*room);
+        }
+        let remotes = remotes::remotes_of(self.base_room);
+        if remotes.is_empty() {
+            return None;
+        }
+        let mut room = remotes[self.patrol_index % remotes.len()];
+        if creep.pos().room_name() == room {
+            self.patrol_index += 1;
+            room = remotes[self.patrol_index % remotes.len()];
+        }
+        Some(room)
+    }
+
+    fn fight(&self, creep: &Creep, hostiles: Vec<Creep>) {
+        let closest = match hostiles
+            .iter()
+            .min_by_key(|h| h.pos().get_range_to(&creep.pos()))
+        {
+            Some(closest) => closest,
+            None => return,
+        };
+        let range = closest.pos().get_range_to(&creep.pos());
+        if range <= RANGED_ATTACK_RANGE {
+            creep.ranged_attack(closest);
+        }
+        if range <= KITE_RANGE && closest.get_active_bodyparts(Part::Attack) > 0 {
+            // Step away, it can't hit back from further out
+            if let Some(direction) = closest.pos().get_direction_to(&creep.pos()) {
+                creep.move_direction(direction);
+            }
+        } else if range > RANGED_ATTACK_RANGE {
+            move_creep_to(creep, &closest.pos(), RANGED_ATTACK_RANGE);
+        }
+    }
+}
+
+impl OokTaskRunnable for Task {
+    fn run(&mut self, _state: &mut BWState, race: &OokRace) -> Result<OokTaskRunnableResult> {
+        let creep = race.creep()?;
+        if let Some(room) = creep.room() {
+            let hostiles = room.find(find::HOSTILE_CREEPS);
+            if !hostiles.is_empty() {
+                self.fight(&creep, hostiles);
+                return Ok(OokTaskRunnableResult::Continue);
+            }
+        }
+        // Quiet remote, time to leave my mark
+        let in_remote = remotes::remotes_of(self.base_room).contains(&creep.pos().room_name());
+        if let Some(controller) = signs::unsigned_controller(&creep).filter(|_| in_remote) {
+            if creep.pos().is_near_to(&controller.pos()) {
+                signs::sign(&creep, &controller);
+            } else {
+                move_creep_to(&creep, &controller.pos(), 1);
+            }
+            return Ok(OokTaskRunnableResult::Continue);
+        }
+        match self.target_room(&creep) {
+            Some(room) => {
+                move_creep_to(&creep, &Position::new(25, 25, room), 20);
+                Ok(OokTaskRunnableResult::Continue)
+            }
+            None => Ok(OokTaskRunnableResult::Finish),
+        }
+    }
+}
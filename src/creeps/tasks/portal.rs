@@ -0,0 +1,67 @@
+use screeps::{find, game::rooms, HasPosition, Position, SharedCreepProperties, Structure};
+
+use crate::{
+    creeps::{
+        jobs,
+        movement::move_creep_to,
+        races::{OokRace, RepresentsCreep},
+        speech::{self, Verbosity},
+    },
+    shards,
+    state::BWState,
+};
+use anyhow::Result;
+
+use super::{OokTaskRunnable, OokTaskRunnableResult};
+
+/// Walks into the portal of a `CrossPortal` job, the creep leaves the shard with the step onto it
+#[derive(Debug, Clone)]
+pub struct Task {
+    job: jobs::CrossPortal,
+    recorded: bool,
+}
+
+impl Task {
+    pub fn new(job: jobs::CrossPortal) -> Self {
+        Task {
+            job,
+            recorded: false,
+        }
+    }
+}
+
+impl OokTaskRunnable for Task {
+    fn run(&mut self, _state: &mut BWState, race: &OokRace) -> Result<OokTaskRunnableResult> {
+        let creep = race.creep()?;
+        if !self.recorded {
+            // The memory stays here, the shard on the other side asks for the job by name
+            shards::record_sent(&creep.name(), &self.job.target_shard, self.job.target_room);
+            self.recorded = true;
+        }
+        let room = match rooms::get(self.job.portal_room) {
+            Some(room) if creep.pos().room_name() == self.job.portal_room => room,
+            _ => {
+                move_creep_to(&creep, &Position::new(25, 25, self.job.portal_room), 20);
+                return Ok(OokTaskRunnableResult::Continue);
+            }
+        };
+        let portal = room
+            .find(find::STRUCTURES)
+            .into_iter()
+            .filter_map(|s| match s {
+                Structure::Portal(portal) => Some(portal.pos()),
+                _ => None,
+            })
+            .min_by_key(|pos| pos.get_range_to(&creep.pos()));
+        match portal {
+            Some(pos) => {
+                move_creep_to(&creep, &pos, 0);
+                Ok(OokTaskRunnableResult::Continue)
+            }
+            None => {
+                speech::say(&creep, "no portal", Verbosity::Status);
+                Ok(OokTaskRunnableResult::Finish)
+            }
+        }
+    }
+}
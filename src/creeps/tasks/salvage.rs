@@ -0,0 +1,143 @@
+use log::warn;
+use screeps::{
+    game::rooms, HasPosition, HasStore, Position, ReturnCode, RoomObjectProperties,
+    SharedCreepProperties, Structure,
+};
+
+use crate::{
+    creeps::{
+        jobs,
+        movement::move_creep_to,
+        races::{OokRace, RepresentsCreep},
+    },
+    game::{owned_rooms, OwnedBy},
+    rooms::{
+        remotes::linear_distance,
+        salvage::{prioritized_loot, LootSource},
+    },
+    state::{get_object_cached, BWState},
+    utils::AnyhowOptionExt,
+};
+use anyhow::Result;
+
+use super::{OokTaskRunnable, OokTaskRunnableResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    Loot,
+    Deliver,
+}
+
+/// Loots the ruins and tombstones of a `Salvage` job, full loads go to the closest terminal
+#[derive(Debug, Clone)]
+pub struct Task {
+    job: jobs::Salvage,
+    step: Step,
+}
+
+impl Task {
+    pub fn new(job: jobs::Salvage) -> Self {
+        Task {
+            job,
+            step: Step::Loot,
+        }
+    }
+
+    fn loot(&mut self, race: &OokRace) -> Result<OokTaskRunnableResult> {
+        let creep = race.creep()?;
+        if creep.store_free_capacity(None) <= 0 {
+            self.step = Step::Deliver;
+            return self.deliver(race);
+        }
+        if creep.pos().room_name() != self.job.target_room {
+            move_creep_to(&creep, &Position::new(25, 25, self.job.target_room), 20);
+            return Ok(OokTaskRunnableResult::Continue);
+        }
+        let room = creep.room().anyhow("salvager without room")?;
+        let loot = match prioritized_loot(&room, creep.pos()).into_iter().next() {
+            Some(loot) => loot,
+            None => {
+                // Everything is looted or gone
+                if creep.store_used_capacity(None) > 0 {
+                    self.step = Step::Deliver;
+                    return Ok(OokTaskRunnableResult::Continue);
+                }
+                return Ok(OokTaskRunnableResult::Finish);
+            }
+        };
+        if !creep.pos().is_near_to(&loot.pos) {
+            move_creep_to(&creep, &loot.pos, 1);
+            return Ok(OokTaskRunnableResult::Continue);
+        }
+        let code = match loot.source {
+            LootSource::Ruin(id) => match get_object_cached(id)? {
+                Some(ruin) => creep.withdraw_all(&ruin, loot.resource_type),
+                None => ReturnCode::NotFound,
+            },
+            LootSource::Tombstone(id) => match get_object_cached(id)? {
+                Some(tombstone) => creep.withdraw_all(&tombstone, loot.resource_type),
+                None => ReturnCode::NotFound,
+            },
+        };
+        if code != ReturnCode::Ok {
+            warn!("Salvaging {:?} failed: {:?}", loot.source, code);
+        }
+        Ok(OokTaskRunnableResult::Continue)
+    }
+
+    /// The terminal closest to the target room with space left, the home storage without one
+    fn destination(&self) -> Option<Structure> {
+        let target_room = self.job.target_room;
+        owned_rooms(OwnedBy::Me)
+            .values()
+            .filter_map(|room| room.terminal())
+            .filter(|terminal| terminal.store_free_capacity(None) > 0)
+            .min_by_key(|terminal| linear_distance(terminal.pos().room_name(), target_room))
+            .map(Structure::Terminal)
+            .or_else(|| {
+                rooms::get(self.job.home_room)
+                    .and_then(|room| room.storage())
+                    .map(Structure::Storage)
+            })
+    }
+
+    fn deliver(&mut self, race: &OokRace) -> Result<OokTaskRunnableResult> {
+        let creep = race.creep()?;
+        let resource_type = creep
+            .store_types()
+            .into_iter()
+            .find(|resource_type| creep.store_used_capacity(Some(*resource_type)) > 0);
+        let resource_type = match resource_type {
+            Some(resource_type) => resource_type,
+            None => {
+                self.step = Step::Loot;
+                return Ok(OokTaskRunnableResult::Continue);
+            }
+        };
+        let destination = self
+            .destination()
+            .anyhow("salvager has no terminal or storage to deliver to")?;
+        if !creep.pos().is_near_to(&destination.pos()) {
+            move_creep_to(&creep, &destination.pos(), 1);
+            return Ok(OokTaskRunnableResult::Continue);
+        }
+        let target = destination
+            .as_transferable()
+            .anyhow("salvage destination not transferable")?;
+        // One resource per tick, the next one goes in the next tick
+        let code = creep.transfer_all(target, resource_type);
+        if code != ReturnCode::Ok {
+            warn!("Salvager couldn't deliver {:?}: {:?}", resource_type, code);
+        }
+        Ok(OokTaskRunnableResult::Continue)
+    }
+}
+
+impl OokTaskRunnable for Task {
+    fn run(&mut self, _state: &mut BWState, race: &OokRace) -> Result<OokTaskRunnableResult> {
+        match self.step {
+            Step::Loot => self.loot(race),
+            Step::Deliver => self.deliver(race),
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use screeps::{Position, RoomName};
+
+use crate::{
+    creeps::{
+        movement::move_creep_to,
+        races::{OokRace, RepresentsCreep},
+    },
+    state::BWState,
+};
+use anyhow::Result;
+
+use super::{OokTaskRunnable, OokTaskRunnableResult};
+
+/// Walks into the room and stays there, the room is visible as long as the creep lives
+#[derive(Debug, Clone)]
+pub struct Task {
+    target_room: RoomName,
+}
+
+impl Task {
+    pub fn new(target_room: RoomName) -> Self {
+        Task { target_room }
+    }
+}
+
+impl OokTaskRunnable for Task {
+    fn run(&mut self, _state: &mut BWState, race: &OokRace) -> Result<OokTaskRunnableResult> {
+        let creep = race.creep()?;
+        // Off the exits, so it doesn't bounce back and forth between the rooms
+        move_creep_to(&creep, &Position::new(25, 25, self.target_room), 20);
+        Ok(OokTaskRunnableResult::Continue)
+    }
+}
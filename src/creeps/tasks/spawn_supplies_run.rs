@@ -1,26 +1,33 @@
-use std::cmp::{self, Reverse};
+use std::cmp;
 use std::collections::{HashMap, HashSet};
 use std::iter::FromIterator;
 
 use log::warn;
 /// Fill extensions and spawns (hopefully) efficiently
 use screeps::{
-    game::{get_object_typed, rooms},
-    FindOptions, HasStore, ObjectId, Path, Position, ResourceType, Room, RoomName, StructureSpawn,
+    game::rooms,
+    HasStore, ObjectId, Position, ResourceType, Room, RoomName, StructureSpawn,
 };
-use screeps::{Creep, HasId, HasPosition, RectStyle, RoomVisual, SharedCreepProperties};
+use screeps::{Creep, HasId, HasPosition, RectStyle, SharedCreepProperties};
 
-use crate::constants::TERMINAL_TRADE_BUFFER;
+use crate::creeps::movement::move_creep_to;
+use crate::creeps::speech::{self, Verbosity};
 use crate::rooms::extensions::StructureSpawnSupply;
-use crate::rooms::resource_provider::{ResourceData, RoomObjectData, TakeResourceResult};
+use crate::{config, trade};
+use crate::visuals::Painter;
+use crate::rooms::resource_provider::{
+    dropped_amount_after, ProviderReservations, ResourceData, RoomObjectData,
+    TakeResourceResult,
+};
 use crate::{
-    creeps::races::{generic_calc_energy_resource_provider, OokRace, RepresentsCreep},
+    creeps::races::{OokRace, RepresentsCreep},
     rooms::{
+        distance::{rank_two_stage, Distance, PATHED_CANDIDATES},
         extensions::{ExtensionFillPath, SuppliersReachPoint},
         resource_provider::ResourceProvider,
         room_state::RoomState,
     },
-    state::BWState,
+    state::{get_object_cached, BWState},
 };
 
 use anyhow::{anyhow, bail, Result};
@@ -40,6 +47,12 @@ pub enum Step {
         open: Vec<SuppliersReachPoint>,
         done: Vec<SuppliersReachPoint>,
     },
+    /// Ran dry before the open points were filled, tops up nearby and goes on with them
+    Refill {
+        target: ResourceProvider,
+        open: Vec<SuppliersReachPoint>,
+        done: Vec<SuppliersReachPoint>,
+    },
 }
 
 #[derive(Clone, Debug)]
@@ -60,7 +73,7 @@ impl Task {
 
     pub fn handling_supplier_points(&self) -> Result<Vec<SuppliersReachPoint>> {
         match &self.step {
-            Step::FillSuppliers { open, done } => {
+            Step::FillSuppliers { open, done } | Step::Refill { open, done, .. } => {
                 let mut points = vec![];
                 points.extend(open);
                 points.extend(done);
@@ -140,12 +153,12 @@ impl Task {
             // TODO store whether we filled up a specific supplier or not
             match supplier {
                 StructureSpawnSupply::Spawn(spawn_id) => {
-                    if let Some(spawn) = get_object_typed(*spawn_id)? {
+                    if let Some(spawn) = get_object_cached(*spawn_id)? {
                         let free_cappa = spawn.store_free_capacity(Some(ResourceType::Energy));
                         if free_cappa > 0 {
                             let amount = cmp::min(creep.energy(), free_cappa as u32);
                             creep.transfer_amount(&spawn, ResourceType::Energy, amount);
-                            creep.say("🚢", false);
+                            speech::say(creep, "🚢", Verbosity::Status);
                             return Ok(Some(amount));
                         }
                     } else {
@@ -154,12 +167,12 @@ impl Task {
                     }
                 }
                 StructureSpawnSupply::Extension(extension_id) => {
-                    if let Some(extension) = get_object_typed(*extension_id)? {
+                    if let Some(extension) = get_object_cached(*extension_id)? {
                         let free_cappa = extension.store_free_capacity(Some(ResourceType::Energy));
                         if free_cappa > 0 {
                             let amount = cmp::min(creep.energy(), free_cappa as u32);
                             creep.transfer_amount(&extension, ResourceType::Energy, amount);
-                            creep.say("🚢", false);
+                            speech::say(creep, "🚢", Verbosity::Status);
                             return Ok(Some(amount));
                         }
                     } else {
@@ -177,14 +190,10 @@ impl Task {
         pos: Position,
         open_supplier_points: Vec<&SuppliersReachPoint>,
     ) -> Result<Option<(SuppliersReachPoint, u32)>> {
-        let mut open_supplier_points = open_supplier_points.clone();
-        open_supplier_points.sort_by_cached_key(|e| {
-            match pos.find_path_to(&e.pos, FindOptions::default().ignore_creeps(true)) {
-                Path::Serialized(p) => room.deserialize_path(&p),
-                Path::Vectorized(p) => p,
-            }
-            .len()
-        });
+        let open_supplier_points =
+            rank_two_stage(open_supplier_points, PATHED_CANDIDATES, true, |e, distance| {
+                -(distance.between(room, &pos, &e.pos) as i32)
+            });
         match open_supplier_points.first() {
             Some(&open_supplier_point) => {
                 let suppliers_ids = open_supplier_point.suppliers.clone();
@@ -193,7 +202,7 @@ impl Task {
                         .into_iter()
                         .fold(0, |acc, supplier| match supplier {
                             StructureSpawnSupply::Spawn(spawn_id) => {
-                                if let Ok(Some(spawn)) = get_object_typed(spawn_id) {
+                                if let Ok(Some(spawn)) = get_object_cached(spawn_id) {
                                     acc + spawn.store_free_capacity(Some(ResourceType::Energy))
                                 } else {
                                     warn!("Spawn not found or error");
@@ -201,7 +210,7 @@ impl Task {
                                 }
                             }
                             StructureSpawnSupply::Extension(extension_id) => {
-                                if let Ok(Some(Extension)) = get_object_typed(extension_id) {
+                                if let Ok(Some(Extension)) = get_object_cached(extension_id) {
                                     acc + Extension.store_free_capacity(Some(ResourceType::Energy))
                                 } else {
                                     warn!("Extension not found or error");
@@ -227,10 +236,15 @@ impl Task {
         let creep = race.creep()?;
         match &self.step {
             Step::Created => {
-                let calc_result = self.calc_resource_provider(&state.room_states, race)?;
+                let calc_result = self.calc_resource_provider(state, race)?;
                 match calc_result {
                     Some(calc_result) => {
-                        creep.say("📦", false);
+                        speech::say(&creep, "📦", Verbosity::Status);
+                        state.provider_reservations.reserve(
+                            &calc_result.resource_provider,
+                            calc_result.resource_type,
+                            calc_result.amount,
+                        );
                         self.step = Step::GetEnergy {
                             target: calc_result.resource_provider,
                         };
@@ -242,13 +256,32 @@ impl Task {
             Step::GetEnergy { .. } => {
                 if creep.store_free_capacity(Some(ResourceType::Energy)) == 0 {
                     self.fill_suppliers(state, race)?;
-                    creep.say("📦✅", false);
+                    speech::say(&creep, "📦✅", Verbosity::Status);
                 }
                 Ok(None)
             }
-            Step::FillSuppliers { open, .. } => {
+            Step::FillSuppliers { open, done } => {
                 if creep.store_used_capacity(Some(ResourceType::Energy)) == 0 {
-                    Ok(Some(OokTaskRunnableResult::CancelAndDoAnother))
+                    if open.len() == 0 {
+                        return Ok(Some(OokTaskRunnableResult::CancelAndDoAnother));
+                    }
+                    match self.refill_provider(state, &creep) {
+                        Some(target) => {
+                            speech::say(&creep, "📦🔁", Verbosity::Status);
+                            state.provider_reservations.reserve(
+                                &target,
+                                ResourceType::Energy,
+                                creep.store_free_capacity(Some(ResourceType::Energy)) as u32,
+                            );
+                            self.step = Step::Refill {
+                                target,
+                                open: open.clone(),
+                                done: done.clone(),
+                            };
+                            Ok(None)
+                        }
+                        None => Ok(Some(OokTaskRunnableResult::CancelAndDoAnother)),
+                    }
                 } else {
                     if open.len() == 0 {
                         Ok(Some(OokTaskRunnableResult::CancelAndDoAnother))
@@ -257,33 +290,80 @@ impl Task {
                     }
                 }
             }
+            Step::Refill { target, .. } => {
+                // Someone else emptied it meanwhile
+                if target.provides(&ResourceType::Energy).unwrap_or(0) == 0 {
+                    Ok(Some(OokTaskRunnableResult::CancelAndDoAnother))
+                } else {
+                    Ok(None)
+                }
+            }
         }
     }
 
+    /// The closest storage or source container within `SPAWN_SUPPLY_REFILL_RANGE` with energy left
+    /// to top up from
+    fn refill_provider(&self, state: &BWState, creep: &Creep) -> Option<ResourceProvider> {
+        let room_state = match state.room_states.get(&self.target_room_name) {
+            Some(RoomState::Base(room_state)) => room_state,
+            _ => return None,
+        };
+        room_state
+            .resource_providers
+            .values()
+            .filter(|provider| match provider {
+                ResourceProvider::LongTermStorage { .. }
+                | ResourceProvider::SourceDump {
+                    room_object_data: RoomObjectData::StorageStructure { .. },
+                } => true,
+                _ => false,
+            })
+            .filter(|provider| {
+                provider
+                    .creep_can_use(creep, ResourceType::Energy)
+                    .unwrap_or(false)
+            })
+            .filter(|provider| {
+                let reserved = state
+                    .provider_reservations
+                    .reserved(provider, ResourceType::Energy);
+                provider
+                    .provides(&ResourceType::Energy)
+                    .unwrap_or(0)
+                    .saturating_sub(reserved)
+                    >= config::SPAWN_SUPPLY_REFILL_MIN_ENERGY
+            })
+            .filter_map(|provider| {
+                let range = creep.pos().get_range_to(&provider.pos().ok()?);
+                Some((provider, range))
+            })
+            .filter(|(_, range)| *range <= config::SPAWN_SUPPLY_REFILL_RANGE)
+            .min_by_key(|(_, range)| *range)
+            .map(|(provider, _)| provider.to_owned())
+    }
+
     fn visualize(&self) {
         if let Step::FillSuppliers { open, done } = &self.step {
-            if let Some(room) = rooms::get(self.target_room_name) {
-                let vis = room.visual();
-                for point in open.iter() {
-                    vis.rect(
-                        point.pos.x() as f32 - 0.5,
-                        point.pos.y() as f32 - 0.5,
-                        1.,
-                        1.,
-                        Some(RectStyle::default().fill("#ccaa33")),
-                    );
-                    // vis.text(pos.0 as f32, pos.1 as f32, num.to_string(), None);
-                }
-                for point in done.iter() {
-                    vis.rect(
-                        point.pos.x() as f32 - 0.5,
-                        point.pos.y() as f32 - 0.5,
-                        1.,
-                        1.,
-                        Some(RectStyle::default().fill("#aacc33")),
-                    );
-                    // vis.text(pos.0 as f32, pos.1 as f32, num.to_string(), None);
-                }
+            let painter = Painter::new(self.target_room_name);
+            for point in open.iter() {
+                painter.rect(
+                    point.pos.x() as f32 - 0.5,
+                    point.pos.y() as f32 - 0.5,
+                    1.,
+                    1.,
+                    Some(RectStyle::default().fill("#ccaa33")),
+                );
+                // painter.text(pos.0 as f32, pos.1 as f32, num.to_string(), None);
+            }
+            for point in done.iter() {
+                painter.rect(
+                    point.pos.x() as f32 - 0.5,
+                    point.pos.y() as f32 - 0.5,
+                    1.,
+                    1.,
+                    Some(RectStyle::default().fill("#aacc33")),
+                );
+                // painter.text(pos.0 as f32, pos.1 as f32, num.to_string(), None);
             }
         }
     }
@@ -299,11 +379,11 @@ impl OokTaskRunnable for Task {
         let res = match &self.step {
             Step::Created => {
                 // precheck didnt find any resource provider
-                creep.say("...", false);
+                speech::say(&creep, "...", Verbosity::Status);
                 Ok(OokTaskRunnableResult::Continue)
             }
             Step::GetEnergy { target } => {
-                creep.say("sgx", false);
+                speech::say(&creep, "sgx", Verbosity::Status);
                 let target_pos = target.pos()?;
                 if creep.pos().is_near_to(&target_pos) {
                     match target.creep_get_resource(
@@ -312,7 +392,7 @@ impl OokTaskRunnable for Task {
                         creep.store_free_capacity(Some(ResourceType::Energy)) as u32,
                     )? {
                         TakeResourceResult::Withdraw { .. } => {
-                            creep.say("⏫", false);
+                            speech::say(&creep, "⏫", Verbosity::Status);
                             self.fill_suppliers(state, race);
                         }
                         TakeResourceResult::Harvest { return_code, .. } => {
@@ -320,7 +400,7 @@ impl OokTaskRunnable for Task {
                             match return_code {
                                 screeps::ReturnCode::Ok => {}
                                 screeps::ReturnCode::NotEnough => {
-                                    creep.say("⏫", false);
+                                    speech::say(&creep, "⏫", Verbosity::Status);
                                     self.fill_suppliers(state, race);
                                 }
                                 _ => {
@@ -329,12 +409,40 @@ impl OokTaskRunnable for Task {
                             }
                         }
                         TakeResourceResult::Pickup { .. } => {
-                            creep.say("⏫", false);
+                            speech::say(&creep, "⏫", Verbosity::Status);
                             self.fill_suppliers(state, race);
                         }
                     }
                 } else {
-                    creep.move_to(&target_pos);
+                    move_creep_to(&creep, &target_pos, 1);
+                }
+                Ok(OokTaskRunnableResult::Continue)
+            }
+            Step::Refill { target, open, done } => {
+                let target_pos = target.pos()?;
+                if creep.pos().is_near_to(&target_pos) {
+                    let taken = target.creep_get_resource(
+                        &creep,
+                        ResourceType::Energy,
+                        creep.store_free_capacity(Some(ResourceType::Energy)) as u32,
+                    )?;
+                    let return_code = match taken {
+                        TakeResourceResult::Withdraw { return_code, .. }
+                        | TakeResourceResult::Harvest { return_code }
+                        | TakeResourceResult::Pickup { return_code } => return_code,
+                    };
+                    if return_code != screeps::ReturnCode::Ok {
+                        warn!("Refill for the spawn supplies failed: {:?}", return_code);
+                        return Ok(OokTaskRunnableResult::CancelAndDoAnother);
+                    }
+                    speech::say(&creep, "⏫", Verbosity::Status);
+                    // The rest of the run stays as planned
+                    self.step = Step::FillSuppliers {
+                        open: open.clone(),
+                        done: done.clone(),
+                    };
+                } else {
+                    move_creep_to(&creep, &target_pos, 1);
                 }
                 Ok(OokTaskRunnableResult::Continue)
             }
@@ -347,7 +455,7 @@ impl OokTaskRunnable for Task {
                             None => {
                                 remove_point = true;
                                 if let Some(next_point) = open.get(1) {
-                                    creep.move_to(&next_point.pos);
+                                    move_creep_to(&creep, &next_point.pos, 0);
                                     Ok(OokTaskRunnableResult::Continue)
                                 } else {
                                     Ok(OokTaskRunnableResult::CancelAndDoAnother)
@@ -355,7 +463,7 @@ impl OokTaskRunnable for Task {
                             }
                         }
                     } else {
-                        creep.move_to(&next_point.pos);
+                        move_creep_to(&creep, &next_point.pos, 0);
                         Ok(OokTaskRunnableResult::Continue)
                     }
                 } else {
@@ -405,21 +513,24 @@ impl OokTaskRunnable for Task {
 impl<'a> FetchesFromResourceProvider<'a> for Task {
     fn calc_resource_provider(
         &self,
-        rooms_state: &'a HashMap<screeps::RoomName, RoomState>,
+        state: &'a BWState,
         race: &'a OokRace,
     ) -> Result<Option<CalcResourceProviderResult>> {
         let creep = race.creep()?;
         let amount = creep.store_free_capacity(Some(ResourceType::Energy));
         let room = rooms::get(self.target_room_name)
             .ok_or_else(|| anyhow!("Room not found {}", self.target_room_name))?;
-        let room_state = rooms_state
+        let room_state = state
+            .room_states
             .get(&self.target_room_name)
             .ok_or_else(|| anyhow!("Room state not found"));
         match room_state? {
-            RoomState::Base(room_state) => carrier_calc_energy_resource_provider(
+            RoomState::Base(room_state) => carrier_calc_resource_provider(
                 &room_state.resource_providers,
+                &state.provider_reservations,
                 &creep,
                 &room,
+                ResourceType::Energy,
                 amount as u32,
             ),
             RoomState::SetupBase(_) => {
@@ -430,15 +541,17 @@ impl<'a> FetchesFromResourceProvider<'a> for Task {
     }
 }
 
-pub fn carrier_calc_energy_resource_provider(
+pub fn carrier_calc_resource_provider(
     resource_providers: &HashMap<String, ResourceProvider>,
+    reservations: &ProviderReservations,
     creep: &Creep,
     room: &Room,
+    resource_type: ResourceType,
     amount: u32,
 ) -> Result<Option<CalcResourceProviderResult>> {
     let working_providers: Vec<&ResourceProvider> = resource_providers
         .iter()
-        .filter_map(|(_id, p)| match p.creep_can_use(&creep) {
+        .filter_map(|(_id, p)| match p.creep_can_use(&creep, resource_type) {
             Ok(true) => Some(p),
             Ok(false) => None,
             Err(err) => {
@@ -447,11 +560,17 @@ pub fn carrier_calc_energy_resource_provider(
             }
         })
         .collect();
-    let prioed = carrier_creep_fetch_from_provider_prio(&room, creep.pos(), working_providers)?;
+    let prioed = carrier_creep_fetch_from_provider_prio(
+        &room,
+        creep.pos(),
+        working_providers,
+        resource_type,
+        reservations,
+    )?;
     match prioed {
         Some(prov) => Ok(Some(CalcResourceProviderResult {
             resource_provider: prov.to_owned(),
-            resource_type: ResourceType::Energy,
+            resource_type,
             amount: amount as u32,
         })),
         None => Ok(None),
@@ -462,55 +581,54 @@ fn carrier_creep_fetch_from_provider_prio<'a>(
     room: &Room,
     creep_pos: Position,
     working_providers: Vec<&'a ResourceProvider>,
+    resource_type: ResourceType,
+    reservations: &ProviderReservations,
 ) -> anyhow::Result<Option<&'a ResourceProvider>> {
-    let mut sorted = working_providers.clone();
-    sorted.sort_by_cached_key(|a| {
-        Reverse(
-            carrier_working_providers_points(room, a, &creep_pos)
-                .unwrap_or(Some(-10000))
-                .unwrap_or(-10000),
-        )
+    let sorted = rank_two_stage(working_providers, PATHED_CANDIDATES, false, |a, distance| {
+        carrier_working_providers_points(room, a, &creep_pos, distance, resource_type, reservations)
+            .unwrap_or(Some(-10000))
+            .unwrap_or(-10000)
     });
-    // sorted.sort_by(|a, b| {
-    //     let a_p = generic_working_providers_points(room, a, &creep_pos)
-    //         .unwrap_or(Some(-10000))
-    //         .unwrap_or(-10000);
-    //     let b_p = generic_working_providers_points(room, b, &creep_pos)
-    //         .unwrap_or(Some(-10000))
-    //         .unwrap_or(-10000);
-    //     a_p.cmp(&b_p).reverse()
-    // });
     Ok(sorted.first().map(|s| *s))
 }
 
-// TODO needs to know the resource type!
 fn carrier_working_providers_points(
     room: &Room,
     prov: &ResourceProvider,
     for_pos: &Position,
+    distance: Distance,
+    resource_type: ResourceType,
+    reservations: &ProviderReservations,
 ) -> anyhow::Result<Option<i32>> {
     let mut points: i32 = 0;
+    let reserved = reservations.reserved(prov, resource_type);
     match prov {
         ResourceProvider::EnergyFarm { .. } => {
             points = -10000;
         }
         ResourceProvider::SourceDump { room_object_data } => {
             points += 200;
-            // TODO Doesnt check which type of resoure yet
+            let path_len = distance.between(room, &room_object_data.pos()?, for_pos);
             let resource_amount = match room_object_data {
                 RoomObjectData::StorageStructure { obj_id } => {
-                    let obj = get_object_typed(*obj_id)?
+                    let obj = get_object_cached(*obj_id)?
                         .ok_or_else(|| anyhow!("object not found {}", *obj_id))?;
                     obj.as_has_store()
-                        .map(|s| s.store_used_capacity(Some(ResourceType::Energy)))
+                        .map(|s| s.store_used_capacity(Some(resource_type)))
                         .unwrap_or(0)
                 }
                 RoomObjectData::Litter { obj_id } => {
-                    let obj = get_object_typed(*obj_id)?
+                    let obj = get_object_cached(*obj_id)?
                         .ok_or_else(|| anyhow!("object not found {}", *obj_id))?;
-                    obj.amount()
+                    if obj.resource_type() == resource_type {
+                        // Whatever is left once we get there
+                        dropped_amount_after(obj.amount(), path_len)
+                    } else {
+                        0
+                    }
                 }
-            };
+            }
+            .saturating_sub(reserved);
             // Poor man's curve
             if resource_amount == 0 {
                 points = 0;
@@ -521,24 +639,17 @@ fn carrier_working_providers_points(
             } else {
                 points += (resource_amount as f32 / 100.).round() as i32;
             }
-            let path = room_object_data
-                .pos()?
-                .find_path_to(for_pos, FindOptions::default());
-            let vec_path = match path {
-                Path::Serialized(p) => room.deserialize_path(&p),
-                Path::Vectorized(p) => p,
-            };
-            points -= vec_path.len() as i32;
+            points -= path_len as i32;
         }
         ResourceProvider::BufferControllerUpgrade { room_object_data } => {
             points += 50;
-            // TODO Doesnt check which type of resoure yet
-            let obj = get_object_typed(room_object_data.obj_id)?
+            let obj = get_object_cached(room_object_data.obj_id)?
                 .ok_or_else(|| anyhow!("object not found {}", room_object_data.obj_id))?;
             let resource_amount = obj
                 .as_has_store()
-                .map(|s| s.store_used_capacity(Some(ResourceType::Energy)))
-                .unwrap_or(0);
+                .map(|s| s.store_used_capacity(Some(resource_type)))
+                .unwrap_or(0)
+                .saturating_sub(reserved);
             // Poor man's curve
             if resource_amount == 0 {
                 points = 0;
@@ -549,58 +660,76 @@ fn carrier_working_providers_points(
             } else {
                 points += (resource_amount as f32 / 100.).round() as i32;
             }
-            let path = room_object_data
-                .pos()?
-                .find_path_to(for_pos, FindOptions::default());
-            let vec_path = match path {
-                Path::Serialized(p) => room.deserialize_path(&p),
-                Path::Vectorized(p) => p,
-            };
-            points -= vec_path.len() as i32 * 3;
+            let path_len = distance.between(room, &room_object_data.pos()?, for_pos);
+            points -= path_len as i32 * 3;
         }
         ResourceProvider::LongTermStorage { room_object_data } => {
             points += 100;
-            // TODO Doesnt check which type of resoure yet
-            let obj = get_object_typed(room_object_data.obj_id)?
+            let obj = get_object_cached(room_object_data.obj_id)?
                 .ok_or_else(|| anyhow!("object not found {}", room_object_data.obj_id))?;
             let resource_amount = obj
                 .as_has_store()
-                .map(|s| s.store_used_capacity(Some(ResourceType::Energy)))
-                .unwrap_or(0);
-            let path = room_object_data
-                .pos()?
-                .find_path_to(for_pos, FindOptions::default());
-            let vec_path = match path {
-                Path::Serialized(p) => room.deserialize_path(&p),
-                Path::Vectorized(p) => p,
-            };
-            points -= vec_path.len() as i32 * 3;
+                .map(|s| s.store_used_capacity(Some(resource_type)))
+                .unwrap_or(0)
+                .saturating_sub(reserved);
+            let path_len = distance.between(room, &room_object_data.pos()?, for_pos);
+            points -= path_len as i32 * 3;
         }
         ResourceProvider::TerminalOverflow { room_object_data } => {
             points += 100;
-            // TODO Doesnt check which type of resoure yet
-            let obj = get_object_typed(room_object_data.obj_id)?.ok_or_else(|| {
+            let obj = get_object_cached(room_object_data.obj_id)?.ok_or_else(|| {
                 anyhow!("object not found {}", room_object_data.obj_id)
             })?;
             let resource_amount = obj
                 .as_has_store()
-                .map(|s| s.store_used_capacity(Some(ResourceType::Energy)))
-                .unwrap_or(0);
-            let overflow_resource_amount = resource_amount as i32 - TERMINAL_TRADE_BUFFER as i32;
+                .map(|s| s.store_used_capacity(Some(resource_type)))
+                .unwrap_or(0)
+                .saturating_sub(reserved);
+            let keep_amount = trade::terminal_keep_amount(room.name(), resource_type);
+            let overflow_resource_amount = resource_amount as i32 - keep_amount as i32;
             if overflow_resource_amount < 0 {
                 // Ensure minimum of energy
                 points = -100;
             } else if overflow_resource_amount > 1000 {
                 points += cmp::max((overflow_resource_amount as f32 / 1000.).round() as i32, 5);
             }
-            let path = room_object_data
-                .pos()?
-                .find_path_to(for_pos, FindOptions::default());
-            let vec_path = match path {
-                Path::Serialized(p) => room.deserialize_path(&p),
-                Path::Vectorized(p) => p,
-            };
-            points -= vec_path.len() as i32 * 3;
+            let path_len = distance.between(room, &room_object_data.pos()?, for_pos);
+            points -= path_len as i32 * 3;
+        }
+        ResourceProvider::Tombstone { tombstone_data } => {
+            let obj = get_object_cached(tombstone_data.obj_id)?
+                .ok_or_else(|| anyhow!("object not found {}", tombstone_data.obj_id))?;
+            let resource_amount = obj
+                .store_used_capacity(Some(resource_type))
+                .saturating_sub(reserved);
+            if resource_amount == 0 {
+                return Ok(None);
+            }
+            // Decays soon, so it goes before everything else
+            points += 300 + (resource_amount as f32 / 50.).round() as i32;
+            let path_len = distance.between(room, &tombstone_data.pos()?, for_pos);
+            if path_len >= obj.ticks_to_decay() {
+                return Ok(None);
+            }
+            points -= path_len as i32 * 3;
+        }
+        ResourceProvider::Unknown {
+            room_object_data: RoomObjectData::Litter { obj_id },
+        } => {
+            let obj = get_object_cached(*obj_id)?
+                .ok_or_else(|| anyhow!("object not found {}", *obj_id))?;
+            if obj.resource_type() != resource_type {
+                return Ok(None);
+            }
+            let path_len = distance.between(room, &obj.pos(), for_pos);
+            let resource_amount = dropped_amount_after(obj.amount(), path_len)
+                .saturating_sub(reserved);
+            if resource_amount < 50 {
+                return Ok(None);
+            }
+            // Rather get it before it is gone
+            points += 250 + (resource_amount as f32 / 50.).round() as i32;
+            points -= path_len as i32 * 3;
         }
         _ => return Ok(None),
     };
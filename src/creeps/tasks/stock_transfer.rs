@@ -0,0 +1,134 @@
+use screeps::{HasId, HasPosition, HasStore, SharedCreepProperties, StructureProperties};
+
+use crate::{
+    creeps::{
+        movement::move_creep_to,
+        races::{OokRace, RepresentsCreep},
+    },
+    intents::{self, Action, Actor, Priority},
+    rooms::stock_balance::{StockTransfer, TransferDirection},
+    state::{get_object_cached, BWState},
+    utils::AnyhowOptionExt,
+};
+use anyhow::Result;
+
+use super::{OokTaskRunnable, OokTaskRunnableResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    Withdraw,
+    Deliver,
+}
+
+/// Carries one `StockTransfer` between storage and terminal
+#[derive(Debug, Clone)]
+pub struct Task {
+    transfer: StockTransfer,
+    step: Step,
+}
+
+impl Task {
+    pub fn new(transfer: StockTransfer) -> Self {
+        Task {
+            transfer,
+            step: Step::Withdraw,
+        }
+    }
+
+    fn withdraw(&mut self, race: &OokRace) -> Result<OokTaskRunnableResult> {
+        let creep = race.creep()?;
+        // Something else in the cargo would end up in the wrong place
+        if creep.store_used_capacity(None) > 0 {
+            return Ok(OokTaskRunnableResult::CancelAndDoAnother);
+        }
+        let resource_type = self.transfer.resource_type;
+        let (pos, available) = match self.transfer.direction {
+            TransferDirection::StorageToTerminal => {
+                let storage = get_object_cached(self.transfer.storage)?.anyhow("Storage not found")?;
+                (storage.pos(), storage.store_used_capacity(Some(resource_type)))
+            }
+            TransferDirection::TerminalToStorage => {
+                let terminal =
+                    get_object_cached(self.transfer.terminal)?.anyhow("Terminal not found")?;
+                (terminal.pos(), terminal.store_used_capacity(Some(resource_type)))
+            }
+        };
+        if !creep.pos().is_near_to(&pos) {
+            move_creep_to(&creep, &pos, 1);
+            return Ok(OokTaskRunnableResult::Continue);
+        }
+        let amount = self
+            .transfer
+            .amount
+            .min(available)
+            .min(creep.store_free_capacity(Some(resource_type)).max(0) as u32);
+        if amount == 0 {
+            return Ok(OokTaskRunnableResult::CancelAndDoAnother);
+        }
+        let target = match self.transfer.direction {
+            TransferDirection::StorageToTerminal => get_object_cached(self.transfer.storage)?
+                .anyhow("Storage not found")?
+                .as_structure()
+                .id(),
+            TransferDirection::TerminalToStorage => get_object_cached(self.transfer.terminal)?
+                .anyhow("Terminal not found")?
+                .as_structure()
+                .id(),
+        };
+        intents::submit(
+            Actor::Creep(creep.id()),
+            Action::Withdraw {
+                target,
+                resource_type,
+                amount,
+            },
+            Priority::Task,
+        );
+        // An empty cargo on the next tick means the withdraw lost or failed, delivering finishes
+        self.step = Step::Deliver;
+        Ok(OokTaskRunnableResult::Continue)
+    }
+
+    fn deliver(&mut self, race: &OokRace) -> Result<OokTaskRunnableResult> {
+        let creep = race.creep()?;
+        let resource_type = self.transfer.resource_type;
+        let amount = creep.store_used_capacity(Some(resource_type));
+        if amount == 0 {
+            return Ok(OokTaskRunnableResult::Finish);
+        }
+        let (pos, target) = match self.transfer.direction {
+            TransferDirection::StorageToTerminal => {
+                let terminal =
+                    get_object_cached(self.transfer.terminal)?.anyhow("Terminal not found")?;
+                (terminal.pos(), terminal.as_structure().id())
+            }
+            TransferDirection::TerminalToStorage => {
+                let storage = get_object_cached(self.transfer.storage)?.anyhow("Storage not found")?;
+                (storage.pos(), storage.as_structure().id())
+            }
+        };
+        if !creep.pos().is_near_to(&pos) {
+            move_creep_to(&creep, &pos, 1);
+            return Ok(OokTaskRunnableResult::Continue);
+        }
+        intents::submit(
+            Actor::Creep(creep.id()),
+            Action::Transfer {
+                target,
+                resource_type,
+                amount,
+            },
+            Priority::Task,
+        );
+        Ok(OokTaskRunnableResult::Finish)
+    }
+}
+
+impl OokTaskRunnable for Task {
+    fn run(&mut self, _state: &mut BWState, race: &OokRace) -> Result<OokTaskRunnableResult> {
+        match self.step {
+            Step::Withdraw => self.withdraw(race),
+            Step::Deliver => self.deliver(race),
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use screeps::{
+    find, game::rooms, Creep, HasPosition, Part, Room, RoomName, SharedCreepProperties,
+    StructureProperties, StructureType,
+};
+
+use crate::{
+    creeps::{
+        movement::move_creep_to,
+        races::{OokRace, RepresentsCreep},
+    },
+    state::BWState,
+    utils::AnyhowOptionExt,
+};
+use anyhow::Result;
+
+use super::{OokTaskRunnable, OokTaskRunnableResult};
+
+const RANGED_HEAL_RANGE: u32 = 3;
+/// Creeps under fire get healed ahead of the damage as if they missed this share of their hits
+const PRE_HEAL_SHARE: f64 = 0.5;
+
+/// What it would cost to replace the creep
+fn body_value(creep: &Creep) -> u32 {
+    creep.body().iter().map(|b| b.part.cost()).sum()
+}
+
+/// If the creep is going to take damage this tick
+fn is_under_fire(creep: &Creep, hostiles: &[Creep], hostile_towers: bool) -> bool {
+    hostile_towers
+        || hostiles.iter().any(|h| {
+            (h.get_active_bodyparts(Part::RangedAttack) > 0
+                && h.pos().in_range_to(&creep.pos(), 3))
+                || (h.get_active_bodyparts(Part::Attack) > 0 && h.pos().is_near_to(&creep.pos()))
+        })
+}
+
+/// The creep most worth healing, damaged valuable creeps first
+fn triage_target(room: &Room, healer: &Creep) -> Option<Creep> {
+    let hostiles = room.find(find::HOSTILE_CREEPS);
+    let hostile_towers = room
+        .find(find::HOSTILE_STRUCTURES)
+        .iter()
+        .any(|s| s.structure_type() == StructureType::Tower);
+    let scored: Vec<(Creep, f64)> = room
+        .find(find::MY_CREEPS)
+        .into_iter()
+        .filter_map(|c| {
+            let mut need = 1. - c.hits() as f64 / c.hits_max() as f64;
+            if is_under_fire(&c, &hostiles, hostile_towers) {
+                need += PRE_HEAL_SHARE;
+            }
+            if need <= 0. {
+                return None;
+            }
+            let score = need * body_value(&c) as f64;
+            Some((c, score))
+        })
+        .collect();
+    let in_range = scored
+        .iter()
+        .any(|(c, _)| c.pos().in_range_to(&healer.pos(), RANGED_HEAL_RANGE));
+    scored
+        .into_iter()
+        .filter(|(c, _)| !in_range || c.pos().in_range_to(&healer.pos(), RANGED_HEAL_RANGE))
+        .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+        .map(|(c, _)| c)
+}
+
+/// Heals the creeps of a room, the most valuable damaged one in range first
+#[derive(Debug, Clone)]
+pub struct Task {
+    room_name: RoomName,
+}
+
+impl Task {
+    pub fn new(room_name: RoomName) -> Self {
+        Task { room_name }
+    }
+
+    /// If anybody in the room needs a healer
+    pub fn is_needed(room: &Room, healer: &Creep) -> bool {
+        triage_target(room, healer).is_some()
+    }
+}
+
+impl OokTaskRunnable for Task {
+    fn run(&mut self, _state: &mut BWState, race: &OokRace) -> Result<OokTaskRunnableResult> {
+        let creep = race.creep()?;
+        let room = rooms::get(self.room_name).anyhow("triage room not visible")?;
+        let target = match triage_target(&room, &creep) {
+            Some(target) => target,
+            None => return Ok(OokTaskRunnableResult::Finish),
+        };
+        if creep.pos().is_near_to(&target.pos()) {
+            creep.heal(&target);
+        } else {
+            if creep.pos().in_range_to(&target.pos(), RANGED_HEAL_RANGE) {
+                creep.ranged_heal(&target);
+            } else if creep.hits() < creep.hits_max() {
+                creep.heal(&creep);
+            }
+            move_creep_to(&creep, &target.pos(), 1);
+        }
+        Ok(OokTaskRunnableResult::Continue)
+    }
+}
@@ -1,4 +1,4 @@
-use std::{collections::HashMap, fmt};
+use std::fmt;
 
 use log::warn;
 use screeps::{
@@ -9,7 +9,9 @@ use screeps::{
 use crate::{
     creeps::{
         generic_creep_fetch_from_provider_prio,
-        races::{generic_calc_energy_resource_provider, OokRace, RepresentsCreep},
+        movement::move_creep_to,
+        races::{generic_calc_resource_provider, OokRace, RepresentsCreep},
+        speech::{self, Verbosity},
     },
     rooms::{
         resource_provider::{ResourceData, ResourceProvider, TakeResourceResult},
@@ -66,7 +68,7 @@ pub struct Task {
 }
 
 impl Task {
-    pub fn new(target_controller_at: Position, state: &BWState, race: &OokRace) -> Result<Self> {
+    pub fn new(target_controller_at: Position, state: &mut BWState, race: &OokRace) -> Result<Self> {
         let mut task = Task {
             step: Step::WaitForResource {
                 controller_pos: target_controller_at,
@@ -76,12 +78,12 @@ impl Task {
         Ok(task)
     }
 
-    fn precheck(&mut self, state: &BWState, race: &OokRace) -> Result<()> {
+    fn precheck(&mut self, state: &mut BWState, race: &OokRace) -> Result<()> {
         let creep = race.creep()?;
         match &self.step {
             Step::GetEnergy { controller_pos, .. } => {
                 if creep.store_free_capacity(Some(ResourceType::Energy)) == 0 {
-                    creep.say("⏫", false);
+                    speech::say(&creep, "⏫", Verbosity::Status);
                     self.step = Step::Upgrade {
                         controller_pos: *controller_pos,
                     };
@@ -90,13 +92,18 @@ impl Task {
             Step::Upgrade { controller_pos } => {
                 if creep.store_used_capacity(Some(ResourceType::Energy)) == 0 {
                     let calc_result = self
-                        .calc_resource_provider(&state.room_states, race)
+                        .calc_resource_provider(state, race)
                         .map_err(|err| {
                             anyhow!("UpgradeController precheck calc_resource_provider, {}", err)
                         })?;
                     match calc_result {
                         Some(calc_result) => {
-                            creep.say("📦", false);
+                            speech::say(&creep, "📦", Verbosity::Status);
+                            state.provider_reservations.reserve(
+                                &calc_result.resource_provider,
+                                calc_result.resource_type,
+                                calc_result.amount,
+                            );
                             self.step = Step::GetEnergy {
                                 controller_pos: *controller_pos,
                                 target: calc_result.resource_provider,
@@ -112,13 +119,18 @@ impl Task {
             }
             Step::WaitForResource { controller_pos } => {
                 let calc_result = self
-                    .calc_resource_provider(&state.room_states, race)
+                    .calc_resource_provider(state, race)
                     .map_err(|err| {
                         anyhow!("UpgradeController precheck calc_resource_provider, {}", err)
                     })?;
                 match calc_result {
                     Some(calc_result) => {
-                        creep.say("📦", false);
+                        speech::say(&creep, "📦", Verbosity::Status);
+                        state.provider_reservations.reserve(
+                            &calc_result.resource_provider,
+                            calc_result.resource_type,
+                            calc_result.amount,
+                        );
                         self.step = Step::GetEnergy {
                             controller_pos: *controller_pos,
                             target: calc_result.resource_provider,
@@ -149,7 +161,7 @@ impl OokTaskRunnable for Task {
                         creep.store_free_capacity(Some(ResourceType::Energy)) as u32,
                     )? {
                         TakeResourceResult::Withdraw { .. } => {
-                            creep.say("⏫", false);
+                            speech::say(&creep, "⏫", Verbosity::Status);
                             self.step = Step::Upgrade {
                                 controller_pos: controller_pos.to_owned(),
                             };
@@ -158,14 +170,14 @@ impl OokTaskRunnable for Task {
                             // Continue harvest until we are full
                         }
                         TakeResourceResult::Pickup { .. } => {
-                            creep.say("⏫", false);
+                            speech::say(&creep, "⏫", Verbosity::Status);
                             self.step = Step::Upgrade {
                                 controller_pos: controller_pos.to_owned(),
                             };
                         }
                     }
                 } else {
-                    creep.move_to(&target_pos);
+                    move_creep_to(&creep, &target_pos, 1);
                 }
             }
             Step::Upgrade { controller_pos } => {
@@ -177,11 +189,11 @@ impl OokTaskRunnable for Task {
                         .ok_or_else(|| anyhow!("uc: controller not found"))?;
                     creep.upgrade_controller(&controller);
                 } else {
-                    creep.move_to(controller_pos);
+                    move_creep_to(&creep, controller_pos, 3);
                 }
             }
             Step::WaitForResource { .. } => {
-                creep.say("⏱ ", false);
+                speech::say(&creep, "⏱ ", Verbosity::Status);
             }
         }
         // TODO
@@ -192,21 +204,25 @@ impl OokTaskRunnable for Task {
 impl<'a> FetchesFromResourceProvider<'a> for Task {
     fn calc_resource_provider(
         &self,
-        rooms_state: &'a HashMap<screeps::RoomName, RoomState>,
+        state: &'a BWState,
         race: &'a OokRace,
     ) -> Result<Option<CalcResourceProviderResult>> {
         let creep = race.creep()?;
         let room = creep.room().ok_or(anyhow!("room of creep not found"))?;
-        let room_state = rooms_state
+        let room_state = state
+            .room_states
             .get(&room.name())
             .ok_or_else(|| anyhow!("Room not found"))?;
         let amount = creep.store_free_capacity(Some(ResourceType::Energy));
         match room_state {
-            RoomState::Base(room_state) => generic_calc_energy_resource_provider(
+            RoomState::Base(room_state) => generic_calc_resource_provider(
                 &room_state.resource_providers,
+                &state.provider_reservations,
                 &creep,
                 &room,
+                ResourceType::Energy,
                 amount as u32,
+                false,
             ),
             RoomState::SetupBase(room_state) => {
                 if let SetupBaseStateVisibility::Visible {
@@ -214,11 +230,14 @@ impl<'a> FetchesFromResourceProvider<'a> for Task {
                     ..
                 } = room_state.state
                 {
-                    generic_calc_energy_resource_provider(
+                    generic_calc_resource_provider(
                         &resource_providers,
+                        &state.provider_reservations,
                         &creep,
                         &room,
+                        ResourceType::Energy,
                         amount as u32,
+                        false,
                     )
                 } else {
                     Ok(None)
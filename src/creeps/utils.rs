@@ -1,17 +1,5 @@
 use screeps::{Bodypart, CREEP_SPAWN_TIME, Part, creep};
 
-use crate::state::UniqId;
-
-use super::races::OokRaceKind;
-
-pub fn create_creep_name(race: &OokRaceKind) -> String {
-    format!(
-        "{}-{}",
-        race,
-        UniqId::new(),
-    )
-}
-
 pub fn get_bodyparts_cost(parts: Vec<creep::Part>) -> u32 {
     let mut val = 0;
     for part in parts {
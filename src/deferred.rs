@@ -0,0 +1,205 @@
+//! Deferred work. Base planning, precomputing the cost matrices for pathing, recalculating wall
+//! targets, paving remotes and pathing highways are expensive but never urgent. They get queued
+//! here and run in the `Deferred` stage, only while the bucket is above
+//! `config::DEFERRED_MIN_BUCKET` and with at most `config::DEFERRED_CPU_PER_TICK` per tick. Jobs
+//! work in small steps and keep their progress, a job that doesn't fit into one tick resumes in
+//! the next one.
+//!
+//! The queue lives in the global, a reset drops it. Everything in there gets queued again sooner
+//! or later anyway.
+use std::{collections::VecDeque, sync::Mutex};
+
+use lazy_static::lazy_static;
+use log::debug;
+use screeps::{game, RoomName};
+
+use crate::{
+    config, economy,
+    game::{owned_rooms, OwnedBy},
+    rooms::{cost_matrix, highways, planner, remotes, stagger},
+};
+
+/// Ticks between two precomputations of the cost matrix of a visible room
+const MATRIX_INTERVAL: u32 = 500;
+/// Ticks between two recalculations of the wall target of a room
+const WALL_TARGET_INTERVAL: u32 = 100;
+/// Ticks between two rounds of road sites along the haul routes of a remote
+const REMOTE_ROADS_INTERVAL: u32 = 200;
+/// Ticks between two pavings of the highways of a room, new roads may change them
+const HIGHWAYS_INTERVAL: u32 = 1_000;
+
+#[derive(Debug, Clone)]
+pub enum Job {
+    /// Places the missing structures of the room, one structure type per step
+    PlaceStructures { room_name: RoomName, next: usize },
+    /// Rebuilds the outdated cost matrices of the rooms, one room per step
+    PrecomputeMatrices { rooms: Vec<RoomName> },
+    /// Recalculates the wall targets of the rooms, one room per step
+    WallTargets { rooms: Vec<RoomName> },
+    /// Places road sites along the haul routes of the remotes, one remote per step
+    RemoteRoads { remotes: Vec<RoomName> },
+    /// Paths the highways of the rooms again, one room per step
+    Highways { rooms: Vec<RoomName> },
+}
+
+enum Progress {
+    Pending,
+    Done,
+}
+
+impl Job {
+    pub fn place_structures(room_name: RoomName) -> Job {
+        Job::PlaceStructures { room_name, next: 0 }
+    }
+
+    /// Queuing the same job again before it is done doesn't make it run twice
+    pub fn is_same(&self, other: &Job) -> bool {
+        match (self, other) {
+            (
+                Job::PlaceStructures { room_name: a, .. },
+                Job::PlaceStructures { room_name: b, .. },
+            ) => a == b,
+            (
+                Job::PrecomputeMatrices { rooms: a },
+                Job::PrecomputeMatrices { rooms: b },
+            ) => a == b,
+            (Job::WallTargets { rooms: a }, Job::WallTargets { rooms: b }) => a == b,
+            (Job::RemoteRoads { remotes: a }, Job::RemoteRoads { remotes: b }) => a == b,
+            (Job::Highways { rooms: a }, Job::Highways { rooms: b }) => a == b,
+            _ => false,
+        }
+    }
+
+    fn step(&mut self) -> Progress {
+        match self {
+            Job::PlaceStructures { room_name, next } => {
+                let ty = match planner::AUTO_PLACED.get(*next) {
+                    Some(ty) => *ty,
+                    None => return Progress::Done,
+                };
+                // The room may be gone by now
+                if let Some(room) = game::rooms::get(*room_name) {
+                    planner::place_missing(&room, ty);
+                }
+                *next += 1;
+                if *next < planner::AUTO_PLACED.len() {
+                    Progress::Pending
+                } else {
+                    Progress::Done
+                }
+            }
+            Job::PrecomputeMatrices { rooms } => {
+                if let Some(room_name) = rooms.pop() {
+                    cost_matrix::refresh(room_name);
+                }
+                if rooms.is_empty() {
+                    Progress::Done
+                } else {
+                    Progress::Pending
+                }
+            }
+            Job::WallTargets { rooms } => {
+                if let Some(room_name) = rooms.pop() {
+                    economy::recalc_wall_target_hits(room_name);
+                }
+                if rooms.is_empty() {
+                    Progress::Done
+                } else {
+                    Progress::Pending
+                }
+            }
+            Job::RemoteRoads { remotes } => {
+                if let Some(remote) = remotes.pop() {
+                    remotes::place_roads(remote);
+                }
+                if remotes.is_empty() {
+                    Progress::Done
+                } else {
+                    Progress::Pending
+                }
+            }
+            Job::Highways { rooms } => {
+                if let Some(room_name) = rooms.pop() {
+                    highways::build(room_name);
+                }
+                if rooms.is_empty() {
+                    Progress::Done
+                } else {
+                    Progress::Pending
+                }
+            }
+        }
+    }
+}
+
+lazy_static! {
+    static ref QUEUE: Mutex<VecDeque<Job>> = Mutex::new(VecDeque::new());
+}
+
+/// Queues `job` unless the same job is waiting already
+pub fn queue(job: Job) {
+    let mut queue = QUEUE.lock().unwrap();
+    if queue.iter().any(|queued| queued.is_same(&job)) {
+        return;
+    }
+    debug!("Deferring {:?}", job);
+    queue.push_back(job);
+}
+
+fn queue_due() {
+    // Staggered by room, each tick only queues the rooms that are due
+    let rooms: Vec<RoomName> = game::rooms::keys()
+        .into_iter()
+        .filter(|room_name| stagger::is_due(*room_name, MATRIX_INTERVAL))
+        .collect();
+    if !rooms.is_empty() {
+        queue(Job::PrecomputeMatrices { rooms });
+    }
+    let rooms: Vec<RoomName> = owned_rooms(OwnedBy::Me)
+        .keys()
+        .cloned()
+        .filter(|room_name| stagger::is_due(*room_name, WALL_TARGET_INTERVAL))
+        .collect();
+    if !rooms.is_empty() {
+        queue(Job::WallTargets { rooms });
+    }
+    let remotes: Vec<RoomName> = owned_rooms(OwnedBy::Me)
+        .keys()
+        .flat_map(|base| remotes::remotes_of(*base))
+        .filter(|remote| stagger::is_due(*remote, REMOTE_ROADS_INTERVAL))
+        .collect();
+    if !remotes.is_empty() {
+        queue(Job::RemoteRoads { remotes });
+    }
+    let rooms: Vec<RoomName> = owned_rooms(OwnedBy::Me)
+        .keys()
+        .cloned()
+        .filter(|room_name| stagger::is_due(*room_name, HIGHWAYS_INTERVAL))
+        .collect();
+    if !rooms.is_empty() {
+        queue(Job::Highways { rooms });
+    }
+}
+
+/// Queues what is due and works off the queue while the bucket and the CPU of the tick allow
+pub fn run() {
+    queue_due();
+    let bucket = game::cpu::bucket();
+    if bucket < config::DEFERRED_MIN_BUCKET {
+        debug!("Bucket at {}, deferred work waits", bucket);
+        return;
+    }
+    let budget_end = game::cpu::get_used() + config::DEFERRED_CPU_PER_TICK;
+    let mut queue = QUEUE.lock().unwrap();
+    while game::cpu::get_used() < budget_end {
+        let job = match queue.front_mut() {
+            Some(job) => job,
+            None => return,
+        };
+        if let Progress::Done = job.step() {
+            if let Some(job) = queue.pop_front() {
+                debug!("Deferred job done: {:?}", job);
+            }
+        }
+    }
+}
@@ -0,0 +1,151 @@
+//! Decides what a room does with its energy. While the storage is above the high-water mark
+//! the surplus goes into more upgraders, thicker walls and market sales.
+use std::{collections::HashMap, sync::Mutex};
+
+use lazy_static::lazy_static;
+use log::{info, warn};
+use screeps::{
+    find,
+    game::{self, market::OrderType},
+    HasStore, MarketResourceType, ResourceType, ReturnCode, Room, RoomName, Structure,
+};
+
+use crate::{config, rooms::stagger, trade};
+
+pub mod boosts;
+pub mod empire;
+pub mod funnel;
+pub mod ledger;
+
+use self::empire::UpgradeRole;
+
+/// Ticks between two tries to sell surplus energy
+const OVERFLOW_SELL_INTERVAL: u32 = 100;
+
+lazy_static! {
+    /// Wall targets of the rooms, recalculated as deferred work
+    static ref WALL_TARGETS: Mutex<HashMap<RoomName, f64>> = Mutex::new(HashMap::new());
+}
+
+/// Share of the storage capacity in use, `None` without storage
+pub fn storage_fill(room: &Room) -> Option<f64> {
+    let storage = room.storage()?;
+    let capacity = storage.store_capacity(None);
+    if capacity == 0 {
+        return None;
+    }
+    Some(storage.store_used_capacity(None) as f64 / capacity as f64)
+}
+
+pub fn is_overflowing(room: &Room) -> bool {
+    storage_fill(room)
+        .map(|fill| fill >= config::STORAGE_HIGH_WATER_MARK)
+        .unwrap_or(false)
+}
+
+/// Workers to spawn on top of the room's target
+///
+/// Donors of the GCL push send their surplus away instead, see `empire`.
+pub fn extra_workers(room: &Room) -> u8 {
+    match empire::role_of(room.name()) {
+        UpgradeRole::Push => return empire::push_extra_workers(room),
+        UpgradeRole::Donor => return 0,
+        UpgradeRole::None => {}
+    }
+    if is_overflowing(room) {
+        config::STORAGE_OVERFLOW_EXTRA_WORKERS
+    } else {
+        0
+    }
+}
+
+fn calc_wall_target_hits(room: &Room) -> f64 {
+    if is_overflowing(room) {
+        config::WALL_TARGET_HITS_OVERFLOW
+    } else {
+        config::WALL_TARGET_HITS
+    }
+}
+
+/// Hits walls and ramparts of the room get repaired up to
+///
+/// As of the last recalculation, see `deferred::Job::WallTargets`. A room without one gets it
+/// calculated right away.
+pub fn wall_target_hits(room: &Room) -> f64 {
+    *WALL_TARGETS
+        .lock()
+        .unwrap()
+        .entry(room.name())
+        .or_insert_with(|| calc_wall_target_hits(room))
+}
+
+pub fn recalc_wall_target_hits(room_name: RoomName) {
+    let mut targets = WALL_TARGETS.lock().unwrap();
+    match game::rooms::get(room_name) {
+        Some(room) => {
+            targets.insert(room_name, calc_wall_target_hits(&room));
+        }
+        None => {
+            targets.remove(&room_name);
+        }
+    }
+}
+
+/// Sells surplus energy out of the terminal while the storage is overflowing
+///
+/// The energy comes out of the terminal's stock, which gets topped up from the storage again.
+pub fn sell_surplus(room: &Room) {
+    if !stagger::is_due(room.name(), OVERFLOW_SELL_INTERVAL) || !is_overflowing(room) {
+        return;
+    }
+    let terminal = match room.find(find::STRUCTURES).into_iter().find_map(|s| match s {
+        Structure::Terminal(terminal) => Some(terminal),
+        _ => None,
+    }) {
+        Some(terminal) => terminal,
+        None => return,
+    };
+    let min_price = match trade::prices::moving_average(ResourceType::Energy) {
+        Some(avg) => avg * config::STORAGE_OVERFLOW_MIN_PRICE_SHARE,
+        None => return,
+    };
+    let best = game::market::get_all_orders(Some(MarketResourceType::Resource(
+        ResourceType::Energy,
+    )))
+    .into_iter()
+    .filter(|o| o.order_type == OrderType::Buy && o.remaining_amount > 0 && o.price >= min_price)
+    .filter(|o| o.room_name.is_some())
+    .max_by(|a, b| a.price.partial_cmp(&b.price).unwrap());
+    let order = match best {
+        Some(order) => order,
+        None => return,
+    };
+    let order_room = match order.room_name {
+        Some(order_room) => order_room,
+        None => return,
+    };
+
+    // Planned sends need their part of the terminal, the rest of the stock may go
+    let reserved = trade::terminal_keep_amount(room.name(), ResourceType::Energy)
+        .saturating_sub(config::terminal_stock_level(ResourceType::Energy));
+    let sellable = terminal
+        .store_used_capacity(Some(ResourceType::Energy))
+        .saturating_sub(reserved);
+    let mut amount = config::STORAGE_OVERFLOW_SELL_AMOUNT.min(order.remaining_amount);
+    let fee = |amount: u32| game::market::calc_transaction_cost(amount, room.name(), order_room);
+    while amount > 0 && amount as f64 + fee(amount) > sellable as f64 {
+        amount /= 2;
+    }
+    if amount < 1000 {
+        return;
+    }
+    match game::market::deal(&order.id, amount, Some(room.name())) {
+        ReturnCode::Ok => info!(
+            "Room {} overflowing, sold {} energy at {}",
+            room.name(),
+            amount,
+            order.price
+        ),
+        code => warn!("Couldn't sell surplus energy of {}: {:?}", room.name(), code),
+    }
+}
@@ -0,0 +1,493 @@
+//! Compounds for boosting, made in the labs. `config::BOOST_TARGETS` lists the compounds and how
+//! much of each to keep. Every `config::BOOST_PLAN_INTERVAL` ticks the base with the most labs
+//! gets a plan: the ingredient tree of every target down to the base minerals, less what its
+//! storage, terminal and labs already hold, turns into a queue of reactions, ingredients first.
+//!
+//! Base minerals the lab room lacks are sent from the terminals of other bases with some to spare,
+//! what they can't cover is bought from the market on `SpendCategory::MineralBuying`.
+//!
+//! The reaction at the front of the queue runs until the lab room holds what it was planned for,
+//! then it leaves the queue, the reactions after it using up its product don't bring it back. The
+//! first two labs by position get its inputs, the labs in range of both make the product, see
+//! `rooms::fill_orders`. After a reset the queue is planned right away.
+use std::{cmp, collections::HashMap, sync::Mutex};
+
+use lazy_static::lazy_static;
+use log::{debug, info, warn};
+use screeps::{
+    game::{self, market::OrderType},
+    HasCooldown, HasPosition, HasStore, MarketResourceType, ResourceType, ReturnCode, Room,
+    RoomName, StructureLab, StructureType,
+};
+
+use crate::{
+    config,
+    game::{owned_rooms, OwnedBy},
+    rooms::structures,
+    server,
+    trade::{
+        self,
+        budget::{self, SpendCategory},
+        prices,
+    },
+};
+
+/// Base minerals, they can't be made
+const BASE_MINERALS: [ResourceType; 7] = [
+    ResourceType::Hydrogen,
+    ResourceType::Oxygen,
+    ResourceType::Utrium,
+    ResourceType::Lemergium,
+    ResourceType::Keanium,
+    ResourceType::Zynthium,
+    ResourceType::Catalyst,
+];
+
+/// What every compound is made of
+const REACTIONS: &[(ResourceType, [ResourceType; 2])] = &[
+    (
+        ResourceType::Hydroxide,
+        [ResourceType::Hydrogen, ResourceType::Oxygen],
+    ),
+    (
+        ResourceType::ZynthiumKeanite,
+        [ResourceType::Zynthium, ResourceType::Keanium],
+    ),
+    (
+        ResourceType::UtriumLemergite,
+        [ResourceType::Utrium, ResourceType::Lemergium],
+    ),
+    (
+        ResourceType::Ghodium,
+        [ResourceType::ZynthiumKeanite, ResourceType::UtriumLemergite],
+    ),
+    (
+        ResourceType::UtriumHydride,
+        [ResourceType::Utrium, ResourceType::Hydrogen],
+    ),
+    (
+        ResourceType::UtriumOxide,
+        [ResourceType::Utrium, ResourceType::Oxygen],
+    ),
+    (
+        ResourceType::KeaniumHydride,
+        [ResourceType::Keanium, ResourceType::Hydrogen],
+    ),
+    (
+        ResourceType::KeaniumOxide,
+        [ResourceType::Keanium, ResourceType::Oxygen],
+    ),
+    (
+        ResourceType::LemergiumHydride,
+        [ResourceType::Lemergium, ResourceType::Hydrogen],
+    ),
+    (
+        ResourceType::LemergiumOxide,
+        [ResourceType::Lemergium, ResourceType::Oxygen],
+    ),
+    (
+        ResourceType::ZynthiumHydride,
+        [ResourceType::Zynthium, ResourceType::Hydrogen],
+    ),
+    (
+        ResourceType::ZynthiumOxide,
+        [ResourceType::Zynthium, ResourceType::Oxygen],
+    ),
+    (
+        ResourceType::GhodiumHydride,
+        [ResourceType::Ghodium, ResourceType::Hydrogen],
+    ),
+    (
+        ResourceType::GhodiumOxide,
+        [ResourceType::Ghodium, ResourceType::Oxygen],
+    ),
+    (
+        ResourceType::UtriumAcid,
+        [ResourceType::UtriumHydride, ResourceType::Hydroxide],
+    ),
+    (
+        ResourceType::UtriumAlkalide,
+        [ResourceType::UtriumOxide, ResourceType::Hydroxide],
+    ),
+    (
+        ResourceType::KeaniumAcid,
+        [ResourceType::KeaniumHydride, ResourceType::Hydroxide],
+    ),
+    (
+        ResourceType::KeaniumAlkalide,
+        [ResourceType::KeaniumOxide, ResourceType::Hydroxide],
+    ),
+    (
+        ResourceType::LemergiumAcid,
+        [ResourceType::LemergiumHydride, ResourceType::Hydroxide],
+    ),
+    (
+        ResourceType::LemergiumAlkalide,
+        [ResourceType::LemergiumOxide, ResourceType::Hydroxide],
+    ),
+    (
+        ResourceType::ZynthiumAcid,
+        [ResourceType::ZynthiumHydride, ResourceType::Hydroxide],
+    ),
+    (
+        ResourceType::ZynthiumAlkalide,
+        [ResourceType::ZynthiumOxide, ResourceType::Hydroxide],
+    ),
+    (
+        ResourceType::GhodiumAcid,
+        [ResourceType::GhodiumHydride, ResourceType::Hydroxide],
+    ),
+    (
+        ResourceType::GhodiumAlkalide,
+        [ResourceType::GhodiumOxide, ResourceType::Hydroxide],
+    ),
+    (
+        ResourceType::CatalyzedUtriumAcid,
+        [ResourceType::UtriumAcid, ResourceType::Catalyst],
+    ),
+    (
+        ResourceType::CatalyzedUtriumAlkalide,
+        [ResourceType::UtriumAlkalide, ResourceType::Catalyst],
+    ),
+    (
+        ResourceType::CatalyzedKeaniumAcid,
+        [ResourceType::KeaniumAcid, ResourceType::Catalyst],
+    ),
+    (
+        ResourceType::CatalyzedKeaniumAlkalide,
+        [ResourceType::KeaniumAlkalide, ResourceType::Catalyst],
+    ),
+    (
+        ResourceType::CatalyzedLemergiumAcid,
+        [ResourceType::LemergiumAcid, ResourceType::Catalyst],
+    ),
+    (
+        ResourceType::CatalyzedLemergiumAlkalide,
+        [ResourceType::LemergiumAlkalide, ResourceType::Catalyst],
+    ),
+    (
+        ResourceType::CatalyzedZynthiumAcid,
+        [ResourceType::ZynthiumAcid, ResourceType::Catalyst],
+    ),
+    (
+        ResourceType::CatalyzedZynthiumAlkalide,
+        [ResourceType::ZynthiumAlkalide, ResourceType::Catalyst],
+    ),
+    (
+        ResourceType::CatalyzedGhodiumAcid,
+        [ResourceType::GhodiumAcid, ResourceType::Catalyst],
+    ),
+    (
+        ResourceType::CatalyzedGhodiumAlkalide,
+        [ResourceType::GhodiumAlkalide, ResourceType::Catalyst],
+    ),
+];
+
+/// Labs a room needs to run reactions, two for the inputs and one for the product
+const MIN_LABS: usize = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Reaction {
+    pub product: ResourceType,
+    pub inputs: [ResourceType; 2],
+    /// Runs until the room holds this much of the product
+    pub target: u32,
+}
+
+#[derive(Debug, Default)]
+struct BoostPlan {
+    /// Unset until the first plan since the reset
+    planned: bool,
+    lab_room: Option<RoomName>,
+    /// Ingredients first
+    reactions: Vec<Reaction>,
+}
+
+lazy_static! {
+    static ref PLAN: Mutex<BoostPlan> = Mutex::new(BoostPlan::default());
+}
+
+fn inputs_of(product: ResourceType) -> Option<[ResourceType; 2]> {
+    REACTIONS
+        .iter()
+        .find(|(ty, _)| *ty == product)
+        .map(|(_, inputs)| *inputs)
+}
+
+/// What the storage, the terminal and the labs of the room hold of the resource
+pub fn room_amount(room: &Room, resource_type: ResourceType) -> u32 {
+    let stored = room
+        .storage()
+        .map(|s| s.store_used_capacity(Some(resource_type)))
+        .unwrap_or(0);
+    let in_terminal = room
+        .terminal()
+        .map(|t| t.store_used_capacity(Some(resource_type)))
+        .unwrap_or(0);
+    let in_labs: u32 = structures::of_type::<StructureLab>(room.name(), StructureType::Lab)
+        .iter()
+        .map(|lab| lab.store_used_capacity(Some(resource_type)))
+        .sum();
+    stored + in_terminal + in_labs
+}
+
+/// Adds the reactions for `amount` of the resource on top of `available`, ingredients first, and
+/// the base minerals missing for them to `missing`
+fn plan_resource(
+    resource_type: ResourceType,
+    amount: u32,
+    available: &mut HashMap<ResourceType, u32>,
+    reactions: &mut Vec<Reaction>,
+    missing: &mut HashMap<ResourceType, u32>,
+    room: &Room,
+) {
+    let have = available
+        .entry(resource_type)
+        .or_insert_with(|| room_amount(room, resource_type));
+    let used = cmp::min(*have, amount);
+    *have -= used;
+    let lacking = amount - used;
+    if lacking == 0 {
+        return;
+    }
+    let inputs = match inputs_of(resource_type) {
+        Some(inputs) => inputs,
+        None => {
+            *missing.entry(resource_type).or_insert(0) += lacking;
+            return;
+        }
+    };
+    for input in inputs.iter() {
+        plan_resource(*input, lacking, available, reactions, missing, room);
+    }
+    match reactions.iter_mut().find(|r| r.product == resource_type) {
+        Some(reaction) => reaction.target += lacking,
+        None => reactions.push(Reaction {
+            product: resource_type,
+            inputs,
+            target: room_amount(room, resource_type) + lacking,
+        }),
+    }
+}
+
+/// The base with a terminal and the most labs
+fn pick_lab_room(bases: &HashMap<RoomName, Room>) -> Option<RoomName> {
+    bases
+        .values()
+        .filter(|room| room.terminal().is_some())
+        .map(|room| {
+            let labs = structures::of_type::<StructureLab>(room.name(), StructureType::Lab).len();
+            (room.name(), labs)
+        })
+        .filter(|(_, labs)| *labs >= MIN_LABS)
+        .max_by_key(|(room_name, labs)| (*labs, room_name.to_string()))
+        .map(|(room_name, _)| room_name)
+}
+
+fn send_description(resource_type: ResourceType) -> String {
+    format!("boosts {:?}", resource_type)
+}
+
+/// Sends the base mineral from other bases to the lab room, returns what they couldn't cover
+fn transfer(
+    bases: &HashMap<RoomName, Room>,
+    lab_room: RoomName,
+    resource_type: ResourceType,
+    amount: u32,
+) -> u32 {
+    let description = send_description(resource_type);
+    let mut left = amount;
+    for room in bases.values() {
+        if left < config::BOOST_MIN_TRANSFER {
+            break;
+        }
+        if room.name() == lab_room || room.terminal().is_none() {
+            continue;
+        }
+        if trade::has_planned_send(room.name(), &description) {
+            // Still on its way
+            left = left.saturating_sub(config::BOOST_MIN_TRANSFER);
+            continue;
+        }
+        let spare = room_amount(room, resource_type)
+            .saturating_sub(config::terminal_stock_level(resource_type));
+        let amount = cmp::min(spare, left);
+        if amount < config::BOOST_MIN_TRANSFER {
+            continue;
+        }
+        trade::plan_send(room.name(), lab_room, resource_type, amount, &description);
+        left -= amount;
+    }
+    left
+}
+
+/// Buys the base mineral for the lab room if the market has it cheap
+fn buy(lab_room: RoomName, resource_type: ResourceType, amount: u32) {
+    let best = game::market::get_all_orders(Some(MarketResourceType::Resource(resource_type)))
+        .into_iter()
+        .filter(|o| o.order_type == OrderType::Sell && o.remaining_amount > 0)
+        .filter(|o| prices::is_buy_opportunity(resource_type, o.price))
+        .min_by(|a, b| a.price.partial_cmp(&b.price).unwrap());
+    let order = match best {
+        Some(order) => order,
+        None => {
+            debug!("No cheap {:?} on the market for boosts", resource_type);
+            return;
+        }
+    };
+    let affordable = (budget::available(SpendCategory::MineralBuying) / order.price) as u32;
+    let amount = cmp::min(cmp::min(amount, order.remaining_amount), affordable);
+    if amount < config::BOOST_MIN_TRANSFER {
+        debug!("Mineral buying budget exhausted for {:?}", resource_type);
+        return;
+    }
+    match game::market::deal(&order.id, amount, Some(lab_room)) {
+        ReturnCode::Ok => {
+            budget::record_spend(SpendCategory::MineralBuying, order.price * amount as f64);
+            info!(
+                "Bought {} {:?} at {} for the boosts in {}",
+                amount, resource_type, order.price, lab_room
+            );
+        }
+        code => warn!("Couldn't buy {:?} for boosts: {:?}", resource_type, code),
+    }
+}
+
+fn plan() {
+    let bases = owned_rooms(OwnedBy::Me);
+    let lab_room = pick_lab_room(&bases);
+    let mut plan = PLAN.lock().unwrap();
+    if lab_room != plan.lab_room {
+        info!("Boosts are made in {:?}", lab_room);
+    }
+    *plan = BoostPlan {
+        planned: true,
+        lab_room,
+        reactions: vec![],
+    };
+    let room = match lab_room.and_then(|room_name| bases.get(&room_name)) {
+        Some(room) => room,
+        None => return,
+    };
+
+    let mut available = HashMap::new();
+    let mut missing = HashMap::new();
+    for (resource_type, amount) in config::BOOST_TARGETS.iter() {
+        plan_resource(
+            *resource_type,
+            *amount,
+            &mut available,
+            &mut plan.reactions,
+            &mut missing,
+            room,
+        );
+    }
+    if !plan.reactions.is_empty() {
+        info!(
+            "Boost reactions in {}: {:?}",
+            room.name(),
+            plan.reactions
+                .iter()
+                .map(|r| r.product)
+                .collect::<Vec<ResourceType>>()
+        );
+    }
+    if !server::has_market() {
+        return;
+    }
+    for resource_type in BASE_MINERALS.iter() {
+        let amount = match missing.get(resource_type) {
+            Some(amount) => *amount,
+            None => continue,
+        };
+        let left = transfer(&bases, room.name(), *resource_type, amount);
+        if left >= config::BOOST_MIN_TRANSFER {
+            buy(room.name(), *resource_type, left);
+        }
+    }
+}
+
+/// The reaction the labs of the room work on, `None` outside of the lab room
+pub fn reaction(room_name: RoomName) -> Option<Reaction> {
+    let plan = PLAN.lock().unwrap();
+    if plan.lab_room != Some(room_name) {
+        return None;
+    }
+    plan.reactions.first().copied()
+}
+
+/// Drops the reactions at the front of the queue that made what they were planned for
+fn pop_done(room: &Room) {
+    let mut plan = PLAN.lock().unwrap();
+    while let Some(reaction) = plan.reactions.first().copied() {
+        if room_amount(room, reaction.product) < reaction.target {
+            break;
+        }
+        info!(
+            "Made {} {:?} in {}",
+            reaction.target,
+            reaction.product,
+            room.name()
+        );
+        plan.reactions.remove(0);
+    }
+}
+
+/// Labs of the room by position, the first two hold the inputs
+pub fn sorted_labs(room_name: RoomName) -> Vec<StructureLab> {
+    let mut labs = structures::of_type::<StructureLab>(room_name, StructureType::Lab);
+    labs.sort_by_key(|lab| (lab.pos().y(), lab.pos().x()));
+    labs
+}
+
+fn run_reactions(room_name: RoomName, reaction: Reaction) {
+    let labs = sorted_labs(room_name);
+    let (input_a, input_b) = match (labs.get(0), labs.get(1)) {
+        (Some(a), Some(b)) => (a, b),
+        _ => return,
+    };
+    if input_a.store_used_capacity(Some(reaction.inputs[0])) == 0
+        || input_b.store_used_capacity(Some(reaction.inputs[1])) == 0
+    {
+        return;
+    }
+    for lab in labs.iter().skip(2) {
+        if lab.cooldown() > 0
+            || !lab.pos().in_range_to(&input_a.pos(), 2)
+            || !lab.pos().in_range_to(&input_b.pos(), 2)
+        {
+            continue;
+        }
+        match lab.run_reaction(input_a, input_b) {
+            ReturnCode::Ok => {}
+            // Full or holding something else, the carriers empty it
+            ReturnCode::Full | ReturnCode::InvalidArgs | ReturnCode::NotEnough => {}
+            code => warn!(
+                "Reaction of {:?} in {} failed: {:?}",
+                reaction.product, room_name, code
+            ),
+        }
+    }
+}
+
+/// Plans the boosts every `config::BOOST_PLAN_INTERVAL` ticks and runs the reactions, call it each
+/// tick
+pub fn run() {
+    if config::BOOST_TARGETS.is_empty() {
+        return;
+    }
+    let planned = PLAN.lock().unwrap().planned;
+    if !planned || game::time() % config::BOOST_PLAN_INTERVAL == 0 {
+        plan();
+    }
+    let lab_room = match PLAN.lock().unwrap().lab_room {
+        Some(lab_room) => lab_room,
+        None => return,
+    };
+    if let Some(room) = game::rooms::get(lab_room) {
+        pop_done(&room);
+    }
+    if let Some(reaction) = reaction(lab_room) {
+        run_reactions(lab_room, reaction);
+    }
+}
@@ -0,0 +1,173 @@
+//! Upgrading across all bases, towards the next GCL. Every `config::EMPIRE_PLAN_INTERVAL` ticks
+//! the progress of the GCL is sampled to estimate when the next level is reached.
+//!
+//! One base is the push room, the one with a terminal closest to its next RCL. Upgrading counts
+//! for the GCL in any room, but below RCL 8 it isn't capped and the room levels up on the way.
+//! Overflowing bases donate their surplus to it through the terminal instead of spawning extra
+//! workers of their own, the push room spawns them while it has the energy. A `funnel` set up in
+//! the console takes precedence.
+use std::{collections::HashSet, sync::Mutex};
+
+use lazy_static::lazy_static;
+use log::info;
+use screeps::{game, HasStore, ResourceType, Room, RoomName};
+
+use crate::{
+    config,
+    game::{owned_rooms, OwnedBy},
+    server, trade,
+};
+
+use super::{funnel, is_overflowing};
+
+/// Description of the sends to the push room
+const PUSH_SEND_DESCRIPTION: &str = "GCL push";
+/// Weight of a new GCL rate sample in the average
+const RATE_WEIGHT: f64 = 0.3;
+/// Highest controller level, upgrading there is capped
+const MAX_RCL: u32 = 8;
+
+#[derive(Debug, Clone, Copy)]
+struct GclSample {
+    time: u32,
+    level: u32,
+    progress: f64,
+}
+
+#[derive(Debug, Default)]
+struct EmpirePlan {
+    last_sample: Option<GclSample>,
+    /// GCL progress per tick
+    rate: Option<f64>,
+    push_room: Option<RoomName>,
+    donors: HashSet<RoomName>,
+}
+
+lazy_static! {
+    static ref PLAN: Mutex<EmpirePlan> = Mutex::new(EmpirePlan::default());
+}
+
+/// How a base takes part in the push
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpgradeRole {
+    Push,
+    /// Sends its surplus to the push room
+    Donor,
+    None,
+}
+
+pub fn role_of(room_name: RoomName) -> UpgradeRole {
+    if let Some(role) = funnel::role_of(room_name) {
+        return role;
+    }
+    let plan = PLAN.lock().unwrap();
+    if plan.push_room == Some(room_name) {
+        UpgradeRole::Push
+    } else if plan.donors.contains(&room_name) {
+        UpgradeRole::Donor
+    } else {
+        UpgradeRole::None
+    }
+}
+
+/// Workers the push room spawns on top of its target, none while it is short of energy
+pub fn push_extra_workers(room: &Room) -> u8 {
+    let stored = room
+        .storage()
+        .map(|s| s.store_used_capacity(Some(ResourceType::Energy)))
+        .unwrap_or(0);
+    if stored >= config::EMPIRE_PUSH_MIN_STORED_ENERGY {
+        funnel::extra_workers().unwrap_or(config::EMPIRE_PUSH_EXTRA_WORKERS)
+    } else {
+        0
+    }
+}
+
+fn sample_gcl(plan: &mut EmpirePlan) {
+    let sample = GclSample {
+        time: game::time(),
+        level: game::gcl::level(),
+        progress: game::gcl::progress(),
+    };
+    if let Some(last) = plan.last_sample {
+        // A level up resets the progress, that sample is skipped
+        if last.level == sample.level && sample.time > last.time {
+            let rate = (sample.progress - last.progress) / (sample.time - last.time) as f64;
+            plan.rate = Some(match plan.rate {
+                Some(avg) => avg + (rate - avg) * RATE_WEIGHT,
+                None => rate,
+            });
+        }
+    }
+    plan.last_sample = Some(sample);
+    let left = game::gcl::progress_total() - sample.progress;
+    match plan.rate {
+        Some(rate) if rate > 0. => info!(
+            "GCL {} at {:.0}%, next level in about {:.0} ticks",
+            sample.level,
+            sample.progress / game::gcl::progress_total() * 100.,
+            left / rate
+        ),
+        _ => info!("GCL {}, no progress measured yet", sample.level),
+    }
+}
+
+/// Controller progress the room still needs for its next level, `None` at RCL 8 or without a
+/// terminal to receive energy
+fn progress_left(room: &Room) -> Option<u32> {
+    let controller = room.controller()?;
+    if controller.level() >= MAX_RCL || room.terminal().is_none() {
+        return None;
+    }
+    Some(
+        controller
+            .progress_total()
+            .unwrap_or(0)
+            .saturating_sub(controller.progress().unwrap_or(0)),
+    )
+}
+
+fn has_planned_push(donor: RoomName) -> bool {
+    trade::has_planned_send(donor, PUSH_SEND_DESCRIPTION)
+}
+
+/// Picks the push room and has the overflowing bases send it energy, call it each tick
+pub fn plan() {
+    if game::time() % config::EMPIRE_PLAN_INTERVAL != 0 {
+        return;
+    }
+    let mut plan = PLAN.lock().unwrap();
+    sample_gcl(&mut plan);
+
+    let bases = owned_rooms(OwnedBy::Me);
+    let push_room = bases
+        .values()
+        .filter_map(|room| progress_left(room).map(|left| (room.name(), left)))
+        .min_by_key(|(_, left)| *left)
+        .map(|(name, _)| name);
+    if push_room != plan.push_room {
+        info!("GCL push room is now {:?}", push_room);
+    }
+    plan.push_room = push_room;
+    plan.donors.clear();
+    let push_room = match push_room {
+        // Sends need the market for their fee, a funnel sends on its own
+        Some(push_room) if server::has_market() && !funnel::is_active() => push_room,
+        _ => return,
+    };
+    for room in bases.values() {
+        if room.name() == push_room || room.terminal().is_none() || !is_overflowing(room) {
+            continue;
+        }
+        plan.donors.insert(room.name());
+        if !has_planned_push(room.name()) {
+            trade::plan_send(
+                room.name(),
+                push_room,
+                ResourceType::Energy,
+                config::EMPIRE_PUSH_SEND_AMOUNT,
+                PUSH_SEND_DESCRIPTION,
+            );
+        }
+    }
+}
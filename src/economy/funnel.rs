@@ -0,0 +1,190 @@
+//! Funnel mode, a GCL push set up by hand. `funnel(room, donors, options)` in the console makes
+//! `room` the push room: every donor sends it energy through the terminal at regular intervals and
+//! it spawns more workers to upgrade with it. A donor whose storage drops below the floor stops
+//! sending, once every donor stopped the funnel shuts itself off. `endFunnel()` ends it early.
+//!
+//! While a funnel is set up it replaces the push room `empire` picks.
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+use log::{info, warn};
+use screeps::{game, HasStore, ResourceType, RoomName};
+use serde::{Deserialize, Serialize};
+
+use crate::{config, constants::MEM_FUNNEL, trade};
+
+use super::empire::UpgradeRole;
+
+/// Description of the sends of the funnel
+const FUNNEL_SEND_DESCRIPTION: &str = "Funnel";
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct Funnel {
+    room: String,
+    donors: Vec<String>,
+    /// Donors stop sending with less energy in their storage
+    floor: u32,
+    /// Energy per send
+    amount: u32,
+    /// Ticks between two sends of a donor
+    interval: u32,
+    /// Workers the push room spawns on top of its target
+    workers: u8,
+    /// Donors that dropped below the floor
+    #[serde(default)]
+    stopped: Vec<String>,
+}
+
+js_serializable!(Funnel);
+js_deserializable!(Funnel);
+
+lazy_static! {
+    /// Tick `Memory.funnel` was last read at and what it held
+    static ref FUNNEL: Mutex<(u32, Option<Funnel>)> = Mutex::new((0, None));
+}
+
+impl Funnel {
+    fn push_room(&self) -> Option<RoomName> {
+        RoomName::new(&self.room).ok()
+    }
+
+    fn active_donors(&self) -> Vec<RoomName> {
+        self.donors
+            .iter()
+            .filter(|donor| !self.stopped.contains(donor))
+            .filter_map(|donor| RoomName::new(donor).ok())
+            .collect()
+    }
+}
+
+fn read() -> Option<Funnel> {
+    match screeps::memory::root().get::<Funnel>(MEM_FUNNEL) {
+        Ok(funnel) => funnel,
+        Err(err) => {
+            warn!("Could not load funnel: {}", err);
+            None
+        }
+    }
+}
+
+/// The funnel, read from memory once per tick
+fn load() -> Option<Funnel> {
+    let time = game::time();
+    let mut cached = FUNNEL.lock().unwrap();
+    if cached.0 != time {
+        *cached = (time, read());
+    }
+    cached.1.clone()
+}
+
+/// Writes the funnel to memory, `None` ends it
+fn store(funnel: Option<Funnel>) {
+    match &funnel {
+        Some(funnel) => screeps::memory::root().set(MEM_FUNNEL, funnel.clone()),
+        None => screeps::memory::root().del(MEM_FUNNEL),
+    }
+    *FUNNEL.lock().unwrap() = (game::time(), funnel);
+}
+
+pub fn is_active() -> bool {
+    load().is_some()
+}
+
+/// How the room takes part in the funnel, `None` without one
+pub fn role_of(room_name: RoomName) -> Option<UpgradeRole> {
+    let funnel = load()?;
+    if funnel.push_room() == Some(room_name) {
+        Some(UpgradeRole::Push)
+    } else if funnel.active_donors().contains(&room_name) {
+        Some(UpgradeRole::Donor)
+    } else {
+        Some(UpgradeRole::None)
+    }
+}
+
+/// Workers the push room of the funnel spawns on top of its target
+pub fn extra_workers() -> Option<u8> {
+    load().map(|funnel| funnel.workers)
+}
+
+/// Has the donors send their energy and stops the ones below the floor, call it each tick
+pub fn run() {
+    let mut funnel = match load() {
+        Some(funnel) => funnel,
+        None => return,
+    };
+    let push_room = match funnel.push_room() {
+        Some(push_room) => push_room,
+        None => {
+            warn!("Funnel to unknown room {}, ending it", funnel.room);
+            store(None);
+            return;
+        }
+    };
+    if funnel.interval == 0 || game::time() % funnel.interval != 0 {
+        return;
+    }
+    for donor in funnel.active_donors() {
+        let stored = game::rooms::get(donor)
+            .and_then(|room| room.storage())
+            .map(|s| s.store_used_capacity(Some(ResourceType::Energy)))
+            .unwrap_or(0);
+        if stored < funnel.floor {
+            warn!(
+                "Funnel donor {} is down to {} energy, it stops sending to {}",
+                donor, stored, push_room
+            );
+            funnel.stopped.push(donor.to_string());
+            continue;
+        }
+        if !trade::has_planned_send(donor, FUNNEL_SEND_DESCRIPTION) {
+            trade::plan_send(
+                donor,
+                push_room,
+                ResourceType::Energy,
+                funnel.amount,
+                FUNNEL_SEND_DESCRIPTION,
+            );
+        }
+    }
+    if funnel.active_donors().is_empty() {
+        info!(
+            "All donors of the funnel to {} stopped, ending it",
+            push_room
+        );
+        store(None);
+    } else {
+        store(Some(funnel));
+    }
+}
+
+/// Makes `funnel(room, donors, options)` and `endFunnel()` available in the console. The options
+/// `floor`, `amount`, `interval` and `workers` default to the `config::FUNNEL_*` values.
+pub fn register_console() {
+    js! {
+        var key = @{MEM_FUNNEL};
+        var defaults = {
+            floor: @{config::FUNNEL_DONOR_FLOOR},
+            amount: @{config::FUNNEL_SEND_AMOUNT},
+            interval: @{config::FUNNEL_SEND_INTERVAL},
+            workers: @{config::FUNNEL_EXTRA_WORKERS as u32},
+        };
+        global.funnel = function(room, donors, options) {
+            options = options || {};
+            Memory[key] = {
+                room: room,
+                donors: donors || [],
+                floor: options.floor !== undefined ? options.floor : defaults.floor,
+                amount: options.amount || defaults.amount,
+                interval: options.interval || defaults.interval,
+                workers: options.workers !== undefined ? options.workers : defaults.workers,
+                stopped: [],
+            };
+            return "Funneling energy of " + Memory[key].donors.join(", ") + " to " + room;
+        };
+        global.endFunnel = function() {
+            delete Memory[key];
+            return "Funnel ended";
+        };
+    }
+}
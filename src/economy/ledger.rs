@@ -0,0 +1,181 @@
+//! Energy the rooms spend on spawning, by what the creeps are for. Every spawn is booked to the
+//! room it was requested for, not the one spawning it, so a remote or a rescued base carries its
+//! own cost. Periods last `config::SPAWN_LEDGER_PERIOD` ticks, `Memory.spawn_ledger` keeps the
+//! running one and the last, to compare against what the sources of the room bring in, see
+//! `rooms::harvest_stats`.
+//!
+//! Big spawns ask for a forecast first: what the sources of the room and its remotes bring in over
+//! the spawn time, less what they spent on spawning per tick in the last period, has to cover the
+//! body. Energy in the storage above `config::SPAWN_FORECAST_STORAGE_FLOOR` counts as well.
+use std::{collections::HashMap, sync::Mutex};
+
+use lazy_static::lazy_static;
+use log::{debug, info, warn};
+use screeps::{HasStore, ResourceType, Room, RoomName};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config,
+    constants::MEM_SPAWN_LEDGER,
+    creeps::jobs::OokCreepJob,
+    rooms::{harvest_stats, remotes},
+    state::{requests, UniqId},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpawnPurpose {
+    /// Farming, hauling, building and upgrading at home
+    Economy = 0,
+    /// Mining, reserving and guarding remotes
+    Remotes = 1,
+    Defense = 2,
+    /// Replacing the creeps of a base in panic
+    Rescue = 3,
+    /// Claiming and setting up new bases, dismantling, salvaging and scouting
+    Expansion = 4,
+}
+
+const PURPOSES: usize = 5;
+
+impl SpawnPurpose {
+    pub fn of_job(job: &OokCreepJob) -> SpawnPurpose {
+        match job {
+            OokCreepJob::FarmExtensionRoom { .. }
+            | OokCreepJob::LogisticsExtensionRoom { .. }
+            | OokCreepJob::ReserveRoom { .. }
+            | OokCreepJob::PatrolRemotes { .. } => SpawnPurpose::Remotes,
+            OokCreepJob::DefendRoom { .. } | OokCreepJob::HealCitizens { .. } => {
+                SpawnPurpose::Defense
+            }
+            OokCreepJob::ClaimRoom { .. }
+            | OokCreepJob::CrossPortal(_)
+            | OokCreepJob::Dismantle(_)
+            | OokCreepJob::Salvage(_)
+            | OokCreepJob::ScoutRoom { .. } => SpawnPurpose::Expansion,
+            _ => SpawnPurpose::Economy,
+        }
+    }
+
+    pub fn of_citizen(citizen: &requests::Citizen) -> SpawnPurpose {
+        if citizen.resolve_panic {
+            SpawnPurpose::Rescue
+        } else {
+            SpawnPurpose::of_job(&citizen.initial_job)
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct SpawnLedger {
+    period_start: u32,
+    /// Energy per room, indexed by `SpawnPurpose`
+    current: HashMap<String, [u32; PURPOSES]>,
+    last: HashMap<String, [u32; PURPOSES]>,
+}
+
+js_serializable!(SpawnLedger);
+js_deserializable!(SpawnLedger);
+
+lazy_static! {
+    static ref LEDGER: Mutex<Option<SpawnLedger>> = Mutex::new(None);
+}
+
+fn with_ledger<T, F: FnOnce(&mut SpawnLedger) -> T>(f: F) -> T {
+    let mut ledger = LEDGER.lock().unwrap();
+    let ledger = ledger.get_or_insert_with(|| {
+        match screeps::memory::root().get::<SpawnLedger>(MEM_SPAWN_LEDGER) {
+            Ok(Some(ledger)) => ledger,
+            Ok(None) => Default::default(),
+            Err(err) => {
+                warn!("Could not load spawn ledger: {}", err);
+                Default::default()
+            }
+        }
+    });
+    let time = screeps::game::time();
+    if ledger.period_start + config::SPAWN_LEDGER_PERIOD <= time {
+        for (room, spent) in &ledger.current {
+            info!(
+                "Spawning in the last period for {}: {:?}",
+                room,
+                spent_by_purpose(spent)
+            );
+        }
+        *ledger = SpawnLedger {
+            period_start: time,
+            current: Default::default(),
+            last: std::mem::take(&mut ledger.current),
+        };
+    }
+    f(ledger)
+}
+
+fn spent_by_purpose(spent: &[u32; PURPOSES]) -> Vec<(SpawnPurpose, u32)> {
+    [
+        SpawnPurpose::Economy,
+        SpawnPurpose::Remotes,
+        SpawnPurpose::Defense,
+        SpawnPurpose::Rescue,
+        SpawnPurpose::Expansion,
+    ]
+    .iter()
+    .map(|purpose| (*purpose, spent[*purpose as usize]))
+    .filter(|(_, energy)| *energy > 0)
+    .collect()
+}
+
+/// Books the energy of a spawn to the room the creep was requested for
+pub fn record_spawn(room_name: RoomName, purpose: SpawnPurpose, request_id: &UniqId, energy: u32) {
+    debug!(
+        "Spawn for request {} of {} cost {} energy ({:?})",
+        request_id, room_name, energy, purpose
+    );
+    with_ledger(|ledger| {
+        ledger.current.entry(room_name.to_string()).or_default()[purpose as usize] += energy;
+        screeps::memory::root().set(MEM_SPAWN_LEDGER, ledger.clone());
+    });
+}
+
+/// Energy per tick the room spends on spawning, over the last period or the running one while
+/// there is no last one
+pub fn upkeep(room_name: RoomName) -> Option<f64> {
+    let time = screeps::game::time();
+    with_ledger(|ledger| {
+        let key = room_name.to_string();
+        if let Some(spent) = ledger.last.get(&key) {
+            return Some(spent.iter().sum::<u32>() as f64 / config::SPAWN_LEDGER_PERIOD as f64);
+        }
+        let elapsed = time.saturating_sub(ledger.period_start);
+        if elapsed == 0 {
+            return None;
+        }
+        ledger
+            .current
+            .get(&key)
+            .map(|spent| spent.iter().sum::<u32>() as f64 / elapsed as f64)
+    })
+}
+
+/// Energy the room has left after spawning the body, `None` without harvest stats of the room.
+/// Its remotes count in.
+///
+/// Negative if the income over the spawn time doesn't cover the body on top of the upkeep.
+pub fn forecast(room: &Room, body_energy: u32, spawn_ticks: u32) -> Option<f64> {
+    let remotes = remotes::remotes_of(room.name());
+    let remote_income: f64 = remotes
+        .iter()
+        .filter_map(|remote| harvest_stats::room_income(*remote))
+        .sum();
+    let income = harvest_stats::room_income(room.name())? + remote_income;
+    // Spawns for the remotes are booked to them
+    let spent: f64 = std::iter::once(room.name())
+        .chain(remotes)
+        .filter_map(upkeep)
+        .sum();
+    let stored = room
+        .storage()
+        .map(|storage| storage.store_used_capacity(Some(ResourceType::Energy)))
+        .unwrap_or(0)
+        .saturating_sub(config::SPAWN_FORECAST_STORAGE_FLOOR);
+    Some(stored as f64 + (income - spent) * spawn_ticks as f64 - body_energy as f64)
+}
@@ -0,0 +1,149 @@
+//! Errors sorted by what the tick loop does about them, see `pipeline::run`. A transient error
+//! goes away on its own and the stage just tries again next tick. Corrupted state gets the
+//! subsystem of the stage reset, a refusing game API is only logged and a logic error, a bug,
+//! also sends an alert.
+//!
+//! Only the stages of the tick loop return `OokResult`. The modules below them, the room states
+//! and the creeps included, keep returning `anyhow` errors, the category is found from the error
+//! enums they carry when they are turned into an `OokError`. An error that doesn't tell counts as
+//! transient, only errors tagged as such are taken for bugs.
+use std::{error::Error, fmt};
+
+use crate::{
+    creeps::{races::RacesError, CreepError},
+    rooms::{resource_provider::ResourceProviderError, room_state::RoomStateError, RoomError},
+    state::ContextError,
+    utils::UtilError,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCategory {
+    Transient,
+    StateCorruption,
+    GameApi,
+    Logic,
+}
+
+impl fmt::Display for ErrorCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorCategory::Transient => f.write_str("transient"),
+            ErrorCategory::StateCorruption => f.write_str("state_corruption"),
+            ErrorCategory::GameApi => f.write_str("game_api"),
+            ErrorCategory::Logic => f.write_str("logic"),
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum OokError {
+    /// Gone by the next tick, like an object out of sight or a room without vision
+    #[error("[Transient] {0}")]
+    Transient(anyhow::Error),
+    /// What is kept between ticks contradicts itself or the game
+    #[error("[StateCorruption] {0}")]
+    StateCorruption(anyhow::Error),
+    /// The game refused a call or answered with something unreadable
+    #[error("[GameApi] {0}")]
+    GameApi(anyhow::Error),
+    /// A bug
+    #[error("[Logic] {0}")]
+    Logic(anyhow::Error),
+}
+
+pub type OokResult<T> = Result<T, OokError>;
+
+impl OokError {
+    pub fn new(category: ErrorCategory, err: anyhow::Error) -> OokError {
+        match category {
+            ErrorCategory::Transient => OokError::Transient(err),
+            ErrorCategory::StateCorruption => OokError::StateCorruption(err),
+            ErrorCategory::GameApi => OokError::GameApi(err),
+            ErrorCategory::Logic => OokError::Logic(err),
+        }
+    }
+
+    /// For errors of the game bindings, which don't tell more than their message
+    pub fn game_api(err: impl fmt::Display) -> OokError {
+        OokError::GameApi(anyhow::Error::msg(err.to_string()))
+    }
+
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            OokError::Transient(_) => ErrorCategory::Transient,
+            OokError::StateCorruption(_) => ErrorCategory::StateCorruption,
+            OokError::GameApi(_) => ErrorCategory::GameApi,
+            OokError::Logic(_) => ErrorCategory::Logic,
+        }
+    }
+}
+
+/// Category of the first error in the chain that tells one, `Transient` if none does
+fn categorize(err: &(dyn Error + 'static)) -> ErrorCategory {
+    let mut current = Some(err);
+    while let Some(err) = current {
+        if let Some(category) = category_of(err) {
+            return category;
+        }
+        current = err.source();
+    }
+    ErrorCategory::Transient
+}
+
+fn category_of(err: &(dyn Error + 'static)) -> Option<ErrorCategory> {
+    if let Some(err) = err.downcast_ref::<OokError>() {
+        return Some(err.category());
+    }
+    if err.is::<ContextError>() || err.is::<RacesError>() {
+        return Some(ErrorCategory::StateCorruption);
+    }
+    if let Some(err) = err.downcast_ref::<CreepError>() {
+        return Some(match err {
+            CreepError::ObjectNotFound(_)
+            | CreepError::RoomNotFound()
+            | CreepError::SourceNotFound(_) => ErrorCategory::Transient,
+            CreepError::MissingPost(_)
+            | CreepError::MissingAssignedSource(_)
+            | CreepError::MissingFarmPosition(_)
+            | CreepError::ResourceProviderIdNotStored => ErrorCategory::StateCorruption,
+            CreepError::CreepNotConvertible() | CreepError::RepairNotAttackable(_) => {
+                ErrorCategory::Logic
+            }
+        });
+    }
+    if let Some(err) = err.downcast_ref::<ResourceProviderError>() {
+        return Some(match err {
+            ResourceProviderError::ObjectNotFound(_) => ErrorCategory::Transient,
+            _ => ErrorCategory::Logic,
+        });
+    }
+    if let Some(err) = err.downcast_ref::<RoomError>() {
+        return Some(match err {
+            RoomError::RoomNotFound(..) => ErrorCategory::Transient,
+            RoomError::RoomNotConfigured(..) => ErrorCategory::StateCorruption,
+            _ => ErrorCategory::Logic,
+        });
+    }
+    if let Some(err) = err.downcast_ref::<UtilError>() {
+        return Some(match err {
+            UtilError::ObjectNotFound(_) => ErrorCategory::Transient,
+            UtilError::InvalidId(_) => ErrorCategory::StateCorruption,
+        });
+    }
+    if err.is::<RoomStateError>() {
+        return Some(ErrorCategory::Transient);
+    }
+    None
+}
+
+impl From<anyhow::Error> for OokError {
+    fn from(err: anyhow::Error) -> OokError {
+        // Already sorted somewhere down the stack
+        let err = match err.downcast::<OokError>() {
+            Ok(err) => return err,
+            Err(err) => err,
+        };
+        let category = categorize(err.as_ref());
+        OokError::new(category, err)
+    }
+}
@@ -0,0 +1,191 @@
+//! Operations started by hand with flags. The name of a flag picks the operation, anything after
+//! the first non-letter is free to tell flags apart, like `claim-2` or `avoid W3N7`:
+//!
+//! - `claim` has the closest base send a claimer to the room of the flag. Once the room is mine it
+//!   gets a room state and the flag is removed.
+//! - `attack` sends a dismantler after the hostile structures at the flag, or all of them in the
+//!   room if there are none at the flag. The flag is removed once they are gone. It needs sight of
+//!   the room to pick the targets and asks for it, see `visibility`.
+//! - `avoid` makes paths lead around the room for as long as the flag stands, see `intel::avoids`
+//!
+//! Other flags are left alone. The operations go through the requests like everything else, so
+//! `Memory.ook_flags` pauses them too, see `switches`.
+use std::collections::HashSet;
+
+use log::{info, warn};
+use screeps::{
+    find, game, look, Flag, HasId, HasPosition, ObjectId, OwnedStructureProperties, RoomName,
+    Structure, StructureProperties, StructureType,
+};
+
+use crate::{
+    creeps::{jobs::OokCreepJob, races::OokRace},
+    rooms::{intel, remotes::linear_distance, room_state::RoomState, visibility},
+    state::{
+        requests::{self, Request, RequestData},
+        BWState,
+    },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operation {
+    Claim,
+    Attack,
+    Avoid,
+}
+
+impl Operation {
+    fn of(flag: &Flag) -> Option<Operation> {
+        let name = flag.name().to_lowercase();
+        let word: String = name.chars().take_while(|c| c.is_alphabetic()).collect();
+        match word.as_str() {
+            "claim" => Some(Operation::Claim),
+            "attack" => Some(Operation::Attack),
+            "avoid" => Some(Operation::Avoid),
+            _ => None,
+        }
+    }
+}
+
+/// The base closest to the room
+fn closest_base(state: &BWState, room_name: RoomName) -> Option<RoomName> {
+    state
+        .room_states
+        .iter()
+        .filter(|(_, room_state)| matches!(room_state, RoomState::Base(_)))
+        .map(|(base, _)| *base)
+        .min_by_key(|base| linear_distance(*base, room_name))
+}
+
+fn claim(state: &mut BWState, flag: &Flag) -> anyhow::Result<()> {
+    let room_name = flag.pos().room_name();
+    let mine = game::rooms::get(room_name)
+        .and_then(|room| room.controller())
+        .map(|controller| controller.my())
+        .unwrap_or(false);
+    if mine {
+        if !state.room_states.contains_key(&room_name)
+            && !state.pending_room_states.contains(&room_name)
+        {
+            info!("Claimed {}, setting it up", room_name);
+            state.pending_room_states.push_back(room_name);
+        }
+        flag.remove();
+        return Ok(());
+    }
+    let base = match closest_base(state, room_name) {
+        Some(base) => base,
+        None => return Ok(()),
+    };
+    let request = Request::new(RequestData::Citizen(requests::Citizen {
+        target_room_name: base,
+        spawning_creep_name: None,
+        initial_job: OokCreepJob::ClaimRoom {
+            target_room: room_name,
+        },
+        resolve_panic: false,
+        spawn_elsewhere: false,
+    }));
+    if state.claims.is_duplicate(&request) {
+        return Ok(());
+    }
+    info!("Flag {}: {} claims {}", flag.name(), base, room_name);
+    state.add_request(request)
+}
+
+/// Hostile structures at the flag, or in its room without any at the flag
+fn attack_targets(flag: &Flag) -> Option<Vec<ObjectId<Structure>>> {
+    let room = game::rooms::get(flag.pos().room_name())?;
+    let is_target = |structure: &Structure| {
+        structure.structure_type() != StructureType::Controller
+            && structure
+                .as_owned()
+                .map(|owned| owned.has_owner() && !owned.my())
+                .unwrap_or(false)
+    };
+    let at_flag: Vec<ObjectId<Structure>> = room
+        .look_for_at(look::STRUCTURES, &flag.pos())
+        .iter()
+        .filter(|structure| is_target(structure))
+        .map(|structure| structure.id())
+        .collect();
+    if !at_flag.is_empty() {
+        return Some(at_flag);
+    }
+    Some(
+        room.find(find::STRUCTURES)
+            .iter()
+            .filter(|structure| is_target(structure))
+            .map(|structure| structure.id())
+            .collect(),
+    )
+}
+
+/// A dismantle request for the room is open or a dismantler works there already
+fn is_attack_handled(state: &BWState, room_name: RoomName) -> bool {
+    let requested = state
+        .requests
+        .values()
+        .chain(state.handled_requests.values().flat_map(|requests| requests.values()))
+        .any(|request| {
+            matches!(&request.data, RequestData::Dismantle(data) if data.target_room_name == room_name)
+        });
+    let working = state.citizens.values().any(
+        |citizen| matches!(citizen, OokRace::Dismantler(d) if d.job.target_room() == room_name),
+    );
+    requested || working
+}
+
+fn attack(state: &mut BWState, flag: &Flag) -> anyhow::Result<()> {
+    let room_name = flag.pos().room_name();
+    let targets = match attack_targets(flag) {
+        Some(targets) => targets,
+        None => {
+            visibility::request(room_name, None);
+            return Ok(());
+        }
+    };
+    if targets.is_empty() {
+        info!(
+            "Flag {}: nothing left to attack in {}",
+            flag.name(),
+            room_name
+        );
+        flag.remove();
+        return Ok(());
+    }
+    if is_attack_handled(state, room_name) {
+        return Ok(());
+    }
+    info!(
+        "Flag {}: attacking {} structures in {}",
+        flag.name(),
+        targets.len(),
+        room_name
+    );
+    state.add_request(Request::new(RequestData::Dismantle(requests::Dismantle {
+        target_room_name: room_name,
+        targets,
+        spawning_creep_name: None,
+    })))
+}
+
+/// Runs the operations of all flags, call it each tick
+pub fn run(state: &mut BWState) {
+    let mut avoided: HashSet<RoomName> = HashSet::new();
+    for flag in game::flags::values() {
+        let result = match Operation::of(&flag) {
+            Some(Operation::Claim) => claim(state, &flag),
+            Some(Operation::Attack) => attack(state, &flag),
+            Some(Operation::Avoid) => {
+                avoided.insert(flag.pos().room_name());
+                Ok(())
+            }
+            None => Ok(()),
+        };
+        if let Err(err) = result {
+            warn!("Flag {} failed: {}", flag.name(), err);
+        }
+    }
+    intel::forbid(avoided);
+}
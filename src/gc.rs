@@ -0,0 +1,136 @@
+//! Garbage collection of the memory and the state. Every `config::GC_STEP_TICKS` ticks one kind of
+//! garbage is collected, the next step takes the next kind, so no tick pays for all of them:
+//!
+//! - the memory of dead creeps, their tasks and jobs with it
+//! - the states of rooms I lost and what `Memory.ook_rooms_data.ook_rooms` keeps of rooms no state
+//!   is tracked for
+//! - handled requests older than `config::GC_REQUEST_HISTORY_TICKS`
+//! - the intel of rooms unseen for `config::GC_INTEL_TICKS`
+use std::collections::HashSet;
+
+use log::{debug, info, warn};
+use screeps::{game, OwnedStructureProperties, RoomName};
+
+use crate::{
+    config,
+    constants::{MEM_OOK_ROOMS, MEM_OOK_ROOMS_DATA},
+    rooms::intel,
+    state::BWState,
+};
+
+/// Offset of the steps in `config::GC_STEP_TICKS`, away from the other periodic work
+const STEP_OFFSET: u32 = 3;
+
+#[derive(Debug, Clone, Copy)]
+enum Garbage {
+    Creeps,
+    Rooms,
+    Requests,
+    Intel,
+}
+
+const GARBAGE: [Garbage; 4] = [
+    Garbage::Creeps,
+    Garbage::Rooms,
+    Garbage::Requests,
+    Garbage::Intel,
+];
+
+/// Collects the garbage due in this tick, call it each tick
+pub fn run(state: &mut BWState) {
+    let time = game::time();
+    if time % config::GC_STEP_TICKS != STEP_OFFSET {
+        return;
+    }
+    let garbage = GARBAGE[(time / config::GC_STEP_TICKS) as usize % GARBAGE.len()];
+    debug!("Collecting garbage: {:?}", garbage);
+    match garbage {
+        Garbage::Creeps => creep_memory(),
+        Garbage::Rooms => rooms(state),
+        Garbage::Requests => requests(state),
+        Garbage::Intel => {
+            let forgotten = intel::forget_unseen(config::GC_INTEL_TICKS);
+            if forgotten > 0 {
+                info!("Forgot the intel of {} rooms", forgotten);
+            }
+        }
+    }
+}
+
+fn creep_memory() {
+    let alive_creeps: HashSet<String> = game::creeps::keys().into_iter().collect();
+    let creeps_memory = match screeps::memory::root().dict("creeps") {
+        Ok(Some(creeps_memory)) => creeps_memory,
+        Ok(None) => return,
+        Err(err) => {
+            warn!(
+                "Not cleaning creep memory, Memory.creeps is no object: {}",
+                err
+            );
+            return;
+        }
+    };
+    for name in creeps_memory.keys() {
+        if !alive_creeps.contains(&name) {
+            debug!("Cleaning up the memory of dead creep {}", name);
+            creeps_memory.del(&name);
+        }
+    }
+}
+
+fn is_mine(room_name: RoomName) -> bool {
+    game::rooms::get(room_name)
+        .and_then(|room| room.controller())
+        .map(|controller| controller.my())
+        .unwrap_or(false)
+}
+
+fn rooms(state: &mut BWState) {
+    let lost: Vec<RoomName> = state
+        .room_states
+        .keys()
+        .filter(|room_name| !is_mine(**room_name))
+        .copied()
+        .collect();
+    for room_name in lost {
+        warn!("Lost {}, dropping its room state", room_name);
+        if let Some(mut room_state) = state.room_states.remove(&room_name) {
+            if let Err(err) = room_state.on_exit(state) {
+                warn!("Error exiting the state of {}: {}", room_name, err);
+            }
+        }
+    }
+
+    let rooms_memory = match screeps::memory::root()
+        .dict(MEM_OOK_ROOMS_DATA)
+        .ok()
+        .flatten()
+        .and_then(|data| data.dict(MEM_OOK_ROOMS).ok().flatten())
+    {
+        Some(rooms_memory) => rooms_memory,
+        None => return,
+    };
+    for name in rooms_memory.keys() {
+        let tracked = RoomName::new(&name)
+            .map(|room_name| {
+                state.room_states.contains_key(&room_name)
+                    || state.pending_room_states.contains(&room_name)
+                    || is_mine(room_name)
+            })
+            .unwrap_or(false);
+        if !tracked {
+            info!("Cleaning up the memory of room {}", name);
+            rooms_memory.del(&name);
+        }
+    }
+}
+
+fn requests(state: &mut BWState) {
+    let oldest = game::time().saturating_sub(config::GC_REQUEST_HISTORY_TICKS);
+    let before = state.handled_requests.len();
+    state.handled_requests.retain(|tick, _| *tick >= oldest);
+    let forgotten = before - state.handled_requests.len();
+    if forgotten > 0 {
+        debug!("Forgot the handled requests of {} ticks", forgotten);
+    }
+}
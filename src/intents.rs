@@ -0,0 +1,216 @@
+//! Intents of one tick. Systems that don't know of each other can act on the same tower or creep in
+//! the same tick, the game then just keeps whatever came last. Instead they submit their action
+//! here with a priority, the `Intents` stage issues the winner of every conflict at the end of the
+//! tick.
+//!
+//! Two actions conflict if the same object does them and they belong to the same `Slot`. A creep
+//! can repair and transfer in one tick, but not transfer and withdraw.
+use std::{collections::HashMap, sync::Mutex};
+
+use lazy_static::lazy_static;
+use log::{debug, warn};
+use screeps::{Creep, ObjectId, RawObjectId, ResourceType, ReturnCode, Structure, StructureTower};
+
+use crate::state::get_object_cached;
+
+/// Who wins a conflict, the higher one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    /// Done on the side, if nothing else wants to
+    Passing,
+    /// Keeping structures up
+    Upkeep,
+    /// What the task of a citizen asks for
+    Task,
+    /// Keeping the room alive
+    Defense,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Actor {
+    Creep(ObjectId<Creep>),
+    Tower(ObjectId<StructureTower>),
+}
+
+impl Actor {
+    fn id(&self) -> RawObjectId {
+        match self {
+            Actor::Creep(id) => (*id).into(),
+            Actor::Tower(id) => (*id).into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Action {
+    Attack {
+        target: ObjectId<Creep>,
+    },
+    Repair {
+        target: ObjectId<Structure>,
+    },
+    Heal {
+        target: ObjectId<Creep>,
+    },
+    Transfer {
+        target: ObjectId<Structure>,
+        resource_type: ResourceType,
+        amount: u32,
+    },
+    Withdraw {
+        target: ObjectId<Structure>,
+        resource_type: ResourceType,
+        amount: u32,
+    },
+}
+
+/// Actions of one object in the same slot cancel each other
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Slot {
+    /// Towers do one thing per tick
+    Tower,
+    /// Attack, repair and the other WORK intents of a creep
+    Work,
+    /// Moving resources in and out of a creep
+    Cargo,
+}
+
+impl Action {
+    fn slot(&self, actor: &Actor) -> Slot {
+        match (actor, self) {
+            (Actor::Tower(_), _) => Slot::Tower,
+            (Actor::Creep(_), Action::Attack { .. })
+            | (Actor::Creep(_), Action::Repair { .. })
+            | (Actor::Creep(_), Action::Heal { .. }) => Slot::Work,
+            (Actor::Creep(_), Action::Transfer { .. })
+            | (Actor::Creep(_), Action::Withdraw { .. }) => Slot::Cargo,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Intent {
+    actor: Actor,
+    action: Action,
+    priority: Priority,
+}
+
+lazy_static! {
+    static ref INTENTS: Mutex<HashMap<(RawObjectId, Slot), Intent>> = Mutex::new(HashMap::new());
+}
+
+/// Records what `actor` wants to do this tick
+///
+/// Replaces an action of the same slot with a lower priority, on a tie the first one stays.
+pub fn submit(actor: Actor, action: Action, priority: Priority) {
+    let key = (actor.id(), action.slot(&actor));
+    let mut intents = INTENTS.lock().unwrap();
+    if let Some(intent) = intents.get(&key) {
+        if intent.priority >= priority {
+            debug!(
+                "Dropping {:?} of {:?}, {:?} goes first",
+                action, actor, intent.action
+            );
+            return;
+        }
+        debug!(
+            "Dropping {:?} of {:?}, {:?} goes first",
+            intent.action, actor, action
+        );
+    }
+    intents.insert(
+        key,
+        Intent {
+            actor,
+            action,
+            priority,
+        },
+    );
+}
+
+fn issue(intent: &Intent) -> anyhow::Result<ReturnCode> {
+    let code = match (&intent.actor, &intent.action) {
+        (Actor::Tower(id), action) => {
+            let tower = match get_object_cached(*id)? {
+                Some(tower) => tower,
+                None => return Ok(ReturnCode::NotFound),
+            };
+            match action {
+                Action::Attack { target } => match get_object_cached(*target)? {
+                    Some(target) => tower.attack(&target),
+                    None => ReturnCode::InvalidTarget,
+                },
+                Action::Repair { target } => match get_object_cached(*target)? {
+                    Some(target) => tower.repair(&target),
+                    None => ReturnCode::InvalidTarget,
+                },
+                Action::Heal { target } => match get_object_cached(*target)? {
+                    Some(target) => tower.heal(&target),
+                    None => ReturnCode::InvalidTarget,
+                },
+                Action::Transfer { .. } | Action::Withdraw { .. } => ReturnCode::InvalidArgs,
+            }
+        }
+        (Actor::Creep(id), action) => {
+            let creep = match get_object_cached(*id)? {
+                Some(creep) => creep,
+                None => return Ok(ReturnCode::NotFound),
+            };
+            match action {
+                Action::Attack { target } => match get_object_cached(*target)? {
+                    Some(target) => creep.attack(&target),
+                    None => ReturnCode::InvalidTarget,
+                },
+                Action::Repair { target } => match get_object_cached(*target)? {
+                    Some(target) => creep.repair(&target),
+                    None => ReturnCode::InvalidTarget,
+                },
+                Action::Heal { target } => match get_object_cached(*target)? {
+                    Some(target) => creep.heal(&target),
+                    None => ReturnCode::InvalidTarget,
+                },
+                Action::Transfer {
+                    target,
+                    resource_type,
+                    amount,
+                } => match get_object_cached(*target)? {
+                    Some(target) => match target.as_transferable() {
+                        Some(target) => creep.transfer_amount(target, *resource_type, *amount),
+                        None => ReturnCode::InvalidTarget,
+                    },
+                    None => ReturnCode::InvalidTarget,
+                },
+                Action::Withdraw {
+                    target,
+                    resource_type,
+                    amount,
+                } => match get_object_cached(*target)? {
+                    Some(target) => match target.as_withdrawable() {
+                        Some(target) => creep.withdraw_amount(target, *resource_type, *amount),
+                        None => ReturnCode::InvalidTarget,
+                    },
+                    None => ReturnCode::InvalidTarget,
+                },
+            }
+        }
+    };
+    Ok(code)
+}
+
+/// Issues the intents that won their conflicts and forgets all of them
+pub fn flush() {
+    let intents: Vec<Intent> = INTENTS.lock().unwrap().drain().map(|(_, i)| i).collect();
+    for intent in intents {
+        match issue(&intent) {
+            Ok(ReturnCode::Ok) => {}
+            Ok(code) => debug!(
+                "{:?} of {:?} failed: {:?}",
+                intent.action, intent.actor, code
+            ),
+            Err(err) => warn!(
+                "Couldn't issue {:?} of {:?}: {}",
+                intent.action, intent.actor, err
+            ),
+        }
+    }
+}
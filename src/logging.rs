@@ -1,5 +1,7 @@
 use stdweb::js;
 
+use crate::rooms::labels;
+
 pub use log::LevelFilter::*;
 
 struct JsLog;
@@ -38,7 +40,7 @@ pub fn setup_logging(verbosity: log::LevelFilter) {
                 "({}) {}: {}",
                 record.level(),
                 record.target(),
-                message
+                labels::label_message(&message.to_string())
             ))
         })
         .chain(Box::new(JsLog) as Box<dyn log::Log>)
@@ -0,0 +1,1091 @@
+//! One tick of the bot as a pipeline of named stages. Each stage is a function over the state,
+//! the stages hand each other what they found in `Tick`. Which stages run and in what order is
+//! configured by `config::TICK_STAGES`, the CPU every stage used ends up in `Memory.stats.stages`.
+use std::{collections::HashMap, convert::TryFrom, fmt};
+
+use log::*;
+use screeps::{find, game::cpu, memory, prelude::*, Creep, ObjectId, RoomName};
+
+use crate::{
+    alerts, config,
+    creeps::{
+        self,
+        harvesting::run_harvester,
+        races::{get_all_citizens_from_creeps, DynamicTasked, OokRace, RoomBound},
+        speech::{self, Verbosity},
+        CreepKind,
+    },
+    deferred, economy,
+    errors::{ErrorCategory, OokError, OokResult},
+    flags, gc,
+    game::{owned_rooms, OwnedBy},
+    intents, profiler, segments,
+    rooms::{
+        self,
+        room_state::{
+            assign_requests, base::BaseState, dummy_handle_requests, init_pending_room_states,
+            pending_room_states, persist_room_states, transition_room_state,
+            update_room_states_from_memory, RoomState, RoomStateChange, RoomStateLifecycle,
+            SetupBaseState,
+        },
+        update_maintenance, MyRoom,
+    },
+    server, shards, switches,
+    state::{requests::Request, BWContext, BWState},
+    trade, visuals,
+};
+
+const MEM_STATS_STAGES: &str = "stats.stages";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// Sources and remotes seen this tick
+    Intel,
+    /// Creeps still living from before the citizens, they handle the context themselves
+    Legacy,
+    /// Room states look at their rooms and come up with requests
+    RoomStates,
+    /// Requests of the room states are added to the state
+    Requests,
+    /// Requests get assigned to rooms, which spawn for them
+    Spawning,
+    /// Citizens do their jobs
+    Creeps,
+    /// Sorts out what the moves of the creeps left behind
+    Movement,
+    /// Citizens, room states and memory are written back
+    Persistence,
+    /// Market, terminals and the GCL push
+    Trade,
+    /// Intents submitted to `intents` get issued, the ones winning their conflicts
+    Intents,
+    /// Heavy work queued in `deferred`, while the bucket allows
+    Deferred,
+    /// CPU usage and pixels
+    Stats,
+}
+
+impl Stage {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Stage::Intel => "intel",
+            Stage::Legacy => "legacy",
+            Stage::RoomStates => "room_states",
+            Stage::Requests => "requests",
+            Stage::Spawning => "spawning",
+            Stage::Creeps => "creeps",
+            Stage::Movement => "movement",
+            Stage::Persistence => "persistence",
+            Stage::Trade => "trade",
+            Stage::Intents => "intents",
+            Stage::Deferred => "deferred",
+            Stage::Stats => "stats",
+        }
+    }
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+type StageFn = fn(&mut BWState, &mut Tick) -> OokResult<()>;
+
+/// What the stages of one tick hand each other
+pub struct Tick {
+    time: u32,
+    citizens: HashMap<ObjectId<Creep>, OokRace>,
+    room_requests: HashMap<RoomName, Request>,
+    /// CPU used by each stage that ran so far
+    stage_cpu: Vec<(Stage, f64)>,
+    /// Room whose state failed the running stage, see `blame`
+    failed_room: Option<RoomName>,
+}
+
+/// Runs all stages of `config::TICK_STAGES`
+///
+/// A failing stage doesn't stop the tick, the stages after it still run. What happens about the
+/// error depends on its category, see `recover`.
+pub fn run() -> OokResult<()> {
+    debug!("loop starting! CPU: {}", cpu::get_used());
+    let replan = switches::load();
+    if switches::is_paused() {
+        info!("Paused by Memory.ook_flags, skipping the tick");
+        return Ok(());
+    }
+    let mut tick = {
+        let mut context = BWContext::get();
+        let state = context.mut_state()?;
+        state.next_tick();
+        if replan {
+            switches::replan(state);
+        }
+        Tick {
+            time: screeps::game::time(),
+            citizens: get_all_citizens_from_creeps(screeps::game::creeps::values(), &state.citizens)
+                .unwrap_or_else(|err| {
+                    error!("Couldnt get citizens: {}", err);
+                    HashMap::new()
+                }),
+            room_requests: HashMap::new(),
+            stage_cpu: vec![],
+            failed_room: None,
+        }
+    };
+    for stage in config::TICK_STAGES {
+        if let Some(subsystem) = switches::subsystem_of_stage(*stage) {
+            if switches::is_subsystem_paused(subsystem) {
+                debug!("Skipping stage {}, {} is paused", stage, subsystem);
+                continue;
+            }
+        }
+        profile_scope!(stage.name());
+        let cpu_before = cpu::get_used();
+        tick.failed_room = None;
+        if let Err(err) = run_stage(*stage, &mut tick) {
+            recover(*stage, err, &mut tick);
+        }
+        tick.stage_cpu.push((*stage, cpu::get_used() - cpu_before));
+    }
+    profiler::report_if_due();
+    Ok(())
+}
+
+/// Reacts to a failed stage by the category of its error
+///
+/// Transient errors are retried by the next tick anyway. Corrupted state gets the part of the
+/// state the stage works on thrown away and rebuilt from the game and memory, for the room states
+/// only the state of the room that failed. Logic errors are bugs and send an alert besides.
+fn recover(stage: Stage, err: OokError, tick: &mut Tick) {
+    match err.category() {
+        ErrorCategory::Transient => warn!("Stage {} failed, retrying next tick: {}", stage, err),
+        ErrorCategory::StateCorruption => {
+            warn!("Stage {} failed on corrupted state, resetting it: {}", stage, err);
+            let mut context = BWContext::get();
+            match context.mut_state() {
+                Ok(state) => reset_stage_state(stage, state, tick),
+                Err(err) => error!("Could not reset the state of stage {}: {}", stage, err),
+            }
+        }
+        ErrorCategory::GameApi => warn!("Stage {} failed: {}", stage, err),
+        ErrorCategory::Logic => {
+            error!("Stage {} failed: {}", stage, err);
+            alerts::stage_failed(stage.name(), &err.to_string());
+        }
+    }
+}
+
+/// Remembers the room whose state failed, only its state is reset if it is corrupted
+fn blame(tick: &mut Tick, room_name: RoomName, err: anyhow::Error) -> OokError {
+    tick.failed_room = Some(room_name);
+    err.into()
+}
+
+/// Drops the state of the room, it is initialized again like a new one
+fn reset_room_state(state: &mut BWState, room_name: RoomName) {
+    if let Some(mut room_state) = state.room_states.remove(&room_name) {
+        if let Err(err) = room_state.on_exit(state) {
+            warn!("Error exiting the state of {}: {}", room_name, err);
+        }
+    }
+    if !state.pending_room_states.contains(&room_name) {
+        state.pending_room_states.push_back(room_name);
+    }
+}
+
+/// Throws away what the stage built up over the ticks, it gets rebuilt over the next ones
+fn reset_stage_state(stage: Stage, state: &mut BWState, tick: &mut Tick) {
+    match stage {
+        Stage::RoomStates | Stage::Persistence => match tick.failed_room {
+            Some(room_name) => reset_room_state(state, room_name),
+            // No room to blame, all of them go
+            None => {
+                state.room_states.clear();
+                state.pending_room_states = pending_room_states();
+            }
+        },
+        Stage::Requests | Stage::Spawning => {
+            state.requests.clear();
+            state.handled_requests.clear();
+            tick.room_requests.clear();
+            // What the rooms wait on is gone with the requests
+            for room_state in state.room_states.values_mut() {
+                room_state.forget_requests();
+            }
+        }
+        Stage::Creeps => {
+            tick.citizens =
+                get_all_citizens_from_creeps(screeps::game::creeps::values(), &HashMap::new())
+                    .unwrap_or_else(|err| {
+                        error!("Couldnt get citizens: {}", err);
+                        HashMap::new()
+                    });
+        }
+        Stage::Legacy => state.kinded_creeps.clear(),
+        Stage::Intel
+        | Stage::Movement
+        | Stage::Trade
+        | Stage::Intents
+        | Stage::Deferred
+        | Stage::Stats => {}
+    }
+}
+
+fn run_stage(stage: Stage, tick: &mut Tick) -> OokResult<()> {
+    let stage_fn: StageFn = match stage {
+        Stage::Intel => run_intel,
+        Stage::Legacy => return run_legacy(tick),
+        Stage::RoomStates => run_room_states,
+        Stage::Requests => run_requests,
+        Stage::Spawning => run_spawning,
+        Stage::Creeps => run_creeps,
+        Stage::Movement => run_movement,
+        Stage::Persistence => run_persistence,
+        Stage::Trade => run_trade,
+        Stage::Intents => run_intents,
+        Stage::Deferred => run_deferred,
+        Stage::Stats => run_stats,
+    };
+    let mut context = BWContext::get();
+    let state = context.mut_state()?;
+    stage_fn(state, tick)
+}
+
+fn run_intel(state: &mut BWState, _tick: &mut Tick) -> OokResult<()> {
+    rooms::harvest_stats::track_sources();
+    rooms::intel::track_rooms();
+    rooms::visibility::run(state);
+    rooms::defense::threats::track();
+    creeps::deaths::track(state);
+    rooms::remotes::track_remotes();
+    shards::sync(state)?;
+    rooms::salvage::request_salvage(state)?;
+    flags::run(state);
+    Ok(())
+}
+
+/// Runs without the context locked, the legacy creeps lock it on their own
+fn run_legacy(_tick: &mut Tick) -> OokResult<()> {
+    let main_room_configured = BWContext::get()
+        .state()?
+        .room_settings
+        .contains_key(&MyRoom::Main);
+    if main_room_configured {
+        maintain_room(&MyRoom::Main)?;
+    }
+    Ok(())
+}
+
+fn run_room_states(state: &mut BWState, tick: &mut Tick) -> OokResult<()> {
+    init_pending_room_states(state);
+    if let Err(err) = update_room_states_from_memory(state) {
+        warn!("Error updating mem of room states {}", err);
+    }
+    finish_bootstrap(state);
+    for (id, room_state) in &state.room_states {
+        let requests = match room_state {
+            RoomState::Base(room_state) => room_state.run(state),
+            RoomState::SetupBase(room_state) => room_state.run(state),
+        }
+        .map_err(|err| blame(tick, *id, err))?;
+        for request in requests {
+            tick.room_requests.insert(*id, request);
+        }
+    }
+    Ok(())
+}
+
+fn run_requests(state: &mut BWState, tick: &mut Tick) -> OokResult<()> {
+    for (room_name, request) in tick.room_requests.drain() {
+        info!("Adding for room: {} // request: {:?}", room_name, request);
+        match state.add_request(request.to_owned()) {
+            Ok(_) => {
+                if let Some(room_data) = state.room_states.get_mut(&room_name) {
+                    match room_data {
+                        RoomState::Base(ref mut room_state) => {
+                            room_state.request_logged(request.request_id.to_owned());
+                        }
+                        RoomState::SetupBase(ref mut room_state) => {
+                            room_state.request_logged(request.request_id.to_owned());
+                        }
+                    }
+                }
+            }
+            Err(err) => warn!("Error adding request {:?} // {}", request, err),
+        }
+    }
+    Ok(())
+}
+
+fn run_spawning(state: &mut BWState, _tick: &mut Tick) -> OokResult<()> {
+    match assign_requests(state) {
+        Ok(assigned_requests) => {
+            dummy_handle_requests(state, assigned_requests)?;
+        }
+        Err(err) => warn!("Could not assign requests {}", err),
+    }
+    Ok(())
+}
+
+fn run_creeps(state: &mut BWState, tick: &mut Tick) -> OokResult<()> {
+    debug!("running creeps");
+    for creep in screeps::game::creeps::values() {
+        debug!("running creep {}", creep.name());
+        chatter(&creep, state.ticks_since_init);
+        if creep.spawning() {
+            continue;
+        }
+        let memory = creep.memory();
+        let kind = memory.string("kind").map_err(OokError::game_api)?;
+        let race = memory.i32("race").map_err(OokError::game_api)?;
+        if kind.is_none() && race.is_none() {
+            run_harvester(creep);
+        }
+    }
+
+    let bases = owned_rooms(OwnedBy::Me);
+    if tick.time % config::LABOR_SHARING_INTERVAL == 0 {
+        rooms::labor::share_builders(&mut tick.citizens, &bases);
+    }
+    for (_id, citizen) in &mut tick.citizens {
+        if creeps::retirement::retire_if_useless(citizen, &bases) {
+            continue;
+        }
+        if creeps::refresh::replace_if_outgrown(citizen, &bases) {
+            continue;
+        }
+        if creeps::emergency::upgrade_if_downgrading(citizen) {
+            continue;
+        }
+        creeps::cpu_usage::measure(citizen, |citizen| {
+            match citizen {
+                OokRace::Carrier(ref mut carrier) => match (*carrier).do_job(state) {
+                    Ok(_) => {}
+                    Err(err) => warn!("Failed do_job: {} // for {:?}:", err, carrier),
+                },
+                OokRace::Worker(ref mut worker) => match (*worker).do_job(state) {
+                    Ok(_) => {}
+                    Err(err) => warn!("Failed do_job: {} // for {:?}:", err, worker),
+                },
+                OokRace::Claimer(ref mut claimer) => {
+                    match (*claimer).do_job(state) {
+                        Ok(_) => {}
+                        Err(err) => warn!("Failed do_job: {} // for {:?}:", err, claimer),
+                    }
+                    info!("claim");
+                }
+                OokRace::Blocker(ref mut blocker) => match (*blocker).do_job(state) {
+                    Ok(_) => {}
+                    Err(err) => warn!("Failed do_job: {} // for {:?}:", err, blocker),
+                },
+                OokRace::Healer(ref mut healer) => match (*healer).do_job(state) {
+                    Ok(_) => {}
+                    Err(err) => warn!("Failed do_job: {} // for {:?}:", err, healer),
+                },
+                OokRace::Dismantler(ref mut dismantler) => {
+                    match (*dismantler).do_job(state) {
+                        Ok(_) => {}
+                        Err(err) => warn!("Failed do_job: {} // for {:?}:", err, dismantler),
+                    }
+                }
+                OokRace::Harasser(ref mut harasser) => match (*harasser).do_job(state) {
+                    Ok(_) => {}
+                    Err(err) => warn!("Failed do_job: {} // for {:?}:", err, harasser),
+                },
+            }
+        });
+        creeps::actions::repair_in_passing(citizen);
+    }
+    Ok(())
+}
+
+fn run_movement(_state: &mut BWState, _tick: &mut Tick) -> OokResult<()> {
+    creeps::movement::clear_spawn_exits();
+    Ok(())
+}
+
+fn run_intents(_state: &mut BWState, _tick: &mut Tick) -> OokResult<()> {
+    intents::flush();
+    Ok(())
+}
+
+fn run_deferred(_state: &mut BWState, _tick: &mut Tick) -> OokResult<()> {
+    rooms::sites::run();
+    deferred::run();
+    Ok(())
+}
+
+fn run_persistence(state: &mut BWState, tick: &mut Tick) -> OokResult<()> {
+    let mut room_state_updates: HashMap<RoomName, RoomState> = HashMap::new();
+    for (room_name, room_state) in state.room_states.iter_mut() {
+        match room_state {
+            RoomState::Base(room_state) => {
+                room_state
+                    .check_room_status(&state.citizens)
+                    .map_err(|err| blame(tick, *room_name, err))?;
+                room_state.check_supplier_fillers(&state.citizens);
+                if rooms::stagger::is_due(*room_name, 10) {
+                    // HACK find out why dis not work sometimes
+                    room_state.update_suppliers();
+                }
+                let change = room_state
+                    .update(&state.handled_requests)
+                    .map_err(|err| blame(tick, *room_name, err))?;
+                match change {
+                    RoomStateChange::FinishSetup => {} // Shouldnt happen
+                    RoomStateChange::Helpless => match SetupBaseState::new(*room_name) {
+                        Ok(state) => {
+                            room_state_updates.insert(*room_name, RoomState::SetupBase(state));
+                        }
+                        Err(err) => {
+                            warn!("Error creating SetupBaseState {}", err);
+                        }
+                    },
+                    RoomStateChange::None => {}
+                }
+            }
+            RoomState::SetupBase(room_state) => {
+                room_state
+                    .check_room_status(&state.citizens)
+                    .map_err(|err| blame(tick, *room_name, err))?;
+                let change = room_state
+                    .update(&state.handled_requests)
+                    .map_err(|err| blame(tick, *room_name, err))?;
+                match change {
+                    RoomStateChange::FinishSetup => match BaseState::new(*room_name) {
+                        Ok(mut base_state) => {
+                            // The citizens that set the room up keep working for the base
+                            base_state.data.helping_citizens =
+                                room_state.data.helping_citizens.clone();
+                            room_state_updates.insert(*room_name, RoomState::Base(base_state));
+                        }
+                        Err(err) => {
+                            warn!("Error creating BaseState {}", err);
+                        }
+                    },
+                    RoomStateChange::Helpless | RoomStateChange::None => {}
+                }
+            }
+        }
+    }
+    for (room_name, new_state) in room_state_updates {
+        if let Err(err) = transition_room_state(state, room_name, new_state) {
+            warn!("Error changing room state of {}: {}", room_name, err);
+        }
+    }
+
+    state.citizens = std::mem::take(&mut tick.citizens);
+    rooms::watchdog::run(state);
+    if let Err(err) = persist_room_states(state) {
+        warn!("Error persisting room states {}", err);
+    }
+
+    gc::run(state);
+    if tick.time % 100 == 7 {
+        creeps::movement::persist_jam_stats();
+        creeps::cpu_usage::report();
+        creeps::deaths::persist_stats();
+        rooms::harvest_stats::persist_source_stats();
+        rooms::intel::persist_portals();
+    }
+    rooms::terrain::persist();
+    segments::persist();
+    Ok(())
+}
+
+fn run_trade(_state: &mut BWState, _tick: &mut Tick) -> OokResult<()> {
+    economy::empire::plan();
+    economy::boosts::run();
+    if !server::has_market() {
+        return Ok(());
+    }
+    economy::funnel::run();
+    trade::run_planned_sends();
+    trade::prices::sample_prices();
+    if server::has_intershard() {
+        trade::intershard::run_intershard_trading();
+    }
+    Ok(())
+}
+
+fn run_stats(state: &mut BWState, tick: &mut Tick) -> OokResult<()> {
+    if server::has_pixels()
+        && config::PIXEL_POLICY.should_generate(
+            tick.time,
+            cpu::bucket(),
+            state.avg_cpu_used,
+            cpu::limit(),
+        )
+    {
+        cpu::generate_pixel();
+    }
+    rooms::labels::draw_labels();
+    visuals::flush();
+    alerts::check_rooms();
+    state.record_cpu_used(cpu::get_used());
+    let stage_cpu: HashMap<String, f64> = tick
+        .stage_cpu
+        .iter()
+        .map(|(stage, used)| (stage.to_string(), *used))
+        .collect();
+    memory::root().path_set(MEM_STATS_STAGES, stage_cpu);
+    info!(
+        "🚀 🦍 🚀 🦍 🚀 🦍 done! cpu: {}; Ticks since last update: {}, Requests: {} & Handled: {} 🍁 🍁 🍁 ",
+        cpu::get_used(),
+        state.ticks_since_init,
+        state.requests.len(),
+        state.handled_requests.len(),
+    );
+    Ok(())
+}
+
+fn chatter(creep: &Creep, ticks_since_init: i32) {
+    match (ticks_since_init + 500) % 1000 {
+        3 => {
+            speech::say(creep, "Did", Verbosity::Chatter);
+        }
+        4 => {
+            speech::say(creep, "you", Verbosity::Chatter);
+        }
+        5 => {
+            speech::say(creep, "ever", Verbosity::Chatter);
+        }
+        6 => {
+            speech::say(creep, "hear", Verbosity::Chatter);
+        }
+        7 => {
+            speech::say(creep, "the", Verbosity::Chatter);
+        }
+        8 => {
+            speech::say(creep, "tragedy", Verbosity::Chatter);
+        }
+        9 => {
+            speech::say(creep, "of", Verbosity::Chatter);
+        }
+        10 => {
+            speech::say(creep, "Darth", Verbosity::Chatter);
+        }
+        11 => {
+            speech::say(creep, "Plagueis", Verbosity::Chatter);
+        }
+        12 => {
+            speech::say(creep, "The", Verbosity::Chatter);
+        }
+        13 => {
+            speech::say(creep, "Wise?", Verbosity::Chatter);
+        }
+        14 => {
+            speech::say(creep, "I", Verbosity::Chatter);
+        }
+        15 => {
+            speech::say(creep, "thought", Verbosity::Chatter);
+        }
+        16 => {
+            speech::say(creep, "not.", Verbosity::Chatter);
+        }
+        17 => {
+            speech::say(creep, "It’s", Verbosity::Chatter);
+        }
+        18 => {
+            speech::say(creep, "not", Verbosity::Chatter);
+        }
+        19 => {
+            speech::say(creep, "a", Verbosity::Chatter);
+        }
+        20 => {
+            speech::say(creep, "story", Verbosity::Chatter);
+        }
+        21 => {
+            speech::say(creep, "the", Verbosity::Chatter);
+        }
+        22 => {
+            speech::say(creep, "Jedi", Verbosity::Chatter);
+        }
+        23 => {
+            speech::say(creep, "would", Verbosity::Chatter);
+        }
+        24 => {
+            speech::say(creep, "tell", Verbosity::Chatter);
+        }
+        25 => {
+            speech::say(creep, "you.", Verbosity::Chatter);
+        }
+        26 => {
+            speech::say(creep, "It’s", Verbosity::Chatter);
+        }
+        27 => {
+            speech::say(creep, "a", Verbosity::Chatter);
+        }
+        28 => {
+            speech::say(creep, "Sith", Verbosity::Chatter);
+        }
+        29 => {
+            speech::say(creep, "legend.", Verbosity::Chatter);
+        }
+        30 => {
+            speech::say(creep, "Darth", Verbosity::Chatter);
+        }
+        31 => {
+            speech::say(creep, "Plagueis", Verbosity::Chatter);
+        }
+        32 => {
+            speech::say(creep, "was", Verbosity::Chatter);
+        }
+        33 => {
+            speech::say(creep, "a", Verbosity::Chatter);
+        }
+        34 => {
+            speech::say(creep, "Dark", Verbosity::Chatter);
+        }
+        35 => {
+            speech::say(creep, "Lord", Verbosity::Chatter);
+        }
+        36 => {
+            speech::say(creep, "of", Verbosity::Chatter);
+        }
+        37 => {
+            speech::say(creep, "the", Verbosity::Chatter);
+        }
+        38 => {
+            speech::say(creep, "Sith,", Verbosity::Chatter);
+        }
+        39 => {
+            speech::say(creep, "so", Verbosity::Chatter);
+        }
+        40 => {
+            speech::say(creep, "powerful", Verbosity::Chatter);
+        }
+        41 => {
+            speech::say(creep, "and", Verbosity::Chatter);
+        }
+        42 => {
+            speech::say(creep, "so", Verbosity::Chatter);
+        }
+        43 => {
+            speech::say(creep, "wise", Verbosity::Chatter);
+        }
+        44 => {
+            speech::say(creep, "he", Verbosity::Chatter);
+        }
+        45 => {
+            speech::say(creep, "could", Verbosity::Chatter);
+        }
+        46 => {
+            speech::say(creep, "use", Verbosity::Chatter);
+        }
+        47 => {
+            speech::say(creep, "the", Verbosity::Chatter);
+        }
+        48 => {
+            speech::say(creep, "Force", Verbosity::Chatter);
+        }
+        49 => {
+            speech::say(creep, "to", Verbosity::Chatter);
+        }
+        50 => {
+            speech::say(creep, "influence", Verbosity::Chatter);
+        }
+        51 => {
+            speech::say(creep, "the", Verbosity::Chatter);
+        }
+        52 => {
+            speech::say(creep, "midichlorians", Verbosity::Chatter);
+        }
+        53 => {
+            speech::say(creep, "to", Verbosity::Chatter);
+        }
+        54 => {
+            speech::say(creep, "create", Verbosity::Chatter);
+        }
+        55 => {
+            speech::say(creep, "life…", Verbosity::Chatter);
+        }
+        56 => {
+            speech::say(creep, "He", Verbosity::Chatter);
+        }
+        57 => {
+            speech::say(creep, "had", Verbosity::Chatter);
+        }
+        58 => {
+            speech::say(creep, "such", Verbosity::Chatter);
+        }
+        59 => {
+            speech::say(creep, "a", Verbosity::Chatter);
+        }
+        60 => {
+            speech::say(creep, "knowledge", Verbosity::Chatter);
+        }
+        61 => {
+            speech::say(creep, "of", Verbosity::Chatter);
+        }
+        62 => {
+            speech::say(creep, "the", Verbosity::Chatter);
+        }
+        63 => {
+            speech::say(creep, "dark", Verbosity::Chatter);
+        }
+        64 => {
+            speech::say(creep, "side", Verbosity::Chatter);
+        }
+        65 => {
+            speech::say(creep, "that", Verbosity::Chatter);
+        }
+        66 => {
+            speech::say(creep, "he", Verbosity::Chatter);
+        }
+        67 => {
+            speech::say(creep, "could", Verbosity::Chatter);
+        }
+        68 => {
+            speech::say(creep, "even", Verbosity::Chatter);
+        }
+        69 => {
+            speech::say(creep, "keep", Verbosity::Chatter);
+        }
+        70 => {
+            speech::say(creep, "the", Verbosity::Chatter);
+        }
+        71 => {
+            speech::say(creep, "ones", Verbosity::Chatter);
+        }
+        72 => {
+            speech::say(creep, "he", Verbosity::Chatter);
+        }
+        73 => {
+            speech::say(creep, "cared", Verbosity::Chatter);
+        }
+        74 => {
+            speech::say(creep, "about", Verbosity::Chatter);
+        }
+        75 => {
+            speech::say(creep, "from", Verbosity::Chatter);
+        }
+        76 => {
+            speech::say(creep, "dying.", Verbosity::Chatter);
+        }
+        77 => {
+            speech::say(creep, "The", Verbosity::Chatter);
+        }
+        78 => {
+            speech::say(creep, "dark", Verbosity::Chatter);
+        }
+        79 => {
+            speech::say(creep, "side", Verbosity::Chatter);
+        }
+        80 => {
+            speech::say(creep, "of", Verbosity::Chatter);
+        }
+        81 => {
+            speech::say(creep, "the", Verbosity::Chatter);
+        }
+        82 => {
+            speech::say(creep, "Force", Verbosity::Chatter);
+        }
+        83 => {
+            speech::say(creep, "is", Verbosity::Chatter);
+        }
+        84 => {
+            speech::say(creep, "a", Verbosity::Chatter);
+        }
+        85 => {
+            speech::say(creep, "pathway", Verbosity::Chatter);
+        }
+        86 => {
+            speech::say(creep, "to", Verbosity::Chatter);
+        }
+        87 => {
+            speech::say(creep, "many", Verbosity::Chatter);
+        }
+        88 => {
+            speech::say(creep, "abilities", Verbosity::Chatter);
+        }
+        89 => {
+            speech::say(creep, "some", Verbosity::Chatter);
+        }
+        90 => {
+            speech::say(creep, "consider", Verbosity::Chatter);
+        }
+        91 => {
+            speech::say(creep, "to", Verbosity::Chatter);
+        }
+        92 => {
+            speech::say(creep, "be", Verbosity::Chatter);
+        }
+        93 => {
+            speech::say(creep, "unnatural.", Verbosity::Chatter);
+        }
+        94 => {
+            speech::say(creep, "He", Verbosity::Chatter);
+        }
+        95 => {
+            speech::say(creep, "became", Verbosity::Chatter);
+        }
+        96 => {
+            speech::say(creep, "so", Verbosity::Chatter);
+        }
+        97 => {
+            speech::say(creep, "powerful…", Verbosity::Chatter);
+        }
+        98 => {
+            speech::say(creep, "the", Verbosity::Chatter);
+        }
+        99 => {
+            speech::say(creep, "only", Verbosity::Chatter);
+        }
+        100 => {
+            speech::say(creep, "thing", Verbosity::Chatter);
+        }
+        101 => {
+            speech::say(creep, "he", Verbosity::Chatter);
+        }
+        102 => {
+            speech::say(creep, "was", Verbosity::Chatter);
+        }
+        103 => {
+            speech::say(creep, "afraid", Verbosity::Chatter);
+        }
+        104 => {
+            speech::say(creep, "of", Verbosity::Chatter);
+        }
+        105 => {
+            speech::say(creep, "was", Verbosity::Chatter);
+        }
+        106 => {
+            speech::say(creep, "losing", Verbosity::Chatter);
+        }
+        107 => {
+            speech::say(creep, "his", Verbosity::Chatter);
+        }
+        108 => {
+            speech::say(creep, "power,", Verbosity::Chatter);
+        }
+        109 => {
+            speech::say(creep, "which", Verbosity::Chatter);
+        }
+        110 => {
+            speech::say(creep, "eventually,", Verbosity::Chatter);
+        }
+        111 => {
+            speech::say(creep, "of", Verbosity::Chatter);
+        }
+        112 => {
+            speech::say(creep, "course,", Verbosity::Chatter);
+        }
+        113 => {
+            speech::say(creep, "he", Verbosity::Chatter);
+        }
+        114 => {
+            speech::say(creep, "did.", Verbosity::Chatter);
+        }
+        115 => {
+            speech::say(creep, "Unfortunately,", Verbosity::Chatter);
+        }
+        116 => {
+            speech::say(creep, "he", Verbosity::Chatter);
+        }
+        117 => {
+            speech::say(creep, "taught", Verbosity::Chatter);
+        }
+        118 => {
+            speech::say(creep, "his", Verbosity::Chatter);
+        }
+        119 => {
+            speech::say(creep, "apprentice", Verbosity::Chatter);
+        }
+        120 => {
+            speech::say(creep, "everything", Verbosity::Chatter);
+        }
+        121 => {
+            speech::say(creep, "he", Verbosity::Chatter);
+        }
+        122 => {
+            speech::say(creep, "knew,", Verbosity::Chatter);
+        }
+        123 => {
+            speech::say(creep, "then", Verbosity::Chatter);
+        }
+        124 => {
+            speech::say(creep, "his", Verbosity::Chatter);
+        }
+        125 => {
+            speech::say(creep, "apprentice", Verbosity::Chatter);
+        }
+        126 => {
+            speech::say(creep, "killed", Verbosity::Chatter);
+        }
+        127 => {
+            speech::say(creep, "him", Verbosity::Chatter);
+        }
+        128 => {
+            speech::say(creep, "in", Verbosity::Chatter);
+        }
+        129 => {
+            speech::say(creep, "his", Verbosity::Chatter);
+        }
+        130 => {
+            speech::say(creep, "sleep.", Verbosity::Chatter);
+        }
+        131 => {
+            speech::say(creep, "Ironic.", Verbosity::Chatter);
+        }
+        132 => {
+            speech::say(creep, "He", Verbosity::Chatter);
+        }
+        133 => {
+            speech::say(creep, "could", Verbosity::Chatter);
+        }
+        134 => {
+            speech::say(creep, "save", Verbosity::Chatter);
+        }
+        135 => {
+            speech::say(creep, "others", Verbosity::Chatter);
+        }
+        136 => {
+            speech::say(creep, "from", Verbosity::Chatter);
+        }
+        137 => {
+            speech::say(creep, "death,", Verbosity::Chatter);
+        }
+        138 => {
+            speech::say(creep, "but", Verbosity::Chatter);
+        }
+        139 => {
+            speech::say(creep, "not", Verbosity::Chatter);
+        }
+        140 => {
+            speech::say(creep, "himself.", Verbosity::Chatter);
+        }
+
+        500 => {
+            speech::say(creep, "they", Verbosity::Chatter);
+        }
+        501 => {
+            speech::say(creep, "destroy", Verbosity::Chatter);
+        }
+        502 => {
+            speech::say(creep, "we", Verbosity::Chatter);
+        }
+        503 => {
+            speech::say(creep, "rebuild", Verbosity::Chatter);
+        }
+        _ => {}
+    }
+}
+
+/// Drops the bootstrap once the fresh room stands on its own
+fn finish_bootstrap(state: &mut BWState) {
+    if let Some(bootstrap) = &state.bootstrap {
+        if bootstrap.finished() {
+            info!("Room {} is bootstrapped", bootstrap.room_name);
+            state.bootstrap = None;
+        }
+    }
+}
+
+fn maintain_room(room_ident: &MyRoom) -> OokResult<()> {
+    let state_kinded_creeps = {
+        let context = BWContext::get();
+        let state = context.state()?;
+        &state.kinded_creeps.clone()
+    };
+    let room = MyRoom::get(room_ident)?;
+    let creeps = room.find(find::MY_CREEPS);
+    let kinded_creeps: Vec<(screeps::objects::Creep, CreepKind)> = creeps
+        .into_iter()
+        .filter_map(|creep| {
+            let id = creep.id().clone();
+            let kinded = if let Some(state_kinded) = state_kinded_creeps.get(&id) {
+                let mut new_state_kinded = (*state_kinded).to_owned();
+                // TODO This is stupid, the creepKind should just not contain the creep, just its
+                // id
+                new_state_kinded.set_creep(creep.clone());
+                Ok(new_state_kinded)
+            } else {
+                let kinded = CreepKind::try_from(creep.clone());
+                if let Ok(kinded) = kinded.as_ref() {
+                    let update_res = BWContext::update_state(|state| {
+                        state.kinded_creeps.insert(creep.id(), kinded.clone());
+                        Ok(())
+                    });
+                    if let Err(err) = update_res {
+                        warn!("Failed updating kinded_creeps state: {}", err);
+                    }
+                }
+                kinded
+            };
+            match kinded {
+                Ok(cr) => Some((creep, cr)),
+                Err(err) => {
+                    // warn!("Could not read creep {}: {}", id, err);
+                    None
+                }
+            }
+        })
+        .collect();
+
+    update_maintenance(room_ident.to_owned())?;
+
+    for (creep, kind_data) in kinded_creeps.into_iter() {
+        match kind_data {
+            CreepKind::Builder(mut builder_data) => {
+                match builder_data.harvest_check() {
+                    Ok(_) => {}
+                    Err(err) => info!("Failed harvest_check builder: {}", err),
+                }
+                if builder_data.harvesting {
+                    match builder_data.harvest() {
+                        Ok(_) => {}
+                        Err(err) => info!("Failed harvest builder: {}", err),
+                    }
+                } else {
+                    match builder_data.build() {
+                        Ok(_) => {}
+                        Err(err) => info!("Failed build builder: {}", err),
+                    }
+                }
+                // Whats happening here:
+                //
+                // 1. Somewhere else:
+                //   1. Load creep from State kinded_creeps
+                //   2. If that does not exist, `try_from` creep and store in State
+                // 2. **CLONE** KindedCreep and use that here
+                // 3. KindedCreep.run / .build / ... updates the cloned entry only
+                // 4. Manually copy the cloned entry back
+                //
+                // There should be a better way instead of cloning and updating back? Cell or
+                // something?
+                BWContext::update_state(|state| {
+                    let kinded = state.kinded_creeps.get_mut(&creep.id());
+                    if let Some(builder) = kinded {
+                        *builder = CreepKind::Builder(builder_data.clone());
+                    }
+                    Ok(())
+                })?;
+            }
+            CreepKind::Farmer(mut farmer_data) => {
+                farmer_data.harvest()?;
+            }
+            CreepKind::Runner(mut runner_data) => {
+                match runner_data.run() {
+                    Ok(_) => {}
+                    Err(err) => info!("Failed running runner: {}", err),
+                }
+                BWContext::update_state(|state| {
+                    let kinded = state.kinded_creeps.get_mut(&creep.id());
+                    if let Some(runner) = kinded {
+                        *runner = CreepKind::Runner(runner_data.clone());
+                    }
+                    Ok(())
+                })?;
+            }
+            CreepKind::Bitch(mut bitch_data) => match bitch_data.run() {
+                Ok(_) => {}
+                Err(err) => info!("Failed running bitch: {}", err),
+            },
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,129 @@
+//! CPU profiling of single scopes, opt-in with the `profiler` feature. `profile_scope!("name")`
+//! measures the rest of the block it is in. Nested scopes are kept apart by their stack, so the
+//! same function called from two places shows up twice.
+//!
+//! Every `config::PROFILER_REPORT_TICKS` ticks the scopes are reported, sorted by the CPU they
+//! used, and start over. The report goes to the console, or with `config::PROFILER_SEGMENT` into
+//! that segment, as folded stacks with the own CPU of each in micro CPU. `flamegraph.pl` takes
+//! them as they are.
+//!
+//! Without the feature the macro is empty, measuring costs a call into JS per scope.
+#![cfg_attr(not(feature = "profiler"), allow(dead_code))]
+use std::{collections::HashMap, sync::Mutex};
+
+use lazy_static::lazy_static;
+use log::info;
+use screeps::game;
+
+use crate::config;
+
+/// Profiles the rest of the enclosing block as `$name`
+///
+/// ```
+///   profile_scope!("BaseState::update");
+/// ```
+macro_rules! profile_scope {
+    ($name:expr) => {
+        #[cfg(feature = "profiler")]
+        let _profile_scope = crate::profiler::Scope::enter($name);
+    };
+}
+
+#[derive(Debug, Default)]
+struct ScopeStats {
+    calls: u32,
+    /// CPU used including the nested scopes
+    total: f64,
+    /// CPU used minus the nested scopes
+    own: f64,
+}
+
+#[derive(Debug, Default)]
+struct Profile {
+    /// The open scopes with the CPU their nested scopes used so far
+    stack: Vec<(&'static str, f64)>,
+    /// By the names of the open scopes, separated by `;`
+    scopes: HashMap<String, ScopeStats>,
+    since: u32,
+}
+
+lazy_static! {
+    static ref PROFILE: Mutex<Profile> = Mutex::new(Profile::default());
+}
+
+/// Measures until dropped, see `profile_scope!`
+pub struct Scope {
+    started_at: f64,
+}
+
+impl Scope {
+    pub fn enter(name: &'static str) -> Scope {
+        PROFILE.lock().unwrap().stack.push((name, 0.));
+        Scope {
+            started_at: game::cpu::get_used(),
+        }
+    }
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        let used = game::cpu::get_used() - self.started_at;
+        let mut profile = PROFILE.lock().unwrap();
+        let key = profile
+            .stack
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<&str>>()
+            .join(";");
+        let nested = match profile.stack.pop() {
+            Some((_, nested)) => nested,
+            None => return,
+        };
+        if let Some((_, parent_nested)) = profile.stack.last_mut() {
+            *parent_nested += used;
+        }
+        let stats = profile.scopes.entry(key).or_default();
+        stats.calls += 1;
+        stats.total += used;
+        stats.own += used - nested;
+    }
+}
+
+/// Reports and resets the scopes if it is time, call it at the end of the tick
+pub fn report_if_due() {
+    let time = game::time();
+    let mut profile = PROFILE.lock().unwrap();
+    if profile.scopes.is_empty() {
+        profile.since = time;
+        return;
+    }
+    let ticks = time - profile.since;
+    if ticks < config::PROFILER_REPORT_TICKS {
+        return;
+    }
+    let mut scopes: Vec<(String, ScopeStats)> = profile.scopes.drain().collect();
+    scopes.sort_by(|(_, a), (_, b)| b.total.partial_cmp(&a.total).unwrap());
+    match config::PROFILER_SEGMENT {
+        Some(segment) => {
+            let folded: Vec<String> = scopes
+                .iter()
+                .map(|(key, stats)| format!("{} {}", key, (stats.own * 1_000_000.) as u64))
+                .collect();
+            screeps::raw_memory::set_segment(segment, &folded.join("\n"));
+            info!("Profile of {} ticks written to segment {}", ticks, segment);
+        }
+        None => {
+            info!("Profile of {} ticks, CPU per tick:", ticks);
+            for (key, stats) in &scopes {
+                info!(
+                    "{:>8.3} total {:>8.3} own {:>6} calls  {}",
+                    stats.total / ticks as f64,
+                    stats.own / ticks as f64,
+                    stats.calls,
+                    key
+                );
+            }
+        }
+    }
+    profile.since = time;
+}
@@ -1,22 +1,49 @@
+pub mod bootstrap;
+pub mod claims;
 pub mod resource_provider;
 pub mod room_ext;
 pub mod room_state;
 pub mod extensions;
+pub mod defense;
+pub mod farm_spots;
+pub mod fill_orders;
+pub mod harvest_stats;
+pub mod highways;
+pub mod intel;
+pub mod labels;
+pub mod labor;
+pub mod distance;
+pub mod cost_matrix;
+pub mod events;
+pub mod planner;
+pub mod rebuild;
+pub mod remotes;
+pub mod repairs;
+pub mod salvage;
+pub mod signs;
+pub mod sites;
+pub mod spawn_area;
+pub mod stagger;
+pub mod stock_balance;
+pub mod structures;
+pub mod terrain;
+pub mod visibility;
+pub mod watchdog;
+pub mod withdraw_permissions;
 
 use std::collections::HashMap;
 
 use log::{debug, warn};
 use screeps::{
-    creep,
     find::{self, SOURCES},
     game::rooms,
-    ConstructionSite, FindOptions, HasId, HasPosition, LookResult, ObjectId, Part, Path, Position,
-    RawObjectId, Room, RoomName, Source, Step, Structure, StructureSpawn,
+    ConstructionSite, FindOptions, HasId, HasPosition, LookResult, ObjectId, Path, Position,
+    RawObjectId, Room, RoomName, Source, Step, Structure, StructureSpawn, StructureType,
 };
-use std::error::Error;
 use anyhow::anyhow;
 
 use crate::{
+    config,
     constants::ROOM_ID_MAIN,
     game::{owned_rooms, OwnedBy},
     state::{BWContext, BWState}
@@ -32,15 +59,12 @@ pub enum RoomError {
     RoomQueueNotPrioritized(),
     #[error("FarmPosition for source not found")]
     FarmPositionSourceNotFound(),
-    #[error("FarmPosition of source not found")]
-    FarmPositionForSourceNotFound(),
 }
 
 #[derive(Debug)]
 pub struct RoomSettings {
     pub name: RoomName,
     pub spawns: Vec<ObjectId<StructureSpawn>>,
-    pub target_creeps: RoomCreepSettings,
     pub maintenance: MaintenanceQueue,
     pub farm_positions: HashMap<ObjectId<Source>, Vec<FarmPosition>>,
 }
@@ -137,216 +161,14 @@ fn main_room_config(_room_ident: MyRoom, room: &Room) -> anyhow::Result<RoomSett
         room.name()
     );
     let farm_positions = farm_positions(room.name())?;
-    let farmers_positions = farmer_positions(&farm_positions)?;
     Ok(RoomSettings {
         name: room.name().clone(),
         spawns: spawns.iter().map(|spawn| spawn.id()).collect(),
-        target_creeps: RoomCreepSettings {
-            builder: [
-                // RoomBuilderSettings {
-                //     parts: [
-                //         Part::Work,
-                //         Part::Work,
-                //         Part::Work,
-                //         Part::Carry,
-                //         Part::Carry,
-                //         Part::Carry,
-                //         Part::Move,
-                //         Part::Move,
-                //         Part::Move,
-                //         Part::Move,
-                //         Part::Move,
-                //         Part::Move,
-                //         Part::Move,
-                //     ]
-                //     .into(),
-                // },
-                // RoomBuilderSettings {
-                //     parts: [
-                //         Part::Work,
-                //         Part::Work,
-                //         Part::Work,
-                //         Part::Work,
-                //         Part::Carry,
-                //         Part::Carry,
-                //         Part::Carry,
-                //         Part::Carry,
-                //         Part::Carry,
-                //         Part::Carry,
-                //         Part::Carry,
-                //         Part::Carry,
-                //         Part::Move,
-                //         Part::Move,
-                //         Part::Move,
-                //         Part::Move,
-                //         Part::Move,
-                //         Part::Move,
-                //         Part::Move,
-                //         Part::Move,
-                //         Part::Move,
-                //     ]
-                //     .into(),
-                // },
-            ]
-            .into(),
-            farmer: vec![].into(),
-            // farmer: farmers_positions
-            //     .iter()
-            //     .map(|farm_pos| RoomFarmerSettings {
-            //         parts: [
-            //             Part::Work,
-            //             Part::Work,
-            //             Part::Work,
-            //             Part::Work,
-            //             Part::Work,
-            //             Part::Work,
-            //             Part::Move,
-            //             Part::Move,
-            //             Part::Move,
-            //         ]
-            //         .into(),
-            //         farm_position: farm_pos.to_owned(),
-            //     })
-            //     .collect::<Vec<RoomFarmerSettings>>()
-            //     .into(),
-            runner: [
-                RoomRunnerSettings {
-                    parts: [
-                        Part::Carry,
-                        Part::Carry,
-                        Part::Carry,
-                        Part::Move,
-                        Part::Move,
-                        Part::Move,
-                    ]
-                    .into(),
-                },
-                // RoomRunnerSettings {
-                //     parts: [
-                //         Part::Carry,
-                //         Part::Carry,
-                //         Part::Carry,
-                //         Part::Carry,
-                //         Part::Carry,
-                //         Part::Carry,
-                //         Part::Carry,
-                //         Part::Carry,
-                //         Part::Carry,
-                //         Part::Carry,
-                //         Part::Carry,
-                //         Part::Carry,
-                //         Part::Carry,
-                //         Part::Carry,
-                //         Part::Carry,
-                //         Part::Carry,
-                //         Part::Move,
-                //         Part::Move,
-                //         Part::Move,
-                //         Part::Move,
-                //         Part::Move,
-                //         Part::Move,
-                //         Part::Move,
-                //         Part::Move,
-                //     ]
-                //     .into(),
-                // },
-                // RoomRunnerSettings {
-                //     parts: [
-                //         Part::Carry,
-                //         Part::Carry,
-                //         Part::Carry,
-                //         Part::Carry,
-                //         Part::Carry,
-                //         Part::Carry,
-                //         Part::Carry,
-                //         Part::Carry,
-                //         Part::Carry,
-                //         Part::Carry,
-                //         Part::Carry,
-                //         Part::Carry,
-                //         Part::Carry,
-                //         Part::Carry,
-                //         Part::Carry,
-                //         Part::Carry,
-                //         Part::Move,
-                //         Part::Move,
-                //         Part::Move,
-                //         Part::Move,
-                //         Part::Move,
-                //         Part::Move,
-                //         Part::Move,
-                //         Part::Move,
-                //     ]
-                //     .into(),
-                // },
-            ]
-            .into(),
-            bitches: [
-                // RoomBitchSettings {
-                //     parts: []
-                //     .into(),
-                // },
-                // RoomBitchSettings {
-                //     parts: []
-                //     .into(),
-                // },
-                // RoomBitchSettings {
-                //     parts: []
-                //     .into(),
-                // },
-                // RoomBitchSettings {
-                //     parts: []
-                //     .into(),
-                // },
-                // RoomBitchSettings {
-                //     parts: [
-                //         Part::Work,
-                //         Part::Work,
-                //         Part::Work,
-                //         Part::Work,
-                //         Part::Work,
-                //         Part::Carry,
-                //         Part::Move,
-                //         Part::Move,
-                //         Part::Move,
-                //     ]
-                //     .into(),
-                // },
-            ]
-            .into(),
-            claimers: [
-                // RoomClaimerSettings {
-                //     parts: [
-                //         Part::Claim,
-                //         Part::Move,
-                //     ]
-                //     .into(),
-                //     target_room: RoomName::new("W12N15")?,
-                // },
-            ]
-            .into(),
-        },
         maintenance,
         farm_positions,
     })
 }
 
-fn farmer_positions(
-    sources_with_farm_pos: &HashMap<ObjectId<Source>, Vec<FarmPosition>>,
-) -> anyhow::Result<Vec<FarmPosition>> {
-    let mut farm_positions_for_farmers = vec![];
-    for (_id, farm_positions) in sources_with_farm_pos.iter() {
-        let prio_pos = prioritized_farm_positions(&farm_positions);
-        farm_positions_for_farmers.push(
-            prio_pos
-                .first()
-                .ok_or(Box::new(RoomError::FarmPositionForSourceNotFound()))?
-                .to_owned(),
-        );
-    }
-    Ok(farm_positions_for_farmers)
-}
-
 pub fn bootstrap_room(state: &mut BWState, target_room_name: RoomName, helper_room_name: RoomName) -> anyhow::Result<()> {
     let helper_room = rooms::get(helper_room_name).ok_or(anyhow!("Helper room not found"))?;
     let target_room = rooms::get(target_room_name);
@@ -355,7 +177,7 @@ pub fn bootstrap_room(state: &mut BWState, target_room_name: RoomName, helper_ro
     Ok(())
 }
 
-pub fn update_maintenance(room_ident: MyRoom) -> Result<(), Box<dyn Error>> {
+pub fn update_maintenance(room_ident: MyRoom) -> anyhow::Result<()> {
     let room = room_ident.room()?;
     let maintenance = match init_maintenance_queue(&room) {
         Ok(m) => m,
@@ -365,29 +187,19 @@ pub fn update_maintenance(room_ident: MyRoom) -> Result<(), Box<dyn Error>> {
             MaintenanceQueue::Prioritized(vec![])
         }
     };
-    BWContext::update_state(move |state: &mut BWState| -> Result<(), Box<dyn Error>> {
-        let room_config =
-            state
-                .room_settings
-                .get_mut(&room_ident)
-                .ok_or(Box::new(RoomError::RoomNotFound(
-                    MyRoom::name(room_ident.to_owned()).into(),
-                )))?;
+    BWContext::update_state(move |state: &mut BWState| -> anyhow::Result<()> {
+        let room_config = state
+            .room_settings
+            .get_mut(&room_ident)
+            .ok_or(RoomError::RoomNotFound(MyRoom::name(room_ident.to_owned()).into()))?;
         room_config.maintenance = maintenance.clone();
         Ok(())
     })
 }
 
-fn init_maintenance_queue(room: &Room) -> Result<MaintenanceQueue, Box<dyn Error>> {
+fn init_maintenance_queue(room: &Room) -> anyhow::Result<MaintenanceQueue> {
     let construction_sites = room.find(find::CONSTRUCTION_SITES);
-    Ok(MaintenanceQueue::Prioritized(
-        construction_sites
-            .into_iter()
-            .map(|site| RoomMaintenance::NewBuild {
-                object_id: site.id(),
-            })
-            .collect(),
-    ))
+    Ok(MaintenanceQueue::from_sites(construction_sites, &BuildPolicy::default()))
 }
 
 fn sources_closest_to_controller(room: &Room) -> Vec<Source> {
@@ -428,44 +240,6 @@ impl PathOptionUnwrapper for Path {
     }
 }
 
-#[derive(Debug)]
-pub struct RoomCreepSettings {
-    pub builder: Vec<RoomBuilderSettings>,
-    pub farmer: Vec<RoomFarmerSettings>,
-    pub bitches: Vec<RoomBitchSettings>,
-    pub runner: Vec<RoomRunnerSettings>,
-    pub claimers: Vec<RoomClaimerSettings>,
-}
-
-#[derive(Debug)]
-pub struct RoomBuilderSettings {
-    pub parts: Vec<creep::Part>,
-}
-
-#[derive(Debug)]
-pub struct RoomFarmerSettings {
-    pub parts: Vec<creep::Part>,
-    pub farm_position: FarmPosition,
-    // target_source: ObjectId,
-}
-
-#[derive(Debug)]
-pub struct RoomRunnerSettings {
-    pub parts: Vec<creep::Part>,
-}
-
-#[derive(Debug)]
-pub struct RoomBitchSettings {
-    pub parts: Vec<creep::Part>,
-}
-
-#[derive(Debug)]
-pub struct RoomClaimerSettings {
-    pub parts: Vec<creep::Part>,
-    pub target_room: RoomName,
-}
-
-
 #[derive(Debug, Clone, PartialEq)]
 pub enum RoomMaintenance {
     NewBuild {
@@ -497,7 +271,64 @@ pub enum MaintenanceQueue {
     // UnderAttack(Vec<RoomMaintenance>),
 }
 
+/// Order construction sites get built in
+#[derive(Debug, Clone)]
+pub struct BuildPolicy {
+    /// Structure types from most to least important, others come after them
+    pub tiers: Vec<StructureType>,
+    /// Sites with progress come before all untouched ones
+    pub finish_started: bool,
+}
+
+impl Default for BuildPolicy {
+    fn default() -> Self {
+        BuildPolicy {
+            tiers: config::BUILD_PRIORITY.to_vec(),
+            finish_started: config::BUILD_FINISH_STARTED,
+        }
+    }
+}
+
+impl BuildPolicy {
+    pub fn tier(&self, structure_type: StructureType) -> usize {
+        self.tiers
+            .iter()
+            .position(|ty| *ty == structure_type)
+            .unwrap_or(self.tiers.len())
+    }
+
+    /// Sort key of a site, lower gets built first. Within a tier the closest to completion wins.
+    fn rank(&self, site: &ConstructionSite) -> (bool, usize, u32) {
+        // Rebuilds of destroyed structures don't wait for the started sites
+        let untouched =
+            self.finish_started && site.progress() == 0 && !rebuild::is_rushed(&site.pos());
+        let missing = site.progress_total() - site.progress();
+        (untouched, self.tier(site.structure_type()), missing)
+    }
+
+    pub fn prioritize(&self, sites: &mut Vec<ConstructionSite>) {
+        sites.sort_by_key(|site| self.rank(site));
+    }
+
+    /// The site to build next
+    pub fn pick(&self, sites: Vec<ConstructionSite>) -> Option<ConstructionSite> {
+        sites.into_iter().min_by_key(|site| self.rank(site))
+    }
+}
+
 impl MaintenanceQueue {
+    pub fn from_sites(mut sites: Vec<ConstructionSite>, policy: &BuildPolicy) -> Self {
+        policy.prioritize(&mut sites);
+        MaintenanceQueue::Prioritized(
+            sites
+                .into_iter()
+                .map(|site| RoomMaintenance::NewBuild {
+                    object_id: site.id(),
+                })
+                .collect(),
+        )
+    }
+
     pub fn items_len(&self) -> usize {
         match &self {
             MaintenanceQueue::Prioritized(i) => i.len(),
@@ -505,14 +336,14 @@ impl MaintenanceQueue {
         }
     }
 
-    pub fn priority_item(&self) -> Result<Option<&RoomMaintenance>, Box<dyn Error>> {
+    pub fn priority_item(&self) -> anyhow::Result<Option<&RoomMaintenance>> {
         match self {
             MaintenanceQueue::Prioritized(items) => Ok(items.first()),
-            MaintenanceQueue::Unsorted(_) => Err(Box::new(RoomError::RoomQueueNotPrioritized())),
+            MaintenanceQueue::Unsorted(_) => Err(RoomError::RoomQueueNotPrioritized().into()),
         }
     }
 
-    pub fn remove_item(&mut self, raw_object_id: RawObjectId) -> Result<(), Box<dyn Error>> {
+    pub fn remove_item(&mut self, raw_object_id: RawObjectId) -> anyhow::Result<()> {
         let items = match self {
             MaintenanceQueue::Prioritized(items) => items,
             MaintenanceQueue::Unsorted(items) => items,
@@ -576,17 +407,13 @@ pub struct FarmPositionData {
 
 pub fn prioritized_farm_positions(farm_positions: &Vec<FarmPosition>) -> Vec<FarmPosition> {
     let mut sorted_farm_positions = farm_positions.clone();
-    // Prioritize farm positions by dropping first
-    sorted_farm_positions.sort_by(|pos_a, pos_b| {
-        let pos_a_val = match pos_a {
-            FarmPosition::Dropping(_) => 0,
-            _ => 1,
-        };
-        let pos_b_val = match pos_b {
+    // Dropping into a container first, then the best tile
+    sorted_farm_positions.sort_by_cached_key(|farm_position| {
+        let kind_val = match farm_position {
             FarmPosition::Dropping(_) => 0,
             _ => 1,
         };
-        pos_a_val.cmp(&pos_b_val)
+        (kind_val, -farm_spots::tile_score(&farm_position.position()))
     });
     sorted_farm_positions
 }
@@ -610,7 +437,7 @@ pub fn farm_positions(
     room_name: RoomName,
 ) -> anyhow::Result<HashMap<ObjectId<Source>, Vec<FarmPosition>>> {
     let room =
-        rooms::get(room_name).ok_or(Box::new(RoomError::RoomNotFound(format!("{}", room_name))))?;
+        rooms::get(room_name).ok_or(RoomError::RoomNotFound(format!("{}", room_name)))?;
     let sources = room.find(find::SOURCES);
 
     let mut positions: HashMap<ObjectId<Source>, Vec<FarmPosition>> = HashMap::new();
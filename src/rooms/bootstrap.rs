@@ -0,0 +1,53 @@
+//! After a respawn there is nothing: no memory, no configured room and no creeps, only a single
+//! spawn. The room gets a `SetupBaseState` running the opener (see `room_state::opener`) and
+//! minimal settings until the controller reaches `OPENER_TARGET_RCL`.
+use log::info;
+use screeps::{Room, RoomName};
+
+use crate::game::{owned_rooms, OwnedBy};
+
+use super::MyRoom;
+
+/// The room counts as bootstrapped once the controller reaches this level
+pub const OPENER_TARGET_RCL: u32 = 2;
+
+#[derive(Debug)]
+pub struct Bootstrap {
+    pub room_name: RoomName,
+}
+
+/// The single owned room if we just (re)spawned
+///
+/// That's the case if it's our only room, it is not configured and the controller is still low.
+pub fn detect_fresh_spawn() -> Option<Room> {
+    let owned_rooms = owned_rooms(OwnedBy::Me);
+    if owned_rooms.len() != 1 {
+        return None;
+    }
+    let (room_name, room) = owned_rooms.into_iter().next()?;
+    if MyRoom::by_room_name(room_name).is_some() {
+        return None;
+    }
+    let level = room.controller().map(|c| c.level()).unwrap_or(0);
+    if level >= OPENER_TARGET_RCL {
+        return None;
+    }
+    Some(room)
+}
+
+impl Bootstrap {
+    pub fn new(room: &Room) -> Bootstrap {
+        info!("Fresh spawn detected, bootstrapping room {}", room.name());
+        Bootstrap {
+            room_name: room.name(),
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        match screeps::game::rooms::get(self.room_name).and_then(|r| r.controller()) {
+            Some(controller) => controller.level() >= OPENER_TARGET_RCL,
+            // Lost the room, nothing to bootstrap anymore
+            None => true,
+        }
+    }
+}
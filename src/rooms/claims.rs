@@ -0,0 +1,163 @@
+//! Claims and reservations in flight, by the room they target. Two bases picking the same remote,
+//! or an expansion requested twice, would send two claimers to one controller and one of them
+//! walks there for nothing.
+//!
+//! `BWState::add_request` refuses a claim or reservation of a room that already has one in flight
+//! and `assign_requests` doesn't spawn for it. A base may renew its own reservation, a claim
+//! never shares its room. The registry is rebuilt every tick from the open requests and the living
+//! claimers, so what ends is released by itself. Claims for other shards dedupe themselves, see
+//! `shards`.
+use std::collections::HashMap;
+
+use log::info;
+use screeps::{Creep, ObjectId, RoomName};
+
+use crate::{
+    creeps::{
+        jobs::OokCreepJob,
+        races::{DynamicTasked, OokRace},
+    },
+    state::{
+        requests::{self, Request, RequestData},
+        UniqId,
+    },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClaimKind {
+    Claim,
+    Reserve,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClaimEntry {
+    pub kind: ClaimKind,
+    /// Base the claimer is for, unknown for claimers whose request is forgotten
+    pub by: Option<RoomName>,
+    /// Request the claimer is spawned for, `None` once the request is forgotten
+    pub request_id: Option<UniqId>,
+}
+
+/// Target room, kind and requesting base of a claim or reservation request
+fn claim_of(request: &Request) -> Option<(RoomName, ClaimKind, RoomName)> {
+    match &request.data {
+        RequestData::Citizen(requests::Citizen {
+            target_room_name,
+            initial_job,
+            ..
+        }) => match initial_job {
+            OokCreepJob::ClaimRoom { target_room } => {
+                Some((*target_room, ClaimKind::Claim, *target_room_name))
+            }
+            OokCreepJob::ReserveRoom { target_room } => {
+                Some((*target_room, ClaimKind::Reserve, *target_room_name))
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ClaimRegistry {
+    entries: HashMap<RoomName, ClaimEntry>,
+}
+
+impl ClaimRegistry {
+    /// If a base other than `base` claims or reserves the room
+    pub fn is_held_by_other(&self, target_room: RoomName, base: RoomName) -> bool {
+        self.entries
+            .get(&target_room)
+            .and_then(|entry| entry.by)
+            .map(|by| by != base)
+            .unwrap_or(false)
+    }
+
+    /// If the request targets a room another claim or reservation is in flight for
+    pub fn is_duplicate(&self, request: &Request) -> bool {
+        let (target_room, kind, by) = match claim_of(request) {
+            Some(claim) => claim,
+            None => return false,
+        };
+        match self.entries.get(&target_room) {
+            None => false,
+            Some(entry) if entry.request_id.as_ref() == Some(&request.request_id) => false,
+            Some(entry) => match (entry.kind, kind) {
+                // The next reserver of the base takes over before the current one dies
+                (ClaimKind::Reserve, ClaimKind::Reserve) => {
+                    entry.by.map(|entry_by| entry_by != by).unwrap_or(false)
+                }
+                _ => true,
+            },
+        }
+    }
+
+    /// Books the claim or reservation of the request, other requests are ignored
+    pub fn register(&mut self, request: &Request) {
+        if let Some((target_room, kind, by)) = claim_of(request) {
+            self.entries.insert(
+                target_room,
+                ClaimEntry {
+                    kind,
+                    by: Some(by),
+                    request_id: Some(request.request_id.to_owned()),
+                },
+            );
+        }
+    }
+
+    /// Rebuilds the registry from the requests and the claimers, call it each tick
+    ///
+    /// Of several requests for one room the oldest holds it.
+    pub fn refresh(
+        &mut self,
+        open_requests: &HashMap<UniqId, Request>,
+        handled_requests: &HashMap<u32, HashMap<UniqId, Request>>,
+        citizens: &HashMap<ObjectId<Creep>, OokRace>,
+    ) {
+        let mut in_flight: Vec<&Request> = open_requests
+            .values()
+            .chain(
+                handled_requests
+                    .values()
+                    .flat_map(|requests| requests.values()),
+            )
+            .filter(|request| claim_of(request).is_some())
+            .collect();
+        in_flight.sort_by(|a, b| a.request_id.cmp(&b.request_id));
+
+        let mut entries: HashMap<RoomName, ClaimEntry> = HashMap::new();
+        for request in in_flight {
+            if let Some((target_room, kind, by)) = claim_of(request) {
+                entries.entry(target_room).or_insert(ClaimEntry {
+                    kind,
+                    by: Some(by),
+                    request_id: Some(request.request_id.to_owned()),
+                });
+            }
+        }
+        for citizen in citizens.values() {
+            let claimer = match citizen {
+                OokRace::Claimer(claimer) => claimer,
+                _ => continue,
+            };
+            let (target_room, kind) = match claimer.job() {
+                OokCreepJob::ClaimRoom { target_room } => (target_room, ClaimKind::Claim),
+                OokCreepJob::ReserveRoom { target_room } => (target_room, ClaimKind::Reserve),
+                _ => continue,
+            };
+            let by = self.entries.get(&target_room).and_then(|entry| entry.by);
+            entries.entry(target_room).or_insert(ClaimEntry {
+                kind,
+                by,
+                request_id: None,
+            });
+        }
+        for (target_room, entry) in &self.entries {
+            if !entries.contains_key(target_room) {
+                info!("{:?} of {} is over", entry.kind, target_room);
+            }
+        }
+        self.entries = entries;
+    }
+}
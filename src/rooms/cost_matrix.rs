@@ -0,0 +1,176 @@
+//! Cached CostMatrix per room, so long distance and crowded pathing doesn't have to rediscover
+//! every structure with each `move_to`.
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Mutex,
+    },
+};
+
+use lazy_static::lazy_static;
+use screeps::{
+    find, game,
+    pathfinder::{self, LocalCostMatrix, MultiRoomCostResult, SearchOptions, SearchResults},
+    Creep, HasPosition, OwnedStructureProperties, Position, Room, RoomName, SharedCreepProperties,
+    Structure, StructureProperties,
+};
+
+use crate::{
+    constants::{CREEP_ID_FARMER, MEM_JOB, MEM_JOB_KIND},
+    creeps::jobs::OokCreepJobKind,
+    rooms::{intel, spawn_area},
+};
+
+/// Rebuild the matrix even if nothing seems to have changed
+const MATRIX_MAX_AGE: u32 = 1000;
+const COST_ROAD: u8 = 1;
+const COST_BLOCKED: u8 = 0xff;
+/// Farmers won't step aside, going around is cheaper than waiting
+const COST_STATIC_CREEP: u8 = 50;
+/// Passing through is fine, but paths shouldn't lead over the tiles fresh creeps spawn on
+const COST_SPAWN_EXIT: u8 = 10;
+
+struct CachedMatrix {
+    built_at: u32,
+    /// Hash of the type and position of every structure, see `layout_hash`
+    layout: u64,
+    matrix: LocalCostMatrix,
+}
+
+lazy_static! {
+    static ref MATRICES: Mutex<HashMap<RoomName, CachedMatrix>> = Mutex::new(HashMap::new());
+}
+
+/// Goes up whenever any matrix changes, paths searched with an older one may be outdated
+static VERSION: AtomicU32 = AtomicU32::new(0);
+
+pub fn version() -> u32 {
+    VERSION.load(Ordering::Relaxed)
+}
+
+/// Forget the matrix of a room, e.g. when something was built or destroyed
+pub fn invalidate(room_name: RoomName) {
+    if MATRICES.lock().unwrap().remove(&room_name).is_some() {
+        VERSION.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Rebuilds the cached matrix of the room if it is outdated
+fn update_cached(room: &Room, matrices: &mut HashMap<RoomName, CachedMatrix>) {
+    let structures = room.find(find::STRUCTURES);
+    let layout = layout_hash(&structures);
+    let outdated = match matrices.get(&room.name()) {
+        Some(cached) => cached.layout != layout || cached.built_at + MATRIX_MAX_AGE < game::time(),
+        None => true,
+    };
+    if outdated {
+        VERSION.fetch_add(1, Ordering::Relaxed);
+        matrices.insert(
+            room.name(),
+            CachedMatrix {
+                built_at: game::time(),
+                layout,
+                matrix: structures_matrix(room, &structures),
+            },
+        );
+    }
+}
+
+/// Builds the matrix of a visible room ahead of time, so pathing doesn't have to
+pub fn refresh(room_name: RoomName) {
+    if let Some(room) = game::rooms::get(room_name) {
+        update_cached(&room, &mut MATRICES.lock().unwrap());
+    }
+}
+
+/// Matrix for structures and static creeps of a room, `None` if the room is not visible
+pub fn room_matrix(room_name: RoomName) -> Option<LocalCostMatrix> {
+    let room = game::rooms::get(room_name)?;
+    let mut matrices = MATRICES.lock().unwrap();
+    update_cached(&room, &mut matrices);
+    let mut matrix = matrices.get(&room_name)?.matrix.clone();
+    // Creeps move, so they are never cached
+    for creep in room.find(find::MY_CREEPS) {
+        if is_static_creep(&creep) {
+            let pos = creep.pos();
+            matrix.set(pos.x() as u8, pos.y() as u8, COST_STATIC_CREEP);
+        }
+    }
+    Some(matrix)
+}
+
+/// Changes when a structure is swapped for another or moved, not only when the count changes
+fn layout_hash(structures: &[Structure]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for structure in structures {
+        structure.structure_type().hash(&mut hasher);
+        structure.pos().packed_repr().hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+fn structures_matrix(room: &Room, structures: &[Structure]) -> LocalCostMatrix {
+    let mut matrix = LocalCostMatrix::new();
+    for pos in spawn_area::room_spawn_exits(room) {
+        matrix.set(pos.x() as u8, pos.y() as u8, COST_SPAWN_EXIT);
+    }
+    for structure in structures {
+        let pos = structure.pos();
+        let cost = match structure {
+            Structure::Road(_) => COST_ROAD,
+            Structure::Container(_) => continue,
+            Structure::Rampart(rampart) => {
+                if rampart.my() || rampart.is_public() {
+                    continue;
+                } else {
+                    COST_BLOCKED
+                }
+            }
+            _ => COST_BLOCKED,
+        };
+        // Don't make a road cheaper than a building or spawn exit on the same tile
+        if matrix.get(pos.x() as u8, pos.y() as u8) < cost {
+            matrix.set(pos.x() as u8, pos.y() as u8, cost);
+        }
+    }
+    matrix
+}
+
+fn is_static_creep(creep: &Creep) -> bool {
+    if creep.name().starts_with(CREEP_ID_FARMER) {
+        return true;
+    }
+    let job_kind_path = format!("{}.{}", MEM_JOB, MEM_JOB_KIND);
+    matches!(
+        creep.memory().path_i32(&job_kind_path),
+        Ok(Some(kind)) if kind == OokCreepJobKind::FarmSource as i32
+    )
+}
+
+/// `PathFinder.search` using the cached matrices of all visible rooms
+///
+/// With `avoid_danger` the path leads around the rooms `intel::avoids`, but for the ones it starts
+/// and ends in.
+pub fn search(from: &Position, to: &Position, range: u32, avoid_danger: bool) -> SearchResults {
+    profile_scope!("cost_matrix::search");
+    let ends = [from.room_name(), to.room_name()];
+    pathfinder::search(
+        from,
+        to,
+        range,
+        SearchOptions::new()
+            .plain_cost(2)
+            .swamp_cost(10)
+            .room_callback(move |room_name| {
+                if avoid_danger && !ends.contains(&room_name) && intel::avoids(room_name) {
+                    return MultiRoomCostResult::Impassable;
+                }
+                match room_matrix(room_name) {
+                    Some(matrix) => MultiRoomCostResult::CostMatrix(matrix.upload()),
+                    None => MultiRoomCostResult::Default,
+                }
+            }),
+    )
+}
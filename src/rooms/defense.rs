@@ -0,0 +1,197 @@
+//! Raids on the walls. Ranged attackers camping outside the ramparts get blocked by cheap melee
+//! creeps standing on the rampart tiles they would break through, the towers shoot what the
+//! blockers hit.
+use screeps::{
+    find, game, look, Attackable, Creep, HasPosition, HasStore, OwnedStructureProperties, Part,
+    Position, ResourceType, Room, SharedCreepProperties, Structure, StructureTower, StructureType,
+    Terrain,
+};
+
+use crate::config;
+
+use super::{planner, structures};
+
+pub mod bunker;
+pub mod drill;
+pub mod threats;
+pub mod towers;
+
+/// Hostiles this close to the walls are camping them
+const CAMP_RANGE: u32 = 3;
+/// Even a big raid doesn't get more blockers, they'd only stand in the way of each other
+const MAX_BLOCKERS: u32 = 4;
+/// Blockers one healer keeps up with
+const BLOCKERS_PER_HEALER: u32 = 2;
+
+fn is_walkable(room: &Room, pos: &Position) -> bool {
+    if game::map::get_room_terrain(pos.room_name()).get(pos.x(), pos.y()) == Terrain::Wall {
+        return false;
+    }
+    room.look_for_at(look::STRUCTURES, pos)
+        .iter()
+        .all(|s| match s {
+            Structure::Road(_) | Structure::Container(_) => true,
+            Structure::Rampart(rampart) => rampart.my() || rampart.is_public(),
+            _ => false,
+        })
+}
+
+fn has_own_rampart(room: &Room, pos: &Position) -> bool {
+    room.look_for_at(look::STRUCTURES, pos)
+        .iter()
+        .any(|s| matches!(s, Structure::Rampart(rampart) if rampart.my()))
+}
+
+/// Hostiles in the room, but for the players `threats` ignores
+pub fn hostiles(room: &Room) -> Vec<Creep> {
+    room.find(find::HOSTILE_CREEPS)
+        .into_iter()
+        .filter(|c| !threats::ignores(c))
+        .collect()
+}
+
+/// Hostile ranged attackers in reach of my ramparts and walls, but for the players `threats`
+/// ignores
+pub fn wall_campers(room: &Room) -> Vec<Creep> {
+    let walls: Vec<Position> = room
+        .find(find::STRUCTURES)
+        .into_iter()
+        .filter_map(|s| match s {
+            Structure::Rampart(rampart) if rampart.my() => Some(rampart.pos()),
+            Structure::Wall(wall) => Some(wall.pos()),
+            _ => None,
+        })
+        .collect();
+    hostiles(room)
+        .into_iter()
+        .filter(|c| c.get_active_bodyparts(Part::RangedAttack) > 0)
+        .filter(|c| walls.iter().any(|w| c.pos().in_range_to(w, CAMP_RANGE)))
+        .collect()
+}
+
+/// Where the wall campers stand, the made up ones of a drill included
+pub fn campers(room: &Room) -> Vec<Position> {
+    let mut campers: Vec<Position> = wall_campers(room).iter().map(|c| c.pos()).collect();
+    campers.extend(drill::simulated_campers(room));
+    campers
+}
+
+/// Walkable rampart tiles at the edge of the wall line in reach of the campers, closest to them
+/// first
+pub fn choke_points(room: &Room) -> Vec<Position> {
+    let campers = campers(room);
+    if campers.is_empty() {
+        return vec![];
+    }
+    let distance_to_campers = |pos: &Position| {
+        campers
+            .iter()
+            .map(|c| pos.get_range_to(c))
+            .min()
+            .unwrap_or(u32::MAX)
+    };
+    let mut points: Vec<Position> = room
+        .find(find::STRUCTURES)
+        .into_iter()
+        .filter_map(|s| match s {
+            Structure::Rampart(rampart) if rampart.my() => Some(rampart.pos()),
+            _ => None,
+        })
+        .filter(|pos| distance_to_campers(pos) <= CAMP_RANGE)
+        .filter(|pos| is_walkable(room, pos))
+        // A breach here opens a way in
+        .filter(|pos| {
+            planner::neighbours(pos, 1)
+                .iter()
+                .any(|n| is_walkable(room, n) && !has_own_rampart(room, n))
+        })
+        .collect();
+    points.sort_by_key(|pos| distance_to_campers(pos));
+    points
+}
+
+/// Blockers to keep in the room while it is raided
+pub fn wanted_blockers(room: &Room) -> u32 {
+    let campers = campers(room).len() as u32;
+    if campers == 0 {
+        return 0;
+    }
+    campers
+        .min(choke_points(room).len() as u32)
+        .min(MAX_BLOCKERS)
+}
+
+/// Healers to keep in the room while it is raided, they keep the blockers standing
+pub fn wanted_healers(room: &Room) -> u32 {
+    let blockers = wanted_blockers(room);
+    (blockers + BLOCKERS_PER_HEALER - 1) / BLOCKERS_PER_HEALER
+}
+
+/// The hostile all towers and blockers should hit
+///
+/// Hostiles next to one of my melee creeps come first, so towers and blockers add up. The one
+/// with the least hits goes down first. Players `threats` ignores aren't shot at.
+pub fn focus_target(room: &Room) -> Option<Creep> {
+    let hostiles = hostiles(room);
+    let melee: Vec<Position> = room
+        .find(find::MY_CREEPS)
+        .into_iter()
+        .filter(|c| c.get_active_bodyparts(Part::Attack) > 0)
+        .map(|c| c.pos())
+        .collect();
+    let (in_melee, others): (Vec<Creep>, Vec<Creep>) = hostiles
+        .into_iter()
+        .partition(|h| melee.iter().any(|pos| pos.is_near_to(&h.pos())));
+    let candidates = if in_melee.is_empty() { others } else { in_melee };
+    candidates.into_iter().min_by_key(|h| h.hits())
+}
+
+/// What the carriers of a room fill first while it is attacked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HaulPriority {
+    /// Spawn and extensions first, then the towers, as in peace
+    Spawning,
+    /// The towers before everything else
+    Towers,
+}
+
+/// If a wall or rampart in reach of the campers is about to break, see
+/// `config::BREACH_WARNING_HITS`
+pub fn is_breaching(room: &Room) -> bool {
+    let campers = wall_campers(room);
+    if campers.is_empty() {
+        return false;
+    }
+    room.find(find::STRUCTURES)
+        .into_iter()
+        .filter(|s| match s {
+            Structure::Rampart(rampart) => rampart.my(),
+            Structure::Wall(_) => true,
+            _ => false,
+        })
+        .filter(|s| campers.iter().any(|c| c.pos().in_range_to(s, CAMP_RANGE)))
+        .any(|s| {
+            s.as_attackable()
+                .map(|a| a.hits() < config::BREACH_WARNING_HITS)
+                .unwrap_or(false)
+        })
+}
+
+/// What carriers fill first. Towers about to lose a wall get everything. A blocker or healer
+/// waiting on the spawn gets its energy before the towers, otherwise the towers shoot the
+/// hostiles and go first.
+pub fn haul_priority(room: &Room, defender_queued: bool) -> HaulPriority {
+    let towers_hungry = structures::of_type::<StructureTower>(room.name(), StructureType::Tower)
+        .iter()
+        .any(|t| t.store_free_capacity(Some(ResourceType::Energy)) > 0);
+    if !towers_hungry {
+        return HaulPriority::Spawning;
+    }
+    if is_breaching(room) {
+        return HaulPriority::Towers;
+    }
+    if defender_queued || hostiles(room).is_empty() {
+        return HaulPriority::Spawning;
+    }
+    HaulPriority::Towers
+}
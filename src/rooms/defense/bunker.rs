@@ -0,0 +1,314 @@
+//! If the walls close the base. Every way from the exits to the spawns, the storage, the terminal
+//! and the towers has to lead through an own rampart or a wall, otherwise a raid just walks in.
+//! Tiles in reach of those structures are protected, the tiles next to the exits can't be built on.
+//!
+//! The ways still open are closed with as few ramparts as possible, by the minimal cut between the
+//! exits and the protected tiles. Every tile is split into an in and an out node joined with a
+//! capacity of 1, so cutting it costs one rampart. Walls, ramparts and their construction sites
+//! are taken out of the graph, they close already.
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+};
+
+use lazy_static::lazy_static;
+use log::debug;
+use screeps::{
+    find, game, HasPosition, OwnedStructureProperties, Position, Room, RoomName, StructureRampart,
+    StructureType, StructureWall,
+};
+
+use crate::rooms::structures;
+
+const ROOM_SIZE: usize = 50;
+const ROOM_AREA: usize = ROOM_SIZE * ROOM_SIZE;
+const TERRAIN_MASK_WALL: u8 = 1;
+/// Tiles this close to a protected structure have to be inside, out of reach of ranged attackers
+const PROTECTED_RANGE: i32 = 3;
+/// More than any cut of a room, edges with it are never cut
+const INFINITE: u32 = 10_000;
+/// Structures that have to be inside the walls
+const PROTECTED: [StructureType; 4] = [
+    StructureType::Spawn,
+    StructureType::Storage,
+    StructureType::Terminal,
+    StructureType::Tower,
+];
+
+#[derive(Debug, Clone)]
+pub struct BunkerCheck {
+    pub checked_at: u32,
+    /// Protected structures are far enough from the exits to close them in at all
+    pub closable: bool,
+    /// Tiles that need a rampart to close the open ways in
+    pub open_tiles: Vec<Position>,
+}
+
+impl BunkerCheck {
+    pub fn is_closed(&self) -> bool {
+        self.closable && self.open_tiles.is_empty()
+    }
+}
+
+lazy_static! {
+    static ref CHECKS: Mutex<HashMap<RoomName, BunkerCheck>> = Mutex::new(HashMap::new());
+}
+
+struct Edge {
+    to: usize,
+    capacity: u32,
+}
+
+/// Flow network for Dinic's algorithm, the reverse of edge `i` is `i ^ 1`
+struct Network {
+    edges: Vec<Edge>,
+    adjacency: Vec<Vec<usize>>,
+}
+
+impl Network {
+    fn new(nodes: usize) -> Network {
+        Network {
+            edges: vec![],
+            adjacency: vec![vec![]; nodes],
+        }
+    }
+
+    fn add_edge(&mut self, from: usize, to: usize, capacity: u32) {
+        self.adjacency[from].push(self.edges.len());
+        self.edges.push(Edge { to, capacity });
+        self.adjacency[to].push(self.edges.len());
+        self.edges.push(Edge {
+            to: from,
+            capacity: 0,
+        });
+    }
+
+    /// Steps from `source` over edges with capacity left, `None` for nodes out of reach
+    fn levels(&self, source: usize) -> Vec<Option<u32>> {
+        let mut levels = vec![None; self.adjacency.len()];
+        levels[source] = Some(0);
+        let mut open = VecDeque::new();
+        open.push_back(source);
+        while let Some(node) = open.pop_front() {
+            for edge in &self.adjacency[node] {
+                let edge = &self.edges[*edge];
+                if edge.capacity > 0 && levels[edge.to].is_none() {
+                    levels[edge.to] = levels[node].map(|l| l + 1);
+                    open.push_back(edge.to);
+                }
+            }
+        }
+        levels
+    }
+
+    fn push(
+        &mut self,
+        node: usize,
+        sink: usize,
+        flow: u32,
+        levels: &[Option<u32>],
+        next_edge: &mut [usize],
+    ) -> u32 {
+        if node == sink {
+            return flow;
+        }
+        while next_edge[node] < self.adjacency[node].len() {
+            let edge = self.adjacency[node][next_edge[node]];
+            let to = self.edges[edge].to;
+            let capacity = self.edges[edge].capacity;
+            let is_next_level = levels[to].is_some() && levels[to] == levels[node].map(|l| l + 1);
+            if capacity > 0 && is_next_level {
+                let pushed = self.push(to, sink, flow.min(capacity), levels, next_edge);
+                if pushed > 0 {
+                    self.edges[edge].capacity -= pushed;
+                    self.edges[edge ^ 1].capacity += pushed;
+                    return pushed;
+                }
+            }
+            next_edge[node] += 1;
+        }
+        0
+    }
+
+    /// Maximal flow from `source` to `sink`, stops early once it reaches `INFINITE`
+    fn max_flow(&mut self, source: usize, sink: usize) -> u32 {
+        let mut flow = 0;
+        loop {
+            let levels = self.levels(source);
+            if levels[sink].is_none() {
+                return flow;
+            }
+            let mut next_edge = vec![0; self.adjacency.len()];
+            loop {
+                let pushed = self.push(source, sink, INFINITE, &levels, &mut next_edge);
+                if pushed == 0 {
+                    break;
+                }
+                flow += pushed;
+                if flow >= INFINITE {
+                    return flow;
+                }
+            }
+        }
+    }
+}
+
+fn index(x: usize, y: usize) -> usize {
+    y * ROOM_SIZE + x
+}
+
+fn is_border(x: usize, y: usize) -> bool {
+    x == 0 || y == 0 || x == ROOM_SIZE - 1 || y == ROOM_SIZE - 1
+}
+
+/// Tiles blocked for hostiles: terrain walls, walls and own ramparts, built or planned
+fn blocked_tiles(room: &Room) -> Vec<bool> {
+    let mut blocked: Vec<bool> = game::map::get_room_terrain(room.name())
+        .get_raw_buffer()
+        .iter()
+        .map(|tile| tile & TERRAIN_MASK_WALL != 0)
+        .collect();
+    let mut barriers: Vec<Position> =
+        structures::of_type::<StructureWall>(room.name(), StructureType::Wall)
+            .iter()
+            .map(|w| w.pos())
+            .collect();
+    barriers.extend(
+        structures::of_type::<StructureRampart>(room.name(), StructureType::Rampart)
+            .iter()
+            .filter(|r| r.my())
+            .map(|r| r.pos()),
+    );
+    barriers.extend(
+        room.find(find::MY_CONSTRUCTION_SITES)
+            .iter()
+            .filter(|s| {
+                s.structure_type() == StructureType::Rampart
+                    || s.structure_type() == StructureType::Wall
+            })
+            .map(|s| s.pos()),
+    );
+    for pos in barriers {
+        blocked[index(pos.x() as usize, pos.y() as usize)] = true;
+    }
+    blocked
+}
+
+/// Tiles in reach of the protected structures
+fn protected_tiles(room: &Room, blocked: &[bool]) -> Vec<bool> {
+    let mut protected = vec![false; ROOM_AREA];
+    for ty in PROTECTED.iter() {
+        for id in structures::ids(room.name(), *ty) {
+            let pos = match game::get_object_erased(id) {
+                Some(object) => object.pos(),
+                None => continue,
+            };
+            for dy in -PROTECTED_RANGE..=PROTECTED_RANGE {
+                for dx in -PROTECTED_RANGE..=PROTECTED_RANGE {
+                    let (x, y) = (pos.x() as i32 + dx, pos.y() as i32 + dy);
+                    if x < 0 || y < 0 || x >= ROOM_SIZE as i32 || y >= ROOM_SIZE as i32 {
+                        continue;
+                    }
+                    let i = index(x as usize, y as usize);
+                    if !blocked[i] {
+                        protected[i] = true;
+                    }
+                }
+            }
+        }
+    }
+    protected
+}
+
+/// Exits and the tiles next to them, nothing can be built there
+fn near_exit_tiles(blocked: &[bool]) -> Vec<bool> {
+    let mut near_exit = vec![false; ROOM_AREA];
+    for y in 0..ROOM_SIZE {
+        for x in 0..ROOM_SIZE {
+            if !is_border(x, y) || blocked[index(x, y)] {
+                continue;
+            }
+            for ny in y.saturating_sub(1)..=(y + 1).min(ROOM_SIZE - 1) {
+                for nx in x.saturating_sub(1)..=(x + 1).min(ROOM_SIZE - 1) {
+                    near_exit[index(nx, ny)] = true;
+                }
+            }
+        }
+    }
+    near_exit
+}
+
+fn analyze(room: &Room) -> BunkerCheck {
+    let blocked = blocked_tiles(room);
+    let protected = protected_tiles(room, &blocked);
+    let near_exit = near_exit_tiles(&blocked);
+    let source = 2 * ROOM_AREA;
+    let sink = source + 1;
+    let mut network = Network::new(sink + 1);
+    for y in 0..ROOM_SIZE {
+        for x in 0..ROOM_SIZE {
+            let i = index(x, y);
+            if blocked[i] {
+                continue;
+            }
+            let (tile_in, tile_out) = (2 * i, 2 * i + 1);
+            let capacity = if protected[i] || near_exit[i] {
+                INFINITE
+            } else {
+                1
+            };
+            network.add_edge(tile_in, tile_out, capacity);
+            if is_border(x, y) {
+                network.add_edge(source, tile_in, INFINITE);
+            }
+            if protected[i] {
+                network.add_edge(tile_out, sink, INFINITE);
+            }
+            for ny in y.saturating_sub(1)..=(y + 1).min(ROOM_SIZE - 1) {
+                for nx in x.saturating_sub(1)..=(x + 1).min(ROOM_SIZE - 1) {
+                    let n = index(nx, ny);
+                    if n != i && !blocked[n] {
+                        network.add_edge(tile_out, 2 * n, INFINITE);
+                    }
+                }
+            }
+        }
+    }
+    let flow = network.max_flow(source, sink);
+    if flow >= INFINITE {
+        return BunkerCheck {
+            checked_at: game::time(),
+            closable: false,
+            open_tiles: vec![],
+        };
+    }
+    // Tiles whose in node is still reachable from the exits but their out node isn't are the cut
+    let reachable = network.levels(source);
+    let open_tiles = (0..ROOM_AREA)
+        .filter(|i| !blocked[*i] && reachable[2 * i].is_some() && reachable[2 * i + 1].is_none())
+        .map(|i| Position::new((i % ROOM_SIZE) as u32, (i / ROOM_SIZE) as u32, room.name()))
+        .collect();
+    BunkerCheck {
+        checked_at: game::time(),
+        closable: true,
+        open_tiles,
+    }
+}
+
+/// Checks the walls of the room again, expensive, run it deferred
+pub fn check(room: &Room) -> BunkerCheck {
+    let check = analyze(room);
+    debug!(
+        "Walls of {}: closable {}, open at {} tiles",
+        room.name(),
+        check.closable,
+        check.open_tiles.len()
+    );
+    CHECKS.lock().unwrap().insert(room.name(), check.clone());
+    check
+}
+
+/// The last check of the walls of the room, if there was one
+pub fn last_check(room_name: RoomName) -> Option<BunkerCheck> {
+    CHECKS.lock().unwrap().get(&room_name).cloned()
+}
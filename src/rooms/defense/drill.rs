@@ -0,0 +1,161 @@
+//! Defense drills. `drill(room, ticks, campers)` in the console makes the room act for `ticks`
+//! ticks as if `campers` ranged attackers camped its walls. Blockers and healers get requested,
+//! the blockers take the choke points next to the made up campers and the towers hold their
+//! energy, just like in a real raid. `endDrill()` ends it early.
+//!
+//! On a private server `drill(room, ticks, campers, true)` also spawns a target dummy, a single
+//! MOVE creep standing where the first camper would, for something to look at.
+use log::{info, warn};
+use screeps::{
+    find, game, memory::MemoryReference, HasPosition, OwnedStructureProperties, Part, Position,
+    ReturnCode, Room, RoomName, SharedCreepProperties, SpawnOptions, Structure,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    constants::{CREEP_ID_PREFIX, CREEP_ID_UNKNOWN, MEM_DRILL, MEM_KIND},
+    creeps::movement::move_creep_to,
+    rooms::planner,
+    server::{self, ServerKind},
+    visuals::Painter,
+};
+
+use super::{has_own_rampart, is_walkable};
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct Drill {
+    room: String,
+    until: u32,
+    campers: u32,
+    #[serde(default)]
+    dummy: bool,
+}
+
+js_serializable!(Drill);
+js_deserializable!(Drill);
+
+/// The drill going on in the room, if any
+fn drill_in(room_name: RoomName) -> Option<Drill> {
+    let drill = match screeps::memory::root().get::<Drill>(MEM_DRILL) {
+        Ok(drill) => drill?,
+        Err(err) => {
+            warn!("Could not load drill: {}", err);
+            return None;
+        }
+    };
+    if drill.until < game::time() || RoomName::new(&drill.room).ok()? != room_name {
+        return None;
+    }
+    Some(drill)
+}
+
+pub fn is_drilling(room_name: RoomName) -> bool {
+    drill_in(room_name).is_some()
+}
+
+/// Where the made up campers of the drill stand, in front of the ramparts
+///
+/// Tiles next to a rampart, without one of their own and further away from the spawn than the
+/// rampart, are outside. The campers get spread over them.
+pub fn simulated_campers(room: &Room) -> Vec<Position> {
+    let drill = match drill_in(room.name()) {
+        Some(drill) => drill,
+        None => return vec![],
+    };
+    let center = match room.find(find::MY_SPAWNS).into_iter().next() {
+        Some(spawn) => spawn.pos(),
+        None => return vec![],
+    };
+    let mut outside: Vec<Position> = vec![];
+    for structure in room.find(find::STRUCTURES) {
+        let rampart = match structure {
+            Structure::Rampart(rampart) if rampart.my() => rampart.pos(),
+            _ => continue,
+        };
+        for pos in planner::neighbours(&rampart, 1) {
+            let is_outside = pos.get_range_to(&center) > rampart.get_range_to(&center)
+                && is_walkable(room, &pos)
+                && !has_own_rampart(room, &pos);
+            if is_outside && !outside.contains(&pos) {
+                outside.push(pos);
+            }
+        }
+    }
+    if outside.is_empty() || drill.campers == 0 {
+        return vec![];
+    }
+    let step = (outside.len() / drill.campers as usize).max(1);
+    outside
+        .into_iter()
+        .step_by(step)
+        .take(drill.campers as usize)
+        .collect()
+}
+
+fn dummy_name(room_name: RoomName) -> String {
+    format!("{}-drill-{}", CREEP_ID_PREFIX, room_name)
+}
+
+/// Shows the made up campers and keeps the target dummy in place, call it each tick per base
+pub fn run(room: &Room) {
+    let drill = match drill_in(room.name()) {
+        Some(drill) => drill,
+        None => return,
+    };
+    let campers = simulated_campers(room);
+    let painter = Painter::new(room.name());
+    for pos in &campers {
+        painter.circle(pos.x() as f32, pos.y() as f32, None);
+    }
+    if !drill.dummy || server::capabilities().kind != ServerKind::Private {
+        return;
+    }
+    let name = dummy_name(room.name());
+    match game::creeps::get(&name) {
+        Some(dummy) => {
+            if let Some(pos) = campers.first() {
+                if dummy.pos() != *pos {
+                    move_creep_to(&dummy, pos, 0);
+                }
+            }
+        }
+        None => {
+            let spawn = match room
+                .find(find::MY_SPAWNS)
+                .into_iter()
+                .find(|s| s.spawning().is_none())
+            {
+                Some(spawn) => spawn,
+                None => return,
+            };
+            // A kind keeps the legacy code from making it harvest
+            let memory = MemoryReference::new();
+            memory.set(MEM_KIND, CREEP_ID_UNKNOWN);
+            let options = SpawnOptions::new().memory(Some(memory));
+            match spawn.spawn_creep_with_options(&[Part::Move], &name, &options) {
+                ReturnCode::Ok => info!("Spawning target dummy for the drill in {}", room.name()),
+                code => warn!("Couldn't spawn target dummy: {:?}", code),
+            }
+        }
+    }
+}
+
+/// Makes `drill(room, ticks, campers, dummy)` and `endDrill()` available in the console
+pub fn register_console() {
+    js! {
+        var key = @{MEM_DRILL};
+        global.drill = function(room, ticks, campers, dummy) {
+            Memory[key] = {
+                room: room,
+                until: Game.time + (ticks || 300),
+                campers: campers || 2,
+                dummy: !!dummy,
+            };
+            return "Drilling " + room + " until " + Memory[key].until;
+        };
+        global.endDrill = function() {
+            delete Memory[key];
+            return "Drill ended";
+        };
+    }
+}
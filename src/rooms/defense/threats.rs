@@ -0,0 +1,281 @@
+//! Players that attacked me. Every attack on my creeps and structures in the event logs adds to
+//! the threat score of the attacking player, every creep of mine they killed before its time adds
+//! `config::THREAT_KILL_SCORE`, see `killer_of`. Scores halve every
+//! `config::THREAT_HALF_LIFE_TICKS`, players that calmed down are forgotten. The ledger is kept in
+//! `Memory.threats`.
+//!
+//! `config::RETALIATION_POLICY` turns the score into the answer to a player: their creeps are
+//! ignored while the score is low, fought off when they come too close and once the score is high
+//! enough the harassers go after the remotes they reserve. NPCs are always fought off.
+use std::{collections::HashMap, sync::Mutex};
+
+use lazy_static::lazy_static;
+use log::{info, warn};
+use screeps::{
+    find, game, Creep, EventType, HasId, ObjectId, OwnedStructureProperties, RawObjectId, Room,
+    RoomName, SharedCreepProperties, StructureTower,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config,
+    constants::{MEM_THREATS, MY_USERNAME},
+    rooms::{events, intel, remotes::linear_distance},
+};
+
+/// Owners of the creeps the game spawns by itself
+const NPCS: &[&str] = &["Invader", "Source Keeper"];
+/// Scores below are forgotten
+const FORGET_SCORE: f64 = 0.1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Retaliation {
+    Ignore,
+    /// Fight their creeps in my rooms
+    DefendOnly,
+    /// Also send the harassers after the remotes they reserve
+    HarassRemotes,
+}
+
+/// Threat scores from which a player gets answered
+#[derive(Debug, Clone, Copy)]
+pub struct RetaliationPolicy {
+    pub defend_at: f64,
+    /// `None` never harasses
+    pub harass_at: Option<f64>,
+}
+
+impl RetaliationPolicy {
+    pub fn retaliation(&self, score: f64) -> Retaliation {
+        match self.harass_at {
+            Some(harass_at) if score >= harass_at => Retaliation::HarassRemotes,
+            _ if score >= self.defend_at => Retaliation::DefendOnly,
+            _ => Retaliation::Ignore,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct Threat {
+    score: f64,
+    updated_at: u32,
+    /// Creeps of mine they killed, all time
+    #[serde(default)]
+    kills: u32,
+}
+
+impl Threat {
+    fn score_at(&self, time: u32) -> f64 {
+        let half_lives =
+            time.saturating_sub(self.updated_at) as f64 / config::THREAT_HALF_LIFE_TICKS as f64;
+        self.score * 0.5_f64.powf(half_lives)
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct ThreatLedger {
+    players: HashMap<String, Threat>,
+}
+
+js_serializable!(ThreatLedger);
+js_deserializable!(ThreatLedger);
+
+lazy_static! {
+    static ref LEDGER: Mutex<Option<ThreatLedger>> = Mutex::new(None);
+}
+
+fn with_ledger<T, F: FnOnce(&mut ThreatLedger) -> T>(f: F) -> T {
+    let mut ledger = LEDGER.lock().unwrap();
+    let ledger = ledger.get_or_insert_with(|| {
+        match screeps::memory::root().get::<ThreatLedger>(MEM_THREATS) {
+            Ok(Some(ledger)) => ledger,
+            Ok(None) => Default::default(),
+            Err(err) => {
+                warn!("Could not load threat ledger: {}", err);
+                Default::default()
+            }
+        }
+    });
+    f(ledger)
+}
+
+fn is_npc(player: &str) -> bool {
+    NPCS.contains(&player)
+}
+
+/// Owner of the creep or tower behind the event
+pub fn owner_of(object_id: &str) -> Option<String> {
+    let raw: RawObjectId = object_id.parse().ok()?;
+    if let Ok(Some(creep)) = game::get_object_typed::<Creep>(ObjectId::from(raw)) {
+        return Some(creep.owner_name());
+    }
+    match game::get_object_typed::<StructureTower>(ObjectId::from(raw)) {
+        Ok(Some(tower)) => tower.owner_name(),
+        _ => None,
+    }
+}
+
+fn is_mine(object_id: &str, room_is_mine: bool) -> bool {
+    let raw: RawObjectId = match object_id.parse() {
+        Ok(raw) => raw,
+        Err(_) => return false,
+    };
+    match game::get_object_typed::<Creep>(ObjectId::from(raw)) {
+        Ok(Some(creep)) => creep.my(),
+        // Structures in my rooms and remotes are mine or in the way of mine
+        _ => room_is_mine,
+    }
+}
+
+fn is_room_mine(room: &Room) -> bool {
+    room.controller()
+        .map(|c| {
+            c.my()
+                || c.reservation()
+                    .map(|r| r.username == MY_USERNAME)
+                    .unwrap_or(false)
+        })
+        .unwrap_or(false)
+}
+
+/// Player that did the most damage to the creep last tick
+pub fn killer_of(room: &Room, creep_id: &str) -> Option<String> {
+    let mut damage_by_player: HashMap<String, u32> = HashMap::new();
+    for event in events::of(room) {
+        let attack = match event.event {
+            EventType::Attack(attack) if attack.target_id == creep_id => attack,
+            _ => continue,
+        };
+        if let Some(player) = owner_of(&event.object_id) {
+            *damage_by_player.entry(player).or_insert(0) += attack.damage;
+        }
+    }
+    damage_by_player
+        .into_iter()
+        .max_by_key(|(_, damage)| *damage)
+        .map(|(player, _)| player)
+}
+
+/// Points scored in the room last tick, by player
+fn scores_in(room: &Room, time: u32) -> HashMap<String, (f64, u32)> {
+    let mut scores: HashMap<String, (f64, u32)> = HashMap::new();
+    let room_is_mine = is_room_mine(room);
+    for event in events::of(room) {
+        let attack = match event.event {
+            EventType::Attack(attack) => attack,
+            _ => continue,
+        };
+        if !is_mine(&attack.target_id, room_is_mine) {
+            continue;
+        }
+        match owner_of(&event.object_id) {
+            Some(player) if player != MY_USERNAME && !is_npc(&player) => {
+                scores.entry(player).or_default().0 +=
+                    attack.damage as f64 * config::THREAT_DAMAGE_SCORE;
+            }
+            _ => {}
+        }
+    }
+    let killers = room
+        .find(find::TOMBSTONES)
+        .into_iter()
+        .filter(|tombstone| tombstone.death_time() + 1 == time)
+        .map(|tombstone| tombstone.creep())
+        .filter(|creep| creep.my() && creep.ticks_to_live().unwrap_or(0) > 1)
+        .filter_map(|creep| killer_of(room, &creep.id().to_string()));
+    for player in killers {
+        if player == MY_USERNAME || is_npc(&player) {
+            continue;
+        }
+        let score = scores.entry(player).or_default();
+        score.0 += config::THREAT_KILL_SCORE;
+        score.1 += 1;
+    }
+    scores
+}
+
+/// Adds up the attacks of the last tick in all visible rooms, call it each tick
+pub fn track() {
+    let time = game::time();
+    let mut scores: HashMap<String, (f64, u32)> = HashMap::new();
+    for room in game::rooms::values() {
+        for (player, (points, kills)) in scores_in(&room, time) {
+            let score = scores.entry(player).or_default();
+            score.0 += points;
+            score.1 += kills;
+        }
+    }
+    if scores.is_empty() {
+        return;
+    }
+    with_ledger(|ledger| {
+        for (player, (points, kills)) in scores {
+            let threat = ledger.players.entry(player.to_owned()).or_default();
+            let before = config::RETALIATION_POLICY.retaliation(threat.score_at(time));
+            threat.score = threat.score_at(time) + points;
+            threat.updated_at = time;
+            threat.kills += kills;
+            let after = config::RETALIATION_POLICY.retaliation(threat.score);
+            if after != before {
+                info!(
+                    "{} is up to a threat of {:.1}, answering with {:?}",
+                    player, threat.score, after
+                );
+            }
+        }
+        ledger
+            .players
+            .retain(|_, threat| threat.score_at(time) >= FORGET_SCORE);
+        screeps::memory::root().set(MEM_THREATS, ledger.clone());
+    });
+}
+
+/// Threat score of the player now
+pub fn score(player: &str) -> f64 {
+    let time = game::time();
+    with_ledger(|ledger| {
+        ledger
+            .players
+            .get(player)
+            .map(|threat| threat.score_at(time))
+            .unwrap_or(0.)
+    })
+}
+
+/// How the creeps of the player are answered
+pub fn retaliation(player: &str) -> Retaliation {
+    if is_npc(player) {
+        return Retaliation::DefendOnly;
+    }
+    config::RETALIATION_POLICY.retaliation(score(player))
+}
+
+/// If the creep is left alone
+pub fn ignores(hostile: &Creep) -> bool {
+    retaliation(&hostile.owner_name()) == Retaliation::Ignore
+}
+
+/// Remotes of players to harass close to the base, closest first
+pub fn harass_targets(base: RoomName) -> Vec<RoomName> {
+    let time = game::time();
+    let players: Vec<String> = with_ledger(|ledger| {
+        ledger
+            .players
+            .iter()
+            .filter(|(_, threat)| {
+                config::RETALIATION_POLICY.retaliation(threat.score_at(time))
+                    == Retaliation::HarassRemotes
+            })
+            .map(|(player, _)| player.to_owned())
+            .collect()
+    });
+    let mut targets: Vec<RoomName> = players
+        .iter()
+        .flat_map(|player| intel::reserved_by(player))
+        .filter(|room_name| {
+            linear_distance(base, *room_name) <= config::RETALIATION_MAX_ROOM_DISTANCE
+        })
+        .collect();
+    targets.sort_by_key(|room_name| (linear_distance(base, *room_name), room_name.to_string()));
+    targets
+}
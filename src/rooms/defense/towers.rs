@@ -0,0 +1,117 @@
+//! Towers of a room. While hostiles are around all towers shoot the focus target, otherwise one
+//! heals the most damaged creep of mine and the others with energy to spare repair. During a drill
+//! they hold their energy as if there were.
+//!
+//! Repairs at range cost a tower most of its power, see `repairs::tower_repair_power`. The rules of
+//! `config::TOWER_REPAIR_RULES` keep towers off what creeps repair cheaper: structures of a type
+//! with a rule are only repaired by towers up to its hits and within its range.
+use log::{debug, warn};
+use screeps::{
+    find, Attackable, HasId, HasPosition, HasStore, ResourceType, Room, Structure,
+    StructureProperties, StructureTower, StructureType,
+};
+
+use crate::{
+    config,
+    intents::{self, Action, Actor, Priority},
+    rooms::{repairs, structures},
+};
+
+use super::{drill, focus_target, hostiles};
+
+/// Energy a tower keeps for shooting, it only repairs with what it has on top
+const MIN_REPAIR_ENERGY: u32 = 500;
+
+/// How far towers repair structures of a type, `None` has no limit
+#[derive(Debug, Clone, Copy)]
+pub struct TowerRepairRule {
+    pub structure: StructureType,
+    /// Towers stop repairing once the structure has this many hits, creeps do the rest
+    pub max_hits: Option<u32>,
+    /// Towers only repair structures up to this range
+    pub max_range: Option<u32>,
+}
+
+/// If the tower at `range` should repair the structure, going by `config::TOWER_REPAIR_RULES`
+fn may_repair(target: &Structure, range: u32) -> bool {
+    let rule = match config::TOWER_REPAIR_RULES
+        .iter()
+        .find(|rule| rule.structure == target.structure_type())
+    {
+        Some(rule) => rule,
+        None => return true,
+    };
+    let hits = target.as_attackable().map(|a| a.hits()).unwrap_or(0);
+    rule.max_hits.map(|max| hits < max).unwrap_or(true)
+        && rule.max_range.map(|max| range <= max).unwrap_or(true)
+}
+
+/// Towers of the room with more than `min_energy` energy
+fn towers(room: &Room, min_energy: u32) -> Vec<StructureTower> {
+    structures::of_type::<StructureTower>(room.name(), StructureType::Tower)
+        .into_iter()
+        .filter(|t| t.store_used_capacity(Some(ResourceType::Energy)) > min_energy)
+        .collect()
+}
+
+/// Runs the towers of the room for this tick
+pub fn run(room: &Room) {
+    if !hostiles(room).is_empty() {
+        // All towers on one target, the blockers at the ramparts hit the same one
+        if let Some(target) = focus_target(room) {
+            for tower in towers(room, 0) {
+                intents::submit(
+                    Actor::Tower(tower.id()),
+                    Action::Attack {
+                        target: target.id(),
+                    },
+                    Priority::Defense,
+                );
+            }
+            warn!("Attacking {}", target.id());
+        }
+        return;
+    }
+    if drill::is_drilling(room.name()) {
+        debug!("Towers of {} hold their energy for the drill", room.name());
+        return;
+    }
+    // Damaged creeps come home for it, see `creeps::retirement`
+    let patient = room
+        .find(find::MY_CREEPS)
+        .into_iter()
+        .filter(|c| c.hits() < c.hits_max())
+        .max_by_key(|c| c.hits_max() - c.hits());
+    let healer = match (patient, towers(room, 0).into_iter().next()) {
+        (Some(patient), Some(tower)) => {
+            intents::submit(
+                Actor::Tower(tower.id()),
+                Action::Heal {
+                    target: patient.id(),
+                },
+                Priority::Upkeep,
+            );
+            Some(tower.id())
+        }
+        _ => None,
+    };
+    // Every tower on a target of its own
+    for tower in towers(room, MIN_REPAIR_ENERGY)
+        .into_iter()
+        .filter(|t| Some(t.id()) != healer)
+    {
+        let tower_pos = tower.pos();
+        let power =
+            |target: &Structure| repairs::tower_repair_power(tower_pos.get_range_to(target));
+        let allowed = |target: &Structure| may_repair(target, tower_pos.get_range_to(target));
+        if let Some(target) = repairs::claim_next(room, power, allowed, true) {
+            intents::submit(
+                Actor::Tower(tower.id()),
+                Action::Repair {
+                    target: target.id(),
+                },
+                Priority::Upkeep,
+            );
+        }
+    }
+}
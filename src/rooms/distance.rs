@@ -0,0 +1,54 @@
+//! Finding paths is expensive, so rank candidates by linear range first and only path the ones
+//! that have a chance to win.
+use std::cmp::Reverse;
+
+use screeps::{FindOptions, Path, Position, Room};
+
+/// Amount of best candidates that get a real path in `rank_two_stage`
+pub const PATHED_CANDIDATES: usize = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Distance {
+    /// Linear range, ignores walls and swamps
+    Range,
+    /// Length of the path found by `find_path_to`, around creeps unless `ignore_creeps`
+    Path { ignore_creeps: bool },
+}
+
+impl Distance {
+    pub fn between(&self, room: &Room, from: &Position, to: &Position) -> u32 {
+        match self {
+            Distance::Range => from.get_range_to(to),
+            Distance::Path { ignore_creeps } => {
+                let options = FindOptions::default().ignore_creeps(*ignore_creeps);
+                let path = match from.find_path_to(to, options) {
+                    Path::Serialized(p) => room.deserialize_path(&p),
+                    Path::Vectorized(p) => p,
+                };
+                path.len() as u32
+            }
+        }
+    }
+}
+
+/// Sorts `candidates` by `score`, highest first
+///
+/// Everything gets scored with `Distance::Range`, then the best `top_k` get scored again with
+/// `Distance::Path` and are put in front. `ignore_creeps` is passed on to the paths, so the ranking
+/// agrees with how the creep will walk.
+pub fn rank_two_stage<T, F>(
+    candidates: Vec<T>,
+    top_k: usize,
+    ignore_creeps: bool,
+    score: F,
+) -> Vec<T>
+where
+    F: Fn(&T, Distance) -> i32,
+{
+    let mut ranked = candidates;
+    ranked.sort_by_cached_key(|c| Reverse(score(c, Distance::Range)));
+    let rest = ranked.split_off(top_k.min(ranked.len()));
+    ranked.sort_by_cached_key(|c| Reverse(score(c, Distance::Path { ignore_creeps })));
+    ranked.extend(rest);
+    ranked
+}
@@ -0,0 +1,30 @@
+//! The event logs of the rooms in sight. `room.get_event_log()` parses the whole log out of JSON
+//! on every call, here it is read once per room and tick and handed out to everyone asking.
+use std::{collections::HashMap, sync::Mutex};
+
+use lazy_static::lazy_static;
+use screeps::{game, Event, Room, RoomName};
+
+#[derive(Debug, Default)]
+struct TickLogs {
+    time: u32,
+    logs: HashMap<RoomName, Vec<Event>>,
+}
+
+lazy_static! {
+    static ref LOGS: Mutex<TickLogs> = Mutex::new(TickLogs::default());
+}
+
+/// The events of the last tick in the room
+pub fn of(room: &Room) -> Vec<Event> {
+    let time = game::time();
+    let mut logs = LOGS.lock().unwrap();
+    if logs.time != time {
+        logs.time = time;
+        logs.logs.clear();
+    }
+    logs.logs
+        .entry(room.name())
+        .or_insert_with(|| room.get_event_log())
+        .clone()
+}
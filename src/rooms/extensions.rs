@@ -1,10 +1,10 @@
 use std::{cmp::Reverse, collections::{HashMap, HashSet}};
 
-use screeps::{HasId, HasPosition, ObjectId, Position, Room, RoomVisual, Structure, StructureExtension, StructureSpawn, find};
+use screeps::{HasId, HasPosition, ObjectId, Position, Room, RoomVisual, Structure, StructureExtension, StructureSpawn, StructureType};
 use serde::{Deserialize, Serialize};
 use std::hash::{Hash, Hasher};
 
-use super::room_ext::RoomExt;
+use super::{room_ext::RoomExt, structures};
 
 #[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq, Eq)]
 pub enum StructureSpawnSupply {
@@ -71,18 +71,22 @@ impl Default for ExtensionFillPath {
 
 impl ExtensionFillPath {
     pub fn best_for_room(room: &Room) -> ExtensionFillPath {
-        let structures = room.find(find::STRUCTURES);
         let walkable_tiles = room_walkable_tiles(room);
         // let extensions: Vec<&StructureExtension> = structures.iter().filter_map(|s| match s {
         //     screeps::Structure::Extension(e) => Some(e),
         //     _ => None,
         // }).collect();
 
-        let spawn_suppliers: Vec<StructureSpawnSupplyForCalc> = structures.into_iter().filter_map(|s| match s {
-            Structure::Extension(e) => Some(StructureSpawnSupplyForCalc::Extension(e)),
-            Structure::Spawn(e) => Some(StructureSpawnSupplyForCalc::Spawn(e)),
-            _ => None,
-        }).collect();
+        let mut spawn_suppliers: Vec<StructureSpawnSupplyForCalc> =
+            structures::of_type(room.name(), StructureType::Extension)
+                .into_iter()
+                .map(StructureSpawnSupplyForCalc::Extension)
+                .collect();
+        spawn_suppliers.extend(
+            structures::of_type(room.name(), StructureType::Spawn)
+                .into_iter()
+                .map(StructureSpawnSupplyForCalc::Spawn),
+        );
 
         let positioned_suppliers: HashMap<(u8, u8), StructureSpawnSupply> = spawn_suppliers.iter().map(|supplier| {
             match supplier {
@@ -0,0 +1,70 @@
+//! Scores the tiles around a source. Farmers stand on the best one and the source container is
+//! placed there, so both always agree on the spot.
+use screeps::{find, game, look, HasPosition, Position, Room, Structure, StructureType, Terrain};
+
+use super::{planner, room_ext::RoomExt};
+
+/// Farmers stand still for their whole life, swamp would only slow down the way there and
+/// everyone walking past
+const PLAIN_BONUS: i32 = 20;
+/// Per walkable neighbour, haulers need room to pick up
+const ACCESS_BONUS: i32 = 5;
+/// A container is planned on the tile
+const PLANNED_CONTAINER_BONUS: i32 = 100;
+/// A link (or its construction site) is in reach
+const LINK_BONUS: i32 = 50;
+
+fn is_wall(pos: &Position) -> bool {
+    game::map::get_room_terrain(pos.room_name()).get(pos.x(), pos.y()) == Terrain::Wall
+}
+
+/// How good `pos` is to farm a source from, higher is better
+pub fn tile_score(pos: &Position) -> i32 {
+    let mut score = 0;
+    if game::map::get_room_terrain(pos.room_name()).get(pos.x(), pos.y()) == Terrain::Plain {
+        score += PLAIN_BONUS;
+    }
+    let walkable_neighbours = planner::neighbours(pos, 1)
+        .iter()
+        .filter(|n| !is_wall(n))
+        .count() as i32;
+    score += walkable_neighbours * ACCESS_BONUS;
+
+    if let Some(room) = game::rooms::get(pos.room_name()) {
+        let container_planned = room
+            .look_for_at(look::CONSTRUCTION_SITES, pos)
+            .iter()
+            .any(|site| site.structure_type() == StructureType::Container);
+        if container_planned {
+            score += PLANNED_CONTAINER_BONUS;
+        }
+        let link_near = room
+            .look_for_around(look::STRUCTURES, *pos, 1)
+            .map(|structures| structures.iter().any(|s| matches!(s, Structure::Link(_))))
+            .unwrap_or(false)
+            || room
+                .look_for_around(look::CONSTRUCTION_SITES, *pos, 1)
+                .map(|sites| {
+                    sites
+                        .iter()
+                        .any(|site| site.structure_type() == StructureType::Link)
+                })
+                .unwrap_or(false);
+        if link_near {
+            score += LINK_BONUS;
+        }
+        // Shorter way for the haulers breaks ties
+        if let Some(spawn) = room.find(find::MY_SPAWNS).first() {
+            score -= pos.get_range_to(&spawn.pos()) as i32;
+        }
+    }
+    score
+}
+
+/// Where the container of the source should go, the best scored free tile next to it
+pub fn container_spot(room: &Room, source_pos: &Position) -> Option<Position> {
+    planner::neighbours(source_pos, 1)
+        .into_iter()
+        .filter(|pos| planner::is_free_tile(room, pos))
+        .max_by_key(|pos| tile_score(pos))
+}
@@ -0,0 +1,209 @@
+//! Structures carriers keep filled besides spawns and extensions: the labs with energy and the
+//! resource they work with, the nuker with energy and ghodium. Everything comes out of the storage
+//! or the terminal, whichever holds more of it. Carriers pick up the orders when there is nothing
+//! to supply.
+//!
+//! Labs get the resources of `config::LAB_RESOURCES` in order, sorted by position. A lab holding
+//! something else is left as it is.
+//!
+//! The labs of the room making boosts get the inputs of the reaction instead, see
+//! `economy::boosts`. Their products and leftovers of other reactions are carried out to the
+//! storage, the orders are then from the lab.
+use screeps::{
+    HasId, HasStore, ObjectId, ResourceType, Room, Structure, StructureLab, StructureNuker,
+    StructureType,
+};
+
+use crate::{
+    config,
+    economy::boosts::{self, Reaction},
+    rooms::structures,
+};
+
+/// Smaller gaps are not worth a trip
+const MIN_FILL_AMOUNT: u32 = 200;
+
+#[derive(Clone, Debug)]
+pub struct FillOrder {
+    /// The storage or the terminal, or the lab emptied
+    pub source: ObjectId<Structure>,
+    /// The lab or the nuker, or where the lab is emptied to
+    pub target: ObjectId<Structure>,
+    pub resource_type: ResourceType,
+    pub amount: u32,
+}
+
+/// The storage or the terminal of the room, the one with more of the resource
+fn best_source(room: &Room, resource_type: ResourceType) -> Option<(ObjectId<Structure>, u32)> {
+    let storage = room.storage().map(|s| {
+        (
+            s.as_structure().id(),
+            s.store_used_capacity(Some(resource_type)),
+        )
+    });
+    let terminal = room.terminal().map(|t| {
+        (
+            t.as_structure().id(),
+            t.store_used_capacity(Some(resource_type)),
+        )
+    });
+    storage
+        .into_iter()
+        .chain(terminal)
+        .filter(|(_, available)| *available > 0)
+        .max_by_key(|(_, available)| *available)
+}
+
+fn order(
+    room: &Room,
+    target: ObjectId<Structure>,
+    resource_type: ResourceType,
+    missing: u32,
+) -> Option<FillOrder> {
+    if missing < MIN_FILL_AMOUNT {
+        return None;
+    }
+    let (source, available) = best_source(room, resource_type)?;
+    let amount = missing.min(available);
+    if amount < MIN_FILL_AMOUNT {
+        return None;
+    }
+    Some(FillOrder {
+        source,
+        target,
+        resource_type,
+        amount,
+    })
+}
+
+fn free(store: &impl HasStore, resource_type: ResourceType) -> u32 {
+    store.store_free_capacity(Some(resource_type)).max(0) as u32
+}
+
+/// Carries the resource out of the lab, into the storage or else the terminal
+fn empty_order(
+    room: &Room,
+    lab: &StructureLab,
+    resource_type: ResourceType,
+    min_amount: u32,
+) -> Option<FillOrder> {
+    let amount = lab.store_used_capacity(Some(resource_type));
+    if amount == 0 || amount < min_amount {
+        return None;
+    }
+    let target = room
+        .storage()
+        .map(|s| s.as_structure().id())
+        .or_else(|| room.terminal().map(|t| t.as_structure().id()))?;
+    Some(FillOrder {
+        source: lab.as_structure().id(),
+        target,
+        resource_type,
+        amount,
+    })
+}
+
+/// The first two labs get the inputs, the others are emptied of the product
+fn reaction_orders(room: &Room, reaction: Reaction) -> Vec<FillOrder> {
+    let labs = boosts::sorted_labs(room.name());
+    let mut empties = vec![];
+    let mut fills = vec![];
+    for (i, lab) in labs.iter().enumerate() {
+        let target = lab.as_structure().id();
+        fills.extend(order(
+            room,
+            target,
+            ResourceType::Energy,
+            free(lab, ResourceType::Energy),
+        ));
+        let input = reaction.inputs.get(i).copied();
+        let mut holds_other = false;
+        for resource_type in lab.store_types() {
+            if resource_type == ResourceType::Energy || Some(resource_type) == input {
+                continue;
+            }
+            holds_other = true;
+            // Leftovers block the lab, the product is worth a trip once there is some of it
+            let min_amount = if resource_type == reaction.product {
+                MIN_FILL_AMOUNT
+            } else {
+                0
+            };
+            empties.extend(empty_order(room, lab, resource_type, min_amount));
+        }
+        if let (Some(input), false) = (input, holds_other) {
+            fills.extend(order(room, target, input, free(lab, input)));
+        }
+    }
+    empties.extend(fills);
+    empties
+}
+
+fn lab_orders(room: &Room) -> Vec<FillOrder> {
+    if let Some(reaction) = boosts::reaction(room.name()) {
+        return reaction_orders(room, reaction);
+    }
+    let labs = boosts::sorted_labs(room.name());
+    let mut orders = vec![];
+    for (i, lab) in labs.iter().enumerate() {
+        let target = lab.as_structure().id();
+        orders.extend(order(
+            room,
+            target,
+            ResourceType::Energy,
+            free(lab, ResourceType::Energy),
+        ));
+        let resource_type = match config::LAB_RESOURCES.get(i) {
+            Some(resource_type) => *resource_type,
+            None => continue,
+        };
+        let holds_other = lab
+            .store_types()
+            .iter()
+            .any(|ty| *ty != ResourceType::Energy && *ty != resource_type);
+        if !holds_other {
+            orders.extend(order(room, target, resource_type, free(lab, resource_type)));
+        }
+    }
+    orders
+}
+
+fn nuker_orders(room: &Room) -> Vec<FillOrder> {
+    let nuker = match structures::of_type::<StructureNuker>(room.name(), StructureType::Nuker)
+        .into_iter()
+        .next()
+    {
+        Some(nuker) => nuker,
+        None => return vec![],
+    };
+    let target = nuker.as_structure().id();
+    let mut orders: Vec<FillOrder> = order(
+        room,
+        target,
+        ResourceType::Ghodium,
+        free(&nuker, ResourceType::Ghodium),
+    )
+    .into_iter()
+    .collect();
+    // The nuker takes a lot of energy, only what the storage has to spare
+    let stored_energy = room
+        .storage()
+        .map(|s| s.store_used_capacity(Some(ResourceType::Energy)))
+        .unwrap_or(0);
+    if stored_energy >= config::NUKER_FILL_MIN_STORED_ENERGY {
+        orders.extend(order(
+            room,
+            target,
+            ResourceType::Energy,
+            free(&nuker, ResourceType::Energy),
+        ));
+    }
+    orders
+}
+
+/// What the labs and the nuker of the room are missing, the labs first
+pub fn fill_orders(room: &Room) -> Vec<FillOrder> {
+    let mut orders = lab_orders(room);
+    orders.extend(nuker_orders(room));
+    orders
+}
@@ -0,0 +1,213 @@
+//! Tracks how much of each source gets harvested per regeneration cycle. Sources that are
+//! consistently under-harvested get a second farmer, the numbers of remote sources show if
+//! mining them is worth it.
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+};
+
+use lazy_static::lazy_static;
+use log::{info, warn};
+use screeps::{find, game, HasId, ObjectId, OwnedStructureProperties, Room, RoomName, Source};
+use serde::{Deserialize, Serialize};
+
+use crate::constants::MY_USERNAME;
+
+/// Ticks a source needs to regenerate, a cycle without any harvesting ends after this
+const REGEN_TICKS: u32 = 300;
+/// Cycles kept per source
+const CYCLES_KEPT: usize = 10;
+/// Cycles needed before a source counts as under-harvested
+const MIN_CYCLES: usize = 5;
+/// Sources harvesting less than this share of their capacity are under-harvested
+const UNDER_HARVESTED_SHARE: f64 = 0.8;
+
+const MEM_STATS_SOURCES: &str = "stats.sources";
+
+#[derive(Clone, Debug)]
+struct SourceTrack {
+    room_name: RoomName,
+    remote: bool,
+    capacity: u32,
+    last_energy: u32,
+    harvested: u32,
+    cycle_started_at: u32,
+    /// Share of the capacity harvested in the last cycles
+    cycles: VecDeque<f64>,
+}
+
+impl SourceTrack {
+    fn finish_cycle(&mut self, capacity: u32, time: u32) {
+        if capacity > 0 {
+            if self.cycles.len() == CYCLES_KEPT {
+                self.cycles.pop_front();
+            }
+            self.cycles
+                .push_back((self.harvested as f64 / capacity as f64).min(1.));
+        }
+        self.harvested = 0;
+        self.cycle_started_at = time;
+    }
+
+    fn utilization(&self) -> Option<f64> {
+        if self.cycles.is_empty() {
+            None
+        } else {
+            Some(self.cycles.iter().sum::<f64>() / self.cycles.len() as f64)
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct SourceStats {
+    room: String,
+    remote: bool,
+    utilization: f64,
+    cycles: u32,
+}
+
+js_serializable!(SourceStats);
+
+lazy_static! {
+    static ref SOURCES: Mutex<HashMap<ObjectId<Source>, SourceTrack>> = Mutex::new(HashMap::new());
+}
+
+/// Owned rooms and rooms reserved by me, the latter are remote
+fn tracked_rooms() -> Vec<(Room, bool)> {
+    game::rooms::values()
+        .into_iter()
+        .filter_map(|room| {
+            let controller = room.controller()?;
+            if controller.my() {
+                Some((room, false))
+            } else if controller
+                .reservation()
+                .map(|r| r.username == MY_USERNAME)
+                .unwrap_or(false)
+            {
+                Some((room, true))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Follows the energy of every tracked source, call it each tick
+pub fn track_sources() {
+    let time = game::time();
+    let mut tracks = SOURCES.lock().unwrap();
+    for (room, remote) in tracked_rooms() {
+        for source in room.find(find::SOURCES) {
+            let energy = source.energy();
+            let capacity = source.energy_capacity();
+            let track = tracks.entry(source.id()).or_insert_with(|| SourceTrack {
+                room_name: room.name(),
+                remote,
+                capacity,
+                last_energy: energy,
+                harvested: 0,
+                cycle_started_at: time,
+                cycles: VecDeque::new(),
+            });
+            track.remote = remote;
+            track.capacity = capacity;
+            if energy < track.last_energy {
+                track.harvested += track.last_energy - energy;
+            } else if energy > track.last_energy {
+                // Regenerated
+                track.finish_cycle(capacity, time);
+            } else if energy == capacity && time - track.cycle_started_at >= REGEN_TICKS {
+                // Nobody touched it for a whole cycle
+                track.finish_cycle(capacity, time);
+            }
+            track.last_energy = energy;
+        }
+    }
+}
+
+/// Average share of the source's capacity harvested in the last cycles
+pub fn utilization(source: ObjectId<Source>) -> Option<f64> {
+    SOURCES.lock().unwrap().get(&source)?.utilization()
+}
+
+/// Average utilization of the tracked sources in the room
+pub fn room_utilization(room_name: RoomName) -> Option<f64> {
+    let tracks = SOURCES.lock().unwrap();
+    let utilizations: Vec<f64> = tracks
+        .values()
+        .filter(|track| track.room_name == room_name)
+        .filter_map(|track| track.utilization())
+        .collect();
+    if utilizations.is_empty() {
+        None
+    } else {
+        Some(utilizations.iter().sum::<f64>() / utilizations.len() as f64)
+    }
+}
+
+/// Energy per tick harvested from the tracked sources in the room, as of their last cycles
+pub fn room_income(room_name: RoomName) -> Option<f64> {
+    let tracks = SOURCES.lock().unwrap();
+    let incomes: Vec<f64> = tracks
+        .values()
+        .filter(|track| track.room_name == room_name)
+        .filter_map(|track| {
+            track
+                .utilization()
+                .map(|u| u * track.capacity as f64 / REGEN_TICKS as f64)
+        })
+        .collect();
+    if incomes.is_empty() {
+        None
+    } else {
+        Some(incomes.iter().sum())
+    }
+}
+
+/// If the source keeps regenerating with energy left over
+pub fn is_under_harvested(source: ObjectId<Source>) -> bool {
+    match SOURCES.lock().unwrap().get(&source) {
+        Some(track) if track.cycles.len() >= MIN_CYCLES => track
+            .utilization()
+            .map(|u| u < UNDER_HARVESTED_SHARE)
+            .unwrap_or(false),
+        _ => false,
+    }
+}
+
+/// Writes the utilization of all tracked sources to `Memory.stats.sources`
+pub fn persist_source_stats() {
+    let mut tracks = SOURCES.lock().unwrap();
+    let time = game::time();
+    // Sources that weren't visible for a while are gone or lost
+    tracks.retain(|_id, track| track.cycle_started_at + REGEN_TICKS * CYCLES_KEPT as u32 >= time);
+    let mut stats = HashMap::new();
+    for (id, track) in tracks.iter() {
+        let utilization = match track.utilization() {
+            Some(utilization) => utilization,
+            None => continue,
+        };
+        if track.cycles.len() >= MIN_CYCLES && utilization < UNDER_HARVESTED_SHARE {
+            let kind = if track.remote { "Remote source" } else { "Source" };
+            warn!(
+                "{} {} in {} only harvested to {:.0}%",
+                kind,
+                id,
+                track.room_name,
+                utilization * 100.
+            );
+        }
+        stats.insert(
+            id.to_string(),
+            SourceStats {
+                room: track.room_name.to_string(),
+                remote: track.remote,
+                utilization,
+                cycles: track.cycles.len() as u32,
+            },
+        );
+    }
+    info!("Persisting harvest stats of {} sources", stats.len());
+    screeps::memory::root().path_set(MEM_STATS_SOURCES, stats);
+}
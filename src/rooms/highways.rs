@@ -0,0 +1,182 @@
+//! Highways, the routes every hauler and upgrader of a base walks: from the storage to each source
+//! and to the controller. Instead of pathing them for every creep anew they are pathed once per
+//! room, see `deferred::Job::Highways`, and kept as steps, one digit per direction.
+//!
+//! A creep standing on a highway with the end of it as target just takes the next step, either
+//! way. Creeps off the highway or stuck on it path live as before, see `movement::move_creep_to`.
+use std::{collections::HashMap, sync::Mutex};
+
+use lazy_static::lazy_static;
+use log::debug;
+use screeps::{find, game, Direction, HasPosition, Position, RoomName};
+
+use crate::utils::DIRECTIONS;
+
+use super::cost_matrix;
+
+/// Creeps work sources from next to them and controllers from up to 3 tiles away
+const SOURCE_RANGE: u32 = 1;
+const CONTROLLER_RANGE: u32 = 3;
+
+#[derive(Debug, Clone)]
+struct Highway {
+    name: String,
+    /// The storage, or the spawn of rooms without one
+    from: Position,
+    /// The source or the controller
+    to: Position,
+    /// First tile of the highway, next to `from`
+    start: Position,
+    /// Direction from each tile to the next, as digits of `Direction`
+    steps: String,
+}
+
+impl Highway {
+    fn step(&self, index: usize) -> Option<Direction> {
+        let digit = *self.steps.as_bytes().get(index)?;
+        DIRECTIONS
+            .iter()
+            .find(|(direction, _, _)| *direction as u8 == digit - b'0')
+            .map(|(direction, _, _)| *direction)
+    }
+
+    fn tiles(&self) -> Vec<Position> {
+        let mut tiles = vec![self.start];
+        let mut pos = self.start;
+        for index in 0..self.steps.len() {
+            let (dx, dy) = match self.step(index).and_then(offset) {
+                Some(offset) => offset,
+                None => break,
+            };
+            pos = Position::new(
+                (pos.x() as i32 + dx) as u32,
+                (pos.y() as i32 + dy) as u32,
+                pos.room_name(),
+            );
+            tiles.push(pos);
+        }
+        tiles
+    }
+}
+
+#[derive(Debug, Default)]
+struct RoomHighways {
+    highways: Vec<Highway>,
+    /// Highways leading over the tile, with the index of the tile on them
+    tiles: HashMap<Position, Vec<(usize, usize)>>,
+}
+
+lazy_static! {
+    static ref HIGHWAYS: Mutex<HashMap<RoomName, RoomHighways>> = Mutex::new(HashMap::new());
+}
+
+fn offset(direction: Direction) -> Option<(i32, i32)> {
+    DIRECTIONS
+        .iter()
+        .find(|(d, _, _)| *d == direction)
+        .map(|(_, dx, dy)| (*dx, *dy))
+}
+
+fn direction(from: &Position, to: &Position) -> Option<Direction> {
+    let dx = to.x() as i32 - from.x() as i32;
+    let dy = to.y() as i32 - from.y() as i32;
+    DIRECTIONS
+        .iter()
+        .find(|(_, x, y)| *x == dx && *y == dy)
+        .map(|(direction, _, _)| *direction)
+}
+
+fn opposite(direction: Direction) -> Option<Direction> {
+    let (dx, dy) = offset(direction)?;
+    DIRECTIONS
+        .iter()
+        .find(|(_, x, y)| *x == -dx && *y == -dy)
+        .map(|(direction, _, _)| *direction)
+}
+
+/// Paths the highway, `None` if it leaves the room or there is no way
+fn pave(name: String, from: Position, to: Position, range: u32) -> Option<Highway> {
+    let results = cost_matrix::search(&from, &to, range, true);
+    let path = results.path();
+    if results.incomplete || path.iter().any(|pos| pos.room_name() != from.room_name()) {
+        return None;
+    }
+    let start = *path.first()?;
+    let steps: Option<String> = path
+        .windows(2)
+        .map(|tiles| direction(&tiles[0], &tiles[1]).map(|d| (b'0' + d as u8) as char))
+        .collect();
+    Some(Highway {
+        name,
+        from,
+        to,
+        start,
+        steps: steps?,
+    })
+}
+
+/// Paths the highways of the room again, the roads may have changed
+pub fn build(room_name: RoomName) {
+    let room = match game::rooms::get(room_name) {
+        Some(room) => room,
+        None => {
+            HIGHWAYS.lock().unwrap().remove(&room_name);
+            return;
+        }
+    };
+    let hub = match room.storage() {
+        Some(storage) => storage.pos(),
+        None => match room.find(find::MY_SPAWNS).first() {
+            Some(spawn) => spawn.pos(),
+            None => return,
+        },
+    };
+    let mut highways: Vec<Highway> = room
+        .find(find::SOURCES)
+        .iter()
+        .enumerate()
+        .filter_map(|(i, source)| pave(format!("source{}", i), hub, source.pos(), SOURCE_RANGE))
+        .collect();
+    if let Some(controller) = room.controller() {
+        highways.extend(pave(
+            "controller".to_string(),
+            hub,
+            controller.pos(),
+            CONTROLLER_RANGE,
+        ));
+    }
+    let mut tiles: HashMap<Position, Vec<(usize, usize)>> = HashMap::new();
+    for (h, highway) in highways.iter().enumerate() {
+        for (i, pos) in highway.tiles().into_iter().enumerate() {
+            tiles.entry(pos).or_default().push((h, i));
+        }
+    }
+    debug!(
+        "Highways of {}: {:?}",
+        room_name,
+        highways
+            .iter()
+            .map(|h| format!("{} {}", h.name, h.steps.len()))
+            .collect::<Vec<String>>()
+    );
+    HIGHWAYS
+        .lock()
+        .unwrap()
+        .insert(room_name, RoomHighways { highways, tiles });
+}
+
+/// The next step towards `target` if the creep at `pos` is on a highway that ends at it
+pub fn next_step(pos: &Position, target: &Position) -> Option<Direction> {
+    let highways = HIGHWAYS.lock().unwrap();
+    let room = highways.get(&pos.room_name())?;
+    room.tiles.get(pos)?.iter().find_map(|(h, index)| {
+        let highway = &room.highways[*h];
+        if highway.to == *target {
+            highway.step(*index)
+        } else if highway.from == *target && *index > 0 {
+            highway.step(index - 1).and_then(opposite)
+        } else {
+            None
+        }
+    })
+}
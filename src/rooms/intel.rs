@@ -0,0 +1,364 @@
+//! What I know of the rooms I've seen, kept while they are out of sight. Every visible room is
+//! looked at in the intel stage: who owns it, how many towers it has and if source keepers live in
+//! it.
+//!
+//! Paths between rooms lead around the dangerous ones, rooms of other players with towers and
+//! source keeper rooms, see `avoids`. Creeps that can fight go through anyway, and the rooms a
+//! path starts or ends in are never avoided. Rooms can also be avoided by hand, see `flags`.
+//!
+//! Portals are kept with their destination, to a room of this shard or another shard, and the tick
+//! they decay at. Rooms with portals aren't forgotten until the portals are gone. They are kept in
+//! `Memory.portals` and loaded from there after a reset, `portals()` in the console lists the known
+//! ones, see `persist_portals`.
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Mutex,
+};
+
+use lazy_static::lazy_static;
+use log::{info, warn};
+use screeps::{
+    game, memory, objects::PortalDestination, HasPosition, OwnedStructureProperties, Position,
+    Room, RoomName, StructurePortal, StructureTower, StructureType,
+};
+use serde::{Deserialize, Serialize};
+use stdweb::{js, unstable::TryInto};
+
+use crate::{
+    constants::{MEM_PORTALS, MY_USERNAME},
+    visuals::Painter,
+};
+
+use super::structures;
+
+/// Rooms not seen for this long are forgotten, whoever lived there may be gone
+const FORGET_TICKS: u32 = 20_000;
+
+/// Where a portal leads
+#[derive(Clone, Debug, PartialEq)]
+pub enum PortalTarget {
+    Room(Position),
+    Shard { shard: String, room: RoomName },
+}
+
+#[derive(Clone, Debug)]
+pub struct PortalIntel {
+    pub pos: Position,
+    pub target: PortalTarget,
+    /// Tick the portal disappears at, `None` for stable ones
+    pub decays_at: Option<u32>,
+}
+
+impl PortalIntel {
+    fn of(portal: &StructurePortal, time: u32) -> PortalIntel {
+        let target = match portal.destination() {
+            PortalDestination::InterRoom(pos) => PortalTarget::Room(pos),
+            PortalDestination::InterShard(destination) => PortalTarget::Shard {
+                shard: destination.shard().to_string(),
+                room: destination.room(),
+            },
+        };
+        // Undefined for stable portals
+        let ticks_to_decay: Option<u32> = js! {
+            return @{portal.as_ref()}.ticksToDecay;
+        }
+        .try_into()
+        .ok();
+        PortalIntel {
+            pos: portal.pos(),
+            target,
+            decays_at: ticks_to_decay.map(|ticks| time + ticks),
+        }
+    }
+
+    /// Like `shard3 W10N10 until 1234567`
+    fn describe(&self) -> String {
+        let target = match &self.target {
+            PortalTarget::Room(pos) => format!("{}", pos),
+            PortalTarget::Shard { shard, room } => format!("{} {}", shard, room),
+        };
+        match self.decays_at {
+            Some(tick) => format!("{} until {}", target, tick),
+            None => target,
+        }
+    }
+}
+
+/// A portal as it is kept in `Memory.portals`, `description` is what the console lists
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct StoredPortal {
+    room: RoomName,
+    x: u32,
+    y: u32,
+    /// `None` for portals to a room of this shard
+    target_shard: Option<String>,
+    target_room: RoomName,
+    /// Where in `target_room` it leads to, only known for portals within the shard
+    target_xy: Option<(u32, u32)>,
+    decays_at: Option<u32>,
+    description: String,
+}
+
+impl StoredPortal {
+    fn of(room: RoomName, portal: &PortalIntel) -> StoredPortal {
+        let (target_shard, target_room, target_xy) = match &portal.target {
+            PortalTarget::Room(pos) => (None, pos.room_name(), Some((pos.x(), pos.y()))),
+            PortalTarget::Shard { shard, room } => (Some(shard.clone()), *room, None),
+        };
+        StoredPortal {
+            room,
+            x: portal.pos.x(),
+            y: portal.pos.y(),
+            target_shard,
+            target_room,
+            target_xy,
+            decays_at: portal.decays_at,
+            description: portal.describe(),
+        }
+    }
+
+    fn intel(&self) -> PortalIntel {
+        let target = match (&self.target_shard, self.target_xy) {
+            (Some(shard), _) => PortalTarget::Shard {
+                shard: shard.clone(),
+                room: self.target_room,
+            },
+            (None, Some((x, y))) => PortalTarget::Room(Position::new(x, y, self.target_room)),
+            // Leads somewhere in the room, the middle is as good as any
+            (None, None) => PortalTarget::Room(Position::new(25, 25, self.target_room)),
+        };
+        PortalIntel {
+            pos: Position::new(self.x, self.y, self.room),
+            target,
+            decays_at: self.decays_at,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct PortalMemory {
+    /// By the position of the portal
+    portals: HashMap<String, StoredPortal>,
+}
+
+js_serializable!(PortalMemory);
+js_deserializable!(PortalMemory);
+
+#[derive(Clone, Debug)]
+pub struct RoomIntel {
+    /// Player owning the controller, `None` if it is mine or nobody's
+    pub hostile_owner: Option<String>,
+    /// Player reserving the controller, `None` if it is me or nobody
+    pub hostile_reserver: Option<String>,
+    /// Towers of the owner
+    pub hostile_towers: u32,
+    pub source_keepers: bool,
+    pub portals: Vec<PortalIntel>,
+    pub last_seen: u32,
+}
+
+impl RoomIntel {
+    /// If creeps passing through get shot
+    pub fn is_dangerous(&self) -> bool {
+        (self.hostile_owner.is_some() && self.hostile_towers > 0) || self.source_keepers
+    }
+}
+
+lazy_static! {
+    static ref INTEL: Mutex<HashMap<RoomName, RoomIntel>> = Mutex::new(load_portals());
+    /// Rooms avoided by hand, dangerous or not
+    static ref FORBIDDEN: Mutex<HashSet<RoomName>> = Mutex::new(HashSet::new());
+}
+
+/// The rooms with the portals of `Memory.portals`, nothing else is known of them until they are
+/// seen again
+fn load_portals() -> HashMap<RoomName, RoomIntel> {
+    let stored = match memory::root().get::<PortalMemory>(MEM_PORTALS) {
+        Ok(Some(stored)) => stored,
+        Ok(None) => return HashMap::new(),
+        Err(err) => {
+            warn!("Could not load portals: {}", err);
+            return HashMap::new();
+        }
+    };
+    let mut intel: HashMap<RoomName, RoomIntel> = HashMap::new();
+    for portal in stored.portals.values() {
+        intel
+            .entry(portal.room)
+            .or_insert_with(|| RoomIntel {
+                hostile_owner: None,
+                hostile_reserver: None,
+                hostile_towers: 0,
+                source_keepers: false,
+                portals: vec![],
+                last_seen: 0,
+            })
+            .portals
+            .push(portal.intel());
+    }
+    intel
+}
+
+fn scan(room: &Room) -> RoomIntel {
+    let time = game::time();
+    let hostile_owner = room
+        .controller()
+        .filter(|c| !c.my())
+        .and_then(|c| c.owner_name());
+    let hostile_reserver = room
+        .controller()
+        .and_then(|c| c.reservation())
+        .map(|r| r.username)
+        .filter(|username| username != MY_USERNAME);
+    let hostile_towers = structures::of_type::<StructureTower>(room.name(), StructureType::Tower)
+        .iter()
+        .filter(|tower| !tower.my())
+        .count() as u32;
+    let source_keepers = !structures::ids(room.name(), StructureType::KeeperLair).is_empty();
+    let portals = structures::of_type::<StructurePortal>(room.name(), StructureType::Portal)
+        .iter()
+        .map(|portal| PortalIntel::of(portal, time))
+        .collect();
+    RoomIntel {
+        hostile_owner,
+        hostile_reserver,
+        hostile_towers,
+        source_keepers,
+        portals,
+        last_seen: time,
+    }
+}
+
+/// Logs the portals that weren't in the intel of the room before
+fn log_new_portals(room_name: RoomName, known: Option<&RoomIntel>, scanned: &RoomIntel) {
+    for portal in &scanned.portals {
+        let is_known = known
+            .map(|k| k.portals.iter().any(|p| p.pos == portal.pos))
+            .unwrap_or(false);
+        if !is_known {
+            info!(
+                "Portal in {} at {}: {}",
+                room_name,
+                portal.pos,
+                portal.describe()
+            );
+        }
+    }
+}
+
+fn visualize_portals(room: &Room, intel: &RoomIntel) {
+    let painter = Painter::new(room.name());
+    for portal in &intel.portals {
+        painter.text(
+            portal.pos.x() as f32,
+            portal.pos.y() as f32 - 1.,
+            portal.describe(),
+            None,
+        );
+    }
+}
+
+/// Updates the intel of all visible rooms, call it each tick
+pub fn track_rooms() {
+    let time = game::time();
+    let mut intel = INTEL.lock().unwrap();
+    for room in game::rooms::values() {
+        let scanned = scan(&room);
+        let known = intel.get(&room.name());
+        let was_dangerous = known.map(|i| i.is_dangerous()).unwrap_or(false);
+        if scanned.is_dangerous() && !was_dangerous {
+            info!("Avoiding {}: {:?}", room.name(), scanned);
+        }
+        log_new_portals(room.name(), known, &scanned);
+        visualize_portals(&room, &scanned);
+        intel.insert(room.name(), scanned);
+    }
+    for room_intel in intel.values_mut() {
+        room_intel
+            .portals
+            .retain(|p| p.decays_at.map(|tick| tick > time).unwrap_or(true));
+    }
+    intel.retain(|_, i| i.last_seen + FORGET_TICKS >= time || !i.portals.is_empty());
+}
+
+/// Forgets the rooms unseen for `ticks`, their portals too, returns how many
+pub fn forget_unseen(ticks: u32) -> usize {
+    let time = game::time();
+    let mut intel = INTEL.lock().unwrap();
+    let known = intel.len();
+    intel.retain(|_, i| i.last_seen + ticks >= time);
+    known - intel.len()
+}
+
+/// What I know of the room, `None` if it was never seen
+pub fn get(room_name: RoomName) -> Option<RoomIntel> {
+    INTEL.lock().unwrap().get(&room_name).cloned()
+}
+
+/// Known rooms reserved by the player
+pub fn reserved_by(player: &str) -> Vec<RoomName> {
+    INTEL
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(_, i)| i.hostile_reserver.as_deref() == Some(player))
+        .map(|(room_name, _)| *room_name)
+        .collect()
+}
+
+/// Makes paths lead around exactly these rooms on top of the dangerous ones
+pub fn forbid(room_names: HashSet<RoomName>) {
+    *FORBIDDEN.lock().unwrap() = room_names;
+}
+
+/// If paths should lead around the room
+pub fn avoids(room_name: RoomName) -> bool {
+    if FORBIDDEN.lock().unwrap().contains(&room_name) {
+        return true;
+    }
+    INTEL
+        .lock()
+        .unwrap()
+        .get(&room_name)
+        .map(|i| i.is_dangerous())
+        .unwrap_or(false)
+}
+
+/// All known portals that haven't decayed yet, with their rooms
+pub fn portals() -> Vec<(RoomName, PortalIntel)> {
+    INTEL
+        .lock()
+        .unwrap()
+        .iter()
+        .flat_map(|(room_name, i)| i.portals.iter().map(move |p| (*room_name, p.clone())))
+        .collect()
+}
+
+/// Writes the known portals to `Memory.portals`, to load them after a reset and for the
+/// `portals()` console query
+pub fn persist_portals() {
+    let portals = portals()
+        .into_iter()
+        .map(|(room_name, portal)| (portal.pos.to_string(), StoredPortal::of(room_name, &portal)))
+        .collect();
+    memory::root().set(MEM_PORTALS, PortalMemory { portals });
+}
+
+/// Makes `portals(filter)` available in the console, listing the known portals whose position or
+/// destination contain `filter`, like a shard or room name
+pub fn register_console() {
+    js! {
+        var key = @{MEM_PORTALS};
+        global.portals = function(filter) {
+            var portals = (Memory[key] || {}).portals || {};
+            var lines = [];
+            for (var pos in portals) {
+                var line = pos + " -> " + portals[pos].description;
+                if (!filter || line.indexOf(filter) !== -1) {
+                    lines.push(line);
+                }
+            }
+            return lines.length ? lines.join("\n") : "No portals known";
+        };
+    }
+}
@@ -0,0 +1,73 @@
+//! Human friendly labels of rooms from `config::ROOM_LABELS`. Log lines get the room names in
+//! them swapped for the labels, see `logging`, and visible labeled rooms show theirs.
+use std::collections::HashMap;
+
+use screeps::{game, RoomName, TextAlign, TextStyle};
+
+use crate::{config, visuals::Painter};
+
+fn is_name_char(c: Option<char>) -> bool {
+    c.map(|c| c.is_ascii_alphanumeric()).unwrap_or(false)
+}
+
+/// Swaps the labeled room names in `message` for their labels, `W1N1` doesn't touch `W1N10`
+pub fn label_message(message: &str) -> String {
+    let mut labeled = message.to_string();
+    for (room, label) in config::ROOM_LABELS {
+        let mut result = String::with_capacity(labeled.len());
+        let mut rest = labeled.as_str();
+        while let Some(index) = rest.find(room) {
+            let before = rest[..index].chars().last();
+            let after = rest[index + room.len()..].chars().next();
+            result.push_str(&rest[..index]);
+            if is_name_char(before) || is_name_char(after) {
+                result.push_str(room);
+            } else {
+                result.push_str(label);
+            }
+            rest = &rest[index + room.len()..];
+        }
+        result.push_str(rest);
+        labeled = result;
+    }
+    labeled
+}
+
+/// Writes the labels of the visible labeled rooms into their top left corner
+pub fn draw_labels() {
+    for (room, label) in config::ROOM_LABELS {
+        let room = match RoomName::new(room).ok().and_then(game::rooms::get) {
+            Some(room) => room,
+            None => continue,
+        };
+        Painter::new(room.name()).text(
+            0.5,
+            1.,
+            label.to_string(),
+            Some(TextStyle::default().align(TextAlign::Left)),
+        );
+    }
+}
+
+/// Makes `roomLabel(name)` and `roomByLabel(label)` available in the console
+pub fn register_console_lookup() {
+    let labels: HashMap<String, String> = config::ROOM_LABELS
+        .iter()
+        .map(|(room, label)| (room.to_string(), label.to_string()))
+        .collect();
+    js! {
+        var labels = @{labels};
+        global.roomLabel = function(name) {
+            return labels[name] || name;
+        };
+        global.roomByLabel = function(label) {
+            var wanted = String(label).toLowerCase();
+            for (var name in labels) {
+                if (labels[name].toLowerCase() === wanted) {
+                    return name;
+                }
+            }
+            return undefined;
+        };
+    }
+}
@@ -0,0 +1,228 @@
+//! Builders lent between bases. A base with nothing to build has its builders upgrade, while a
+//! neighbor with a big construction backlog builds for ages. Every base publishes its balance in
+//! work-ticks, one WORK part building for one tick: what its builders can still do in their life
+//! minus what its construction sites need. Bases without anything to build lend their builders to
+//! the bases short of work-ticks within `config::LABOR_SHARING_RADIUS`, until the shortfall is
+//! covered. The builders whose bodies fit building best go first, see `creeps::fitness`.
+//!
+//! Lent builders keep their base, only the target of their job changes. They come home once
+//! there is nothing left to build there or their base got construction sites of its own.
+//!
+//! A base with a backlog also spawns more workers of its own, one per
+//! `config::BACKLOG_WORK_TICKS_PER_WORKER` of it, as many as its energy surplus pays for. Once the
+//! sites are built no more are spawned and the extra ones die off.
+use std::{cmp::Ordering, collections::HashMap, convert::TryFrom};
+
+use log::{info, warn};
+use screeps::{
+    find, Creep, HasId, HasStore, ObjectId, Part, ResourceType, Room, RoomName, CREEP_LIFE_TIME,
+};
+
+use crate::{
+    config,
+    creeps::{
+        fitness,
+        jobs::OokCreepJob,
+        races::{self, OokRace, OokRaceKind, RepresentsCreep, RoomBound},
+    },
+    economy::ledger,
+    rooms::{harvest_stats, remotes::linear_distance},
+};
+
+/// Build progress of one WORK part per tick
+const BUILD_POWER: u32 = 5;
+/// Ticks a creep needs to cross a room, roughly
+const ROOM_TRAVEL_TICKS: u32 = 50;
+
+/// A builder, a worker with the job of building a room
+#[derive(Debug)]
+struct Builder {
+    id: ObjectId<Creep>,
+    home: RoomName,
+    target: RoomName,
+    work: u32,
+    ticks_to_live: u32,
+    /// Of its body for building, see `fitness`
+    fitness: f64,
+}
+
+impl Builder {
+    fn of_citizen(citizen: &OokRace) -> Option<Builder> {
+        let worker = match citizen {
+            OokRace::Worker(worker) => worker,
+            _ => return None,
+        };
+        let target = match worker.job {
+            OokCreepJob::BootstrapRoom { target_room } => target_room,
+            _ => return None,
+        };
+        let creep = worker.creep().ok()?;
+        Some(Builder {
+            id: creep.id(),
+            home: worker.room_name_of_base().ok()?,
+            target,
+            work: creep.get_active_bodyparts(Part::Work),
+            ticks_to_live: creep.ticks_to_live().unwrap_or(0),
+            fitness: fitness::fitness(&fitness::active_parts(&creep), &worker.job),
+        })
+    }
+
+    /// What the builder can still build in the room, after walking there
+    fn work_ticks_in(&self, room_name: RoomName) -> u32 {
+        let travel = linear_distance(self.target, room_name) as u32 * ROOM_TRAVEL_TICKS;
+        self.work * self.ticks_to_live.saturating_sub(travel)
+    }
+
+    /// Work-ticks in the room weighed by fitness, the best builders to lend go first
+    fn rank_for(&self, room_name: RoomName) -> f64 {
+        self.work_ticks_in(room_name) as f64 * self.fitness
+    }
+}
+
+#[derive(Debug)]
+struct Balance {
+    /// Work-ticks of the builders working in the room
+    supply: u32,
+    /// Work-ticks the construction sites of the room need
+    demand: u32,
+}
+
+impl Balance {
+    fn shortfall(&self) -> u32 {
+        self.demand.saturating_sub(self.supply)
+    }
+}
+
+/// Work-ticks the construction sites of the room need
+fn backlog(room: &Room) -> u32 {
+    room.find(find::MY_CONSTRUCTION_SITES)
+        .iter()
+        .map(|site| site.progress_total().saturating_sub(site.progress()))
+        .sum::<u32>()
+        / BUILD_POWER
+}
+
+/// Energy per tick the room has left over for more workers, the income less the spawning and
+/// the storage above its floor spread over a creep life
+fn energy_surplus(room: &Room) -> f64 {
+    let income = harvest_stats::room_income(room.name()).unwrap_or(0.);
+    let upkeep = ledger::upkeep(room.name()).unwrap_or(0.);
+    let stored = room
+        .storage()
+        .map(|storage| storage.store_used_capacity(Some(ResourceType::Energy)))
+        .unwrap_or(0)
+        .saturating_sub(config::SPAWN_FORECAST_STORAGE_FLOOR);
+    (income - upkeep).max(0.) + stored as f64 / CREEP_LIFE_TIME as f64
+}
+
+/// Workers to spawn on top of the room's target for its construction backlog
+pub fn backlog_extra_workers(room: &Room) -> u8 {
+    let backlog = backlog(room);
+    if backlog == 0 {
+        return 0;
+    }
+    let for_backlog = (backlog + config::BACKLOG_WORK_TICKS_PER_WORKER - 1)
+        / config::BACKLOG_WORK_TICKS_PER_WORKER;
+    let affordable = (energy_surplus(room) / config::BACKLOG_WORKER_ENERGY_PER_TICK) as u32;
+    for_backlog
+        .min(affordable)
+        .min(config::BACKLOG_MAX_EXTRA_WORKERS as u32) as u8
+}
+
+/// Gives the builder the job of building the room, the cached citizen follows
+fn assign(citizens: &mut HashMap<ObjectId<Creep>, OokRace>, builder: &Builder, room: RoomName) {
+    let creep = match citizens.get(&builder.id).and_then(|c| c.creep().ok()) {
+        Some(creep) => creep,
+        None => return,
+    };
+    let job = OokCreepJob::BootstrapRoom { target_room: room };
+    match races::adopt(&creep, OokRaceKind::Worker, job, builder.home)
+        .and_then(|_| OokRace::try_from(&creep))
+    {
+        Ok(citizen) => {
+            citizens.insert(builder.id, citizen);
+        }
+        Err(err) => warn!("Couldn't send {} to {}: {}", builder.id, room, err),
+    }
+}
+
+/// Lends builders of bases with nothing to build to the bases close by that are short of them and
+/// brings the lent ones home again. `bases` are the rooms owned by me.
+pub fn share_builders(
+    citizens: &mut HashMap<ObjectId<Creep>, OokRace>,
+    bases: &HashMap<RoomName, Room>,
+) {
+    let backlogs: HashMap<RoomName, u32> = bases
+        .iter()
+        .map(|(name, room)| (*name, backlog(room)))
+        .collect();
+    let mut builders: Vec<Builder> = citizens.values().filter_map(Builder::of_citizen).collect();
+
+    for builder in builders.iter_mut().filter(|b| b.target != b.home) {
+        let done = backlogs.get(&builder.target).cloned().unwrap_or(0) == 0;
+        let needed_home = backlogs.get(&builder.home).cloned().unwrap_or(0) > 0;
+        if done || needed_home {
+            info!(
+                "{} comes home from {} to {}",
+                builder.id, builder.target, builder.home
+            );
+            assign(citizens, builder, builder.home);
+            builder.target = builder.home;
+        }
+    }
+
+    let mut balances: HashMap<RoomName, Balance> = backlogs
+        .iter()
+        .map(|(name, demand)| {
+            let balance = Balance {
+                supply: 0,
+                demand: *demand,
+            };
+            (*name, balance)
+        })
+        .collect();
+    for builder in &builders {
+        if let Some(balance) = balances.get_mut(&builder.target) {
+            balance.supply += builder.work_ticks_in(builder.target);
+        }
+    }
+
+    let mut short: Vec<(RoomName, u32)> = balances
+        .iter()
+        .map(|(name, balance)| (*name, balance.shortfall()))
+        .filter(|(_, shortfall)| *shortfall > 0)
+        .collect();
+    short.sort_by_key(|(_, shortfall)| std::cmp::Reverse(*shortfall));
+    for (room, mut shortfall) in short {
+        for (lender, demand) in &backlogs {
+            if *demand > 0 || linear_distance(*lender, room) > config::LABOR_SHARING_RADIUS {
+                continue;
+            }
+            let mut idle: Vec<usize> = (0..builders.len())
+                .filter(|i| builders[*i].home == *lender && builders[*i].target == *lender)
+                .collect();
+            idle.sort_by(|a, b| {
+                builders[*b]
+                    .rank_for(room)
+                    .partial_cmp(&builders[*a].rank_for(room))
+                    .unwrap_or(Ordering::Equal)
+            });
+            let lendable = idle
+                .len()
+                .saturating_sub(config::LABOR_SHARING_KEPT_BUILDERS);
+            for i in idle.into_iter().take(lendable) {
+                if shortfall == 0 {
+                    break;
+                }
+                let work_ticks = builders[i].work_ticks_in(room);
+                if work_ticks == 0 || builders[i].fitness == 0. {
+                    continue;
+                }
+                info!("{} of {} helps building {}", builders[i].id, lender, room);
+                assign(citizens, &builders[i], room);
+                builders[i].target = room;
+                shortfall = shortfall.saturating_sub(work_ticks);
+            }
+        }
+    }
+}
@@ -0,0 +1,183 @@
+//! Places the structures the controller level allows but the room doesn't have yet. There is no
+//! real base layout, structures are put in a checkerboard around the first spawn so everything
+//! stays reachable. Ramparts go where the walls leave a way in, see `defense::bunker`.
+use log::{info, warn};
+use screeps::{
+    find, game, look, HasPosition, Position, Room, Structure, StructureProperties, StructureType,
+    Terrain,
+};
+
+use super::{defense::bunker, sites, spawn_area, terrain};
+
+/// Structures that get placed automatically on level up, in this order
+pub const AUTO_PLACED: [StructureType; 5] = [
+    StructureType::Extension,
+    StructureType::Tower,
+    StructureType::Storage,
+    StructureType::Lab,
+    StructureType::Rampart,
+];
+/// Structures are placed at most this far away from the spawn
+const MAX_RANGE_TO_SPAWN: i32 = 8;
+
+/// How many more structures of `ty` the controller level allows, counting construction sites and
+/// queued ones
+pub fn missing_structures(room: &Room, ty: StructureType) -> u32 {
+    let level = room.controller().map(|c| c.level()).unwrap_or(0);
+    let allowed = ty.controller_structures(level);
+    let built = room
+        .find(find::STRUCTURES)
+        .into_iter()
+        .filter(|s| s.structure_type() == ty)
+        .count() as u32;
+    let planned = room
+        .find(find::MY_CONSTRUCTION_SITES)
+        .into_iter()
+        .filter(|s| s.structure_type() == ty)
+        .count() as u32;
+    let queued = sites::queued(room.name(), ty);
+    allowed.saturating_sub(built + planned + queued)
+}
+
+/// Queues up to `amount` construction sites of `ty` around the spawn, returns how many got queued
+pub fn place_near_spawn(room: &Room, ty: StructureType, amount: u32) -> u32 {
+    if amount == 0 {
+        return 0;
+    }
+    let spawn_pos = match room.find(find::MY_SPAWNS).first() {
+        Some(spawn) => spawn.pos(),
+        None => return 0,
+    };
+    // Fresh creeps need somewhere to go
+    let spawn_exits = spawn_area::room_spawn_exits(room);
+    // Nothing gets built into the narrow passages of the room
+    let terrain = terrain::analysis(room.name());
+    let mut placed = 0;
+    for range in 2..=MAX_RANGE_TO_SPAWN {
+        for pos in neighbours(&spawn_pos, range) {
+            if placed == amount {
+                return placed;
+            }
+            let dx = pos.x() as i32 - spawn_pos.x() as i32;
+            let dy = pos.y() as i32 - spawn_pos.y() as i32;
+            if (dx + dy) % 2 != 0 || !is_free_tile(room, &pos) {
+                continue;
+            }
+            if spawn_exits.iter().any(|exit| exit.is_near_to(&pos)) {
+                continue;
+            }
+            if terrain
+                .as_ref()
+                .map(|t| t.is_chokepoint(&pos))
+                .unwrap_or(false)
+            {
+                continue;
+            }
+            sites::request(pos, ty);
+            placed += 1;
+        }
+    }
+    placed
+}
+
+/// Places the missing structures of `ty`, see `deferred::Job::PlaceStructures`
+pub fn place_missing(room: &Room, ty: StructureType) {
+    let missing = missing_structures(room, ty);
+    if missing == 0 {
+        return;
+    }
+    if ty == StructureType::Rampart {
+        place_ramparts(room);
+        return;
+    }
+    let placed = place_near_spawn(room, ty, missing);
+    if placed < missing {
+        warn!(
+            "Room {}: queued only {} of {} missing {:?}",
+            room.name(),
+            placed,
+            missing,
+            ty
+        );
+    } else {
+        info!("Room {}: queued {} {:?}", room.name(), placed, ty);
+    }
+}
+
+/// Queues ramparts on the tiles the walls leave open
+fn place_ramparts(room: &Room) {
+    let check = bunker::check(room);
+    if !check.closable {
+        warn!("Room {}: too close to the exits to close in", room.name());
+        return;
+    }
+    let mut placed = 0;
+    for pos in &check.open_tiles {
+        if !sites::is_queued(pos) {
+            sites::request(*pos, StructureType::Rampart);
+            placed += 1;
+        }
+    }
+    if placed > 0 {
+        info!(
+            "Room {}: queued {} of {} ramparts to close the walls",
+            room.name(),
+            placed,
+            check.open_tiles.len()
+        );
+    }
+}
+
+/// Structures in the way: leftovers of a previous owner and own structures standing where a site
+/// of another type is queued
+pub fn misplaced_structures(room: &Room) -> Vec<Structure> {
+    let mut misplaced: Vec<Structure> = room
+        .find(find::HOSTILE_STRUCTURES)
+        .into_iter()
+        .map(|s| s.as_structure())
+        .collect();
+    misplaced.extend(
+        room.find(find::MY_STRUCTURES)
+            .into_iter()
+            .map(|s| s.as_structure())
+            .filter(|s| is_in_the_way(s.structure_type(), &s.pos())),
+    );
+    misplaced
+}
+
+/// If an own structure of `ty` at `pos` blocks a planned one. Extensions and towers keep the base
+/// running and are never in the way, wherever they stand.
+fn is_in_the_way(ty: StructureType, pos: &Position) -> bool {
+    match ty {
+        StructureType::Extension | StructureType::Tower | StructureType::Spawn => false,
+        _ => sites::queued_at(pos).map_or(false, |planned| planned != ty),
+    }
+}
+
+pub fn is_free_tile(room: &Room, pos: &Position) -> bool {
+    if game::map::get_room_terrain(room.name()).get(pos.x(), pos.y()) == Terrain::Wall {
+        return false;
+    }
+    room.look_for_at(look::STRUCTURES, pos).is_empty()
+        && room.look_for_at(look::CONSTRUCTION_SITES, pos).is_empty()
+        && !sites::is_queued(pos)
+}
+
+/// Positions exactly `range` away from `center`, inside the room borders
+pub fn neighbours(center: &Position, range: i32) -> Vec<Position> {
+    let mut positions = vec![];
+    for dx in -range..=range {
+        for dy in -range..=range {
+            if dx.abs() != range && dy.abs() != range {
+                continue;
+            }
+            let x = center.x() as i32 + dx;
+            let y = center.y() as i32 + dy;
+            if x < 1 || x > 48 || y < 1 || y > 48 {
+                continue;
+            }
+            positions.push(Position::new(x as u32, y as u32, center.room_name()));
+        }
+    }
+    positions
+}
@@ -0,0 +1,109 @@
+//! Rebuilding what got destroyed. `structures::refresh` notices when a structure of
+//! `config::REBUILT_STRUCTURES` is gone, by its destroy event or by its id missing in the next
+//! scan, and a construction site is queued right where it stood. It is placed before the other
+//! queued sites, see `sites`, and built in the order of `config::BUILD_PRIORITY` like any other,
+//! but for `config::REBUILD_RUSH_TICKS` it doesn't wait for the started sites, see `BuildPolicy`.
+//! Structures dismantled on purpose stay gone, the site queued where they stood takes the tile.
+//!
+//! What depends on the structures doesn't wait for its periodic update: the cost matrix of the
+//! room is dropped, a base refreshes its suppliers and resource providers and the legacy main room
+//! its farm positions, which drop into the source containers.
+use std::{collections::HashMap, sync::Mutex};
+
+use lazy_static::lazy_static;
+use log::warn;
+use screeps::{game, OwnedStructureProperties, Position, Room, RoomName, StructureType};
+
+use crate::{
+    config,
+    rooms::{
+        cost_matrix, farm_positions, room_state::RoomState, sites, structures::Destroyed,
+        MyRoom,
+    },
+    state::BWState,
+};
+
+lazy_static! {
+    /// Rebuild sites and the tick they were queued at
+    static ref RUSHED: Mutex<HashMap<Position, u32>> = Mutex::new(HashMap::new());
+}
+
+/// If the site at `pos` is a fresh rebuild, it doesn't wait for the started sites
+pub fn is_rushed(pos: &Position) -> bool {
+    RUSHED
+        .lock()
+        .unwrap()
+        .get(pos)
+        .map(|placed_at| placed_at + config::REBUILD_RUSH_TICKS > game::time())
+        .unwrap_or(false)
+}
+
+fn place_rebuild(room: &Room, gone: &Destroyed) {
+    if sites::is_queued(&gone.pos) {
+        return;
+    }
+    warn!(
+        "Room {}: {:?} at {} destroyed, rebuilding it",
+        room.name(),
+        gone.structure_type,
+        gone.pos
+    );
+    RUSHED.lock().unwrap().insert(gone.pos, game::time());
+    sites::request(gone.pos, gone.structure_type);
+}
+
+fn update_dependents(state: &mut BWState, room: &Room, gone: &[Destroyed]) {
+    let types: Vec<StructureType> = gone.iter().map(|g| g.structure_type).collect();
+    if let Some(RoomState::Base(base)) = state.room_states.get_mut(&room.name()) {
+        if let Err(err) = base.handle_destroyed(room, &types) {
+            warn!(
+                "Room {}: failed handling destroyed structures: {}",
+                room.name(),
+                err
+            );
+        }
+    }
+    let container_gone = types.contains(&StructureType::Container);
+    if let (true, Some(my_room)) = (container_gone, MyRoom::by_room_name(room.name())) {
+        match (
+            farm_positions(room.name()),
+            state.room_settings.get_mut(&my_room),
+        ) {
+            (Ok(positions), Some(settings)) => settings.farm_positions = positions,
+            (Err(err), _) => warn!(
+                "Room {}: failed updating farm positions: {}",
+                room.name(),
+                err
+            ),
+            _ => {}
+        }
+    }
+}
+
+/// Places the rebuilds of the destroyed structures in my rooms and updates what depends on them,
+/// call it right after `structures::refresh`
+pub fn react(state: &mut BWState, destroyed: Vec<Destroyed>) {
+    let mut by_room: HashMap<RoomName, Vec<Destroyed>> = HashMap::new();
+    for gone in destroyed {
+        by_room.entry(gone.pos.room_name()).or_default().push(gone);
+    }
+    let time = game::time();
+    RUSHED
+        .lock()
+        .unwrap()
+        .retain(|_, placed_at| *placed_at + config::REBUILD_RUSH_TICKS > time);
+    for (room_name, gone) in by_room {
+        cost_matrix::invalidate(room_name);
+        let room = match game::rooms::get(room_name) {
+            Some(room) => room,
+            None => continue,
+        };
+        if !room.controller().map(|c| c.my()).unwrap_or(false) {
+            continue;
+        }
+        for structure in &gone {
+            place_rebuild(&room, structure);
+        }
+        update_dependents(state, &room, &gone);
+    }
+}
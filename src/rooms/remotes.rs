@@ -0,0 +1,465 @@
+//! Remote rooms, the rooms reserved by me. Each belongs to the closest base, which protects it.
+//! Hostiles seen in them, or attacks showing up in their event log, mark them as threatened for a
+//! while, the room may turn invisible as soon as the miners are dead.
+//!
+//! The rooms in `config::RESERVED_REMOTES` get reserved by the closest base. Their reservation
+//! ticks are kept in the intel, so the base spawns the next reserver early enough that the
+//! reservation never drops below `config::RESERVATION_FLOOR_TICKS`.
+//!
+//! The haul routes from the source containers of a remote to the storage of its base are measured
+//! as well, the haulers need enough CARRY to take all the sources make during a round trip. Once a
+//! remote pays off, the routes get paved, so the haulers need less MOVE.
+use std::{cmp, collections::HashMap, sync::Mutex};
+
+use lazy_static::lazy_static;
+use log::{debug, info};
+use screeps::{
+    constants::{CARRY_CAPACITY, ENERGY_REGEN_TIME, SOURCE_ENERGY_CAPACITY},
+    find, game, look, EventType, HasId, HasPosition, HasStore, OwnedStructureProperties, Part,
+    Position, ResourceType, Room, RoomName, Structure, StructureContainer, StructureType, Terrain,
+};
+
+use crate::{
+    config,
+    constants::MY_USERNAME,
+    deferred,
+    game::{owned_rooms, OwnedBy},
+    rooms::{
+        cost_matrix,
+        defense::threats::{self, Retaliation},
+        events, harvest_stats, sites, structures, visibility,
+    },
+};
+
+/// Remotes further away than this from every base are not protected
+const MAX_REMOTE_DISTANCE: i32 = 2;
+/// A remote stays threatened this long after the last sign of hostiles
+const THREAT_MEMORY_TICKS: u32 = 300;
+/// Remotes not seen for this long are forgotten, the reservation is probably gone
+const FORGET_TICKS: u32 = 5000;
+/// Harassers one base keeps at most, invaders rarely come in bigger groups
+const MAX_HARASSERS: u32 = 2;
+/// Ticks a reserver needs per room of distance to get to the controller
+const RESERVER_TICKS_PER_ROOM: u32 = 50;
+/// Ticks spawning a reserver takes, it has four body parts at most
+const RESERVER_SPAWN_TICKS: u32 = 4 * 3;
+
+#[derive(Clone, Debug)]
+struct RemoteIntel {
+    base: RoomName,
+    last_seen: u32,
+    last_hostile_seen: Option<u32>,
+    /// Ticks left on my reservation when the room was last seen
+    reservation_ticks: u32,
+    haul_routes: Option<HaulRoutes>,
+}
+
+/// The measured ways from the source containers of a remote to the storage of the base
+#[derive(Clone, Debug)]
+struct HaulRoutes {
+    /// Ticks a hauler needs there and back, per source container
+    round_trips: Vec<u32>,
+    /// The tiles from each source container to the storage
+    paths: Vec<Vec<Position>>,
+    /// Roads on every tile of the paths
+    paved: bool,
+    /// Containers and roads in the remote and the base when measured, the routes change with them
+    containers: usize,
+    roads: usize,
+}
+
+impl RemoteIntel {
+    /// Ticks left on my reservation now, assuming nobody kept it up since the room was last seen
+    fn estimated_reservation_ticks(&self, time: u32) -> u32 {
+        self.reservation_ticks
+            .saturating_sub(time.saturating_sub(self.last_seen))
+    }
+}
+
+lazy_static! {
+    static ref REMOTES: Mutex<HashMap<RoomName, RemoteIntel>> = Mutex::new(HashMap::new());
+}
+
+/// Distance between the room centers, in rooms
+pub fn linear_distance(a: RoomName, b: RoomName) -> i32 {
+    let (x_diff, y_diff) = a - b;
+    ((x_diff * x_diff + y_diff * y_diff) as f32).sqrt().round() as i32
+}
+
+fn is_reserved_by_me(room: &Room) -> bool {
+    room.controller()
+        .and_then(|c| c.reservation())
+        .map(|r| r.username == MY_USERNAME)
+        .unwrap_or(false)
+}
+
+fn my_reservation_ticks(room: &Room) -> u32 {
+    room.controller()
+        .and_then(|c| c.reservation())
+        .filter(|r| r.username == MY_USERNAME)
+        .map(|r| r.ticks_to_end)
+        .unwrap_or(0)
+}
+
+fn is_wanted_remote(room_name: RoomName) -> bool {
+    config::RESERVED_REMOTES
+        .iter()
+        .any(|name| RoomName::new(name).map(|n| n == room_name).unwrap_or(false))
+}
+
+/// The closest base in reach of the remote
+fn base_of(bases: &[RoomName], room_name: RoomName) -> Option<RoomName> {
+    bases
+        .iter()
+        .map(|base| (*base, linear_distance(*base, room_name)))
+        .filter(|(_, distance)| *distance <= MAX_REMOTE_DISTANCE)
+        .min_by_key(|(_, distance)| *distance)
+        .map(|(base, _)| base)
+}
+
+/// Hostiles that can hurt miners are in the room, or something attacked in it last tick. Players
+/// `threats` ignores don't count.
+fn shows_hostiles(room: &Room) -> bool {
+    let armed_hostiles = room.find(find::HOSTILE_CREEPS).iter().any(|c| {
+        (c.get_active_bodyparts(Part::Attack) > 0 || c.get_active_bodyparts(Part::RangedAttack) > 0)
+            && !threats::ignores(c)
+    });
+    if armed_hostiles {
+        return true;
+    }
+    let my_creeps: Vec<String> = room
+        .find(find::MY_CREEPS)
+        .iter()
+        .map(|c| c.id().to_string())
+        .collect();
+    events::of(room).into_iter().any(|event| match event.event {
+        EventType::Attack(_) => {
+            !my_creeps.contains(&event.object_id)
+                && threats::owner_of(&event.object_id)
+                    .map(|player| threats::retaliation(&player) != Retaliation::Ignore)
+                    .unwrap_or(true)
+        }
+        _ => false,
+    })
+}
+
+/// Containers next to the sources of the room
+fn source_containers(room: &Room) -> Vec<StructureContainer> {
+    let sources = room.find(find::SOURCES);
+    structures::of_type::<StructureContainer>(room.name(), StructureType::Container)
+        .into_iter()
+        .filter(|c| sources.iter().any(|s| c.pos().is_near_to(&s.pos())))
+        .collect()
+}
+
+fn road_count(remote: RoomName, base: RoomName) -> usize {
+    structures::ids(remote, StructureType::Road).len()
+        + structures::ids(base, StructureType::Road).len()
+}
+
+/// Tiles in rooms out of sight count as without road
+fn has_road(pos: &Position) -> bool {
+    game::rooms::get(pos.room_name())
+        .map(|room| {
+            room.look_for_at(look::STRUCTURES, pos)
+                .iter()
+                .any(|s| matches!(s, Structure::Road(_)))
+        })
+        .unwrap_or(false)
+}
+
+/// CARRY parts per MOVE of the haulers, they only get away with less MOVE on roads
+fn carry_per_move(paved: bool) -> u32 {
+    if paved {
+        2
+    } else {
+        1
+    }
+}
+
+/// Ticks a loaded hauler needs for the step onto the tile
+///
+/// Every loaded CARRY makes fatigue by the terrain, 1 on roads, 2 on plains and 10 on swamps,
+/// each MOVE takes 2 of it per tick.
+fn loaded_step_ticks(pos: &Position, carry_per_move: u32) -> u32 {
+    let fatigue = if has_road(pos) {
+        1
+    } else {
+        match game::map::get_room_terrain(pos.room_name()).get(pos.x(), pos.y()) {
+            Terrain::Swamp => 10,
+            _ => 2,
+        }
+    };
+    cmp::max(carry_per_move * fatigue / 2, 1)
+}
+
+/// Walks the paths from the source containers to the storage of the base
+///
+/// Empty haulers make no fatigue and walk a tile each tick, the way back loaded depends on the
+/// roads and the terrain.
+fn measure_haul_routes(room: &Room, base: RoomName) -> Option<HaulRoutes> {
+    let storage = game::rooms::get(base)?.storage()?;
+    let paths: Vec<Vec<Position>> = source_containers(room)
+        .iter()
+        .map(|container| cost_matrix::search(&container.pos(), &storage.pos(), 1, true).path())
+        .collect();
+    let paved = !paths.is_empty() && paths.iter().flatten().all(has_road);
+    let round_trips: Vec<u32> = paths
+        .iter()
+        .map(|path| {
+            path.len() as u32
+                + path
+                    .iter()
+                    .map(|pos| loaded_step_ticks(pos, carry_per_move(paved)))
+                    .sum::<u32>()
+        })
+        .collect();
+    debug!(
+        "Measured haul routes of {} to {}: {:?}, paved: {}",
+        room.name(),
+        base,
+        round_trips,
+        paved
+    );
+    Some(HaulRoutes {
+        round_trips,
+        containers: paths.len(),
+        roads: road_count(room.name(), base),
+        paths,
+        paved,
+    })
+}
+
+/// Measures the haul routes again if containers or roads were built or lost since
+fn update_haul_routes(room: &Room, intel: &mut RemoteIntel) {
+    let outdated = match &intel.haul_routes {
+        Some(routes) => {
+            routes.roads != road_count(room.name(), intel.base)
+                || routes.containers != source_containers(room).len()
+        }
+        None => true,
+    };
+    if outdated {
+        intel.haul_routes = measure_haul_routes(room, intel.base);
+    }
+}
+
+/// Updates the intel of all visible remotes, call it each tick
+pub fn track_remotes() {
+    let time = game::time();
+    let bases: Vec<RoomName> = owned_rooms(OwnedBy::Me).keys().cloned().collect();
+    let mut remotes = REMOTES.lock().unwrap();
+    for room in game::rooms::values() {
+        if room.controller().map(|c| c.my()).unwrap_or(false)
+            || !(is_reserved_by_me(&room) || is_wanted_remote(room.name()))
+        {
+            continue;
+        }
+        let base = match base_of(&bases, room.name()) {
+            Some(base) => base,
+            None => continue,
+        };
+        let intel = remotes.entry(room.name()).or_insert_with(|| RemoteIntel {
+            base,
+            last_seen: time,
+            last_hostile_seen: None,
+            reservation_ticks: 0,
+            haul_routes: None,
+        });
+        if intel.base != base {
+            intel.haul_routes = None;
+        }
+        intel.base = base;
+        intel.last_seen = time;
+        intel.reservation_ticks = my_reservation_ticks(&room);
+        update_haul_routes(&room, intel);
+        if shows_hostiles(&room) {
+            if intel.last_hostile_seen.is_none() {
+                info!("Hostiles in remote {} of {}", room.name(), base);
+            }
+            intel.last_hostile_seen = Some(time);
+        }
+    }
+    remotes.retain(|name, intel| intel.last_seen + FORGET_TICKS >= time || is_wanted_remote(*name));
+}
+
+/// Ticks from spawning a reserver in `base` until it reserves in `remote`
+fn reserver_lead_ticks(base: RoomName, remote: RoomName) -> u32 {
+    RESERVER_SPAWN_TICKS
+        + cmp::max(linear_distance(base, remote), 1) as u32 * RESERVER_TICKS_PER_ROOM
+}
+
+/// Configured remotes of the base whose reservation drops to the floor before a reserver spawned
+/// now could get there, together with that lead time
+///
+/// Remotes never seen count as unreserved.
+pub fn reservations_due(base: RoomName) -> Vec<(RoomName, u32)> {
+    let time = game::time();
+    let bases: Vec<RoomName> = owned_rooms(OwnedBy::Me).keys().cloned().collect();
+    let remotes = REMOTES.lock().unwrap();
+    let mut due: Vec<(RoomName, u32)> = config::RESERVED_REMOTES
+        .iter()
+        .filter_map(|name| RoomName::new(name).ok())
+        .filter(|room_name| base_of(&bases, *room_name) == Some(base))
+        .filter_map(|room_name| {
+            let lead = reserver_lead_ticks(base, room_name);
+            let ticks = remotes
+                .get(&room_name)
+                .map(|intel| intel.estimated_reservation_ticks(time))
+                .unwrap_or(0);
+            if ticks <= config::RESERVATION_FLOOR_TICKS + lead {
+                Some((room_name, lead))
+            } else {
+                None
+            }
+        })
+        .collect();
+    due.sort_by_key(|(name, _)| name.to_string());
+    due
+}
+
+/// CARRY parts the haulers of the remote need in total, to keep up with its sources
+///
+/// A reserved source makes `SOURCE_ENERGY_CAPACITY` per regeneration, all of it made during a
+/// round trip has to fit. `None` while the routes are not measured, the remote or its base lacking
+/// containers or storage.
+pub fn hauler_carry_parts(remote: RoomName) -> Option<u32> {
+    let remotes = REMOTES.lock().unwrap();
+    let routes = remotes.get(&remote)?.haul_routes.as_ref()?;
+    if routes.round_trips.is_empty() {
+        return None;
+    }
+    Some(
+        routes
+            .round_trips
+            .iter()
+            .map(|ticks| {
+                let energy = SOURCE_ENERGY_CAPACITY * ticks / ENERGY_REGEN_TIME;
+                (energy + CARRY_CAPACITY - 1) / CARRY_CAPACITY
+            })
+            .sum(),
+    )
+}
+
+/// CARRY parts the haulers of each remote of the base need, for the remotes mined well enough to
+/// pay for them
+pub fn hauling_due(base: RoomName) -> Vec<(RoomName, u32)> {
+    remotes_of(base)
+        .into_iter()
+        .filter(|remote| {
+            harvest_stats::room_utilization(*remote)
+                .map(|u| u >= config::REMOTE_HAULING_MIN_UTILIZATION)
+                .unwrap_or(false)
+        })
+        .filter_map(|remote| hauler_carry_parts(remote).map(|carry| (remote, carry)))
+        .collect()
+}
+
+/// The source container of the room with the most energy in it
+pub fn fullest_source_container(room: &Room) -> Option<StructureContainer> {
+    source_containers(room)
+        .into_iter()
+        .max_by_key(|c| c.store_used_capacity(Some(ResourceType::Energy)))
+}
+
+/// If roads lead from every source container of the remote to the storage of the base
+pub fn is_paved(remote: RoomName) -> bool {
+    REMOTES
+        .lock()
+        .unwrap()
+        .get(&remote)
+        .and_then(|intel| intel.haul_routes.as_ref())
+        .map(|routes| routes.paved)
+        .unwrap_or(false)
+}
+
+/// Queues the next road sites along the haul routes of the remote, once its sources are harvested
+/// well enough
+///
+/// At most `config::REMOTE_ROAD_SITES_PER_STEP` per call, tiles in rooms out of sight wait.
+pub fn place_roads(remote: RoomName) {
+    let paths = match REMOTES
+        .lock()
+        .unwrap()
+        .get(&remote)
+        .and_then(|intel| intel.haul_routes.as_ref())
+    {
+        Some(routes) if !routes.paved => routes.paths.clone(),
+        _ => return,
+    };
+    let pays_off = harvest_stats::room_utilization(remote)
+        .map(|u| u >= config::REMOTE_ROADS_MIN_UTILIZATION)
+        .unwrap_or(false);
+    if !pays_off {
+        return;
+    }
+    let mut placed = 0;
+    for pos in paths.iter().flatten() {
+        if placed >= config::REMOTE_ROAD_SITES_PER_STEP {
+            break;
+        }
+        let room = match game::rooms::get(pos.room_name()) {
+            Some(room) => room,
+            None => {
+                // Paved once the room is in sight
+                visibility::request(
+                    pos.room_name(),
+                    Some(deferred::Job::RemoteRoads {
+                        remotes: vec![remote],
+                    }),
+                );
+                continue;
+            }
+        };
+        if has_road(pos)
+            || !room.look_for_at(look::CONSTRUCTION_SITES, pos).is_empty()
+            || sites::is_queued(pos)
+        {
+            continue;
+        }
+        sites::request(*pos, StructureType::Road);
+        placed += 1;
+    }
+    if placed > 0 {
+        info!("Queued {} road sites for remote {}", placed, remote);
+    }
+}
+
+/// All remotes the base protects
+pub fn remotes_of(base: RoomName) -> Vec<RoomName> {
+    let mut remotes: Vec<RoomName> = REMOTES
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(_, intel)| intel.base == base)
+        .map(|(name, _)| *name)
+        .collect();
+    remotes.sort_by_key(|name| name.to_string());
+    remotes
+}
+
+/// Harassers the base should keep patrolling its remotes and the remotes it retaliates on
+pub fn wanted_harassers(base: RoomName) -> u32 {
+    let targets = threatened_remotes_of(base).len() + threats::harass_targets(base).len();
+    cmp::min(targets as u32, MAX_HARASSERS)
+}
+
+/// Remotes of the base with recent signs of hostiles
+pub fn threatened_remotes_of(base: RoomName) -> Vec<RoomName> {
+    let time = game::time();
+    let mut remotes: Vec<RoomName> = REMOTES
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(_, intel)| intel.base == base)
+        .filter(|(_, intel)| {
+            intel
+                .last_hostile_seen
+                .map(|seen| seen + THREAT_MEMORY_TICKS >= time)
+                .unwrap_or(false)
+        })
+        .map(|(name, _)| *name)
+        .collect();
+    remotes.sort_by_key(|name| name.to_string());
+    remotes
+}
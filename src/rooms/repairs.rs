@@ -0,0 +1,101 @@
+//! Who repairs what in this tick. Towers, builders and creeps passing by all repair, and without
+//! knowing of each other they'd put their energy into the same structure and overshoot its hits.
+//! Every repairer claims its target here first, a claimed target is off the table for all others
+//! until the next tick.
+use std::{collections::HashMap, sync::Mutex};
+
+use lazy_static::lazy_static;
+use screeps::{Attackable, HasId, ObjectId, Room, Structure};
+
+use crate::{
+    creeps::{prio_repair_targets, RepairTarget},
+    economy,
+};
+
+/// Hits a WORK part repairs per tick
+pub const CREEP_REPAIR_POWER: u32 = 100;
+const TOWER_REPAIR_POWER: u32 = 800;
+const TOWER_OPTIMAL_RANGE: u32 = 5;
+const TOWER_FALLOFF_RANGE: u32 = 20;
+/// Share of the power a tower loses at `TOWER_FALLOFF_RANGE`
+const TOWER_FALLOFF: f64 = 0.75;
+
+lazy_static! {
+    /// Hits each claimed target gets repaired by in this tick
+    static ref CLAIMS: Mutex<HashMap<ObjectId<Structure>, u32>> = Mutex::new(HashMap::new());
+}
+
+/// Hits a tower repairs at `range`
+pub fn tower_repair_power(range: u32) -> u32 {
+    let range = range.max(TOWER_OPTIMAL_RANGE).min(TOWER_FALLOFF_RANGE);
+    let falloff = TOWER_FALLOFF * (range - TOWER_OPTIMAL_RANGE) as f64
+        / (TOWER_FALLOFF_RANGE - TOWER_OPTIMAL_RANGE) as f64;
+    (TOWER_REPAIR_POWER as f64 * (1. - falloff)).round() as u32
+}
+
+/// Hits the structure needs until it is repaired, walls and ramparts only go up to the wall target
+pub fn hits_missing(structure: &Structure, wall_target: f64) -> u32 {
+    let attackable = match structure.as_attackable() {
+        Some(attackable) => attackable,
+        None => return 0,
+    };
+    let wanted = match structure {
+        Structure::Wall(_) | Structure::Rampart(_) => {
+            (wall_target as u32).min(attackable.hits_max())
+        }
+        _ => attackable.hits_max(),
+    };
+    wanted.saturating_sub(attackable.hits())
+}
+
+pub fn is_claimed(target: ObjectId<Structure>) -> bool {
+    CLAIMS.lock().unwrap().contains_key(&target)
+}
+
+/// Claims `target` for a repair of `power` hits, false if someone else repairs it already
+pub fn claim(target: ObjectId<Structure>, power: u32) -> bool {
+    let mut claims = CLAIMS.lock().unwrap();
+    if claims.contains_key(&target) {
+        return false;
+    }
+    claims.insert(target, power);
+    true
+}
+
+/// Picks and claims the most urgent repair target of the room nobody claimed yet
+///
+/// A repairer of `power` gets a target missing at least that many hits, so none of it is wasted.
+/// If every target misses less it gets the most urgent one anyway. Targets `allowed` rejects are
+/// left to the others.
+pub fn claim_next(
+    room: &Room,
+    power: impl Fn(&Structure) -> u32,
+    allowed: impl Fn(&Structure) -> bool,
+    important_only: bool,
+) -> Option<Structure> {
+    let wall_target = economy::wall_target_hits(room);
+    let candidates: Vec<(Structure, u32)> = prio_repair_targets(room)
+        .into_iter()
+        .filter_map(|target| match target {
+            RepairTarget::Important { target } => Some(target),
+            RepairTarget::Arbeitsbeschaffung { target } if !important_only => Some(target),
+            RepairTarget::Arbeitsbeschaffung { .. } => None,
+        })
+        .filter(|target| !is_claimed(target.id()) && allowed(target))
+        .map(|target| {
+            let power = power(&target);
+            (target, power)
+        })
+        .collect();
+    let (target, power) = candidates
+        .iter()
+        .find(|(target, power)| hits_missing(target, wall_target) >= *power)
+        .or_else(|| candidates.first())?;
+    claim(target.id(), (*power).min(hits_missing(target, wall_target)));
+    Some(target.clone())
+}
+
+/// Forgets all claims, called when the tick starts
+pub fn clear() {
+    CLAIMS.lock().unwrap().clear();
+}
@@ -1,12 +1,15 @@
-use std::{cmp, error::Error};
+use std::{cmp, collections::HashMap};
 
 use log::warn;
 use screeps::{
-    find, game::get_object_typed, look, HasId, HasPosition, HasStore, ObjectId, Position,
-    RawObjectId, ResourceType, ReturnCode, Room, SharedCreepProperties, StructureProperties,
+    find, look, HasId, HasPosition, HasStore, ObjectId, Position,
+    RawObjectId, ResourceType, ReturnCode, Room, SharedCreepProperties, Structure,
+    StructureProperties, StructureType,
 };
 
-use super::room_ext::RoomExt;
+use crate::state::get_object_cached;
+
+use super::{room_ext::RoomExt, structures, withdraw_permissions};
 
 #[derive(thiserror::Error, Debug)]
 pub enum ResourceProviderError {
@@ -24,17 +27,85 @@ trait StructureWithStore: HasPosition + HasId + HasStore {}
 
 pub trait ResourceData {
     fn pos(&self) -> anyhow::Result<Position>;
-    fn provides(&self, resource_type: &screeps::ResourceType) -> Result<u32, Box<dyn Error>>;
-    /// Checks if the creep can use this resource
-    fn creep_can_use(&self, creep: &screeps::Creep) -> Result<bool, Box<dyn Error>>;
+    fn provides(&self, resource_type: &screeps::ResourceType) -> anyhow::Result<u32>;
+    /// Checks if the creep can take the resource from this, see `withdraw_permissions`
+    fn creep_can_use(
+        &self,
+        creep: &screeps::Creep,
+        resource_type: ResourceType,
+    ) -> anyhow::Result<bool>;
     fn creep_get_resource(
         &self,
         creep: &screeps::Creep,
         resource_type: ResourceType,
         ideal_amount: u32,
     ) -> anyhow::Result<TakeResourceResult>;
-    // TODO
-    // fn reserve(&mut self, type: ResourceType, amount: u32) -> Result<bool, Box<dyn Error>>;
+}
+
+/// Amounts creeps already plan to take from a provider in this tick
+///
+/// Gets cleared on every tick, so it only prevents creeps deciding in the same tick from
+/// rushing to the same (small) pile.
+#[derive(Clone, Debug, Default)]
+pub struct ProviderReservations {
+    reserved: HashMap<(String, ResourceType), u32>,
+}
+
+impl ProviderReservations {
+    pub fn reserve(
+        &mut self,
+        provider: &ResourceProvider,
+        resource_type: ResourceType,
+        amount: u32,
+    ) {
+        *self
+            .reserved
+            .entry((provider.ident(), resource_type))
+            .or_insert(0) += amount;
+    }
+
+    pub fn reserved(&self, provider: &ResourceProvider, resource_type: ResourceType) -> u32 {
+        self.reserved
+            .get(&(provider.ident(), resource_type))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Same as `reserve`, for objects taken from without being a provider, like the fetch
+    /// targets of `fetch_for_consumer`
+    pub fn reserve_object(
+        &mut self,
+        obj_id: RawObjectId,
+        resource_type: ResourceType,
+        amount: u32,
+    ) {
+        *self
+            .reserved
+            .entry((format!("{}", obj_id), resource_type))
+            .or_insert(0) += amount;
+    }
+
+    pub fn reserved_object(&self, obj_id: RawObjectId, resource_type: ResourceType) -> u32 {
+        self.reserved
+            .get(&(format!("{}", obj_id), resource_type))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// What the provider has left after all reservations of this tick
+    pub fn available(
+        &self,
+        provider: &ResourceProvider,
+        resource_type: ResourceType,
+    ) -> anyhow::Result<u32> {
+        Ok(provider
+            .provides(&resource_type)?
+            .saturating_sub(self.reserved(provider, resource_type)))
+    }
+
+    pub fn clear(&mut self) {
+        self.reserved.clear();
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -67,6 +138,10 @@ pub enum ResourceProvider {
     LongTermStorage { room_object_data: StructureData },
     /// Overflow in Terminal
     TerminalOverflow { room_object_data: StructureData },
+    /// Leftovers of a dead creep, gone soon
+    Tombstone { tombstone_data: TombstoneData },
+    /// Spawns and extensions, the energy the next creep gets spawned with
+    SpawnSupply { spawn_supply_data: SpawnSupplyData },
     /// Some source somewhere
     Unknown { room_object_data: RoomObjectData },
 }
@@ -80,10 +155,36 @@ impl ResourceProvider {
             BufferControllerUpgrade { room_object_data } => room_object_data.obj_id.into(),
             LongTermStorage { room_object_data } => room_object_data.obj_id.into(),
             TerminalOverflow { room_object_data } => room_object_data.obj_id.into(),
+            Tombstone { tombstone_data } => tombstone_data.obj_id.into(),
+            SpawnSupply { spawn_supply_data } => spawn_supply_data.structure_data.obj_id.into(),
             Unknown { room_object_data } => room_object_data.obj_id(),
         };
         format!("{}", obj_id)
     }
+
+    /// Spawns and extensions, only construction may take from them
+    pub fn is_spawn_supply(&self) -> bool {
+        matches!(self, ResourceProvider::SpawnSupply { .. })
+    }
+
+    /// The structure the resource gets taken from, if it is one
+    fn structure(&self) -> anyhow::Result<Option<Structure>> {
+        use ResourceProvider::*;
+        let obj_id = match self {
+            BufferControllerUpgrade { room_object_data }
+            | LongTermStorage { room_object_data }
+            | TerminalOverflow { room_object_data } => room_object_data.obj_id,
+            SpawnSupply { spawn_supply_data } => spawn_supply_data.structure_data.obj_id,
+            SourceDump {
+                room_object_data: RoomObjectData::StorageStructure { obj_id },
+            }
+            | Unknown {
+                room_object_data: RoomObjectData::StorageStructure { obj_id },
+            } => *obj_id,
+            _ => return Ok(None),
+        };
+        Ok(get_object_cached(obj_id)?)
+    }
 }
 
 impl ResourceData for ResourceProvider {
@@ -95,11 +196,13 @@ impl ResourceData for ResourceProvider {
             BufferControllerUpgrade { room_object_data } => room_object_data.pos(),
             LongTermStorage { room_object_data } => room_object_data.pos(),
             TerminalOverflow { room_object_data } => room_object_data.pos(),
+            Tombstone { tombstone_data } => tombstone_data.pos(),
+            SpawnSupply { spawn_supply_data } => spawn_supply_data.pos(),
             Unknown { room_object_data } => room_object_data.pos(),
         }
     }
 
-    fn provides(&self, resource_type: &screeps::ResourceType) -> Result<u32, Box<dyn Error>> {
+    fn provides(&self, resource_type: &screeps::ResourceType) -> anyhow::Result<u32> {
         use ResourceProvider::*;
         match self {
             EnergyFarm { resource_farm_data } => resource_farm_data.provides(resource_type),
@@ -109,20 +212,50 @@ impl ResourceData for ResourceProvider {
             }
             LongTermStorage { room_object_data } => room_object_data.provides(resource_type),
             TerminalOverflow { room_object_data } => room_object_data.provides(resource_type),
+            Tombstone { tombstone_data } => tombstone_data.provides(resource_type),
+            SpawnSupply { spawn_supply_data } => spawn_supply_data.provides(resource_type),
             Unknown { room_object_data } => room_object_data.provides(resource_type),
         }
     }
 
-    fn creep_can_use(&self, creep: &screeps::Creep) -> Result<bool, Box<dyn Error>> {
+    fn creep_can_use(
+        &self,
+        creep: &screeps::Creep,
+        resource_type: ResourceType,
+    ) -> anyhow::Result<bool> {
         use ResourceProvider::*;
-        match self {
-            EnergyFarm { resource_farm_data } => resource_farm_data.creep_can_use(creep),
-            SourceDump { room_object_data } => room_object_data.creep_can_use(creep),
-            BufferControllerUpgrade { room_object_data } => room_object_data.creep_can_use(creep),
-            LongTermStorage { room_object_data } => room_object_data.creep_can_use(creep),
-            TerminalOverflow { room_object_data } => room_object_data.creep_can_use(creep),
-            Unknown { room_object_data } => room_object_data.creep_can_use(creep),
+        let usable = match self {
+            EnergyFarm { resource_farm_data } => {
+                resource_farm_data.creep_can_use(creep, resource_type)
+            }
+            SourceDump { room_object_data } => room_object_data.creep_can_use(creep, resource_type),
+            BufferControllerUpgrade { room_object_data } => {
+                room_object_data.creep_can_use(creep, resource_type)
+            }
+            LongTermStorage { room_object_data } => {
+                room_object_data.creep_can_use(creep, resource_type)
+            }
+            TerminalOverflow { room_object_data } => {
+                room_object_data.creep_can_use(creep, resource_type)
+            }
+            Tombstone { tombstone_data } => tombstone_data.creep_can_use(creep, resource_type),
+            SpawnSupply { spawn_supply_data } => {
+                spawn_supply_data.creep_can_use(creep, resource_type)
+            }
+            Unknown { room_object_data } => room_object_data.creep_can_use(creep, resource_type),
+        }?;
+        if !usable {
+            return Ok(false);
         }
+        Ok(match self.structure()? {
+            Some(structure) => withdraw_permissions::may_withdraw(
+                creep,
+                structure.pos().room_name(),
+                resource_type,
+                structure.structure_type(),
+            ),
+            None => true,
+        })
     }
 
     fn creep_get_resource(
@@ -148,6 +281,12 @@ impl ResourceData for ResourceProvider {
             TerminalOverflow { room_object_data } => {
                 room_object_data.creep_get_resource(creep, resource_type, ideal_amount)
             }
+            Tombstone { tombstone_data } => {
+                tombstone_data.creep_get_resource(creep, resource_type, ideal_amount)
+            }
+            SpawnSupply { spawn_supply_data } => {
+                spawn_supply_data.creep_get_resource(creep, resource_type, ideal_amount)
+            }
             Unknown { room_object_data } => {
                 room_object_data.creep_get_resource(creep, resource_type, ideal_amount)
             }
@@ -162,33 +301,32 @@ pub struct ResourceFarmData {
 
 impl ResourceData for ResourceFarmData {
     fn pos(&self) -> anyhow::Result<Position> {
-        let obj = get_object_typed(self.obj_id)?.ok_or_else(|| {
-            Box::new(ResourceProviderError::ObjectNotFound(format!(
-                "pos: {}",
-                self.obj_id
-            )))
+        let obj = get_object_cached(self.obj_id)?.ok_or_else(|| {
+            ResourceProviderError::ObjectNotFound(format!("pos: {}", self.obj_id))
         })?;
         Ok(obj.pos())
     }
 
-    fn provides(&self, resource_type: &screeps::ResourceType) -> Result<u32, Box<dyn Error>> {
+    fn provides(&self, resource_type: &screeps::ResourceType) -> anyhow::Result<u32> {
         if *resource_type != ResourceType::Energy {
-            return Err(Box::new(ResourceProviderError::ResourceTypeMismatch(
+            return Err(ResourceProviderError::ResourceTypeMismatch(
                 ResourceType::Energy,
                 *resource_type,
                 format!("{}", self.obj_id),
-            )));
+            )
+            .into());
         }
-        let obj = get_object_typed(self.obj_id)?.ok_or_else(|| {
-            Box::new(ResourceProviderError::ObjectNotFound(format!(
-                "prov: {}",
-                self.obj_id
-            )))
+        let obj = get_object_cached(self.obj_id)?.ok_or_else(|| {
+            ResourceProviderError::ObjectNotFound(format!("prov: {}", self.obj_id))
         })?;
         Ok(obj.energy())
     }
 
-    fn creep_can_use(&self, creep: &screeps::Creep) -> Result<bool, Box<dyn Error>> {
+    fn creep_can_use(
+        &self,
+        creep: &screeps::Creep,
+        _resource_type: ResourceType,
+    ) -> anyhow::Result<bool> {
         Ok(creep.get_active_bodyparts(screeps::Part::Work) > 0)
     }
 
@@ -198,7 +336,7 @@ impl ResourceData for ResourceFarmData {
         resource_type: ResourceType,
         ideal_amount: u32,
     ) -> anyhow::Result<TakeResourceResult> {
-        let obj = get_object_typed(self.obj_id)?;
+        let obj = get_object_cached(self.obj_id)?;
         if let Some(obj) = obj {
             if resource_type == ResourceType::Energy {
                 Ok(TakeResourceResult::Harvest {
@@ -228,21 +366,15 @@ pub struct StructureData {
 
 impl ResourceData for StructureData {
     fn pos(&self) -> anyhow::Result<Position> {
-        let obj = get_object_typed(self.obj_id)?.ok_or_else(|| {
-            Box::new(ResourceProviderError::ObjectNotFound(format!(
-                "pos2: {}",
-                self.obj_id
-            )))
+        let obj = get_object_cached(self.obj_id)?.ok_or_else(|| {
+            ResourceProviderError::ObjectNotFound(format!("pos2: {}", self.obj_id))
         })?;
         Ok(obj.pos())
     }
 
-    fn provides(&self, resource_type: &screeps::ResourceType) -> Result<u32, Box<dyn Error>> {
-        let obj = get_object_typed(self.obj_id)?.ok_or_else(|| {
-            Box::new(ResourceProviderError::ObjectNotFound(format!(
-                "prov2: {}",
-                self.obj_id
-            )))
+    fn provides(&self, resource_type: &screeps::ResourceType) -> anyhow::Result<u32> {
+        let obj = get_object_cached(self.obj_id)?.ok_or_else(|| {
+            ResourceProviderError::ObjectNotFound(format!("prov2: {}", self.obj_id))
         })?;
         let obj_with_store = obj
             .as_has_store()
@@ -250,7 +382,11 @@ impl ResourceData for StructureData {
         Ok(obj_with_store.store_used_capacity(Some(*resource_type)))
     }
 
-    fn creep_can_use(&self, creep: &screeps::Creep) -> Result<bool, Box<dyn Error>> {
+    fn creep_can_use(
+        &self,
+        creep: &screeps::Creep,
+        _resource_type: ResourceType,
+    ) -> anyhow::Result<bool> {
         Ok(creep.get_active_bodyparts(screeps::Part::Carry) > 0)
     }
 
@@ -260,11 +396,8 @@ impl ResourceData for StructureData {
         resource_type: ResourceType,
         ideal_amount: u32,
     ) -> anyhow::Result<TakeResourceResult> {
-        let obj = get_object_typed(self.obj_id)?.ok_or_else(|| {
-            Box::new(ResourceProviderError::ObjectNotFound(format!(
-                "gets-r2: {}",
-                self.obj_id
-            )))
+        let obj = get_object_cached(self.obj_id)?.ok_or_else(|| {
+            ResourceProviderError::ObjectNotFound(format!("gets-r2: {}", self.obj_id))
         })?;
         let store_obj = obj
             .as_has_store()
@@ -287,6 +420,102 @@ impl ResourceData for StructureData {
     }
 }
 
+/// A spawn or extension lending its energy to construction
+///
+/// Builders may only take from it while the room doesn't wait for creeps to spawn, otherwise
+/// they'd eat the energy the spawn is saving up and have to go to storage or containers instead.
+/// Carriers never take from it, they are the ones filling it, and neither do upgraders, see
+/// `generic_calc_resource_provider`.
+#[derive(Clone, Debug)]
+pub struct SpawnSupplyData {
+    pub structure_data: StructureData,
+    /// Spawns of the room are queued up
+    pub spawn_queued: bool,
+}
+
+impl ResourceData for SpawnSupplyData {
+    fn pos(&self) -> anyhow::Result<Position> {
+        self.structure_data.pos()
+    }
+
+    fn provides(&self, resource_type: &screeps::ResourceType) -> anyhow::Result<u32> {
+        self.structure_data.provides(resource_type)
+    }
+
+    fn creep_can_use(
+        &self,
+        creep: &screeps::Creep,
+        resource_type: ResourceType,
+    ) -> anyhow::Result<bool> {
+        if self.spawn_queued || creep.get_active_bodyparts(screeps::Part::Work) == 0 {
+            return Ok(false);
+        }
+        self.structure_data.creep_can_use(creep, resource_type)
+    }
+
+    fn creep_get_resource(
+        &self,
+        creep: &screeps::Creep,
+        resource_type: ResourceType,
+        ideal_amount: u32,
+    ) -> anyhow::Result<TakeResourceResult> {
+        self.structure_data
+            .creep_get_resource(creep, resource_type, ideal_amount)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TombstoneData {
+    pub obj_id: ObjectId<screeps::Tombstone>,
+}
+
+impl ResourceData for TombstoneData {
+    fn pos(&self) -> anyhow::Result<Position> {
+        let obj = get_object_cached(self.obj_id)?.ok_or_else(|| {
+            ResourceProviderError::ObjectNotFound(format!("pos5: {}", self.obj_id))
+        })?;
+        Ok(obj.pos())
+    }
+
+    fn provides(&self, resource_type: &screeps::ResourceType) -> anyhow::Result<u32> {
+        let obj = get_object_cached(self.obj_id)?.ok_or_else(|| {
+            ResourceProviderError::ObjectNotFound(format!("prov5: {}", self.obj_id))
+        })?;
+        Ok(obj.store_used_capacity(Some(*resource_type)))
+    }
+
+    fn creep_can_use(
+        &self,
+        creep: &screeps::Creep,
+        _resource_type: ResourceType,
+    ) -> anyhow::Result<bool> {
+        Ok(creep.get_active_bodyparts(screeps::Part::Carry) > 0)
+    }
+
+    fn creep_get_resource(
+        &self,
+        creep: &screeps::Creep,
+        resource_type: ResourceType,
+        ideal_amount: u32,
+    ) -> anyhow::Result<TakeResourceResult> {
+        let obj = get_object_cached(self.obj_id)?.ok_or_else(|| {
+            ResourceProviderError::ObjectNotFound(format!("gets-r5: {}", self.obj_id))
+        })?;
+        let amount = cmp::min(
+            obj.store_used_capacity(Some(resource_type)),
+            cmp::min(
+                ideal_amount,
+                creep.store_free_capacity(Some(resource_type)) as u32,
+            ),
+        );
+
+        Ok(TakeResourceResult::Withdraw {
+            return_code: creep.withdraw_amount(&obj, resource_type, amount),
+            tried_amount: amount,
+        })
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum RoomObjectData {
     StorageStructure {
@@ -295,7 +524,7 @@ pub enum RoomObjectData {
     Litter {
         obj_id: ObjectId<screeps::Resource>,
     },
-    // Later on you might want to add variants for Ruin and Tombstone
+    // Later on you might want to add a variant for Ruin
 }
 
 impl RoomObjectData {
@@ -313,35 +542,26 @@ impl ResourceData for RoomObjectData {
         use RoomObjectData::*;
         match self {
             StorageStructure { obj_id } => {
-                let obj = get_object_typed(*obj_id)?.ok_or_else(|| {
-                    Box::new(ResourceProviderError::ObjectNotFound(format!(
-                        "pos3: {}",
-                        *obj_id
-                    )))
+                let obj = get_object_cached(*obj_id)?.ok_or_else(|| {
+                    ResourceProviderError::ObjectNotFound(format!("pos3: {}", *obj_id))
                 })?;
                 Ok(obj.pos())
             }
             Litter { obj_id } => {
-                let obj = get_object_typed(*obj_id)?.ok_or_else(|| {
-                    Box::new(ResourceProviderError::ObjectNotFound(format!(
-                        "pos31 {}",
-                        *obj_id
-                    )))
+                let obj = get_object_cached(*obj_id)?.ok_or_else(|| {
+                    ResourceProviderError::ObjectNotFound(format!("pos31 {}", *obj_id))
                 })?;
                 Ok(obj.pos())
             }
         }
     }
 
-    fn provides(&self, resource_type: &screeps::ResourceType) -> Result<u32, Box<dyn Error>> {
+    fn provides(&self, resource_type: &screeps::ResourceType) -> anyhow::Result<u32> {
         use RoomObjectData::*;
         match self {
             StorageStructure { obj_id } => {
-                let obj = get_object_typed(*obj_id)?.ok_or_else(|| {
-                    Box::new(ResourceProviderError::ObjectNotFound(format!(
-                        "prov3: {}",
-                        *obj_id
-                    )))
+                let obj = get_object_cached(*obj_id)?.ok_or_else(|| {
+                    ResourceProviderError::ObjectNotFound(format!("prov3: {}", *obj_id))
                 })?;
                 let obj_with_store = obj
                     .as_has_store()
@@ -349,11 +569,8 @@ impl ResourceData for RoomObjectData {
                 Ok(obj_with_store.store_used_capacity(Some(*resource_type)))
             }
             Litter { obj_id } => {
-                let obj = get_object_typed(*obj_id)?.ok_or_else(|| {
-                    Box::new(ResourceProviderError::ObjectNotFound(format!(
-                        "prov31: {}",
-                        *obj_id
-                    )))
+                let obj = get_object_cached(*obj_id)?.ok_or_else(|| {
+                    ResourceProviderError::ObjectNotFound(format!("prov31: {}", *obj_id))
                 })?;
                 if obj.resource_type() != *resource_type {
                     Err(ResourceProviderError::ResourceTypeMismatch(
@@ -367,7 +584,11 @@ impl ResourceData for RoomObjectData {
         }
     }
 
-    fn creep_can_use(&self, creep: &screeps::Creep) -> Result<bool, Box<dyn Error>> {
+    fn creep_can_use(
+        &self,
+        creep: &screeps::Creep,
+        _resource_type: ResourceType,
+    ) -> anyhow::Result<bool> {
         Ok(creep.get_active_bodyparts(screeps::Part::Carry) > 0)
     }
 
@@ -380,11 +601,8 @@ impl ResourceData for RoomObjectData {
         use RoomObjectData::*;
         match self {
             StorageStructure { obj_id } => {
-                let obj = get_object_typed(*obj_id)?.ok_or_else(|| {
-                    Box::new(ResourceProviderError::ObjectNotFound(format!(
-                        "get_r4: {}",
-                        obj_id
-                    )))
+                let obj = get_object_cached(*obj_id)?.ok_or_else(|| {
+                    ResourceProviderError::ObjectNotFound(format!("get_r4: {}", obj_id))
                 })?;
                 let store_obj = obj.as_has_store().ok_or_else(|| {
                     ResourceProviderError::ObjectNoStore(format!("get_r41: {}", obj_id))
@@ -406,18 +624,15 @@ impl ResourceData for RoomObjectData {
                 })
             }
             Litter { obj_id } => {
-                let obj = get_object_typed(*obj_id)?.ok_or_else(|| {
-                    Box::new(ResourceProviderError::ObjectNotFound(format!(
-                        "getr45{}",
-                        obj_id
-                    )))
+                let obj = get_object_cached(*obj_id)?.ok_or_else(|| {
+                    ResourceProviderError::ObjectNotFound(format!("getr45{}", obj_id))
                 })?;
                 if obj.resource_type() != resource_type {
-                    Err(Box::new(ResourceProviderError::ResourceTypeMismatch(
+                    Err(ResourceProviderError::ResourceTypeMismatch(
                         resource_type,
                         obj.resource_type(),
                         format!("{}", obj.id()),
-                    )))?;
+                    ))?;
                 }
                 Ok(TakeResourceResult::Pickup {
                     return_code: creep.pickup(&obj),
@@ -427,8 +642,57 @@ impl ResourceData for RoomObjectData {
     }
 }
 
-pub fn calc_resource_providers(room: &Room) -> anyhow::Result<Vec<ResourceProvider>> {
-    let structures: Vec<screeps::Structure> = room.find(find::STRUCTURES);
+/// Dropped resources smaller than this are not worth a detour if they are not next to a source
+const LITTER_MIN_AMOUNT: u32 = 100;
+
+/// Amount left of a dropped resource after `ticks`
+///
+/// Dropped resources lose `ceil(amount / 1000)` every tick.
+pub fn dropped_amount_after(amount: u32, ticks: u32) -> u32 {
+    let mut amount = amount;
+    for _ in 0..ticks {
+        if amount == 0 {
+            break;
+        }
+        amount -= (amount + 999) / 1000;
+    }
+    amount
+}
+
+/// `spawn_queued` keeps builders away from the spawns and extensions while the room waits for
+/// creeps to spawn
+pub fn calc_resource_providers(
+    room: &Room,
+    spawn_queued: bool,
+) -> anyhow::Result<Vec<ResourceProvider>> {
+    profile_scope!("calc_resource_providers");
+    let room_name = room.name();
+    let mut candidates: Vec<Structure> = vec![];
+    candidates.extend(
+        structures::of_type(room_name, StructureType::Container)
+            .into_iter()
+            .map(Structure::Container),
+    );
+    candidates.extend(
+        structures::of_type(room_name, StructureType::Storage)
+            .into_iter()
+            .map(Structure::Storage),
+    );
+    candidates.extend(
+        structures::of_type(room_name, StructureType::Terminal)
+            .into_iter()
+            .map(Structure::Terminal),
+    );
+    candidates.extend(
+        structures::of_type(room_name, StructureType::Spawn)
+            .into_iter()
+            .map(Structure::Spawn),
+    );
+    candidates.extend(
+        structures::of_type(room_name, StructureType::Extension)
+            .into_iter()
+            .map(Structure::Extension),
+    );
 
     // let containers: Vec<&screeps::StructureContainer> = structures
     //     .iter()
@@ -447,7 +711,7 @@ pub fn calc_resource_providers(room: &Room) -> anyhow::Result<Vec<ResourceProvid
     //     })
     //     .collect();
 
-    let structure_providers = structures.into_iter().filter_map(|s| match s {
+    let structure_providers = candidates.into_iter().filter_map(|s| match s {
         screeps::Structure::Container(container) => calc_container(&room, container)
             .unwrap_or_else(|err| {
                 warn!("failed calcing container: {}", err);
@@ -461,6 +725,14 @@ pub fn calc_resource_providers(room: &Room) -> anyhow::Result<Vec<ResourceProvid
             warn!("failed calcing terminal: {}", err);
             None
         }),
+        screeps::Structure::Spawn(_) | screeps::Structure::Extension(_) => {
+            Some(ResourceProvider::SpawnSupply {
+                spawn_supply_data: SpawnSupplyData {
+                    structure_data: StructureData { obj_id: s.id() },
+                    spawn_queued,
+                },
+            })
+        }
         _ => None,
     });
 
@@ -475,6 +747,15 @@ pub fn calc_resource_providers(room: &Room) -> anyhow::Result<Vec<ResourceProvid
         })
         .collect();
 
+    let tombstones: Vec<ResourceProvider> = room
+        .find(find::TOMBSTONES)
+        .iter()
+        .filter(|t| t.store_used_capacity(None) > 0)
+        .map(|t| ResourceProvider::Tombstone {
+            tombstone_data: TombstoneData { obj_id: t.id() },
+        })
+        .collect();
+
     let sources: Vec<ResourceProvider> = room
         .find(find::SOURCES)
         .iter()
@@ -488,6 +769,7 @@ pub fn calc_resource_providers(room: &Room) -> anyhow::Result<Vec<ResourceProvid
     let mut providers = vec![];
     providers.extend(structure_providers);
     providers.extend(litters);
+    providers.extend(tombstones);
     providers.extend(sources);
 
     Ok(providers)
@@ -496,7 +778,7 @@ pub fn calc_resource_providers(room: &Room) -> anyhow::Result<Vec<ResourceProvid
 fn calc_container(
     room: &Room,
     container: screeps::StructureContainer,
-) -> Result<Option<ResourceProvider>, Box<dyn Error>> {
+) -> anyhow::Result<Option<ResourceProvider>> {
     let container_pos = container.pos();
     let sources = room.look_for_around(look::SOURCES, container_pos, 1)?;
     if sources.len() > 0 {
@@ -520,9 +802,7 @@ fn calc_container(
     Ok(None)
 }
 
-fn calc_storage(
-    storage: screeps::StructureStorage,
-) -> Result<Option<ResourceProvider>, Box<dyn Error>> {
+fn calc_storage(storage: screeps::StructureStorage) -> anyhow::Result<Option<ResourceProvider>> {
     Ok(Some(ResourceProvider::LongTermStorage {
         room_object_data: StructureData {
             obj_id: storage.as_structure().id(),
@@ -530,9 +810,7 @@ fn calc_storage(
     }))
 }
 
-fn calc_terminal(
-    terminal: screeps::StructureTerminal,
-) -> Result<Option<ResourceProvider>, Box<dyn Error>> {
+fn calc_terminal(terminal: screeps::StructureTerminal) -> anyhow::Result<Option<ResourceProvider>> {
     Ok(Some(ResourceProvider::TerminalOverflow {
         room_object_data: StructureData {
             obj_id: terminal.as_structure().id(),
@@ -543,7 +821,7 @@ fn calc_terminal(
 fn calc_litter(
     room: &Room,
     litter: &screeps::Resource,
-) -> Result<Option<ResourceProvider>, Box<dyn Error>> {
+) -> anyhow::Result<Option<ResourceProvider>> {
     let litter_pos = litter.pos();
     let sources = room.look_for_around(look::SOURCES, litter_pos, 1)?;
     if sources.len() > 0 {
@@ -553,6 +831,14 @@ fn calc_litter(
             },
         }));
     }
+    // Drops of dead creeps and the like
+    if litter.amount() >= LITTER_MIN_AMOUNT {
+        return Ok(Some(ResourceProvider::Unknown {
+            room_object_data: RoomObjectData::Litter {
+                obj_id: litter.id(),
+            },
+        }));
+    }
     Ok(None)
 }
 
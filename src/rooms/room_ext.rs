@@ -43,8 +43,4 @@ impl RoomExt for screeps::Room {
             cmp::max(y - steps, 0) as u8..cmp::min(y + steps + include_end_val, 49) as u8,
         )
     }
-
-    // TODO bunch of functions that cache for the tick
-    // if they arent already optimized
-    // Like `room.find(find::STRUCTURES)`
 }
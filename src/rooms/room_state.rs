@@ -1,33 +1,46 @@
 pub mod base;
+pub mod opener;
 pub mod setup_base;
+pub mod spawn_failures;
 
 use std::cmp;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 
-use crate::creeps::jobs::{FarmSource, OokCreepJob};
-use crate::creeps::races::carrier::{OokCreepCarrier, TrySpawnCarrierOptions};
+use crate::creeps::jobs::{self, FarmSource, OokCreepJob};
+use crate::creeps::races::blocker::{self, OokCreepBlocker, TrySpawnBlockerOptions};
+use crate::creeps::races::carrier::{self, OokCreepCarrier, TrySpawnCarrierOptions};
+use crate::creeps::races::claimer::{self, OokCreepClaimer, TrySpawnClaimerOptions};
+use crate::creeps::races::dismantler::{self, OokCreepDismantler, TrySpawnDismantlerOptions};
+use crate::creeps::races::harasser::{self, OokCreepHarasser, TrySpawnHarasserOptions};
+use crate::creeps::races::healer::{self, OokCreepHealer, TrySpawnHealerOptions};
 use crate::creeps::races::worker::{OokCreepWorker, TrySpawnWorkerOptions};
 use crate::creeps::races::{OokRaceBodyComposition, OokRaceKind};
-use crate::creeps::{Spawnable, TrySpawnOptions, TrySpawnResult, TrySpawnResultData};
+use crate::creeps::naming::NameRegistry;
+use crate::creeps::renewal::{self, Renewal};
+use crate::creeps::tasks::claim_controller;
+use crate::creeps::utils::SpawnableTimer;
+use crate::creeps::{SpawnPlan, Spawnable, TrySpawnOptions, TrySpawnResult, TrySpawnResultData};
 use crate::state::requests::{self, Request, RequestData};
 use crate::state::{RequestHandledOpts, UniqId};
+use crate::config::ROOM_STATE_INITS_PER_TICK;
 use crate::utils::AnyhowOptionExt;
 use anyhow::{anyhow, bail, Context};
-use log::{info, warn};
+use log::{debug, info, warn};
 use num_derive::{FromPrimitive, ToPrimitive};
 use num_traits::FromPrimitive;
 use screeps::game::rooms;
-use screeps::{find, HasId, ReturnCode, Room};
+use screeps::{find, Creep, HasId, ObjectId, Part, ReturnCode, Room, StructureSpawn};
 use screeps::{memory::MemoryReference, RoomName};
 use serde::{Deserialize, Serialize};
 use stdweb::JsSerialize;
 
 use crate::{
+    config,
     constants::{MEM_OOK_ROOMS, MEM_OOK_ROOMS_DATA, MEM_ROOM_STATE_KIND},
+    economy::ledger::{self, SpawnPurpose},
     game::{owned_rooms, OwnedBy},
-    rooms::room_state::base::BaseState,
+    rooms::{remotes, room_state::base::BaseState, visibility},
     state::BWState,
-    utils::ResultOptionExt,
 };
 
 pub use self::setup_base::{SetupBaseState, SetupBaseStateVisibility};
@@ -57,6 +70,19 @@ pub trait RoomStateLifecycle<T> {
     ) -> anyhow::Result<RoomStateChange>;
 
     fn request_logged(&mut self, request_id: UniqId);
+
+    /// Forgets the requests logged and not handled yet, they are gone from the state
+    fn forget_requests(&mut self);
+
+    /// Called once the room switched to this state, before it runs the first time
+    fn on_enter(&mut self, _state: &mut BWState) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Called when the room switches to another state, before that one enters
+    fn on_exit(&mut self, _state: &mut BWState) -> anyhow::Result<()> {
+        Ok(())
+    }
 }
 
 pub trait RoomStatePersistable<T> {
@@ -94,6 +120,35 @@ impl RoomState {
         }
     }
 
+    /// Citizens working for the room
+    pub fn helping_citizens(&self) -> &[ObjectId<Creep>] {
+        match self {
+            RoomState::Base(state) => &state.data.helping_citizens,
+            RoomState::SetupBase(state) => &state.data.helping_citizens,
+        }
+    }
+
+    pub fn on_enter(&mut self, state: &mut BWState) -> anyhow::Result<()> {
+        match self {
+            RoomState::Base(room_state) => room_state.on_enter(state),
+            RoomState::SetupBase(room_state) => room_state.on_enter(state),
+        }
+    }
+
+    pub fn on_exit(&mut self, state: &mut BWState) -> anyhow::Result<()> {
+        match self {
+            RoomState::Base(room_state) => room_state.on_exit(state),
+            RoomState::SetupBase(room_state) => room_state.on_exit(state),
+        }
+    }
+
+    pub fn forget_requests(&mut self) {
+        match self {
+            RoomState::Base(room_state) => room_state.forget_requests(),
+            RoomState::SetupBase(room_state) => room_state.forget_requests(),
+        }
+    }
+
     pub fn resource_provider(&self, id: &str) -> Option<&ResourceProvider> {
         match self {
             RoomState::Base(state) => state.resource_providers.get(id),
@@ -144,49 +199,88 @@ impl RoomStatePersistable<Self> for RoomState {
     }
 }
 
-pub fn legacy_init_room_states() -> anyhow::Result<HashMap<RoomName, RoomState>> {
-    warn!("Legacy room handling!!");
-    let mut room_states: HashMap<RoomName, RoomState> = HashMap::new();
-    let owned_rooms = owned_rooms(OwnedBy::Me);
-    for (name, room) in owned_rooms.iter() {
-        let state = BaseState::new(room.name());
-        match state {
-            Ok(state) => {
-                room_states.insert(*name, RoomState::Base(state));
-            }
-            Err(err) => {
-                warn!("Unable to init room state for {}: {}", name, err);
-            }
-        };
+/// Swaps the state of the room, the old one exits before the new one enters
+pub fn transition_room_state(
+    state: &mut BWState,
+    room_name: RoomName,
+    mut new_state: RoomState,
+) -> anyhow::Result<()> {
+    if let Some(mut old_state) = state.room_states.remove(&room_name) {
+        old_state
+            .on_exit(state)
+            .with_context(|| format!("exiting the state of {}", room_name))?;
     }
-    Ok(room_states)
-}
-
-pub fn init_room_states() -> anyhow::Result<HashMap<RoomName, RoomState>> {
-    let mut room_states: HashMap<RoomName, RoomState> = HashMap::new();
-    match screeps::memory::root().dict(MEM_OOK_ROOMS_DATA) {
-        Ok(Some(data)) => {
-            let rooms = data
-                .dict(MEM_OOK_ROOMS)
-                .err_or_none("rooms dict missing".to_string())?;
-            for room_name in rooms.keys() {
-                let room = rooms
-                    .get(&room_name)
-                    .err_or_none("wtf, could not get room for key")?;
-                match RoomState::load_from_memory(&room) {
-                    Ok(room_state) => {
-                        room_states.insert(room_state.room_name(), room_state);
-                    }
-                    Err(err) => warn!("Could not load mem for room: {}", err),
-                }
-            }
+    new_state
+        .on_enter(state)
+        .with_context(|| format!("entering the state of {}", room_name))?;
+    state.room_states.insert(room_name, new_state);
+    Ok(())
+}
+
+/// Rooms to create states for, the ones with spawns first
+///
+/// Creating all states at once can blow the CPU limit after a reset, see
+/// `init_pending_room_states`. Rooms persisted in memory are picked up again, without any
+/// persisted room all owned rooms become bases.
+pub fn pending_room_states() -> VecDeque<RoomName> {
+    let persisted: Vec<RoomName> = screeps::memory::root()
+        .dict(MEM_OOK_ROOMS_DATA)
+        .ok()
+        .flatten()
+        .and_then(|data| data.dict(MEM_OOK_ROOMS).ok().flatten())
+        .map(|rooms| {
+            rooms
+                .keys()
+                .iter()
+                .filter_map(|name| RoomName::new(name).ok())
+                .collect()
+        })
+        .unwrap_or_default();
+    let mut room_names: Vec<RoomName> = if persisted.is_empty() {
+        warn!("Legacy room handling!!");
+        owned_rooms(OwnedBy::Me).keys().cloned().collect()
+    } else {
+        persisted
+    };
+    room_names.sort_by_key(|name| {
+        rooms::get(*name)
+            .map(|room| room.find(find::MY_SPAWNS).is_empty())
+            .unwrap_or(true)
+    });
+    room_names.into()
+}
+
+fn init_room_state(room_name: RoomName) -> anyhow::Result<RoomState> {
+    let memory = screeps::memory::root()
+        .dict(MEM_OOK_ROOMS_DATA)
+        .ok()
+        .flatten()
+        .and_then(|data| data.dict(MEM_OOK_ROOMS).ok().flatten())
+        .and_then(|rooms| rooms.dict(&room_name.to_string()).ok().flatten());
+    match memory {
+        Some(memory) => RoomState::load_from_memory(&memory),
+        None => Ok(RoomState::Base(BaseState::new(room_name)?)),
+    }
+}
+
+/// Creates the states of the next few pending rooms, spreading the cost over the first ticks
+pub fn init_pending_room_states(state: &mut BWState) {
+    for _ in 0..ROOM_STATE_INITS_PER_TICK {
+        let room_name = match state.pending_room_states.pop_front() {
+            Some(room_name) => room_name,
+            None => return,
+        };
+        if state.room_states.contains_key(&room_name) {
+            continue;
         }
-        _ => {
-            warn!("Failed loading rooms mem");
-            return legacy_init_room_states();
+        match init_room_state(room_name) {
+            Ok(room_state) => {
+                info!("Initialized room state of {}", room_name);
+                state.room_states.insert(room_name, room_state);
+            }
+            Err(err) => warn!("Unable to init room state for {}: {}", room_name, err),
         }
     }
-    Ok(room_states)
 }
 
 /// If I manually changed something in the memory, it should be taken into account
@@ -213,47 +307,30 @@ pub fn update_room_states_from_memory(state: &mut BWState) -> anyhow::Result<()>
     Ok(())
 }
 
+/// Writes the room states to memory, rooms still pending keep what they had
 pub fn persist_room_states(state: &BWState) -> anyhow::Result<()> {
-    let mut new_rooms: HashMap<RoomName, Box<HashMap<String, Box<dyn JsSerialize>>>> =
-        HashMap::new();
-    let room_states = &state.room_states;
     let rooms_data = screeps::memory::root()
         .dict_or_create(MEM_OOK_ROOMS_DATA)
-        .map_err(|e| anyhow!("Could not get mem rooms_state: {}", e))?;
-
-    for (room_name, room_state) in room_states {
-        // if *room_name == RoomName::new("W11N16")? {
-        //     continue;
-        // }
+        .map_err(|e| anyhow!("Could not get mem rooms_state: {}", e))?
+        .dict_or_create(MEM_OOK_ROOMS)
+        .map_err(|e| anyhow!("Could not get mem rooms: {}", e))?;
 
-        let mem = room_state.to_memory();
-        match mem {
+    for (room_name, room_state) in &state.room_states {
+        match room_state.to_memory() {
             Ok(mem) => {
-                // let x = mem.iter()
-                //     .map(|(i, v)| (i.clone(), &**v))
-                //     .collect::<HashMap<String, &dyn JsSerialize>>();
-                new_rooms.insert(room_name.to_owned(), Box::new(mem));
+                rooms_data.set(
+                    &room_name.to_string(),
+                    mem.iter()
+                        .map(|(i, v)| (i.clone(), &**v))
+                        .collect::<HashMap<String, &dyn JsSerialize>>(),
+                );
             }
             Err(_) => {
                 warn!("Could not create data to room {}", room_name);
             }
         }
     }
-    rooms_data.set(
-        MEM_OOK_ROOMS,
-        new_rooms
-            .iter()
-            .map(|(room_name, room_data)| {
-                (
-                    room_name.to_string(),
-                    room_data
-                        .iter()
-                        .map(|(i, v)| (i.clone(), &**v))
-                        .collect::<HashMap<String, &dyn JsSerialize>>(),
-                )
-            })
-            .collect::<HashMap<String, HashMap<String, &dyn JsSerialize>>>(),
-    );
+    // The memory of rooms without state is dropped by `gc`
     Ok(())
 }
 
@@ -262,9 +339,21 @@ pub fn persist_room_states(state: &BWState) -> anyhow::Result<()> {
 // NOTE Perhaps, instead of returning a HashMap, use an enum so requests can be handled by
 //   something else than rooms?
 // NOTE Later on we might want to handle _all_ spawning with these requests
-pub fn assign_requests(state: &mut BWState) -> anyhow::Result<HashMap<RoomName, Request>> {
-    let mut request_handlers: HashMap<RoomName, Request> = HashMap::new();
+pub fn assign_requests(state: &mut BWState) -> anyhow::Result<HashMap<RoomName, Vec<Request>>> {
+    let mut request_handlers: HashMap<RoomName, Vec<Request>> = HashMap::new();
     for (id, request) in &state.requests {
+        if state.claims.is_duplicate(request) {
+            continue;
+        }
+        let avoided = spawn_failures::avoided_rooms(id);
+        if !avoided.is_empty() {
+            if let Some(room_name) = reroute(state, request, &avoided) {
+                request_handlers.entry(room_name).or_default().push(request.to_owned());
+                continue;
+            }
+            // Better to keep trying where it failed, with the backoff, than to never spawn it
+            debug!("No other base can take request {}, assigning it as usual", id);
+        }
         match request {
             Request {
                 data:
@@ -277,12 +366,18 @@ pub fn assign_requests(state: &mut BWState) -> anyhow::Result<HashMap<RoomName,
                 if let Some(target_room) = state.room_states.get(target_room_name) {
                     match target_room {
                         RoomState::Base(room_state) => {
-                            request_handlers.insert(room_state.room_name, request.to_owned());
+                            request_handlers
+                                .entry(room_state.room_name)
+                                .or_default()
+                                .push(request.to_owned());
                         }
                         RoomState::SetupBase(room_state) => {
                             // TODO use the get_helping_room_for_request from below if we
                             //   cant spawn the creeps we need
-                            request_handlers.insert(room_state.room_name, request.to_owned());
+                            request_handlers
+                                .entry(room_state.room_name)
+                                .or_default()
+                                .push(request.to_owned());
                         }
                     }
                 } else {
@@ -290,7 +385,10 @@ pub fn assign_requests(state: &mut BWState) -> anyhow::Result<HashMap<RoomName,
                     // from another room
                     match get_helping_room_for_request(state, request) {
                         Ok(Some(closest_room)) => {
-                            request_handlers.insert(closest_room, request.to_owned());
+                            request_handlers
+                                .entry(closest_room)
+                                .or_default()
+                                .push(request.to_owned());
                         }
                         Ok(None) => {}
                         Err(err) => {
@@ -299,6 +397,32 @@ pub fn assign_requests(state: &mut BWState) -> anyhow::Result<HashMap<RoomName,
                     }
                 }
             }
+            Request {
+                data:
+                    RequestData::Citizen(requests::Citizen {
+                        target_room_name,
+                        spawn_elsewhere: true,
+                        ..
+                    }),
+                ..
+            } => match get_helping_room_for_request(state, request) {
+                Ok(Some(helping_room)) => {
+                    request_handlers.entry(helping_room).or_default().push(request.to_owned());
+                }
+                Ok(None) => {
+                    // No base is big enough either, the room keeps trying until it grows into
+                    // the body
+                    if state.room_states.contains_key(target_room_name) {
+                        request_handlers
+                            .entry(*target_room_name)
+                            .or_default()
+                            .push(request.to_owned());
+                    }
+                }
+                Err(err) => {
+                    warn!("error get_helping_room_for_request: {}", err);
+                }
+            },
             Request {
                 data:
                     RequestData::Citizen(requests::Citizen {
@@ -309,34 +433,121 @@ pub fn assign_requests(state: &mut BWState) -> anyhow::Result<HashMap<RoomName,
                 if let Some(target_room) = state.room_states.get(target_room_name) {
                     match target_room {
                         RoomState::Base(room_state) => {
-                            request_handlers.insert(room_state.room_name, request.to_owned());
+                            request_handlers
+                                .entry(room_state.room_name)
+                                .or_default()
+                                .push(request.to_owned());
                         }
                         RoomState::SetupBase(room_state) => {
-                            // HACK just use a regular vec instead or prioritize correctly
-                            if let Some(rh) = request_handlers.get(&room_state.room_name) {
-                                let existing_important = match &rh.data {
-                                    RequestData::Citizen(data) => {
-                                        data.resolve_panic
-                                    },
-                                    _ => false,
-                                };
-                                if !existing_important {
-                                    request_handlers.insert(room_state.room_name, request.to_owned());
-                                }
-                            } else {
-                                request_handlers.insert(room_state.room_name, request.to_owned());
-                            }
+                            request_handlers
+                                .entry(room_state.room_name)
+                                .or_default()
+                                .push(request.to_owned());
                         }
                     }
                 } else {
                     warn!("room for room_state {} is invisible ayy", target_room_name);
                 }
             }
+            Request {
+                data:
+                    RequestData::Dismantle(requests::Dismantle {
+                        target_room_name, ..
+                    }),
+                ..
+            } => {
+                // Own bases tear down their own stuff, foreign rooms get the closest base
+                if let Some(RoomState::Base(room_state)) = state.room_states.get(target_room_name)
+                {
+                    request_handlers
+                        .entry(room_state.room_name)
+                        .or_default()
+                        .push(request.to_owned());
+                } else {
+                    match get_helping_room_for_request(state, request) {
+                        Ok(Some(closest_room)) => {
+                            request_handlers
+                                .entry(closest_room)
+                                .or_default()
+                                .push(request.to_owned());
+                        }
+                        Ok(None) => {}
+                        Err(err) => {
+                            warn!("error get_helping_room_for_request: {}", err);
+                        }
+                    }
+                }
+            }
+            Request {
+                data: RequestData::InterShardClaim(_),
+                ..
+            }
+            | Request {
+                data: RequestData::Salvage(_),
+                ..
+            } => match get_helping_room_for_request(state, request) {
+                Ok(Some(closest_room)) => {
+                    request_handlers.entry(closest_room).or_default().push(request.to_owned());
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    warn!("error get_helping_room_for_request: {}", err);
+                }
+            },
         }
     }
+    for queue in request_handlers.values_mut() {
+        queue.sort_by_key(|request| cmp::Reverse(spawn_priority(request)));
+    }
     Ok(request_handlers)
 }
 
+/// The closest base that didn't fail spawning for the request too often yet and is big enough for
+/// the body, see `spawn_failures`
+fn reroute(state: &BWState, request: &Request, avoided: &[RoomName]) -> Option<RoomName> {
+    let target_room_name = match &request.data {
+        RequestData::BootstrapWorkerCitizen(data) => data.target_room_name,
+        RequestData::Citizen(data) => data.target_room_name,
+        RequestData::Dismantle(data) => data.target_room_name,
+        RequestData::InterShardClaim(data) => data.portal_room,
+        RequestData::Salvage(data) => data.target_room_name,
+    };
+    state
+        .room_states
+        .iter()
+        .filter(|(room_name, room_state)| {
+            matches!(room_state, RoomState::Base(_)) && !avoided.contains(room_name)
+        })
+        .filter(|(room_name, _)| match &request.data {
+            RequestData::Citizen(data) => rooms::get(**room_name)
+                .and_then(|room| {
+                    plan_citizen(&room, &data.initial_job, data.target_room_name)
+                        .ok()
+                        .flatten()
+                })
+                .is_some(),
+            _ => true,
+        })
+        .map(|(room_name, _)| *room_name)
+        .min_by_key(|room_name| {
+            let (x_diff, y_diff) = target_room_name - *room_name;
+            x_diff * x_diff + y_diff * y_diff
+        })
+}
+
+/// Higher goes first when a room has more requests than free spawns, panic requests get the room
+/// going again and preempt everything else, renewals included
+fn spawn_priority(request: &Request) -> u8 {
+    match &request.data {
+        RequestData::Citizen(requests::Citizen {
+            resolve_panic: true,
+            ..
+        }) => 2,
+        RequestData::BootstrapWorkerCitizen(_) => 1,
+        _ => 0,
+    }
+}
+
 fn get_helping_room_for_request(
     state: &BWState,
     request: &Request,
@@ -349,6 +560,29 @@ fn get_helping_room_for_request(
                     ..
                 }),
             ..
+        }
+        | Request {
+            data:
+                RequestData::Dismantle(requests::Dismantle {
+                    target_room_name, ..
+                }),
+            ..
+        }
+        | Request {
+            data:
+                RequestData::Salvage(requests::Salvage {
+                    target_room_name, ..
+                }),
+            ..
+        }
+        // The base closest to the portal sends the claimer
+        | Request {
+            data:
+                RequestData::InterShardClaim(requests::InterShardClaim {
+                    portal_room: target_room_name,
+                    ..
+                }),
+            ..
         } => {
             let mut rooms_able_to_help: Vec<RoomName> = state
                 .room_states
@@ -369,6 +603,37 @@ fn get_helping_room_for_request(
             });
             Ok(rooms_able_to_help.first().map(|r| r.to_owned()))
         }
+        // The closest base big enough for the body
+        Request {
+            data:
+                RequestData::Citizen(requests::Citizen {
+                    target_room_name,
+                    initial_job,
+                    spawn_elsewhere: true,
+                    ..
+                }),
+            ..
+        } => {
+            let mut rooms_able_to_help: Vec<RoomName> = vec![];
+            for (room_name, room_state) in &state.room_states {
+                if room_name == target_room_name || !matches!(room_state, RoomState::Base(_)) {
+                    continue;
+                }
+                let room = match rooms::get(*room_name) {
+                    Some(room) => room,
+                    None => continue,
+                };
+                if plan_citizen(&room, initial_job, *target_room_name)?.is_some() {
+                    rooms_able_to_help.push(*room_name);
+                }
+            }
+            rooms_able_to_help.sort_unstable_by_key(|&a| {
+                let (x_diff, y_diff) = *target_room_name - a;
+                let linear_len = ((x_diff * x_diff + y_diff * y_diff) as f32).sqrt().round() as i32;
+                linear_len
+            });
+            Ok(rooms_able_to_help.first().map(|r| r.to_owned()))
+        }
         Request {
             data: RequestData::Citizen(requests::Citizen { .. }),
             ..
@@ -376,142 +641,456 @@ fn get_helping_room_for_request(
     }
 }
 
+/// What the requests handled so far this tick took of a room, the game only takes it at the end
+/// of the tick
+#[derive(Debug, Default)]
+struct SpawnBudget {
+    used_spawns: Vec<ObjectId<StructureSpawn>>,
+    spent_energy: u32,
+}
+
+impl SpawnBudget {
+    /// Spawns of the room neither spawning nor used by a request this tick
+    fn free_spawns(&self, room: &Room) -> Vec<ObjectId<StructureSpawn>> {
+        room.find(find::MY_SPAWNS)
+            .iter()
+            .filter(|s| s.spawning().is_none() && !self.used_spawns.contains(&s.id()))
+            .map(|s| s.id())
+            .collect()
+    }
+
+    fn energy_left(&self, room: &Room) -> u32 {
+        room.energy_available().saturating_sub(self.spent_energy)
+    }
+
+    fn note(&mut self, result: &anyhow::Result<TrySpawnResult>) {
+        match result {
+            Ok(TrySpawnResult::Spawned(data)) | Ok(TrySpawnResult::ForceSpawned(data))
+                if data.return_code == ReturnCode::Ok =>
+            {
+                self.used_spawns.push(data.used_spawn);
+                self.spent_energy += data.used_energy_amount;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// An item of the spawn queue of a room
+#[derive(Debug)]
+enum SpawnQueueItem {
+    Spawn(Request),
+    /// Renews a creep next to a spawn, see `creeps::renewal`
+    Renew(Renewal),
+}
+
+impl SpawnQueueItem {
+    /// Higher goes first. Renewals come after every request, a spawn only renews when it is idle.
+    fn priority(&self) -> u8 {
+        match self {
+            SpawnQueueItem::Spawn(request) => spawn_priority(request) + 1,
+            SpawnQueueItem::Renew(_) => 0,
+        }
+    }
+}
+
+/// Renews if the spawn of the renewal is still free and the room has the energy left
+fn run_renewal(room: &Room, renewal: &Renewal, budget: &mut SpawnBudget) {
+    if !budget.free_spawns(room).contains(&renewal.spawn)
+        || budget.energy_left(room) < renewal.energy
+    {
+        return;
+    }
+    match renewal::renew(renewal) {
+        ReturnCode::Ok => {
+            budget.used_spawns.push(renewal.spawn);
+            budget.spent_energy += renewal.energy;
+        }
+        code => debug!("Could not renew {}: {:?}", renewal.creep_name, code),
+    }
+}
+
 pub fn dummy_handle_requests(
     state: &mut BWState,
-    requests: HashMap<RoomName, Request>,
+    requests: HashMap<RoomName, Vec<Request>>,
 ) -> anyhow::Result<()> {
-    for (room_name, request) in requests {
-        match &request {
-            Request {
-                request_id,
-                data: RequestData::BootstrapWorkerCitizen(request_data),
-            } => {
-                let source_room = rooms::get(room_name);
-                if let Some(source_room) = source_room {
-                    let room_energy = source_room.energy_available();
-                    let target_spawn_energy: u32 = source_room.energy_capacity_available();
-
-                    match OokCreepWorker::try_spawn(
-                        &TrySpawnOptions {
-                            assumed_job: OokCreepJob::BootstrapRoom {
-                                target_room: request_data.target_room_name.to_owned(),
+    spawn_failures::retain(|id| state.requests.contains_key(id));
+    let mut queues: HashMap<RoomName, Vec<SpawnQueueItem>> = requests
+        .into_iter()
+        .map(|(room_name, queue)| {
+            let queue = queue.into_iter().map(SpawnQueueItem::Spawn).collect();
+            (room_name, queue)
+        })
+        .collect();
+    for (room_name, room) in owned_rooms(OwnedBy::Me) {
+        let renewals = renewal::due(&room).into_iter().map(SpawnQueueItem::Renew);
+        queues.entry(room_name).or_default().extend(renewals);
+    }
+    for (room_name, mut queue) in queues {
+        queue.sort_by_key(|item| cmp::Reverse(item.priority()));
+        let mut budget = SpawnBudget::default();
+        // Requests due this tick, each one served takes a spawn
+        let mut wanted = 0;
+        for item in queue {
+            if let Some(source_room) = rooms::get(room_name) {
+                if budget.free_spawns(&source_room).is_empty() {
+                    break;
+                }
+            }
+            let request = match item {
+                SpawnQueueItem::Spawn(request) => request,
+                SpawnQueueItem::Renew(renewal) => {
+                    // A request left without a spawn needs the energy more, the spawns aren't
+                    // idle
+                    if budget.used_spawns.len() < wanted {
+                        break;
+                    }
+                    if let Some(source_room) = rooms::get(room_name) {
+                        run_renewal(&source_room, &renewal, &mut budget);
+                    }
+                    continue;
+                }
+            };
+            if !spawn_failures::is_due(&request.request_id) {
+                continue;
+            }
+            wanted += 1;
+            match &request {
+                Request {
+                    request_id,
+                    data: RequestData::BootstrapWorkerCitizen(request_data),
+                } => {
+                    let source_room = rooms::get(room_name);
+                    if let Some(source_room) = source_room {
+                        let target_spawn_energy: u32 = spawn_failures::body_energy(
+                            request_id,
+                            source_room.energy_capacity_available(),
+                        );
+
+                        let creep_name = state.creep_names.next_name(OokRaceKind::Worker);
+                        let result = OokCreepWorker::try_spawn(
+                            &TrySpawnOptions {
+                                assumed_job: OokCreepJob::BootstrapRoom {
+                                    target_room: request_data.target_room_name.to_owned(),
+                                },
+                                available_spawns: budget.free_spawns(&source_room),
+                                force_spawn: false,
+                                race: OokRaceKind::Worker,
+                                spawn_room: &source_room,
+                                target_energy_usage: target_spawn_energy,
+                                request_id: Some(request_id.to_owned()),
+                                preset_parts: None,
+                                creep_name,
                             },
-                            available_spawns: source_room
-                                .find(find::MY_SPAWNS)
-                                .iter()
-                                .map(|s| s.id())
-                                .collect(),
-                            force_spawn: false,
-                            race: OokRaceKind::Worker,
-                            spawn_room: &source_room,
-                            target_energy_usage: target_spawn_energy,
-                            request_id: Some(request_id.to_owned()),
-                            preset_parts: None,
-                        },
-                        &TrySpawnWorkerOptions {
-                            post_ident: "XXX".into(),
-                            base_room: request_data.target_room_name.to_owned(),
-                        },
-                    ) {
-                        Ok(TrySpawnResult::Spawned(TrySpawnResultData {
-                            return_code: ReturnCode::Ok,
-                            creep_name,
-                            ..
-                        })) => {
-                            let mut request_data = request_data.to_owned();
-                            request_data.spawning_creep_name = Some(creep_name);
-                            let request = Request {
-                                request_id: request_id.to_owned(),
-                                data: RequestData::BootstrapWorkerCitizen(request_data),
-                            };
-                            state.request_handled(
-                                request,
-                                RequestHandledOpts::DelayHandleForOneTick,
-                            )?;
-                        }
-                        Ok(TrySpawnResult::ForceSpawned(TrySpawnResultData {
-                            return_code: ReturnCode::Ok,
-                            creep_name,
-                            ..
-                        })) => {
-                            let mut request_data = request_data.to_owned();
-                            request_data.spawning_creep_name = Some(creep_name);
-                            let request = Request {
-                                request_id: request_id.to_owned(),
-                                data: RequestData::BootstrapWorkerCitizen(request_data),
-                            };
-                            state.request_handled(
-                                request,
-                                RequestHandledOpts::DelayHandleForOneTick,
-                            )?;
-                        }
-                        Ok(_) => {
-                            info!("Could not spawn for request {:?}", request);
+                            &TrySpawnWorkerOptions {
+                                post_ident: "XXX".into(),
+                                base_room: request_data.target_room_name.to_owned(),
+                            },
+                        );
+                        state.creep_names.note_spawn(&result);
+                        budget.note(&result);
+                        spawn_failures::note(request_id, room_name, &result);
+                        record_spawn_cost(
+                            request_data.target_room_name,
+                            SpawnPurpose::Expansion,
+                            request_id,
+                            &result,
+                        );
+                        match result {
+                            Ok(TrySpawnResult::Spawned(TrySpawnResultData {
+                                return_code: ReturnCode::Ok,
+                                creep_name,
+                                ..
+                            })) => {
+                                let mut request_data = request_data.to_owned();
+                                request_data.spawning_creep_name = Some(creep_name);
+                                let request = Request {
+                                    request_id: request_id.to_owned(),
+                                    data: RequestData::BootstrapWorkerCitizen(request_data),
+                                };
+                                state.request_handled(
+                                    request,
+                                    RequestHandledOpts::DelayHandleForOneTick,
+                                )?;
+                            }
+                            Ok(TrySpawnResult::ForceSpawned(TrySpawnResultData {
+                                return_code: ReturnCode::Ok,
+                                creep_name,
+                                ..
+                            })) => {
+                                let mut request_data = request_data.to_owned();
+                                request_data.spawning_creep_name = Some(creep_name);
+                                let request = Request {
+                                    request_id: request_id.to_owned(),
+                                    data: RequestData::BootstrapWorkerCitizen(request_data),
+                                };
+                                state.request_handled(
+                                    request,
+                                    RequestHandledOpts::DelayHandleForOneTick,
+                                )?;
+                            }
+                            Ok(_) => {
+                                info!("Could not spawn for request {:?}", request);
+                            }
+                            Err(err) => warn!("err hurrdurur {}", err),
                         }
-                        Err(err) => warn!("err hurrdurur {}", err),
+                    } else {
+                        warn!(
+                            "Could not fulfill request {:?} cuz room {} is not visible",
+                            request, room_name
+                        );
+                        visibility::request(room_name, None);
                     }
-                } else {
-                    warn!(
-                        "Could not fulfill request {:?} cuz room {} is not visible",
-                        request, room_name
-                    );
                 }
-            }
-            Request {
-                request_id,
-                data: RequestData::Citizen(request_data),
-            } => {
-                let source_room = rooms::get(room_name);
-                if let Some(source_room) = source_room {
-                    match spawn_citizen(&source_room, request_id.to_owned(), request_data) {
-                        Ok(TrySpawnResult::Spawned(TrySpawnResultData {
-                            return_code: ReturnCode::Ok,
-                            creep_name,
-                            ..
-                        })) => {
-                            let mut request_data = request_data.to_owned();
-                            request_data.spawning_creep_name = Some(creep_name);
-                            let request = Request {
-                                request_id: request_id.to_owned(),
-                                data: RequestData::Citizen(request_data),
-                            };
-                            state.request_handled(
-                                request,
-                                RequestHandledOpts::DelayHandleForOneTick,
-                            )?;
+                Request {
+                    request_id,
+                    data: RequestData::Citizen(request_data),
+                } => {
+                    let source_room = rooms::get(room_name);
+                    if let Some(source_room) = source_room {
+                        let result = spawn_citizen(
+                            &source_room,
+                            request_id.to_owned(),
+                            request_data,
+                            &mut state.creep_names,
+                            &mut budget,
+                        );
+                        spawn_failures::note(request_id, room_name, &result);
+                        match result {
+                            Ok(TrySpawnResult::Spawned(TrySpawnResultData {
+                                return_code: ReturnCode::Ok,
+                                creep_name,
+                                ..
+                            })) => {
+                                let mut request_data = request_data.to_owned();
+                                request_data.spawning_creep_name = Some(creep_name);
+                                let request = Request {
+                                    request_id: request_id.to_owned(),
+                                    data: RequestData::Citizen(request_data),
+                                };
+                                state.request_handled(
+                                    request,
+                                    RequestHandledOpts::DelayHandleForOneTick,
+                                )?;
+                            }
+                            Ok(TrySpawnResult::ForceSpawned(TrySpawnResultData {
+                                return_code: ReturnCode::Ok,
+                                creep_name,
+                                ..
+                            })) => {
+                                let mut request_data = request_data.to_owned();
+                                request_data.spawning_creep_name = Some(creep_name);
+                                let request = Request {
+                                    request_id: request_id.to_owned(),
+                                    data: RequestData::Citizen(request_data),
+                                };
+                                state.request_handled(
+                                    request,
+                                    RequestHandledOpts::DelayHandleForOneTick,
+                                )?;
+                            }
+                            Ok(TrySpawnResult::Skipped) => {}
+                            Ok(_) => {
+                                info!("Could not spawn for request {:?}", request);
+                            }
+                            Err(err) => {
+                                warn!(
+                                    "Error spawning citizen for request {} : {}",
+                                    request_id, err
+                                );
+                            }
                         }
-                        Ok(TrySpawnResult::ForceSpawned(TrySpawnResultData {
-                            return_code: ReturnCode::Ok,
-                            creep_name,
-                            ..
-                        })) => {
-                            let mut request_data = request_data.to_owned();
-                            request_data.spawning_creep_name = Some(creep_name);
-                            let request = Request {
-                                request_id: request_id.to_owned(),
-                                data: RequestData::Citizen(request_data),
-                            };
-                            state.request_handled(
-                                request,
-                                RequestHandledOpts::DelayHandleForOneTick,
-                            )?;
+                    } else {
+                        warn!(
+                            "Could not fulfill request {:?} cuz room {} is not visible",
+                            request, room_name
+                        );
+                        visibility::request(room_name, None);
+                    }
+                }
+                Request {
+                    request_id,
+                    data: RequestData::Dismantle(request_data),
+                } => {
+                    let source_room = rooms::get(room_name);
+                    if let Some(source_room) = source_room {
+                        // Spawns like any citizen, the handling room is its home
+                        let citizen = requests::Citizen {
+                            target_room_name: room_name,
+                            spawning_creep_name: None,
+                            initial_job: OokCreepJob::Dismantle(jobs::Dismantle {
+                                home_room: room_name,
+                                target_room: request_data.target_room_name,
+                                targets: request_data.targets.to_owned(),
+                            }),
+                            resolve_panic: false,
+                            spawn_elsewhere: false,
+                        };
+                        let result = spawn_citizen(
+                            &source_room,
+                            request_id.to_owned(),
+                            &citizen,
+                            &mut state.creep_names,
+                            &mut budget,
+                        );
+                        spawn_failures::note(request_id, room_name, &result);
+                        match result {
+                            Ok(TrySpawnResult::Spawned(TrySpawnResultData {
+                                return_code: ReturnCode::Ok,
+                                creep_name,
+                                ..
+                            })) => {
+                                let mut request_data = request_data.to_owned();
+                                request_data.spawning_creep_name = Some(creep_name);
+                                let request = Request {
+                                    request_id: request_id.to_owned(),
+                                    data: RequestData::Dismantle(request_data),
+                                };
+                                state.request_handled(
+                                    request,
+                                    RequestHandledOpts::DelayHandleForOneTick,
+                                )?;
+                            }
+                            Ok(TrySpawnResult::Skipped) => {}
+                            Ok(_) => {
+                                info!("Could not spawn for request {:?}", request);
+                            }
+                            Err(err) => {
+                                warn!(
+                                    "Error spawning dismantler for request {} : {}",
+                                    request_id, err
+                                );
+                            }
                         }
-                        Ok(TrySpawnResult::Skipped) => {}
-                        Ok(_) => {
-                            info!("Could not spawn for request {:?}", request);
+                    } else {
+                        warn!(
+                            "Could not fulfill request {:?} cuz room {} is not visible",
+                            request, room_name
+                        );
+                        visibility::request(room_name, None);
+                    }
+                }
+                Request {
+                    request_id,
+                    data: RequestData::InterShardClaim(request_data),
+                } => {
+                    let source_room = rooms::get(room_name);
+                    if let Some(source_room) = source_room {
+                        let citizen = requests::Citizen {
+                            target_room_name: room_name,
+                            spawning_creep_name: None,
+                            initial_job: OokCreepJob::CrossPortal(jobs::CrossPortal {
+                                portal_room: request_data.portal_room,
+                                target_shard: request_data.to_shard.to_owned(),
+                                target_room: request_data.target_room,
+                            }),
+                            resolve_panic: false,
+                            spawn_elsewhere: false,
+                        };
+                        let result = spawn_citizen(
+                            &source_room,
+                            request_id.to_owned(),
+                            &citizen,
+                            &mut state.creep_names,
+                            &mut budget,
+                        );
+                        spawn_failures::note(request_id, room_name, &result);
+                        match result {
+                            Ok(TrySpawnResult::Spawned(TrySpawnResultData {
+                                return_code: ReturnCode::Ok,
+                                creep_name,
+                                ..
+                            })) => {
+                                let mut request_data = request_data.to_owned();
+                                request_data.spawning_creep_name = Some(creep_name);
+                                let request = Request {
+                                    request_id: request_id.to_owned(),
+                                    data: RequestData::InterShardClaim(request_data),
+                                };
+                                state.request_handled(
+                                    request,
+                                    RequestHandledOpts::DelayHandleForOneTick,
+                                )?;
+                            }
+                            Ok(TrySpawnResult::Skipped) => {}
+                            Ok(_) => {
+                                info!("Could not spawn for request {:?}", request);
+                            }
+                            Err(err) => {
+                                warn!(
+                                    "Error spawning claimer for request {} : {}",
+                                    request_id, err
+                                );
+                            }
                         }
-                        Err(err) => {
-                            warn!(
-                                "Error spawning citizen for request {} : {}",
-                                request_id, err
-                            );
+                    } else {
+                        warn!(
+                            "Could not fulfill request {:?} cuz room {} is not visible",
+                            request, room_name
+                        );
+                        visibility::request(room_name, None);
+                    }
+                }
+                Request {
+                    request_id,
+                    data: RequestData::Salvage(request_data),
+                } => {
+                    let source_room = rooms::get(room_name);
+                    if let Some(source_room) = source_room {
+                        let citizen = requests::Citizen {
+                            target_room_name: room_name,
+                            spawning_creep_name: None,
+                            initial_job: OokCreepJob::Salvage(jobs::Salvage {
+                                home_room: room_name,
+                                target_room: request_data.target_room_name,
+                                done: false,
+                            }),
+                            resolve_panic: false,
+                            spawn_elsewhere: false,
+                        };
+                        let result = spawn_citizen(
+                            &source_room,
+                            request_id.to_owned(),
+                            &citizen,
+                            &mut state.creep_names,
+                            &mut budget,
+                        );
+                        spawn_failures::note(request_id, room_name, &result);
+                        match result {
+                            Ok(TrySpawnResult::Spawned(TrySpawnResultData {
+                                return_code: ReturnCode::Ok,
+                                creep_name,
+                                ..
+                            })) => {
+                                let mut request_data = request_data.to_owned();
+                                request_data.spawning_creep_name = Some(creep_name);
+                                let request = Request {
+                                    request_id: request_id.to_owned(),
+                                    data: RequestData::Salvage(request_data),
+                                };
+                                state.request_handled(
+                                    request,
+                                    RequestHandledOpts::DelayHandleForOneTick,
+                                )?;
+                            }
+                            Ok(TrySpawnResult::Skipped) => {}
+                            Ok(_) => {
+                                info!("Could not spawn for request {:?}", request);
+                            }
+                            Err(err) => {
+                                warn!(
+                                    "Error spawning salvager for request {} : {}",
+                                    request_id, err
+                                );
+                            }
                         }
+                    } else {
+                        warn!(
+                            "Could not fulfill request {:?} cuz room {} is not visible",
+                            request, room_name
+                        );
+                        visibility::request(room_name, None);
                     }
-                } else {
-                    warn!(
-                        "Could not fulfill request {:?} cuz room {} is not visible",
-                        request, room_name
-                    );
                 }
             }
         }
@@ -523,36 +1102,58 @@ fn spawn_citizen(
     source_room: &Room,
     request_id: UniqId,
     request_data: &requests::Citizen,
+    names: &mut NameRegistry,
+    budget: &mut SpawnBudget,
 ) -> anyhow::Result<TrySpawnResult> {
-    let room_energy = source_room.energy_available();
-    let mut target_spawn_energy: u32 = source_room.energy_capacity_available();
+    let available_spawns = budget.free_spawns(source_room);
+    if available_spawns.is_empty() {
+        return Ok(TrySpawnResult::Skipped);
+    }
+    let room_energy = budget.energy_left(source_room);
+    let mut target_spawn_energy: u32 =
+        spawn_failures::body_energy(&request_id, source_room.energy_capacity_available());
     if request_data.resolve_panic {
-        target_spawn_energy = cmp::max(room_energy, 300);
+        // Nobody fills the extensions anymore, what is there now is all there is
+        target_spawn_energy = room_energy;
+    } else {
+        target_spawn_energy =
+            match fit_to_forecast(source_room, request_data, target_spawn_energy)? {
+                Some(energy) => energy,
+                None => return Ok(TrySpawnResult::Skipped),
+            };
     }
-    let (race_kind, parts) = if let Some(spawn_data) =
-        creep_spawn_options_from_job(&request_data.initial_job, target_spawn_energy)?
-    {
+    let spawn_data =
+        match creep_spawn_options_from_job(&request_data.initial_job, target_spawn_energy)? {
+            Some(spawn_data) => Some(spawn_data),
+            None if request_data.resolve_panic => {
+                panic_body(source_room, &request_data.initial_job)?
+            }
+            None => None,
+        };
+    let (race_kind, parts) = if let Some(spawn_data) = spawn_data {
         spawn_data
     } else {
         // Not enough energy
         return Ok(TrySpawnResult::Skipped);
     };
+    if budget.spent_energy > 0 && parts.iter().map(|p| p.cost()).sum::<u32>() > room_energy {
+        // Another spawn took the energy this tick, try again next tick
+        return Ok(TrySpawnResult::Skipped);
+    }
 
-    match race_kind {
+    let creep_name = names.next_name(race_kind);
+    let result = match race_kind {
         OokRaceKind::Worker => OokCreepWorker::try_spawn(
             &TrySpawnOptions {
                 assumed_job: request_data.initial_job.to_owned(),
-                available_spawns: source_room
-                    .find(find::MY_SPAWNS)
-                    .iter()
-                    .map(|s| s.id())
-                    .collect(),
+                available_spawns: available_spawns.to_owned(),
                 force_spawn: false,
                 race: race_kind,
                 spawn_room: &source_room,
                 target_energy_usage: target_spawn_energy,
                 request_id: Some(request_id.to_owned()),
                 preset_parts: Some(parts),
+                creep_name: creep_name.to_owned(),
             },
             &TrySpawnWorkerOptions {
                 post_ident: "XXX".into(),
@@ -565,11 +1166,7 @@ fn spawn_citizen(
         OokRaceKind::Carrier => OokCreepCarrier::try_spawn(
             &TrySpawnOptions {
                 assumed_job: request_data.initial_job.to_owned(),
-                available_spawns: source_room
-                    .find(find::MY_SPAWNS)
-                    .iter()
-                    .map(|s| s.id())
-                    .collect(),
+                available_spawns: available_spawns.to_owned(),
                 force_spawn: false,
                 race: race_kind,
                 spawn_room: &source_room,
@@ -577,6 +1174,7 @@ fn spawn_citizen(
                 request_id: Some(request_id.to_owned()),
                 // TODO Check if room has it covered with roads and adjust move parts to that
                 preset_parts: Some(parts),
+                creep_name: creep_name.to_owned(),
             },
             &TrySpawnCarrierOptions {
                 post_ident: "XXX".into(),
@@ -587,10 +1185,351 @@ fn spawn_citizen(
         OokRaceKind::CloseCombatDefender => {
             bail!("TODO spawn_citizen does not handle {:?} yet", race_kind)
         }
-        OokRaceKind::Claimer => bail!("TODO spawn_citizen does not handle {:?} yet", race_kind),
+        OokRaceKind::Claimer => OokCreepClaimer::try_spawn(
+            &TrySpawnOptions {
+                assumed_job: request_data.initial_job.to_owned(),
+                available_spawns: available_spawns.to_owned(),
+                force_spawn: false,
+                race: race_kind,
+                spawn_room: &source_room,
+                target_energy_usage: target_spawn_energy,
+                request_id: Some(request_id.to_owned()),
+                preset_parts: Some(parts),
+                creep_name: creep_name.to_owned(),
+            },
+            &TrySpawnClaimerOptions {
+                post_ident: "XXX".into(),
+            },
+        ),
+        OokRaceKind::Blocker => OokCreepBlocker::try_spawn(
+            &TrySpawnOptions {
+                assumed_job: request_data.initial_job.to_owned(),
+                available_spawns: available_spawns.to_owned(),
+                force_spawn: false,
+                race: race_kind,
+                spawn_room: &source_room,
+                target_energy_usage: target_spawn_energy,
+                request_id: Some(request_id.to_owned()),
+                preset_parts: Some(parts),
+                creep_name: creep_name.to_owned(),
+            },
+            &TrySpawnBlockerOptions {
+                post_ident: "XXX".into(),
+                base_room: request_data.target_room_name.to_owned(),
+            },
+        ),
+        OokRaceKind::Healer => OokCreepHealer::try_spawn(
+            &TrySpawnOptions {
+                assumed_job: request_data.initial_job.to_owned(),
+                available_spawns: available_spawns.to_owned(),
+                force_spawn: false,
+                race: race_kind,
+                spawn_room: &source_room,
+                target_energy_usage: target_spawn_energy,
+                request_id: Some(request_id.to_owned()),
+                preset_parts: Some(parts),
+                creep_name: creep_name.to_owned(),
+            },
+            &TrySpawnHealerOptions {
+                post_ident: "XXX".into(),
+                base_room: request_data.target_room_name.to_owned(),
+            },
+        ),
+        OokRaceKind::Dismantler => OokCreepDismantler::try_spawn(
+            &TrySpawnOptions {
+                assumed_job: request_data.initial_job.to_owned(),
+                available_spawns: available_spawns.to_owned(),
+                force_spawn: false,
+                race: race_kind,
+                spawn_room: &source_room,
+                target_energy_usage: target_spawn_energy,
+                request_id: Some(request_id.to_owned()),
+                preset_parts: Some(parts),
+                creep_name: creep_name.to_owned(),
+            },
+            &TrySpawnDismantlerOptions {
+                post_ident: "XXX".into(),
+                base_room: request_data.target_room_name.to_owned(),
+            },
+        ),
+        OokRaceKind::Harasser => OokCreepHarasser::try_spawn(
+            &TrySpawnOptions {
+                assumed_job: request_data.initial_job.to_owned(),
+                available_spawns: available_spawns.to_owned(),
+                force_spawn: false,
+                race: race_kind,
+                spawn_room: &source_room,
+                target_energy_usage: target_spawn_energy,
+                request_id: Some(request_id.to_owned()),
+                preset_parts: Some(parts),
+                creep_name: creep_name.to_owned(),
+            },
+            &TrySpawnHarasserOptions {
+                post_ident: "XXX".into(),
+                base_room: request_data.target_room_name.to_owned(),
+            },
+        ),
+    };
+    names.note_spawn(&result);
+    budget.note(&result);
+    record_spawn_cost(
+        request_data.target_room_name,
+        SpawnPurpose::of_citizen(request_data),
+        &request_id,
+        &result,
+    );
+    result
+}
+
+/// Energy to spawn the citizen with so the room can afford it, `None` defers the spawn
+///
+/// Big bodies get shrunk until the forecast of the room covers them, down to
+/// `config::SPAWN_FORECAST_MAX_SHRINK`, see `economy::ledger::forecast`.
+fn fit_to_forecast(
+    source_room: &Room,
+    request_data: &requests::Citizen,
+    target_spawn_energy: u32,
+) -> anyhow::Result<Option<u32>> {
+    match SpawnPurpose::of_citizen(request_data) {
+        SpawnPurpose::Defense | SpawnPurpose::Rescue => return Ok(Some(target_spawn_energy)),
+        _ => {}
+    }
+    let min_energy = (target_spawn_energy as f64 * config::SPAWN_FORECAST_MAX_SHRINK) as u32;
+    let mut energy = target_spawn_energy;
+    while energy >= min_energy {
+        let parts = match creep_spawn_options_from_job(&request_data.initial_job, energy)? {
+            Some((_, parts)) => parts,
+            None => return Ok(Some(energy)),
+        };
+        let cost: u32 = parts.iter().map(|p| p.cost()).sum();
+        if cost < config::SPAWN_FORECAST_MIN_ENERGY {
+            return Ok(Some(energy));
+        }
+        match ledger::forecast(source_room, cost, parts.get_spawn_time() as u32) {
+            Some(left) if left < 0. => {}
+            _ => {
+                if energy < target_spawn_energy {
+                    debug!(
+                        "{} can't afford {} energy for {:?}, shrunk to {}",
+                        source_room.name(),
+                        target_spawn_energy,
+                        request_data.initial_job.kind(),
+                        energy
+                    );
+                }
+                return Ok(Some(energy));
+            }
+        }
+        energy = energy * 3 / 4;
+    }
+    debug!(
+        "{} can't afford {:?} now, deferring it",
+        source_room.name(),
+        request_data.initial_job.kind()
+    );
+    Ok(None)
+}
+
+/// Books the energy of a successful spawn in the ledger, see `economy::ledger`
+fn record_spawn_cost(
+    room_name: RoomName,
+    purpose: SpawnPurpose,
+    request_id: &UniqId,
+    result: &anyhow::Result<TrySpawnResult>,
+) {
+    match result {
+        Ok(TrySpawnResult::Spawned(data)) | Ok(TrySpawnResult::ForceSpawned(data))
+            if data.return_code == ReturnCode::Ok =>
+        {
+            ledger::record_spawn(room_name, purpose, request_id, data.used_energy_amount);
+        }
+        _ => {}
+    }
+}
+
+/// The creep `source_room` would spawn for the job when its extensions are full, `None` if its
+/// energy capacity isn't enough for any body
+pub fn plan_citizen(
+    source_room: &Room,
+    job: &OokCreepJob,
+    base_room: RoomName,
+) -> anyhow::Result<Option<SpawnPlan>> {
+    let target_spawn_energy = source_room.energy_capacity_available();
+    let (race_kind, parts) = match creep_spawn_options_from_job(job, target_spawn_energy)? {
+        Some(spawn_data) => spawn_data,
+        None => return Ok(None),
+    };
+    let opts = TrySpawnOptions {
+        assumed_job: job.to_owned(),
+        available_spawns: source_room
+            .find(find::MY_SPAWNS)
+            .iter()
+            .map(|s| s.id())
+            .collect(),
+        force_spawn: false,
+        race: race_kind,
+        spawn_room: source_room,
+        target_energy_usage: target_spawn_energy,
+        request_id: None,
+        preset_parts: Some(parts),
+        creep_name: String::new(),
+    };
+    let plan = match race_kind {
+        OokRaceKind::Worker => OokCreepWorker::plan_spawn(
+            &opts,
+            &TrySpawnWorkerOptions {
+                post_ident: "XXX".into(),
+                base_room,
+            },
+        )?,
+        OokRaceKind::Carrier => OokCreepCarrier::plan_spawn(
+            &opts,
+            &TrySpawnCarrierOptions {
+                post_ident: "XXX".into(),
+                base_room,
+            },
+        )?,
+        OokRaceKind::Claimer => OokCreepClaimer::plan_spawn(
+            &opts,
+            &TrySpawnClaimerOptions {
+                post_ident: "XXX".into(),
+            },
+        )?,
+        OokRaceKind::Blocker => OokCreepBlocker::plan_spawn(
+            &opts,
+            &TrySpawnBlockerOptions {
+                post_ident: "XXX".into(),
+                base_room,
+            },
+        )?,
+        OokRaceKind::Healer => OokCreepHealer::plan_spawn(
+            &opts,
+            &TrySpawnHealerOptions {
+                post_ident: "XXX".into(),
+                base_room,
+            },
+        )?,
+        OokRaceKind::Dismantler => OokCreepDismantler::plan_spawn(
+            &opts,
+            &TrySpawnDismantlerOptions {
+                post_ident: "XXX".into(),
+                base_room,
+            },
+        )?,
+        OokRaceKind::Harasser => OokCreepHarasser::plan_spawn(
+            &opts,
+            &TrySpawnHarasserOptions {
+                post_ident: "XXX".into(),
+                base_room,
+            },
+        )?,
+        // No job spawns these, see `creep_spawn_options_from_job`
+        OokRaceKind::StaticWorker | OokRaceKind::Attacker | OokRaceKind::CloseCombatDefender => {
+            return Ok(None)
+        }
+    };
+    Ok(Some(plan))
+}
+
+/// Energy a blocker may cost at most
+const BLOCKER_MAX_ENERGY: u32 = 650;
+/// Energy a harasser may cost at most, it only has to outgun invaders
+const HARASSER_MAX_ENERGY: u32 = 1000;
+/// CLAIM parts a reserver gets at most. One only holds the reservation where it is, two make it
+/// grow.
+const RESERVER_MAX_UNITS: u32 = 2;
+/// CLAIM parts a claimer gets at most when it has to attack the reservation of someone else
+/// first. Each one takes a tick off the reservation per tick.
+const UNRESERVE_MAX_UNITS: u32 = 5;
+
+/// Bodies panic spawns fall back to when the composition of the race gives nothing for the
+/// energy there is, the cheapest first. Races without one aren't spawned in a panic.
+fn panic_bodies(race: OokRaceKind) -> &'static [&'static [Part]] {
+    use Part::*;
+    match race {
+        OokRaceKind::Worker => &[
+            &[Work, Carry, Move],
+            &[Work, Work, Carry, Move],
+            &[Work, Work, Carry, Carry, Move, Move],
+            &[Work, Work, Work, Carry, Carry, Move, Move, Move],
+        ],
+        OokRaceKind::Carrier => &[
+            &[Carry, Carry, Move],
+            &[Carry, Carry, Carry, Carry, Move, Move],
+            &[Carry, Carry, Carry, Carry, Carry, Carry, Move, Move, Move],
+        ],
+        OokRaceKind::Blocker => &[
+            &[Attack, Attack, Move, Move],
+            &[Attack, Attack, Attack, Move, Move, Move],
+        ],
+        OokRaceKind::Healer => &[&[Heal, Move]],
+        OokRaceKind::Harasser => &[
+            &[RangedAttack, Move, Move],
+            &[RangedAttack, RangedAttack, Move, Move],
+        ],
+        OokRaceKind::Dismantler => &[&[Work, Work, Move, Move]],
+        _ => &[],
     }
 }
 
+/// The biggest body of the panic ladder of the race of the job the room can spawn right now
+fn panic_body(
+    source_room: &Room,
+    job: &OokCreepJob,
+) -> anyhow::Result<Option<(OokRaceKind, Vec<Part>)>> {
+    let race = match creep_spawn_options_from_job(job, source_room.energy_capacity_available())? {
+        Some((race, _parts)) => race,
+        None => return Ok(None),
+    };
+    let room_energy = source_room.energy_available();
+    Ok(panic_bodies(race)
+        .iter()
+        .rev()
+        .find(|body| body.iter().map(|p| p.cost()).sum::<u32>() <= room_energy)
+        .map(|body| {
+            info!("Panic spawning a {:?} with {:?}", race, body);
+            (race, body.to_vec())
+        }))
+}
+
+/// The room is in sight and someone else than me holds the reservation of its controller, an NPC
+/// or another player
+fn is_reserved_by_others(room_name: RoomName) -> bool {
+    rooms::get(room_name)
+        .and_then(|room| room.controller())
+        .and_then(|controller| claim_controller::foreign_reservation_ticks(&controller))
+        .is_some()
+}
+
+/// Body of a farmer, capped at what saturates a source
+pub fn farmer_body(target_energy_usage: u32) -> Option<Vec<screeps::Part>> {
+    // TODO check for roads to improve comp
+    // TODO check for container / link to improve comp
+    let limit_work = cmp::min(target_energy_usage, 900);
+    OokRaceBodyComposition {
+        mov: 1,
+        carry: 0,
+        work: 2,
+        attack: 0,
+        ranged_attack: 0,
+        heal: 0,
+        tough: 0,
+        claim: 0,
+    }
+    .parts_for_x_energy(limit_work)
+    .map(|(parts, _energy)| parts)
+}
+
+/// Body of a dismantler with the TOUGH parts in front
+pub fn dismantler_body(target_energy_usage: u32) -> Option<Vec<screeps::Part>> {
+    dismantler::COMPOSITION
+        .parts_for_x_energy(target_energy_usage)
+        .map(|(mut parts, _energy)| {
+            parts.sort_by_key(|p| *p != screeps::Part::Tough);
+            parts
+        })
+}
+
 fn creep_spawn_options_from_job(
     job: &OokCreepJob,
     target_energy_usage: u32,
@@ -637,38 +1576,59 @@ fn creep_spawn_options_from_job(
             }
         }
         OokCreepJob::FarmSource(FarmSource { .. }) => {
-            // TODO check for roads to improve comp
-            // TODO check for container / link to improve comp
-            // TODO better composition handling, see OokRace::Worker, different
-            // target_energy_usage should use different kinds of composition
-            let limit_work = cmp::min(target_energy_usage, 900);
-            let comp = OokRaceBodyComposition {
-                mov: 1,
-                carry: 0,
-                work: 2,
-                attack: 0,
-                ranged_attack: 0,
-                heal: 0,
-                tough: 0,
-                claim: 0,
-            }
-            .parts_for_x_energy(limit_work);
-            if let Some((parts, _energy)) = comp {
-                Ok(Some((OokRaceKind::Worker, parts)))
-            } else {
-                Ok(None)
-            }
+            Ok(farmer_body(target_energy_usage).map(|parts| (OokRaceKind::Worker, parts)))
         }
         OokCreepJob::FarmExtensionRoom { .. } => {
             bail!("Unhandled job to create spawn options {:?}", job)
         }
-        OokCreepJob::LogisticsExtensionRoom { .. } => {
-            bail!("Unhandled job to create spawn options {:?}", job)
+        OokCreepJob::LogisticsExtensionRoom { extension_room, .. } => {
+            // Big enough to take all the remote makes during a round trip, as far as the energy
+            // allows
+            let carry = match remotes::hauler_carry_parts(*extension_room) {
+                Some(carry) => carry,
+                None => return Ok(None),
+            };
+            // Off roads they need a MOVE per CARRY to not crawl back loaded
+            let composition = if remotes::is_paved(*extension_room) {
+                carrier::COMPOSITION
+            } else {
+                OokRaceBodyComposition {
+                    mov: 1,
+                    carry: 1,
+                    work: 0,
+                    attack: 0,
+                    ranged_attack: 0,
+                    heal: 0,
+                    tough: 0,
+                    claim: 0,
+                }
+            };
+            let units = (carry + composition.carry - 1) / composition.carry;
+            let unit_cost = composition.single_parts_unit_cost();
+            Ok(composition
+                .parts_for_x_energy(cmp::min(target_energy_usage, units * unit_cost))
+                .map(|(parts, _energy)| (OokRaceKind::Carrier, parts)))
         }
         OokCreepJob::MaintainStructures { .. } => {
             bail!("Unhandled job to create spawn options {:?}", job)
         }
-        OokCreepJob::ClaimRoom { .. } => bail!("Unhandled job to create spawn options {:?}", job),
+        OokCreepJob::ClaimRoom { target_room } if is_reserved_by_others(*target_room) => {
+            // The reservation has to be attacked away first, every CLAIM part speeds that up
+            let unit_cost = claimer::COMPOSITION.single_parts_unit_cost();
+            Ok(claimer::COMPOSITION
+                .parts_for_x_energy(cmp::min(
+                    target_energy_usage,
+                    unit_cost * UNRESERVE_MAX_UNITS,
+                ))
+                .map(|(parts, _energy)| (OokRaceKind::Claimer, parts)))
+        }
+        OokCreepJob::ClaimRoom { .. } | OokCreepJob::CrossPortal(jobs::CrossPortal { .. }) => {
+            // A single CLAIM part does it, more only claim faster
+            let unit_cost = claimer::COMPOSITION.single_parts_unit_cost();
+            Ok(claimer::COMPOSITION
+                .parts_for_x_energy(cmp::min(target_energy_usage, unit_cost))
+                .map(|(parts, _energy)| (OokRaceKind::Claimer, parts)))
+        }
         OokCreepJob::BootstrapRoom { .. } => {
             // TODO check for roads to improve comp
             // TODO check for container / link to improve comp
@@ -691,6 +1651,42 @@ fn creep_spawn_options_from_job(
                 Ok(None)
             }
         }
+        OokCreepJob::DefendRoom { .. } => {
+            // Blockers only need to outlast the raid, keep them cheap
+            let limit_energy = cmp::min(target_energy_usage, BLOCKER_MAX_ENERGY);
+            Ok(blocker::COMPOSITION
+                .parts_for_x_energy(limit_energy)
+                .map(|(parts, _energy)| (OokRaceKind::Blocker, parts)))
+        }
+        OokCreepJob::HealCitizens { .. } => Ok(healer::COMPOSITION
+            .parts_for_x_energy(target_energy_usage)
+            .map(|(parts, _energy)| (OokRaceKind::Healer, parts))),
+        OokCreepJob::Dismantle(jobs::Dismantle { .. }) => {
+            Ok(dismantler_body(target_energy_usage).map(|parts| (OokRaceKind::Dismantler, parts)))
+        }
+        OokCreepJob::PatrolRemotes { .. } => {
+            let limit_energy = cmp::min(target_energy_usage, HARASSER_MAX_ENERGY);
+            Ok(harasser::COMPOSITION
+                .parts_for_x_energy(limit_energy)
+                .map(|(parts, _energy)| (OokRaceKind::Harasser, parts)))
+        }
+        // As big as the room affords, every trip home is a long one
+        OokCreepJob::Salvage(jobs::Salvage { .. }) => Ok(carrier::COMPOSITION
+            .parts_for_x_energy(target_energy_usage)
+            .map(|(parts, _energy)| (OokRaceKind::Carrier, parts))),
+        OokCreepJob::ReserveRoom { target_room } => {
+            let max_units = if is_reserved_by_others(*target_room) {
+                UNRESERVE_MAX_UNITS
+            } else {
+                RESERVER_MAX_UNITS
+            };
+            let unit_cost = claimer::COMPOSITION.single_parts_unit_cost();
+            Ok(claimer::COMPOSITION
+                .parts_for_x_energy(cmp::min(target_energy_usage, unit_cost * max_units))
+                .map(|(parts, _energy)| (OokRaceKind::Claimer, parts)))
+        }
+        // Only has to get there and stand around
+        OokCreepJob::ScoutRoom { .. } => Ok(Some((OokRaceKind::Claimer, vec![Part::Move]))),
     }
 }
 
@@ -707,6 +1703,11 @@ pub enum TargetSpawnKind {
     Carrier = 0,
     Farmer = 1,
     Worker = 2,
+    Defender = 3,
+    Healer = 4,
+    Dismantler = 5,
+    Harasser = 6,
+    Claimer = 7,
 }
 
 /// Should only be used if you pass the initial job
@@ -719,8 +1720,16 @@ impl From<OokCreepJob> for TargetSpawnKind {
             OokCreepJob::FarmExtensionRoom { .. } => TargetSpawnKind::Farmer,
             OokCreepJob::LogisticsExtensionRoom { .. } => TargetSpawnKind::Carrier,
             OokCreepJob::MaintainStructures { .. } => TargetSpawnKind::Worker,
-            OokCreepJob::ClaimRoom { .. } => TargetSpawnKind::Worker, // TODO
+            OokCreepJob::ClaimRoom { .. } => TargetSpawnKind::Claimer,
             OokCreepJob::BootstrapRoom { .. } => TargetSpawnKind::Worker,
+            OokCreepJob::DefendRoom { .. } => TargetSpawnKind::Defender,
+            OokCreepJob::HealCitizens { .. } => TargetSpawnKind::Healer,
+            OokCreepJob::Dismantle(jobs::Dismantle { .. }) => TargetSpawnKind::Dismantler,
+            OokCreepJob::PatrolRemotes { .. } => TargetSpawnKind::Harasser,
+            OokCreepJob::CrossPortal(jobs::CrossPortal { .. }) => TargetSpawnKind::Claimer,
+            OokCreepJob::Salvage(jobs::Salvage { .. }) => TargetSpawnKind::Carrier,
+            OokCreepJob::ReserveRoom { .. } => TargetSpawnKind::Claimer,
+            OokCreepJob::ScoutRoom { .. } => TargetSpawnKind::Claimer,
         }
     }
 }
@@ -735,8 +1744,16 @@ impl From<&OokCreepJob> for TargetSpawnKind {
             OokCreepJob::FarmExtensionRoom { .. } => TargetSpawnKind::Farmer,
             OokCreepJob::LogisticsExtensionRoom { .. } => TargetSpawnKind::Carrier,
             OokCreepJob::MaintainStructures { .. } => TargetSpawnKind::Worker,
-            OokCreepJob::ClaimRoom { .. } => TargetSpawnKind::Worker, // TODO
+            OokCreepJob::ClaimRoom { .. } => TargetSpawnKind::Claimer,
             OokCreepJob::BootstrapRoom { .. } => TargetSpawnKind::Worker,
+            OokCreepJob::DefendRoom { .. } => TargetSpawnKind::Defender,
+            OokCreepJob::HealCitizens { .. } => TargetSpawnKind::Healer,
+            OokCreepJob::Dismantle(jobs::Dismantle { .. }) => TargetSpawnKind::Dismantler,
+            OokCreepJob::PatrolRemotes { .. } => TargetSpawnKind::Harasser,
+            OokCreepJob::CrossPortal(jobs::CrossPortal { .. }) => TargetSpawnKind::Claimer,
+            OokCreepJob::Salvage(jobs::Salvage { .. }) => TargetSpawnKind::Carrier,
+            OokCreepJob::ReserveRoom { .. } => TargetSpawnKind::Claimer,
+            OokCreepJob::ScoutRoom { .. } => TargetSpawnKind::Claimer,
         }
     }
 }
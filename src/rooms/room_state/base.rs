@@ -1,4 +1,5 @@
 use std::{
+    cmp,
     collections::{HashMap, HashSet},
     convert::TryFrom,
     iter::FromIterator,
@@ -9,10 +10,10 @@ use anyhow::{anyhow, bail, Context};
 use log::{info, warn};
 use screeps::{
     find,
-    game::{self, creeps, get_object_typed, rooms},
+    game::{self, get_object_typed, rooms},
     memory::MemoryReference,
-    Creep, EventType, HasId, HasStore, ObjectId, ResourceType, RoomName, Source, Structure,
-    StructureTower,
+    Creep, EventType, HasId, HasPosition, HasStore, ObjectId, Part, ResourceType, Room, RoomName,
+    SharedCreepProperties, Source, Structure, StructureProperties, StructureType, CREEP_LIFE_TIME,
 };
 use serde::{Deserialize, Serialize};
 use stdweb::JsSerialize;
@@ -20,22 +21,30 @@ use stdweb::JsSerialize;
 use crate::{
     constants::{MEM_BASE_DATA, MEM_ROOM_NAME, MEM_ROOM_STATE_KIND},
     creeps::{
-        get_prio_repair_target,
         jobs::{self, OokCreepJob},
-        races::{carrier::OokCreepCarrier, worker::OokCreepWorker, OokRace, RepresentsCreep},
-        tasks::OokCreepTask,
-        RepairTarget,
+        races::{
+            carrier::OokCreepCarrier, worker::OokCreepWorker, DynamicTasked, OokRace,
+            RepresentsCreep,
+        },
+        tasks::{farm, OokCreepTask},
     },
+    deferred, economy,
     rooms::{
+        defense, events,
         extensions::{ExtensionFillPath, StructureSpawnSupply, SuppliersReachPoint},
-        room_state::{TargetSpawnKind, TargetSpawns},
+        harvest_stats, labor, planner, remotes,
+        room_state::{farmer_body, plan_citizen, spawn_failures, TargetSpawnKind, TargetSpawns},
+        stagger,
     },
+    server,
     state::{
+        get_object_cached,
         requests::{self, Request, RequestData},
         BWState, UniqId,
     },
-    trade,
+    switches, trade,
     utils::AnyhowOptionExt,
+    visuals::Painter,
 };
 
 use super::{
@@ -47,6 +56,12 @@ use super::{
 };
 
 const PANIC_THRESHOLD_TICKS: u32 = 100;
+/// WORK parts that harvest a full source before it regenerates
+const SOURCE_SATURATING_WORK: u32 = 5;
+/// Retry placing missing structures this often, e.g. if the construction site limit was hit
+const PLACE_STRUCTURES_INTERVAL: u32 = 500;
+/// Carriers a base keeps even if its targets say otherwise, somebody has to fill the spawn
+const MIN_CARRIERS: u8 = 1;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct BaseData {
@@ -54,6 +69,9 @@ pub struct BaseData {
     pub target_spawns: TargetSpawns,
     /// Creeps filling extensions & spawns right now
     pub supplier_fillers: Vec<ObjectId<Creep>>,
+    /// Controller level the structures were last placed for
+    #[serde(default)]
+    pub placed_for_rcl: u32,
 }
 
 js_serializable!(BaseData);
@@ -65,6 +83,7 @@ impl Default for BaseData {
             helping_citizens: vec![],
             target_spawns: Default::default(),
             supplier_fillers: vec![],
+            placed_for_rcl: 0,
         }
     }
 }
@@ -127,6 +146,31 @@ impl BaseState {
         Ok(Vec::from_iter(supplier_points))
     }
 
+    /// If a blocker or healer for this room waits on the spawn
+    pub fn defender_queued(&self, state: &BWState) -> bool {
+        self.open_requests
+            .iter()
+            .filter_map(|req| {
+                state
+                    .get_current_or_old_request(req.to_owned())
+                    .map(|(req, _id)| req)
+            })
+            .any(|request| match &request.data {
+                RequestData::Citizen(requests::Citizen {
+                    target_room_name,
+                    initial_job,
+                    ..
+                }) => {
+                    *target_room_name == self.room_name
+                        && matches!(
+                            TargetSpawnKind::from(initial_job),
+                            TargetSpawnKind::Defender | TargetSpawnKind::Healer
+                        )
+                }
+                _ => false,
+            })
+    }
+
     pub fn creep_handles_filling_extensions(
         &mut self,
         creep_id: ObjectId<Creep>,
@@ -144,6 +188,60 @@ impl BaseState {
     }
 }
 
+/// Farmers assigned to a source, `count` includes the `spawning` ones
+#[derive(Clone, Debug, Default)]
+struct SourceFarmers {
+    count: u32,
+    spawning: u32,
+    work: u32,
+    biggest_work: u32,
+}
+
+/// WORK parts of a farmer spawned with the full energy capacity of the room
+fn farmer_work_parts(energy_capacity: u32) -> u32 {
+    farmer_body(energy_capacity)
+        .map(|parts| parts.iter().filter(|p| **p == Part::Work).count() as u32)
+        .unwrap_or(0)
+}
+
+/// Early on small farmers can't saturate a source alone, so several of them share it on
+/// distinct farm positions. Once a single farmer is big enough, the others aren't replaced.
+fn wanted_farmers(
+    source: ObjectId<Source>,
+    farmers: &SourceFarmers,
+    work_per_farmer: u32,
+    positions: u32,
+) -> u32 {
+    if farmers.biggest_work >= SOURCE_SATURATING_WORK {
+        return 1;
+    }
+    if farmers.spawning > 0 {
+        // Wait for it to show up before deciding on more
+        return farmers.count;
+    }
+    if work_per_farmer >= SOURCE_SATURATING_WORK && farmers.count > 0 {
+        // One big farmer takes over, the small ones are left to die out
+        return cmp::min(farmers.count + 1, positions);
+    }
+    let mut wanted = if work_per_farmer == 0 || work_per_farmer >= SOURCE_SATURATING_WORK {
+        1
+    } else {
+        (SOURCE_SATURATING_WORK + work_per_farmer - 1) / work_per_farmer
+    };
+    if farmers.count >= wanted
+        && farmers.work < SOURCE_SATURATING_WORK
+        && harvest_stats::is_under_harvested(source)
+    {
+        info!(
+            "Source {} under-harvested ({:.0}%), requesting another farmer",
+            source,
+            harvest_stats::utilization(source).unwrap_or(0.) * 100.
+        );
+        wanted = farmers.count + 1;
+    }
+    cmp::max(1, cmp::min(wanted, positions))
+}
+
 impl BaseState {
     fn spawn_citizens_up_to_target(&self, state: &BWState) -> anyhow::Result<Vec<Request>> {
         let mut requests: Vec<Request> = vec![];
@@ -152,19 +250,58 @@ impl BaseState {
             worker: 0,
             carrier: 0,
         };
-        let mut unhandled_sources: HashSet<ObjectId<Source>> =
-            self.sources.iter().cloned().collect();
+        let mut source_farmers: HashMap<ObjectId<Source>, SourceFarmers> = self
+            .sources
+            .iter()
+            .map(|id| (*id, Default::default()))
+            .collect();
+        let mut blockers = 0;
+        let mut healers = 0;
+        let mut harassers = 0;
+        // Rooms reserved by living reservers, with the ticks they have left
+        let mut reservers: Vec<(RoomName, u32)> = vec![];
+        // CARRY of the living haulers per remote, and the remotes a hauler is spawned for
+        let mut hauler_carry: HashMap<RoomName, u32> = HashMap::new();
+        let mut spawning_haulers: Vec<RoomName> = vec![];
         for id in &self.data.helping_citizens {
             match state.citizens.get(id) {
-                Some(OokRace::Worker(OokCreepWorker {
-                    job: OokCreepJob::FarmSource(jobs::FarmSource { target_source, .. }),
-                    ..
-                })) => {
-                    unhandled_sources.remove(target_source);
+                Some(OokRace::Worker(worker)) => match &worker.job {
+                    OokCreepJob::FarmSource(jobs::FarmSource { target_source, .. }) => {
+                        let work = worker
+                            .creep()
+                            .map(|c| c.get_active_bodyparts(Part::Work))
+                            .unwrap_or(0);
+                        if let Some(farmers) = source_farmers.get_mut(target_source) {
+                            farmers.count += 1;
+                            farmers.work += work;
+                            farmers.biggest_work = cmp::max(farmers.biggest_work, work);
+                        }
+                    }
+                    _ => current_spawns.worker += 1,
+                },
+                Some(OokRace::Claimer(claimer)) => {
+                    if let OokCreepJob::ReserveRoom { target_room } = claimer.job() {
+                        let ticks_to_live = claimer
+                            .creep()
+                            .map(|c| c.ticks_to_live().unwrap_or(CREEP_LIFE_TIME))
+                            .unwrap_or(0);
+                        reservers.push((target_room, ticks_to_live));
+                    }
                 }
-                Some(OokRace::Worker(_)) => current_spawns.worker += 1,
-                Some(OokRace::Claimer(_)) => {}
-                Some(OokRace::Carrier(_)) => current_spawns.carrier += 1,
+                Some(OokRace::Carrier(carrier)) => match &carrier.job {
+                    OokCreepJob::LogisticsExtensionRoom { extension_room, .. } => {
+                        let carry = carrier
+                            .creep()
+                            .map(|c| c.get_active_bodyparts(Part::Carry))
+                            .unwrap_or(0);
+                        *hauler_carry.entry(*extension_room).or_default() += carry;
+                    }
+                    _ => current_spawns.carrier += 1,
+                },
+                Some(OokRace::Blocker(_)) => blockers += 1,
+                Some(OokRace::Healer(_)) => healers += 1,
+                Some(OokRace::Dismantler(_)) => {}
+                Some(OokRace::Harasser(_)) => harassers += 1,
                 None => {
                     warn!("Missing citizen for helping citizen {}", id);
                 }
@@ -203,19 +340,42 @@ impl BaseState {
                     if let OokCreepJob::FarmSource(jobs::FarmSource { target_source, .. }, ..) =
                         initial_job
                     {
-                        unhandled_sources.remove(target_source);
+                        if let Some(farmers) = source_farmers.get_mut(target_source) {
+                            farmers.count += 1;
+                            farmers.spawning += 1;
+                        }
+                    } else if let OokCreepJob::ReserveRoom { target_room } = initial_job {
+                        // Not even spawned, it has its whole life ahead
+                        reservers.push((*target_room, CREEP_LIFE_TIME));
+                    } else if let OokCreepJob::LogisticsExtensionRoom { extension_room, .. } =
+                        initial_job
+                    {
+                        spawning_haulers.push(*extension_room);
                     } else if *target_room_name == self.room_name {
                         match TargetSpawnKind::from(initial_job) {
                             TargetSpawnKind::Carrier => open_request_spawns.carrier += 1,
                             TargetSpawnKind::Farmer => {}
                             TargetSpawnKind::Worker => open_request_spawns.worker += 1,
+                            TargetSpawnKind::Defender => blockers += 1,
+                            TargetSpawnKind::Healer => healers += 1,
+                            TargetSpawnKind::Dismantler => {}
+                            TargetSpawnKind::Harasser => harassers += 1,
+                            TargetSpawnKind::Claimer => {}
                         }
                     }
                 }
+                RequestData::Dismantle(_) => {}
+                RequestData::InterShardClaim(_) => {}
+                RequestData::Salvage(_) => {}
             }
         }
 
-        if current_spawns.worker + open_request_spawns.worker < self.data.target_spawns.worker {
+        // Surplus energy and construction backlogs get spent by more workers
+        let target_workers = self.data.target_spawns.worker
+            + rooms::get(self.room_name)
+                .map(|room| economy::extra_workers(&room) + labor::backlog_extra_workers(&room))
+                .unwrap_or(0);
+        if current_spawns.worker + open_request_spawns.worker < target_workers {
             let new_request = Request::new(RequestData::Citizen(requests::Citizen {
                 target_room_name: self.room_name,
                 spawning_creep_name: None,
@@ -223,10 +383,12 @@ impl BaseState {
                     target_room: self.room_name,
                 },
                 resolve_panic: false,
+                spawn_elsewhere: false,
             }));
             requests.push(new_request);
         }
-        if current_spawns.carrier + open_request_spawns.carrier < self.data.target_spawns.carrier {
+        let target_carriers = cmp::max(self.data.target_spawns.carrier, MIN_CARRIERS);
+        if current_spawns.carrier + open_request_spawns.carrier < target_carriers {
             let new_request = Request::new(RequestData::Citizen(requests::Citizen {
                 target_room_name: self.room_name,
                 spawning_creep_name: None,
@@ -234,25 +396,163 @@ impl BaseState {
                     target_room: self.room_name,
                 },
                 resolve_panic: false,
+                spawn_elsewhere: false,
             }));
             requests.push(new_request);
         }
 
-        for unhandled_source in unhandled_sources {
+        let room = rooms::get(self.room_name).anyhow("spawn citizens room not found")?;
+        // Ranged attackers camping the walls get blocked at the ramparts
+        let wanted_blockers = defense::wanted_blockers(&room);
+        if blockers < wanted_blockers {
+            info!(
+                "Room {} raided, requesting blocker {}/{}",
+                self.room_name,
+                blockers + 1,
+                wanted_blockers
+            );
+            let new_request = Request::new(RequestData::Citizen(requests::Citizen {
+                target_room_name: self.room_name,
+                spawning_creep_name: None,
+                initial_job: OokCreepJob::DefendRoom {
+                    target_room: self.room_name,
+                },
+                resolve_panic: false,
+                spawn_elsewhere: false,
+            }));
+            requests.push(new_request);
+        }
+        let wanted_healers = defense::wanted_healers(&room);
+        if healers < wanted_healers {
+            info!(
+                "Room {} raided, requesting healer {}/{}",
+                self.room_name,
+                healers + 1,
+                wanted_healers
+            );
+            let new_request = Request::new(RequestData::Citizen(requests::Citizen {
+                target_room_name: self.room_name,
+                spawning_creep_name: None,
+                initial_job: OokCreepJob::HealCitizens {
+                    target_room: self.room_name,
+                },
+                resolve_panic: false,
+                spawn_elsewhere: false,
+            }));
+            requests.push(new_request);
+        }
+        // Invaders in the remotes get chased off before they kill the miners
+        let wanted_harassers = remotes::wanted_harassers(self.room_name);
+        if harassers < wanted_harassers {
+            info!(
+                "Remotes of {} threatened, requesting harasser {}/{}",
+                self.room_name,
+                harassers + 1,
+                wanted_harassers
+            );
+            let new_request = Request::new(RequestData::Citizen(requests::Citizen {
+                target_room_name: self.room_name,
+                spawning_creep_name: None,
+                initial_job: OokCreepJob::PatrolRemotes {
+                    target_room: self.room_name,
+                },
+                resolve_panic: false,
+                spawn_elsewhere: false,
+            }));
+            requests.push(new_request);
+        }
+
+        // Reservers get replaced before the old one dies, so the reservation never drops below the
+        // floor
+        for (remote, lead) in remotes::reservations_due(self.room_name) {
+            let covered = reservers
+                .iter()
+                .any(|(target_room, ticks_to_live)| *target_room == remote && *ticks_to_live > lead);
+            if covered || state.claims.is_held_by_other(remote, self.room_name) {
+                continue;
+            }
+            info!("Reservation of {} runs low, requesting reserver", remote);
+            let new_request = Request::new(RequestData::Citizen(requests::Citizen {
+                target_room_name: self.room_name,
+                spawning_creep_name: None,
+                initial_job: OokCreepJob::ReserveRoom {
+                    target_room: remote,
+                },
+                resolve_panic: false,
+                spawn_elsewhere: false,
+            }));
+            requests.push(new_request);
+        }
+
+        // Haulers take home all the remote makes during a round trip, one at a time
+        for (remote, carry) in remotes::hauling_due(self.room_name) {
+            let hauling = hauler_carry.get(&remote).cloned().unwrap_or(0);
+            if hauling >= carry || spawning_haulers.contains(&remote) {
+                continue;
+            }
+            info!("Haulers of {} have {}/{} CARRY, requesting hauler", remote, hauling, carry);
+            let new_request = Request::new(RequestData::Citizen(requests::Citizen {
+                target_room_name: self.room_name,
+                spawning_creep_name: None,
+                initial_job: OokCreepJob::LogisticsExtensionRoom {
+                    extension_room: remote,
+                    target_room: self.room_name,
+                },
+                resolve_panic: false,
+                spawn_elsewhere: false,
+            }));
+            requests.push(new_request);
+        }
+
+        let farm_positions = farm::farm_positions(&room)?;
+        let work_per_farmer = farmer_work_parts(room.energy_capacity_available());
+        for (source, farmers) in source_farmers {
+            let positions = farm_positions.get(&source).map(|p| p.len()).unwrap_or(1) as u32;
+            let wanted = wanted_farmers(source, &farmers, work_per_farmer, positions);
+            if farmers.count >= wanted {
+                continue;
+            }
             let target_room_name = self.room_name;
             let new_request = Request::new(RequestData::Citizen(requests::Citizen {
                 target_room_name,
                 spawning_creep_name: None,
                 initial_job: OokCreepJob::FarmSource(jobs::FarmSource {
                     target_room: target_room_name,
-                    target_source: unhandled_source,
+                    target_source: source,
                 }),
                 resolve_panic: false,
+                spawn_elsewhere: false,
             }));
             requests.push(new_request);
         }
 
-        Ok(requests)
+        // Bodies the room can't make yet, like reservers before RCL 3, come from a bigger base
+        let mut planned = Vec::with_capacity(requests.len());
+        for mut request in requests {
+            if let RequestData::Citizen(citizen) = &mut request.data {
+                match plan_citizen(&room, &citizen.initial_job, self.room_name) {
+                    Ok(Some(_)) => {}
+                    Ok(None) => {
+                        info!(
+                            "Room {} can't spawn for {:?}, asking for help",
+                            self.room_name, citizen.initial_job
+                        );
+                        citizen.spawn_elsewhere = true;
+                    }
+                    Err(err) => {
+                        // The other requests of the room still get out
+                        warn!(
+                            "Room {} can't plan {:?}, skipping it: {}",
+                            self.room_name, citizen.initial_job, err
+                        );
+                        continue;
+                    }
+                }
+            }
+            planned.push(request);
+        }
+
+        Ok(planned)
     }
 
     pub fn check_supplier_fillers(&mut self, citizens: &HashMap<ObjectId<Creep>, OokRace>) -> () {
@@ -279,7 +579,7 @@ impl BaseState {
             for supplier in &point.suppliers {
                 match supplier {
                     StructureSpawnSupply::Spawn(spawn_id) => {
-                        if let Some(spawn) = get_object_typed(*spawn_id)? {
+                        if let Some(spawn) = get_object_cached(*spawn_id)? {
                             if spawn.store_free_capacity(Some(ResourceType::Energy)) != 0 {
                                 suppliers_to_fill.insert(point.to_owned());
                                 continue;
@@ -287,7 +587,7 @@ impl BaseState {
                         }
                     }
                     StructureSpawnSupply::Extension(extension_id) => {
-                        if let Some(extension) = get_object_typed(*extension_id)? {
+                        if let Some(extension) = get_object_cached(*extension_id)? {
                             if extension.store_free_capacity(Some(ResourceType::Energy)) != 0 {
                                 suppliers_to_fill.insert(point.to_owned());
                                 continue;
@@ -301,61 +601,32 @@ impl BaseState {
         Ok(())
     }
 
-    fn handle_towers(&self) -> anyhow::Result<()> {
-        let room = rooms::get(self.room_name).anyhow("handle_towers room not found")?;
-        let structures = room.find(find::STRUCTURES);
-        let towers: Vec<StructureTower> = structures
-            .into_iter()
-            .filter_map(|s| match s {
-                Structure::Tower(t) => {
-                    if t.store_used_capacity(Some(ResourceType::Energy)) > 500 {
-                        Some(t)
-                    } else {
-                        None
-                    }
-                }
-                _ => None,
-            })
-            .collect();
+    /// If citizens wait on the spawn energy, the ones this room asked for and the ones other rooms
+    /// handed over to its spawns
+    fn is_spawn_queued(&self) -> bool {
+        !self.open_requests.is_empty() || spawn_failures::is_short_of_energy(self.room_name)
+    }
 
-        let enemies = room.find(find::HOSTILE_CREEPS);
-        if enemies.len() > 0 {
-            let structures = room.find(find::STRUCTURES);
-            let towers: Vec<StructureTower> = structures
-                .into_iter()
-                .filter_map(|s| match s {
-                    Structure::Tower(t) => {
-                        if t.store_used_capacity(Some(ResourceType::Energy)) > 0 {
-                            Some(t)
-                        } else {
-                            None
-                        }
-                    }
-                    _ => None,
-                })
-                .collect();
-            info!("tw {:?}", towers.len());
-            if let Some(target) = enemies.first() {
-                for (i, tower) in towers.iter().enumerate() {
-                    if i == 0 {
-                        if let Some(target) = enemies.last() {
-                            tower.attack(target);
-                        }
-                    } else {
-                        tower.attack(target);
-                    }
-                    warn!("Attacking {}", target.id());
-                }
-            }
-        } else {
-            match get_prio_repair_target(&room) {
-                Ok(Some(RepairTarget::Important { target })) => towers.iter().for_each(|t| {
-                    t.repair(&target);
-                }),
-                _ => {}
-            }
+    /// Catches up with destroyed structures right away instead of at the next update
+    pub fn handle_destroyed(&mut self, room: &Room, types: &[StructureType]) -> anyhow::Result<()> {
+        let supplier_gone = types
+            .iter()
+            .any(|ty| matches!(ty, StructureType::Spawn | StructureType::Extension));
+        if supplier_gone {
+            self.suppliers_fill_path = ExtensionFillPath::best_for_room(room);
+            self.update_suppliers()?;
         }
+        self.resource_providers = calc_resource_providers(room, self.is_spawn_queued())?
+            .into_iter()
+            .map(|prov| (prov.ident(), prov))
+            .collect();
+        Ok(())
+    }
 
+    fn handle_towers(&self) -> anyhow::Result<()> {
+        let room = rooms::get(self.room_name).anyhow("handle_towers room not found")?;
+        defense::drill::run(&room);
+        defense::towers::run(&room);
         Ok(())
     }
 
@@ -369,18 +640,16 @@ impl BaseState {
     }
 
     fn visualize(&self) {
-        if let Some(room) = rooms::get(self.room_name) {
-            let vis = room.visual();
-            for point in self.suppliers_fill_path.points.iter() {
-                vis.rect(
-                    point.pos.x() as f32 - 0.5,
-                    point.pos.y() as f32 - 0.5,
-                    1.,
-                    1.,
-                    None,
-                );
-                // vis.text(pos.0 as f32, pos.1 as f32, num.to_string(), None);
-            }
+        let painter = Painter::new(self.room_name);
+        for point in self.suppliers_fill_path.points.iter() {
+            painter.rect(
+                point.pos.x() as f32 - 0.5,
+                point.pos.y() as f32 - 0.5,
+                1.,
+                1.,
+                None,
+            );
+            // painter.text(pos.0 as f32, pos.1 as f32, num.to_string(), None);
         }
     }
 
@@ -421,9 +690,62 @@ impl BaseState {
         Ok(())
     }
 
+    /// Asks for a dismantler if structures are in the way and nobody tears them down yet
+    fn request_dismantling(&self, state: &BWState) -> Option<Request> {
+        if !stagger::is_due(self.room_name, PLACE_STRUCTURES_INTERVAL) {
+            return None;
+        }
+        let requested = self
+            .open_requests
+            .iter()
+            .filter_map(|id| state.get_current_or_old_request(id.to_owned()))
+            .any(|(request, _)| matches!(request.data, RequestData::Dismantle(_)));
+        let working = state.citizens.values().any(|citizen| {
+            matches!(citizen, OokRace::Dismantler(d) if d.job.target_room() == self.room_name)
+        });
+        if requested || working {
+            return None;
+        }
+        let room = rooms::get(self.room_name)?;
+        let misplaced = planner::misplaced_structures(&room);
+        if misplaced.is_empty() {
+            return None;
+        }
+        for structure in &misplaced {
+            info!(
+                "Room {}: {:?} at {} is in the way",
+                self.room_name,
+                structure.structure_type(),
+                structure.pos()
+            );
+        }
+        if !switches::is_dismantling_allowed() {
+            info!(
+                "Room {}: not dismantling, set Memory.ook_flags.dismantle to tear them down",
+                self.room_name
+            );
+            return None;
+        }
+        let targets: Vec<ObjectId<Structure>> = misplaced.iter().map(|s| s.id()).collect();
+        info!(
+            "Room {} has {} misplaced structures, requesting dismantler",
+            self.room_name,
+            targets.len()
+        );
+        Some(Request::new(RequestData::Dismantle(requests::Dismantle {
+            target_room_name: self.room_name,
+            targets,
+            spawning_creep_name: None,
+        })))
+    }
+
     fn trade(&self) {
+        if !server::has_market() {
+            return;
+        }
         if let Some(room) = rooms::get(self.room_name) {
             trade::get_energy(&room);
+            economy::sell_surplus(&room);
         }
     }
 }
@@ -433,7 +755,7 @@ impl RoomStateLifecycle<BaseState> for BaseState {
         let room = rooms::get(room_name).ok_or(anyhow!("Room not found to create BaseState"))?;
         // let my_room = MyRoom::by_room_name(room.name())
         //     .ok_or_else(|| Box::new(RoomStateError::MyRoomNotFound(format!("{}", room.name()))))?;
-        let resource_providers: HashMap<_, _> = calc_resource_providers(&room)?
+        let resource_providers: HashMap<_, _> = calc_resource_providers(&room, false)?
             .into_iter()
             .map(|prov| (prov.ident(), prov))
             .collect();
@@ -455,7 +777,7 @@ impl RoomStateLifecycle<BaseState> for BaseState {
 
     fn handle_events(&mut self, state: &mut BWState) -> anyhow::Result<Vec<Request>> {
         if let Some(room) = rooms::get(self.room_name) {
-            for event in room.get_event_log() {
+            for event in events::of(&room) {
                 let object_id = event.object_id;
                 match event.event {
                     EventType::Attack(_) => {}
@@ -492,20 +814,26 @@ impl RoomStateLifecycle<BaseState> for BaseState {
                 vec![]
             }
         };
+        let mut requests = spawn_requests;
+        if let Some(request) = self.request_dismantling(state) {
+            requests.push(request);
+        }
         // self.visualize();
         self.trade();
-        Ok(spawn_requests)
+        Ok(requests)
     }
 
     fn update(
         &mut self,
         handled_requests: &HashMap<u32, HashMap<UniqId, Request>>,
     ) -> anyhow::Result<RoomStateChange> {
+        profile_scope!("BaseState::update");
         let room = rooms::get(self.room_name);
         let mut state_change = RoomStateChange::None;
         if let Some(room) = room {
             // FIXME Only update things that need to be updated
-            let providers: HashMap<_, _> = calc_resource_providers(&room)?
+            // Citizens asked for and not spawned yet wait on the spawn energy
+            let providers: HashMap<_, _> = calc_resource_providers(&room, self.is_spawn_queued())?
                 .into_iter()
                 .map(|prov| (prov.ident(), prov))
                 .collect();
@@ -518,6 +846,17 @@ impl RoomStateLifecycle<BaseState> for BaseState {
             if room.find(find::MY_SPAWNS).len() < 1 {
                 state_change = RoomStateChange::Helpless;
             }
+            let level = room.controller().map(|c| c.level()).unwrap_or(0);
+            if level != self.data.placed_for_rcl
+                || stagger::is_due(self.room_name, PLACE_STRUCTURES_INTERVAL)
+            {
+                if level != self.data.placed_for_rcl {
+                    info!("Room {} changed to RCL {}", self.room_name, level);
+                }
+                // The maintenance queue picks up the new construction sites once they are placed
+                deferred::queue(deferred::Job::place_structures(self.room_name));
+                self.data.placed_for_rcl = level;
+            }
         } else {
             self.resource_providers = HashMap::new();
             // Cant see room, e.g. nothing in there
@@ -549,10 +888,12 @@ impl RoomStateLifecycle<BaseState> for BaseState {
                         Some(handled_req) => match &handled_req.data {
                             RequestData::BootstrapWorkerCitizen(
                                 requests::BootstrapWorkerCitizen {
-                                    target_room_name,
-                                    spawning_creep_name: Some(spawning_creep_name),
+                                    target_room_name, ..
                                 },
-                            ) => {
+                            )
+                            | RequestData::Citizen(requests::Citizen {
+                                target_room_name, ..
+                            }) => {
                                 info!("Closing request");
                                 closed_requests.push(i);
                                 should_update_suppliers = true;
@@ -562,7 +903,7 @@ impl RoomStateLifecycle<BaseState> for BaseState {
                                         self.room_name, target_room_name
                                     );
                                 }
-                                match creeps::get(&spawning_creep_name)
+                                match requests::creep_of(open_request)
                                     .map(|c| OokRace::try_from(&c))
                                 {
                                     Some(Ok(creep)) => {
@@ -572,54 +913,21 @@ impl RoomStateLifecycle<BaseState> for BaseState {
                                         warn!("Couldnt convert creep for handled request! {}", err);
                                     }
                                     None => {
-                                        warn!("Couldnt find creep for handled request! Creep name: {}", spawning_creep_name);
+                                        warn!("Couldnt find creep for handled request {}", open_request);
                                     }
                                 }
                             }
-                            RequestData::BootstrapWorkerCitizen(
-                                requests::BootstrapWorkerCitizen {
-                                    spawning_creep_name: None,
-                                    ..
-                                },
-                            ) => {
-                                warn!(
-                                    "Handled request for BootstrapWorkerCitizen ha sno spawning_creep_name"
-                                );
+                            RequestData::Dismantle(_) => {
+                                // The dismantler works on its own, it isn't helping the room
+                                closed_requests.push(i);
                             }
-                            RequestData::Citizen(requests::Citizen {
-                                target_room_name,
-                                spawning_creep_name: Some(spawning_creep_name),
-                                ..
-                            }) => {
-                                info!("Closing request");
+                            RequestData::InterShardClaim(_) => {
+                                // The claimer leaves the shard, it isn't helping the room
                                 closed_requests.push(i);
-                                should_update_suppliers = true;
-                                if *target_room_name != self.room_name {
-                                    warn!(
-                                        "WTF, handling different target room?! {} // {}",
-                                        self.room_name, target_room_name
-                                    );
-                                }
-                                match creeps::get(&spawning_creep_name)
-                                    .map(|c| OokRace::try_from(&c))
-                                {
-                                    Some(Ok(creep)) => {
-                                        self.data.helping_citizens.push(creep.creep()?.id());
-                                    }
-                                    Some(Err(err)) => {
-                                        warn!("Couldnt convert creep for handled request! {}", err);
-                                    }
-                                    None => {
-                                        warn!("Couldnt find creep for handled request! Creep name: {}", spawning_creep_name);
-                                    }
-                                }
                             }
-                            RequestData::Citizen(requests::Citizen {
-                                spawning_creep_name: None,
-                                ..
-                            }) => {
-                                should_update_suppliers = true;
-                                warn!("Handled request for Citizen has no spawning_creep_name");
+                            RequestData::Salvage(_) => {
+                                // The salvager works on its own
+                                closed_requests.push(i);
                             }
                         },
                         None => {}
@@ -645,6 +953,33 @@ impl RoomStateLifecycle<BaseState> for BaseState {
     fn request_logged(&mut self, request_id: UniqId) {
         self.open_requests.push(request_id);
     }
+
+    fn forget_requests(&mut self) {
+        self.open_requests.clear();
+    }
+
+    fn on_enter(&mut self, _state: &mut BWState) -> anyhow::Result<()> {
+        info!("Room {} is a base now", self.room_name);
+        // Citizens handed over by the state before may have died since
+        self.data
+            .helping_citizens
+            .retain(|id| matches!(get_object_cached(*id), Ok(Some(_))));
+        if let Some(room) = rooms::get(self.room_name) {
+            self.resource_providers = calc_resource_providers(&room, false)?
+                .into_iter()
+                .map(|prov| (prov.ident(), prov))
+                .collect();
+        }
+        Ok(())
+    }
+
+    fn on_exit(&mut self, state: &mut BWState) -> anyhow::Result<()> {
+        // Requests not spawning yet would spawn for a state that is gone
+        for request_id in self.open_requests.drain(..) {
+            state.requests.remove(&request_id);
+        }
+        Ok(())
+    }
 }
 
 impl RoomStatePersistable<Self> for BaseState {
@@ -0,0 +1,183 @@
+//! Sequences the first steps of a fresh room, optimized for reaching RCL3 as fast as possible:
+//! small all-rounders first, then containers and extensions, then specialized citizens.
+use log::{info, warn};
+use screeps::{
+    find, game, HasPosition, Part, Position, ReturnCode, Room, SharedCreepProperties, SpawnOptions,
+    Structure, StructureType,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    constants::CREEP_ID_OPENER,
+    rooms::{farm_spots, planner, sites, spawn_area::spawn_directions},
+};
+
+/// Sources at RCL1 usually have ~3 free tiles around them
+const OPENER_CREEPS_PER_SOURCE: usize = 3;
+const OPENER_BODY: [Part; 4] = [Part::Work, Part::Carry, Part::Move, Part::Move];
+
+const MEM_STATS_OPENER: &str = "stats.opener";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OpenerPhase {
+    /// Spawn all-rounders that harvest, fill the spawn and upgrade
+    AllRounders,
+    /// RCL2: keep the all-rounders, place a container at each source and the extensions
+    Infrastructure,
+    /// Containers are there, farmers and carriers take over
+    Specialize,
+    /// RCL3 reached
+    Done,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Opener {
+    pub phase: OpenerPhase,
+    pub started_at: u32,
+    pub rcl2_at: Option<u32>,
+    pub rcl3_at: Option<u32>,
+}
+
+impl Opener {
+    pub fn new() -> Opener {
+        Opener {
+            phase: OpenerPhase::AllRounders,
+            started_at: game::time(),
+            rcl2_at: None,
+            rcl3_at: None,
+        }
+    }
+
+    /// Specialized citizens are spawned by the room state, not by the opener
+    pub fn spawns_all_rounders(&self) -> bool {
+        match self.phase {
+            OpenerPhase::AllRounders | OpenerPhase::Infrastructure => true,
+            OpenerPhase::Specialize | OpenerPhase::Done => false,
+        }
+    }
+
+    /// Moves to the next phase if the room is ready for it
+    pub fn update(&mut self, room: &Room) {
+        let level = room.controller().map(|c| c.level()).unwrap_or(0);
+        let time = game::time();
+        if level >= 2 && self.rcl2_at.is_none() {
+            self.rcl2_at = Some(time);
+        }
+        let next = match self.phase {
+            OpenerPhase::AllRounders if level >= 2 => Some(OpenerPhase::Infrastructure),
+            OpenerPhase::Infrastructure if sources_have_containers(room) => {
+                Some(OpenerPhase::Specialize)
+            }
+            OpenerPhase::Specialize if level >= 3 => Some(OpenerPhase::Done),
+            _ => None,
+        };
+        if let Some(next) = next {
+            info!(
+                "Opener in {}: {:?} -> {:?} after {} ticks",
+                room.name(),
+                self.phase,
+                next,
+                time - self.started_at
+            );
+            self.phase = next;
+        }
+        if self.phase == OpenerPhase::Done && self.rcl3_at.is_none() {
+            self.rcl3_at = Some(time);
+            self.persist_stats(room);
+        }
+    }
+
+    /// Spawns the all-rounders and places the construction sites of the current phase
+    pub fn run(&self, room: &Room) -> anyhow::Result<()> {
+        if self.spawns_all_rounders() {
+            spawn_all_rounder(room)?;
+        }
+        if self.phase == OpenerPhase::Infrastructure {
+            place_source_containers(room);
+            place_extensions(room);
+        }
+        Ok(())
+    }
+
+    fn persist_stats(&self, room: &Room) {
+        let ticks_to_rcl3 = self.rcl3_at.unwrap_or(0) - self.started_at;
+        info!("Opener in {} reached RCL3 after {} ticks", room.name(), ticks_to_rcl3);
+        let path = format!("{}.{}", MEM_STATS_OPENER, room.name());
+        screeps::memory::root().path_set(&format!("{}.ticks_to_rcl3", path), ticks_to_rcl3);
+        if let Some(rcl2_at) = self.rcl2_at {
+            screeps::memory::root()
+                .path_set(&format!("{}.ticks_to_rcl2", path), rcl2_at - self.started_at);
+        }
+    }
+}
+
+fn spawn_all_rounder(room: &Room) -> anyhow::Result<()> {
+    let all_rounders = room
+        .find(find::MY_CREEPS)
+        .into_iter()
+        .filter(|c| c.name().starts_with(CREEP_ID_OPENER))
+        .count();
+    let target = room.find(find::SOURCES).len() * OPENER_CREEPS_PER_SOURCE;
+    if all_rounders >= target {
+        return Ok(());
+    }
+    let body_cost: u32 = OPENER_BODY.iter().map(|p| p.cost()).sum();
+    if room.energy_available() < body_cost {
+        return Ok(());
+    }
+    let spawn = match room
+        .find(find::MY_SPAWNS)
+        .into_iter()
+        .find(|s| s.spawning().is_none())
+    {
+        Some(spawn) => spawn,
+        None => return Ok(()),
+    };
+    let name = format!("{}-{}", CREEP_ID_OPENER, game::time());
+    let options = SpawnOptions::new().directions(&spawn_directions(&spawn));
+    match spawn.spawn_creep_with_options(&OPENER_BODY, &name, &options) {
+        ReturnCode::Ok => info!("Spawning opener {} ({}/{})", name, all_rounders + 1, target),
+        code => warn!("Couldn't spawn opener: {:?}", code),
+    }
+    Ok(())
+}
+
+fn has_container_near(room: &Room, pos: &Position) -> bool {
+    let built = room
+        .find(find::STRUCTURES)
+        .into_iter()
+        .any(|s| matches!(s, Structure::Container(_)) && s.pos().is_near_to(pos));
+    let planned = room
+        .find(find::MY_CONSTRUCTION_SITES)
+        .into_iter()
+        .any(|s| s.structure_type() == StructureType::Container && s.pos().is_near_to(pos));
+    let queued = planner::neighbours(pos, 1).iter().any(sites::is_queued);
+    built || planned || queued
+}
+
+fn sources_have_containers(room: &Room) -> bool {
+    room.find(find::SOURCES).into_iter().all(|source| {
+        room.find(find::STRUCTURES)
+            .into_iter()
+            .any(|s| matches!(s, Structure::Container(_)) && s.pos().is_near_to(&source))
+    })
+}
+
+/// A container next to each source, on the tile farmers prefer
+fn place_source_containers(room: &Room) {
+    for source in room.find(find::SOURCES) {
+        let source_pos = source.pos();
+        if has_container_near(room, &source_pos) {
+            continue;
+        }
+        if let Some(pos) = farm_spots::container_spot(room, &source_pos) {
+            sites::request(pos, StructureType::Container);
+        }
+    }
+}
+
+/// Extensions are what makes bigger creeps possible, so they go first
+fn place_extensions(room: &Room) {
+    let missing = planner::missing_structures(room, StructureType::Extension);
+    planner::place_near_spawn(room, StructureType::Extension, missing);
+}
@@ -9,23 +9,26 @@ use anyhow::{anyhow, bail, Context};
 use log::{info, warn};
 use screeps::{
     find,
-    game::{self, creeps, get_object_typed, rooms},
+    game::{self, get_object_typed, rooms},
     memory::MemoryReference,
-    Creep, EventType, HasId, HasStore, ObjectId, ResourceType, RoomName, Source, Structure,
-    StructureTower,
+    Creep, EventType, HasId, ObjectId, Room, RoomName, Source, StructureType,
 };
 use stdweb::JsSerialize;
 
 use crate::{
+    config,
     constants::{MEM_BASE_DATA, MEM_ROOM_NAME, MEM_ROOM_STATE_KIND},
     creeps::{
-        get_prio_repair_target,
         jobs::{self, OokCreepJob},
         races::{worker::OokCreepWorker, OokRace, RepresentsCreep},
-        RepairTarget,
     },
-    rooms::room_state::TargetSpawns,
+    rooms::{
+        defense, events,
+        room_state::{spawn_failures, TargetSpawns},
+        structures, visibility,
+    },
     state::{
+        get_object_cached,
         requests::{self, Request, RequestData},
         BWState, UniqId,
     },
@@ -37,6 +40,7 @@ use super::{
         resource_provider::{calc_resource_providers, ResourceProvider},
         room_state::{RoomStateKind, RoomStateLifecycle, RoomStatePersistable},
     },
+    opener::Opener,
     RoomStateChange, TargetSpawnKind,
 };
 
@@ -46,6 +50,9 @@ const PANIC_THRESHOLD_TICKS: u32 = 100;
 pub struct SetupBaseData {
     pub helping_citizens: Vec<ObjectId<Creep>>,
     pub target_spawns: TargetSpawns,
+    /// Only set for a freshly spawned room, see `Opener`
+    #[serde(default)]
+    pub opener: Option<Opener>,
 }
 
 js_serializable!(SetupBaseData);
@@ -56,6 +63,7 @@ impl Default for SetupBaseData {
         SetupBaseData {
             helping_citizens: vec![],
             target_spawns: Default::default(),
+            opener: None,
         }
     }
 }
@@ -76,6 +84,19 @@ pub struct SetupBaseState {
 }
 
 impl SetupBaseState {
+    /// A room that has to start from nothing, see `Opener`
+    pub fn with_opener(room_name: RoomName) -> anyhow::Result<SetupBaseState> {
+        let mut state = SetupBaseState::new(room_name)?;
+        state.data.opener = Some(Opener::new());
+        // Farmers are spawned per source anyway, these come after them
+        state.data.target_spawns = TargetSpawns {
+            farmer: 0,
+            worker: 2,
+            carrier: 2,
+        };
+        Ok(state)
+    }
+
     fn spawn_citizens_up_to_target(&self, state: &BWState) -> anyhow::Result<Vec<Request>> {
         let mut requests: Vec<Request> = vec![];
 
@@ -100,6 +121,10 @@ impl SetupBaseState {
                 }
                 Some(OokRace::Worker(_)) => current_spawns.worker += 1,
                 Some(OokRace::Claimer(_)) => {}
+                Some(OokRace::Blocker(_)) => {}
+                Some(OokRace::Healer(_)) => {}
+                Some(OokRace::Dismantler(_)) => {}
+                Some(OokRace::Harasser(_)) => {}
                 Some(OokRace::Carrier(_)) => current_spawns.carrier += 1,
                 None => {
                     warn!("Missing citizen for helping citizen {}", id);
@@ -145,9 +170,17 @@ impl SetupBaseState {
                             TargetSpawnKind::Carrier => open_request_spawns.carrier += 1,
                             TargetSpawnKind::Farmer => {}
                             TargetSpawnKind::Worker => open_request_spawns.worker += 1,
+                            TargetSpawnKind::Defender => {}
+                            TargetSpawnKind::Healer => {}
+                            TargetSpawnKind::Dismantler => {}
+                            TargetSpawnKind::Harasser => {}
+                            TargetSpawnKind::Claimer => {}
                         }
                     }
                 }
+                RequestData::Dismantle(_) => {}
+                RequestData::InterShardClaim(_) => {}
+                RequestData::Salvage(_) => {}
             }
         }
 
@@ -169,6 +202,7 @@ impl SetupBaseState {
                     target_source: unhandled_source.clone(),
                 }),
                 resolve_panic: false,
+                spawn_elsewhere: false,
             }));
             requests.push(new_request);
         }
@@ -183,6 +217,7 @@ impl SetupBaseState {
                         target_room: self.room_name,
                     },
                     resolve_panic: false,
+                    spawn_elsewhere: false,
                 }));
                 requests.push(new_request);
             }
@@ -196,6 +231,7 @@ impl SetupBaseState {
                         target_room: self.room_name,
                     },
                     resolve_panic: false,
+                    spawn_elsewhere: false,
                 }));
                 requests.push(new_request);
             }
@@ -235,6 +271,7 @@ impl SetupBaseState {
                     target_room_name,
                     initial_job,
                     resolve_panic: true,
+                    spawn_elsewhere: false,
                     ..
                 }) => {
                     if let OokCreepJob::FarmSource(jobs::FarmSource { .. }, ..) = initial_job {
@@ -244,6 +281,11 @@ impl SetupBaseState {
                             TargetSpawnKind::Carrier => have_carrier = true,
                             TargetSpawnKind::Farmer => {}
                             TargetSpawnKind::Worker => {}
+                            TargetSpawnKind::Defender => {}
+                            TargetSpawnKind::Healer => {}
+                            TargetSpawnKind::Dismantler => {}
+                            TargetSpawnKind::Harasser => {}
+                            TargetSpawnKind::Claimer => {}
                         }
                     }
                 }
@@ -263,6 +305,7 @@ impl SetupBaseState {
                             target_source: *source,
                         }),
                         resolve_panic: true,
+                        spawn_elsewhere: false,
                     }));
                     requests.push(new_request);
                 }
@@ -276,6 +319,7 @@ impl SetupBaseState {
                     target_room: self.room_name,
                 },
                 resolve_panic: true,
+                spawn_elsewhere: false,
             }));
             requests.push(new_request);
         }
@@ -284,59 +328,7 @@ impl SetupBaseState {
 
     fn handle_towers(&self) -> anyhow::Result<()> {
         let room = rooms::get(self.room_name).anyhow("handle_towers room not found")?;
-        let structures = room.find(find::STRUCTURES);
-        let towers: Vec<StructureTower> = structures
-            .into_iter()
-            .filter_map(|s| match s {
-                Structure::Tower(t) => {
-                    if t.store_used_capacity(Some(ResourceType::Energy)) > 500 {
-                        Some(t)
-                    } else {
-                        None
-                    }
-                }
-                _ => None,
-            })
-            .collect();
-
-        let enemies = room.find(find::HOSTILE_CREEPS);
-        if enemies.len() > 0 {
-            let structures = room.find(find::STRUCTURES);
-            let towers: Vec<StructureTower> = structures
-                .into_iter()
-                .filter_map(|s| match s {
-                    Structure::Tower(t) => {
-                        if t.store_used_capacity(Some(ResourceType::Energy)) > 0 {
-                            Some(t)
-                        } else {
-                            None
-                        }
-                    }
-                    _ => None,
-                })
-                .collect();
-            info!("tw {:?}", towers.len());
-            if let Some(target) = enemies.first() {
-                for (i, tower) in towers.iter().enumerate() {
-                    if i == 0 {
-                        if let Some(target) = enemies.last() {
-                            tower.attack(target);
-                        }
-                    } else {
-                        tower.attack(target);
-                    }
-                    warn!("Attacking {}", target.id());
-                }
-            }
-        } else {
-            match get_prio_repair_target(&room) {
-                Ok(Some(RepairTarget::Important { target })) => towers.iter().for_each(|t| {
-                    t.repair(&target);
-                }),
-                _ => {}
-            }
-        }
-
+        defense::towers::run(&room);
         Ok(())
     }
 
@@ -378,6 +370,49 @@ impl SetupBaseState {
         Ok(())
     }
 
+    /// The room can go on as a base: it spawns itself, has extensions and somewhere to store the
+    /// energy, and its farmers and carriers are all there
+    fn is_set_up(&self) -> bool {
+        let room = match rooms::get(self.room_name) {
+            Some(room) => room,
+            None => return false,
+        };
+        let has_store = room.storage().is_some()
+            || !structures::ids(self.room_name, StructureType::Container).is_empty();
+        !room.find(find::MY_SPAWNS).is_empty()
+            && structures::ids(self.room_name, StructureType::Extension).len()
+                >= config::SETUP_FINISHED_MIN_EXTENSIONS
+            && has_store
+            && self.panic_countdown.is_none()
+            && self.has_crew(&room)
+    }
+
+    /// Every source has a farmer and at least one carrier is alive
+    fn has_crew(&self, room: &Room) -> bool {
+        let citizens: Vec<OokRace> = self
+            .data
+            .helping_citizens
+            .iter()
+            .filter_map(|id| get_object_cached(*id).ok().flatten())
+            .filter_map(|creep| OokRace::try_from(&creep).ok())
+            .collect();
+        let farmed: Vec<ObjectId<Source>> = citizens
+            .iter()
+            .filter_map(|citizen| match citizen {
+                OokRace::Worker(OokCreepWorker {
+                    job: OokCreepJob::FarmSource(jobs::FarmSource { target_source, .. }),
+                    ..
+                }) => Some(*target_source),
+                _ => None,
+            })
+            .collect();
+        citizens.iter().any(|c| matches!(c, OokRace::Carrier(_)))
+            && room
+                .find(find::SOURCES)
+                .iter()
+                .all(|source| farmed.contains(&source.id()))
+    }
+
     fn panicing(&self) -> bool {
         if let Some(panic_countdown) = self.panic_countdown {
             warn!("Panicing in room {}", self.room_name);
@@ -392,7 +427,7 @@ impl RoomStateLifecycle<SetupBaseState> for SetupBaseState {
     fn new(room_name: RoomName) -> anyhow::Result<SetupBaseState> {
         match rooms::get(room_name) {
             Some(room) => {
-                let resource_providers: HashMap<_, _> = calc_resource_providers(&room)?
+                let resource_providers: HashMap<_, _> = calc_resource_providers(&room, false)?
                     .into_iter()
                     .map(|prov| (prov.ident(), prov))
                     .collect();
@@ -424,7 +459,7 @@ impl RoomStateLifecycle<SetupBaseState> for SetupBaseState {
     // TODO use dis
     fn handle_events(&mut self, state: &mut BWState) -> anyhow::Result<Vec<Request>> {
         if let Some(room) = rooms::get(self.room_name) {
-            for event in room.get_event_log() {
+            for event in events::of(&room) {
                 let object_id = event.object_id;
                 match event.event {
                     EventType::Attack(_) => {}
@@ -451,6 +486,17 @@ impl RoomStateLifecycle<SetupBaseState> for SetupBaseState {
         if let Err(err) = self.handle_towers() {
             warn!("Error executing handle_towers: {}", err);
         }
+        if let Some(opener) = &self.data.opener {
+            if let Some(room) = rooms::get(self.room_name) {
+                if let Err(err) = opener.run(&room) {
+                    warn!("Error running opener in room {}: {}", self.room_name, err);
+                }
+            }
+            // The opener does the spawning until it is time to specialize
+            if opener.spawns_all_rounders() {
+                return Ok(vec![]);
+            }
+        }
         let spawn_requests = match self.spawn_citizens_up_to_target(state) {
             Ok(spawn_requests) => spawn_requests,
             Err(err) => {
@@ -470,8 +516,15 @@ impl RoomStateLifecycle<SetupBaseState> for SetupBaseState {
     ) -> anyhow::Result<RoomStateChange> {
         let room = rooms::get(self.room_name);
         if let Some(room) = room {
+            if let Some(opener) = &mut self.data.opener {
+                opener.update(&room);
+            }
             // FIXME Only update things that need to be updated
-            let providers: HashMap<_, _> = calc_resource_providers(&room)?
+            // Citizens asked for and not spawned yet wait on the spawn energy, so do the ones other
+            // rooms handed over to this one
+            let spawn_queued = !self.open_requests.is_empty()
+                || spawn_failures::is_short_of_energy(self.room_name);
+            let providers: HashMap<_, _> = calc_resource_providers(&room, spawn_queued)?
                 .into_iter()
                 .map(|prov| (prov.ident(), prov))
                 .collect();
@@ -485,6 +538,7 @@ impl RoomStateLifecycle<SetupBaseState> for SetupBaseState {
             };
         } else {
             self.state = SetupBaseStateVisibility::NotVisible {};
+            visibility::request(self.room_name, None);
         }
 
         let mut gone_citizens: Vec<usize> = vec![];
@@ -511,10 +565,12 @@ impl RoomStateLifecycle<SetupBaseState> for SetupBaseState {
                         Some(handled_req) => match &handled_req.data {
                             RequestData::BootstrapWorkerCitizen(
                                 requests::BootstrapWorkerCitizen {
-                                    target_room_name,
-                                    spawning_creep_name: Some(spawning_creep_name),
+                                    target_room_name, ..
                                 },
-                            ) => {
+                            )
+                            | RequestData::Citizen(requests::Citizen {
+                                target_room_name, ..
+                            }) => {
                                 info!("Closing request");
                                 closed_requests.push(i);
                                 if *target_room_name != self.room_name {
@@ -523,64 +579,31 @@ impl RoomStateLifecycle<SetupBaseState> for SetupBaseState {
                                         self.room_name, target_room_name
                                     );
                                 }
-                                match creeps::get(&spawning_creep_name)
+                                match requests::creep_of(open_request)
                                     .map(|c| OokRace::try_from(&c))
                                 {
                                     Some(Ok(creep)) => {
                                         self.data.helping_citizens.push(creep.creep()?.id());
-                                        info!("OKOKOKOKO push");
                                     }
                                     Some(Err(err)) => {
                                         warn!("Couldnt convert creep for handled request! {}", err);
                                     }
                                     None => {
-                                        warn!("Couldnt find creep for handled request! Creep name: {}", spawning_creep_name);
+                                        warn!("Couldnt find creep for handled request {}", open_request);
                                     }
                                 }
                             }
-                            RequestData::BootstrapWorkerCitizen(
-                                requests::BootstrapWorkerCitizen {
-                                    spawning_creep_name: None,
-                                    ..
-                                },
-                            ) => {
-                                warn!(
-                                    "Handled request for BootstrapWorkerCitizen ha sno spawning_creep_name"
-                                );
+                            RequestData::Dismantle(_) => {
+                                // The dismantler works on its own
+                                closed_requests.push(i);
                             }
-                            RequestData::Citizen(requests::Citizen {
-                                target_room_name,
-                                spawning_creep_name: Some(spawning_creep_name),
-                                ..
-                            }) => {
-                                info!("Closing request");
+                            RequestData::InterShardClaim(_) => {
+                                // The claimer leaves the shard
                                 closed_requests.push(i);
-                                if *target_room_name != self.room_name {
-                                    warn!(
-                                        "WTF, handling different target room?! {} // {}",
-                                        self.room_name, target_room_name
-                                    );
-                                }
-                                match creeps::get(&spawning_creep_name)
-                                    .map(|c| OokRace::try_from(&c))
-                                {
-                                    Some(Ok(creep)) => {
-                                        self.data.helping_citizens.push(creep.creep()?.id());
-                                        info!("OKOKOKOKO push");
-                                    }
-                                    Some(Err(err)) => {
-                                        warn!("Couldnt convert creep for handled request! {}", err);
-                                    }
-                                    None => {
-                                        warn!("Couldnt find creep for handled request! Creep name: {}", spawning_creep_name);
-                                    }
-                                }
                             }
-                            RequestData::Citizen(requests::Citizen {
-                                spawning_creep_name: None,
-                                ..
-                            }) => {
-                                warn!("Handled request for Citizen has no spawning_creep_name");
+                            RequestData::Salvage(_) => {
+                                // The salvager works on its own
+                                closed_requests.push(i);
                             }
                         },
                         None => {}
@@ -598,12 +621,45 @@ impl RoomStateLifecycle<SetupBaseState> for SetupBaseState {
             self.data.helping_citizens,
             self.open_requests.len()
         );
+        if self.is_set_up() {
+            info!("Room {} is set up", self.room_name);
+            return Ok(RoomStateChange::FinishSetup);
+        }
         Ok(RoomStateChange::None)
     }
 
     fn request_logged(&mut self, request_id: UniqId) {
         self.open_requests.push(request_id);
     }
+
+    fn forget_requests(&mut self) {
+        self.open_requests.clear();
+    }
+
+    fn on_enter(&mut self, _state: &mut BWState) -> anyhow::Result<()> {
+        info!("Room {} gets set up again", self.room_name);
+        // Citizens handed over by the state before may have died since
+        self.data
+            .helping_citizens
+            .retain(|id| matches!(get_object_cached(*id), Ok(Some(_))));
+        if let (Some(room), SetupBaseStateVisibility::Visible { resource_providers, .. }) =
+            (rooms::get(self.room_name), &mut self.state)
+        {
+            *resource_providers = calc_resource_providers(&room, false)?
+                .into_iter()
+                .map(|prov| (prov.ident(), prov))
+                .collect();
+        }
+        Ok(())
+    }
+
+    fn on_exit(&mut self, state: &mut BWState) -> anyhow::Result<()> {
+        // Requests not spawning yet would spawn for a state that is gone
+        for request_id in self.open_requests.drain(..) {
+            state.requests.remove(&request_id);
+        }
+        Ok(())
+    }
 }
 
 impl RoomStatePersistable<Self> for SetupBaseState {
@@ -0,0 +1,160 @@
+//! Spawns that failed for a request. The return code decides what happens next:
+//!
+//! - `Busy` and `NameExists` pass by themselves, the request is tried again the next tick
+//! - `NotEnough` waits `config::SPAWN_RETRY_TICKS`, twice as long after each failure
+//! - `InvalidArgs` means the body doesn't work, each one halves the energy the body is planned with
+//! - anything else waits like missing energy
+//!
+//! After `config::SPAWN_FAILURES_BEFORE_REROUTE` failures in one room the request goes to the
+//! closest other base, see `assign_requests`. Without one it stays where it was and keeps backing
+//! off there.
+use std::{cmp, collections::HashMap, sync::Mutex};
+
+use lazy_static::lazy_static;
+use log::{info, warn};
+use screeps::{game, ReturnCode, RoomName};
+
+use crate::{config, creeps::TrySpawnResult, state::UniqId};
+
+/// What a lone spawn holds, bodies aren't downgraded below it
+const MIN_BODY_ENERGY: u32 = 300;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+    /// Goes away by itself
+    Passing,
+    Energy,
+    Body,
+    Other,
+}
+
+impl FailureKind {
+    pub fn of(code: ReturnCode) -> FailureKind {
+        match code {
+            ReturnCode::Busy | ReturnCode::NameExists => FailureKind::Passing,
+            ReturnCode::NotEnough => FailureKind::Energy,
+            ReturnCode::InvalidArgs => FailureKind::Body,
+            _ => FailureKind::Other,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Failures {
+    /// Room the failures were counted in
+    room: Option<RoomName>,
+    in_room: u32,
+    /// Times the body was halved
+    downgrades: u32,
+    next_try: u32,
+    /// The last try found the spawns without enough energy
+    short_of_energy: bool,
+    /// Rooms the request failed too often in
+    avoided: Vec<RoomName>,
+}
+
+lazy_static! {
+    static ref FAILURES: Mutex<HashMap<UniqId, Failures>> = Mutex::new(HashMap::new());
+}
+
+fn record(request_id: &UniqId, room_name: RoomName, kind: FailureKind) {
+    let time = game::time();
+    let mut failures = FAILURES.lock().unwrap();
+    let failures = failures.entry(request_id.to_owned()).or_default();
+    if failures.room != Some(room_name) {
+        failures.room = Some(room_name);
+        failures.in_room = 0;
+    }
+    failures.in_room += 1;
+    failures.short_of_energy = kind == FailureKind::Energy;
+    match kind {
+        FailureKind::Passing => failures.next_try = time + 1,
+        FailureKind::Body => {
+            failures.downgrades += 1;
+            failures.next_try = time + 1;
+        }
+        FailureKind::Energy | FailureKind::Other => {
+            let backoff = config::SPAWN_RETRY_TICKS << cmp::min(failures.in_room - 1, 6);
+            failures.next_try = time + backoff;
+        }
+    }
+    info!(
+        "Spawning for request {} failed in {} ({:?}, {} times)",
+        request_id, room_name, kind, failures.in_room
+    );
+    if failures.in_room >= config::SPAWN_FAILURES_BEFORE_REROUTE {
+        warn!(
+            "Request {} failed {} times in {}, another base takes over",
+            request_id, failures.in_room, room_name
+        );
+        if !failures.avoided.contains(&room_name) {
+            failures.avoided.push(room_name);
+        }
+        failures.in_room = 0;
+        failures.next_try = time;
+    }
+}
+
+/// Books the result of a spawn for the request in `room_name`, a successful one forgets the
+/// failures
+pub fn note(request_id: &UniqId, room_name: RoomName, result: &anyhow::Result<TrySpawnResult>) {
+    match result {
+        Ok(TrySpawnResult::Spawned(data)) | Ok(TrySpawnResult::ForceSpawned(data)) => {
+            if data.return_code == ReturnCode::Ok {
+                FAILURES.lock().unwrap().remove(request_id);
+            } else {
+                record(request_id, room_name, FailureKind::of(data.return_code));
+            }
+        }
+        Ok(TrySpawnResult::Skipped) => {}
+        Err(_) => record(request_id, room_name, FailureKind::Other),
+    }
+}
+
+/// If the request may be tried again this tick
+pub fn is_due(request_id: &UniqId) -> bool {
+    FAILURES
+        .lock()
+        .unwrap()
+        .get(request_id)
+        .map(|failures| failures.next_try <= game::time())
+        .unwrap_or(true)
+}
+
+/// Energy to plan the body of the request with, halved for every body that didn't work
+pub fn body_energy(request_id: &UniqId, energy: u32) -> u32 {
+    let downgrades = FAILURES
+        .lock()
+        .unwrap()
+        .get(request_id)
+        .map(|failures| failures.downgrades)
+        .unwrap_or(0);
+    if downgrades == 0 {
+        return energy;
+    }
+    cmp::min(energy, cmp::max(energy >> cmp::min(downgrades, 8), MIN_BODY_ENERGY))
+}
+
+/// If a request spawning in `room_name`, wherever it came from, waits for the spawns to fill up
+pub fn is_short_of_energy(room_name: RoomName) -> bool {
+    FAILURES
+        .lock()
+        .unwrap()
+        .values()
+        .any(|failures| failures.room == Some(room_name) && failures.short_of_energy)
+}
+
+/// Rooms that failed too often to spawn for the request
+pub fn avoided_rooms(request_id: &UniqId) -> Vec<RoomName> {
+    FAILURES
+        .lock()
+        .unwrap()
+        .get(request_id)
+        .map(|failures| failures.avoided.clone())
+        .unwrap_or_default()
+}
+
+/// Forgets the failures of the requests that are gone
+pub fn retain<F: Fn(&UniqId) -> bool>(is_open: F) {
+    FAILURES.lock().unwrap().retain(|id, _| is_open(id));
+}
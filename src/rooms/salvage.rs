@@ -0,0 +1,194 @@
+//! Salvage. Ruins and tombstones of a cleared room still hold what the room had stored, and it
+//! decays with them if nobody comes. The closest base sends a big carrier to loot them, the most
+//! valuable and soonest decaying loot first, see `tasks::salvage`.
+use std::cmp::Ordering;
+
+use log::info;
+use screeps::{
+    find, game, HasId, HasPosition, HasStore, ObjectId, OwnedStructureProperties, Part, Position,
+    ResourceType, Room, RoomName, Ruin, Tombstone,
+};
+
+use crate::{
+    config,
+    creeps::{jobs::OokCreepJob, races::OokRace},
+    game::{owned_rooms, OwnedBy},
+    rooms::remotes::linear_distance,
+    state::{
+        requests::{self, Request, RequestData},
+        BWState,
+    },
+};
+
+/// Loot decaying within this many ticks counts double
+const URGENT_DECAY_TICKS: u32 = 500;
+
+#[derive(Debug, Clone, Copy)]
+pub enum LootSource {
+    Ruin(ObjectId<Ruin>),
+    Tombstone(ObjectId<Tombstone>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Loot {
+    pub source: LootSource,
+    pub pos: Position,
+    pub ticks_to_decay: u32,
+    /// The most valuable resource in there, it gets withdrawn first
+    pub resource_type: ResourceType,
+    pub value: f64,
+}
+
+/// What a unit of the resource is worth hauling home, energy the least and power the most
+pub fn resource_value(resource_type: ResourceType) -> f64 {
+    match resource_type {
+        ResourceType::Energy => 1.,
+        ResourceType::Power => 10.,
+        _ => 3.,
+    }
+}
+
+fn loot_in(
+    source: LootSource,
+    pos: Position,
+    ticks_to_decay: u32,
+    store: &impl HasStore,
+) -> Option<Loot> {
+    let stored: Vec<(ResourceType, u32)> = store
+        .store_types()
+        .into_iter()
+        .map(|resource_type| {
+            (
+                resource_type,
+                store.store_used_capacity(Some(resource_type)),
+            )
+        })
+        .filter(|(_, amount)| *amount > 0)
+        .collect();
+    let (resource_type, _) = stored.iter().max_by(|(a, _), (b, _)| {
+        resource_value(*a)
+            .partial_cmp(&resource_value(*b))
+            .unwrap_or(Ordering::Equal)
+    })?;
+    let value = stored
+        .iter()
+        .map(|(resource_type, amount)| *amount as f64 * resource_value(*resource_type))
+        .sum();
+    Some(Loot {
+        source,
+        pos,
+        ticks_to_decay,
+        resource_type: *resource_type,
+        value,
+    })
+}
+
+/// Every ruin and tombstone of the room with something in it
+pub fn loot(room: &Room) -> Vec<Loot> {
+    let ruins = room.find(find::RUINS).into_iter().filter_map(|ruin| {
+        loot_in(
+            LootSource::Ruin(ruin.id()),
+            ruin.pos(),
+            ruin.ticks_to_decay(),
+            &ruin,
+        )
+    });
+    let tombstones = room
+        .find(find::TOMBSTONES)
+        .into_iter()
+        .filter_map(|tombstone| {
+            loot_in(
+                LootSource::Tombstone(tombstone.id()),
+                tombstone.pos(),
+                tombstone.ticks_to_decay(),
+                &tombstone,
+            )
+        });
+    ruins.chain(tombstones).collect()
+}
+
+/// Loot of the room for a creep at `pos`, best first
+///
+/// Value over range, doubled for loot decaying soon. Loot decaying before the creep gets there is
+/// left out.
+pub fn prioritized_loot(room: &Room, pos: Position) -> Vec<Loot> {
+    let mut loot: Vec<(f64, Loot)> = loot(room)
+        .into_iter()
+        .filter_map(|loot| {
+            let range = pos.get_range_to(&loot.pos);
+            if loot.ticks_to_decay <= range {
+                return None;
+            }
+            let urgency = if loot.ticks_to_decay < URGENT_DECAY_TICKS {
+                2.
+            } else {
+                1.
+            };
+            Some((loot.value * urgency / (range + 1) as f64, loot))
+        })
+        .collect();
+    loot.sort_by(|(a, _), (b, _)| b.partial_cmp(a).unwrap_or(Ordering::Equal));
+    loot.into_iter().map(|(_, loot)| loot).collect()
+}
+
+/// Nobody owns the room and nothing in there fights back
+fn is_cleared(room: &Room) -> bool {
+    let owned = room
+        .controller()
+        .map(|controller| controller.owner_name().is_some())
+        .unwrap_or(false);
+    let defended = room.find(find::HOSTILE_CREEPS).iter().any(|hostile| {
+        hostile.get_active_bodyparts(Part::Attack) > 0
+            || hostile.get_active_bodyparts(Part::RangedAttack) > 0
+    });
+    !owned && !defended
+}
+
+/// A request for the room is open or a salvager is on it already
+fn is_salvage_handled(state: &BWState, room_name: RoomName) -> bool {
+    let requested = state
+        .requests
+        .values()
+        .chain(state.handled_requests.values().flat_map(|requests| requests.values()))
+        .any(|request| {
+            matches!(&request.data, RequestData::Salvage(data) if data.target_room_name == room_name)
+        });
+    let spawned = state.citizens.values().any(|citizen| match citizen {
+        OokRace::Carrier(carrier) => matches!(
+            &carrier.job,
+            OokCreepJob::Salvage(job) if job.target_room == room_name
+        ),
+        _ => false,
+    });
+    requested || spawned
+}
+
+/// Asks for salvagers for cleared rooms in sight that are close to a base and worth it
+pub fn request_salvage(state: &mut BWState) -> anyhow::Result<()> {
+    if game::time() % config::SALVAGE_CHECK_INTERVAL != 0 {
+        return Ok(());
+    }
+    let bases = owned_rooms(OwnedBy::Me);
+    for room in game::rooms::values() {
+        let room_name = room.name();
+        if bases.contains_key(&room_name) || !is_cleared(&room) {
+            continue;
+        }
+        let close = bases
+            .keys()
+            .any(|base| linear_distance(*base, room_name) <= config::SALVAGE_MAX_ROOM_DISTANCE);
+        if !close {
+            continue;
+        }
+        let value: f64 = loot(&room).iter().map(|loot| loot.value).sum();
+        if value < config::SALVAGE_MIN_VALUE || is_salvage_handled(state, room_name) {
+            continue;
+        }
+        info!("Salvaging {}, the loot is worth {}", room_name, value);
+        state.add_request(Request::new(RequestData::Salvage(requests::Salvage {
+            target_room_name: room_name,
+            spawning_creep_name: None,
+        })))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,63 @@
+//! Controller signs. My creeps sign the controllers they come by with `config::CONTROLLER_SIGN`.
+//! The sign of a controller tells what was signed last, so a controller is only signed again once
+//! the configured text differs from it.
+use log::{info, warn};
+use screeps::{
+    Creep, HasPosition, OwnedStructureProperties, ReturnCode, RoomObjectProperties,
+    StructureController,
+};
+
+use crate::{config::CONTROLLER_SIGN, constants::MY_USERNAME};
+
+/// If the controller is missing my current sign
+pub fn needs_sign(controller: &StructureController) -> bool {
+    let text = match CONTROLLER_SIGN {
+        Some(text) => text,
+        None => return false,
+    };
+    // Rooms of others are none of my business
+    if controller.has_owner() && !controller.my() {
+        return false;
+    }
+    let reserved_by_others = controller
+        .reservation()
+        .map(|r| r.username != MY_USERNAME)
+        .unwrap_or(false);
+    if reserved_by_others {
+        return false;
+    }
+    match controller.sign() {
+        Some(sign) => sign.username != MY_USERNAME || sign.text != text,
+        None => true,
+    }
+}
+
+/// The controller of the room the creep is in, if it still needs my sign
+pub fn unsigned_controller(creep: &Creep) -> Option<StructureController> {
+    creep
+        .room()
+        .and_then(|room| room.controller())
+        .filter(|controller| needs_sign(controller))
+}
+
+/// Signs the controller if it needs it, the creep has to stand next to it
+pub fn sign(creep: &Creep, controller: &StructureController) {
+    let text = match CONTROLLER_SIGN {
+        Some(text) => text,
+        None => return,
+    };
+    if !needs_sign(controller) || !creep.pos().is_near_to(&controller.pos()) {
+        return;
+    }
+    match creep.sign_controller(controller, text) {
+        ReturnCode::Ok => info!("Signed controller in {}", controller.pos().room_name()),
+        code => warn!("Could not sign controller, return code {:?}", code),
+    }
+}
+
+/// Signs the controller of the room if the creep happens to pass by it
+pub fn sign_in_passing(creep: &Creep) {
+    if let Some(controller) = unsigned_controller(creep) {
+        sign(creep, &controller);
+    }
+}
@@ -0,0 +1,147 @@
+//! Construction sites go through here instead of `room.create_construction_site`. The game allows
+//! `config::SITE_LIMIT` sites per player, placing more just fails and a planner asking for
+//! hundreds would lose most of them without anyone noticing.
+//!
+//! Sites are queued with `request` and placed in the `Deferred` stage: at most
+//! `config::SITES_PER_ROOM_PER_TICK` per room and tick, in the order of `BuildPolicy`, and only as
+//! long as the sites of all rooms stay below the limit. A site that can't be placed is tried again
+//! after `config::SITE_RETRY_TICKS`, twice as long after each failure, and dropped after
+//! `config::SITE_MAX_ATTEMPTS`.
+//!
+//! The queue lives in the global like the deferred jobs, the planners queue their sites again.
+use std::{cmp, collections::HashMap, sync::Mutex};
+
+use lazy_static::lazy_static;
+use log::{debug, warn};
+use screeps::{game, Position, ReturnCode, RoomName, StructureType};
+
+use crate::config;
+
+use super::{rebuild, BuildPolicy};
+
+#[derive(Debug, Clone)]
+struct SiteRequest {
+    pos: Position,
+    structure_type: StructureType,
+    queued_at: u32,
+    attempts: u32,
+    /// Not tried again before this tick
+    next_try: u32,
+}
+
+lazy_static! {
+    static ref QUEUE: Mutex<Vec<SiteRequest>> = Mutex::new(vec![]);
+}
+
+/// Queues a site of `structure_type` at `pos`, unless one is queued there already
+pub fn request(pos: Position, structure_type: StructureType) {
+    let mut queue = QUEUE.lock().unwrap();
+    if queue.iter().any(|r| r.pos == pos) {
+        return;
+    }
+    queue.push(SiteRequest {
+        pos,
+        structure_type,
+        queued_at: game::time(),
+        attempts: 0,
+        next_try: game::time(),
+    });
+}
+
+/// If a site waits to be placed at `pos`
+pub fn is_queued(pos: &Position) -> bool {
+    QUEUE.lock().unwrap().iter().any(|r| r.pos == *pos)
+}
+
+/// Type of the site waiting to be placed at `pos`, if there is one
+pub fn queued_at(pos: &Position) -> Option<StructureType> {
+    QUEUE
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|r| r.pos == *pos)
+        .map(|r| r.structure_type)
+}
+
+/// Sites of `structure_type` waiting to be placed in the room
+pub fn queued(room_name: RoomName, structure_type: StructureType) -> u32 {
+    QUEUE
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|r| r.pos.room_name() == room_name && r.structure_type == structure_type)
+        .count() as u32
+}
+
+/// Backs off the request, `false` once it ran out of attempts
+fn retry_later(request: &mut SiteRequest, code: ReturnCode) -> bool {
+    request.attempts += 1;
+    if request.attempts >= config::SITE_MAX_ATTEMPTS {
+        warn!(
+            "Giving up on the {:?} site at {}: {:?}",
+            request.structure_type, request.pos, code
+        );
+        return false;
+    }
+    let backoff = config::SITE_RETRY_TICKS << cmp::min(request.attempts - 1, 10);
+    debug!(
+        "Couldn't place {:?} site at {}: {:?}, retrying in {} ticks",
+        request.structure_type, request.pos, code, backoff
+    );
+    request.next_try = game::time() + backoff;
+    true
+}
+
+/// Places the queued sites the limits allow, call it each tick
+pub fn run() {
+    let mut queue = QUEUE.lock().unwrap();
+    if queue.is_empty() {
+        return;
+    }
+    let time = game::time();
+    let mut site_count = game::construction_sites::values().len() as u32;
+    let policy = BuildPolicy::default();
+    queue.sort_by_key(|r| {
+        (
+            !rebuild::is_rushed(&r.pos),
+            policy.tier(r.structure_type),
+            r.queued_at,
+        )
+    });
+    let mut placed_in: HashMap<RoomName, u32> = HashMap::new();
+    let mut kept = Vec::with_capacity(queue.len());
+    for mut request in queue.drain(..) {
+        let room_name = request.pos.room_name();
+        let placed = placed_in.get(&room_name).copied().unwrap_or(0);
+        if request.next_try > time
+            || site_count >= config::SITE_LIMIT
+            || placed >= config::SITES_PER_ROOM_PER_TICK
+        {
+            kept.push(request);
+            continue;
+        }
+        // Rooms out of sight wait without using up attempts
+        let room = match game::rooms::get(room_name) {
+            Some(room) => room,
+            None => {
+                kept.push(request);
+                continue;
+            }
+        };
+        match room.create_construction_site(&request.pos, request.structure_type) {
+            ReturnCode::Ok => {
+                site_count += 1;
+                placed_in.insert(room_name, placed + 1);
+            }
+            code => {
+                if retry_later(&mut request, code) {
+                    kept.push(request);
+                }
+            }
+        }
+    }
+    *queue = kept;
+    for (room_name, placed) in placed_in {
+        debug!("Placed {} construction sites in {}", placed, room_name);
+    }
+}
@@ -0,0 +1,86 @@
+//! Spawns put fresh creeps on any free tile around them, including the tiles carriers use to
+//! fill extensions. The exits are the neighbours of a spawn that are not next to any other
+//! spawn or extension, fresh creeps only get placed there and nobody else should stand on them.
+use screeps::{
+    find, game, look, Direction, HasId, HasPosition, OwnedStructureProperties, Position, Room,
+    RoomObjectProperties, Structure, StructureSpawn, Terrain,
+};
+
+use crate::utils::DIRECTIONS;
+
+fn is_walkable(room: &Room, pos: &Position) -> bool {
+    if game::map::get_room_terrain(pos.room_name()).get(pos.x(), pos.y()) == Terrain::Wall {
+        return false;
+    }
+    room.look_for_at(look::STRUCTURES, pos)
+        .iter()
+        .all(|s| match s {
+            Structure::Road(_) | Structure::Container(_) => true,
+            Structure::Rampart(rampart) => rampart.my() || rampart.is_public(),
+            _ => false,
+        })
+}
+
+/// Walkable neighbours of the spawn with the direction leading to them
+fn walkable_neighbours(spawn: &StructureSpawn, room: &Room) -> Vec<(Direction, Position)> {
+    let pos = spawn.pos();
+    DIRECTIONS
+        .iter()
+        .filter_map(|(direction, dx, dy)| {
+            let x = pos.x() as i32 + dx;
+            let y = pos.y() as i32 + dy;
+            if x < 1 || x > 48 || y < 1 || y > 48 {
+                return None;
+            }
+            let neighbour = Position::new(x as u32, y as u32, pos.room_name());
+            if is_walkable(room, &neighbour) {
+                Some((*direction, neighbour))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Exit tiles of the spawn, falls back to all walkable neighbours if the spawn is boxed in by
+/// extensions
+pub fn spawn_exits(spawn: &StructureSpawn) -> Vec<(Direction, Position)> {
+    let room = match spawn.room() {
+        Some(room) => room,
+        None => return vec![],
+    };
+    let neighbours = walkable_neighbours(spawn, &room);
+    let suppliers: Vec<Position> = room
+        .find(find::STRUCTURES)
+        .into_iter()
+        .filter(|s| match s {
+            Structure::Extension(_) => true,
+            Structure::Spawn(other) => other.id() != spawn.id(),
+            _ => false,
+        })
+        .map(|s| s.pos())
+        .collect();
+    let exits: Vec<(Direction, Position)> = neighbours
+        .iter()
+        .filter(|(_, pos)| !suppliers.iter().any(|s| s.is_near_to(pos)))
+        .cloned()
+        .collect();
+    if exits.is_empty() {
+        neighbours
+    } else {
+        exits
+    }
+}
+
+/// Directions to pass to `SpawnOptions::directions`
+pub fn spawn_directions(spawn: &StructureSpawn) -> Vec<Direction> {
+    spawn_exits(spawn).into_iter().map(|(direction, _)| direction).collect()
+}
+
+/// Exit tiles of all own spawns in the room
+pub fn room_spawn_exits(room: &Room) -> Vec<Position> {
+    room.find(find::MY_SPAWNS)
+        .iter()
+        .flat_map(|spawn| spawn_exits(spawn).into_iter().map(|(_, pos)| pos))
+        .collect()
+}
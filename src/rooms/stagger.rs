@@ -0,0 +1,25 @@
+//! Periodic work of the rooms, spread over the ticks. With `game::time() % interval == 0` every
+//! room does its expensive update in the same tick, and the CPU spikes while the ticks in between
+//! idle. Here the room name picks the tick in the interval instead, so the work of the rooms is
+//! spread out. The offset only depends on the name, it stays the same over global resets.
+use screeps::{game, RoomName};
+
+/// The tick in every `interval` ticks the room does its work, in `0..interval`
+pub fn offset(room_name: RoomName, interval: u32) -> u32 {
+    if interval == 0 {
+        return 0;
+    }
+    // FNV-1a, unlike the hasher of std it stays the same on every build
+    let hash = room_name
+        .to_string()
+        .bytes()
+        .fold(0x811c_9dc5_u32, |hash, byte| {
+            (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+        });
+    hash % interval
+}
+
+/// If the periodic work of the room with the interval is due in this tick
+pub fn is_due(room_name: RoomName, interval: u32) -> bool {
+    interval > 0 && game::time() % interval == offset(room_name, interval)
+}
@@ -0,0 +1,94 @@
+//! Keeps the terminal stocked at the levels of `config::TERMINAL_STOCK_LEVELS` plus what planned
+//! sends need, everything above that goes into the storage. Carriers pick up the transfers when
+//! they have nothing better to do.
+use std::collections::HashSet;
+
+use screeps::{
+    find, HasId, HasStore, ObjectId, ResourceType, Room, Structure, StructureStorage,
+    StructureTerminal,
+};
+
+use crate::{server, trade};
+
+/// Smaller differences are not worth a trip
+const MIN_TRANSFER_AMOUNT: u32 = 100;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransferDirection {
+    StorageToTerminal,
+    TerminalToStorage,
+}
+
+#[derive(Clone, Debug)]
+pub struct StockTransfer {
+    pub storage: ObjectId<StructureStorage>,
+    pub terminal: ObjectId<StructureTerminal>,
+    pub direction: TransferDirection,
+    pub resource_type: ResourceType,
+    pub amount: u32,
+}
+
+fn storage_and_terminal(room: &Room) -> Option<(StructureStorage, StructureTerminal)> {
+    let mut storage = None;
+    let mut terminal = None;
+    for structure in room.find(find::STRUCTURES) {
+        match structure {
+            Structure::Storage(s) => storage = Some(s),
+            Structure::Terminal(t) => terminal = Some(t),
+            _ => {}
+        }
+    }
+    Some((storage?, terminal?))
+}
+
+/// Transfers that bring the terminal to its stock levels, topping up the terminal comes first
+/// as sends wait for it
+pub fn stock_transfers(room: &Room) -> Vec<StockTransfer> {
+    // Without a market the terminal has nothing to stock up for
+    if !server::has_market() {
+        return vec![];
+    }
+    let (storage, terminal) = match storage_and_terminal(room) {
+        Some(structures) => structures,
+        None => return vec![],
+    };
+    let resource_types: HashSet<ResourceType> = terminal
+        .store_types()
+        .into_iter()
+        .chain(storage.store_types())
+        .collect();
+    let mut transfers: Vec<StockTransfer> = resource_types
+        .into_iter()
+        .filter_map(|resource_type| {
+            let target = trade::terminal_keep_amount(room.name(), resource_type);
+            let in_terminal = terminal.store_used_capacity(Some(resource_type));
+            let (direction, amount) = if in_terminal < target {
+                let amount = (target - in_terminal)
+                    .min(storage.store_used_capacity(Some(resource_type)))
+                    .min(terminal.store_free_capacity(Some(resource_type)).max(0) as u32);
+                (TransferDirection::StorageToTerminal, amount)
+            } else {
+                let amount = (in_terminal - target)
+                    .min(storage.store_free_capacity(Some(resource_type)).max(0) as u32);
+                (TransferDirection::TerminalToStorage, amount)
+            };
+            if amount < MIN_TRANSFER_AMOUNT {
+                return None;
+            }
+            Some(StockTransfer {
+                storage: storage.id(),
+                terminal: terminal.id(),
+                direction,
+                resource_type,
+                amount,
+            })
+        })
+        .collect();
+    transfers.sort_by_key(|t| {
+        (
+            t.direction != TransferDirection::StorageToTerminal,
+            std::cmp::Reverse(t.amount),
+        )
+    });
+    transfers
+}
@@ -0,0 +1,148 @@
+//! The structures of the rooms in sight, by type. `room.find(find::STRUCTURES)` crosses into JS
+//! and wraps every structure of the room, walls and roads included, just to pick the few of one
+//! type. Here the ids of each type are kept over ticks and only the wanted ones get looked up, see
+//! `get_object_cached`.
+//!
+//! A room is scanned again when a structure in it got destroyed, its construction sites changed or
+//! `config::STRUCTURE_CACHE_TICKS` passed. Lives next to the state like the object cache, the
+//! legacy creeps and the towers run without it.
+//!
+//! The positions of the structures in `config::REBUILT_STRUCTURES` are kept as well, the ones
+//! gone in the new scan are handed to `rebuild`.
+use std::{collections::HashMap, sync::Mutex};
+
+use lazy_static::lazy_static;
+use log::debug;
+use screeps::{
+    find, game, EventType, HasId, HasPosition, ObjectId, OwnedStructureProperties, Position,
+    RawObjectId, Room, RoomName, SizedRoomObject, StructureType,
+};
+
+use crate::{config, state::get_object_cached};
+
+use super::events;
+
+/// A structure that was there in the last scan and isn't anymore
+#[derive(Debug, Clone)]
+pub struct Destroyed {
+    pub structure_type: StructureType,
+    pub pos: Position,
+}
+
+#[derive(Debug, Default)]
+struct RoomStructures {
+    scanned_at: u32,
+    construction_sites: usize,
+    by_type: HashMap<StructureType, Vec<RawObjectId>>,
+    /// Mine and unowned ones of `config::REBUILT_STRUCTURES`
+    rebuilt: HashMap<RawObjectId, Destroyed>,
+}
+
+lazy_static! {
+    static ref STRUCTURES: Mutex<HashMap<RoomName, RoomStructures>> = Mutex::new(HashMap::new());
+}
+
+fn scan(room: &Room) -> RoomStructures {
+    let mut by_type: HashMap<StructureType, Vec<RawObjectId>> = HashMap::new();
+    let mut rebuilt = HashMap::new();
+    for structure in room.find(find::STRUCTURES) {
+        let structure_type = structure.structure_type();
+        let id: RawObjectId = structure.id().into();
+        by_type.entry(structure_type).or_default().push(id);
+        let is_hostile = structure.as_owned().map(|s| !s.my()).unwrap_or(false);
+        if config::REBUILT_STRUCTURES.contains(&structure_type) && !is_hostile {
+            rebuilt.insert(
+                id,
+                Destroyed {
+                    structure_type,
+                    pos: structure.pos(),
+                },
+            );
+        }
+    }
+    RoomStructures {
+        scanned_at: game::time(),
+        construction_sites: room.find(find::CONSTRUCTION_SITES).len(),
+        by_type,
+        rebuilt,
+    }
+}
+
+fn is_outdated(room: &Room, cached: &RoomStructures) -> bool {
+    if game::time() - cached.scanned_at >= config::STRUCTURE_CACHE_TICKS {
+        return true;
+    }
+    // A finished construction site is gone, the new structure doesn't make an event of its own
+    if room.find(find::CONSTRUCTION_SITES).len() != cached.construction_sites {
+        return true;
+    }
+    events::of(room)
+        .into_iter()
+        .any(|event| match event.event {
+            EventType::ObjectDestroyed(ev) => ev.object_type != "creep",
+            _ => false,
+        })
+}
+
+/// Scans the rooms that changed again, call it once at the start of the tick. Returns the
+/// structures that are gone since the last scan of their room.
+pub fn refresh() -> Vec<Destroyed> {
+    let mut cache = STRUCTURES.lock().unwrap();
+    let visible = game::rooms::hashmap();
+    cache.retain(|room_name, _| visible.contains_key(room_name));
+    let mut destroyed = vec![];
+    for (room_name, room) in visible {
+        let outdated = cache
+            .get(&room_name)
+            .map(|cached| is_outdated(&room, cached))
+            .unwrap_or(true);
+        if outdated {
+            debug!("Scanning structures of {}", room_name);
+            let scanned = scan(&room);
+            if let Some(cached) = cache.get(&room_name) {
+                destroyed.extend(
+                    cached
+                        .rebuilt
+                        .iter()
+                        .filter(|(id, _)| !scanned.rebuilt.contains_key(id))
+                        .map(|(_, gone)| gone.clone()),
+                );
+            }
+            cache.insert(room_name, scanned);
+        }
+    }
+    destroyed
+}
+
+/// The ids of the structures of the type in the room
+pub fn ids(room_name: RoomName, structure_type: StructureType) -> Vec<RawObjectId> {
+    let mut cache = STRUCTURES.lock().unwrap();
+    if !cache.contains_key(&room_name) {
+        match game::rooms::get(room_name) {
+            Some(room) => {
+                cache.insert(room_name, scan(&room));
+            }
+            None => return vec![],
+        }
+    }
+    cache
+        .get(&room_name)
+        .and_then(|cached| cached.by_type.get(&structure_type))
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// The structures of the type in the room, `T` has to be the one of the type
+///
+/// ```
+///   let towers: Vec<StructureTower> = structures::of_type(room_name, StructureType::Tower);
+/// ```
+pub fn of_type<T>(room_name: RoomName, structure_type: StructureType) -> Vec<T>
+where
+    T: HasId + SizedRoomObject + Clone + Send + 'static,
+{
+    ids(room_name, structure_type)
+        .into_iter()
+        .filter_map(|id| get_object_cached(ObjectId::<T>::from(id)).ok().flatten())
+        .collect()
+}
@@ -0,0 +1,278 @@
+//! Terrain analysis of a room: how far every tile is from the next wall, how far from the next exit
+//! and which tiles are chokepoints. Terrain never changes, so a room is analyzed once when someone
+//! asks for it and kept in the global.
+//!
+//! Every analysis also goes into a memory segment of its own, see `segments`. Segments are only
+//! readable a tick after they got activated, so after a reset the first `analysis` of a room
+//! activates its segment and returns `None`, the next one reads it.
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use lazy_static::lazy_static;
+use log::debug;
+use screeps::{game, Position, RoomName};
+
+use crate::segments::{self, Read};
+
+const ROOM_SIZE: usize = 50;
+const ROOM_AREA: usize = ROOM_SIZE * ROOM_SIZE;
+/// Bumped when the encoding or the analysis changes, older segments get analyzed again
+const FORMAT_VERSION: char = '1';
+const TERRAIN_MASK_WALL: u8 = 1;
+/// Passages at most this wide are chokepoints
+const CHOKEPOINT_MAX_WIDTH: usize = 3;
+/// Tiles out of reach of the exits
+pub const UNREACHABLE: u8 = 0xff;
+
+/// One byte per tile of a room
+#[derive(Clone)]
+pub struct TileMap(Vec<u8>);
+
+impl TileMap {
+    fn filled(value: u8) -> TileMap {
+        TileMap(vec![value; ROOM_AREA])
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> u8 {
+        self.0[y * ROOM_SIZE + x]
+    }
+
+    fn set(&mut self, x: usize, y: usize, value: u8) {
+        self.0[y * ROOM_SIZE + x] = value;
+    }
+
+    pub fn at(&self, pos: &Position) -> u8 {
+        self.get(pos.x() as usize, pos.y() as usize)
+    }
+}
+
+pub struct TerrainAnalysis {
+    /// Range to the closest wall, 0 on walls. The room border counts as wall.
+    pub wall_distance: TileMap,
+    /// Steps to the closest exit tile, `UNREACHABLE` for walls and enclosed tiles
+    pub exit_distance: TileMap,
+    /// 1 on chokepoints, 0 everywhere else
+    pub chokepoints: TileMap,
+}
+
+impl TerrainAnalysis {
+    pub fn is_chokepoint(&self, pos: &Position) -> bool {
+        self.chokepoints.at(pos) != 0
+    }
+
+    fn encode(&self) -> String {
+        let mut encoded = String::with_capacity(1 + 3 * ROOM_AREA);
+        encoded.push(FORMAT_VERSION);
+        for map in &[&self.wall_distance, &self.exit_distance, &self.chokepoints] {
+            encoded.push_str(&segments::encode_bytes(&map.0));
+        }
+        encoded
+    }
+
+    fn decode(encoded: &str) -> Option<TerrainAnalysis> {
+        let mut chars = encoded.chars();
+        if chars.next()? != FORMAT_VERSION {
+            return None;
+        }
+        let bytes = segments::decode_bytes(chars.as_str())?;
+        if bytes.len() != 3 * ROOM_AREA {
+            return None;
+        }
+        Some(TerrainAnalysis {
+            wall_distance: TileMap(bytes[..ROOM_AREA].to_vec()),
+            exit_distance: TileMap(bytes[ROOM_AREA..2 * ROOM_AREA].to_vec()),
+            chokepoints: TileMap(bytes[2 * ROOM_AREA..].to_vec()),
+        })
+    }
+}
+
+fn is_walkable(walls: &[bool], x: i32, y: i32) -> bool {
+    x >= 0
+        && y >= 0
+        && (x as usize) < ROOM_SIZE
+        && (y as usize) < ROOM_SIZE
+        && !walls[y as usize * ROOM_SIZE + x as usize]
+}
+
+/// Chebyshev distance transform in two passes
+fn wall_distance(walls: &[bool]) -> TileMap {
+    let mut map = TileMap::filled(UNREACHABLE);
+    let value = |map: &TileMap, x: i32, y: i32| -> u8 {
+        if x < 0 || y < 0 || x as usize >= ROOM_SIZE || y as usize >= ROOM_SIZE {
+            0
+        } else {
+            map.get(x as usize, y as usize)
+        }
+    };
+    for y in 0..ROOM_SIZE as i32 {
+        for x in 0..ROOM_SIZE as i32 {
+            if !is_walkable(walls, x, y) {
+                map.set(x as usize, y as usize, 0);
+                continue;
+            }
+            let closest = [(-1, 0), (-1, -1), (0, -1), (1, -1)]
+                .iter()
+                .map(|(dx, dy)| value(&map, x + dx, y + dy))
+                .min()
+                .unwrap_or(0);
+            map.set(x as usize, y as usize, closest.saturating_add(1));
+        }
+    }
+    for y in (0..ROOM_SIZE as i32).rev() {
+        for x in (0..ROOM_SIZE as i32).rev() {
+            if !is_walkable(walls, x, y) {
+                continue;
+            }
+            let closest = [(1, 0), (1, 1), (0, 1), (-1, 1)]
+                .iter()
+                .map(|(dx, dy)| value(&map, x + dx, y + dy))
+                .min()
+                .unwrap_or(0);
+            let current = map.get(x as usize, y as usize);
+            map.set(
+                x as usize,
+                y as usize,
+                current.min(closest.saturating_add(1)),
+            );
+        }
+    }
+    map
+}
+
+/// Flood fill from all exit tiles
+fn exit_distance(walls: &[bool]) -> TileMap {
+    let mut map = TileMap::filled(UNREACHABLE);
+    let mut open = VecDeque::new();
+    let last = ROOM_SIZE as i32 - 1;
+    for i in 0..ROOM_SIZE as i32 {
+        for (x, y) in [(i, 0), (i, last), (0, i), (last, i)].iter() {
+            if is_walkable(walls, *x, *y) && map.get(*x as usize, *y as usize) != 0 {
+                map.set(*x as usize, *y as usize, 0);
+                open.push_back((*x, *y));
+            }
+        }
+    }
+    while let Some((x, y)) = open.pop_front() {
+        let next = map
+            .get(x as usize, y as usize)
+            .saturating_add(1)
+            .min(UNREACHABLE - 1);
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                let (nx, ny) = (x + dx, y + dy);
+                if is_walkable(walls, nx, ny) && map.get(nx as usize, ny as usize) == UNREACHABLE {
+                    map.set(nx as usize, ny as usize, next);
+                    open.push_back((nx, ny));
+                }
+            }
+        }
+    }
+    map
+}
+
+/// Walkable tiles from `x`/`y` in the direction up to the next wall, `None` if there is no wall
+/// within `CHOKEPOINT_MAX_WIDTH` tiles
+fn run_length(walls: &[bool], x: i32, y: i32, dx: i32, dy: i32) -> Option<usize> {
+    for steps in 0..CHOKEPOINT_MAX_WIDTH {
+        let (nx, ny) = (x + dx * (steps as i32 + 1), y + dy * (steps as i32 + 1));
+        if !is_walkable(walls, nx, ny) {
+            return Some(steps);
+        }
+        // Leading out of the room, there's no wall closing the passage
+        if nx == 0 || ny == 0 || nx == ROOM_SIZE as i32 - 1 || ny == ROOM_SIZE as i32 - 1 {
+            return None;
+        }
+    }
+    None
+}
+
+/// Tiles reachable from the exits with walls on both sides, at most `CHOKEPOINT_MAX_WIDTH` apart
+fn chokepoints(walls: &[bool], exit_distance: &TileMap) -> TileMap {
+    let mut map = TileMap::filled(0);
+    for y in 1..ROOM_SIZE as i32 - 1 {
+        for x in 1..ROOM_SIZE as i32 - 1 {
+            if !is_walkable(walls, x, y) || exit_distance.get(x as usize, y as usize) == UNREACHABLE
+            {
+                continue;
+            }
+            let narrow = [(1, 0), (0, 1), (1, 1), (1, -1)].iter().any(|(dx, dy)| {
+                match (
+                    run_length(walls, x, y, *dx, *dy),
+                    run_length(walls, x, y, -dx, -dy),
+                ) {
+                    (Some(a), Some(b)) => a + b + 1 <= CHOKEPOINT_MAX_WIDTH,
+                    _ => false,
+                }
+            });
+            if narrow {
+                map.set(x as usize, y as usize, 1);
+            }
+        }
+    }
+    map
+}
+
+fn analyze(room_name: RoomName) -> TerrainAnalysis {
+    let walls: Vec<bool> = game::map::get_room_terrain(room_name)
+        .get_raw_buffer()
+        .iter()
+        .map(|tile| tile & TERRAIN_MASK_WALL != 0)
+        .collect();
+    let exit_distance = exit_distance(&walls);
+    TerrainAnalysis {
+        wall_distance: wall_distance(&walls),
+        chokepoints: chokepoints(&walls, &exit_distance),
+        exit_distance,
+    }
+}
+
+fn segment_key(room_name: RoomName) -> String {
+    format!("terrain.{}", room_name)
+}
+
+lazy_static! {
+    static ref ANALYSES: Mutex<HashMap<RoomName, Arc<TerrainAnalysis>>> =
+        Mutex::new(HashMap::new());
+    /// Analyzed in this global but not in a segment yet
+    static ref UNSAVED: Mutex<HashSet<RoomName>> = Mutex::new(HashSet::new());
+}
+
+/// Analysis of the room, `None` while its segment is being loaded
+pub fn analysis(room_name: RoomName) -> Option<Arc<TerrainAnalysis>> {
+    if let Some(analysis) = ANALYSES.lock().unwrap().get(&room_name) {
+        return Some(analysis.clone());
+    }
+    let analysis = match segments::get(&segment_key(room_name)) {
+        Read::Loaded(encoded) => match TerrainAnalysis::decode(&encoded) {
+            Some(analysis) => analysis,
+            None => {
+                debug!("Terrain of {} in its segment is outdated", room_name);
+                UNSAVED.lock().unwrap().insert(room_name);
+                analyze(room_name)
+            }
+        },
+        Read::Pending => return None,
+        Read::Missing => {
+            UNSAVED.lock().unwrap().insert(room_name);
+            analyze(room_name)
+        }
+    };
+    let analysis = Arc::new(analysis);
+    ANALYSES.lock().unwrap().insert(room_name, analysis.clone());
+    Some(analysis)
+}
+
+/// Hands new analyses to their segments, call it before `segments::persist`
+///
+/// Analyses without a segment left stay in the global only, they get analyzed again after a reset.
+pub fn persist() {
+    let mut unsaved = UNSAVED.lock().unwrap();
+    let analyses = ANALYSES.lock().unwrap();
+    for room_name in unsaved.drain() {
+        if let Some(analysis) = analyses.get(&room_name) {
+            segments::set(&segment_key(room_name), analysis.encode());
+        }
+    }
+}
@@ -0,0 +1,175 @@
+//! Room visibility on request. Code that needs to see a room it has no creep in asks for it with
+//! `request` and may pass a deferred job that gets queued once the room is in sight. An observer
+//! in range scans the room, it is visible in the tick after. Without one the closest base sends a
+//! scout, see `OokCreepJob::ScoutRoom`.
+//!
+//! A request is dropped once the room is visible or after `config::VISIBILITY_REQUEST_TICKS`,
+//! whoever still needs the room asks again. The requests live in the global, a reset drops them.
+use std::{collections::HashMap, sync::Mutex};
+
+use lazy_static::lazy_static;
+use log::{debug, info, warn};
+use screeps::{game, HasId, ObjectId, ReturnCode, RoomName, StructureObserver, StructureType};
+
+use crate::{
+    config,
+    creeps::{
+        jobs::OokCreepJob,
+        races::{DynamicTasked, OokRace},
+    },
+    deferred::{self, Job},
+    game::{owned_rooms, OwnedBy},
+    rooms::{remotes::linear_distance, structures},
+    state::{
+        requests::{self, Request, RequestData},
+        BWState,
+    },
+};
+
+/// Rooms an observer can scan in each direction
+const OBSERVER_RANGE: i32 = 10;
+
+#[derive(Debug, Clone)]
+struct VisibilityRequest {
+    requested_at: u32,
+    /// Queued once the room is visible
+    on_visible: Vec<Job>,
+}
+
+lazy_static! {
+    static ref REQUESTS: Mutex<HashMap<RoomName, VisibilityRequest>> = Mutex::new(HashMap::new());
+}
+
+/// Asks for sight of the room, `on_visible` gets queued once there is. Asking again keeps the
+/// request alive.
+pub fn request(room_name: RoomName, on_visible: Option<Job>) {
+    let mut requests = REQUESTS.lock().unwrap();
+    let entry = requests
+        .entry(room_name)
+        .or_insert_with(|| VisibilityRequest {
+            requested_at: game::time(),
+            on_visible: vec![],
+        });
+    entry.requested_at = game::time();
+    if let Some(job) = on_visible {
+        if !entry.on_visible.iter().any(|queued| queued.is_same(&job)) {
+            entry.on_visible.push(job);
+        }
+    }
+}
+
+/// Rooms between the two in the direction they are furthest apart
+fn range(a: RoomName, b: RoomName) -> i32 {
+    let (x_diff, y_diff) = a - b;
+    x_diff.abs().max(y_diff.abs())
+}
+
+/// A request for a scout of the room is open or the scout is on its way
+fn is_scout_handled(state: &BWState, room_name: RoomName) -> bool {
+    let is_scouting = |job: &OokCreepJob| {
+        matches!(job, OokCreepJob::ScoutRoom { target_room } if *target_room == room_name)
+    };
+    let requested = state
+        .requests
+        .values()
+        .chain(
+            state
+                .handled_requests
+                .values()
+                .flat_map(|requests| requests.values()),
+        )
+        .any(|request| match &request.data {
+            RequestData::Citizen(citizen) => is_scouting(&citizen.initial_job),
+            _ => false,
+        });
+    let spawned = state.citizens.values().any(|citizen| match citizen {
+        OokRace::Claimer(claimer) => is_scouting(&claimer.job()),
+        _ => false,
+    });
+    requested || spawned
+}
+
+fn request_scout(state: &mut BWState, room_name: RoomName, bases: &[RoomName]) {
+    if is_scout_handled(state, room_name) {
+        return;
+    }
+    let base = match bases
+        .iter()
+        .filter(|base| linear_distance(**base, room_name) <= config::SCOUT_MAX_ROOM_DISTANCE)
+        .min_by_key(|base| linear_distance(**base, room_name))
+    {
+        Some(base) => *base,
+        None => {
+            debug!("No base close enough to scout {}", room_name);
+            return;
+        }
+    };
+    info!("Sending a scout from {} to {}", base, room_name);
+    let result = state.add_request(Request::new(RequestData::Citizen(requests::Citizen {
+        target_room_name: base,
+        spawning_creep_name: None,
+        initial_job: OokCreepJob::ScoutRoom {
+            target_room: room_name,
+        },
+        resolve_panic: false,
+        spawn_elsewhere: false,
+    })));
+    if let Err(err) = result {
+        warn!("Could not request a scout for {}: {}", room_name, err);
+    }
+}
+
+/// Queues the jobs of the rooms that came into sight and gets the others observed or scouted,
+/// call it each tick after the rooms are tracked
+pub fn run(state: &mut BWState) {
+    let now = game::time();
+    let mut pending: Vec<RoomName> = vec![];
+    REQUESTS.lock().unwrap().retain(|room_name, request| {
+        if game::rooms::get(*room_name).is_some() {
+            for job in request.on_visible.drain(..) {
+                deferred::queue(job);
+            }
+            return false;
+        }
+        if now - request.requested_at > config::VISIBILITY_REQUEST_TICKS {
+            debug!("Gave up on sight of {}", room_name);
+            return false;
+        }
+        pending.push(*room_name);
+        true
+    });
+    if pending.is_empty() {
+        return;
+    }
+    let bases: Vec<RoomName> = owned_rooms(OwnedBy::Me).keys().copied().collect();
+    // Each observer scans one room per tick
+    let observers: Vec<(RoomName, ObjectId<StructureObserver>)> = bases
+        .iter()
+        .flat_map(|base| {
+            structures::of_type::<StructureObserver>(*base, StructureType::Observer)
+                .into_iter()
+                .map(move |observer| (*base, observer.id()))
+        })
+        .collect();
+    let mut busy: Vec<ObjectId<StructureObserver>> = vec![];
+    for room_name in pending {
+        let in_range: Vec<&(RoomName, ObjectId<StructureObserver>)> = observers
+            .iter()
+            .filter(|(base, _)| range(*base, room_name) <= OBSERVER_RANGE)
+            .collect();
+        if in_range.is_empty() {
+            request_scout(state, room_name, &bases);
+            continue;
+        }
+        // A busy observer gets to the room in one of the next ticks
+        let free = in_range.into_iter().find(|(_, id)| !busy.contains(id));
+        if let Some((_, id)) = free {
+            busy.push(*id);
+            if let Ok(Some(observer)) = game::get_object_typed(*id) {
+                if observer.observe_room(room_name) != ReturnCode::Ok {
+                    warn!("Observer {} could not scan {}", id, room_name);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,228 @@
+//! Notices rooms that got stuck, which otherwise goes on until a human looks. A room is stuck if
+//! it didn't spawn for `config::WATCHDOG_SPAWN_STALL_TICKS` although its extensions are full, its
+//! controller made no progress for `config::WATCHDOG_CONTROLLER_STALL_TICKS` or most of its
+//! citizens were out of tasks for `config::WATCHDOG_IDLE_CHECKS` checks in a row.
+//!
+//! A stuck room gets replanned first. If it gets stuck again before a full stall window passed,
+//! see `stall_window`, its room state is thrown away and created anew, with the citizens it had.
+//! Both send an alert. The clocks live in the global, a reset restarts them, which only delays
+//! noticing a stuck room.
+use std::{cmp, collections::HashMap, fmt, sync::Mutex};
+
+use lazy_static::lazy_static;
+use log::{info, warn};
+use screeps::{game, RoomName, StructureSpawn, StructureType};
+
+use crate::{
+    alerts, config,
+    rooms::{
+        room_state::{
+            base::BaseState, transition_room_state, RoomState, RoomStateLifecycle, SetupBaseState,
+        },
+        stagger, structures,
+    },
+    state::BWState,
+    switches,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stall {
+    Spawning,
+    Controller,
+    Idle,
+}
+
+impl fmt::Display for Stall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stall::Spawning => f.write_str("no spawn despite full extensions"),
+            Stall::Controller => f.write_str("no controller progress"),
+            Stall::Idle => f.write_str("idle citizens"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Liveness {
+    /// Tick a spawn of the room was spawning last
+    spawned_at: u32,
+    /// Controller level and progress when they changed last
+    progress: (u32, u32),
+    progressed_at: u32,
+    /// Checks in a row that found most citizens idle
+    idle_checks: u32,
+    /// Times the room was stuck without a full stall window in between
+    trips: u32,
+    tripped_at: u32,
+}
+
+impl Liveness {
+    fn new(time: u32) -> Liveness {
+        Liveness {
+            spawned_at: time,
+            progress: (0, 0),
+            progressed_at: time,
+            idle_checks: 0,
+            trips: 0,
+            tripped_at: time,
+        }
+    }
+
+    /// Gives the room the full time again after the watchdog stepped in
+    fn restart(&mut self, time: u32) {
+        self.spawned_at = time;
+        self.progressed_at = time;
+        self.idle_checks = 0;
+    }
+}
+
+lazy_static! {
+    static ref LIVENESS: Mutex<HashMap<RoomName, Liveness>> = Mutex::new(HashMap::new());
+}
+
+/// Tracks the rooms with a state and steps in for the stuck ones, call it once per tick after the
+/// citizens did their jobs
+pub fn run(state: &mut BWState) {
+    let time = game::time();
+    let mut liveness = LIVENESS.lock().unwrap();
+    liveness.retain(|room_name, _| state.room_states.contains_key(room_name));
+    let mut stuck: Vec<(RoomName, Stall, u32)> = vec![];
+    for (room_name, room_state) in &state.room_states {
+        let room_liveness = liveness
+            .entry(*room_name)
+            .or_insert_with(|| Liveness::new(time));
+        track(*room_name, room_liveness, time);
+        if !stagger::is_due(*room_name, config::WATCHDOG_CHECK_INTERVAL) {
+            continue;
+        }
+        match check(*room_name, room_state, room_liveness, state, time) {
+            Some(stall) => {
+                room_liveness.trips += 1;
+                room_liveness.tripped_at = time;
+                room_liveness.restart(time);
+                stuck.push((*room_name, stall, room_liveness.trips));
+            }
+            None => {
+                if time - room_liveness.tripped_at > stall_window() {
+                    room_liveness.trips = 0;
+                }
+            }
+        }
+    }
+    drop(liveness);
+    for (room_name, stall, trips) in stuck {
+        step_in(state, room_name, stall, trips);
+    }
+}
+
+/// Ticks the clocks need to run out after a restart, a room that didn't trip for as long got well
+fn stall_window() -> u32 {
+    cmp::max(
+        cmp::max(
+            config::WATCHDOG_SPAWN_STALL_TICKS,
+            config::WATCHDOG_CONTROLLER_STALL_TICKS,
+        ),
+        config::WATCHDOG_IDLE_CHECKS * config::WATCHDOG_CHECK_INTERVAL,
+    )
+}
+
+/// Restarts the clocks of what the room got done this tick
+fn track(room_name: RoomName, liveness: &mut Liveness, time: u32) {
+    let spawns = structures::of_type::<StructureSpawn>(room_name, StructureType::Spawn);
+    if spawns.is_empty() || spawns.iter().any(|spawn| spawn.spawning().is_some()) {
+        liveness.spawned_at = time;
+    }
+    let progress = game::rooms::get(room_name)
+        .and_then(|room| room.controller())
+        .map(|controller| (controller.level(), controller.progress().unwrap_or(0)))
+        .unwrap_or((0, 0));
+    if progress != liveness.progress {
+        liveness.progress = progress;
+        liveness.progressed_at = time;
+    }
+}
+
+/// Why the room is stuck, if it is
+fn check(
+    room_name: RoomName,
+    room_state: &RoomState,
+    liveness: &mut Liveness,
+    state: &BWState,
+    time: u32,
+) -> Option<Stall> {
+    let room = game::rooms::get(room_name)?;
+    let full = room.energy_capacity_available() > 0
+        && room.energy_available() >= room.energy_capacity_available();
+    if full && time - liveness.spawned_at > config::WATCHDOG_SPAWN_STALL_TICKS {
+        return Some(Stall::Spawning);
+    }
+    let (level, _) = liveness.progress;
+    if level > 0
+        && level < 8
+        && time - liveness.progressed_at > config::WATCHDOG_CONTROLLER_STALL_TICKS
+    {
+        return Some(Stall::Controller);
+    }
+    let citizens: Vec<bool> = room_state
+        .helping_citizens()
+        .iter()
+        .filter_map(|id| state.citizens.get(id))
+        .map(|citizen| citizen.is_idle())
+        .collect();
+    let idle = citizens.iter().filter(|idle| **idle).count();
+    if citizens.len() >= config::WATCHDOG_IDLE_MIN_CITIZENS
+        && idle as f64 > citizens.len() as f64 * config::WATCHDOG_IDLE_SHARE
+    {
+        liveness.idle_checks += 1;
+    } else {
+        liveness.idle_checks = 0;
+    }
+    if liveness.idle_checks >= config::WATCHDOG_IDLE_CHECKS {
+        return Some(Stall::Idle);
+    }
+    None
+}
+
+/// Replans the room on the first trip and resets its state if that didn't help
+fn step_in(state: &mut BWState, room_name: RoomName, stall: Stall, trips: u32) {
+    let action = if trips <= 1 {
+        if let Some(room_state) = state.room_states.get_mut(&room_name) {
+            switches::replan_room(room_name, room_state);
+        }
+        "replanned it"
+    } else {
+        match reset_room_state(state, room_name) {
+            Ok(()) => "reset its room state",
+            Err(err) => {
+                warn!(
+                    "Could not reset the state of stuck room {}: {}",
+                    room_name, err
+                );
+                "could not reset its room state"
+            }
+        }
+    };
+    info!("Room {} is stuck ({}), {}", room_name, stall, action);
+    alerts::deadlock(
+        room_name,
+        &format!("{} is stuck with {}, {}", room_name, stall, action),
+    );
+}
+
+/// Creates the room state anew, the citizens keep working for the room
+fn reset_room_state(state: &mut BWState, room_name: RoomName) -> anyhow::Result<()> {
+    let new_state = match state.room_states.get(&room_name) {
+        Some(RoomState::Base(old)) => {
+            let mut base = BaseState::new(room_name)?;
+            base.data.helping_citizens = old.data.helping_citizens.clone();
+            RoomState::Base(base)
+        }
+        Some(RoomState::SetupBase(old)) => {
+            let mut setup = SetupBaseState::new(room_name)?;
+            setup.data.helping_citizens = old.data.helping_citizens.clone();
+            RoomState::SetupBase(setup)
+        }
+        None => return Ok(()),
+    };
+    transition_room_state(state, room_name, new_state)
+}
@@ -0,0 +1,104 @@
+//! Who may take what from which structure. Without it every creep that can carry takes from every
+//! provider, so upgraders drain the terminal and anyone walks off with the compounds of the labs.
+//! The rules are in `config::WITHDRAW_PERMISSIONS`, the first one matching decides and taking is
+//! allowed if none does. Rules with a room only apply there, so a room can differ from the others
+//! by listing its rules first.
+use screeps::{Creep, ResourceType, RoomName, SharedCreepProperties, StructureType};
+use std::convert::TryFrom;
+
+use crate::{config, constants::MEM_JOB_KIND, creeps::jobs::OokCreepJobKind};
+
+/// What a creep takes resources for, going by its job
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Upgrader,
+    Builder,
+    /// Carries resources between the structures of the room
+    Hauler,
+    Farmer,
+    /// Creeps without a job of the above, like the legacy ones
+    Other,
+}
+
+impl Role {
+    pub fn of_creep(creep: &Creep) -> Role {
+        let kind = match creep.memory().i32(MEM_JOB_KIND) {
+            Ok(Some(kind)) => kind,
+            _ => return Role::Other,
+        };
+        match OokCreepJobKind::try_from(kind) {
+            Ok(OokCreepJobKind::UpgradeController) => Role::Upgrader,
+            Ok(OokCreepJobKind::MaintainStructures) | Ok(OokCreepJobKind::BootstrapRoom) => {
+                Role::Builder
+            }
+            Ok(OokCreepJobKind::RoomLogistics) | Ok(OokCreepJobKind::LogisticsExtensionRoom) => {
+                Role::Hauler
+            }
+            Ok(OokCreepJobKind::FarmSource) | Ok(OokCreepJobKind::FarmExtensionRoom) => {
+                Role::Farmer
+            }
+            _ => Role::Other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Resources {
+    Any,
+    Energy,
+    /// Everything but energy, minerals and compounds
+    NotEnergy,
+}
+
+impl Resources {
+    fn matches(&self, resource_type: ResourceType) -> bool {
+        match self {
+            Resources::Any => true,
+            Resources::Energy => resource_type == ResourceType::Energy,
+            Resources::NotEnergy => resource_type != ResourceType::Energy,
+        }
+    }
+}
+
+/// One entry of the matrix, `None` matches everything
+#[derive(Debug, Clone, Copy)]
+pub struct WithdrawRule {
+    pub room: Option<&'static str>,
+    pub role: Option<Role>,
+    pub resources: Resources,
+    pub structure: StructureType,
+    pub allow: bool,
+}
+
+impl WithdrawRule {
+    fn matches(
+        &self,
+        room_name: RoomName,
+        role: Role,
+        resource_type: ResourceType,
+        structure: StructureType,
+    ) -> bool {
+        self.structure == structure
+            && self.resources.matches(resource_type)
+            && self.role.map(|r| r == role).unwrap_or(true)
+            && self
+                .room
+                .map(|r| RoomName::new(r).map(|r| r == room_name).unwrap_or(false))
+                .unwrap_or(true)
+    }
+}
+
+/// If the creep may take the resource from a structure of the type in the room
+pub fn may_withdraw(
+    creep: &Creep,
+    room_name: RoomName,
+    resource_type: ResourceType,
+    structure: StructureType,
+) -> bool {
+    let role = Role::of_creep(creep);
+    config::WITHDRAW_PERMISSIONS
+        .iter()
+        .find(|rule| rule.matches(room_name, role, resource_type, structure))
+        .map(|rule| rule.allow)
+        .unwrap_or(true)
+}
@@ -0,0 +1,210 @@
+//! Bulky data in memory segments instead of `Memory`, which gets parsed every tick. Data is kept
+//! by key, each key gets a segment of its own out of `config::DATA_SEGMENTS`, `Memory.segments`
+//! knows which.
+//!
+//! Segments are only readable a tick after they got activated and at most
+//! `MAX_ACTIVE_SEGMENTS` are active at once. `get` of a segment that isn't active asks for it and
+//! returns `Read::Pending`, the next tick it can be read. The segments read last stay active, the
+//! least recently read make room for the ones asked for.
+//!
+//! Writes wait for `persist` at the end of the tick, which writes at most
+//! `MAX_WRITES_PER_TICK` of them.
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+};
+
+use lazy_static::lazy_static;
+use log::warn;
+use screeps::raw_memory;
+use serde::{Deserialize, Serialize};
+
+use crate::{config, constants::MEM_SEGMENTS};
+
+/// The game keeps at most this many segments active and writes at most this many per tick
+const MAX_ACTIVE_SEGMENTS: usize = 10;
+const MAX_WRITES_PER_TICK: usize = 10;
+/// Size limit of a segment, in chars
+const MAX_SEGMENT_SIZE: usize = 100 * 1024;
+/// Every byte is stored as one char starting here, keeps the segment printable
+const CHAR_OFFSET: u32 = 0x30;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct SegmentsMemory {
+    /// Segment of every key
+    #[serde(default)]
+    keys: HashMap<String, u32>,
+}
+
+js_serializable!(SegmentsMemory);
+js_deserializable!(SegmentsMemory);
+
+fn load_memory() -> SegmentsMemory {
+    match screeps::memory::root().get::<SegmentsMemory>(MEM_SEGMENTS) {
+        Ok(Some(memory)) => memory,
+        Ok(None) => Default::default(),
+        Err(err) => {
+            warn!("Could not load segments memory: {}", err);
+            Default::default()
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Segments {
+    memory: SegmentsMemory,
+    memory_changed: bool,
+    /// Active segments and the ones asked for, the least recently read first
+    recently_read: VecDeque<u32>,
+    /// Data waiting for `persist`, by segment
+    writes: HashMap<u32, String>,
+}
+
+impl Segments {
+    fn touch(&mut self, segment: u32) {
+        self.recently_read.retain(|s| *s != segment);
+        self.recently_read.push_back(segment);
+        while self.recently_read.len() > MAX_ACTIVE_SEGMENTS {
+            self.recently_read.pop_front();
+        }
+    }
+
+    fn allocate(&mut self, key: &str) -> Option<u32> {
+        if let Some(segment) = self.memory.keys.get(key) {
+            return Some(*segment);
+        }
+        let used: Vec<u32> = self.memory.keys.values().cloned().collect();
+        let segment = config::DATA_SEGMENTS
+            .clone()
+            .find(|segment| !used.contains(segment))?;
+        self.memory.keys.insert(key.to_string(), segment);
+        self.memory_changed = true;
+        Some(segment)
+    }
+}
+
+lazy_static! {
+    static ref SEGMENTS: Mutex<Segments> = Mutex::new(Segments {
+        memory: load_memory(),
+        memory_changed: false,
+        recently_read: VecDeque::new(),
+        writes: HashMap::new(),
+    });
+}
+
+#[derive(Debug)]
+pub enum Read {
+    Loaded(String),
+    /// The segment gets activated, try again next tick
+    Pending,
+    /// Nothing stored under the key
+    Missing,
+}
+
+/// The data stored under the key
+pub fn get(key: &str) -> Read {
+    let mut segments = SEGMENTS.lock().unwrap();
+    let segment = match segments.memory.keys.get(key) {
+        Some(segment) => *segment,
+        None => return Read::Missing,
+    };
+    if let Some(data) = segments.writes.get(&segment) {
+        return Read::Loaded(data.clone());
+    }
+    segments.touch(segment);
+    match raw_memory::get_segment(segment) {
+        Some(data) if data.is_empty() => Read::Missing,
+        Some(data) => Read::Loaded(data),
+        None => Read::Pending,
+    }
+}
+
+/// Stores the data under the key at the end of the tick, false if it doesn't fit or no segment is
+/// left for it
+pub fn set(key: &str, data: String) -> bool {
+    if data.len() > MAX_SEGMENT_SIZE {
+        warn!("{} chars for segment {} are too many", data.len(), key);
+        return false;
+    }
+    let mut segments = SEGMENTS.lock().unwrap();
+    match segments.allocate(key) {
+        Some(segment) => {
+            segments.writes.insert(segment, data);
+            true
+        }
+        None => {
+            warn!("No segment left for {}", key);
+            false
+        }
+    }
+}
+
+/// Frees the segment of the key
+pub fn remove(key: &str) {
+    let mut segments = SEGMENTS.lock().unwrap();
+    if let Some(segment) = segments.memory.keys.remove(key) {
+        segments.writes.insert(segment, String::new());
+        segments.memory_changed = true;
+    }
+}
+
+/// Writes what was set and activates the segments for the next tick, call it at the end of the
+/// tick
+pub fn persist() {
+    let mut segments = SEGMENTS.lock().unwrap();
+    let writing: Vec<u32> = segments
+        .writes
+        .keys()
+        .take(MAX_WRITES_PER_TICK)
+        .cloned()
+        .collect();
+    for segment in writing {
+        if let Some(data) = segments.writes.remove(&segment) {
+            raw_memory::set_segment(segment, &data);
+        }
+    }
+    if segments.memory_changed {
+        screeps::memory::root().set(MEM_SEGMENTS, segments.memory.clone());
+        segments.memory_changed = false;
+    }
+    let active: Vec<u32> = segments.recently_read.iter().cloned().collect();
+    if !active.is_empty() {
+        raw_memory::set_active_segments(&active);
+    }
+}
+
+/// Bytes as printable chars, one per byte
+pub fn encode_bytes(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|byte| {
+            std::char::from_u32(*byte as u32 + CHAR_OFFSET).expect("offset byte is a valid char")
+        })
+        .collect()
+}
+
+/// The bytes of `encode_bytes`, `None` if a char is not one of them
+pub fn decode_bytes(encoded: &str) -> Option<Vec<u8>> {
+    encoded
+        .chars()
+        .map(|c| {
+            (c as u32)
+                .checked_sub(CHAR_OFFSET)
+                .filter(|b| *b <= u8::MAX as u32)
+                .map(|b| b as u8)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bytes_round_trip() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        let encoded = encode_bytes(&bytes);
+        assert_eq!(encoded.chars().count(), bytes.len());
+        assert_eq!(decode_bytes(&encoded), Some(bytes));
+    }
+}
@@ -0,0 +1,109 @@
+//! What the server the bot runs on offers. Private servers often come without a market, pixels or
+//! intershard resources, seasonal servers change the rules again. Calling those APIs anyway
+//! throws, so the subsystems using them ask here first.
+//!
+//! Everything is detected at runtime once per global. The `private-server` and `season` cargo
+//! features switch off what those servers are known to lack, for servers where detection is
+//! fooled by stubbed APIs.
+use std::fmt;
+
+use lazy_static::lazy_static;
+use log::info;
+use stdweb::unstable::TryInto;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServerKind {
+    Official,
+    Private,
+    Season,
+}
+
+impl fmt::Display for ServerKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServerKind::Official => f.write_str("official"),
+            ServerKind::Private => f.write_str("private"),
+            ServerKind::Season => f.write_str("season"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Capabilities {
+    pub kind: ServerKind,
+    /// Orders, deals and the transaction costs terminal sends are planned with
+    pub market: bool,
+    pub pixels: bool,
+    /// `InterShardMemory` and the account resources traded between shards
+    pub intershard: bool,
+}
+
+fn js_bool(value: stdweb::Value) -> bool {
+    value.try_into().unwrap_or(false)
+}
+
+fn detect() -> Capabilities {
+    let market = js_bool(js! {
+        return typeof Game.market !== "undefined"
+            && typeof Game.market.getAllOrders === "function";
+    });
+    let pixels = js_bool(js! {
+        return typeof Game.cpu.generatePixel === "function";
+    });
+    let intershard = js_bool(js! {
+        return typeof InterShardMemory !== "undefined" && typeof Game.resources !== "undefined";
+    });
+    let season = js_bool(js! {
+        return !!Game.shard && Game.shard.name === "shardSeason";
+    });
+    let kind = if cfg!(feature = "season") || season {
+        ServerKind::Season
+    } else if cfg!(feature = "private-server") || !market {
+        ServerKind::Private
+    } else {
+        ServerKind::Official
+    };
+    let capabilities = match kind {
+        ServerKind::Official => Capabilities {
+            kind,
+            market,
+            pixels,
+            intershard,
+        },
+        // Seasons run on a single shard of their own
+        ServerKind::Season => Capabilities {
+            kind,
+            market,
+            pixels: false,
+            intershard: false,
+        },
+        ServerKind::Private => Capabilities {
+            kind,
+            market: market && !cfg!(feature = "private-server"),
+            pixels: pixels && !cfg!(feature = "private-server"),
+            intershard: intershard && !cfg!(feature = "private-server"),
+        },
+    };
+    info!("Running on a {} server: {:?}", kind, capabilities);
+    capabilities
+}
+
+lazy_static! {
+    static ref CAPABILITIES: Capabilities = detect();
+}
+
+pub fn capabilities() -> Capabilities {
+    *CAPABILITIES
+}
+
+pub fn has_market() -> bool {
+    capabilities().market
+}
+
+pub fn has_pixels() -> bool {
+    capabilities().pixels
+}
+
+pub fn has_intershard() -> bool {
+    capabilities().intershard
+}
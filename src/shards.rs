@@ -0,0 +1,227 @@
+//! Shards. Every shard has a `Memory` of its own, so the state of the bot is per shard already and
+//! needs no namespace. `InterShardMemory` is the only thing shards share, each one publishes its
+//! `ShardIntel` in there for the others to read.
+//!
+//! A colony asks another shard for a claimer with `config::INTERSHARD_CLAIMS`. The shard that is
+//! asked spawns one and sends it through a portal. Creep memory doesn't cross shards, so the
+//! claimer is listed in the intel of the shard it left and gets its job back from there.
+use log::{info, warn};
+use screeps::{game, OwnedStructureProperties, RoomName, SharedCreepProperties};
+use serde::{Deserialize, Serialize};
+use stdweb::unstable::TryInto;
+
+use crate::{
+    config,
+    constants::MEM_RACE_KIND,
+    creeps::{
+        jobs::OokCreepJob,
+        races::{claimer::OokCreepClaimer, DynamicTasked, OokRace},
+    },
+    server,
+    state::{
+        requests::{self, Request, RequestData},
+        BWState,
+    },
+};
+
+/// Claimers live this long, if the room isn't claimed by then another one is sent
+const SENT_FORGET_TICKS: u32 = 600;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct InterShardClaim {
+    /// Shard asked to send the claimer
+    pub from_shard: String,
+    /// Room on `from_shard` with the portal to the colony
+    pub portal_room: RoomName,
+    /// Room to claim on the shard publishing the claim
+    pub target_room: RoomName,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SentCreep {
+    pub name: String,
+    pub to_shard: String,
+    pub target_room: RoomName,
+    pub sent_at: u32,
+}
+
+/// What a shard publishes in `InterShardMemory`
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ShardIntel {
+    #[serde(default)]
+    pub claims: Vec<InterShardClaim>,
+    #[serde(default)]
+    pub sent: Vec<SentCreep>,
+}
+
+js_serializable!(ShardIntel);
+js_deserializable!(ShardIntel);
+
+/// Name of the shard the bot runs on, empty on servers without shards
+pub fn current_shard() -> String {
+    let name = js! {
+        return Game.shard ? Game.shard.name : "";
+    };
+    name.try_into().unwrap_or_default()
+}
+
+/// Intel this shard published, empty if there is none yet
+pub fn read_local() -> ShardIntel {
+    if !server::has_intershard() {
+        return ShardIntel::default();
+    }
+    let intel = js! {
+        try {
+            return JSON.parse(InterShardMemory.getLocal() || "{}");
+        } catch (e) {
+            return {};
+        }
+    };
+    intel.try_into().unwrap_or_default()
+}
+
+/// Intel `shard` published, `None` if it published nothing readable
+pub fn read_remote(shard: &str) -> Option<ShardIntel> {
+    if !server::has_intershard() {
+        return None;
+    }
+    let intel = js! {
+        try {
+            return JSON.parse(InterShardMemory.getRemote(@{shard}) || "null");
+        } catch (e) {
+            return null;
+        }
+    };
+    intel.try_into().ok()
+}
+
+pub fn write_local(intel: &ShardIntel) {
+    if !server::has_intershard() {
+        return;
+    }
+    js! {
+        InterShardMemory.setLocal(JSON.stringify(@{intel.to_owned()}));
+    }
+}
+
+/// Lists a creep leaving for `to_shard`, so it gets its job back over there
+pub fn record_sent(name: &str, to_shard: &str, target_room: RoomName) {
+    let mut intel = read_local();
+    if intel.sent.iter().any(|sent| sent.name == name) {
+        return;
+    }
+    info!("Sending {} to {} to claim {}", name, to_shard, target_room);
+    intel.sent.push(SentCreep {
+        name: name.to_string(),
+        to_shard: to_shard.to_string(),
+        target_room,
+        sent_at: game::time(),
+    });
+    write_local(&intel);
+}
+
+/// Claims of this shard from the config, rooms that are mine already are done
+fn wanted_claims(shard: &str) -> Vec<InterShardClaim> {
+    config::INTERSHARD_CLAIMS
+        .iter()
+        .filter(|claim| claim.shard == shard)
+        .filter_map(|claim| {
+            Some(InterShardClaim {
+                from_shard: claim.from_shard.to_string(),
+                portal_room: RoomName::new(claim.portal_room).ok()?,
+                target_room: RoomName::new(claim.target_room).ok()?,
+            })
+        })
+        .filter(|claim| {
+            !game::rooms::get(claim.target_room)
+                .and_then(|room| room.controller())
+                .map(|controller| controller.my())
+                .unwrap_or(false)
+        })
+        .collect()
+}
+
+/// A request for the claim is open, or its claimer is spawning or on its way
+fn is_claim_handled(state: &BWState, local: &ShardIntel, claim: &InterShardClaim) -> bool {
+    let requested = state
+        .requests
+        .values()
+        .chain(state.handled_requests.values().flat_map(|requests| requests.values()))
+        .any(|request| {
+            matches!(&request.data, RequestData::InterShardClaim(data) if data.target_room == claim.target_room)
+        });
+    let spawned = state.citizens.values().any(|citizen| match citizen {
+        OokRace::Claimer(claimer) => matches!(
+            claimer.job(),
+            OokCreepJob::CrossPortal(job) if job.target_room == claim.target_room
+        ),
+        _ => false,
+    });
+    requested
+        || spawned
+        || local
+            .sent
+            .iter()
+            .any(|sent| sent.target_room == claim.target_room)
+}
+
+/// Gives a claimer that came through a portal its job back
+fn adopt_arrival(sent: &SentCreep) {
+    let creep = match game::creeps::get(&sent.name) {
+        Some(creep) => creep,
+        None => return,
+    };
+    if creep.memory().i32(MEM_RACE_KIND).ok().flatten().is_some() {
+        return;
+    }
+    info!("{} arrived to claim {}", sent.name, sent.target_room);
+    let job = OokCreepJob::ClaimRoom {
+        target_room: sent.target_room,
+    };
+    if let Err(err) = OokCreepClaimer::adopt(&creep, job) {
+        warn!("Couldn't adopt {}: {}", sent.name, err);
+    }
+}
+
+/// Publishes the claims of this shard and picks up what the other shards ask for or sent over
+pub fn sync(state: &mut BWState) -> anyhow::Result<()> {
+    if !server::has_intershard() {
+        return Ok(());
+    }
+    let time = game::time();
+    let shard = current_shard();
+    let mut local = read_local();
+    local.claims = wanted_claims(&shard);
+    local
+        .sent
+        .retain(|sent| sent.sent_at + SENT_FORGET_TICKS >= time);
+    for other in config::SHARDS.iter().filter(|other| **other != shard) {
+        let remote = match read_remote(other) {
+            Some(remote) => remote,
+            None => continue,
+        };
+        for claim in remote
+            .claims
+            .iter()
+            .filter(|claim| claim.from_shard == shard)
+        {
+            if is_claim_handled(state, &local, claim) {
+                continue;
+            }
+            info!("{} asks for a claimer for {}", other, claim.target_room);
+            state.add_request(Request::new(RequestData::InterShardClaim(
+                requests::InterShardClaim {
+                    to_shard: other.to_string(),
+                    portal_room: claim.portal_room,
+                    target_room: claim.target_room,
+                    spawning_creep_name: None,
+                },
+            )))?;
+        }
+        for sent in remote.sent.iter().filter(|sent| sent.to_shard == shard) {
+            adopt_arrival(sent);
+        }
+    }
+    write_local(&local);
+    Ok(())
+}
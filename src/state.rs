@@ -4,16 +4,22 @@ pub mod requests;
 use core::fmt;
 use lazy_static::lazy_static;
 use log::{info, warn};
-use screeps::{game, ObjectId, RoomName};
+use screeps::{game, HasId, ObjectId, RawObjectId, RoomName, SizedRoomObject};
 use std::{
-    collections::HashMap,
-    error::Error,
+    any::Any,
+    collections::{HashMap, VecDeque},
     sync::{atomic::AtomicUsize, Mutex, MutexGuard},
 };
 
 use crate::{
-    creeps::{races::OokRace, CreepKind},
-    rooms::{room_state::RoomState, MyRoom, RoomSettings},
+    config,
+    creeps::{naming::NameRegistry, races::OokRace, CreepKind},
+    rooms::{
+        bootstrap::Bootstrap, claims::ClaimRegistry, rebuild, repairs,
+        resource_provider::ProviderReservations, room_state::RoomState, structures, MyRoom,
+        RoomSettings,
+    },
+    switches,
 };
 
 use anyhow::anyhow;
@@ -22,6 +28,9 @@ use self::requests::{Request, RequestData, BootstrapWorkerCitizen};
 
 lazy_static! {
     pub static ref CONTEXT: Mutex<BWContext> = Mutex::new(BWContext::Initializing);
+    /// Objects looked up in this tick, see `get_object_cached`
+    static ref TICK_OBJECT_CACHE: Mutex<HashMap<RawObjectId, Box<dyn Any + Send>>> =
+        Mutex::new(HashMap::new());
 }
 
 static IN_TICK_UNIQUE_ID: AtomicUsize = AtomicUsize::new(0);
@@ -32,6 +41,27 @@ pub fn get_in_tick_unique_id() -> usize {
     IN_TICK_UNIQUE_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
 }
 
+/// Same as `game::get_object_typed`, but only crosses into JS once per id and tick
+///
+/// Lives next to the context instead of in it, so it can be used while the context is locked.
+pub fn get_object_cached<T>(id: ObjectId<T>) -> anyhow::Result<Option<T>>
+where
+    T: HasId + SizedRoomObject + Clone + Send + 'static,
+{
+    let raw_id: RawObjectId = id.into();
+    if let Some(cached) = TICK_OBJECT_CACHE.lock().unwrap().get(&raw_id) {
+        if let Some(obj) = cached.downcast_ref::<Option<T>>() {
+            return Ok(obj.clone());
+        }
+    }
+    let obj = game::get_object_typed(id)?;
+    TICK_OBJECT_CACHE
+        .lock()
+        .unwrap()
+        .insert(raw_id, Box::new(obj.clone()));
+    Ok(obj)
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum ContextError {
     #[error("Context has not been initialized yet! In: {0}")]
@@ -67,12 +97,10 @@ impl BWContext {
         }
     }
 
-    pub fn mut_state(&mut self) -> Result<&mut BWState, Box<dyn std::error::Error>> {
+    pub fn mut_state(&mut self) -> anyhow::Result<&mut BWState> {
         match self {
             BWContext::Initialized(context) => Ok(&mut context.state),
-            _ => Err(Box::new(ContextError::ContextNotInitialized(
-                "BWContext.state".into(),
-            ))),
+            _ => Err(ContextError::ContextNotInitialized("BWContext.state".into()).into()),
         }
     }
 
@@ -92,17 +120,17 @@ impl BWContext {
     ///   let mut state = context.mut_state()?;
     ///   state.ticks_since_init = state.ticks_since_init + 1;
     /// ```
-    pub fn update_state<F>(updater: F) -> Result<(), Box<dyn std::error::Error>>
+    pub fn update_state<F>(updater: F) -> anyhow::Result<()>
     where
-        F: FnOnce(&mut BWState) -> Result<(), Box<dyn Error>>,
+        F: FnOnce(&mut BWState) -> anyhow::Result<()>,
     {
-        let mut context = CONTEXT.lock()?;
+        let mut context = BWContext::get();
         let mut state = context.mut_state()?;
         updater(&mut state)?;
         Ok(())
     }
 
-    pub fn update_state_self<F>(&mut self, updater: F) -> Result<(), Box<dyn std::error::Error>>
+    pub fn update_state_self<F>(&mut self, updater: F) -> anyhow::Result<()>
     where
         F: Fn(&mut BWState) -> (),
     {
@@ -121,12 +149,23 @@ pub struct BWState {
     pub ticks_since_init: i32,
     pub room_settings: HashMap<MyRoom, RoomSettings>,
     pub room_states: HashMap<RoomName, RoomState>,
+    /// Rooms whose states are created over the next ticks, see `init_pending_room_states`
+    pub pending_room_states: VecDeque<RoomName>,
     #[deprecated]
     pub kinded_creeps: HashMap<ObjectId<screeps::Creep>, CreepKind>,
     pub citizens: HashMap<ObjectId<screeps::Creep>, OokRace>,
     pub requests: HashMap<UniqId, Request>,
     /// Requests handled in Game Ticks -> RequestId
     pub handled_requests: HashMap<u32, HashMap<UniqId, Request>>,
+    /// Resources creeps decided to fetch this tick, see `ProviderReservations`
+    pub provider_reservations: ProviderReservations,
+    /// Smoothed CPU usage of the last ticks, see `BWState::record_cpu_used`
+    pub avg_cpu_used: f64,
+    /// Set while a freshly spawned room runs its opener
+    pub bootstrap: Option<Bootstrap>,
+    pub creep_names: NameRegistry,
+    /// Claims and reservations in flight, see `rooms::claims`
+    pub claims: ClaimRegistry,
     // Fast access for cached data at Room -> x -> y
     // pub pois: HashMap<RoomName, HashMap<u32, HashMap<u32, PoisAt>>>,
 }
@@ -149,9 +188,29 @@ impl BWState {
     pub fn next_tick(&mut self) {
         self.ticks_since_init = self.ticks_since_init + 1;
         IN_TICK_UNIQUE_ID.store(0, std::sync::atomic::Ordering::Relaxed);
+        TICK_OBJECT_CACHE.lock().unwrap().clear();
+        self.provider_reservations.clear();
+        repairs::clear();
+        self.claims
+            .refresh(&self.requests, &self.handled_requests, &self.citizens);
+        let destroyed = structures::refresh();
+        rebuild::react(self, destroyed);
+    }
+
+    pub fn record_cpu_used(&mut self, cpu_used: f64) {
+        self.avg_cpu_used += (cpu_used - self.avg_cpu_used) * config::CPU_AVG_WEIGHT;
     }
 
     pub fn add_request(&mut self, request: Request) -> anyhow::Result<()> {
+        if let Some(subsystem) = switches::subsystem_of_request(&request) {
+            if switches::is_subsystem_paused(subsystem) {
+                return Err(anyhow!("{} is paused", subsystem));
+            }
+        }
+        if self.claims.is_duplicate(&request) {
+            return Err(anyhow!("another claim of the target room is in flight"));
+        }
+        self.claims.register(&request);
         match request {
             Request{ data: RequestData::BootstrapWorkerCitizen(BootstrapWorkerCitizen { .. }), ..} => {
                 info!("Inserting request : {:?}", request);
@@ -163,6 +222,21 @@ impl BWState {
                 self.requests.insert(request.request_id.to_owned(), request);
                 Ok(())
             }
+            Request{ data: RequestData::Dismantle(requests::Dismantle { .. }), ..} => {
+                info!("Inserting request : {:?}", request);
+                self.requests.insert(request.request_id.to_owned(), request);
+                Ok(())
+            }
+            Request{ data: RequestData::InterShardClaim(requests::InterShardClaim { .. }), ..} => {
+                info!("Inserting request : {:?}", request);
+                self.requests.insert(request.request_id.to_owned(), request);
+                Ok(())
+            }
+            Request{ data: RequestData::Salvage(requests::Salvage { .. }), ..} => {
+                info!("Inserting request : {:?}", request);
+                self.requests.insert(request.request_id.to_owned(), request);
+                Ok(())
+            }
         }
     }
 
@@ -1,6 +1,9 @@
-use screeps::RoomName;
+use std::{collections::HashMap, sync::Mutex};
 
-use crate::creeps::jobs::OokCreepJob;
+use lazy_static::lazy_static;
+use screeps::{game, Creep, ObjectId, RoomName, SharedCreepProperties, Structure};
+
+use crate::{constants::MEM_REQUEST_ID, creeps::jobs::OokCreepJob};
 
 use super::UniqId;
 //
@@ -37,6 +40,9 @@ impl Request {
 pub enum RequestData {
     BootstrapWorkerCitizen(BootstrapWorkerCitizen),
     Citizen(Citizen),
+    Dismantle(Dismantle),
+    InterShardClaim(InterShardClaim),
+    Salvage(Salvage),
 }
 
 #[derive(Clone, Debug)] 
@@ -51,6 +57,64 @@ pub struct Citizen {
     pub spawning_creep_name: Option<String>,
     pub initial_job: OokCreepJob,
     pub resolve_panic: bool,
+    /// The target room can't spawn the creep, the closest base that can does. See
+    /// `room_state::plan_citizen`.
+    pub spawn_elsewhere: bool,
+}
+
+/// Tear down `targets`, the energy they give goes to the storage of the room handling the request
+#[derive(Clone, Debug)]
+pub struct Dismantle {
+    pub target_room_name: RoomName,
+    pub targets: Vec<ObjectId<Structure>>,
+    pub spawning_creep_name: Option<String>,
+}
+
+/// Another shard wants `target_room` claimed, the claimer goes there through the portal in
+/// `portal_room`, see `shards`
+#[derive(Clone, Debug)]
+pub struct InterShardClaim {
+    pub to_shard: String,
+    pub portal_room: RoomName,
+    pub target_room: RoomName,
+    pub spawning_creep_name: Option<String>,
+}
+
+/// Loot the ruins and tombstones left in `target_room_name`, see `rooms::salvage`
+#[derive(Clone, Debug)]
+pub struct Salvage {
+    pub target_room_name: RoomName,
+    pub spawning_creep_name: Option<String>,
+}
+
+lazy_static! {
+    /// Tick of the last lookup and the names of the creeps by the request they were spawned for
+    static ref CREEPS_BY_REQUEST: Mutex<(u32, HashMap<UniqId, String>)> =
+        Mutex::new((0, HashMap::new()));
+}
+
+/// The request the creep was spawned for, the races keep it in their memory
+pub fn request_of(creep: &Creep) -> Option<UniqId> {
+    creep
+        .memory()
+        .string(MEM_REQUEST_ID)
+        .ok()
+        .flatten()
+        .map(UniqId::from)
+}
+
+/// The creep spawned for the request, while it lives. The creeps are indexed once per tick.
+pub fn creep_of(request_id: &UniqId) -> Option<Creep> {
+    let time = game::time();
+    let mut index = CREEPS_BY_REQUEST.lock().unwrap();
+    if index.0 != time {
+        let by_request = game::creeps::values()
+            .into_iter()
+            .filter_map(|creep| request_of(&creep).map(|id| (id, creep.name())))
+            .collect();
+        *index = (time, by_request);
+    }
+    index.1.get(request_id).and_then(|name| game::creeps::get(name))
 }
 
 // #[derive(Clone, Debug)]
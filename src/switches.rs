@@ -0,0 +1,159 @@
+//! Switches to intervene by hand, set in `Memory.ook_flags` from the console and read at the
+//! start of every tick:
+//!
+//! - `pause` skips whole ticks, nothing runs until it is unset
+//! - `pause_spawning` skips the `Spawning` stage, requests pile up but nothing spawns for them
+//! - `pause_trade` skips the `Trade` stage
+//! - `pause_expansion` refuses new claim requests, for this shard and others
+//! - `pause_combat` refuses new requests for blockers, healers and harassers
+//! - `dismantle` lets bases request dismantlers for the structures in their way, without it they
+//!   are only logged
+//! - `replan` places the structures of every base again and drops the cost matrices, it is unset
+//!   right away
+//!
+//! Like `Memory.ook_flags = {pause_trade: true}`. Creeps already spawned keep doing their jobs.
+use std::{fmt, sync::Mutex};
+
+use lazy_static::lazy_static;
+use log::{info, warn};
+use screeps::RoomName;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    constants::MEM_OOK_FLAGS,
+    creeps::jobs::OokCreepJob,
+    pipeline::Stage,
+    rooms::{cost_matrix, room_state::RoomState},
+    state::{
+        requests::{self, Request, RequestData},
+        BWState,
+    },
+};
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct Flags {
+    #[serde(default)]
+    pause: bool,
+    #[serde(default)]
+    pause_spawning: bool,
+    #[serde(default)]
+    pause_trade: bool,
+    #[serde(default)]
+    pause_expansion: bool,
+    #[serde(default)]
+    pause_combat: bool,
+    #[serde(default)]
+    dismantle: bool,
+    #[serde(default)]
+    replan: bool,
+}
+
+js_serializable!(Flags);
+js_deserializable!(Flags);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Subsystem {
+    Spawning,
+    Trade,
+    Expansion,
+    Combat,
+}
+
+impl fmt::Display for Subsystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Subsystem::Spawning => f.write_str("spawning"),
+            Subsystem::Trade => f.write_str("trade"),
+            Subsystem::Expansion => f.write_str("expansion"),
+            Subsystem::Combat => f.write_str("combat"),
+        }
+    }
+}
+
+lazy_static! {
+    /// Flags of this tick, see `load`
+    static ref FLAGS: Mutex<Flags> = Mutex::new(Flags::default());
+}
+
+/// Reads the flags of this tick, call it first thing in the tick
+///
+/// Returns if the replan was asked for, the flag is unset then. While paused the replan waits for
+/// the first tick that runs.
+pub fn load() -> bool {
+    let mut flags = match screeps::memory::root().get::<Flags>(MEM_OOK_FLAGS) {
+        Ok(flags) => flags.unwrap_or_default(),
+        Err(err) => {
+            warn!("Could not load the flags, ignoring them: {}", err);
+            Flags::default()
+        }
+    };
+    let replan = flags.replan && !flags.pause;
+    if replan {
+        flags.replan = false;
+        screeps::memory::root().set(MEM_OOK_FLAGS, flags.clone());
+    }
+    *FLAGS.lock().unwrap() = flags;
+    replan
+}
+
+/// If the whole bot is paused
+pub fn is_paused() -> bool {
+    FLAGS.lock().unwrap().pause
+}
+
+/// If structures in the way may be torn down, it has to be asked for
+pub fn is_dismantling_allowed() -> bool {
+    FLAGS.lock().unwrap().dismantle
+}
+
+pub fn is_subsystem_paused(subsystem: Subsystem) -> bool {
+    let flags = FLAGS.lock().unwrap();
+    match subsystem {
+        Subsystem::Spawning => flags.pause_spawning,
+        Subsystem::Trade => flags.pause_trade,
+        Subsystem::Expansion => flags.pause_expansion,
+        Subsystem::Combat => flags.pause_combat,
+    }
+}
+
+/// Subsystem whose pause skips the stage
+pub fn subsystem_of_stage(stage: Stage) -> Option<Subsystem> {
+    match stage {
+        Stage::Spawning => Some(Subsystem::Spawning),
+        Stage::Trade => Some(Subsystem::Trade),
+        _ => None,
+    }
+}
+
+/// Subsystem whose pause refuses the request
+pub fn subsystem_of_request(request: &Request) -> Option<Subsystem> {
+    match &request.data {
+        RequestData::Citizen(requests::Citizen { initial_job, .. }) => match initial_job {
+            OokCreepJob::ClaimRoom { .. } | OokCreepJob::CrossPortal(_) => {
+                Some(Subsystem::Expansion)
+            }
+            OokCreepJob::DefendRoom { .. }
+            | OokCreepJob::HealCitizens { .. }
+            | OokCreepJob::PatrolRemotes { .. } => Some(Subsystem::Combat),
+            _ => None,
+        },
+        RequestData::InterShardClaim(_) => Some(Subsystem::Expansion),
+        _ => None,
+    }
+}
+
+/// Has every base place its structures again and drops the cost matrices of all rooms
+pub fn replan(state: &mut BWState) {
+    info!("Replanning {} rooms", state.room_states.len());
+    for (room_name, room_state) in state.room_states.iter_mut() {
+        replan_room(*room_name, room_state);
+    }
+}
+
+/// Has the base place its structures again and drops the cost matrix of the room
+pub fn replan_room(room_name: RoomName, room_state: &mut RoomState) {
+    if let RoomState::Base(base) = room_state {
+        base.data.placed_for_rcl = 0;
+    }
+    cost_matrix::invalidate(room_name);
+}
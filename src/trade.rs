@@ -1,8 +1,216 @@
-use std::cmp::{self, Reverse};
+use std::{
+    cmp::{self, Reverse},
+    sync::Mutex,
+};
 
-use log::{info, warn};
+use lazy_static::lazy_static;
+use log::{debug, info, warn};
 /// Trade with ppl
-use screeps::{HasCooldown, HasStore, MarketResourceType, ResourceType, Room, Structure, StructureTerminal, find, game::{self, market::OrderType}};
+use screeps::{HasCooldown, HasStore, MarketResourceType, ResourceType, ReturnCode, Room, RoomName, Structure, StructureTerminal, find, game::{self, market::OrderType}};
+use serde::{Deserialize, Serialize};
+
+use crate::{config, constants::MEM_TERMINAL_SENDS};
+
+pub mod budget;
+pub mod intershard;
+pub mod prices;
+
+use self::budget::SpendCategory;
+
+/// Failed sends are retried after `SEND_BACKOFF_TICKS * 2^attempts` ticks
+const SEND_BACKOFF_TICKS: u32 = 10;
+const SEND_MAX_BACKOFF_TICKS: u32 = 1000;
+const SEND_MAX_ATTEMPTS: u32 = 8;
+
+/// A terminal send that waits until the terminal holds the amount and the energy fee
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlannedSend {
+    pub from: RoomName,
+    pub destination: RoomName,
+    pub resource_type: ResourceType,
+    pub amount: u32,
+    pub description: String,
+    /// Energy the send costs, computed when planning
+    pub fee: u32,
+    /// Don't try before this tick, set on cooldown and failures
+    pub not_before: u32,
+    pub attempts: u32,
+}
+
+impl PlannedSend {
+    /// What the terminal needs to hold for this send
+    fn requirements(&self) -> Vec<(ResourceType, u32)> {
+        if self.resource_type == ResourceType::Energy {
+            vec![(ResourceType::Energy, self.amount + self.fee)]
+        } else {
+            vec![(self.resource_type, self.amount), (ResourceType::Energy, self.fee)]
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct PlannedSends {
+    sends: Vec<PlannedSend>,
+}
+
+js_serializable!(PlannedSends);
+js_deserializable!(PlannedSends);
+
+lazy_static! {
+    /// Loaded from memory on first use, so sends survive a global reset
+    static ref PLANNED_SENDS: Mutex<Option<PlannedSends>> = Mutex::new(None);
+}
+
+fn with_planned_sends<T, F: FnOnce(&mut PlannedSends) -> T>(f: F) -> T {
+    let mut planned = PLANNED_SENDS.lock().unwrap();
+    let sends = planned.get_or_insert_with(|| {
+        match screeps::memory::root().get::<PlannedSends>(MEM_TERMINAL_SENDS) {
+            Ok(Some(sends)) => sends,
+            Ok(None) => Default::default(),
+            Err(err) => {
+                warn!("Could not load planned terminal sends: {}", err);
+                Default::default()
+            }
+        }
+    });
+    f(sends)
+}
+
+/// Queues a send, the terminal gets topped up with `amount` and the fee before it happens
+pub fn plan_send(
+    from: RoomName,
+    destination: RoomName,
+    resource_type: ResourceType,
+    amount: u32,
+    description: &str,
+) {
+    let fee = game::market::calc_transaction_cost(amount, from, destination).ceil() as u32;
+    info!(
+        "Planning send of {} {:?} from {} to {}, fee {}",
+        amount, resource_type, from, destination, fee
+    );
+    with_planned_sends(|planned| {
+        planned.sends.push(PlannedSend {
+            from,
+            destination,
+            resource_type,
+            amount,
+            description: description.to_string(),
+            fee,
+            not_before: game::time(),
+            attempts: 0,
+        })
+    });
+}
+
+/// If a send with the description from the room is still waiting
+pub fn has_planned_send(from: RoomName, description: &str) -> bool {
+    with_planned_sends(|planned| {
+        planned
+            .sends
+            .iter()
+            .any(|send| send.from == from && send.description == description)
+    })
+}
+
+/// Amount of `resource_type` the terminal of the room has to keep, the configured stock level
+/// plus what the planned sends need
+pub fn terminal_keep_amount(room_name: RoomName, resource_type: ResourceType) -> u32 {
+    let buffer = config::terminal_stock_level(resource_type);
+    let planned: u32 = with_planned_sends(|planned| {
+        planned
+            .sends
+            .iter()
+            .filter(|send| send.from == room_name)
+            .flat_map(|send| send.requirements())
+            .filter(|(ty, _)| *ty == resource_type)
+            .map(|(_, amount)| amount)
+            .sum()
+    });
+    buffer + planned
+}
+
+fn find_terminal(room_name: RoomName) -> Option<StructureTerminal> {
+    game::rooms::get(room_name)?
+        .find(find::STRUCTURES)
+        .into_iter()
+        .find_map(|s| match s {
+            Structure::Terminal(t) => Some(t),
+            _ => None,
+        })
+}
+
+/// Executes the planned sends whose terminal holds everything they need
+pub fn run_planned_sends() {
+    let time = game::time();
+    with_planned_sends(|planned| {
+        // Each terminal can only send once per tick
+        let mut used_terminals: Vec<RoomName> = vec![];
+        planned.sends.retain(|send| {
+            if send.attempts >= SEND_MAX_ATTEMPTS {
+                warn!("Giving up on send {:?}", send);
+            }
+            send.attempts < SEND_MAX_ATTEMPTS
+        });
+        let mut done: Vec<usize> = vec![];
+        for (i, send) in planned.sends.iter_mut().enumerate() {
+            if send.not_before > time || used_terminals.contains(&send.from) {
+                continue;
+            }
+            let terminal = match find_terminal(send.from) {
+                Some(terminal) => terminal,
+                None => {
+                    warn!("No terminal in {} for planned send", send.from);
+                    send.attempts += 1;
+                    send.not_before = time + SEND_MAX_BACKOFF_TICKS;
+                    continue;
+                }
+            };
+            let stocked = send
+                .requirements()
+                .iter()
+                .all(|(ty, amount)| terminal.store_used_capacity(Some(*ty)) >= *amount);
+            if !stocked {
+                // Runners are topping it up, see `terminal_keep_amount`
+                continue;
+            }
+            if terminal.cooldown() > 0 {
+                send.not_before = time + terminal.cooldown();
+                continue;
+            }
+            used_terminals.push(send.from);
+            match terminal.send(
+                send.resource_type,
+                send.amount,
+                send.destination,
+                Some(&send.description),
+            ) {
+                ReturnCode::Ok => {
+                    info!(
+                        "Sent {} {:?} from {} to {}",
+                        send.amount, send.resource_type, send.from, send.destination
+                    );
+                    done.push(i);
+                }
+                code => {
+                    send.attempts += 1;
+                    let backoff = SEND_BACKOFF_TICKS * 2u32.pow(send.attempts);
+                    send.not_before = time + cmp::min(backoff, SEND_MAX_BACKOFF_TICKS);
+                    warn!(
+                        "Send from {} failed with {:?}, retrying in {} ticks",
+                        send.from,
+                        code,
+                        send.not_before - time
+                    );
+                }
+            }
+        }
+        for i in done.into_iter().rev() {
+            planned.sends.remove(i);
+        }
+        screeps::memory::root().set(MEM_TERMINAL_SENDS, planned.clone());
+    });
+}
 
 pub fn get_energy(room: &Room) {
     if let Some(terminal) = room
@@ -26,7 +234,7 @@ pub fn get_energy(room: &Room) {
             )));
             let mut good_orders: Vec<(f64, game::market::Order)> = orders
                 .into_iter()
-                .filter(|o| o.order_type == OrderType::Sell && o.remaining_amount >= 1000 && o.price < 0.75)
+                .filter(|o| o.order_type == OrderType::Sell && o.remaining_amount >= 1000)
                 .filter_map(|o| {
                     if let Some(order_room_name) = o.room_name {
                         let target_amount = get_target_amount(o.remaining_amount);
@@ -35,8 +243,14 @@ pub fn get_energy(room: &Room) {
                             order_room_name,
                             room.name(),
                         );
-                        if trans_cost / (target_amount as f64) < 0.66 {
-                            Some((trans_cost / target_amount as f64, o))
+                        // The fee is paid in energy, so less of it arrives
+                        let net_amount = target_amount as f64 - trans_cost;
+                        if net_amount <= 0. {
+                            return None;
+                        }
+                        let effective_price = o.price * target_amount as f64 / net_amount;
+                        if prices::is_buy_opportunity(ResourceType::Energy, effective_price) {
+                            Some((effective_price, o))
                         } else {
                             None
                         }
@@ -45,12 +259,20 @@ pub fn get_energy(room: &Room) {
                     }
                 })
                 .collect();
-            good_orders.sort_unstable_by(|(efficiency_a, _o), (efficiency_b, _o2)| {
-                efficiency_a.partial_cmp(efficiency_b).unwrap()
+            good_orders.sort_unstable_by(|(price_a, _o), (price_b, _o2)| {
+                price_a.partial_cmp(price_b).unwrap()
             });
             if let Some((_, order)) = good_orders.first() {
-                match game::market::deal(&order.id, get_target_amount(order.remaining_amount), Some(room.name())) {
+                // Buy less if the budget doesn't cover all of it
+                let affordable = (budget::available(SpendCategory::EnergyBuying) / order.price) as u32;
+                let amount = cmp::min(get_target_amount(order.remaining_amount), affordable);
+                if amount < 1000 {
+                    debug!("Energy buying budget exhausted for room {}", room.name());
+                    return;
+                }
+                match game::market::deal(&order.id, amount, Some(room.name())) {
                     screeps::ReturnCode::Ok => {
+                        budget::record_spend(SpendCategory::EnergyBuying, order.price * amount as f64);
                         info!("Done trade for room {}: {:?}", room.name(), order);
                     },
                     ret => warn!("Market trade: Unknown return code {:?}", ret),
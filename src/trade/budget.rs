@@ -0,0 +1,83 @@
+//! Empire wide credit accounting. Every market operation that costs credits asks for its budget
+//! first, so a price spike can't drain everything.
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{config, constants::MEM_CREDIT_BUDGET};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpendCategory {
+    EnergyBuying = 0,
+    MineralBuying = 1,
+    /// Pixels and CPU unlocks
+    PixelCpu = 2,
+}
+
+impl SpendCategory {
+    fn budget(&self) -> f64 {
+        match self {
+            SpendCategory::EnergyBuying => config::CREDIT_BUDGET_ENERGY_BUYING,
+            SpendCategory::MineralBuying => config::CREDIT_BUDGET_MINERAL_BUYING,
+            SpendCategory::PixelCpu => config::CREDIT_BUDGET_PIXEL_CPU,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct CreditBudget {
+    period_start: u32,
+    /// Indexed by `SpendCategory`
+    spent: [f64; 3],
+}
+
+js_serializable!(CreditBudget);
+js_deserializable!(CreditBudget);
+
+lazy_static! {
+    static ref BUDGET: Mutex<Option<CreditBudget>> = Mutex::new(None);
+}
+
+fn with_budget<T, F: FnOnce(&mut CreditBudget) -> T>(f: F) -> T {
+    let mut budget = BUDGET.lock().unwrap();
+    let budget = budget.get_or_insert_with(|| {
+        match screeps::memory::root().get::<CreditBudget>(MEM_CREDIT_BUDGET) {
+            Ok(Some(budget)) => budget,
+            Ok(None) => Default::default(),
+            Err(err) => {
+                warn!("Could not load credit budget: {}", err);
+                Default::default()
+            }
+        }
+    });
+    let time = screeps::game::time();
+    if budget.period_start + config::CREDIT_BUDGET_PERIOD <= time {
+        info!("New credit budget period, last one spent {:?}", budget.spent);
+        *budget = CreditBudget {
+            period_start: time,
+            spent: Default::default(),
+        };
+    }
+    f(budget)
+}
+
+/// Credits the category may spend right now, respecting the reserve floor
+pub fn available(category: SpendCategory) -> f64 {
+    let above_floor = screeps::game::market::credits() - config::CREDIT_RESERVE_FLOOR;
+    let left_in_budget = with_budget(|budget| category.budget() - budget.spent[category as usize]);
+    above_floor.min(left_in_budget).max(0.)
+}
+
+pub fn can_spend(category: SpendCategory, credits: f64) -> bool {
+    credits <= available(category)
+}
+
+/// Books credits that were spent, call it after the market accepted the operation
+pub fn record_spend(category: SpendCategory, credits: f64) {
+    with_budget(|budget| {
+        budget.spent[category as usize] += credits;
+        screeps::memory::root().set(MEM_CREDIT_BUDGET, budget.clone());
+    });
+}
@@ -0,0 +1,146 @@
+//! Sells pixels and buys CPU unlocks when the market price crosses the bands in `config`. Every
+//! deal is written to `Memory.stats.intershard_trades`, so it can be audited later.
+use log::{debug, info, warn};
+use screeps::{
+    game::{self, market::OrderType},
+    IntershardResourceType, MarketResourceType, ReturnCode,
+};
+use serde::{Deserialize, Serialize};
+use stdweb::unstable::TryInto;
+
+use super::budget::{self, SpendCategory};
+use crate::config;
+
+/// Ticks between two looks at the intershard market
+const TRADE_INTERVAL: u32 = 1000;
+/// Trades kept in the stats, older ones get dropped
+const TRADE_LOG_SIZE: usize = 50;
+
+const MEM_STATS: &str = "stats";
+const MEM_INTERSHARD_TRADES: &str = "intershard_trades";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct IntershardTrade {
+    time: u32,
+    resource: String,
+    order_type: String,
+    amount: u32,
+    price: f64,
+    credits_after: f64,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct IntershardTrades {
+    trades: Vec<IntershardTrade>,
+}
+
+js_serializable!(IntershardTrades);
+js_deserializable!(IntershardTrades);
+
+/// Amount of an intershard resource the account holds, these are not part of any store
+fn account_resource(name: &str) -> u32 {
+    let amount = js! {
+        return Game.resources[@{name}] || 0;
+    };
+    amount.try_into().unwrap_or(0)
+}
+
+fn log_trade(resource: &str, order_type: OrderType, amount: u32, price: f64) {
+    let trade = IntershardTrade {
+        time: game::time(),
+        resource: resource.to_string(),
+        order_type: format!("{:?}", order_type),
+        amount,
+        price,
+        credits_after: game::market::credits(),
+    };
+    info!("Intershard trade: {:?}", trade);
+    let stats = match screeps::memory::root().dict_or_create(MEM_STATS) {
+        Ok(stats) => stats,
+        Err(err) => {
+            warn!("Memory.{} is not an object: {}", MEM_STATS, err);
+            return;
+        }
+    };
+    let mut log = match stats.get::<IntershardTrades>(MEM_INTERSHARD_TRADES) {
+        Ok(Some(log)) => log,
+        Ok(None) => Default::default(),
+        Err(err) => {
+            warn!("Could not load intershard trade log: {}", err);
+            Default::default()
+        }
+    };
+    log.trades.push(trade);
+    if log.trades.len() > TRADE_LOG_SIZE {
+        let excess = log.trades.len() - TRADE_LOG_SIZE;
+        log.trades.drain(..excess);
+    }
+    stats.set(MEM_INTERSHARD_TRADES, log);
+}
+
+/// Sells pixels above `PIXEL_KEEP` to the best buy order, if it pays enough
+fn sell_pixels() {
+    let pixels = account_resource("pixel");
+    if pixels <= config::PIXEL_KEEP {
+        return;
+    }
+    let resource = MarketResourceType::IntershardResource(IntershardResourceType::Pixel);
+    let best = game::market::get_all_orders(Some(resource))
+        .into_iter()
+        .filter(|o| {
+            o.order_type == OrderType::Buy
+                && o.remaining_amount > 0
+                && o.price >= config::PIXEL_SELL_MIN_PRICE
+        })
+        .max_by(|a, b| a.price.partial_cmp(&b.price).unwrap());
+    let order = match best {
+        Some(order) => order,
+        None => {
+            debug!("No pixel buy order above {}", config::PIXEL_SELL_MIN_PRICE);
+            return;
+        }
+    };
+    let amount = std::cmp::min(pixels - config::PIXEL_KEEP, order.remaining_amount);
+    match game::market::deal(&order.id, amount, None) {
+        ReturnCode::Ok => log_trade("pixel", OrderType::Sell, amount, order.price),
+        code => warn!("Couldn't sell {} pixels to {}: {:?}", amount, order.id, code),
+    }
+}
+
+/// Buys one CPU unlock at a time from the cheapest sell order, if it's cheap enough and the
+/// budget covers it
+fn buy_cpu_unlock() {
+    let resource = MarketResourceType::IntershardResource(IntershardResourceType::CpuUnlock);
+    let cheapest = game::market::get_all_orders(Some(resource))
+        .into_iter()
+        .filter(|o| {
+            o.order_type == OrderType::Sell
+                && o.remaining_amount > 0
+                && o.price <= config::CPU_UNLOCK_BUY_MAX_PRICE
+        })
+        .min_by(|a, b| a.price.partial_cmp(&b.price).unwrap());
+    let order = match cheapest {
+        Some(order) => order,
+        None => return,
+    };
+    if !budget::can_spend(SpendCategory::PixelCpu, order.price) {
+        debug!("CPU unlock at {} is over the budget", order.price);
+        return;
+    }
+    match game::market::deal(&order.id, 1, None) {
+        ReturnCode::Ok => {
+            budget::record_spend(SpendCategory::PixelCpu, order.price);
+            log_trade("cpuUnlock", OrderType::Buy, 1, order.price);
+        }
+        code => warn!("Couldn't buy a CPU unlock from {}: {:?}", order.id, code),
+    }
+}
+
+/// Runs the intershard trades, does nothing unless `INTERSHARD_TRADING_ENABLED` is set
+pub fn run_intershard_trading() {
+    if !config::INTERSHARD_TRADING_ENABLED || game::time() % TRADE_INTERVAL != 0 {
+        return;
+    }
+    sell_pixels();
+    buy_cpu_unlock();
+}
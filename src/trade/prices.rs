@@ -0,0 +1,131 @@
+//! Keeps a short price history per resource, so buying can follow the market instead of fixed
+//! thresholds.
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+};
+
+use lazy_static::lazy_static;
+use log::debug;
+use screeps::{
+    game::{self, market::OrderType},
+    MarketResourceType, ResourceType,
+};
+
+/// Ticks between two samples of the same resource
+const SAMPLE_INTERVAL: u32 = 100;
+/// Samples kept per resource, older ones get dropped
+const RING_SIZE: usize = 48;
+/// Samples used for the moving average
+const AVERAGE_WINDOW: usize = 24;
+/// Buying is only worth it this much below the average
+const BUY_DISCOUNT: f64 = 0.9;
+
+pub const TRACKED_RESOURCES: [ResourceType; 9] = [
+    ResourceType::Energy,
+    ResourceType::Power,
+    ResourceType::Hydrogen,
+    ResourceType::Oxygen,
+    ResourceType::Utrium,
+    ResourceType::Lemergium,
+    ResourceType::Keanium,
+    ResourceType::Zynthium,
+    ResourceType::Catalyst,
+];
+
+#[derive(Clone, Copy, Debug)]
+struct PriceSample {
+    /// Cheapest sell order at the time
+    lowest_sell: Option<f64>,
+    /// Best paying buy order at the time
+    highest_buy: Option<f64>,
+    /// Average price of the last day from the market history
+    history_avg: Option<f64>,
+}
+
+impl PriceSample {
+    /// The price this sample stands for
+    fn reference(&self) -> Option<f64> {
+        self.history_avg.or(match (self.lowest_sell, self.highest_buy) {
+            (Some(sell), Some(buy)) => Some((sell + buy) / 2.),
+            (sell, buy) => sell.or(buy),
+        })
+    }
+}
+
+lazy_static! {
+    static ref PRICES: Mutex<HashMap<ResourceType, VecDeque<PriceSample>>> =
+        Mutex::new(HashMap::new());
+}
+
+fn sample(resource_type: ResourceType) -> PriceSample {
+    let market_resource = MarketResourceType::Resource(resource_type);
+    let orders = game::market::get_all_orders(Some(market_resource));
+    let lowest_sell = orders
+        .iter()
+        .filter(|o| o.order_type == OrderType::Sell && o.remaining_amount > 0)
+        .map(|o| o.price)
+        .fold(None, |min: Option<f64>, p| Some(min.map_or(p, |m| m.min(p))));
+    let highest_buy = orders
+        .iter()
+        .filter(|o| o.order_type == OrderType::Buy && o.remaining_amount > 0)
+        .map(|o| o.price)
+        .fold(None, |max: Option<f64>, p| Some(max.map_or(p, |m| m.max(p))));
+    let history_avg = game::market::get_history(Some(market_resource))
+        .last()
+        .map(|record| record.avg_price);
+    PriceSample {
+        lowest_sell,
+        highest_buy,
+        history_avg,
+    }
+}
+
+/// Samples each tracked resource about every `SAMPLE_INTERVAL` ticks, one after the other so the
+/// order lookups don't all land on the same tick
+pub fn sample_prices() {
+    let time = game::time();
+    let slot = SAMPLE_INTERVAL / TRACKED_RESOURCES.len() as u32;
+    if time % slot != 0 {
+        return;
+    }
+    let resource_type = TRACKED_RESOURCES[(time / slot) as usize % TRACKED_RESOURCES.len()];
+    let sample = sample(resource_type);
+    debug!("Price sample {:?}: {:?}", resource_type, sample);
+    let mut prices = PRICES.lock().unwrap();
+    let ring = prices.entry(resource_type).or_insert_with(VecDeque::new);
+    if ring.len() == RING_SIZE {
+        ring.pop_front();
+    }
+    ring.push_back(sample);
+}
+
+/// Average of the last `AVERAGE_WINDOW` samples, `None` until there is a sample
+pub fn moving_average(resource_type: ResourceType) -> Option<f64> {
+    let prices = PRICES.lock().unwrap();
+    let references: Vec<f64> = prices
+        .get(&resource_type)?
+        .iter()
+        .rev()
+        .take(AVERAGE_WINDOW)
+        .filter_map(|s| s.reference())
+        .collect();
+    if references.is_empty() {
+        None
+    } else {
+        Some(references.iter().sum::<f64>() / references.len() as f64)
+    }
+}
+
+/// Most a unit of `resource_type` should cost right now
+pub fn max_buy_price(resource_type: ResourceType) -> Option<f64> {
+    moving_average(resource_type).map(|avg| avg * BUY_DISCOUNT)
+}
+
+/// If buying at `price` is clearly below what the market usually asks
+pub fn is_buy_opportunity(resource_type: ResourceType, price: f64) -> bool {
+    match max_buy_price(resource_type) {
+        Some(max_price) => price <= max_price,
+        None => false,
+    }
+}
@@ -1,19 +1,32 @@
 use core::fmt;
-use std::error::Error;
 
-use screeps::{ObjectId, RawObjectId, StructureController};
+use screeps::{Direction, ObjectId, RawObjectId, StructureController};
 
 use anyhow::anyhow;
 
+/// Every direction with the step it takes on x and y, clockwise from the top
+pub const DIRECTIONS: [(Direction, i32, i32); 8] = [
+    (Direction::Top, 0, -1),
+    (Direction::TopRight, 1, -1),
+    (Direction::Right, 1, 0),
+    (Direction::BottomRight, 1, 1),
+    (Direction::Bottom, 0, 1),
+    (Direction::BottomLeft, -1, 1),
+    (Direction::Left, -1, 0),
+    (Direction::TopLeft, -1, -1),
+];
+
 #[derive(thiserror::Error, Debug)]
 pub enum UtilError {
     #[error("object not found {0}")]
     ObjectNotFound(String),
+    #[error("not an object id {0}")]
+    InvalidId(String),
 }
 
 pub trait HexStr {
     fn to_hex_string(&self) -> String;
-    fn from_hex_string(hex_str: &str) -> Result<RawObjectId, Box<dyn Error>>;
+    fn from_hex_string(hex_str: &str) -> anyhow::Result<RawObjectId>;
 }
 
 impl HexStr for RawObjectId {
@@ -22,16 +35,18 @@ impl HexStr for RawObjectId {
         format!("{:X}", num)
     }
 
-    fn from_hex_string(hex_str: &str) -> Result<Self, Box<dyn Error>> {
+    fn from_hex_string(hex_str: &str) -> anyhow::Result<Self> {
         use screeps::traits::TryFrom;
-        let num = u128::from_str_radix(hex_str, 16)?;
-        Ok(Self::try_from(num)?)
+        let invalid = || UtilError::InvalidId(hex_str.to_owned());
+        let num = u128::from_str_radix(hex_str, 16).map_err(|_| invalid())?;
+        Ok(Self::try_from(num).map_err(|_| invalid())?)
     }
 }
 
-pub fn as_object_id<T>(num: u128) -> Result<ObjectId<T>, Box<(dyn Error)>> {
+pub fn as_object_id<T>(num: u128) -> anyhow::Result<ObjectId<T>> {
     use screeps::traits::TryFrom;
-    Ok(ObjectId::from(RawObjectId::try_from(num)?))
+    let raw = RawObjectId::try_from(num).map_err(|_| UtilError::InvalidId(format!("{:X}", num)))?;
+    Ok(ObjectId::from(raw))
 }
 
 pub trait ResultOptionExt<T, M> {
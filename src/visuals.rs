@@ -0,0 +1,107 @@
+//! Room visuals. Every visual is sent along with the intents of the tick, so everything drawn goes
+//! through a `Painter` instead of `room.visual()`. The primitives are collected over the tick and
+//! drawn at the end of it in the `Stats` stage, in one call per room.
+//!
+//! Each room gets `config::VISUALS_ROOM_BYTES` of visuals by a rough estimate, what comes after
+//! that is dropped. Drawing stops once it took `config::VISUALS_CPU_PER_TICK`, and while the
+//! bucket is below `config::VISUALS_MIN_BUCKET` nothing gets collected at all.
+use std::{collections::HashMap, sync::Mutex};
+
+use lazy_static::lazy_static;
+use log::debug;
+use screeps::{game, CircleStyle, RectStyle, RoomName, RoomVisual, TextStyle, Visual};
+
+use crate::config;
+
+/// Estimated size of a primitive without its text
+const PRIMITIVE_BYTES: usize = 40;
+
+#[derive(Default)]
+struct Batch {
+    visuals: Vec<Visual>,
+    bytes: usize,
+    dropped: u32,
+}
+
+lazy_static! {
+    static ref BATCHES: Mutex<HashMap<RoomName, Batch>> = Mutex::new(HashMap::new());
+    /// Tick of the last bucket check and if it was too low then
+    static ref LOW_BUCKET: Mutex<(u32, bool)> = Mutex::new((0, false));
+}
+
+fn is_bucket_low() -> bool {
+    let time = game::time();
+    let mut low_bucket = LOW_BUCKET.lock().unwrap();
+    if low_bucket.0 != time {
+        *low_bucket = (time, game::cpu::bucket() < config::VISUALS_MIN_BUCKET);
+    }
+    low_bucket.1
+}
+
+/// Draws into one room
+///
+/// ```
+///   let painter = Painter::new(room.name());
+///   painter.circle(pos.x() as f32, pos.y() as f32, None);
+/// ```
+pub struct Painter {
+    room_name: RoomName,
+    enabled: bool,
+}
+
+impl Painter {
+    pub fn new(room_name: RoomName) -> Painter {
+        Painter {
+            room_name,
+            enabled: !is_bucket_low(),
+        }
+    }
+
+    pub fn text(&self, x: f32, y: f32, text: String, style: Option<TextStyle>) {
+        let bytes = PRIMITIVE_BYTES + text.len();
+        self.add(bytes, || Visual::text(x, y, text, style));
+    }
+
+    pub fn rect(&self, x: f32, y: f32, width: f32, height: f32, style: Option<RectStyle>) {
+        self.add(PRIMITIVE_BYTES, || Visual::rect(x, y, width, height, style));
+    }
+
+    pub fn circle(&self, x: f32, y: f32, style: Option<CircleStyle>) {
+        self.add(PRIMITIVE_BYTES, || Visual::circle(x, y, style));
+    }
+
+    fn add<F: FnOnce() -> Visual>(&self, bytes: usize, visual: F) {
+        if !self.enabled {
+            return;
+        }
+        let mut batches = BATCHES.lock().unwrap();
+        let batch = batches.entry(self.room_name).or_default();
+        if batch.bytes + bytes > config::VISUALS_ROOM_BYTES {
+            batch.dropped += 1;
+            return;
+        }
+        batch.bytes += bytes;
+        batch.visuals.push(visual());
+    }
+}
+
+/// Draws what was collected this tick, call it once at the end of the tick
+pub fn flush() {
+    let batches: Vec<(RoomName, Batch)> = BATCHES.lock().unwrap().drain().collect();
+    let budget_end = game::cpu::get_used() + config::VISUALS_CPU_PER_TICK;
+    for (room_name, batch) in batches {
+        if game::cpu::get_used() >= budget_end {
+            debug!("Out of CPU for visuals, skipping {}", room_name);
+            continue;
+        }
+        RoomVisual::new(Some(room_name)).draw_multi(&batch.visuals);
+        if batch.dropped > 0 {
+            debug!(
+                "Dropped {} visuals in {}, over {} bytes",
+                batch.dropped,
+                room_name,
+                config::VISUALS_ROOM_BYTES
+            );
+        }
+    }
+}